@@ -0,0 +1,104 @@
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+use crate::browsing_history::BrowsingHistory;
+use crate::network::encode_form_urlencoded_text;
+use crate::network::url::Url;
+
+#[cfg(test)] mod tests;
+
+
+pub const DEFAULT_SEARCH_URL: &str = "https://duckduckgo.com/html/?q=%s";
+const SCHEMES_WE_LOAD: [&str; 6] = ["http", "https", "file", "about", "data", "view-source"];
+const MAX_SUGGESTIONS: usize = 8;
+const BOOKMARK_BONUS: f32 = 100.0; //this is as much as a recent visit
+
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Suggestion {
+    pub url: Url,
+    pub title: String, //empty when we don't know it, like for bookmarks we never visited
+}
+
+
+pub fn url_for_typed_text(typed_text: &str, search_url: &str) -> Url {
+    //what is typed in the address bar is either an address (possibly without the scheme), or something to search for
+    let typed_text = typed_text.trim();
+
+    //we check the scheme on the text, because the url parser falls back to a file url when there is none
+    let typed_scheme = typed_text.split(':').next().unwrap().to_lowercase();
+    if typed_text.contains(':') && SCHEMES_WE_LOAD.contains(&typed_scheme.as_str()) {
+        return Url::from(&typed_text.to_owned());
+    }
+
+    //TODO: we could also fall back to http when the site can't be loaded over https
+    if !typed_text.contains(char::is_whitespace) && looks_like_host(typed_text) {
+        let url = Url::from(&format!("https://{}", typed_text));
+        if !url.host.is_empty() {
+            return url;
+        }
+    }
+
+    return Url::from(&search_url.replace("%s", &encode_form_urlencoded_text(typed_text)));
+}
+
+
+pub fn url_for_dropped_text(dropped_text: &str) -> Option<Url> {
+    //links dragged from other programs come in as a list of urls, one per line, where lines starting with # are comments
+    let first_url = dropped_text.lines().map(|line| line.trim()).find(|line| !line.is_empty() && !line.starts_with('#'))?;
+
+    //unlike typed text, we don't search for other dropped text, since that is usually just text dropped on the wrong place
+    let dropped_scheme = first_url.split(':').next().unwrap().to_lowercase();
+    if first_url.contains(':') && !first_url.contains(char::is_whitespace) && SCHEMES_WE_LOAD.contains(&dropped_scheme.as_str()) {
+        return Some(Url::from(&first_url.to_owned()));
+    }
+    return None;
+}
+
+
+fn looks_like_host(typed_text: &str) -> bool {
+    //text like "example.com/page" or "localhost:8080" is an address, while text like "hello" or "1.5" is more likely something to search for
+    let host_and_port = typed_text.split(|c| c == '/' || c == '?' || c == '#').next().unwrap();
+    let host = host_and_port.split(':').next().unwrap();
+    if host == "localhost" {
+        return true;
+    }
+
+    let labels: Vec<&str> = host.split('.').collect();
+    if labels.len() < 2 || labels.iter().any(|label| label.is_empty()) {
+        return false;
+    }
+    let is_ipv4_address = labels.len() == 4 && labels.iter().all(|label| label.chars().all(|c| c.is_ascii_digit()));
+    let has_top_level_domain = labels.last().unwrap().chars().all(|c| c.is_alphabetic()); //this includes international ones
+    return is_ipv4_address || has_top_level_domain;
+}
+
+
+pub fn find_suggestions(typed_text: &str, browsing_history: &BrowsingHistory, bookmarks: &Vec<Url>, now: SystemTime) -> Vec<Suggestion> {
+    let typed_text = typed_text.trim().to_lowercase();
+    if typed_text.is_empty() {
+        return Vec::new();
+    }
+    let matches = |url: &Url, title: &String| url.to_string().to_lowercase().contains(&typed_text) || title.to_lowercase().contains(&typed_text);
+
+    let mut scored_suggestions = Vec::new();
+    for page in browsing_history.pages.iter() {
+        if matches(&page.url, &page.title) {
+            let bonus = if bookmarks.contains(&page.url) { BOOKMARK_BONUS } else { 0.0 };
+            scored_suggestions.push((page.frecency(now) + bonus, Suggestion { url: page.url.clone(), title: page.title.clone() }));
+        }
+    }
+    for bookmark in bookmarks {
+        if browsing_history.find(bookmark).is_none() && matches(bookmark, &String::new()) {
+            scored_suggestions.push((BOOKMARK_BONUS, Suggestion { url: bookmark.clone(), title: String::new() }));
+        }
+    }
+
+    //the highest score comes first, and for the same score the shortest url, which is usually the start page of a site
+    scored_suggestions.sort_by(|(score_a, suggestion_a), (score_b, suggestion_b)| {
+        return score_b.partial_cmp(score_a).unwrap_or(Ordering::Equal)
+                      .then(suggestion_a.url.to_string().len().cmp(&suggestion_b.url.to_string().len()));
+    });
+    return scored_suggestions.into_iter().take(MAX_SUGGESTIONS).map(|(_, suggestion)| suggestion).collect();
+}
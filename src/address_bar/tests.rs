@@ -0,0 +1,64 @@
+use std::time::{Duration, SystemTime};
+
+use crate::address_bar::{find_suggestions, url_for_dropped_text, url_for_typed_text, DEFAULT_SEARCH_URL};
+use crate::browsing_history::BrowsingHistory;
+use crate::network::url::Url;
+
+
+#[test]
+fn test_url_for_typed_text() {
+    let url_for = |typed_text: &str| url_for_typed_text(typed_text, DEFAULT_SEARCH_URL).to_string();
+
+    assert_eq!(url_for("https://www.example.com/page"), "https://www.example.com/page");
+    assert_eq!(url_for("  about:home "), "about:home");
+    assert_eq!(url_for("www.example.com/page?q=1"), "https://www.example.com/page?q=1");
+    assert_eq!(url_for("localhost:8080/test"), "https://localhost:8080/test");
+    assert_eq!(url_for("127.0.0.1"), "https://127.0.0.1/");
+
+    assert_eq!(url_for("rust borrow checker"), "https://duckduckgo.com/html/?q=rust+borrow+checker");
+    assert_eq!(url_for("webcrustacean"), "https://duckduckgo.com/html/?q=webcrustacean");
+    assert_eq!(url_for("1.5"), "https://duckduckgo.com/html/?q=1.5");
+    assert_eq!(url_for("what is example.com"), "https://duckduckgo.com/html/?q=what+is+example.com");
+    assert_eq!(url_for_typed_text("a&b", "https://search.example.com/?s=%s").to_string(), "https://search.example.com/?s=a%26b");
+}
+
+
+#[test]
+fn test_url_for_dropped_text() {
+    let url_for = |dropped_text: &str| url_for_dropped_text(dropped_text).map(|url| url.to_string());
+
+    assert_eq!(url_for("https://www.example.com/page"), Some(String::from("https://www.example.com/page")));
+    assert_eq!(url_for("# a comment\r\nhttps://first.example.com/\r\nhttps://second.example.com/"), Some(String::from("https://first.example.com/")));
+    assert_eq!(url_for("  file:///tmp/page.html\n"), Some(String::from("file:///tmp/page.html")));
+
+    assert_eq!(url_for("just some text"), None);
+    assert_eq!(url_for("javascript:alert(1)"), None);
+    assert_eq!(url_for(""), None);
+}
+
+
+#[test]
+fn test_suggestions_are_ranked_by_frecency() {
+    let now = SystemTime::now();
+    let long_ago = now - Duration::from_secs(60 * 24 * 60 * 60);
+
+    let mut browsing_history = BrowsingHistory::new(None);
+    browsing_history.record_visit(&Url::from(&String::from("https://rarely.example.com/")), Some(String::from("Rare")), now);
+    for _ in 0..3 {
+        browsing_history.record_visit(&Url::from(&String::from("https://often.example.com/#part")), Some(String::from("Often")), now);
+    }
+    for _ in 0..5 {
+        browsing_history.record_visit(&Url::from(&String::from("https://old.example.com/")), Some(String::from("Old")), long_ago);
+    }
+    browsing_history.record_visit(&Url::from(&String::from("https://other.org/")), Some(String::from("Example of something")), now);
+    let bookmarks = vec![Url::from(&String::from("https://bookmark.example.com/"))];
+
+    let suggestions = find_suggestions("Example", &browsing_history, &bookmarks, now);
+    let suggested_urls: Vec<String> = suggestions.iter().map(|suggestion| suggestion.url.to_string()).collect();
+    assert_eq!(suggested_urls, vec!["https://often.example.com/", "https://old.example.com/", "https://other.org/", "https://rarely.example.com/",
+                                    "https://bookmark.example.com/"]);
+    assert_eq!(suggestions[0].title, "Often");
+
+    assert!(find_suggestions("nothing like this", &browsing_history, &bookmarks, now).is_empty());
+    assert!(find_suggestions("  ", &browsing_history, &bookmarks, now).is_empty());
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::color::Color;
+use crate::dom::DomNodeId;
+use crate::layout::{FullLayout, LayoutNodeContent, LayoutNodeId};
+use crate::style::transition::{TimingFunction, TransitionStyle};
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimatedProperty {
+    Color,
+    BackgroundColor,
+    Opacity,
+}
+impl AnimatedProperty {
+    fn css_name(&self) -> &str {
+        return match self {
+            AnimatedProperty::Color => "color",
+            AnimatedProperty::BackgroundColor => "background-color",
+            AnimatedProperty::Opacity => "opacity",
+        };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimatedValue {
+    Color(Color),
+    Number(f32),
+}
+impl AnimatedValue {
+    fn interpolate(&self, other: &AnimatedValue, fraction: f32) -> AnimatedValue {
+        let interpolate_number = |from: f32, to: f32| from + ((to - from) * fraction);
+        let interpolate_channel = |from: u8, to: u8| interpolate_number(from as f32, to as f32).round().clamp(0.0, 255.0) as u8;
+
+        return match (self, other) {
+            (AnimatedValue::Number(from), AnimatedValue::Number(to)) => {
+                AnimatedValue::Number(interpolate_number(*from, *to))
+            },
+            (AnimatedValue::Color(from), AnimatedValue::Color(to)) => {
+                AnimatedValue::Color(Color::new_with_alpha(interpolate_channel(from.r, to.r), interpolate_channel(from.g, to.g),
+                                                           interpolate_channel(from.b, to.b), interpolate_channel(from.a, to.a)))
+            },
+            _ => { panic!("can't interpolate between values of different types"); }
+        };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct RunningTransition {
+    from: AnimatedValue,
+    to: AnimatedValue,
+    start: Instant,
+    delay: Duration,
+    duration: Duration,
+    timing_function: TimingFunction,
+}
+impl RunningTransition {
+    fn value_at(&self, now: Instant) -> AnimatedValue {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed <= self.delay {
+            return self.from;
+        }
+        let progress = (elapsed - self.delay).as_secs_f32() / self.duration.as_secs_f32();
+        return self.from.interpolate(&self.to, self.timing_function.apply(progress));
+    }
+
+    fn is_finished(&self, now: Instant) -> bool {
+        return now.saturating_duration_since(self.start) >= self.delay + self.duration;
+    }
+}
+
+
+//We key the animation state by the id of the DOM node the values come from, and not by that of the layout node, because
+//layout nodes are rebuilt (and get a new id) exactly when their styles change, which is the moment a transition needs to start.
+type AnimationKey = (DomNodeId, AnimatedProperty);
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct AnimationState {
+    running_transitions: HashMap<AnimationKey, RunningTransition>,
+    last_target_values: HashMap<AnimationKey, AnimatedValue>,
+    current_values: HashMap<AnimationKey, AnimatedValue>,
+}
+impl AnimationState {
+    pub fn new() -> AnimationState {
+        return AnimationState { running_transitions: HashMap::new(), last_target_values: HashMap::new(), current_values: HashMap::new() };
+    }
+
+    pub fn clear(&mut self) {
+        self.running_transitions.clear();
+        self.last_target_values.clear();
+        self.current_values.clear();
+    }
+
+    pub fn update(&mut self, full_layout: &FullLayout, now: Instant) {
+        //we check for changed values on every frame, since layout nodes don't tell us when they are rebuilt
+        self.check_for_new_transitions(full_layout, full_layout.root_node, now);
+
+        self.current_values.clear();
+        for (key, transition) in self.running_transitions.iter() {
+            self.current_values.insert(*key, transition.value_at(now));
+        }
+        self.running_transitions.retain(|_, transition| !transition.is_finished(now));
+    }
+
+    pub fn get_current_value(&self, dom_node_id: DomNodeId, property: AnimatedProperty) -> Option<AnimatedValue> {
+        return self.current_values.get(&(dom_node_id, property)).copied();
+    }
+
+    pub fn get_current_color(&self, dom_node_id: DomNodeId, property: AnimatedProperty) -> Option<Color> {
+        let value = self.get_current_value(dom_node_id, property);
+        if value.is_some() {
+            match value.unwrap() {
+                AnimatedValue::Color(color) => { return Some(color); },
+                AnimatedValue::Number(_) => {},
+            }
+        }
+        return None;
+    }
+
+    pub fn get_current_number(&self, dom_node_id: DomNodeId, property: AnimatedProperty) -> Option<f32> {
+        let value = self.get_current_value(dom_node_id, property);
+        if value.is_some() {
+            match value.unwrap() {
+                AnimatedValue::Number(number) => { return Some(number); },
+                AnimatedValue::Color(_) => {},
+            }
+        }
+        return None;
+    }
+
+    fn check_for_new_transitions(&mut self, full_layout: &FullLayout, layout_node_id: LayoutNodeId, now: Instant) {
+        let layout_node = &full_layout.nodes[layout_node_id];
+        if layout_node.from_dom_node.is_some() && !layout_node.transitions.is_empty() {
+            let dom_node_id = layout_node.from_dom_node.unwrap();
+
+            self.check_for_new_transition(dom_node_id, AnimatedProperty::Opacity, AnimatedValue::Number(layout_node.opacity),
+                                          &layout_node.transitions, now);
+
+            match &layout_node.content {
+                LayoutNodeContent::BoxLayoutNode(box_node) => {
+                    self.check_for_new_transition(dom_node_id, AnimatedProperty::BackgroundColor, AnimatedValue::Color(box_node.background_color),
+                                                  &layout_node.transitions, now);
+                },
+                LayoutNodeContent::TextLayoutNode(text_node) => {
+                    self.check_for_new_transition(dom_node_id, AnimatedProperty::BackgroundColor, AnimatedValue::Color(text_node.background_color),
+                                                  &layout_node.transitions, now);
+                    let font_color = text_node.rects.first().map(|rect| rect.font_color);
+                    if font_color.is_some() {
+                        self.check_for_new_transition(dom_node_id, AnimatedProperty::Color, AnimatedValue::Color(font_color.unwrap()),
+                                                      &layout_node.transitions, now);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if layout_node.children.is_some() {
+            for child_id in layout_node.children.as_ref().unwrap() {
+                self.check_for_new_transitions(full_layout, *child_id, now);
+            }
+        }
+    }
+
+    fn check_for_new_transition(&mut self, dom_node_id: DomNodeId, property: AnimatedProperty, target_value: AnimatedValue,
+                                transitions: &Vec<TransitionStyle>, now: Instant) {
+        //the last matching transition in the list wins, like in the cascade
+        let transition_style = transitions.iter().rev().find(|transition| transition.applies_to(property.css_name()));
+        if transition_style.is_none() {
+            return;
+        }
+        let transition_style = transition_style.unwrap();
+
+        let key = (dom_node_id, property);
+        let previous_target_value = self.last_target_values.insert(key, target_value);
+        if previous_target_value.is_none() || previous_target_value.unwrap() == target_value {
+            //either we see this node for the first time, or nothing changed
+            return;
+        }
+
+        //when a transition is interrupted, we start the new one from wherever the old one currently is
+        let running_transition = self.running_transitions.get(&key);
+        let start_value = if running_transition.is_some() { running_transition.unwrap().value_at(now) } else { previous_target_value.unwrap() };
+
+        self.running_transitions.insert(key, RunningTransition { from: start_value, to: target_value, start: now, delay: transition_style.delay,
+                                                                 duration: transition_style.duration, timing_function: transition_style.timing_function });
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(test)] mod tests;
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+
+//The nodes of the DOM and layout trees are stored in arenas, and refer to each other (like to their children) by their id. This means we only
+//need to borrow the arena (or the document or layout it is in) to walk and change a tree, instead of every node on the way.
+//The slots of removed nodes are used again, with a higher generation, so an id of a removed node never finds the node that replaced it.
+pub struct NodeId<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+//these are implemented by hand, because deriving them would only implement them when T implements them as well
+impl<T> Clone for NodeId<T> {
+    fn clone(&self) -> NodeId<T> {
+        *self
+    }
+}
+impl<T> Copy for NodeId<T> {}
+impl<T> PartialEq for NodeId<T> {
+    fn eq(&self, other: &NodeId<T>) -> bool {
+        return self.index == other.index && self.generation == other.generation;
+    }
+}
+impl<T> Eq for NodeId<T> {}
+impl<T> Hash for NodeId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+impl<T> fmt::Debug for NodeId<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return write!(formatter, "{}v{}", self.index, self.generation);
+    }
+}
+impl<T> NodeId<T> {
+    pub fn index(&self) -> usize {
+        //this is unique for the nodes that are in the arena at the same time, and small, so it is useful for showing the node to people
+        return self.index as usize;
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct ArenaSlot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Arena<T> {
+    slots: Vec<ArenaSlot<T>>,
+    free_slots: Vec<u32>,
+}
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        return Arena { slots: Vec::new(), free_slots: Vec::new() };
+    }
+
+    pub fn insert(&mut self, value: T) -> NodeId<T> {
+        return self.insert_with(|_| value);
+    }
+
+    pub fn insert_with<F: FnOnce(NodeId<T>) -> T>(&mut self, make_value: F) -> NodeId<T> {
+        //this is for values that need to know their own id, like nodes
+        let id = match self.free_slots.pop() {
+            Some(index) => NodeId { index, generation: self.slots[index as usize].generation, marker: PhantomData },
+            None => {
+                self.slots.push(ArenaSlot { generation: 0, value: None });
+                NodeId { index: (self.slots.len() - 1) as u32, generation: 0, marker: PhantomData }
+            },
+        };
+
+        self.slots[id.index as usize].value = Some(make_value(id));
+        return id;
+    }
+
+    pub fn remove(&mut self, id: NodeId<T>) -> Option<T> {
+        if !self.contains(id) {
+            return None;
+        }
+
+        let slot = &mut self.slots[id.index as usize];
+        slot.generation += 1;
+        self.free_slots.push(id.index);
+        return slot.value.take();
+    }
+
+    pub fn contains(&self, id: NodeId<T>) -> bool {
+        return self.get(id).is_some();
+    }
+
+    pub fn get(&self, id: NodeId<T>) -> Option<&T> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        return slot.value.as_ref();
+    }
+
+    pub fn get_mut(&mut self, id: NodeId<T>) -> Option<&mut T> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        return slot.value.as_mut();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId<T>, &T)> {
+        return self.slots.iter().enumerate().filter(|(_, slot)| slot.value.is_some()).map(|(index, slot)| {
+            return (NodeId { index: index as u32, generation: slot.generation, marker: PhantomData }, slot.value.as_ref().unwrap());
+        });
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (NodeId<T>, &mut T)> {
+        return self.slots.iter_mut().enumerate().filter(|(_, slot)| slot.value.is_some()).map(|(index, slot)| {
+            return (NodeId { index: index as u32, generation: slot.generation, marker: PhantomData }, slot.value.as_mut().unwrap());
+        });
+    }
+}
+impl<T> Index<NodeId<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: NodeId<T>) -> &T {
+        //using the id of a node that was removed is a bug, like using a dangling reference would be
+        return self.get(id).expect("node id used after the node was removed");
+    }
+}
+impl<T> IndexMut<NodeId<T>> for Arena<T> {
+    fn index_mut(&mut self, id: NodeId<T>) -> &mut T {
+        return self.get_mut(id).expect("node id used after the node was removed");
+    }
+}
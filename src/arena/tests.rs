@@ -0,0 +1,74 @@
+use crate::arena::{Arena, NodeId};
+
+
+#[test]
+fn test_insert_and_get() {
+    let mut arena = Arena::new();
+    let first_id = arena.insert(String::from("first"));
+    let second_id = arena.insert_with(|id: NodeId<String>| format!("second at {}", id.index()));
+
+    assert_ne!(first_id, second_id);
+    assert_eq!(arena[first_id], "first");
+    assert_eq!(arena[second_id], "second at 1");
+    assert!(arena.contains(first_id));
+
+    arena[first_id].push_str(" changed");
+    assert_eq!(arena.get(first_id), Some(&String::from("first changed")));
+
+    let values: Vec<&String> = arena.iter().map(|(_, value)| value).collect();
+    assert_eq!(values, vec!["first changed", "second at 1"]);
+}
+
+
+#[test]
+fn test_remove() {
+    let mut arena = Arena::new();
+    let first_id = arena.insert(1);
+    let second_id = arena.insert(2);
+
+    assert_eq!(arena.remove(first_id), Some(1));
+    assert!(!arena.contains(first_id));
+    assert!(arena.get(first_id).is_none());
+    assert!(arena.get_mut(first_id).is_none());
+    assert_eq!(arena.iter().map(|(id, _)| id).collect::<Vec<NodeId<i32>>>(), vec![second_id]);
+
+    //removing it again does nothing
+    assert_eq!(arena.remove(first_id), None);
+    assert_eq!(arena[second_id], 2);
+}
+
+
+#[test]
+fn test_removed_slots_are_reused_with_a_new_generation() {
+    let mut arena = Arena::new();
+    let old_id = arena.insert("old");
+    arena.remove(old_id);
+
+    //the new node gets the slot of the removed one, but its id is different, so the old id does not find it
+    let new_id = arena.insert("new");
+    assert_eq!(new_id.index(), old_id.index());
+    assert_ne!(new_id, old_id);
+    assert!(arena.get(old_id).is_none());
+    assert_eq!(arena[new_id], "new");
+    assert_eq!(format!("{:?}", old_id), "0v0");
+    assert_eq!(format!("{:?}", new_id), "0v1");
+
+    //removing the old id again should not remove the new node
+    assert_eq!(arena.remove(old_id), None);
+    assert_eq!(arena[new_id], "new");
+
+    //a slot is only reused when it is free
+    let other_id = arena.insert("other");
+    assert_eq!(other_id.index(), 1);
+}
+
+
+#[test]
+#[should_panic(expected = "node id used after the node was removed")]
+fn test_using_a_removed_id_panics() {
+    let mut arena = Arena::new();
+    let id = arena.insert(1);
+    arena.remove(id);
+    arena.insert(2);
+    arena[id] += 1;
+}
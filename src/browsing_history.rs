@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::debug::debug_log_warn;
+use crate::network::http_cache::{create_private_directory, user_cache_directory};
+use crate::network::url::Url;
+
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+
+//this is shared by the ui, the pages (for styling visited links) and the threads building about:history, and is saved to disk on every change
+pub static BROWSING_HISTORY: LazyLock<Mutex<BrowsingHistory>> = LazyLock::new(|| {
+    return Mutex::new(BrowsingHistory::new(user_cache_directory().map(|directory| directory.join("history"))));
+});
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct VisitedPage {
+    pub url: Url,
+    pub title: String,
+    pub visit_count: u32,
+    pub last_visit: SystemTime,
+}
+impl VisitedPage {
+    pub fn frecency(&self, now: SystemTime) -> f32 {
+        //this is a simplified version of what firefox does: pages visited more often rank higher, and recent visits count more than old ones
+        //TODO: we only know when the last visit was, so all visits are weighted as if they were that recent
+        let age = now.duration_since(self.last_visit).unwrap_or(Duration::ZERO);
+        let recency_weight = if age < DAY * 4 {
+            100.0
+        } else if age < DAY * 14 {
+            70.0
+        } else if age < DAY * 31 {
+            50.0
+        } else if age < DAY * 90 {
+            30.0
+        } else {
+            10.0
+        };
+        return self.visit_count as f32 * recency_weight;
+    }
+
+    pub fn days_since_last_visit(&self, now: SystemTime) -> u64 {
+        return now.duration_since(self.last_visit).unwrap_or(Duration::ZERO).as_secs() / DAY.as_secs();
+    }
+
+    fn serialize(&self) -> String {
+        let last_visit_secs = self.last_visit.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        return [self.url.to_string(), self.title.clone(), self.visit_count.to_string(), last_visit_secs.to_string()].join("\t");
+    }
+
+    fn deserialize(line: &str) -> Option<VisitedPage> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != NR_OF_SERIALIZED_FIELDS {
+            return None;
+        }
+
+        return Some(VisitedPage {
+            url: Url::from(&fields[0].to_owned()),
+            title: fields[1].to_owned(),
+            visit_count: fields[2].parse::<u32>().ok()?,
+            last_visit: UNIX_EPOCH.checked_add(Duration::from_secs(fields[3].parse::<u64>().ok()?))?,
+        });
+    }
+}
+const NR_OF_SERIALIZED_FIELDS: usize = 4;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct BrowsingHistory {
+    //every page we visited, each only once, unlike ui::History, which is the list for going back and forward
+    pub pages: Vec<VisitedPage>,
+    file: Option<PathBuf>, //when this is None, the history is only kept in memory
+}
+impl BrowsingHistory {
+    pub fn new(file: Option<PathBuf>) -> BrowsingHistory {
+        let mut pages = Vec::new();
+        if file.is_some() {
+            let read_result = fs::read_to_string(file.as_ref().unwrap());
+            if read_result.is_ok() {
+                pages = read_result.unwrap().lines().filter_map(VisitedPage::deserialize).collect();
+            }
+        }
+        return BrowsingHistory { pages, file };
+    }
+
+    pub fn record_visit(&mut self, url: &Url, title: Option<String>, time: SystemTime) {
+        //the fragment is only a place on the page, so visits to different fragments are visits to the same page
+        let url = url.without_fragment();
+        //tabs and newlines would break the lines of the file (and are not shown in titles anyway)
+        let title = title.unwrap_or(String::new()).replace(|c: char| c == '\t' || c == '\n' || c == '\r', " ");
+
+        let existing_page = self.pages.iter_mut().find(|page| page.url == url);
+        if existing_page.is_some() {
+            let existing_page = existing_page.unwrap();
+            existing_page.visit_count += 1;
+            existing_page.last_visit = time;
+            existing_page.title = title;
+        } else {
+            self.pages.push(VisitedPage { url, title, visit_count: 1, last_visit: time });
+        }
+        self.save();
+    }
+
+    pub fn find(&self, url: &Url) -> Option<&VisitedPage> {
+        let url = url.without_fragment();
+        return self.pages.iter().find(|page| page.url == url);
+    }
+
+    pub fn search(&self, text: &str) -> Vec<&VisitedPage> {
+        //this is what about:history shows, the most recently visited pages first
+        let text = text.trim().to_lowercase();
+        let mut found_pages: Vec<&VisitedPage> = self.pages.iter().filter(|page| {
+            return page.url.to_string().to_lowercase().contains(&text) || page.title.to_lowercase().contains(&text);
+        }).collect();
+        found_pages.sort_by(|page_a, page_b| page_b.last_visit.cmp(&page_a.last_visit));
+        return found_pages;
+    }
+
+    pub fn remove(&mut self, url: &Url) {
+        let url = url.without_fragment();
+        self.pages.retain(|page| page.url != url);
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.save();
+    }
+
+    fn save(&self) {
+        if self.file.is_none() {
+            return;
+        }
+
+        let lines: Vec<String> = self.pages.iter().map(|page| page.serialize()).collect();
+        let path = self.file.as_ref().unwrap();
+        let write_result = create_private_directory(path.parent().unwrap()).and_then(|_| fs::write(path, lines.join("\n")));
+        if write_result.is_err() {
+            debug_log_warn(format!("Could not write the browsing history to: {:?}", path));
+        }
+    }
+}
@@ -4,12 +4,19 @@
 pub struct Color {
     pub r: u8,
     pub g: u8,
-    pub b: u8
+    pub b: u8,
+    pub a: u8,
 }
 impl Color {
-    pub const fn new(p_r: u8, p_g: u8, p_b: u8) -> Color { Color { r: p_r, g: p_g, b: p_b } }
+    pub const fn new(p_r: u8, p_g: u8, p_b: u8) -> Color { Color { r: p_r, g: p_g, b: p_b, a: 255 } }
+    pub const fn new_with_alpha(p_r: u8, p_g: u8, p_b: u8, p_a: u8) -> Color { Color { r: p_r, g: p_g, b: p_b, a: p_a } }
+
+    pub fn is_transparent(&self) -> bool {
+        return self.a == 0;
+    }
 
     pub fn from_string(color_name: &String) -> Option<Color> {
+        let color_name = color_name.trim().to_lowercase();
 
         let first_char = color_name.chars().next();
         if first_char.is_none() {
@@ -17,40 +24,28 @@ impl Color {
         }
 
         if first_char.unwrap() == '#' {
+            return parse_hex_color(&color_name[1..]);
+        }
 
-            if color_name.len() == 4 {
-                let r = u8::from_str_radix(&color_name[1..2], 16).ok();
-                let g = u8::from_str_radix(&color_name[2..3], 16).ok();
-                let b = u8::from_str_radix(&color_name[3..4], 16).ok();
-
-                if r.is_none() || g.is_none() || b.is_none() {
-                    return None;
-                }
-
-                let r = r.unwrap() + (16 * r.unwrap());
-                let g = g.unwrap() + (16 * g.unwrap());
-                let b = b.unwrap() + (16 * b.unwrap());
-
-                return Some(Color {r, g, b})
+        if color_name.ends_with(')') {
+            let open_paren_idx = color_name.find('(');
+            if open_paren_idx.is_none() {
+                return None;
             }
-
-            if color_name.len() == 7 {
-                let r = u8::from_str_radix(&color_name[1..3], 16).ok();
-                let g = u8::from_str_radix(&color_name[3..5], 16).ok();
-                let b = u8::from_str_radix(&color_name[5..7], 16).ok();
-
-                if r.is_none() || g.is_none() || b.is_none() {
-                    return None;
-                }
-
-                return Some(Color {r: r.unwrap(), g: g.unwrap(), b: b.unwrap() })
+            let function_name = color_name[..open_paren_idx.unwrap()].trim();
+            let arguments = split_color_function_arguments(&color_name[open_paren_idx.unwrap() + 1..color_name.len() - 1]);
+            if arguments.is_none() {
+                return None;
             }
+            let arguments = arguments.unwrap();
 
-            return None;
+            return match function_name {
+                "rgb" | "rgba" => parse_rgb_function(&arguments),
+                "hsl" | "hsla" => parse_hsl_function(&arguments),
+                _ => None,
+            };
         }
 
-        //TODO: I still need to support hsl and rgb color values (as specified in html / css)
-
         return match color_name.as_str() {
             "aqua" => Some(Color::new(0, 255, 255)),
             "black" => Some(Color::BLACK),
@@ -66,6 +61,7 @@ impl Color {
             "red" => Some(Color::new(255, 0, 0)),
             "silver" => Some(Color::new(192, 192, 192)),
             "teal" => Some(Color::new(0, 128, 128)),
+            "transparent" => Some(Color::TRANSPARENT),
             "white" => Some(Color::WHITE),
             "yellow" => Some(Color::new(255, 255, 0)),
             _ => None
@@ -74,7 +70,150 @@ impl Color {
 
     //Below we only define Colors we use in other parts of the code in a hardcoded way:
     pub const BLACK: Color = Color::new(0, 0, 0);
+    pub const TRANSPARENT: Color = Color::new_with_alpha(0, 0, 0, 0);
     pub const GRAY: Color = Color::new(128, 128, 128);
     pub const WHITE: Color = Color::new(255, 255, 255);
     pub const DEFAULT_SELECTION_COLOR: Color = Color::new(180, 213, 255);  //TODO: maybe belongs in the ui module? we have other colors there as well...
 }
+
+
+fn parse_hex_color(hex_digits: &str) -> Option<Color> {
+    if !hex_digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    //the short forms (3 or 4 digits) repeat each digit, so "f" means "ff"
+    let (digit_size, nr_components) = match hex_digits.len() {
+        3 => (1, 3),
+        4 => (1, 4),
+        6 => (2, 3),
+        8 => (2, 4),
+        _ => { return None; }
+    };
+
+    let mut components = Vec::new();
+    for component_idx in 0..nr_components {
+        let start = component_idx * digit_size;
+        let value = u8::from_str_radix(&hex_digits[start..start + digit_size], 16).unwrap();
+        if digit_size == 1 {
+            components.push(value + (16 * value));
+        } else {
+            components.push(value);
+        }
+    }
+
+    let alpha = if nr_components == 4 { components[3] } else { 255 };
+    return Some(Color::new_with_alpha(components[0], components[1], components[2], alpha));
+}
+
+
+fn split_color_function_arguments(arguments_text: &str) -> Option<Vec<String>> {
+    //This supports both the legacy syntax with commas (rgba(1, 2, 3, 0.5)) and the modern one with spaces and a slash (rgb(1 2 3 / 50%))
+
+    let mut arguments: Vec<String> = if arguments_text.contains(',') {
+        arguments_text.split(',').map(|argument| argument.trim().to_owned()).collect()
+    } else {
+        let (color_part, alpha_part) = if arguments_text.contains('/') {
+            let slash_idx = arguments_text.find('/').unwrap();
+            (&arguments_text[..slash_idx], Some(arguments_text[slash_idx + 1..].trim()))
+        } else {
+            (arguments_text, None)
+        };
+
+        let mut arguments: Vec<String> = color_part.split_whitespace().map(|argument| argument.to_owned()).collect();
+        if alpha_part.is_some() {
+            arguments.push(alpha_part.unwrap().to_owned());
+        }
+        arguments
+    };
+
+    arguments.retain(|argument| !argument.is_empty());
+    if arguments.len() != 3 && arguments.len() != 4 {
+        return None;
+    }
+    return Some(arguments);
+}
+
+
+fn parse_rgb_function(arguments: &Vec<String>) -> Option<Color> {
+    let mut color_components = Vec::new();
+    for argument in &arguments[..3] {
+        let value = if argument.ends_with('%') {
+            parse_percentage(argument).map(|percentage| percentage * 255.0)
+        } else {
+            argument.parse::<f32>().ok()
+        };
+        if value.is_none() {
+            return None;
+        }
+        color_components.push(clamp_to_u8(value.unwrap()));
+    }
+
+    let alpha = parse_alpha_argument(arguments.get(3));
+    if alpha.is_none() {
+        return None;
+    }
+
+    return Some(Color::new_with_alpha(color_components[0], color_components[1], color_components[2], alpha.unwrap()));
+}
+
+
+fn parse_hsl_function(arguments: &Vec<String>) -> Option<Color> {
+    let hue_text = arguments[0].trim_end_matches("deg");
+    let hue = hue_text.parse::<f32>().ok();
+    let saturation = parse_percentage(&arguments[1]);
+    let lightness = parse_percentage(&arguments[2]);
+    let alpha = parse_alpha_argument(arguments.get(3));
+
+    if hue.is_none() || saturation.is_none() || lightness.is_none() || alpha.is_none() {
+        return None;
+    }
+
+    let hue = hue.unwrap().rem_euclid(360.0);
+    let saturation = saturation.unwrap().clamp(0.0, 1.0);
+    let lightness = lightness.unwrap().clamp(0.0, 1.0);
+
+    //conversion as described in https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+    let convert = |n: f32| -> u8 {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        let value = lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+        return clamp_to_u8(value * 255.0);
+    };
+
+    return Some(Color::new_with_alpha(convert(0.0), convert(8.0), convert(4.0), alpha.unwrap()));
+}
+
+
+fn parse_alpha_argument(argument: Option<&String>) -> Option<u8> {
+    if argument.is_none() {
+        return Some(255);
+    }
+    let argument = argument.unwrap();
+
+    let alpha = if argument.ends_with('%') {
+        parse_percentage(argument)
+    } else {
+        argument.parse::<f32>().ok()
+    };
+    if alpha.is_none() {
+        return None;
+    }
+    return Some(clamp_to_u8(alpha.unwrap().clamp(0.0, 1.0) * 255.0));
+}
+
+
+fn parse_percentage(text: &str) -> Option<f32> {
+    //returns the percentage as a fraction (so 50% becomes 0.5)
+    let number_text = text.trim().trim_end_matches('%');
+    let parsed = number_text.parse::<f32>().ok();
+    if parsed.is_none() {
+        return None;
+    }
+    return Some(parsed.unwrap() / 100.0);
+}
+
+
+fn clamp_to_u8(value: f32) -> u8 {
+    return value.round().clamp(0.0, 255.0) as u8;
+}
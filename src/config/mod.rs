@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+use crate::address_bar::DEFAULT_SEARCH_URL;
+use crate::debug::debug_log_warn;
+use crate::network::{ProxySettings, UA_FIREFOX_WINDOWS};
+
+#[cfg(test)] mod tests;
+
+
+const CONFIG_FILE_NAME: &str = ".webcrustacean.toml";
+const OLD_CONFIG_FILE_NAME: &str = ".webcrustacean.conf"; //this is still read when there is no config file with the new name yet
+
+
+//these are the settings shown (and editable) on about:config, other values in the config file (like host overrides) can only be set in the file
+pub const SETTING_DEFINITIONS: [SettingDefinition; 11] = [
+    SettingDefinition { key: "general.start_url", default_value: "about:home", description: "The page that is opened when no url is given" },
+    SettingDefinition { key: "search.url", default_value: DEFAULT_SEARCH_URL, description: "The search engine, %s is replaced by what we search for" },
+    SettingDefinition { key: "network.user_agent", default_value: UA_FIREFOX_WINDOWS, description: "How we identify ourselves to websites" },
+    SettingDefinition { key: "javascript.enabled", default_value: "true", description: "Whether the scripts of pages are run" },
+    SettingDefinition { key: "images.enabled", default_value: "true", description: "Whether images are loaded" },
+    SettingDefinition { key: "fonts.default_size", default_value: "18", description: "The font size of text that has no size set by the page" },
+    SettingDefinition { key: "fonts.minimum_size", default_value: "0", description: "Smaller text is shown with this size" },
+    SettingDefinition { key: "proxy.http", default_value: "", description: "The proxy for http requests" },
+    SettingDefinition { key: "proxy.https", default_value: "", description: "The proxy for https requests" },
+    SettingDefinition { key: "proxy.all", default_value: "", description: "The proxy for both http and https requests" },
+    SettingDefinition { key: "proxy.no_proxy", default_value: "", description: "Hosts we connect to without a proxy, separated by commas" },
+];
+
+
+//this is shared by the ui, the pages and the resource threads, and is updated when settings are changed on about:config
+pub static SETTINGS: LazyLock<Mutex<Settings>> = LazyLock::new(|| {
+    //tests should not depend on the config file of whoever runs them
+    let config = if cfg!(test) { Config::parse(&String::new()) } else { Config::load() };
+    return Mutex::new(Settings::from_config(&config));
+});
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SettingDefinition {
+    pub key: &'static str,
+    pub default_value: &'static str,
+    pub description: &'static str,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct Settings {
+    pub start_url: String,
+    pub search_url: String, //the %s in this is replaced by what we search for
+    pub user_agent: String,
+    pub javascript_enabled: bool,
+    pub images_enabled: bool,
+    pub default_font_size: f32,
+    pub minimum_font_size: f32,
+    pub proxy: ProxySettings,
+}
+impl Settings {
+    pub fn from_config(config: &Config) -> Settings {
+        return Settings {
+            start_url: config.get_setting("general.start_url"),
+            search_url: config.get_setting("search.url"),
+            user_agent: config.get_setting("network.user_agent"),
+            javascript_enabled: config.get_bool_setting("javascript.enabled"),
+            images_enabled: config.get_bool_setting("images.enabled"),
+            default_font_size: config.get_number_setting("fonts.default_size"),
+            minimum_font_size: config.get_number_setting("fonts.minimum_size"),
+            proxy: ProxySettings::from_config(config),
+        };
+    }
+}
+
+
+//the config file is a (simple) toml file, with a setting on each line, like "proxy.http = "http://proxy.example.com:8080"", and comments starting with #
+//settings can also be grouped under a [section], and for older config files we still accept values without quotes
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+impl Config {
+    pub fn load() -> Config {
+        return Config::parse(&read_config_file());
+    }
+
+    pub fn parse(text: &String) -> Config {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let section_end = line.find(']');
+                if line.starts_with("[[") || section_end.is_none() {
+                    debug_log_warn(format!("Ignoring config line with an unsupported section: {}", line));
+                    continue;
+                }
+                section = line[1..section_end.unwrap()].trim().to_owned();
+                continue;
+            }
+
+            let equals_idx = line.find('=');
+            if equals_idx.is_none() {
+                debug_log_warn(format!("Ignoring config line without a value: {}", line));
+                continue;
+            }
+            let equals_idx = equals_idx.unwrap();
+            let key = full_key(&section, line[..equals_idx].trim().trim_matches('"'));
+            values.insert(key, parse_value(line[equals_idx + 1..].trim()));
+        }
+        return Config { values };
+    }
+
+    pub fn get(&self, key: &str) -> Option<&String> {
+        return self.values.get(key);
+    }
+
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        //lists are comma separated (toml arrays are read as such a list as well)
+        let value = self.get(key);
+        if value.is_none() {
+            return Vec::new();
+        }
+        return value.unwrap().split(',').map(|item| item.trim().to_owned()).filter(|item| !item.is_empty()).collect();
+    }
+
+    pub fn keys_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self.values.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+        keys.sort();
+        return keys;
+    }
+
+    pub fn all_values(&self) -> Vec<(String, String)> {
+        let mut values: Vec<(String, String)> = self.values.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+        values.sort();
+        return values;
+    }
+
+    pub fn get_setting(&self, key: &str) -> String {
+        //this gives the value of one of the settings in SETTING_DEFINITIONS, or its default when it is not in the config
+        let value = self.get(key);
+        if value.is_some() {
+            return value.unwrap().clone();
+        }
+        return default_setting_value(key).to_owned();
+    }
+
+    fn get_bool_setting(&self, key: &str) -> bool {
+        let value = self.get_setting(key);
+        if value != "true" && value != "false" {
+            debug_log_warn(format!("Ignoring the setting {}, since it should be true or false", key));
+            return default_setting_value(key) == "true";
+        }
+        return value == "true";
+    }
+
+    fn get_number_setting(&self, key: &str) -> f32 {
+        let value = self.get_setting(key).parse::<f32>();
+        if value.is_err() {
+            debug_log_warn(format!("Ignoring the setting {}, since it should be a number", key));
+            return default_setting_value(key).parse::<f32>().unwrap();
+        }
+        return value.unwrap();
+    }
+}
+
+
+fn default_setting_value(key: &str) -> &'static str {
+    return SETTING_DEFINITIONS.iter().find(|definition| definition.key == key).unwrap().default_value;
+}
+
+
+pub fn config_file_path() -> Option<PathBuf> {
+    let home_directory = env::var("HOME").or(env::var("USERPROFILE"));
+    return home_directory.ok().map(|home_directory| PathBuf::from(home_directory).join(CONFIG_FILE_NAME));
+}
+
+
+fn read_config_file() -> String {
+    let path = config_file_path();
+    if path.is_none() {
+        return String::new();
+    }
+
+    let mut path = path.unwrap();
+    if !path.is_file() {
+        path = path.with_file_name(OLD_CONFIG_FILE_NAME);
+        if !path.is_file() {
+            return String::new();
+        }
+    }
+
+    let read_result = fs::read_to_string(&path);
+    if read_result.is_err() {
+        debug_log_warn(format!("Could not read the config file: {:?}", path));
+        return String::new();
+    }
+    return read_result.unwrap();
+}
+
+
+pub fn save_config_changes(changes: &Vec<(String, Option<String>)>) -> Config {
+    //a change without a value removes the setting from the file, so its default is used again
+    let text = apply_config_changes(&read_config_file(), changes);
+    let path = config_file_path();
+    if path.is_none() || fs::write(path.as_ref().unwrap(), &text).is_err() {
+        debug_log_warn(format!("Could not write the config file: {:?}", path));
+    }
+    return Config::parse(&text);
+}
+
+
+pub fn apply_config_changes(text: &String, changes: &Vec<(String, Option<String>)>) -> String {
+    //we only change the lines of the changed settings, so the comments and the order of the file are kept
+    let mut remaining_changes: Vec<&(String, Option<String>)> = changes.iter().collect();
+    let mut lines = Vec::new();
+    let mut first_section_line_idx = None;
+    let mut section = String::new();
+
+    for line in text.lines() {
+        let trimmed_line = line.trim();
+        if trimmed_line.starts_with('[') && trimmed_line.find(']').is_some() {
+            section = trimmed_line[1..trimmed_line.find(']').unwrap()].trim().to_owned();
+            if first_section_line_idx.is_none() {
+                first_section_line_idx = Some(lines.len());
+            }
+        } else if !trimmed_line.starts_with('#') && trimmed_line.find('=').is_some() {
+            let written_key = trimmed_line[..trimmed_line.find('=').unwrap()].trim();
+            let key = full_key(&section, written_key.trim_matches('"'));
+
+            let change_idx = remaining_changes.iter().position(|(changed_key, _)| *changed_key == key);
+            if change_idx.is_some() {
+                let (_, value) = remaining_changes.remove(change_idx.unwrap());
+                if value.is_some() {
+                    lines.push(format!("{} = {}", written_key, format_value(value.as_ref().unwrap())));
+                }
+                continue;
+            }
+        }
+        lines.push(line.to_owned());
+    }
+
+    //new settings go before the first section, since otherwise they would become part of that section
+    let new_lines = remaining_changes.iter().filter(|(_, value)| value.is_some())
+                                     .map(|(key, value)| format!("{} = {}", key, format_value(value.as_ref().unwrap())));
+    let insert_idx = first_section_line_idx.unwrap_or(lines.len());
+    lines.splice(insert_idx..insert_idx, new_lines);
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    return text;
+}
+
+
+fn full_key(section: &String, key: &str) -> String {
+    if section.is_empty() {
+        return key.to_owned();
+    }
+    return format!("{}.{}", section, key);
+}
+
+
+fn parse_value(text: &str) -> String {
+    if text.starts_with('"') {
+        //a basic string, that can have escapes in it
+        let mut value = String::new();
+        let mut chars = text[1..].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => { break; },
+                '\\' => {
+                    match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(escaped_char) => value.push(escaped_char),
+                        None => {},
+                    }
+                },
+                _ => value.push(c),
+            }
+        }
+        return value;
+    }
+
+    if text.starts_with('\'') {
+        //a literal string, without escapes
+        return text[1..].split('\'').next().unwrap().to_owned();
+    }
+
+    if text.starts_with('[') {
+        //arrays are read as a comma separated list, like lists in older config files
+        let array_end = text.rfind(']').unwrap_or(text.len());
+        let items: Vec<String> = text[1..array_end].split(',').map(|item| parse_value(item.trim())).filter(|item| !item.is_empty()).collect();
+        return items.join(", ");
+    }
+
+    //in toml only things like numbers and booleans are written without quotes, but older config files also have urls without them, which can have a #
+    return text.split(" #").next().unwrap().trim().to_owned();
+}
+
+
+fn format_value(value: &String) -> String {
+    let is_bool_or_number = value == "true" || value == "false" || (!value.is_empty() && value.chars().all(|c| c.is_ascii_digit() || c == '.'));
+    if is_bool_or_number {
+        return value.clone();
+    }
+    return format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t"));
+}
@@ -0,0 +1,78 @@
+use crate::config::{apply_config_changes, Config, Settings};
+use crate::network::UA_FIREFOX_WINDOWS;
+
+
+#[test]
+fn test_parse_config() {
+    let text = String::from("# a comment\n\
+                             general.start_url = \"https://www.example.com/\"\n\
+                             javascript.enabled = false # scripts are slow\n\
+                             \n\
+                             [proxy]\n\
+                             http = 'http://proxy.example.com:8080'\n\
+                             no_proxy = [\"localhost\", \"example.org\"]\n\
+                             \n\
+                             [network]\n\
+                             \"user_agent\" = \"say \\\"hi\\\"\"\n");
+    let config = Config::parse(&text);
+
+    assert_eq!(config.get("general.start_url"), Some(&String::from("https://www.example.com/")));
+    assert_eq!(config.get("javascript.enabled"), Some(&String::from("false")));
+    assert_eq!(config.get("proxy.http"), Some(&String::from("http://proxy.example.com:8080")));
+    assert_eq!(config.get_list("proxy.no_proxy"), vec![String::from("localhost"), String::from("example.org")]);
+    assert_eq!(config.get("network.user_agent"), Some(&String::from("say \"hi\"")));
+    assert_eq!(config.get("http"), None);
+
+    //older config files have values without quotes
+    let config = Config::parse(&String::from("proxy.http = http://proxy.example.com:8080\nproxy.no_proxy = localhost, example.org\n"));
+    assert_eq!(config.get("proxy.http"), Some(&String::from("http://proxy.example.com:8080")));
+    assert_eq!(config.get_list("proxy.no_proxy"), vec![String::from("localhost"), String::from("example.org")]);
+}
+
+
+#[test]
+fn test_apply_config_changes() {
+    let text = String::from("# my settings\n\
+                             images.enabled = false\n\
+                             fonts.default_size = 20\n\
+                             \n\
+                             [proxy]\n\
+                             http = \"http://proxy.example.com:8080\"\n");
+    let changes = vec![
+        (String::from("images.enabled"), None),
+        (String::from("proxy.http"), Some(String::from("http://other.example.com"))),
+        (String::from("fonts.minimum_size"), Some(String::from("12"))),
+        (String::from("search.url"), Some(String::from("https://search.example.com/?q=%s"))),
+        (String::from("javascript.enabled"), None),
+    ];
+
+    assert_eq!(apply_config_changes(&text, &changes), "# my settings\n\
+                                                        fonts.default_size = 20\n\
+                                                        \n\
+                                                        fonts.minimum_size = 12\n\
+                                                        search.url = \"https://search.example.com/?q=%s\"\n\
+                                                        [proxy]\n\
+                                                        http = \"http://other.example.com\"\n");
+
+    let changed_text = apply_config_changes(&String::new(), &vec![(String::from("network.user_agent"), Some(String::from("a \"quoted\" agent")))]);
+    assert_eq!(Config::parse(&changed_text).get("network.user_agent"), Some(&String::from("a \"quoted\" agent")));
+}
+
+
+#[test]
+fn test_settings_from_config() {
+    let settings = Settings::from_config(&Config::parse(&String::new()));
+    assert_eq!(settings.start_url, "about:home");
+    assert_eq!(settings.user_agent, UA_FIREFOX_WINDOWS);
+    assert!(settings.javascript_enabled);
+    assert!(settings.images_enabled);
+    assert_eq!(settings.default_font_size, 18.0);
+    assert_eq!(settings.minimum_font_size, 0.0);
+
+    let settings = Settings::from_config(&Config::parse(&String::from("javascript.enabled = false\nimages.enabled = maybe\n\
+                                                                       fonts.default_size = 16.5\nfonts.minimum_size = large\n")));
+    assert!(!settings.javascript_enabled);
+    assert!(settings.images_enabled);
+    assert_eq!(settings.default_font_size, 16.5);
+    assert_eq!(settings.minimum_font_size, 0.0);
+}
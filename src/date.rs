@@ -0,0 +1,30 @@
+//These convert between dates in the (proleptic) gregorian calendar and the number of days since 1970-01-01, for the dates in cookies, in the
+//request log and in javascript. They are the algorithms from http://howardhinnant.github.io/date_algorithms.html, which also work for dates
+//before 1970 (those give a negative number of days).
+
+
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    //the month is 1-based, and the day can be out of range for the month, which then moves the date forward or backward
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    return era * 146097 + day_of_era - 719468;
+}
+
+
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    //the inverse of days_from_civil, this gives the year, month (1-based) and day
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400;
+    return (if month <= 2 { year + 1 } else { year }, month, day);
+}
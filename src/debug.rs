@@ -1,13 +1,8 @@
-use std::rc::Rc;
-use std::cell::RefCell;
-
-use crate::dom::Document;
+use crate::dom::{Document, DomNodeId};
 use crate::html_lexer::HtmlTokenWithLocation;
-use crate::layout::LayoutNode;
-
-#[cfg(debug_assertions)] use crate::dom::ElementDomNode;
+use crate::layout::{Display, FullLayout, LayoutNode, LayoutNodeContent, LayoutNodeId};
 
-#[cfg(debug_assertions)] const INDENT_AMOUNT: u32 = 2;
+const INDENT_AMOUNT: u32 = 2;
 
 
 //TODO: a few of these should probably output json (they are trees, mostly), so I can nicely format them, and collapse parts.
@@ -19,13 +14,39 @@ pub fn debug_print_dom_tree(_: &Document, _: &str) {}
 #[allow(dead_code)]
 #[cfg(debug_assertions)]
 pub fn debug_print_dom_tree(document: &Document) {
-    debug_print_dom_node_tree_with_indent(&document.document_node, 0);
+    debug_print_dom_node_tree_with_indent(document, document.document_node, 0);
 }
 
 
-#[cfg(debug_assertions)]
-fn debug_print_dom_node_tree_with_indent(dom_node: &Rc<RefCell<ElementDomNode>>, indent_cnt: u32) {
-    let dom_node = dom_node.borrow();
+pub fn print_dom_node_for_inspection(document: &Document, dom_node_id: DomNodeId) {
+    //TODO: we don't have developer tools yet, so inspecting an element prints it (with everything inside it) to the console, also in release builds
+    println!("== inspecting element");
+    debug_print_dom_node_tree_with_indent(document, dom_node_id, 0);
+    println!("== done inspecting element");
+}
+
+
+pub fn describe_layout_node(layout_node: &LayoutNode, document: &Document) -> String {
+    //this is shown when hovering boxes with the layout overlay on, also in release builds
+    let element_name = layout_node.from_dom_node.and_then(|dom_node_id| document.nodes[dom_node_id].name.clone());
+    let display = if layout_node.display == Display::Block { "block" } else { "inline" };
+    let mut description = format!("{} {} ({})", element_name.map(|name| format!("<{}>", name)).unwrap_or(String::from("anonymous")),
+                                  layout_node.content.kind_name(), display);
+
+    let bounding_box = layout_node.get_bounding_box();
+    if bounding_box.is_some() {
+        let bounding_box = bounding_box.unwrap();
+        description += format!(": {:.1} x {:.1} at {:.1}, {:.1}", bounding_box.width, bounding_box.height, bounding_box.x, bounding_box.y).as_str();
+    }
+    if let LayoutNodeContent::TextLayoutNode(text_node) = &layout_node.content {
+        description += format!(", {} rects, font size {}", text_node.rects.len(), text_node.rects.first().map(|rect| rect.font.size).unwrap_or(0)).as_str();
+    }
+    return description;
+}
+
+
+fn debug_print_dom_node_tree_with_indent(document: &Document, dom_node_id: DomNodeId, indent_cnt: u32) {
+    let dom_node = &document.nodes[dom_node_id];
 
     let mut indent = String::new();
     for _ in 0..indent_cnt {
@@ -36,23 +57,22 @@ fn debug_print_dom_node_tree_with_indent(dom_node: &Rc<RefCell<ElementDomNode>>,
         debug_assert!(dom_node.children.is_none());
         debug_assert!(dom_node.attributes.is_none());
 
-        println!("{}TEXT: \"{}\" ({}) (parent: {})", indent, dom_node.text.as_ref().unwrap().text_content, dom_node.internal_id, dom_node.parent_id);
+        println!("{}TEXT: \"{}\" ({:?}) (parent: {:?})", indent, dom_node.text.as_ref().unwrap().text_content, dom_node.id, dom_node.parent);
 
     } else {
         debug_assert!(dom_node.text.is_none());
 
-        println!("{}{} ({}) (parent: {})", indent, dom_node.name.clone().unwrap_or("".to_owned()), dom_node.internal_id, dom_node.parent_id);
+        println!("{}{} ({:?}) (parent: {:?})", indent, dom_node.name.clone().unwrap_or("".to_owned()), dom_node.id, dom_node.parent);
 
         if dom_node.attributes.is_some() {
             for att in dom_node.attributes.as_ref().unwrap() {
-                let att = att.borrow();
-                println!("{}ATTR: ({} = {}) (parent: {})", indent, att.name, att.value, att.parent_id);
+                println!("{}ATTR: ({} = {}) (parent: {:?})", indent, att.name, att.value, att.parent_id);
             }
         }
 
         if dom_node.children.is_some() {
-            for child in dom_node.children.clone().unwrap() {
-                debug_print_dom_node_tree_with_indent(&child, indent_cnt + INDENT_AMOUNT);
+            for child_id in dom_node.children.as_ref().unwrap() {
+                debug_print_dom_node_tree_with_indent(document, *child_id, indent_cnt + INDENT_AMOUNT);
             }
         }
     }
@@ -87,24 +107,24 @@ pub fn debug_print_html_tokens(tokens: &Vec<HtmlTokenWithLocation>) {
 
 #[allow(dead_code)]
 #[cfg(not(debug_assertions))]
-pub fn debug_print_layout_tree(_: &Rc<RefCell<LayoutNode>>) {}
+pub fn debug_print_layout_tree(_: &FullLayout) {}
 #[allow(dead_code)]
 #[cfg(debug_assertions)]
-pub fn debug_print_layout_tree(node: &Rc<RefCell<LayoutNode>>) {
+pub fn debug_print_layout_tree(full_layout: &FullLayout) {
     println!("== dumping layout tree");
-    debug_print_layout_tree_with_indent(node, 0);
+    debug_print_layout_tree_with_indent(full_layout, full_layout.root_node, 0);
     println!("== done dumping layout tree");
 }
 
 
 #[cfg(debug_assertions)]
-fn debug_print_layout_tree_with_indent(node: &Rc<RefCell<LayoutNode>>, indent_cnt: u32) {
+fn debug_print_layout_tree_with_indent(full_layout: &FullLayout, node_id: LayoutNodeId, indent_cnt: u32) {
     let mut indent = String::new();
     for _ in 0..indent_cnt {
         indent.push(' ');
     }
 
-    let node = node.borrow();
+    let node = &full_layout.nodes[node_id];
 
     let rect_str = match &node.content {
         crate::layout::LayoutNodeContent::TextLayoutNode(text_node) => {
@@ -117,6 +137,9 @@ fn debug_print_layout_tree_with_indent(node: &Rc<RefCell<LayoutNode>>, indent_cn
         crate::layout::LayoutNodeContent::ImageLayoutNode(_) => todo!(),  //TODO: implement
         crate::layout::LayoutNodeContent::ButtonLayoutNode(_) => todo!(),  //TODO: implement
         crate::layout::LayoutNodeContent::TextInputLayoutNode(_) => todo!(),  //TODO: implement
+        crate::layout::LayoutNodeContent::TextAreaLayoutNode(_) => todo!(),  //TODO: implement
+        crate::layout::LayoutNodeContent::FileInputLayoutNode(_) => todo!(),  //TODO: implement
+        crate::layout::LayoutNodeContent::IframeLayoutNode(_) => todo!(),  //TODO: implement
         crate::layout::LayoutNodeContent::BoxLayoutNode(_) => todo!(),  //TODO: implement
         crate::layout::LayoutNodeContent::NoContent => todo!(), //TODO: implement
         crate::layout::LayoutNodeContent::TableLayoutNode(_) => todo!(), //TODO: implement
@@ -129,10 +152,10 @@ fn debug_print_layout_tree_with_indent(node: &Rc<RefCell<LayoutNode>>, indent_cn
         "!visible"
     };
 
-    println!("{}{:?} ({}) {}", indent, rect_str, node.internal_id, visible);
+    println!("{}{:?} ({:?}) {}", indent, rect_str, node_id, visible);
     if node.children.is_some() {
-        for child in node.children.clone().unwrap() {
-            debug_print_layout_tree_with_indent(&child, indent_cnt + INDENT_AMOUNT)
+        for child_id in node.children.as_ref().unwrap() {
+            debug_print_layout_tree_with_indent(full_layout, *child_id, indent_cnt + INDENT_AMOUNT)
         }
     }
 }
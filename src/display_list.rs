@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use image::DynamicImage;
+
+use crate::color::Color;
+use crate::layout::Rect;
+use crate::platform::{fonts::Font, Platform, Position};
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum PaintCommand {
+    FillRect { rect: Rect, color: Color },
+    StrokeRect { rect: Rect, color: Color },
+    Line { start: Position, end: Position, color: Color },
+    Text { text: String, x: f32, y: f32, font: Font, color: Color },
+    Image { image: Arc<DynamicImage>, x: f32, y: f32 },
+    ImageTiles { image: Arc<DynamicImage>, tile_positions: Vec<Position>, tile_width: f32, tile_height: f32, clip: Rect },
+    PushClip(Rect),
+    PushOpacity(f32),
+    PushScrollOffset(f32), //the commands until the matching pop are scrolled this much more, like the content of an iframe
+    Pop, //ends the last clip, opacity or scroll offset that was pushed
+}
+impl PaintCommand {
+    fn vertical_extent(&self) -> Option<(f32, f32)> {
+        //the top and bottom of what the command draws, None for commands that are not drawn on a location themselves
+        return match self {
+            PaintCommand::FillRect { rect, .. } | PaintCommand::StrokeRect { rect, .. } | PaintCommand::PushClip(rect) => Some((rect.y, rect.y + rect.height)),
+            PaintCommand::Line { start, end, .. } => Some((start.y.min(end.y), start.y.max(end.y))),
+            //we don't know the size of the text here, but glyphs don't go further down than twice the font size
+            PaintCommand::Text { y, font, .. } => Some((*y, y + (font.size as f32 * 2.0))),
+            PaintCommand::Image { image, y, .. } => Some((*y, y + image.height() as f32)),
+            PaintCommand::ImageTiles { clip, .. } => Some((clip.y, clip.y + clip.height)),
+            PaintCommand::PushOpacity(_) | PaintCommand::PushScrollOffset(_) | PaintCommand::Pop => None,
+        };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct DisplayList {
+    //what to paint, in order, in page coordinates (before scrolling), so the same list can be painted again for another scroll position
+    pub commands: Vec<PaintCommand>,
+}
+impl DisplayList {
+    pub fn new() -> DisplayList {
+        return DisplayList { commands: Vec::new() };
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.commands.push(PaintCommand::FillRect { rect: Rect { x, y, width, height }, color });
+    }
+
+    pub fn draw_square(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.commands.push(PaintCommand::StrokeRect { rect: Rect { x, y, width, height }, color });
+    }
+
+    pub fn draw_line(&mut self, start: Position, end: Position, color: Color) {
+        self.commands.push(PaintCommand::Line { start, end, color });
+    }
+
+    pub fn render_text(&mut self, text: &String, x: f32, y: f32, font: &Font, color: Color) {
+        if text.is_empty() {
+            return;
+        }
+        self.commands.push(PaintCommand::Text { text: text.clone(), x, y, font: font.clone(), color });
+    }
+
+    pub fn render_image(&mut self, image: &Arc<DynamicImage>, x: f32, y: f32) {
+        self.commands.push(PaintCommand::Image { image: Arc::clone(image), x, y });
+    }
+
+    pub fn render_image_tiles(&mut self, image: &Arc<DynamicImage>, tile_positions: Vec<Position>, tile_width: f32, tile_height: f32, clip: Rect) {
+        self.commands.push(PaintCommand::ImageTiles { image: Arc::clone(image), tile_positions, tile_width, tile_height, clip });
+    }
+
+    pub fn push_clip(&mut self, rect: Rect) {
+        self.commands.push(PaintCommand::PushClip(rect));
+    }
+
+    pub fn push_opacity(&mut self, opacity: f32) {
+        self.commands.push(PaintCommand::PushOpacity(opacity));
+    }
+
+    pub fn push_scroll_offset(&mut self, scroll_y: f32) {
+        self.commands.push(PaintCommand::PushScrollOffset(scroll_y));
+    }
+
+    pub fn pop(&mut self) {
+        self.commands.push(PaintCommand::Pop);
+    }
+
+    pub fn replay(&self, platform: &mut Platform, scroll_y: f32) {
+        //what is below the bottom of the window can be skipped, the window can be resized, so we check its height every time
+        let window_height = platform.get_window_dimensions().height;
+        replay_commands(platform, &self.commands, scroll_y, window_height);
+    }
+}
+
+
+fn replay_commands(platform: &mut Platform, commands: &[PaintCommand], scroll_y: f32, window_height: f32) {
+    let mut idx = 0;
+    while idx < commands.len() {
+        let command = &commands[idx];
+
+        let extent = command.vertical_extent();
+        if extent.is_some() {
+            let (top, bottom) = extent.unwrap();
+            if bottom < scroll_y || top > scroll_y + window_height {
+                //the command is not on the screen, for a clip that means nothing inside it is
+                idx = if matches!(command, PaintCommand::PushClip(_)) { find_matching_pop(commands, idx) + 1 } else { idx + 1 };
+                continue;
+            }
+        }
+
+        match command {
+            PaintCommand::FillRect { rect, color } => {
+                platform.fill_rect(rect.x, rect.y - scroll_y, rect.width, rect.height, *color, 255);
+            },
+            PaintCommand::StrokeRect { rect, color } => {
+                platform.draw_square(rect.x, rect.y - scroll_y, rect.width, rect.height, *color, 255);
+            },
+            PaintCommand::Line { start, end, color } => {
+                platform.draw_line(Position { x: start.x, y: start.y - scroll_y }, Position { x: end.x, y: end.y - scroll_y }, *color);
+            },
+            PaintCommand::Text { text, x, y, font, color } => {
+                platform.render_text(text, *x, y - scroll_y, font, *color);
+            },
+            PaintCommand::Image { image, x, y } => {
+                platform.render_image(image, *x, y - scroll_y);
+            },
+            PaintCommand::ImageTiles { image, tile_positions, tile_width, tile_height, clip } => {
+                let tile_positions = tile_positions.iter().map(|position| Position { x: position.x, y: position.y - scroll_y }).collect();
+                platform.render_image_tiles(image, &tile_positions, *tile_width, *tile_height, clip.x, clip.y - scroll_y, clip.width, clip.height);
+            },
+            PaintCommand::PushClip(rect) => {
+                let pop_idx = find_matching_pop(commands, idx);
+                platform.render_clipped(rect.x, rect.y - scroll_y, rect.width, rect.height, |platform| {
+                    replay_commands(platform, &commands[(idx + 1)..pop_idx], scroll_y, window_height);
+                });
+                idx = pop_idx;
+            },
+            PaintCommand::PushOpacity(opacity) => {
+                let pop_idx = find_matching_pop(commands, idx);
+                platform.render_with_opacity(*opacity, |platform| {
+                    replay_commands(platform, &commands[(idx + 1)..pop_idx], scroll_y, window_height);
+                });
+                idx = pop_idx;
+            },
+            PaintCommand::PushScrollOffset(extra_scroll_y) => {
+                let pop_idx = find_matching_pop(commands, idx);
+                replay_commands(platform, &commands[(idx + 1)..pop_idx], scroll_y + extra_scroll_y, window_height);
+                idx = pop_idx;
+            },
+            PaintCommand::Pop => {
+                panic!("Invalid state"); //pops are skipped together with the push they belong to
+            },
+        }
+        idx += 1;
+    }
+}
+
+
+fn find_matching_pop(commands: &[PaintCommand], push_idx: usize) -> usize {
+    let mut depth = 0;
+    for idx in (push_idx + 1)..commands.len() {
+        match commands[idx] {
+            PaintCommand::PushClip(_) | PaintCommand::PushOpacity(_) | PaintCommand::PushScrollOffset(_) => { depth += 1; },
+            PaintCommand::Pop => {
+                if depth == 0 {
+                    return idx;
+                }
+                depth -= 1;
+            },
+            _ => {},
+        }
+    }
+    panic!("Invalid state"); //every push should have a pop
+}
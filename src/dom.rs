@@ -1,183 +1,620 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::ops::Deref;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use image::DynamicImage;
 
+use crate::arena::{Arena, NodeId};
+use crate::debug::debug_log_warn;
+use crate::html_parser::{NON_BREAKING_SPACE, VOID_ELEMENTS};
+use crate::network::{encode_form_urlencoded, is_cross_scheme_file_access, is_mixed_content, percent_decode, CacheMode};
 use crate::network::url::Url;
-use crate::platform::Platform;
+use crate::platform::fonts::FontContext;
 use crate::resource_loader::{
     self,
+    LoadedResource,
+    RequestSource,
+    ResourcePriority,
     ResourceRequestJobTracker,
     ResourceThreadPool,
 };
 use crate::script::js_ast::Script;
-use crate::style::StyleContext;
+use crate::script::js_console;
+use crate::style::{
+    css_lexer,
+    css_parser,
+    media_query::MediaEnvironment,
+    CachedStyles,
+    StyleContext,
+    StyleImport,
+    StyleSheetSlot,
+};
+use crate::svg;
 use crate::ui_components::{
     Button,
+    FileInput,
     PageComponent,
-    TextField
+    TextArea,
+    TextField,
+    TEXT_AREA_DEFAULT_COLS,
+    TEXT_AREA_DEFAULT_ROWS,
 };
 
 
-static NEXT_DOM_NODE_INTERNAL_ID: AtomicUsize = AtomicUsize::new(1);
-pub fn get_next_dom_node_interal_id() -> usize { NEXT_DOM_NODE_INTERNAL_ID.fetch_add(1, Ordering::Relaxed) }
+const MAX_STYLE_IMPORT_DEPTH: usize = 8;
+
+
+pub type DomNodeId = NodeId<ElementDomNode>;
 
 
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct Document {
-    pub document_node: Rc<RefCell<ElementDomNode>>,
-    pub all_nodes: HashMap<usize, Rc<RefCell<ElementDomNode>>>,
+    pub document_node: DomNodeId,
+    pub nodes: Arena<ElementDomNode>, //this has all nodes, also the ones scripts made or removed, which are not in the tree
     pub style_context: StyleContext,
-    pub base_url: Url, //The url this DOM was loaded from
+    pub pending_style_imports: Vec<PendingStyleImport>,
+    pub background_images: HashMap<String, Arc<DynamicImage>>, //keyed by the url as written in the css
+    pub background_image_job_trackers: HashMap<String, ResourceRequestJobTracker<Arc<DynamicImage>>>,
+    pub background_images_need_scheduling: bool,
+    pub page_url: Url, //The url this DOM was loaded from
+    pub base_url: Url, //The url relative urls are resolved against, which is the page url, unless the page has a base element
+    pub nodes_to_post_construct: Vec<DomNodeId>, //nodes made by scripts, which are set up before the next layout
+    pub ready_state: DocumentReadyState,
+    pub cache_mode: CacheMode, //this is how the images and style sheets of the document are loaded, so a hard reload also loads those again
 }
 impl Document {
     pub fn new_empty() -> Document {
-        return Document { document_node: Rc::from(RefCell::from(ElementDomNode::new_empty())),
-            all_nodes: HashMap::new(), style_context: StyleContext { user_agent_sheet: vec![], author_sheet: vec![] }, base_url: Url::empty() };
+        let mut nodes = Arena::new();
+        let document_node = nodes.insert_with(ElementDomNode::new_document_node);
+        return Document { document_node, nodes, pending_style_imports: Vec::new(), background_images: HashMap::new(),
+            background_image_job_trackers: HashMap::new(), background_images_need_scheduling: false, page_url: Url::empty(), base_url: Url::empty(),
+            nodes_to_post_construct: Vec::new(), style_context: StyleContext::new(vec![], vec![], MediaEnvironment::new_default()),
+            ready_state: DocumentReadyState::Complete, cache_mode: CacheMode::Default };
+    }
+    pub fn has_pending_resources(&self) -> bool {
+        //this is true while images or styles the document needs are still loading, and the document is complete when there are none left
+        //TODO: the documents in frames should also be loaded before the document is complete
+        if !self.pending_style_imports.is_empty() || self.background_images_need_scheduling || !self.background_image_job_trackers.is_empty() {
+            return true;
+        }
+        let loading_image = self.find_node_in_subtree(self.document_node, &|node| {
+            return node.name.is_some() && node.name.as_ref().unwrap() == "img" && node.image.is_none();
+        });
+        return loading_image.is_some();
     }
     pub fn update_all_dom_nodes(&mut self, resource_thread_pool: &mut ResourceThreadPool) -> bool {
         //returns whether there are dirty nodes after the update
 
-        return self.document_node.borrow_mut().update(resource_thread_pool, self);
+        let styles_changed = self.update_style_imports(resource_thread_pool);
+        if styles_changed {
+            self.background_images_need_scheduling = true;
+        }
+        let background_images_changed = self.update_background_images(resource_thread_pool);
+
+        if styles_changed || background_images_changed {
+            //any node might have different styles (or a new background image) now, and styles are resolved while building the layout tree,
+            //so we rebuild everything
+            for (_, node) in self.nodes.iter_mut() {
+                node.dirty = true;
+            }
+        }
+
+        return self.update_node(self.document_node, resource_thread_pool);
+    }
+    pub fn post_construct_new_nodes(&mut self, font_context: &FontContext) {
+        for node_id in mem::take(&mut self.nodes_to_post_construct) {
+            self.post_construct(node_id, font_context);
+        }
+    }
+    pub fn create_node(&mut self, name: Option<&str>, text: Option<&str>) -> DomNodeId {
+        //this makes an element (with a name) or a text node for scripts, which is not in the tree yet
+        let node_id = self.nodes.insert_with(|id| ElementDomNode {
+            id,
+            parent: None,
+            is_document_node: false,
+            dirty: false,
+            text: text.map(|text| DomText { text_content: text.to_owned(), non_breaking_space_positions: None }),
+            name: name.map(|name| name.to_ascii_lowercase()),
+            name_for_layout: name.map(|name| TagName::from_string(&name.to_owned())).unwrap_or(TagName::Other),
+            children: if name.is_some() { Some(Vec::new()) } else { None },
+            attributes: if name.is_some() { Some(Vec::new()) } else { None },
+            image: None,
+            img_job_tracker: None,
+            scripts: None,
+            page_component: None,
+            default_value: None,
+            cached_styles: None,
+        });
+
+        self.nodes_to_post_construct.push(node_id);
+        return node_id;
+    }
+    pub fn add_parsed_nodes(&mut self, node_ids: &Vec<DomNodeId>) {
+        //nodes that were parsed separately (like for innerHTML) are already in the arena, but still need to be set up and inserted in the tree
+        self.nodes_to_post_construct.extend(node_ids.iter().copied());
+    }
+    pub fn is_in_tree(&self, node_id: DomNodeId) -> bool {
+        //nodes made by scripts, or removed by them, are not in the tree until they are inserted (again)
+        let mut node_id_to_check = Some(node_id);
+        while node_id_to_check.is_some() {
+            let node_to_check = &self.nodes[node_id_to_check.unwrap()];
+            if node_to_check.is_document_node {
+                return true;
+            }
+            node_id_to_check = node_to_check.parent;
+        }
+        return false;
     }
-    pub fn find_parent_with_name(&self, start_node: &ElementDomNode, name_to_match: &str) -> Option<Rc<RefCell<ElementDomNode>>> {
-        let mut node_id_to_check = start_node.parent_id;
+    pub fn insert_child(&mut self, parent_id: DomNodeId, child_id: DomNodeId, reference_child_id: Option<DomNodeId>) -> Result<(), String> {
+        //this inserts the child before reference_child, or at the end without one, and takes it out of where it was before
+        if self.nodes[parent_id].children.is_none() {
+            return Err(String::from("this node can't have children"));
+        }
+        if self.find_node_in_subtree(child_id, &|node| node.id == parent_id).is_some() {
+            return Err(String::from("a node can't be inserted into itself"));
+        }
 
-        while node_id_to_check != 0 {
-            let node_to_check = self.all_nodes[&node_id_to_check].clone();
+        let mut reference_child_id = reference_child_id;
+        if reference_child_id.is_some() {
+            if self.nodes[reference_child_id.unwrap()].parent != Some(parent_id) {
+                return Err(String::from("the node to insert before is not a child of this node"));
+            }
 
-            if node_to_check.borrow().name.is_some() && node_to_check.borrow().name.as_ref().unwrap().as_str() == name_to_match {
-                return Some(node_to_check);
+            if reference_child_id == Some(child_id) {
+                //inserting a node before itself keeps it where it is, so we insert it before its next sibling instead
+                let siblings = self.nodes[parent_id].children.as_ref().unwrap();
+                let index = siblings.iter().position(|node_id| *node_id == child_id).unwrap();
+                reference_child_id = siblings.get(index + 1).copied();
             }
+        }
+
+        let old_parent_id = self.nodes[child_id].parent;
+        if old_parent_id.is_some() {
+            self.remove_child(old_parent_id.unwrap(), child_id)?;
+        }
 
-            node_id_to_check = node_to_check.borrow().parent_id;
+        let parent = &mut self.nodes[parent_id];
+        let children = parent.children.as_mut().unwrap();
+        let index = match reference_child_id {
+            Some(reference_child_id) => children.iter().position(|node_id| *node_id == reference_child_id).unwrap(),
+            None => children.len(),
+        };
+        children.insert(index, child_id);
+
+        //the layout is rebuilt for dirty nodes, so the parent gets a new layout with the new child in it
+        parent.dirty = true;
+        self.nodes[child_id].parent = Some(parent_id);
+        return Ok(());
+    }
+    pub fn remove_child(&mut self, parent_id: DomNodeId, child_id: DomNodeId) -> Result<(), String> {
+        let parent = &mut self.nodes[parent_id];
+        let index = parent.children.as_ref().and_then(|children| children.iter().position(|node_id| *node_id == child_id));
+        if index.is_none() {
+            return Err(String::from("the node to remove is not a child of this node"));
+        }
+
+        parent.children.as_mut().unwrap().remove(index.unwrap());
+        parent.dirty = true;
+        self.nodes[child_id].parent = None;
+        return Ok(());
+    }
+    pub fn replace_children(&mut self, parent_id: DomNodeId, new_children: Vec<DomNodeId>) {
+        //the new children should not be in the tree already (they are new nodes, for innerHTML and textContent)
+        if self.nodes[parent_id].children.is_none() {
+            return;
+        }
+
+        for old_child_id in self.nodes[parent_id].children.clone().unwrap() {
+            self.nodes[old_child_id].parent = None;
+        }
+        for new_child_id in &new_children {
+            self.nodes[*new_child_id].parent = Some(parent_id);
+        }
+        let parent = &mut self.nodes[parent_id];
+        parent.children = Some(new_children);
+        parent.dirty = true;
+    }
+    pub fn get_parent(&self, node_id: DomNodeId) -> Option<DomNodeId> {
+        return self.nodes[node_id].parent;
+    }
+    pub fn find_node_in_subtree(&self, node_id: DomNodeId, matches: &dyn Fn(&ElementDomNode) -> bool) -> Option<DomNodeId> {
+        //this returns the first matching node in tree order
+        let node = &self.nodes[node_id];
+        if matches(node) {
+            return Some(node_id);
+        }
+
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                let possible_node_id = self.find_node_in_subtree(*child_id, matches);
+                if possible_node_id.is_some() {
+                    return possible_node_id;
+                }
+            }
         }
 
         return None;
     }
-}
+    pub fn collect_node_ids(&self, node_id: DomNodeId, node_ids: &mut HashSet<DomNodeId>) {
+        node_ids.insert(node_id);
+        if self.nodes[node_id].children.is_some() {
+            for child_id in self.nodes[node_id].children.as_ref().unwrap() {
+                self.collect_node_ids(*child_id, node_ids);
+            }
+        }
+    }
+    fn update_background_images(&mut self, resource_thread_pool: &mut ResourceThreadPool) -> bool {
+        //returns whether any new background images were loaded
 
+        if self.background_images_need_scheduling {
+            for image_url in self.style_context.collect_background_image_urls() {
+                if self.background_images.contains_key(&image_url) || self.background_image_job_trackers.contains_key(&image_url) {
+                    continue;
+                }
 
-#[cfg_attr(debug_assertions, derive(Debug))]
-pub enum TagName {
-    B,
-    Br,
-    Img,
-    Input,
-    Script,
-    Style,
-    Table,
-    Title,
+                //TODO: urls in stylesheets should be resolved against the url of the stylesheet, not the document
+                let full_url = Url::from_base_url(&image_url, Some(&self.base_url));
+                if !self.allows_subresource(&full_url) {
+                    //we treat a blocked image the same as one that could not be loaded
+                    self.background_images.insert(image_url, resource_loader::fallback_image());
+                    continue;
+                }
+                self.background_image_job_trackers.insert(image_url, resource_loader::schedule_load_image(&full_url, self.cache_mode, resource_thread_pool));
+            }
+            self.background_images_need_scheduling = false;
+        }
 
-    Other,
-}
-impl TagName {
-    pub fn from_string(tag_being_parsed: &String) -> TagName {
-        return match tag_being_parsed.as_str() {
+        let mut loaded_image_urls = Vec::new();
+        for (image_url, job_tracker) in self.background_image_job_trackers.iter() {
+            let try_recv_result = job_tracker.receiver.try_recv();
+            if try_recv_result.is_ok() {
+                self.background_images.insert(image_url.clone(), try_recv_result.unwrap());
+                loaded_image_urls.push(image_url.clone());
+            }
+        }
+        for image_url in &loaded_image_urls {
+            self.background_image_job_trackers.remove(image_url);
+        }
 
-            "b" => TagName::B,
-            "br" => TagName::Br,
-            "img" => TagName::Img,
-            "input" => TagName::Input,
-            "script" => TagName::Script,
-            "style" => TagName::Style,
-            "table" => TagName::Table,
-            "title" => TagName::Title,
+        return !loaded_image_urls.is_empty();
+    }
+    fn update_style_imports(&mut self, resource_thread_pool: &mut ResourceThreadPool) -> bool {
+        //returns whether any new style rules were added
 
-            _ => {
-                //this is not an error, since we only translate tags that we need to do something for in the layout tree
-                TagName::Other
+        let mut styles_changed = false;
+        let mut new_pending_imports = Vec::new();
+
+        for pending_import in self.pending_style_imports.iter_mut() {
+            if pending_import.job_tracker.is_none() {
+                if !subresource_allowed(&self.page_url, &pending_import.url) {
+                    pending_import.done = true;
+                    continue;
+                }
+                pending_import.job_tracker = Some(resource_loader::schedule_load_text(&pending_import.url, ResourcePriority::Stylesheet, self.cache_mode, RequestSource::Subresource,
+                                                                                           resource_thread_pool));
+                continue;
+            }
+
+            let try_recv_result = pending_import.job_tracker.as_ref().unwrap().receiver.try_recv();
+            if try_recv_result.is_ok() {
+                let loaded_css = try_recv_result.unwrap();
+                let style_tokens = css_lexer::lex_css(&loaded_css.text(), 1, 1);
+                let style_sheet = css_parser::parse_css(&style_tokens);
+
+                for (import_idx, nested_import) in style_sheet.imports.iter().enumerate() {
+                    if pending_import.depth() + 1 >= MAX_STYLE_IMPORT_DEPTH {
+                        debug_log_warn(format!("not loading nested @import, maximum depth reached: {}", nested_import.url));
+                        continue;
+                    }
+                    //when the sheet was redirected, the urls in it are relative to where it was loaded from in the end
+                    let nested_slot = [pending_import.slot.clone(), vec![import_idx]].concat();
+                    new_pending_imports.push(PendingStyleImport::new(nested_import, &loaded_css.url, nested_slot));
+                }
+
+                self.style_context.fill_import_slot(&pending_import.slot, style_sheet);
+                pending_import.job_tracker = None;
+                pending_import.done = true;
+                styles_changed = true;
             }
         }
+
+        self.pending_style_imports.retain(|pending_import| !pending_import.done);
+        self.pending_style_imports.extend(new_pending_imports);
+
+        return styles_changed;
     }
-}
+    pub fn find_parent_with_name(&self, start_node_id: DomNodeId, name_to_match: &str) -> Option<DomNodeId> {
+        let mut node_id_to_check = self.nodes[start_node_id].parent;
 
+        while node_id_to_check.is_some() {
+            let node_to_check = &self.nodes[node_id_to_check.unwrap()];
 
-#[derive(PartialEq)]
-pub enum NavigationAction {
-    None,
-    Get(Url),
-    Post(PostData),
-}
+            if node_to_check.name.is_some() && node_to_check.name.as_ref().unwrap().as_str() == name_to_match {
+                return node_id_to_check;
+            }
 
+            node_id_to_check = node_to_check.parent;
+        }
 
-//TODO: this should be moved to a network related module (just a seperate thing in network?)
-#[derive(PartialEq)]
-pub struct PostData {
-    pub url: Url,
-    pub fields: HashMap<String, String>,
-}
+        return None;
+    }
+    pub fn find_body(&self) -> Option<DomNodeId> {
+        return self.find_node_in_subtree(self.document_node, &|node| node.name.is_some() && node.name.as_ref().unwrap() == "body");
+    }
+    pub fn find_node_with_id(&self, id: &str) -> Option<DomNodeId> {
+        //TODO: this walks the whole tree, we might want to keep a map from ids to nodes if we start using this more often
+        return self.find_node_in_subtree(self.document_node, &|node| {
+            let node_id = node.get_attribute_value("id");
+            return node_id.is_some() && node_id.unwrap() == id;
+        });
+    }
+    pub fn allows_subresource(&self, url: &Url) -> bool {
+        return subresource_allowed(&self.page_url, url);
+    }
+    pub fn find_fragment_target(&self, fragment: &String) -> Option<DomNodeId> {
+        //the fragment of a url points to the element with that id, or otherwise to an anchor with that name
+        let fragment = String::from_utf8_lossy(&percent_decode(fragment)).into_owned();
+        let node_with_id = self.find_node_with_id(&fragment);
+        if node_with_id.is_some() {
+            return node_with_id;
+        }
+        return self.find_node_in_subtree(self.document_node, &|node| {
+            let node_name = node.get_attribute_value("name");
+            return node.name.is_some() && node.name.as_ref().unwrap() == "a" && node_name.is_some() && node_name.unwrap() == fragment;
+        });
+    }
+    pub fn get_title(&self) -> Option<String> {
+        let possible_title_node = self.find_node_in_subtree(self.document_node, &|node| node.name.is_some() && node.name.as_ref().unwrap() == "title");
+        if possible_title_node.is_none() {
+            return None;
+        }
+        let title_node = &self.nodes[possible_title_node.unwrap()];
+
+        let mut title = String::new();
+        if title_node.children.is_some() {
+            for child_id in title_node.children.as_ref().unwrap() {
+                let child = &self.nodes[*child_id];
+                if child.text.is_some() {
+                    title.push_str(&child.text.as_ref().unwrap().text_content);
+                }
+            }
+        }
 
+        //whitespace in the title is collapsed, and stripped from the start and the end
+        let title = title.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if title.is_empty() {
+            return None;
+        }
+        return Some(title);
+    }
+    pub fn get_favicon_url(&self) -> Option<Url> {
+        let possible_icon_link = self.find_node_in_subtree(self.document_node, &|node| {
+            if node.name.is_none() || node.name.as_ref().unwrap() != "link" || node.get_attribute_value("href").is_none() {
+                return false;
+            }
+            //the rel attribute is a list of keywords, older pages often use "shortcut icon"
+            let rel = node.get_attribute_value("rel");
+            return rel.is_some() && rel.unwrap().split_ascii_whitespace().any(|keyword| keyword.eq_ignore_ascii_case("icon"));
+        });
+
+        if possible_icon_link.is_some() {
+            let href = self.nodes[possible_icon_link.unwrap()].get_attribute_value("href").unwrap();
+            return Some(Url::from_base_url(&href, Some(&self.base_url)));
+        }
 
-#[cfg_attr(debug_assertions, derive(Debug))]
-pub struct ElementDomNode {
-    //TODO: we are already getting many optional fiels here again, so we need something similar as in layout nodes. Probably just enum variants
-    //      check the DOM spec, there is also types and subtypes defined there. Staying close to that will make the JS implementation easier for DOM manipulation
-    //      that might it also make it easier to add methods for specific elements, like submitting a form
+        //without an icon link, browsers try the default location, but that only makes sense for pages coming from a server
+        if self.page_url.scheme == "http" || self.page_url.scheme == "https" {
+            return Some(Url::from_base_url(&String::from("/favicon.ico"), Some(&self.page_url)));
+        }
+        return None;
+    }
+    pub fn find_details_for_summary(&self, start_node_id: DomNodeId) -> Option<DomNodeId> {
+        //this finds the details element of which start_node is (or is inside of) the summary
+        let start_node = &self.nodes[start_node_id];
+        let is_summary = start_node.name.is_some() && start_node.name.as_ref().unwrap() == "summary";
+        let possible_summary_id = if is_summary { Some(start_node_id) } else { self.find_parent_with_name(start_node_id, "summary") };
+        if possible_summary_id.is_none() {
+            return None;
+        }
+        let summary_id = possible_summary_id.unwrap();
 
-    pub internal_id: usize,
-    pub parent_id: usize,
-    pub is_document_node: bool,
+        let possible_details_id = self.nodes[summary_id].parent;
+        if possible_details_id.is_none() || self.nodes[possible_details_id.unwrap()].name.as_deref() != Some("details") {
+            return None;
+        }
+        let details = &self.nodes[possible_details_id.unwrap()];
 
-    pub dirty: bool,
+        //only the first summary in a details element works as its summary, others are just part of the content
+        let first_summary_id = details.children.as_ref().unwrap().iter().find(|child_id| self.nodes[**child_id].name.as_deref() == Some("summary"));
+        if first_summary_id != Some(&summary_id) {
+            return None;
+        }
+        return possible_details_id;
+    }
+    pub fn get_meta_refresh(&self) -> Option<(Duration, Url)> {
+        //this returns after how much time we should navigate, and to where, for a <meta http-equiv="refresh"> element
+        let possible_refresh_node = self.find_node_in_subtree(self.document_node, &|node| {
+            if node.name.is_none() || node.name.as_ref().unwrap() != "meta" || node.get_attribute_value("content").is_none() {
+                return false;
+            }
+            let http_equiv = node.get_attribute_value("http-equiv");
+            return http_equiv.is_some() && http_equiv.unwrap().trim().eq_ignore_ascii_case("refresh");
+        });
+        if possible_refresh_node.is_none() {
+            return None;
+        }
 
-    pub text: Option<DomText>,
-    pub name: Option<String>,
-    pub name_for_layout: TagName,
+        let content = self.nodes[possible_refresh_node.unwrap()].get_attribute_value("content").unwrap();
+        let parsed_content = parse_meta_refresh_content(&content);
+        if parsed_content.is_none() {
+            debug_log_warn(format!("Could not parse the content of a meta refresh: {}", content));
+            return None;
+        }
+        let (delay_seconds, possible_url) = parsed_content.unwrap();
 
-    pub children: Option<Vec<Rc<RefCell<ElementDomNode>>>>,
-    pub attributes: Option<Vec<Rc<RefCell<AttributeDomNode>>>>,
+        //without a url, the page refreshes itself
+        let url = if possible_url.is_some() { Url::from_base_url(&possible_url.unwrap(), Some(&self.base_url)) } else { self.page_url.clone() };
 
-    pub image: Option<Rc<DynamicImage>>,
-    pub img_job_tracker: Option<ResourceRequestJobTracker<DynamicImage>>,
+        //websites can't send the user to the pages of the browser itself (like about:history?clear=1), or to local files, without them clicking
+        if (url.scheme == "about" || url.scheme == "file") && url.scheme != self.page_url.scheme {
+            debug_log_warn(format!("Not following a meta refresh from {} to {}", self.page_url.to_string(), url.to_string()));
+            return None;
+        }
+        return Some((Duration::from_secs(delay_seconds), url));
+    }
+    pub fn find_control_for_label(&self, start_node_id: DomNodeId) -> Option<DomNodeId> {
+        //this finds the control belonging to the label that start_node is (or is inside of)
+        let start_node = &self.nodes[start_node_id];
+        let is_label = start_node.name.is_some() && start_node.name.as_ref().unwrap() == "label";
+        let possible_label_id = if is_label { Some(start_node_id) } else { self.find_parent_with_name(start_node_id, "label") };
+        if possible_label_id.is_none() {
+            return None;
+        }
+        let label_id = possible_label_id.unwrap();
+
+        //a label with a for attribute is for the control with that id, otherwise it is for the first control inside of it
+        let for_id = self.nodes[label_id].get_attribute_value("for");
+        if for_id.is_some() {
+            let possible_control_id = self.find_node_with_id(&for_id.unwrap());
+            if possible_control_id.is_some() && self.nodes[possible_control_id.unwrap()].page_component.is_some() {
+                return possible_control_id;
+            }
+            return None;
+        }
 
-    pub scripts: Option<Vec<Rc<Script>>>,
+        return self.find_node_in_subtree(label_id, &|node| node.id != label_id && node.page_component.is_some());
+    }
+    pub fn find_node_for_component(&self, component: &PageComponent) -> DomNodeId {
+        for (node_id, node) in self.nodes.iter() {
+            if node.page_component.is_some() && node.page_component.as_ref().unwrap().borrow().get_id() == component.get_id() {
+                return node_id;
+            }
+        }
 
-    pub page_component: Option<Rc<RefCell<PageComponent>>>,
-}
-impl ElementDomNode {
-    pub fn get_attribute_value(&self, attribute_name: &str) -> Option<String> {
-        if self.attributes.is_some() {
-            for att in self.attributes.as_ref().unwrap() {
-                if att.borrow().name == attribute_name {
-                    return Some(att.borrow().value.clone());
+        //We panic here, since if we have a component, it should be somewhere in the DOM, otherwise we have a bug
+        panic!("Component not found");
+    }
+    pub fn compute_base_url(&self, page_url: &Url) -> Url {
+        //only the first base element with an href counts, and its href itself is relative to the page url
+        let possible_base_node = self.find_node_in_subtree(self.document_node, &|node| {
+            return node.name.is_some() && node.name.as_ref().unwrap() == "base" && node.get_attribute_value("href").is_some();
+        });
+        if possible_base_node.is_none() {
+            return page_url.clone();
+        }
+
+        let href = self.nodes[possible_base_node.unwrap()].get_attribute_value("href").unwrap();
+        return Url::from_base_url(&href, Some(page_url));
+    }
+    pub fn get_text_content(&self, node_id: DomNodeId) -> String {
+        //this is all the text in the node and the nodes in it, without any markup
+        let node = &self.nodes[node_id];
+        if node.text.is_some() {
+            return node.text.as_ref().unwrap().text_content.clone();
+        }
+
+        let mut text_content = String::new();
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                text_content.push_str(&self.get_text_content(*child_id));
+            }
+        }
+        return text_content;
+    }
+    pub fn get_inner_html(&self, node_id: DomNodeId) -> String {
+        //this serializes the children of the node back to html
+        let node = &self.nodes[node_id];
+        let mut html = String::new();
+        if node.children.is_none() {
+            return html;
+        }
+        let contains_raw_text = node.name.as_deref() == Some("script") || node.name.as_deref() == Some("style");
+
+        for child_id in node.children.as_ref().unwrap() {
+            let child = &self.nodes[*child_id];
+            if child.text.is_some() {
+                let text = &child.text.as_ref().unwrap().text_content;
+                if contains_raw_text {
+                    html.push_str(text);
+                    continue;
                 }
+                html.push_str(&text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace(NON_BREAKING_SPACE, "&nbsp;"));
+                continue;
+            }
+
+            let name = child.name.as_ref().unwrap();
+            html.push('<');
+            html.push_str(name);
+            if child.attributes.is_some() {
+                for attribute in child.attributes.as_ref().unwrap() {
+                    html.push_str(&format!(" {}=\"{}\"", attribute.name, attribute.value.replace('&', "&amp;").replace('"', "&quot;")));
+                }
+            }
+            html.push('>');
+
+            if !VOID_ELEMENTS.contains(&name.as_str()) {
+                html.push_str(&self.get_inner_html(*child_id));
+                html.push_str(&format!("</{}>", name));
             }
         }
-        return None;
+        return html;
     }
-    pub fn post_construct(&mut self, platform: &mut Platform) {
+    pub fn post_construct(&mut self, node_id: DomNodeId, font_context: &FontContext) {
         //here we set things up that don't need to happen every update step, but that we don't want to do during html parsing
 
-        if self.name.is_some() && self.name.as_ref().unwrap() == "input" {
+        if self.nodes[node_id].name.is_some() && self.nodes[node_id].name.as_ref().unwrap() == "svg" {
+            //inline svg is rendered to an image once, and then laid out like an img element
+            let image = svg::render_svg_element(&svg::svg_element_from_dom_node(self, node_id));
+            self.nodes[node_id].image = Some(Arc::new(image));
+            return; //the children are part of the image, so they don't need to be set up
+        }
+
+        let is_text_area = self.nodes[node_id].name.is_some() && self.nodes[node_id].name.as_ref().unwrap() == "textarea";
+        let initial_text = if is_text_area { self.get_text_content(node_id) } else { String::new() }; //the initial value of a textarea is the text inside it
+        let node = &mut self.nodes[node_id];
 
-            let mut input_type = self.get_attribute_value("type");
+        if node.name.is_some() && node.name.as_ref().unwrap() == "input" {
+
+            let mut input_type = node.get_attribute_value("type");
             if input_type.is_none() {
                 input_type = Some(String::from("text"));
             }
-            let mut input_value = self.get_attribute_value("value");
+            let mut input_value = node.get_attribute_value("value");
             if input_value.is_none() {
                 input_value = Some(String::from(""));
             }
 
-            match input_type.unwrap().as_str() {
+            match input_type.as_ref().unwrap().as_str() {
                 "text" => {
+                    node.default_value = input_value.clone();
+
                     //We create the component at (0,0) with size (1,1), the layout pass will update that to the correct positions and sizes
                     let mut text_field = TextField::new(0.0, 0.0, 21.0, 1.0, false);
-                    text_field.set_text(platform, input_value.unwrap());
-                    self.page_component = Some(Rc::from(RefCell::from(PageComponent::TextField(text_field))));
+                    text_field.placeholder = node.get_attribute_value("placeholder").unwrap_or(String::new());
+                    text_field.max_length = node.get_attribute_value("maxlength").and_then(|max_length| max_length.trim().parse::<usize>().ok());
+                    text_field.disabled = node.get_attribute_value("disabled").is_some();
+                    text_field.readonly = node.get_attribute_value("readonly").is_some();
+                    text_field.set_text(font_context, input_value.unwrap());
+                    node.page_component = Some(Rc::from(RefCell::from(PageComponent::TextField(text_field))));
                 },
-                "submit" => {
+                "submit" | "reset" => {
                     if input_value == Some(String::from("")) {
-                        input_value = Some(String::from("Submit"));
+                        input_value = Some(if input_type.as_ref().unwrap() == "submit" { String::from("Submit") } else { String::from("Reset") });
                     }
 
                     //We create the component at (0,0) with size (1,1), the layout pass will update that to the correct positions and sizes
                     let button = Button::new(0.0, 0.0, 1.0, 1.0, input_value.unwrap());
-                    self.page_component = Some(Rc::from(RefCell::from(PageComponent::Button(button))));
+                    node.page_component = Some(Rc::from(RefCell::from(PageComponent::Button(button))));
+                },
+                "file" => {
+                    //We create the component at (0,0) with size (1,1), the layout pass will update that to the correct positions and sizes
+                    let file_input = FileInput::new(0.0, 0.0, 1.0, 1.0);
+                    node.page_component = Some(Rc::from(RefCell::from(PageComponent::FileInput(file_input))));
                 },
                 _ =>  {
                     //Ignoring other values for now
@@ -185,149 +622,551 @@ impl ElementDomNode {
             }
         }
 
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                child.borrow_mut().post_construct(platform);
+        if is_text_area {
+            //invalid values (including 0) fall back to the defaults, like in other browsers
+            let parse_size_attribute = |attribute_name: &str, default: usize| {
+                let value = node.get_attribute_value(attribute_name).and_then(|value| value.trim().parse::<usize>().ok());
+                return if value.is_some() && value.unwrap() > 0 { value.unwrap() } else { default };
+            };
+            let cols = parse_size_attribute("cols", TEXT_AREA_DEFAULT_COLS);
+            let rows = parse_size_attribute("rows", TEXT_AREA_DEFAULT_ROWS);
+
+            node.default_value = Some(initial_text.clone());
+
+            //We create the component at (0,0) with size (1,1), the layout pass will update that to the correct positions and sizes
+            let mut text_area = TextArea::new(0.0, 0.0, 1.0, 1.0, cols, rows);
+            text_area.set_text(font_context, initial_text);
+            node.page_component = Some(Rc::from(RefCell::from(PageComponent::TextArea(text_area))));
+        }
+
+        if node.children.is_some() {
+            for child_id in node.children.clone().unwrap() {
+                self.post_construct(child_id, font_context);
             }
         }
     }
-    fn update(&mut self, resource_thread_pool: &mut ResourceThreadPool, document: &Document) -> bool {
+    fn update_node(&mut self, node_id: DomNodeId, resource_thread_pool: &mut ResourceThreadPool) -> bool {
         //returns whether there are dirty nodes after the update (being itself, or any of the children)
 
         let mut any_child_dirty = false;
 
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                let child_dirty = child.borrow_mut().update(resource_thread_pool, document);
+        if self.nodes[node_id].children.is_some() {
+            for child_id in self.nodes[node_id].children.clone().unwrap() {
+                let child_dirty = self.update_node(child_id, resource_thread_pool);
                 if child_dirty {
                     any_child_dirty = true;
                 }
             }
         }
 
-        if self.image.is_none() && self.name.is_some() && self.name.as_ref().unwrap() == "img" {
-            let image_src = self.get_attribute_value("src");
+        let node = &self.nodes[node_id];
+        if node.image.is_none() && node.name.is_some() && node.name.as_ref().unwrap() == "img" {
+            let image_src = node.get_attribute_value("src");
 
             if image_src.is_some() {
-                if self.img_job_tracker.is_none() {
-                    let image_url = Url::from_base_url(&image_src.unwrap(), Some(&document.base_url));
+                if node.img_job_tracker.is_none() {
+                    let image_url = Url::from_base_url(&image_src.unwrap(), Some(&self.base_url));
+                    if !self.allows_subresource(&image_url) {
+                        let node = &mut self.nodes[node_id];
+                        node.image = Some(resource_loader::fallback_image());
+                        node.dirty = true;
+                        return true;
+                    }
 
-                    self.img_job_tracker = Some(resource_loader::schedule_load_image(&image_url, resource_thread_pool)); //TODO: eventually store the threadpool
-                                                                                                                         //      on a more general context object
+                    self.nodes[node_id].img_job_tracker = Some(resource_loader::schedule_load_image(&image_url, self.cache_mode, resource_thread_pool)); //TODO: eventually store the threadpool
+                                                                                                                                                         //      on a more general context object
 
                 } else {
-                    let try_recv_result = self.img_job_tracker.as_ref().unwrap().receiver.try_recv();
+                    let try_recv_result = node.img_job_tracker.as_ref().unwrap().receiver.try_recv();
                     if try_recv_result.is_ok() {
-                        self.image = Some(Rc::from(try_recv_result.unwrap()));
-                        self.dirty = true;
-                        self.img_job_tracker = None;
+                        let node = &mut self.nodes[node_id];
+                        node.image = Some(try_recv_result.unwrap());
+                        node.dirty = true;
+                        node.img_job_tracker = None;
                     }
 
                 }
 
             } else {
-                self.image = Some(Rc::from(resource_loader::fallback_image()));
-                self.dirty = true;
+                let node = &mut self.nodes[node_id];
+                node.image = Some(resource_loader::fallback_image());
+                node.dirty = true;
             }
         }
 
-        return any_child_dirty || self.dirty;
+        return any_child_dirty || self.nodes[node_id].dirty;
     }
 
-    pub fn click(&self, document: &Document) -> NavigationAction {
+    pub fn click(&mut self, node_id: DomNodeId, font_context: &FontContext) -> NavigationAction {
+        let node = &self.nodes[node_id];
 
-        if self.page_component.is_some() {
-            self.page_component.as_ref().unwrap().borrow_mut().click();
+        if node.page_component.is_some() {
+            node.page_component.as_ref().unwrap().borrow_mut().click();
         }
 
-        let possible_link_parent = document.find_parent_with_name(self, "a");
+        let possible_link_parent = self.find_parent_with_name(node_id, "a");
 
         if possible_link_parent.is_some() {
-            let link_parent = possible_link_parent.unwrap();
-            let opt_href = link_parent.borrow().get_attribute_value("href");
+            let opt_href = self.nodes[possible_link_parent.unwrap()].get_attribute_value("href");
             if opt_href.is_some() {
-                return NavigationAction::Get(Url::from_base_url(&opt_href.unwrap(), Some(&document.base_url)));
+                return NavigationAction::Get(Url::from_base_url(&opt_href.unwrap(), Some(&self.base_url)));
             }
         }
 
-        if self.name.is_some() {
-            let name = self.name.as_ref().unwrap();
+        if node.name.is_some() {
+            let name = node.name.as_ref().unwrap();
 
             if name.as_str() == "input" {
-                let input_type = self.get_attribute_value("type");
-                if input_type.is_some() && input_type.unwrap().as_str() == "submit" {
-                    return self.submit_form(document);
+                let input_type = node.get_attribute_value("type");
+                if input_type.is_some() && input_type.as_ref().unwrap().as_str() == "submit" {
+                    return self.submit_form(node_id);
+                }
+                if input_type.is_some() && input_type.as_ref().unwrap().as_str() == "reset" {
+                    self.reset_form(node_id, font_context);
+                    return NavigationAction::None;
                 }
             }
         }
 
+        //clicking the summary of a details element opens or closes it, which is reflected in the open attribute, as it is for scripts
+        let possible_details = self.find_details_for_summary(node_id);
+        if possible_details.is_some() {
+            let details = &mut self.nodes[possible_details.unwrap()];
+            let is_open = details.get_attribute_value("open").is_some();
+            if is_open {
+                details.remove_attribute("open");
+            } else {
+                details.set_attribute("open", "");
+            }
+            return NavigationAction::None;
+        }
+
+        //clicking a label activates its control, focussing text fields is done when handling the mouse down in the UI
+        //TODO: labels should also toggle checkboxes, once we support those
+        if self.nodes[node_id].page_component.is_none() {
+            let possible_control = self.find_control_for_label(node_id);
+            if possible_control.is_some() {
+                let control_id = possible_control.unwrap();
+                let control_is_activatable = match self.nodes[control_id].page_component.as_ref().unwrap().borrow().deref() {
+                    PageComponent::Button(_) | PageComponent::FileInput(_) => true,
+                    PageComponent::TextField(_) | PageComponent::TextArea(_) => false,
+                };
+                if control_is_activatable {
+                    return self.click(control_id, font_context);
+                }
+                return NavigationAction::None;
+            }
+        }
+
+        //clicks on the content of a button are clicks on the button itself
+        let node = &self.nodes[node_id];
+        if node.name.is_some() && node.name.as_ref().unwrap() == "button" {
+            return self.click_button(node_id, font_context);
+        }
+        let possible_button_parent = self.find_parent_with_name(node_id, "button");
+        if possible_button_parent.is_some() {
+            return self.click_button(possible_button_parent.unwrap(), font_context);
+        }
+
         return NavigationAction::None;
     }
 
-    pub fn submit_form(&self, document: &Document) -> NavigationAction {
-        let possible_form_parent = document.find_parent_with_name(self, "form");
+    fn click_button(&self, button_id: DomNodeId, font_context: &FontContext) -> NavigationAction {
+        let button = &self.nodes[button_id];
+        if button.get_attribute_value("disabled").is_some() {
+            return NavigationAction::None;
+        }
+
+        let button_type = button.get_attribute_value("type").map(|button_type| button_type.trim().to_ascii_lowercase());
+        match button_type.as_deref() {
+            Some("reset") => {
+                self.reset_form(button_id, font_context);
+                return NavigationAction::None;
+            },
+            Some("button") => {
+                //TODO: these buttons only do something via event handlers, which we don't support yet
+                return NavigationAction::None;
+            },
+            _ => {
+                //submit is the default, also for missing or invalid types
+                return self.submit_form(button_id);
+            },
+        }
+    }
+
+    pub fn reset_form(&self, node_id: DomNodeId, font_context: &FontContext) {
+        let possible_form_parent = self.find_parent_with_name(node_id, "form");
+        if possible_form_parent.is_some() {
+            self.reset_all_inputs(possible_form_parent.unwrap(), font_context);
+        }
+    }
+
+    fn reset_all_inputs(&self, node_id: DomNodeId, font_context: &FontContext) {
+        let node = &self.nodes[node_id];
+        if node.page_component.is_some() {
+            let default_value = node.default_value.clone().unwrap_or(String::new());
+
+            match node.page_component.as_ref().unwrap().borrow_mut().deref_mut() {
+                PageComponent::Button(_) => {},
+                PageComponent::TextField(text_field) => {
+                    text_field.set_text(font_context, default_value);
+                },
+                PageComponent::TextArea(text_area) => {
+                    text_area.set_text(font_context, default_value);
+                },
+                PageComponent::FileInput(file_input) => {
+                    file_input.selected_file = None;
+                },
+            }
+        }
+
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                self.reset_all_inputs(*child_id, font_context);
+            }
+        }
+    }
+
+    pub fn submit_form(&self, node_id: DomNodeId) -> NavigationAction {
+        let possible_form_parent = self.find_parent_with_name(node_id, "form");
         if possible_form_parent.is_some() {
+            let form_id = possible_form_parent.unwrap();
+            let form = &self.nodes[form_id];
+
+            //like in the spec, get is the default for a missing or invalid method
+            let method = form.get_attribute_value("method");
+            let is_post = method.is_some() && method.unwrap().trim().eq_ignore_ascii_case("post");
+
+            //multipart encoding only exists for the body of a post
+            let enctype = form.get_attribute_value("enctype");
+            let multipart = is_post && enctype.is_some() && enctype.unwrap().trim().eq_ignore_ascii_case("multipart/form-data");
 
             let mut all_fields = HashMap::new();
-            possible_form_parent.as_ref().unwrap().borrow().collect_all_inputs(&mut all_fields);
+            let mut all_files = HashMap::new();
+            self.collect_all_inputs(form_id, &mut all_fields, &mut all_files);
+
+            if !multipart {
+                //without multipart encoding, only the names of the selected files are sent
+                for (name, path) in all_files.drain() {
+                    let file_name = path.and_then(|path| path.file_name().map(|file_name| file_name.to_string_lossy().to_string()));
+                    all_fields.insert(name, file_name.unwrap_or(String::new()));
+                }
+            }
+
+            let action_url_text = form.get_attribute_value("action");
+            if action_url_text.is_some() {
+                let mut action_url = Url::from_base_url(&action_url_text.unwrap(), Some(&self.base_url));
+
+                if !is_post {
+                    //for get, the fields replace the query of the action url
+                    action_url.query = encode_form_urlencoded(&all_fields);
+                    return NavigationAction::SubmitForm(form_id, Box::new(NavigationAction::Get(action_url)));
+                }
 
-            let post_url_text = possible_form_parent.unwrap().borrow().get_attribute_value("action");
-            if post_url_text.is_some() {
                 let postdata = PostData {
-                    url: Url::from_base_url(&post_url_text.unwrap(), Some(&document.base_url)),
+                    url: action_url,
                     fields: all_fields,
+                    files: all_files,
+                    multipart,
                 };
 
-                return NavigationAction::Post(postdata);
+                return NavigationAction::SubmitForm(form_id, Box::new(NavigationAction::Post(postdata)));
             }
         }
         return NavigationAction::None;
     }
 
-    fn collect_all_inputs(&self, fields: &mut HashMap<String, String>) {
+    fn collect_all_inputs(&self, node_id: DomNodeId, fields: &mut HashMap<String, String>, files: &mut HashMap<String, Option<PathBuf>>) {
+        let node = &self.nodes[node_id];
 
-        if self.name.is_some() && self.name.as_ref().unwrap().as_str() == "input" && self.page_component.is_some() {
+        let is_form_field = node.name.is_some() && (node.name.as_ref().unwrap().as_str() == "input" || node.name.as_ref().unwrap().as_str() == "textarea");
+        if is_form_field && node.page_component.is_some() {
 
-            let input_name = self.get_attribute_value("name");
-            if input_name.is_some() { //According to spec, elements without name should not be sent
+            let input_name = node.get_attribute_value("name");
+            let is_disabled = node.get_attribute_value("disabled").is_some();
+            if input_name.is_some() && !is_disabled { //According to spec, elements without name and disabled elements should not be sent
 
-                let component = self.page_component.as_ref().unwrap().borrow();
-                let input_value = match component.deref() {
+                let component = node.page_component.as_ref().unwrap().borrow();
+                match component.deref() {
                     PageComponent::Button(_) => {
                         //TODO: should a non-pressed button also have its value sent? (the key should be sent in any case, but maybe with empty value)
-                        String::new()
+                        fields.insert(input_name.unwrap(), String::new());
                     },
                     PageComponent::TextField(text_field) => {
-                        text_field.text.clone()
+                        fields.insert(input_name.unwrap(), text_field.text.clone());
                     },
-                };
+                    PageComponent::TextArea(text_area) => {
+                        //line breaks in a textarea are always submitted as CRLF
+                        fields.insert(input_name.unwrap(), text_area.text.replace("\r\n", "\n").replace('\n', "\r\n"));
+                    },
+                    PageComponent::FileInput(file_input) => {
+                        files.insert(input_name.unwrap(), file_input.selected_file.clone());
+                    },
+                }
+            }
+        }
 
-                fields.insert(input_name.unwrap(), input_value);
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                self.collect_all_inputs(*child_id, fields, files);
             }
         }
+    }
+}
+
+
+fn parse_meta_refresh_content(content: &String) -> Option<(u64, Option<String>)> {
+    //the content looks like "5; url=http://example.com", where the url part is optional
+    let content = content.trim_start();
+
+    //the delay is a number, of which we ignore the fractional part, like other browsers do
+    let delay_end_idx = content.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(content.len());
+    if delay_end_idx == 0 {
+        return None;
+    }
+    let delay_text = content[..delay_end_idx].split('.').next().unwrap();
+    let delay_seconds = if delay_text.is_empty() { 0 } else { delay_text.parse::<u64>().ok()? };
+
+    let rest = &content[delay_end_idx..];
+    if rest.trim().is_empty() {
+        return Some((delay_seconds, None));
+    }
+    if !rest.starts_with(|c: char| c == ';' || c == ',' || c.is_whitespace()) {
+        return None;
+    }
+
+    let mut url_text = rest.trim_start_matches(|c: char| c == ';' || c == ',' || c.is_whitespace());
+    if url_text.len() >= 3 && url_text[..3].eq_ignore_ascii_case("url") {
+        let after_url_keyword = url_text[3..].trim_start();
+        if after_url_keyword.starts_with('=') {
+            url_text = after_url_keyword[1..].trim_start();
+        }
+    }
+
+    if url_text.starts_with('"') || url_text.starts_with('\'') {
+        let quote = url_text.chars().next().unwrap();
+        url_text = &url_text[1..];
+        if url_text.contains(quote) {
+            url_text = &url_text[..url_text.find(quote).unwrap()];
+        }
+    }
+
+    let url_text = url_text.trim();
+    if url_text.is_empty() {
+        return Some((delay_seconds, None));
+    }
+    return Some((delay_seconds, Some(url_text.to_owned())));
+}
+
+
+fn subresource_allowed(page_url: &Url, url: &Url) -> bool {
+    if is_mixed_content(page_url, url) {
+        js_console::log_warning(format!("Mixed content: the page at '{}' was loaded over https, but requested the insecure resource '{}'. \
+                                         This request has been blocked.", page_url.to_string(), url.to_string()).as_str());
+        return false;
+    }
+    if is_cross_scheme_file_access(page_url, url) {
+        js_console::log_warning(format!("Not allowed to load local resource '{}' from the page at '{}'.", url.to_string(), page_url.to_string()).as_str());
+        return false;
+    }
+    return true;
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct PendingStyleImport {
+    pub url: Url,
+    pub slot: StyleSheetSlot, //where the imported sheet goes in the style context, which is reserved so its rules end up in the right order
+    pub job_tracker: Option<ResourceRequestJobTracker<LoadedResource>>,
+    pub done: bool,
+}
+impl PendingStyleImport {
+    pub fn new(style_import: &StyleImport, base_url: &Url, slot: StyleSheetSlot) -> PendingStyleImport {
+        //the conditions of the @import are applied when the sheet goes into its slot, so we only need the url here
+        let url = Url::from_base_url(&style_import.url, Some(base_url));
+        return PendingStyleImport { url, slot, job_tracker: None, done: false };
+    }
+
+    pub fn depth(&self) -> usize {
+        //how many @imports deep this import is, so we don't loop forever on sheets importing each other
+        return self.slot.len() - 2;
+    }
+}
+
 
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                child.borrow().collect_all_inputs(fields);
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum DocumentReadyState {
+    Loading, //the document is being parsed, and its scripts are running
+    Interactive, //the document is parsed and its scripts have run, but images and styles might still be loading
+    Complete,
+}
+impl DocumentReadyState {
+    pub fn name(&self) -> &'static str {
+        //this is the name scripts see in document.readyState
+        return match self {
+            DocumentReadyState::Loading => "loading",
+            DocumentReadyState::Interactive => "interactive",
+            DocumentReadyState::Complete => "complete",
+        };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum TagName {
+    B,
+    Br,
+    Button,
+    Details,
+    Head,
+    Iframe,
+    Img,
+    Input,
+    Script,
+    Style,
+    Summary,
+    Svg,
+    Table,
+    Textarea,
+    Title,
+
+    Other,
+}
+impl TagName {
+    pub fn from_string(tag_being_parsed: &String) -> TagName {
+        //tag names are case insensitive, the lexer already lowercases them, but we don't want to depend on that here
+        return match tag_being_parsed.to_ascii_lowercase().as_str() {
+
+            "b" => TagName::B,
+            "br" => TagName::Br,
+            "button" => TagName::Button,
+            "details" => TagName::Details,
+            "head" => TagName::Head,
+            "iframe" => TagName::Iframe,
+            "img" => TagName::Img,
+            "input" => TagName::Input,
+            "script" => TagName::Script,
+            "style" => TagName::Style,
+            "summary" => TagName::Summary,
+            "svg" => TagName::Svg,
+            "table" => TagName::Table,
+            "textarea" => TagName::Textarea,
+            "title" => TagName::Title,
+
+            _ => {
+                //this is not an error, since we only translate tags that we need to do something for in the layout tree
+                TagName::Other
             }
         }
     }
+}
+
+
+#[derive(PartialEq)]
+pub enum NavigationAction {
+    None,
+    Get(Url),
+    Post(PostData),
+    SubmitForm(DomNodeId, Box<NavigationAction>), //submitting the form with this id, which scripts can still cancel in its submit event
+}
+
 
-    pub fn new_empty() -> ElementDomNode {
+//TODO: this should be moved to a network related module (just a seperate thing in network?)
+#[derive(PartialEq)]
+pub struct PostData {
+    pub url: Url,
+    pub fields: HashMap<String, String>,
+    pub files: HashMap<String, Option<PathBuf>>, //these are only sent as files for multipart forms, otherwise just the filename is in fields
+    pub multipart: bool,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ElementDomNode {
+    //TODO: we are already getting many optional fiels here again, so we need something similar as in layout nodes. Probably just enum variants
+    //      check the DOM spec, there is also types and subtypes defined there. Staying close to that will make the JS implementation easier for DOM manipulation
+    //      that might it also make it easier to add methods for specific elements, like submitting a form
+
+    pub id: DomNodeId,
+    pub parent: Option<DomNodeId>,
+    pub is_document_node: bool,
+
+    pub dirty: bool,
+
+    pub text: Option<DomText>,
+    pub name: Option<String>,
+    pub name_for_layout: TagName,
+
+    pub children: Option<Vec<DomNodeId>>,
+    pub attributes: Option<Vec<AttributeDomNode>>,
+
+    pub image: Option<Arc<DynamicImage>>,
+    pub img_job_tracker: Option<ResourceRequestJobTracker<Arc<DynamicImage>>>,
+
+    pub scripts: Option<Vec<Rc<Script>>>,
+
+    pub page_component: Option<Rc<RefCell<PageComponent>>>,
+    pub default_value: Option<String>, //the value a form field gets back when its form is reset
+
+    pub cached_styles: Option<CachedStyles>, //this is kept up to date by style::compute_styles, when building the layout
+}
+impl ElementDomNode {
+    pub fn get_attribute_value(&self, attribute_name: &str) -> Option<String> {
+        if self.attributes.is_some() {
+            for att in self.attributes.as_ref().unwrap() {
+                if att.name == attribute_name {
+                    return Some(att.value.clone());
+                }
+            }
+        }
+        return None;
+    }
+    pub fn set_attribute(&mut self, attribute_name: &str, value: &str) {
+        //attributes can change the styles and layout of the node, so we always mark it dirty
+        self.dirty = true;
+        if self.cached_styles.is_some() {
+            self.cached_styles.as_mut().unwrap().mark_outdated();
+        }
+
+        if self.attributes.is_none() {
+            self.attributes = Some(Vec::new());
+        }
+        for attribute in self.attributes.as_mut().unwrap() {
+            if attribute.name == attribute_name {
+                attribute.value = value.to_owned();
+                return;
+            }
+        }
+        let new_attribute = AttributeDomNode { name: attribute_name.to_owned(), value: value.to_owned(), parent_id: self.id };
+        self.attributes.as_mut().unwrap().push(new_attribute);
+    }
+    pub fn remove_attribute(&mut self, attribute_name: &str) {
+        self.dirty = true;
+        if self.cached_styles.is_some() {
+            self.cached_styles.as_mut().unwrap().mark_outdated();
+        }
+        if self.attributes.is_some() {
+            self.attributes.as_mut().unwrap().retain(|attribute| attribute.name != attribute_name);
+        }
+    }
+    pub fn new_document_node(id: DomNodeId) -> ElementDomNode {
         return ElementDomNode {
-            internal_id: 0,
-            parent_id: 0,
+            id,
+            parent: None,
             is_document_node: true,
             dirty: false,
             text: None,
             name: None,
             name_for_layout: TagName::Other,
-            children: None,
+            children: Some(Vec::new()),
             attributes: None,
             image: None,
             img_job_tracker: None,
             scripts: None,
             page_component: None,
+            default_value: None,
+            cached_styles: None,
         };
     }
 }
@@ -337,7 +1176,7 @@ impl ElementDomNode {
 pub struct AttributeDomNode {
     pub name: String,
     pub value: String,
-    #[allow(dead_code)] pub parent_id: usize,  //TODO: if we really don't use this, we might want to remove it and make attributes an HashMap<String, String>
+    #[allow(dead_code)] pub parent_id: DomNodeId,  //TODO: if we really don't use this, we might want to remove it and make attributes an HashMap<String, String>
 }
 
 
@@ -346,18 +1185,3 @@ pub struct DomText {
     pub text_content: String,
     pub non_breaking_space_positions: Option<HashSet<usize>>,
 }
-
-
-pub fn find_dom_node_for_component(component: &PageComponent, document: &Document) -> Rc<RefCell<ElementDomNode>> {
-
-    for node in document.all_nodes.values() {
-        if node.borrow().page_component.is_some() {
-            if node.borrow().page_component.as_ref().unwrap().borrow().get_id() == component.get_id() {
-                return node.clone();
-            }
-        }
-    }
-
-    //We panic here, since if we have a component, it should be somewhere in the DOM, otherwise we have a bug
-    panic!("Component not found");
-}
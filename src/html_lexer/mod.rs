@@ -2,11 +2,23 @@ use std::iter::Peekable;
 use std::str::Chars;
 
 use crate::debug::debug_log_warn;
+use crate::html_lexer::character_references::{
+    MAX_NAMED_CHARACTER_REFERENCE_LENGTH,
+    NAMED_CHARACTER_REFERENCES,
+};
 
+mod character_references;
 #[cfg(test)] mod tests;
 
 
-const DOCTYPE_CHARS: [char; 7] = ['d', 'o', 'c', 't', 'y', 'p', 'e'];
+//numeric character references in the C1 control range are interpreted as windows-1252, like browsers have always done
+const WINDOWS_1252_REPLACEMENTS: [(u32, char); 27] = [
+    (0x80, '\u{20AC}'), (0x82, '\u{201A}'), (0x83, '\u{0192}'), (0x84, '\u{201E}'), (0x85, '\u{2026}'), (0x86, '\u{2020}'),
+    (0x87, '\u{2021}'), (0x88, '\u{02C6}'), (0x89, '\u{2030}'), (0x8A, '\u{0160}'), (0x8B, '\u{2039}'), (0x8C, '\u{0152}'),
+    (0x8E, '\u{017D}'), (0x91, '\u{2018}'), (0x92, '\u{2019}'), (0x93, '\u{201C}'), (0x94, '\u{201D}'), (0x95, '\u{2022}'),
+    (0x96, '\u{2013}'), (0x97, '\u{2014}'), (0x98, '\u{02DC}'), (0x99, '\u{2122}'), (0x9A, '\u{0161}'), (0x9B, '\u{203A}'),
+    (0x9C, '\u{0153}'), (0x9E, '\u{017E}'), (0x9F, '\u{0178}'),
+];
 
 
 #[cfg_attr(debug_assertions, derive(Debug))]
@@ -29,7 +41,7 @@ pub enum HtmlToken {
     Whitespace(String),
     Comment(String),
     Doctype(String),
-    Entity(String),
+    Entity(String), //this contains the text the character reference resolves to
     Style(String),
     Script(String),
 }
@@ -43,6 +55,22 @@ pub struct AttributeContent {
 }
 
 
+#[derive(Clone, Copy, PartialEq)]
+enum TextContentType {
+    RawText,          //for example style, the content is just text
+    EscapableRawText, //title and textarea, the content is text, but character references are resolved
+    ScriptData,       //like raw text, but with special handling of "<!--"
+}
+
+
+#[derive(Clone, Copy, PartialEq)]
+enum ScriptEscapeState {
+    NotEscaped,
+    Escaped,
+    DoubleEscaped,
+}
+
+
 //TODO: move this iterator to a parsing.rs or something, since it is also used for javascript
 pub struct TrackingIterator<'document> {
     pub iter: Peekable<Chars<'document>>,
@@ -69,31 +97,42 @@ impl TrackingIterator<'_> {
         return self.iter.peek().is_some();
     }
 
-    pub fn next_tokens_no_whitespace_are(&mut self, text_to_check: &str) -> bool {
-        let mut temp_html_iterator = self.iter.clone();
-        let mut expected_iterator = text_to_check.chars();
+    pub fn next_chars_are(&self, text_to_check: &str, ignore_case: bool) -> bool {
+        let mut lookahead_iterator = self.iter.clone();
 
-        loop {
-            let mut next_expected = expected_iterator.next();
-            while next_expected.is_some() && next_expected.unwrap() == ' ' {
-                next_expected = expected_iterator.next();
-            }
-            if next_expected.is_none() {
-                return true;
-            }
-
-            let mut next_in_html = temp_html_iterator.next();
-            while next_in_html.is_some() && next_in_html.unwrap() == ' ' {
-                next_in_html = temp_html_iterator.next();
+        for expected_char in text_to_check.chars() {
+            let next_char = lookahead_iterator.next();
+            if next_char.is_none() {
+                return false;
             }
-            if next_in_html.is_none() {
+            let chars_match = if ignore_case { next_char.unwrap().eq_ignore_ascii_case(&expected_char) } else { next_char.unwrap() == expected_char };
+            if !chars_match {
                 return false;
             }
+        }
+        return true;
+    }
 
-            if next_in_html.unwrap() != next_expected.unwrap() {
-                return false;
+    pub fn next_chars_are_tag(&self, tag_name: &str, is_end_tag: bool) -> bool {
+        //checks for "<name" or "</name", followed by something that ends the name (so "</scripts" is not a script end tag)
+        let tag_start = if is_end_tag { "</" } else { "<" };
+        if !self.next_chars_are(&format!("{}{}", tag_start, tag_name), true) {
+            return false;
+        }
+
+        let char_after_name = self.iter.clone().nth(tag_start.len() + tag_name.chars().count());
+        return char_after_name.is_some() && (is_whitespace(char_after_name.unwrap()) || char_after_name.unwrap() == '/' || char_after_name.unwrap() == '>');
+    }
+
+    fn consume_chars(&mut self, number_of_chars: usize) -> String {
+        let mut consumed = String::new();
+        for _ in 0..number_of_chars {
+            if !self.has_next() {
+                break;
             }
+            consumed.push(self.next());
         }
+        return consumed;
     }
 }
 
@@ -121,7 +160,7 @@ pub fn lex_html(document: &str) -> Vec<HtmlTokenWithLocation> {
                     html_iterator.next();
                     eat_whitespace(&mut html_iterator);
 
-                    let tag_name = consume_full_name(&mut html_iterator);
+                    let tag_name = consume_full_name(&mut html_iterator).to_ascii_lowercase(); //tag names are case insensitive
                     eat_whitespace(&mut html_iterator);
 
                     if let Some('>') = html_iterator.peek() {
@@ -136,46 +175,34 @@ pub fn lex_html(document: &str) -> Vec<HtmlTokenWithLocation> {
                 } else if let Some('!') = html_iterator.peek() {  //we are reading a comment or doctype
                     html_iterator.next(); //eat the !
 
-                    if let Some('-') = html_iterator.peek() {
-                        html_iterator.next(); //eat the -
-                        if let Some('-') = html_iterator.peek() { //we are reading a comment
-                            html_iterator.next(); //eat the -
+                    if html_iterator.next_chars_are("--", false) {
+                        html_iterator.consume_chars(2);
+                        let comment_content = lex_comment(&mut html_iterator);
+                        tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Comment(comment_content), line: line_nr, character: char_nr } );
 
-                            let comment_content = lex_comment(&mut html_iterator);
-                            tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Comment(comment_content), line: line_nr, character: char_nr } );
-                        } else {
-                            debug_log_warn(format!("Unexpected chars after <! ({}:{})", line_nr, char_nr));
-                        }
-                    } else {
-                        let mut is_doctype = true;
-
-                        for current_char in DOCTYPE_CHARS {
-                            if html_iterator.has_next() {
-                                if (*html_iterator.peek().unwrap()).to_ascii_lowercase() == current_char {
-                                    html_iterator.next(); //TODO: ideally we don't consume from the iterator until we are sure all the chars match
-                                } else {
-                                    is_doctype = false;
-                                    break;
-                                }
-                            } else {
-                                is_doctype = false;
-                                break;
-                            }
-                        }
+                    } else if html_iterator.next_chars_are("doctype", true) {
+                        html_iterator.consume_chars(7);
+                        let rest_of_tag_content = consume_until_char(&mut html_iterator, '>');
+                        tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Doctype(rest_of_tag_content), line: line_nr, character: char_nr } );
 
-                        if is_doctype {
-                            let rest_of_tag_content = consume_until_char(&mut html_iterator, '>');
-                            tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Doctype(rest_of_tag_content), line: line_nr, character: char_nr } );
-                        } else {
-                            todo!(); //TODO: implement
-                        }
+                    } else if html_iterator.next_chars_are("[CDATA[", false) {
+                        //TODO: CDATA sections are text in svg and mathml, but we don't support those yet. In html they are comments.
+                        html_iterator.consume_chars(7);
+                        let cdata_content = consume_until_text(&mut html_iterator, "]]>");
+                        tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Comment(format!("[CDATA[{}]]", cdata_content)),
+                                                            line: line_nr, character: char_nr } );
 
+                    } else {
+                        //anything else is a "bogus comment" according to the spec, which ends at the first >
+                        debug_log_warn(format!("Unexpected chars after <! ({}:{})", line_nr, char_nr));
+                        let comment_content = consume_until_char(&mut html_iterator, '>');
+                        tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Comment(comment_content), line: line_nr, character: char_nr } );
                     }
 
                 } else { //we are reading an opening tag
                     eat_whitespace(&mut html_iterator);
 
-                    let tag_name = consume_full_name(&mut html_iterator);
+                    let tag_name = consume_full_name(&mut html_iterator).to_ascii_lowercase(); //tag names are case insensitive
                     eat_whitespace(&mut html_iterator);
 
                     tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::OpenTag {name: tag_name.clone()}, line: line_nr, character: char_nr } );
@@ -210,37 +237,11 @@ pub fn lex_html(document: &str) -> Vec<HtmlTokenWithLocation> {
                                                             line: html_iterator.current_line,
                                                             character: html_iterator.current_char } );
 
-                        if tag_name == "style" { //TODO: do we already lower() the tag names?
-
-                            let mut css_data = String::new();
-                            while html_iterator.has_next() && !html_iterator.next_tokens_no_whitespace_are("</style>") {
-                                css_data.push(html_iterator.next());
-                            }
-
-                            tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Style(css_data),
-                                                                line: html_iterator.current_line, //TODO: line (and char below are wrong, should be start positions...)
-                                                                character: html_iterator.current_char });
+                        let text_content_type = get_text_content_type(&tag_name);
+                        if text_content_type.is_some() {
+                            lex_text_content_of_element(&mut html_iterator, &tag_name, text_content_type.unwrap(), &mut tokens);
                         }
 
-                        if tag_name == "script" { //TODO: do we already lower() the tag names?
-
-                            let mut in_quotes = false;
-                            let mut script_data = String::new();
-                            while html_iterator.has_next() && !(!in_quotes && html_iterator.next_tokens_no_whitespace_are("</script>")) {
-
-                                let next_char = html_iterator.next();
-                                if next_char == '"' {
-                                    in_quotes = !in_quotes;
-                                }
-                                script_data.push(next_char);
-                            }
-
-                            tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Script(script_data),
-                                                                line: html_iterator.current_line, //TODO: line (and char below are wrong, should be start positions...)
-                                                                character: html_iterator.current_char });
-                        }
-
-
                     } else {
                         //Given the while loop above, this should not be reachable
                         panic!("Illegal state");
@@ -249,8 +250,13 @@ pub fn lex_html(document: &str) -> Vec<HtmlTokenWithLocation> {
                 }
             },
             '&' => {
-                let entity_data = consume_until_char(&mut html_iterator, ';');
-                tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Entity(entity_data), line: line_nr, character: char_nr } );
+                let character_reference = consume_character_reference(&mut html_iterator, false);
+                if character_reference.is_some() {
+                    tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Entity(character_reference.unwrap()), line: line_nr, character: char_nr } );
+                } else {
+                    //this is not a character reference, so the & is just text
+                    tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::Text(String::from("&")), line: line_nr, character: char_nr } );
+                }
             },
             ' ' | '\n' | '\t' | '\r' => {
                 let mut str_buffer = String::new();
@@ -284,9 +290,9 @@ pub fn lex_html(document: &str) -> Vec<HtmlTokenWithLocation> {
 
 
 fn consume_tag_attribute(html_iterator: &mut TrackingIterator) -> HtmlToken {
-    let attribute_name = consume_full_name(html_iterator);
+    let attribute_name = consume_full_name(html_iterator).to_ascii_lowercase(); //attribute names are case insensitive, like tag names
 
-    let mut attribute_value: String;
+    let attribute_value: String;
     eat_whitespace(html_iterator);
     if let Some('=') = html_iterator.peek() {
         html_iterator.next();
@@ -294,25 +300,17 @@ fn consume_tag_attribute(html_iterator: &mut TrackingIterator) -> HtmlToken {
 
         if let Some('"') = html_iterator.peek() {
             html_iterator.next(); //eat the quote
-
-            attribute_value = String::new();
-            while html_iterator.has_next() && html_iterator.peek().unwrap() != &'"' {
-                attribute_value.push(html_iterator.next());
-            }
+            attribute_value = consume_attribute_value(html_iterator, |c| c == '"');
             html_iterator.next(); //eat the quote
 
         } else if let Some('\'') = html_iterator.peek() {
             html_iterator.next(); //eat the quote
-
-            attribute_value = String::new();
-            while html_iterator.has_next() && html_iterator.peek().unwrap() != &'\'' {
-                attribute_value.push(html_iterator.next());
-            }
+            attribute_value = consume_attribute_value(html_iterator, |c| c == '\'');
             html_iterator.next(); //eat the quote
 
         } else {
             //no quotes in the attributes value, so we read until next whitespace or other special char
-            attribute_value = consume_full_name(html_iterator);
+            attribute_value = consume_attribute_value(html_iterator, |c| is_whitespace(c) || c == '=' || c == '<' || c == '>');
         }
     } else {
         //this is the case where the attribute does not have "="
@@ -323,6 +321,218 @@ fn consume_tag_attribute(html_iterator: &mut TrackingIterator) -> HtmlToken {
 }
 
 
+fn consume_attribute_value<F: Fn(char) -> bool>(html_iterator: &mut TrackingIterator, is_end_char: F) -> String {
+    let mut attribute_value = String::new();
+
+    while html_iterator.has_next() && !is_end_char(*html_iterator.peek().unwrap()) {
+        let next_char = html_iterator.next();
+        if next_char == '&' {
+            let character_reference = consume_character_reference(html_iterator, true);
+            attribute_value.push_str(character_reference.as_deref().unwrap_or("&"));
+        } else {
+            attribute_value.push(next_char);
+        }
+    }
+
+    return attribute_value;
+}
+
+
+fn consume_character_reference(html_iterator: &mut TrackingIterator, in_attribute: bool) -> Option<String> {
+    //This is called after the "&" is consumed. It returns None (and consumes nothing) when the text is not a character reference.
+    if let Some('#') = html_iterator.peek() {
+        return consume_numeric_character_reference(html_iterator);
+    }
+
+    //We look ahead for the longest name in the table that matches, which means that for example "&notin;" is a single reference,
+    //while "&notit;" is the legacy reference "&not" followed by "it;"
+    let mut candidate = String::new();
+    let mut lookahead_iterator = html_iterator.iter.clone();
+    while candidate.len() < MAX_NAMED_CHARACTER_REFERENCE_LENGTH {
+        let next_char = lookahead_iterator.peek();
+        if next_char.is_none() || !(next_char.unwrap().is_ascii_alphanumeric() || *next_char.unwrap() == ';') {
+            break;
+        }
+        let next_char = lookahead_iterator.next().unwrap();
+        candidate.push(next_char);
+        if next_char == ';' {
+            break;
+        }
+    }
+    let char_after_candidate = lookahead_iterator.peek().copied();
+
+    for length in (1..=candidate.len()).rev() {
+        let name = &candidate[..length];
+        let value = lookup_named_character_reference(name);
+        if value.is_none() {
+            continue;
+        }
+
+        if in_attribute && !name.ends_with(';') {
+            //for historical reasons, things like "?a=1&copy=2" in attributes are not seen as references when the ";" is missing
+            let char_after_name = if length < candidate.len() { candidate[length..].chars().next() } else { char_after_candidate };
+            if char_after_name.is_some() && (char_after_name.unwrap() == '=' || char_after_name.unwrap().is_ascii_alphanumeric()) {
+                return None;
+            }
+        }
+
+        for _ in 0..length {
+            html_iterator.next();
+        }
+        return Some(value.unwrap().to_owned());
+    }
+
+    return None;
+}
+
+
+fn consume_numeric_character_reference(html_iterator: &mut TrackingIterator) -> Option<String> {
+    let mut lookahead_iterator = html_iterator.iter.clone();
+    lookahead_iterator.next(); //the #
+
+    let is_hex = lookahead_iterator.peek().is_some() && (*lookahead_iterator.peek().unwrap() == 'x' || *lookahead_iterator.peek().unwrap() == 'X');
+    let radix = if is_hex { 16 } else { 10 };
+    if is_hex {
+        lookahead_iterator.next();
+    }
+
+    let mut digits = String::new();
+    while lookahead_iterator.peek().is_some() && lookahead_iterator.peek().unwrap().is_digit(radix) {
+        digits.push(lookahead_iterator.next().unwrap());
+    }
+    if digits.is_empty() {
+        return None;
+    }
+    let has_semicolon = lookahead_iterator.peek() == Some(&';');
+
+    let length = 1 + (if is_hex { 1 } else { 0 }) + digits.len() + (if has_semicolon { 1 } else { 0 });
+    for _ in 0..length {
+        html_iterator.next();
+    }
+
+    //numbers that are too big for a u32 are also outside of the unicode range, so they get the replacement character as well
+    let code_point = u32::from_str_radix(&digits, radix).unwrap_or(u32::MAX);
+
+    let replacement = WINDOWS_1252_REPLACEMENTS.iter().find(|(number, _)| *number == code_point);
+    if replacement.is_some() {
+        return Some(replacement.unwrap().1.to_string());
+    }
+
+    //char::from_u32 also returns None for surrogates
+    let character = if code_point == 0 { None } else { char::from_u32(code_point) };
+    return Some(character.unwrap_or(char::REPLACEMENT_CHARACTER).to_string());
+}
+
+
+fn lookup_named_character_reference(name: &str) -> Option<&'static str> {
+    let idx = NAMED_CHARACTER_REFERENCES.binary_search_by(|(reference_name, _)| reference_name.cmp(&name));
+    if idx.is_err() {
+        return None;
+    }
+    return Some(NAMED_CHARACTER_REFERENCES[idx.unwrap()].1);
+}
+
+
+fn get_text_content_type(tag_name: &str) -> Option<TextContentType> {
+    return match tag_name {
+        "script" => Some(TextContentType::ScriptData),
+        "style" | "xmp" | "iframe" | "noembed" | "noframes" => Some(TextContentType::RawText),
+        "title" | "textarea" => Some(TextContentType::EscapableRawText),
+        _ => None,
+    };
+}
+
+
+fn lex_text_content_of_element(html_iterator: &mut TrackingIterator, tag_name: &str, text_content_type: TextContentType,
+                               tokens: &mut Vec<HtmlTokenWithLocation>) {
+    //The content of these elements is not parsed as html, it only ends at the matching end tag. For scripts, we also need to track
+    //whether we are inside "<!--", since "<!--<script>" hides the next "</script>" (see https://html.spec.whatwg.org/multipage/parsing.html#script-data-state)
+    let content_line = html_iterator.current_line;
+    let content_char = html_iterator.current_char + 1;
+
+    let mut content = String::new();
+    let mut script_escape_state = ScriptEscapeState::NotEscaped;
+    let mut consecutive_dashes = 0;
+
+    while html_iterator.has_next() {
+        match script_escape_state {
+            ScriptEscapeState::NotEscaped => {
+                if html_iterator.next_chars_are_tag(tag_name, true) {
+                    break;
+                }
+                if text_content_type == TextContentType::ScriptData && html_iterator.next_chars_are("<!--", false) {
+                    content.push_str(&html_iterator.consume_chars(4));
+                    script_escape_state = ScriptEscapeState::Escaped;
+                    consecutive_dashes = 2;
+                    continue;
+                }
+            },
+            ScriptEscapeState::Escaped => {
+                if html_iterator.next_chars_are_tag(tag_name, true) {
+                    break;
+                }
+                if html_iterator.next_chars_are_tag("script", false) {
+                    content.push_str(&html_iterator.consume_chars(7));
+                    script_escape_state = ScriptEscapeState::DoubleEscaped;
+                    consecutive_dashes = 0;
+                    continue;
+                }
+            },
+            ScriptEscapeState::DoubleEscaped => {
+                if html_iterator.next_chars_are_tag("script", true) {
+                    content.push_str(&html_iterator.consume_chars(8));
+                    script_escape_state = ScriptEscapeState::Escaped;
+                    consecutive_dashes = 0;
+                    continue;
+                }
+            },
+        }
+
+        let next_char = html_iterator.next();
+
+        if next_char == '>' && consecutive_dashes >= 2 {
+            //a "-->" ends any escaping
+            script_escape_state = ScriptEscapeState::NotEscaped;
+        }
+        consecutive_dashes = if next_char == '-' { consecutive_dashes + 1 } else { 0 };
+
+        if next_char == '&' && text_content_type == TextContentType::EscapableRawText {
+            let character_reference = consume_character_reference(html_iterator, false);
+            content.push_str(character_reference.as_deref().unwrap_or("&"));
+        } else {
+            content.push(next_char);
+        }
+    }
+
+    let content_token = match text_content_type {
+        TextContentType::ScriptData => Some(HtmlToken::Script(content)),
+        TextContentType::RawText if tag_name == "style" => Some(HtmlToken::Style(content)),
+        _ => if content.is_empty() { None } else { Some(HtmlToken::Text(content)) },
+    };
+    if content_token.is_some() {
+        tokens.push(HtmlTokenWithLocation { html_token: content_token.unwrap(), line: content_line, character: content_char });
+    }
+
+    if html_iterator.has_next() {
+        //we are at the end tag, anything between the name and the > (like attributes) is ignored
+        let close_tag_line = html_iterator.current_line;
+        let close_tag_char = html_iterator.current_char + 1;
+        consume_until_char(html_iterator, '>');
+        tokens.push(HtmlTokenWithLocation { html_token: HtmlToken::CloseTag { name: tag_name.to_owned() }, line: close_tag_line, character: close_tag_char });
+    }
+}
+
+
+fn consume_until_text(html_iterator: &mut TrackingIterator, limit: &str) -> String {
+    let mut str_buffer = String::new();
+    while html_iterator.has_next() && !html_iterator.next_chars_are(limit, false) {
+        str_buffer.push(html_iterator.next());
+    }
+    html_iterator.consume_chars(limit.chars().count()); //eat the limit text
+    return str_buffer;
+}
+
+
 fn consume_until_char(html_iterator: &mut TrackingIterator, limit: char) -> String {
     let mut str_buffer = String::new();
     while html_iterator.has_next() && *html_iterator.peek().unwrap() != limit {
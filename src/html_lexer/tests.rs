@@ -167,12 +167,15 @@ fn test_tag_with_script_1() {
 
 #[test]
 fn test_tag_with_script_2() {
+    //like in browsers, a script end tag inside a string still ends the script
     let html = "<script>x = \"</script>\";</script>";
 
     let expected_tokens = vec![
         html_open("script"),
         html_open_tag_end(),
-        html_script("x = \"</script>\";"),
+        html_script("x = \""),
+        html_close("script"),
+        html_text("\";"),
         html_close("script"),
     ];
 
@@ -259,3 +262,139 @@ fn test_data_rule() {
     let tokens = html_lexer::lex_html(html);
     assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
 }
+
+
+#[test]
+fn test_character_references() {
+    let html = "a&amp;b &#65;&#x1F600;&notin;&notit; &nosuchthing; &#xZZ;";
+
+    let expected_tokens = vec![
+        html_text("a"),
+        html_entity("&"),
+        html_text("b"),
+        html_whitespace(" "),
+        html_entity("A"),
+        html_entity("\u{1F600}"),
+        html_entity("\u{2209}"),
+        html_entity("\u{ac}"), //the legacy reference "&not", without a ";"
+        html_text("it;"),
+        html_whitespace(" "),
+        html_text("&"),
+        html_text("nosuchthing;"),
+        html_whitespace(" "),
+        html_text("&"),
+        html_text("#xZZ;"),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
+
+
+#[test]
+fn test_invalid_numeric_character_references() {
+    let html = "&#0;&#x80;&#xD800;&#1114112;&#99999999999999;";
+
+    let expected_tokens = vec![
+        html_entity("\u{FFFD}"),
+        html_entity("\u{20AC}"),
+        html_entity("\u{FFFD}"),
+        html_entity("\u{FFFD}"),
+        html_entity("\u{FFFD}"),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
+
+
+#[test]
+fn test_character_references_in_attributes() {
+    let html = "<a title=\"&lt;&#x41;&gt;\" href='?a=1&copy=2&amp;b' alt=x&amp;y></a>";
+
+    let expected_tokens = vec![
+        html_open("a"),
+        html_attribute("title", "<A>"),
+        html_attribute("href", "?a=1&copy=2&b"),
+        html_attribute("alt", "x&y"),
+        html_open_tag_end(),
+        html_close("a"),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
+
+
+#[test]
+fn test_script_with_escaped_script_end_tag() {
+    //inside "<!--", a "<script>" makes the next "</script>" part of the script content
+    let html = "<script><!--<script>x = 1;</script>--></script><b>";
+
+    let expected_tokens = vec![
+        html_open("script"),
+        html_open_tag_end(),
+        html_script("<!--<script>x = 1;</script>-->"),
+        html_close("script"),
+        html_open("b"),
+        html_open_tag_end(),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
+
+
+#[test]
+fn test_raw_text_elements() {
+    let html = "<style>a::after { content: \"<b>\" }</STYLE ><title>a <b> &amp; b</title>";
+
+    let expected_tokens = vec![
+        html_open("style"),
+        html_open_tag_end(),
+        html_style("a::after { content: \"<b>\" }"),
+        html_close("style"),
+        html_open("title"),
+        html_open_tag_end(),
+        html_text("a <b> & b"),
+        html_close("title"),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
+
+
+#[test]
+fn test_cdata_and_bogus_comments() {
+    let html = "<![CDATA[x<y]]><!bogus>a";
+
+    let expected_tokens = vec![
+        html_comment("[CDATA[x<y]]"),
+        html_comment("bogus"),
+        html_text("a"),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
+
+
+#[test]
+fn test_uppercase_tag_and_attribute_names() {
+    let html = "<IMG SRC=\"A.png\"><Div Class=x>text</DIV>";
+
+    let expected_tokens = vec![
+        html_open("img"),
+        html_attribute("src", "A.png"),
+        html_open_tag_end(),
+        html_open("div"),
+        html_attribute("class", "x"),
+        html_open_tag_end(),
+        html_text("text"),
+        html_close("div"),
+    ];
+
+    let tokens = html_lexer::lex_html(html);
+    assert!(tokens_equal_ignoring_location(tokens, expected_tokens));
+}
@@ -1,191 +1,138 @@
-use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::rc::Rc;
 
+use crate::arena::Arena;
 use crate::debug::debug_log_warn;
 use crate::dom::{
-    AttributeDomNode,
+    PendingStyleImport,
     Document,
+    DocumentReadyState,
+    DomNodeId,
     DomText,
     ElementDomNode,
-    get_next_dom_node_interal_id,
-    TagName,
 };
 use crate::html_lexer::{HtmlToken, HtmlTokenWithLocation};
+use crate::html_parser::tree_builder::{
+    TreeBuilder,
+    TreeToken,
+};
+use crate::network::CacheMode;
 use crate::network::url::Url;
 use crate::script::{js_lexer, js_parser};
 use crate::style::{
     css_lexer,
     css_parser,
     get_user_agent_style_sheet,
-    StyleRule,
+    media_query::MediaEnvironment,
     StyleContext,
+    StyleSheet,
 };
 
 
 #[cfg(test)] mod tests;
+mod tree_builder;
+
+pub use crate::html_parser::tree_builder::VOID_ELEMENTS;
 
 
-const SELF_CLOSING_TAGS: [&str; 6] = ["br", "hr", "img", "input", "link", "meta"];
+pub const NON_BREAKING_SPACE: &str = "\u{a0}";
 
 
 pub fn parse(html_tokens: Vec<HtmlTokenWithLocation>, main_url: &Url) -> Document {
-    let mut all_nodes = HashMap::new();
-    let mut document_style_rules = Vec::new();
+    let (tree_builder, document_style_sheets) = build_tree(html_tokens, Arena::new());
+
+    let mut document = Document { nodes: tree_builder.nodes, document_node: tree_builder.document_node,
+                                  style_context: StyleContext::new(get_user_agent_style_sheet(), Vec::new(), MediaEnvironment::new_default()),
+                                  pending_style_imports: Vec::new(), page_url: main_url.clone(), base_url: main_url.clone(),
+                                  background_images: HashMap::new(), background_image_job_trackers: HashMap::new(), background_images_need_scheduling: true,
+                                  nodes_to_post_construct: Vec::new(), ready_state: DocumentReadyState::Loading, cache_mode: CacheMode::Default };
+
+    document.base_url = document.compute_base_url(main_url);
+    for style_sheet in document_style_sheets {
+        let style_imports = style_sheet.imports.clone();
+        let sheet_slot = document.style_context.add_author_style_sheet(style_sheet);
+        for (import_idx, style_import) in style_imports.iter().enumerate() {
+            let import_slot = [sheet_slot.clone(), vec![import_idx]].concat();
+            document.pending_style_imports.push(PendingStyleImport::new(style_import, &document.base_url, import_slot));
+        }
+    }
 
-    let mut document_children = Vec::new();
-    let mut current_token_idx = 0;
+    return document;
+}
 
-    let document_node_id = get_next_dom_node_interal_id();
 
-    while current_token_idx < html_tokens.len() {
-        let mut tag_stack = Vec::new();
-        document_children.push(parse_node(&html_tokens, &mut current_token_idx, document_node_id, &mut all_nodes, &mut document_style_rules, &mut tag_stack));
-        current_token_idx += 1;
+pub fn parse_fragment(html_tokens: Vec<HtmlTokenWithLocation>, document: &mut Document) -> Vec<DomNodeId> {
+    //this parses html as the content of an element (like for innerHTML), and returns the nodes it contains, which are in the arena of the document,
+    //but not in its tree yet
+    //TODO: the spec parses fragments in the context of the element they are for (so "<td>" works inside a table row), we always parse them
+    //      like the content of the body. Style elements in the fragment are also not applied yet.
+    let mut fragment_tokens = vec![
+        HtmlTokenWithLocation { html_token: HtmlToken::OpenTag { name: String::from("body") }, line: 0, character: 0 },
+        HtmlTokenWithLocation { html_token: HtmlToken::OpenTagEnd, line: 0, character: 0 },
+    ];
+    fragment_tokens.extend(html_tokens);
+    //the nodes are built directly in the arena of the document, the document, html and body nodes around them are removed again afterwards
+    let (mut tree_builder, _) = build_tree(fragment_tokens, mem::replace(&mut document.nodes, Arena::new()));
+
+    let html_node = tree_builder.nodes[tree_builder.document_node].children.as_ref().unwrap()[0];
+    let wrapper_nodes: Vec<DomNodeId> = tree_builder.nodes[html_node].children.clone().unwrap();
+    let body_node = *wrapper_nodes.iter().find(|node| tree_builder.nodes[**node].name.as_deref() == Some("body")).unwrap();
+    let nodes = tree_builder.nodes[body_node].children.take().unwrap();
+    for node in &nodes {
+        tree_builder.nodes[*node].parent = None;
     }
 
-    let document_node = ElementDomNode {
-        internal_id: document_node_id,
-        parent_id: 0,
-        is_document_node: true,
-        dirty: false,
-        text: None,
-        name: None,
-        name_for_layout: TagName::Other,
-        children: Some(document_children),
-        attributes: None,
-        image: None,
-        img_job_tracker: None,
-        scripts: None,
-        page_component: None,
-    };
-
-    let rc_doc_node = Rc::new(RefCell::from(document_node));
-    let rc_doc_node_clone = Rc::clone(&rc_doc_node);
-    all_nodes.insert(document_node_id, rc_doc_node);
-
-    let style_context = StyleContext {
-        user_agent_sheet: get_user_agent_style_sheet(),
-        author_sheet: document_style_rules,
-    };
-    return Document { all_nodes, style_context, document_node: rc_doc_node_clone, base_url: main_url.clone() };
+    tree_builder.nodes.remove(tree_builder.document_node);
+    tree_builder.nodes.remove(html_node);
+    for wrapper_node in wrapper_nodes {
+        tree_builder.nodes.remove(wrapper_node);
+    }
+    document.nodes = tree_builder.nodes;
+    return nodes;
 }
 
 
-fn parse_node(html_tokens: &Vec<HtmlTokenWithLocation>, current_token_idx: &mut usize, parent_id: usize,
-              all_nodes: &mut HashMap<usize, Rc<RefCell<ElementDomNode>>>, styles: &mut Vec<StyleRule>,
-              tag_stack: &mut Vec<String>) -> Rc<RefCell<ElementDomNode>> {
-    let node_being_build_internal_id = get_next_dom_node_interal_id();
+fn build_tree(html_tokens: Vec<HtmlTokenWithLocation>, nodes: Arena<ElementDomNode>) -> (TreeBuilder, Vec<StyleSheet>) {
+    let mut tree_builder = TreeBuilder::new(nodes);
+    let mut document_style_sheets = Vec::new();
 
     let mut tag_being_parsed = None;
-    let mut children = Vec::new();
     let mut attributes = Vec::new();
-    let mut scripts = Vec::new();
+    let mut current_token_idx = 0;
 
-    while *current_token_idx < html_tokens.len() {
-        let current_token = html_tokens.get(*current_token_idx).unwrap();
+    while current_token_idx < html_tokens.len() {
+        let current_token = html_tokens.get(current_token_idx).unwrap();
 
         match &current_token.html_token {
             HtmlToken::OpenTag { name } => {
-                if tag_being_parsed.is_none() {
-                    tag_being_parsed = Some(name.clone());
-                } else {
-                    tag_stack.push(tag_being_parsed.clone().unwrap());
-                    let new_node = parse_node(html_tokens, current_token_idx, node_being_build_internal_id, all_nodes, styles, tag_stack);
-                    children.push(new_node);
-                }
-            },
-            HtmlToken::OpenTagEnd => {
-                //Some tags can't have children and therefore also no (self)close tag
-
-                //TODO: did I handle uppercase tags already? (needs to happen in the lexer)
-                if tag_being_parsed.is_some() && SELF_CLOSING_TAGS.contains(&tag_being_parsed.as_ref().unwrap().as_str()) {
-                    let new_node = ElementDomNode {
-                        internal_id: node_being_build_internal_id,
-                        name_for_layout: TagName::from_string(&tag_being_parsed.as_ref().unwrap()),
-                        name: tag_being_parsed,
-                        children: Some(children),
-                        parent_id,
-                        text: None,
-                        attributes: Some(attributes),
-                        is_document_node: false,
-                        dirty: false,
-                        image: None,
-                        img_job_tracker: None,
-                        scripts: None,
-                        page_component: None,
-                    };
-
-                    let rc_node = Rc::new(RefCell::from(new_node));
-                    all_nodes.insert(node_being_build_internal_id, Rc::clone(&rc_node));
-                    return rc_node;
-                }
+                tag_being_parsed = Some(name.clone());
+                attributes = Vec::new();
             },
             HtmlToken::Attribute(token) => {
-                let attribute = AttributeDomNode {
-                    name: token.name.clone(),
-                    value: token.value.clone(),
-                    parent_id: node_being_build_internal_id,
-                };
-                attributes.push(Rc::new(RefCell::from(attribute)));
-
+                attributes.push((token.name.clone(), token.value.clone()));
             },
-            HtmlToken::CloseTag { name } => {
-
-                if SELF_CLOSING_TAGS.contains(&name.as_str()) {
-                    //these tags should never be closed, so we just ignore when that happens anyway
-                    *current_token_idx += 1;
-                    continue;
-                }
-
-                let mut tag_to_close = tag_being_parsed.clone();
-
-                if tag_being_parsed.is_none() || name != tag_being_parsed.as_ref().unwrap() {
-
-                    //TODO is tag_being_parsed the same as the last item in tag_stack? Do I need both?
-
-                    if tag_stack.contains(&name) {
-                        //we are trying to close a tag we know about, but not the one we are parsing now, so we close that one instead
-                        //and then we are setting the current token one back, so we will retry closing this tag one recursion level higher.
-                        tag_to_close = Some(tag_being_parsed.unwrap().clone());
-                        *current_token_idx -= 1;
-                    } else {
-                        //we are closing a tag whe have never opened, we should ignore it
-                        *current_token_idx += 1;
-                        continue;
+            HtmlToken::OpenTagEnd => {
+                if tag_being_parsed.is_some() {
+                    let name = tag_being_parsed.take().unwrap();
+
+                    //The lexer emits a close tag right after a self-closing tag. Void elements are always closed directly by the tree builder,
+                    //so we skip it for those (for other elements the spec says to ignore the "/", but we still close them for now).
+                    let next_token = html_tokens.get(current_token_idx + 1);
+                    if VOID_ELEMENTS.contains(&name.as_str()) && next_token.is_some() &&
+                       next_token.unwrap().html_token == (HtmlToken::CloseTag { name: name.clone() }) {
+                        current_token_idx += 1;
                     }
 
+                    tree_builder.process_token(TreeToken::StartTag { name, attributes: mem::take(&mut attributes) });
                 }
-
-                let new_node = ElementDomNode {
-                    internal_id: node_being_build_internal_id,
-                    name_for_layout: TagName::from_string(&tag_to_close.as_ref().unwrap()),
-                    name: tag_to_close,
-                    children: Some(children),
-                    parent_id,
-                    text: None,
-                    attributes: Some(attributes),
-                    is_document_node: false,
-                    dirty: false,
-                    image: None,
-                    img_job_tracker: None,
-                    scripts: if scripts.len() == 0 { None } else { Some(scripts) },
-                    page_component: None,
-                };
-
-                let rc_node = Rc::new(RefCell::from(new_node));
-                all_nodes.insert(node_being_build_internal_id, Rc::clone(&rc_node));
-                return rc_node;
+            },
+            HtmlToken::CloseTag { name } => {
+                tree_builder.process_token(TreeToken::EndTag { name: name.clone() });
             },
             HtmlToken::Text(_) | HtmlToken::Whitespace(_) | HtmlToken::Entity(_) => {
-                let parent_for_node = if tag_being_parsed.is_some() { node_being_build_internal_id } else { parent_id };
-                let text_node = read_all_text_for_text_node(html_tokens, current_token_idx, parent_for_node);
-
-                if tag_being_parsed.is_some() {
-                    children.push(Rc::new(RefCell::from(text_node)));
-                } else {
-                    return Rc::new(RefCell::from(text_node));
-                }
+                let text = read_all_text_for_text_node(&html_tokens, &mut current_token_idx);
+                tree_builder.process_token(TreeToken::Text(text));
             },
             HtmlToken::Comment(_) => {},
             HtmlToken::Doctype(_) => {
@@ -193,58 +140,32 @@ fn parse_node(html_tokens: &Vec<HtmlTokenWithLocation>, current_token_idx: &mut
             },
             HtmlToken::Style(content) => {
                 let style_tokens = css_lexer::lex_css(content, current_token.line, current_token.character);
-                styles.append(&mut css_parser::parse_css(&style_tokens));
+                document_style_sheets.push(css_parser::parse_css(&style_tokens));
             },
             HtmlToken::Script(content) => {
-                let mut script_type = String::from("text/javascript");
-                for att_node in &attributes {
-                    if att_node.borrow().name == "type" {
-                        script_type = att_node.borrow().value.clone();
-                    }
-                }
+                //the script element is the current node in the tree builder, since the script content directly follows its open tag
+                let script_type = tree_builder.get_current_node_attribute("type").unwrap_or(String::from("text/javascript"));
 
                 if script_type == "text/javascript" {
                     let js_tokens = js_lexer::lex_js(content, current_token.line, current_token.character);
                     let script = js_parser::parse_js(&js_tokens);
-                    scripts.push(Rc::from(script));
+                    tree_builder.add_script_to_current_node(Rc::from(script));
                 } else {
                     debug_log_warn(format!("unrecognised script type: {}", script_type));
                 }
             },
         }
 
-        *current_token_idx += 1;
+        current_token_idx += 1;
     }
 
-    if tag_being_parsed.is_some() {
-        let new_node = ElementDomNode { //TODO: I probably want a ::new() function, because I'm going to have a lot of fields that
-                                        //      are constructed on :update()
-            internal_id: node_being_build_internal_id,
-            name_for_layout: TagName::from_string(&tag_being_parsed.as_ref().unwrap()),
-            name: tag_being_parsed,
-            children: Some(children),
-            parent_id,
-            text: None,
-            attributes: Some(attributes),
-            is_document_node: false,
-            dirty: false,
-            image: None,
-            img_job_tracker: None,
-            scripts: if scripts.len() == 0 { None } else { Some(scripts) },
-            page_component: None,
-        };
-
-        let rc_node = Rc::new(RefCell::from(new_node));
-        all_nodes.insert(node_being_build_internal_id, Rc::clone(&rc_node));
-
-        return rc_node;
-    }
+    tree_builder.finish();
 
-    panic!("this should not happen (leaving the parse loop without returning)");
+    return (tree_builder, document_style_sheets);
 }
 
 
-fn read_all_text_for_text_node(html_tokens: &Vec<HtmlTokenWithLocation>, current_token_idx: &mut usize, parent_id: usize) -> ElementDomNode {
+fn read_all_text_for_text_node(html_tokens: &Vec<HtmlTokenWithLocation>, current_token_idx: &mut usize) -> DomText {
     let mut text_content = String::new();
     let mut non_breaking_space_positions: Option<HashSet<usize>> = None;
 
@@ -258,34 +179,25 @@ fn read_all_text_for_text_node(html_tokens: &Vec<HtmlTokenWithLocation>, current
             HtmlToken::Whitespace(_) => {
                 text_content.push_str(" ");
             },
-            HtmlToken::Entity(entity) => {
-                match entity.as_str() {
-                    "amp" => { text_content.push('&'); }
-                    "apos" => { text_content.push('\'') }
-                    "gt" => { text_content.push('>'); }
-                    "lt" => { text_content.push('<'); }
-                    "quot" => { text_content.push('"') }
-
-                    "nbsp" => {
-                        let position = text_content.len();
-                        non_breaking_space_positions = match non_breaking_space_positions {
-                            Some(mut set) => {
-                                set.insert(position);
-                                Some(set)
-                            },
-                            None => {
-                                let mut set = HashSet::new();
-                                set.insert(position);
-                                Some(set)
-                            },
-                        }
-                    }
-
-                    _ => {
-                        //unknown entity, just use as text
-                        text_content.push_str(entity);
+            HtmlToken::Entity(character_reference) => {
+                if character_reference == NON_BREAKING_SPACE {
+                    //we keep track of where the non breaking spaces are, because the layout should not wrap the text on them
+                    let position = text_content.len();
+                    non_breaking_space_positions = match non_breaking_space_positions {
+                        Some(mut set) => {
+                            set.insert(position);
+                            Some(set)
+                        },
+                        None => {
+                            let mut set = HashSet::new();
+                            set.insert(position);
+                            Some(set)
+                        },
                     }
                 }
+
+                //the lexer already resolved the reference to the text it stands for
+                text_content.push_str(character_reference);
             }
             _ => break 'text_token_loop
         }
@@ -296,22 +208,5 @@ fn read_all_text_for_text_node(html_tokens: &Vec<HtmlTokenWithLocation>, current
     //we now subtract one from the idx, because we break from the above loop because we should not handle that char yet and the main loop will increment it:
     *current_token_idx -= 1;
 
-    let dom_text = DomText { text_content, non_breaking_space_positions };
-
-    let node = ElementDomNode {
-        internal_id: get_next_dom_node_interal_id(),
-        parent_id: parent_id,
-        text: Some(dom_text),
-        name: None,
-        name_for_layout: TagName::Other,
-        children: None,
-        attributes: None,
-        is_document_node: false,
-        dirty: false,
-        image: None,
-        img_job_tracker: None,
-        scripts: None,
-        page_component: None,
-    };
-    return node;
+    return DomText { text_content, non_breaking_space_positions };
 }
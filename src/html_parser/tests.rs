@@ -1,7 +1,15 @@
-use crate::dom::ElementDomNode;
+use std::time::Duration;
+
+use std::path::PathBuf;
+
+use crate::dom::{Document, DomNodeId, ElementDomNode};
+use crate::html_lexer;
 use crate::html_parser;
 use crate::network::url::Url;
+use crate::platform::KeyCode;
+use crate::platform::fonts::FontContext;
 use crate::test_util::*;
+use crate::ui_components::{PageComponent, TextField};
 
 
 #[test]
@@ -18,16 +26,17 @@ fn test_basic_parsing_1() {
     let main_url = Url::from(&String::from("http://www.google.com")); //TODO: would be nice if we can define these as (lazy?) consts?
     let parse_result = html_parser::parse(tokens, &main_url);
     let document = parse_result;
-    let doc_node = &document.document_node.borrow();
-    assert_eq!(doc_node.children.as_ref().unwrap().len(), 2);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    assert_eq!(body_node.children.as_ref().unwrap().len(), 2);
 
-    let generic_a_node = doc_node.children.as_ref().unwrap()[0].borrow();
+    let generic_a_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
     assert_element_name_is(&generic_a_node, "a");
 
     let a_children = generic_a_node.children.as_ref().unwrap();
     assert_eq!(a_children.len(), 1);
 
-    assert_text_on_node_is(&a_children[0].borrow(), "text");
+    assert_text_on_node_is(&document.nodes[a_children[0]], "text");
 }
 
 
@@ -46,11 +55,12 @@ fn test_text_concatenation() {
     let main_url = Url::from(&String::from("http://www.google.com"));
     let parse_result = html_parser::parse(tokens, &main_url);
     let document = parse_result;
-    let doc_node = &document.document_node.borrow();
-    assert_eq!(doc_node.children.as_ref().unwrap().len(), 1);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    assert_eq!(body_node.children.as_ref().unwrap().len(), 1);
 
-    let div_node = doc_node.children.as_ref().unwrap()[0].borrow();
-    let text_node = div_node.children.as_ref().unwrap()[0].borrow();
+    let div_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
+    let text_node = &document.nodes[div_node.children.as_ref().unwrap()[0]];
     assert_text_on_node_is(&text_node, "two words");
 }
 
@@ -78,22 +88,23 @@ fn test_not_closing_a_tag() {
     let main_url = Url::from(&String::from("http://www.google.com"));
     let parse_result = html_parser::parse(tokens, &main_url);
     let document = parse_result;
-    let doc_node = &document.document_node.borrow();
-    assert_eq!(doc_node.children.as_ref().unwrap().len(), 1);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    assert_eq!(body_node.children.as_ref().unwrap().len(), 1);
 
     //TODO: it would be much nicer if we can just compare with a tree of nodes here, that we layout like in json, or just with tabs
 
-    assert_element_name_is(&doc_node.children.as_ref().unwrap()[0].borrow(), "div");
+    assert_element_name_is(&document.nodes[body_node.children.as_ref().unwrap()[0]], "div");
 
-    let div_node = doc_node.children.as_ref().unwrap()[0].borrow();
+    let div_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
     let div_childs = div_node.children.as_ref().unwrap();
     assert_eq!(div_childs.len(), 1);
-    assert_element_name_is(&div_childs[0].borrow(), "b");
+    assert_element_name_is(&document.nodes[div_childs[0]], "b");
 
-    let b_node = div_childs[0].borrow();
+    let b_node = &document.nodes[div_childs[0]];
     let b_childs = b_node.children.as_ref().unwrap();
     assert_eq!(b_childs.len(), 1);
-    assert_element_name_is(&b_childs[0].borrow(), "p");
+    assert_element_name_is(&document.nodes[b_childs[0]], "p");
 }
 
 
@@ -107,7 +118,7 @@ fn test_closing_a_tag_we_did_not_open() {
         html_open("b"),
         html_open_tag_end(),
 
-        html_close("p"), //this one should be ignored
+        html_close("p"), //there is no p to close, so this creates an empty one
 
         html_close("b"),
 
@@ -117,15 +128,22 @@ fn test_closing_a_tag_we_did_not_open() {
     let main_url = Url::from(&String::from("http://www.google.com"));
     let parse_result = html_parser::parse(tokens, &main_url);
     let document = parse_result;
-    let doc_node = &document.document_node.borrow();
-    assert_eq!(doc_node.children.as_ref().unwrap().len(), 1);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    assert_eq!(body_node.children.as_ref().unwrap().len(), 1);
 
-    assert_element_name_is(&doc_node.children.as_ref().unwrap()[0].borrow(), "div");
+    assert_element_name_is(&document.nodes[body_node.children.as_ref().unwrap()[0]], "div");
 
-    let div_node = doc_node.children.as_ref().unwrap()[0].borrow();
+    let div_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
     let div_childs = div_node.children.as_ref().unwrap();
     assert_eq!(div_childs.len(), 1);
-    assert_element_name_is(&div_childs[0].borrow(), "b");
+    assert_element_name_is(&document.nodes[div_childs[0]], "b");
+
+    let b_node = &document.nodes[div_childs[0]];
+    let b_childs = b_node.children.as_ref().unwrap();
+    assert_eq!(b_childs.len(), 1);
+    assert_element_name_is(&document.nodes[b_childs[0]], "p");
+    assert_eq!(document.nodes[b_childs[0]].children.as_ref().unwrap().len(), 0);
 }
 
 
@@ -146,22 +164,592 @@ fn test_missing_last_closing_tag() {
     let main_url = Url::from(&String::from("http://www.google.com"));
     let parse_result = html_parser::parse(tokens, &main_url);
     let document = parse_result;
-    let doc_node = &document.document_node.borrow();
+    let doc_node = &document.nodes[document.document_node];
     assert_eq!(doc_node.children.as_ref().unwrap().len(), 1);
 
-    assert_element_name_is(&doc_node.children.as_ref().unwrap()[0].borrow(), "html");
+    assert_element_name_is(&document.nodes[doc_node.children.as_ref().unwrap()[0]], "html");
 
-    let html_node = doc_node.children.as_ref().unwrap()[0].borrow();
+    let html_node = &document.nodes[doc_node.children.as_ref().unwrap()[0]];
     let html_childs = html_node.children.as_ref().unwrap();
-    assert_eq!(html_childs.len(), 1);
-    assert_element_name_is(&html_childs[0].borrow(), "body");
+    assert_eq!(html_childs.len(), 2);
+    assert_element_name_is(&document.nodes[html_childs[0]], "head");
+    assert_element_name_is(&document.nodes[html_childs[1]], "body");
 
-    let body_node = html_childs[0].borrow();
+    let body_node = &document.nodes[html_childs[1]];
     let body_childs = body_node.children.as_ref().unwrap();
     assert_eq!(body_childs.len(), 0);
 }
 
 
+#[test]
+fn test_text_with_character_references() {
+
+    let tokens = vec![
+        html_open("div"),
+        html_open_tag_end(),
+        html_text("a"),
+        html_entity("&"),
+        html_text("b"),
+        html_entity("\u{a0}"),
+        html_text("c"),
+        html_close("div"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    let div_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
+    let text_node = &document.nodes[div_node.children.as_ref().unwrap()[0]];
+    assert_text_on_node_is(&text_node, "a&b\u{a0}c");
+
+    let non_breaking_space_positions = text_node.text.as_ref().unwrap().non_breaking_space_positions.as_ref().unwrap();
+    assert_eq!(non_breaking_space_positions.len(), 1);
+    assert!(non_breaking_space_positions.contains(&3));
+}
+
+
+#[test]
+fn test_leading_newline_in_textarea_is_ignored() {
+
+    let tokens = vec![
+        html_open("textarea"),
+        html_open_tag_end(),
+        html_text("\nfirst line\nsecond line"),
+        html_close("textarea"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    let textarea_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&textarea_node, "textarea");
+    assert_text_on_node_is(&document.nodes[textarea_node.children.as_ref().unwrap()[0]], "first line\nsecond line");
+}
+
+
+#[test]
+fn test_find_node_with_id() {
+
+    let tokens = vec![
+        html_open("div"),
+        html_attribute("id", "outer"),
+        html_open_tag_end(),
+        html_open("input"),
+        html_attribute("id", "field"),
+        html_open_tag_end(),
+        html_close("div"),
+        html_open("p"),
+        html_attribute("id", "field"),
+        html_open_tag_end(),
+        html_close("p"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+
+    assert_element_name_is(&document.nodes[document.find_node_with_id("outer").unwrap()], "div");
+    //when an id is used more than once, the first node in the tree wins
+    assert_element_name_is(&document.nodes[document.find_node_with_id("field").unwrap()], "input");
+    assert!(document.find_node_with_id("missing").is_none());
+}
+
+
+#[test]
+fn test_title_and_favicon_url() {
+
+    let tokens = vec![
+        html_open("head"),
+        html_open_tag_end(),
+        html_open("title"),
+        html_open_tag_end(),
+        html_text("  The   page\ntitle "),
+        html_close("title"),
+        html_open("link"),
+        html_attribute("rel", "Shortcut Icon"),
+        html_attribute("href", "images/icon.png"),
+        html_open_tag_end(),
+        html_close("head"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com/some/page.html"));
+    let document = html_parser::parse(tokens, &main_url);
+
+    assert_eq!(document.get_title(), Some(String::from("The page title")));
+    assert_eq!(document.get_favicon_url().unwrap().to_string(), "http://www.google.com/some/images/icon.png");
+}
+
+
+#[test]
+fn test_default_favicon_url() {
+
+    let tokens = vec![
+        html_open("p"),
+        html_open_tag_end(),
+        html_text("no title or icon here"),
+        html_close("p"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com/some/page.html"));
+    let document = html_parser::parse(tokens, &main_url);
+
+    assert!(document.get_title().is_none());
+    assert_eq!(document.get_favicon_url().unwrap().to_string(), "http://www.google.com/favicon.ico");
+}
+
+
+#[test]
+fn test_local_files_are_only_subresources_of_local_pages() {
+
+    let tokens = || vec![
+        html_open("iframe"),
+        html_attribute("src", "file:///home/"),
+        html_open_tag_end(),
+        html_close("iframe"),
+    ];
+
+    //a website should not be able to show a folder listing or the files of the user
+    let local_folder = Url::from(&String::from("file:///home/"));
+    let remote_document = html_parser::parse(tokens(), &Url::from(&String::from("https://www.example.com/page.html")));
+    assert!(!remote_document.allows_subresource(&local_folder));
+    assert!(!remote_document.allows_subresource(&Url::from(&String::from("file:///home/user/image.png"))));
+    assert!(remote_document.allows_subresource(&Url::from(&String::from("https://www.example.com/image.png"))));
+
+    let local_document = html_parser::parse(tokens(), &Url::from(&String::from("file:///home/user/page.html")));
+    assert!(local_document.allows_subresource(&local_folder));
+}
+
+
+#[test]
+fn test_base_href_and_meta_refresh() {
+
+    let tokens = vec![
+        html_open("head"),
+        html_open_tag_end(),
+        html_open("base"),
+        html_attribute("href", "/other/folder/"),
+        html_open_tag_end(),
+        html_open("base"),
+        html_attribute("href", "http://www.example.com/"),
+        html_open_tag_end(),
+        html_open("meta"),
+        html_attribute("http-equiv", "Refresh"),
+        html_attribute("content", "5.9; URL='next.html'"),
+        html_open_tag_end(),
+        html_open("link"),
+        html_attribute("rel", "icon"),
+        html_attribute("href", "icon.png"),
+        html_open_tag_end(),
+        html_close("head"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com/some/page.html"));
+    let document = html_parser::parse(tokens, &main_url);
+
+    //only the first base element counts, and relative urls in the page are resolved against it
+    assert_eq!(document.base_url.to_string(), "http://www.google.com/other/folder/");
+    assert_eq!(document.get_favicon_url().unwrap().to_string(), "http://www.google.com/other/folder/icon.png");
+
+    let (delay, refresh_url) = document.get_meta_refresh().unwrap();
+    assert_eq!(delay, Duration::from_secs(5));
+    assert_eq!(refresh_url.to_string(), "http://www.google.com/other/folder/next.html");
+}
+
+
+#[test]
+fn test_meta_refresh_to_browser_pages_or_local_files() {
+    let refresh_to = |page_url: &str, refresh_url: &str| {
+        let tokens = vec![
+            html_open("meta"),
+            html_attribute("http-equiv", "refresh"),
+            html_attribute("content", &format!("0; url={}", refresh_url)),
+            html_open_tag_end(),
+        ];
+        let document = html_parser::parse(tokens, &Url::from(&String::from(page_url)));
+        return document.get_meta_refresh().map(|(_, url)| url.to_string());
+    };
+
+    //otherwise any website could clear the history, or show the files of the user, without them doing anything
+    assert!(refresh_to("https://www.example.com/", "about:history?clear=1").is_none());
+    assert!(refresh_to("https://www.example.com/", "file:///home/").is_none());
+    assert_eq!(refresh_to("https://www.example.com/", "https://www.example.com/next").unwrap(), "https://www.example.com/next");
+    assert_eq!(refresh_to("file:///home/user/page.html", "file:///home/user/next.html").unwrap(), "file:///home/user/next.html");
+}
+
+
+#[test]
+fn test_meta_refresh_without_url() {
+
+    let tokens = vec![
+        html_open("meta"),
+        html_attribute("http-equiv", "refresh"),
+        html_attribute("content", "30"),
+        html_open_tag_end(),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com/some/page.html"));
+    let document = html_parser::parse(tokens, &main_url);
+
+    let (delay, refresh_url) = document.get_meta_refresh().unwrap();
+    assert_eq!(delay, Duration::from_secs(30));
+    assert_eq!(refresh_url.to_string(), "http://www.google.com/some/page.html");
+}
+
+
+#[test]
+fn test_details_summary_and_open_attribute() {
+
+    let tokens = vec![
+        html_open("details"),
+        html_open_tag_end(),
+        html_open("summary"),
+        html_attribute("id", "first"),
+        html_open_tag_end(),
+        html_open("b"),
+        html_attribute("id", "inside"),
+        html_open_tag_end(),
+        html_text("more"),
+        html_close("b"),
+        html_close("summary"),
+        html_open("summary"),
+        html_attribute("id", "second"),
+        html_open_tag_end(),
+        html_close("summary"),
+        html_close("details"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let mut document = html_parser::parse(tokens, &main_url);
+
+    let first_summary = document.find_node_with_id("first").unwrap();
+    let details = document.find_details_for_summary(first_summary).unwrap();
+    assert_element_name_is(&document.nodes[details], "details");
+    assert!(document.find_details_for_summary(document.find_node_with_id("inside").unwrap()).is_some());
+    //only the first summary belongs to the details element
+    assert!(document.find_details_for_summary(document.find_node_with_id("second").unwrap()).is_none());
+
+    assert!(document.nodes[details].get_attribute_value("open").is_none());
+    document.nodes[details].set_attribute("open", "");
+    assert_eq!(document.nodes[details].get_attribute_value("open"), Some(String::new()));
+    assert!(document.nodes[details].dirty);
+    document.nodes[details].remove_attribute("open");
+    assert!(document.nodes[details].get_attribute_value("open").is_none());
+}
+
+
+#[test]
+fn test_reset_buttons_restore_the_fields_of_their_form() {
+    let html = r#"<html><body>
+        <form>
+            <input id="name" value="start">
+            <textarea id="comment">default text</textarea>
+            <input id="file" type="file">
+            <button id="reset_button" type="reset">Reset</button>
+            <input id="reset_input" type="reset">
+        </form>
+        <input id="outside" value="outside">
+    </body></html>"#;
+
+    let main_url = Url::from(&String::from("http://www.example.com"));
+    let mut document = html_parser::parse(html_lexer::lex_html(html), &main_url);
+    let font_context = FontContext::new();
+    let document_node = document.document_node;
+    document.post_construct(document_node, &font_context);
+
+    let component = |document: &Document, id: &str| {
+        let node_id = document.find_node_with_id(id).unwrap();
+        return document.nodes[node_id].page_component.clone().unwrap();
+    };
+    let change_all_fields = |document: &Document| {
+        for id in ["name", "outside"] {
+            if let PageComponent::TextField(text_field) = &mut *component(document, id).borrow_mut() {
+                text_field.set_text(&font_context, String::from("changed"));
+            }
+        }
+        if let PageComponent::TextArea(text_area) = &mut *component(document, "comment").borrow_mut() {
+            text_area.set_text(&font_context, String::from("changed"));
+        }
+        if let PageComponent::FileInput(file_input) = &mut *component(document, "file").borrow_mut() {
+            file_input.selected_file = Some(PathBuf::from("/home/user/file.txt"));
+        }
+    };
+    let text_of = |document: &Document, id: &str| {
+        return match &*component(document, id).borrow() {
+            PageComponent::TextField(text_field) => text_field.text.clone(),
+            PageComponent::TextArea(text_area) => text_area.text.clone(),
+            _ => panic!("not a text component"),
+        };
+    };
+
+    for reset_id in ["reset_button", "reset_input"] {
+        change_all_fields(&document);
+        let reset_node = document.find_node_with_id(reset_id).unwrap();
+        document.click(reset_node, &font_context);
+
+        assert_eq!(text_of(&document, "name"), "start");
+        assert_eq!(text_of(&document, "comment"), "default text");
+        match &*component(&document, "file").borrow() {
+            PageComponent::FileInput(file_input) => assert!(file_input.selected_file.is_none()),
+            _ => panic!("not a file input"),
+        };
+
+        //fields that are not in the form keep what was typed in them
+        assert_eq!(text_of(&document, "outside"), "changed");
+    }
+}
+
+
+#[test]
+fn test_text_field_attributes_limit_editing() {
+    let html = r#"<html><body>
+        <input id="limited" maxlength="5">
+        <input id="disabled" value="fixed" disabled>
+        <input id="readonly" value="fixed" readonly>
+    </body></html>"#;
+
+    let main_url = Url::from(&String::from("http://www.example.com"));
+    let mut document = html_parser::parse(html_lexer::lex_html(html), &main_url);
+    let font_context = FontContext::new();
+    let document_node = document.document_node;
+    document.post_construct(document_node, &font_context);
+
+    let edit_text_field = |id: &str, edit: &dyn Fn(&mut TextField)| {
+        let node_id = document.find_node_with_id(id).unwrap();
+        return match &mut *document.nodes[node_id].page_component.as_ref().unwrap().borrow_mut() {
+            PageComponent::TextField(text_field) => {
+                edit(text_field);
+                text_field.text.clone()
+            },
+            _ => panic!("not a text field"),
+        };
+    };
+
+    //text that does not fit is cut off, both when typed and when pasted
+    assert_eq!(edit_text_field("limited", &|text_field| text_field.insert_text(&font_context, &String::from("abcdefgh"))), "abcde");
+    assert_eq!(edit_text_field("limited", &|text_field| text_field.handle_keyboard_input(&font_context, Some(&String::from("x")), None)), "abcde");
+    edit_text_field("limited", &|text_field| text_field.handle_keyboard_input(&font_context, None, Some(KeyCode::BACKSPACE)));
+    assert_eq!(edit_text_field("limited", &|text_field| text_field.paste_text(&font_context, &String::from("xyz"))), "abcdx");
+
+    for id in ["disabled", "readonly"] {
+        assert_eq!(edit_text_field(id, &|text_field| text_field.handle_keyboard_input(&font_context, Some(&String::from("x")), None)), "fixed");
+        assert_eq!(edit_text_field(id, &|text_field| text_field.handle_keyboard_input(&font_context, None, Some(KeyCode::BACKSPACE))), "fixed");
+        assert_eq!(edit_text_field(id, &|text_field| text_field.paste_text(&font_context, &String::from("pasted"))), "fixed");
+        assert_eq!(edit_text_field(id, &|text_field| {
+            text_field.select_all();
+            assert!(text_field.cut_selected_text(&font_context).is_empty());
+        }), "fixed");
+    }
+}
+
+
+#[test]
+fn test_implied_html_head_and_body() {
+
+    let tokens = vec![
+        html_whitespace(" "),
+        html_open("title"),
+        html_open_tag_end(),
+        html_text("page"),
+        html_close("title"),
+
+        html_open("p"),
+        html_open_tag_end(),
+        html_text("text"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+    let doc_node = &document.nodes[document.document_node];
+    assert_eq!(doc_node.children.as_ref().unwrap().len(), 1);
+
+    let html_node = &document.nodes[doc_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&html_node, "html");
+    let html_childs = html_node.children.as_ref().unwrap();
+    assert_eq!(html_childs.len(), 2);
+
+    let head_node = &document.nodes[html_childs[0]];
+    assert_element_name_is(&head_node, "head");
+    assert_eq!(head_node.children.as_ref().unwrap().len(), 1);
+    let title_node = &document.nodes[head_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&title_node, "title");
+    assert_text_on_node_is(&document.nodes[title_node.children.as_ref().unwrap()[0]], "page");
+
+    let body_node = &document.nodes[html_childs[1]];
+    assert_element_name_is(&body_node, "body");
+    assert_eq!(body_node.children.as_ref().unwrap().len(), 1);
+    let p_node = &document.nodes[body_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&p_node, "p");
+    assert_text_on_node_is(&document.nodes[p_node.children.as_ref().unwrap()[0]], "text");
+}
+
+
+#[test]
+fn test_implicitly_closing_p_and_li() {
+
+    let tokens = vec![
+        html_open("p"),
+        html_open_tag_end(),
+        html_text("one"),
+
+        html_open("p"),
+        html_open_tag_end(),
+        html_text("two"),
+
+        html_open("ul"),
+        html_open_tag_end(),
+        html_open("li"),
+        html_open_tag_end(),
+        html_text("first"),
+        html_open("li"),
+        html_open_tag_end(),
+        html_text("second"),
+        html_close("ul"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    let body_childs = body_node.children.as_ref().unwrap();
+    assert_eq!(body_childs.len(), 3);
+
+    assert_element_name_is(&document.nodes[body_childs[0]], "p");
+    assert_text_on_node_is(&document.nodes[document.nodes[body_childs[0]].children.as_ref().unwrap()[0]], "one");
+    assert_element_name_is(&document.nodes[body_childs[1]], "p");
+    assert_text_on_node_is(&document.nodes[document.nodes[body_childs[1]].children.as_ref().unwrap()[0]], "two");
+
+    let ul_node = &document.nodes[body_childs[2]];
+    assert_element_name_is(&ul_node, "ul");
+    let ul_childs = ul_node.children.as_ref().unwrap();
+    assert_eq!(ul_childs.len(), 2);
+    assert_element_name_is(&document.nodes[ul_childs[0]], "li");
+    assert_text_on_node_is(&document.nodes[document.nodes[ul_childs[0]].children.as_ref().unwrap()[0]], "first");
+    assert_element_name_is(&document.nodes[ul_childs[1]], "li");
+    assert_text_on_node_is(&document.nodes[document.nodes[ul_childs[1]].children.as_ref().unwrap()[0]], "second");
+}
+
+
+#[test]
+fn test_table_with_implied_tbody_and_foster_parenting() {
+
+    let tokens = vec![
+        html_open("table"),
+        html_open_tag_end(),
+        html_text("misplaced"),
+        html_open("tr"),
+        html_open_tag_end(),
+        html_open("td"),
+        html_open_tag_end(),
+        html_text("cell"),
+        html_close("table"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    let body_childs = body_node.children.as_ref().unwrap();
+    assert_eq!(body_childs.len(), 2);
+
+    //the text is not allowed in the table, so it is moved to before it
+    assert_text_on_node_is(&document.nodes[body_childs[0]], "misplaced");
+
+    let table_node = &document.nodes[body_childs[1]];
+    assert_element_name_is(&table_node, "table");
+    assert_eq!(table_node.children.as_ref().unwrap().len(), 1);
+
+    let tbody_node = &document.nodes[table_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&tbody_node, "tbody");
+    let tr_node = &document.nodes[tbody_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&tr_node, "tr");
+    let td_node = &document.nodes[tr_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(&td_node, "td");
+    assert_text_on_node_is(&document.nodes[td_node.children.as_ref().unwrap()[0]], "cell");
+}
+
+
+#[test]
+fn test_misnested_formatting_elements() {
+
+    //<b>1<p>2</b>3</p> should become <b>1</b><p><b>2</b>3</p>
+    let tokens = vec![
+        html_open("b"),
+        html_open_tag_end(),
+        html_text("1"),
+        html_open("p"),
+        html_open_tag_end(),
+        html_text("2"),
+        html_close("b"),
+        html_text("3"),
+        html_close("p"),
+    ];
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(tokens, &main_url);
+    let body_node = get_body_node(&document);
+    let body_node = &document.nodes[body_node];
+    let body_childs = body_node.children.as_ref().unwrap();
+    assert_eq!(body_childs.len(), 2);
+
+    let b_node = &document.nodes[body_childs[0]];
+    assert_element_name_is(&b_node, "b");
+    assert_eq!(b_node.children.as_ref().unwrap().len(), 1);
+    assert_text_on_node_is(&document.nodes[b_node.children.as_ref().unwrap()[0]], "1");
+
+    let p_node = &document.nodes[body_childs[1]];
+    assert_element_name_is(&p_node, "p");
+    let p_childs = p_node.children.as_ref().unwrap();
+    assert_eq!(p_childs.len(), 2);
+    assert_element_name_is(&document.nodes[p_childs[0]], "b");
+    assert_text_on_node_is(&document.nodes[document.nodes[p_childs[0]].children.as_ref().unwrap()[0]], "2");
+    assert_text_on_node_is(&document.nodes[p_childs[1]], "3");
+
+    //the parent ids should follow the moved nodes, since styles are inherited via them
+    assert_eq!(document.nodes[p_childs[0]].parent, Some(p_node.id));
+}
+
+
+#[test]
+fn test_template_elements() {
+    let html = "<html><head><template id='in_head'><b>x</b></template></head>\
+                <body><div id='outer'><template id='in_body'><p>x</p><table><td>y</td></table></template>after</div>end</body></html>";
+
+    let main_url = Url::from(&String::from("http://www.google.com"));
+    let document = html_parser::parse(html_lexer::lex_html(html), &main_url);
+
+    //the content of a template stays inside it, and parsing continues where the template was opened
+    let in_head = document.find_node_with_id("in_head").unwrap();
+    assert_element_name_is(&document.nodes[document.nodes[in_head].parent.unwrap()], "head");
+    assert_element_name_is(&document.nodes[document.nodes[in_head].children.as_ref().unwrap()[0]], "b");
+
+    let outer = document.find_node_with_id("outer").unwrap();
+    let outer_childs = document.nodes[outer].children.as_ref().unwrap();
+    assert_eq!(outer_childs.len(), 2);
+    let in_body = &document.nodes[outer_childs[0]];
+    assert_element_name_is(in_body, "template");
+    let template_childs = in_body.children.as_ref().unwrap();
+    assert_eq!(template_childs.len(), 2);
+    assert_element_name_is(&document.nodes[template_childs[0]], "p");
+    assert_element_name_is(&document.nodes[template_childs[1]], "table");
+    assert_text_on_node_is(&document.nodes[outer_childs[1]], "after");
+
+    let body_childs = document.nodes[get_body_node(&document)].children.as_ref().unwrap();
+    assert_text_on_node_is(&document.nodes[*body_childs.last().unwrap()], "end");
+}
+
+
+fn get_body_node(document: &Document) -> DomNodeId {
+    let doc_node = &document.nodes[document.document_node];
+    let html_node = &document.nodes[doc_node.children.as_ref().unwrap()[0]];
+    assert_element_name_is(html_node, "html");
+
+    let body_node_id = html_node.children.as_ref().unwrap()[1];
+    assert_element_name_is(&document.nodes[body_node_id], "body");
+    return body_node_id;
+}
+
+
 fn assert_element_name_is(node: &ElementDomNode, name: &str) {
     assert!(node.name.is_some());
     assert_eq!(node.name.as_ref().unwrap(), name);
@@ -172,3 +760,4 @@ fn assert_text_on_node_is(node: &ElementDomNode, text: &str) {
     assert!(node.text.is_some());
     assert_eq!(node.text.as_ref().unwrap().text_content, text);
 }
+
@@ -0,0 +1,1553 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+
+use crate::arena::Arena;
+use crate::debug::debug_log_warn;
+use crate::dom::{
+    AttributeDomNode,
+    DomNodeId,
+    DomText,
+    ElementDomNode,
+    TagName,
+};
+use crate::script::js_ast::Script;
+
+
+//This implements the tree construction stage of the html spec (https://html.spec.whatwg.org/multipage/parsing.html#tree-construction).
+//It takes care of implied elements (html, head, body, tbody), closing elements implicitly (like p and li), misnested formatting
+//elements (the adoption agency algorithm) and content in tables that is not allowed there (foster parenting).
+//TODO: we don't implement the select, template and frameset insertion modes, and foreign content (svg and mathml) yet
+
+
+pub const VOID_ELEMENTS: [&str; 17] = ["area", "base", "basefont", "bgsound", "br", "col", "embed", "hr", "img", "input", "keygen", "link",
+                                       "meta", "param", "source", "track", "wbr"];
+
+const SPECIAL_ELEMENTS: [&str; 83] = ["address", "applet", "area", "article", "aside", "base", "basefont", "bgsound", "blockquote", "body", "br",
+                                      "button", "caption", "center", "col", "colgroup", "dd", "details", "dir", "div", "dl", "dt", "embed",
+                                      "fieldset", "figcaption", "figure", "footer", "form", "frame", "frameset", "h1", "h2", "h3", "h4", "h5",
+                                      "h6", "head", "header", "hgroup", "hr", "html", "iframe", "img", "input", "keygen", "li", "link",
+                                      "listing", "main", "marquee", "menu", "meta", "nav", "noembed", "noframes", "noscript", "object", "ol",
+                                      "p", "param", "plaintext", "pre", "script", "search", "section", "select", "source", "style", "summary",
+                                      "table", "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "title", "tr", "track", "ul",
+                                      "wbr", "xmp"];
+
+const ELEMENTS_WITH_IMPLIED_END_TAG: [&str; 10] = ["dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc"];
+
+const HEADING_ELEMENTS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+const DEFAULT_SCOPE_BOUNDARIES: [&str; 9] = ["applet", "caption", "html", "table", "td", "th", "marquee", "object", "template"];
+
+const TABLE_SCOPE_BOUNDARIES: [&str; 3] = ["html", "table", "template"];
+
+const MAX_ADOPTION_AGENCY_OUTER_LOOPS: usize = 8;
+
+const MAX_ADOPTION_AGENCY_INNER_LOOPS_KEEPING_FORMATTING_ELEMENTS: usize = 3;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum TreeToken {
+    StartTag { name: String, attributes: Vec<(String, String)> },
+    EndTag { name: String },
+    Text(DomText),
+    EndOfFile,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    AfterBody,
+    AfterAfterBody,
+}
+
+
+#[derive(Clone, Copy)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+enum ActiveFormattingEntry {
+    Marker,
+    //we keep the name and attributes of the token, because the adoption agency algorithm needs to create copies of the element
+    Element { node: DomNodeId, name: String, attributes: Vec<(String, String)> },
+}
+
+
+pub struct TreeBuilder {
+    pub nodes: Arena<ElementDomNode>,
+    pub document_node: DomNodeId,
+
+    insertion_mode: InsertionMode,
+    original_insertion_mode: InsertionMode, //the mode to go back to after the Text insertion mode
+    open_elements: Vec<DomNodeId>,
+    active_formatting_elements: Vec<ActiveFormattingEntry>,
+    head_element: Option<DomNodeId>,
+    form_element: Option<DomNodeId>,
+    foster_parenting: bool,
+}
+impl TreeBuilder {
+    pub fn new(mut nodes: Arena<ElementDomNode>) -> TreeBuilder {
+        //the nodes are added to the given arena, which is empty unless we are parsing a fragment for an existing document
+        let document_node = nodes.insert_with(ElementDomNode::new_document_node);
+
+        return TreeBuilder {
+            nodes,
+            document_node,
+            insertion_mode: InsertionMode::Initial,
+            original_insertion_mode: InsertionMode::Initial,
+            open_elements: Vec::new(),
+            active_formatting_elements: Vec::new(),
+            head_element: None,
+            form_element: None,
+            foster_parenting: false,
+        };
+    }
+
+    pub fn finish(&mut self) {
+        self.process_token(TreeToken::EndOfFile);
+    }
+
+    pub fn get_current_node_attribute(&self, attribute_name: &str) -> Option<String> {
+        if self.open_elements.is_empty() {
+            return None;
+        }
+        return self.nodes[self.current_node()].get_attribute_value(attribute_name);
+    }
+
+    pub fn add_script_to_current_node(&mut self, script: Rc<Script>) {
+        if self.open_elements.is_empty() {
+            debug_log_warn("found a script outside of any element".to_owned());
+            return;
+        }
+
+        let current_node = self.current_node();
+        let current_node = &mut self.nodes[current_node];
+        if current_node.scripts.is_none() {
+            current_node.scripts = Some(Vec::new());
+        }
+        current_node.scripts.as_mut().unwrap().push(script);
+    }
+
+    pub fn process_token(&mut self, token: TreeToken) {
+        match self.insertion_mode {
+            InsertionMode::Initial => { self.handle_initial(token); },
+            InsertionMode::BeforeHtml => { self.handle_before_html(token); },
+            InsertionMode::BeforeHead => { self.handle_before_head(token); },
+            InsertionMode::InHead => { self.handle_in_head(token); },
+            InsertionMode::AfterHead => { self.handle_after_head(token); },
+            InsertionMode::InBody => { self.handle_in_body(token); },
+            InsertionMode::Text => { self.handle_text(token); },
+            InsertionMode::InTable => { self.handle_in_table(token); },
+            InsertionMode::InCaption => { self.handle_in_caption(token); },
+            InsertionMode::InColumnGroup => { self.handle_in_column_group(token); },
+            InsertionMode::InTableBody => { self.handle_in_table_body(token); },
+            InsertionMode::InRow => { self.handle_in_row(token); },
+            InsertionMode::InCell => { self.handle_in_cell(token); },
+            InsertionMode::AfterBody => { self.handle_after_body(token); },
+            InsertionMode::AfterAfterBody => { self.handle_after_after_body(token); },
+        }
+    }
+
+    fn handle_initial(&mut self, token: TreeToken) {
+        //TODO: the doctype should decide between quirks mode and no-quirks mode here, for now we only do no-quirks mode
+        let token = self.process_leading_whitespace(token, false);
+        if token.is_none() {
+            return;
+        }
+
+        self.insertion_mode = InsertionMode::BeforeHtml;
+        self.process_token(token.unwrap());
+    }
+
+    fn handle_before_html(&mut self, token: TreeToken) {
+        let token = self.process_leading_whitespace(token, false);
+        if token.is_none() {
+            return;
+        }
+        let token = token.unwrap();
+
+        match &token {
+            TreeToken::StartTag { name, attributes } if name == "html" => {
+                self.insert_html_element(attributes);
+                self.insertion_mode = InsertionMode::BeforeHead;
+                return;
+            },
+            TreeToken::EndTag { name } if !["head", "body", "html", "br"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.insert_html_element(&Vec::new());
+        self.insertion_mode = InsertionMode::BeforeHead;
+        self.process_token(token);
+    }
+
+    fn handle_before_head(&mut self, token: TreeToken) {
+        let token = self.process_leading_whitespace(token, false);
+        if token.is_none() {
+            return;
+        }
+        let token = token.unwrap();
+
+        match &token {
+            TreeToken::StartTag { name, .. } if name == "html" => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::StartTag { name, attributes } if name == "head" => {
+                self.head_element = Some(self.insert_element(name, attributes));
+                self.insertion_mode = InsertionMode::InHead;
+                return;
+            },
+            TreeToken::EndTag { name } if !["head", "body", "html", "br"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.head_element = Some(self.insert_element("head", &Vec::new()));
+        self.insertion_mode = InsertionMode::InHead;
+        self.process_token(token);
+    }
+
+    fn handle_in_head(&mut self, token: TreeToken) {
+        let token = self.process_leading_whitespace(token, true);
+        if token.is_none() {
+            return;
+        }
+        let token = token.unwrap();
+
+        match &token {
+            TreeToken::StartTag { name, .. } if name == "html" => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::StartTag { name, attributes } => {
+                match name.as_str() {
+                    "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                        self.insert_element(name, attributes);
+                        self.open_elements.pop();
+                        return;
+                    },
+                    "title" | "style" | "script" | "noframes" => {
+                        self.insert_element_with_text_content(name, attributes);
+                        return;
+                    },
+                    "template" => {
+                        //TODO: the spec has a separate "in template" insertion mode, and puts the content in a document fragment. We parse the
+                        //      content as we do in the body, and keep it as children of the template element
+                        self.insert_element(name, attributes);
+                        self.active_formatting_elements.push(ActiveFormattingEntry::Marker);
+                        self.insertion_mode = InsertionMode::InBody;
+                        return;
+                    },
+                    "head" => {
+                        return;
+                    },
+                    _ => {},
+                }
+            },
+            TreeToken::EndTag { name } if name == "template" => {
+                let has_template = self.open_elements.iter().any(|node| self.node_name(*node) == "template");
+                if has_template {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&["template"]);
+                    self.clear_active_formatting_elements_up_to_last_marker();
+                    self.reset_insertion_mode();
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if name == "head" => {
+                self.open_elements.pop();
+                self.insertion_mode = InsertionMode::AfterHead;
+                return;
+            },
+            TreeToken::EndTag { name } if !["body", "html", "br"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.open_elements.pop();
+        self.insertion_mode = InsertionMode::AfterHead;
+        self.process_token(token);
+    }
+
+    fn handle_after_head(&mut self, token: TreeToken) {
+        let token = self.process_leading_whitespace(token, true);
+        if token.is_none() {
+            return;
+        }
+        let token = token.unwrap();
+
+        match &token {
+            TreeToken::StartTag { name, attributes } => {
+                match name.as_str() {
+                    "html" => {
+                        self.handle_in_body(token);
+                        return;
+                    },
+                    "body" => {
+                        self.insert_element(name, attributes);
+                        self.insertion_mode = InsertionMode::InBody;
+                        return;
+                    },
+                    "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style" | "template" | "title" => {
+                        //these belong in the head, even though we already closed it
+                        let head_element = self.head_element.unwrap();
+                        self.open_elements.push(head_element);
+                        self.handle_in_head(token);
+                        self.open_elements.retain(|node| *node != head_element);
+                        return;
+                    },
+                    "head" => {
+                        return;
+                    },
+                    _ => {},
+                }
+            },
+            TreeToken::EndTag { name } if name == "template" => {
+                self.handle_in_head(token);
+                return;
+            },
+            TreeToken::EndTag { name } if !["body", "html", "br"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.insert_element("body", &Vec::new());
+        self.insertion_mode = InsertionMode::InBody;
+        self.process_token(token);
+    }
+
+    fn handle_in_body(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Text(text) => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_text(text);
+            },
+            TreeToken::StartTag { name, attributes } => {
+                self.handle_in_body_start_tag(name, attributes);
+            },
+            TreeToken::EndTag { name } => {
+                self.handle_in_body_end_tag(name);
+            },
+            TreeToken::EndOfFile => {},
+        }
+    }
+
+    fn handle_in_body_start_tag(&mut self, name: String, attributes: Vec<(String, String)>) {
+        match name.as_str() {
+            "html" => {
+                let html_element = self.open_elements[0];
+                self.merge_attributes(html_element, &attributes);
+            },
+            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style" | "template" | "title" => {
+                self.handle_in_head(TreeToken::StartTag { name, attributes });
+            },
+            "body" => {
+                if self.open_elements.len() > 1 && self.node_name(self.open_elements[1]) == "body" {
+                    let body_element = self.open_elements[1];
+                    self.merge_attributes(body_element, &attributes);
+                }
+            },
+            "address" | "article" | "aside" | "blockquote" | "center" | "details" | "dialog" | "dir" | "div" | "dl" | "fieldset" |
+            "figcaption" | "figure" | "footer" | "header" | "hgroup" | "main" | "menu" | "nav" | "ol" | "p" | "search" | "section" |
+            "summary" | "ul" | "pre" | "listing" => {
+                self.close_p_element_in_button_scope();
+                self.insert_element(&name, &attributes);
+            },
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p_element_in_button_scope();
+                if HEADING_ELEMENTS.contains(&self.node_name(self.current_node()).as_str()) {
+                    //headings can't be nested, so the new one closes the current one
+                    self.open_elements.pop();
+                }
+                self.insert_element(&name, &attributes);
+            },
+            "form" => {
+                if self.form_element.is_some() {
+                    return;
+                }
+                self.close_p_element_in_button_scope();
+                self.form_element = Some(self.insert_element(&name, &attributes));
+            },
+            "li" | "dd" | "dt" => {
+                //a new list item closes the previous one, if we are still in it
+                let names_to_close: &[&str] = if name == "li" { &["li"] } else { &["dd", "dt"] };
+
+                for node in self.open_elements.clone().iter().rev() {
+                    let open_name = self.node_name(*node);
+                    if names_to_close.contains(&open_name.as_str()) {
+                        self.generate_implied_end_tags(Some(&open_name));
+                        self.pop_until(&[&open_name]);
+                        break;
+                    }
+                    if is_special(&open_name) && open_name != "address" && open_name != "div" && open_name != "p" {
+                        break;
+                    }
+                }
+
+                self.close_p_element_in_button_scope();
+                self.insert_element(&name, &attributes);
+            },
+            "plaintext" => {
+                //TODO: the lexer should switch to the plaintext state here
+                self.close_p_element_in_button_scope();
+                self.insert_element(&name, &attributes);
+            },
+            "button" => {
+                if self.has_element_in_scope(&["button"], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&["button"]);
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&name, &attributes);
+            },
+            "a" => {
+                //a link inside a link closes the first link
+                let existing_link = self.find_active_formatting_element_after_last_marker("a");
+                if existing_link.is_some() {
+                    let existing_link = existing_link.unwrap();
+                    self.run_adoption_agency("a");
+                    self.remove_active_formatting_element(existing_link);
+                    self.open_elements.retain(|node| *node != existing_link);
+                }
+
+                self.reconstruct_active_formatting_elements();
+                let node = self.insert_element(&name, &attributes);
+                self.push_active_formatting_element(node, name, attributes);
+            },
+            "b" | "big" | "code" | "em" | "font" | "i" | "s" | "small" | "strike" | "strong" | "tt" | "u" | "nobr" => {
+                self.reconstruct_active_formatting_elements();
+                if name == "nobr" && self.has_element_in_scope(&["nobr"], Scope::Default) {
+                    self.run_adoption_agency("nobr");
+                    self.reconstruct_active_formatting_elements();
+                }
+
+                let node = self.insert_element(&name, &attributes);
+                self.push_active_formatting_element(node, name, attributes);
+            },
+            "applet" | "marquee" | "object" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&name, &attributes);
+                self.active_formatting_elements.push(ActiveFormattingEntry::Marker);
+            },
+            "table" => {
+                //TODO: in quirks mode, the p element should not be closed here
+                self.close_p_element_in_button_scope();
+                self.insert_element(&name, &attributes);
+                self.insertion_mode = InsertionMode::InTable;
+            },
+            "area" | "br" | "embed" | "img" | "keygen" | "wbr" | "input" => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&name, &attributes);
+                self.open_elements.pop();
+            },
+            "param" | "source" | "track" => {
+                self.insert_element(&name, &attributes);
+                self.open_elements.pop();
+            },
+            "hr" => {
+                self.close_p_element_in_button_scope();
+                self.insert_element(&name, &attributes);
+                self.open_elements.pop();
+            },
+            "image" => {
+                //this is a very old name for img, which the spec still wants us to support
+                self.handle_in_body_start_tag("img".to_owned(), attributes);
+            },
+            "textarea" | "iframe" | "noembed" => {
+                self.insert_element_with_text_content(&name, &attributes);
+            },
+            "xmp" => {
+                self.close_p_element_in_button_scope();
+                self.reconstruct_active_formatting_elements();
+                self.insert_element_with_text_content(&name, &attributes);
+            },
+            "optgroup" | "option" => {
+                if self.node_name(self.current_node()) == "option" {
+                    self.open_elements.pop();
+                }
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&name, &attributes);
+            },
+            "caption" | "col" | "colgroup" | "frame" | "head" | "tbody" | "td" | "tfoot" | "th" | "thead" | "tr" => {
+                //these are only allowed in specific contexts, which are handled by other insertion modes
+            },
+            _ => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_element(&name, &attributes);
+            },
+        }
+    }
+
+    fn handle_in_body_end_tag(&mut self, name: String) {
+        match name.as_str() {
+            "template" => {
+                self.handle_in_head(TreeToken::EndTag { name });
+            },
+            "body" => {
+                if self.has_element_in_scope(&["body"], Scope::Default) {
+                    self.insertion_mode = InsertionMode::AfterBody;
+                }
+            },
+            "html" => {
+                if self.has_element_in_scope(&["body"], Scope::Default) {
+                    self.insertion_mode = InsertionMode::AfterBody;
+                    self.process_token(TreeToken::EndTag { name });
+                }
+            },
+            "address" | "article" | "aside" | "blockquote" | "button" | "center" | "details" | "dialog" | "dir" | "div" | "dl" |
+            "fieldset" | "figcaption" | "figure" | "footer" | "header" | "hgroup" | "listing" | "main" | "menu" | "nav" | "ol" |
+            "pre" | "search" | "section" | "summary" | "ul" => {
+                if self.has_element_in_scope(&[&name], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[&name]);
+                }
+            },
+            "form" => {
+                let form_element = self.form_element.take();
+                if form_element.is_none() || !self.has_node_in_scope(form_element.unwrap()) {
+                    return;
+                }
+                self.generate_implied_end_tags(None);
+                let form_element = form_element.unwrap();
+                self.open_elements.retain(|node| *node != form_element);
+            },
+            "p" => {
+                if !self.has_element_in_scope(&["p"], Scope::Button) {
+                    //a </p> without an open p element results in an empty p element
+                    self.insert_element("p", &Vec::new());
+                }
+                self.close_p_element_in_button_scope();
+            },
+            "li" => {
+                if self.has_element_in_scope(&["li"], Scope::ListItem) {
+                    self.generate_implied_end_tags(Some("li"));
+                    self.pop_until(&["li"]);
+                }
+            },
+            "dd" | "dt" => {
+                if self.has_element_in_scope(&[&name], Scope::Default) {
+                    self.generate_implied_end_tags(Some(&name));
+                    self.pop_until(&[&name]);
+                }
+            },
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                //any heading end tag closes any open heading
+                if self.has_element_in_scope(&HEADING_ELEMENTS, Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&HEADING_ELEMENTS);
+                }
+            },
+            "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s" | "small" | "strike" | "strong" | "tt" | "u" => {
+                self.run_adoption_agency(&name);
+            },
+            "applet" | "marquee" | "object" => {
+                if self.has_element_in_scope(&[&name], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[&name]);
+                    self.clear_active_formatting_elements_up_to_last_marker();
+                }
+            },
+            "br" => {
+                //</br> is treated as <br>, because browsers have always done that
+                self.handle_in_body_start_tag(name, Vec::new());
+            },
+            _ => {
+                self.handle_any_other_end_tag(&name);
+            },
+        }
+    }
+
+    fn handle_text(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Text(mut text) => {
+                let current_node = self.current_node();
+                let is_start_of_textarea = self.node_name(current_node) == "textarea" && self.nodes[current_node].children.as_ref().unwrap().is_empty();
+                if is_start_of_textarea && text.text_content.starts_with('\n') {
+                    //a newline directly after the textarea start tag is not part of its value
+                    text.text_content.remove(0);
+                    if text.non_breaking_space_positions.is_some() {
+                        let positions = text.non_breaking_space_positions.unwrap().into_iter().map(|position| position - 1).collect();
+                        text.non_breaking_space_positions = Some(positions);
+                    }
+                    if text.text_content.is_empty() {
+                        return;
+                    }
+                }
+                self.insert_text(text);
+            },
+            TreeToken::EndOfFile => {
+                self.open_elements.pop();
+                self.insertion_mode = self.original_insertion_mode;
+                self.process_token(token);
+            },
+            TreeToken::EndTag { .. } => {
+                self.open_elements.pop();
+                self.insertion_mode = self.original_insertion_mode;
+            },
+            TreeToken::StartTag { name, .. } => {
+                //this should not happen, since the lexer does not produce tags inside these elements
+                debug_log_warn(format!("ignoring start tag {} inside an element that can only contain text", name));
+            },
+        }
+    }
+
+    fn handle_in_table(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::Text(text) => {
+                if is_whitespace_only(text) && ["table", "tbody", "tfoot", "thead", "tr"].contains(&self.node_name(self.current_node()).as_str()) {
+                    self.insert_text(match token { TreeToken::Text(text) => text, _ => unreachable!() });
+                    return;
+                }
+            },
+            TreeToken::StartTag { name, attributes } => {
+                match name.as_str() {
+                    "caption" => {
+                        self.clear_stack_back_to(&["table", "template", "html"]);
+                        self.active_formatting_elements.push(ActiveFormattingEntry::Marker);
+                        self.insert_element(name, attributes);
+                        self.insertion_mode = InsertionMode::InCaption;
+                        return;
+                    },
+                    "colgroup" => {
+                        self.clear_stack_back_to(&["table", "template", "html"]);
+                        self.insert_element(name, attributes);
+                        self.insertion_mode = InsertionMode::InColumnGroup;
+                        return;
+                    },
+                    "col" => {
+                        self.clear_stack_back_to(&["table", "template", "html"]);
+                        self.insert_element("colgroup", &Vec::new());
+                        self.insertion_mode = InsertionMode::InColumnGroup;
+                        self.process_token(token);
+                        return;
+                    },
+                    "tbody" | "tfoot" | "thead" => {
+                        self.clear_stack_back_to(&["table", "template", "html"]);
+                        self.insert_element(name, attributes);
+                        self.insertion_mode = InsertionMode::InTableBody;
+                        return;
+                    },
+                    "td" | "th" | "tr" => {
+                        //rows are always in a table section
+                        self.clear_stack_back_to(&["table", "template", "html"]);
+                        self.insert_element("tbody", &Vec::new());
+                        self.insertion_mode = InsertionMode::InTableBody;
+                        self.process_token(token);
+                        return;
+                    },
+                    "table" => {
+                        //a table directly inside a table closes the first one
+                        if self.has_element_in_scope(&["table"], Scope::Table) {
+                            self.pop_until(&["table"]);
+                            self.reset_insertion_mode();
+                            self.process_token(token);
+                        }
+                        return;
+                    },
+                    "style" | "script" | "template" => {
+                        self.handle_in_head(token);
+                        return;
+                    },
+                    "input" => {
+                        let is_hidden = attributes.iter().any(|(name, value)| name == "type" && value.eq_ignore_ascii_case("hidden"));
+                        if is_hidden {
+                            self.insert_element(name, attributes);
+                            self.open_elements.pop();
+                            return;
+                        }
+                    },
+                    "form" => {
+                        if self.form_element.is_none() {
+                            self.form_element = Some(self.insert_element(name, attributes));
+                            self.open_elements.pop();
+                        }
+                        return;
+                    },
+                    _ => {},
+                }
+            },
+            TreeToken::EndTag { name } => {
+                match name.as_str() {
+                    "table" => {
+                        if self.has_element_in_scope(&["table"], Scope::Table) {
+                            self.pop_until(&["table"]);
+                            self.reset_insertion_mode();
+                        }
+                        return;
+                    },
+                    "body" | "caption" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot" | "th" | "thead" | "tr" => {
+                        return;
+                    },
+                    "template" => {
+                        self.handle_in_head(token);
+                        return;
+                    },
+                    _ => {},
+                }
+            },
+            TreeToken::EndOfFile => {
+                self.handle_in_body(token);
+                return;
+            },
+        }
+
+        //Content that is not allowed in a table is moved to just before the table, this is called foster parenting
+        self.foster_parenting = true;
+        self.handle_in_body(token);
+        self.foster_parenting = false;
+    }
+
+    fn handle_in_caption(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::EndTag { name } if name == "caption" => {
+                self.close_caption();
+                return;
+            },
+            TreeToken::StartTag { name, .. } if ["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"].contains(&name.as_str()) => {
+                if self.close_caption() {
+                    self.process_token(token);
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if name == "table" => {
+                if self.close_caption() {
+                    self.process_token(token);
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if ["body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.handle_in_body(token);
+    }
+
+    fn handle_in_column_group(&mut self, token: TreeToken) {
+        let token = self.process_leading_whitespace(token, true);
+        if token.is_none() {
+            return;
+        }
+        let token = token.unwrap();
+
+        match &token {
+            TreeToken::StartTag { name, .. } if name == "html" => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::StartTag { name, attributes } if name == "col" => {
+                self.insert_element(name, attributes);
+                self.open_elements.pop();
+                return;
+            },
+            TreeToken::EndTag { name } if name == "colgroup" => {
+                if self.node_name(self.current_node()) == "colgroup" {
+                    self.open_elements.pop();
+                    self.insertion_mode = InsertionMode::InTable;
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if name == "col" => {
+                return;
+            },
+            TreeToken::EndOfFile => {
+                self.handle_in_body(token);
+                return;
+            },
+            _ => {},
+        }
+
+        if self.node_name(self.current_node()) == "colgroup" {
+            self.open_elements.pop();
+            self.insertion_mode = InsertionMode::InTable;
+            self.process_token(token);
+        }
+    }
+
+    fn handle_in_table_body(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::StartTag { name, attributes } if name == "tr" => {
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_element(name, attributes);
+                self.insertion_mode = InsertionMode::InRow;
+                return;
+            },
+            TreeToken::StartTag { name, .. } if name == "th" || name == "td" => {
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_element("tr", &Vec::new());
+                self.insertion_mode = InsertionMode::InRow;
+                self.process_token(token);
+                return;
+            },
+            TreeToken::EndTag { name } if ["tbody", "tfoot", "thead"].contains(&name.as_str()) => {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                    self.open_elements.pop();
+                    self.insertion_mode = InsertionMode::InTable;
+                }
+                return;
+            },
+            TreeToken::StartTag { name, .. } if ["caption", "col", "colgroup", "tbody", "tfoot", "thead"].contains(&name.as_str()) => {
+                self.close_table_body_and_reprocess(token);
+                return;
+            },
+            TreeToken::EndTag { name } if name == "table" => {
+                self.close_table_body_and_reprocess(token);
+                return;
+            },
+            TreeToken::EndTag { name } if ["body", "caption", "col", "colgroup", "html", "td", "th", "tr"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.handle_in_table(token);
+    }
+
+    fn handle_in_row(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::StartTag { name, attributes } if name == "th" || name == "td" => {
+                self.clear_stack_back_to(&["tr", "template", "html"]);
+                self.insert_element(name, attributes);
+                self.insertion_mode = InsertionMode::InCell;
+                self.active_formatting_elements.push(ActiveFormattingEntry::Marker);
+                return;
+            },
+            TreeToken::EndTag { name } if name == "tr" => {
+                self.close_row();
+                return;
+            },
+            TreeToken::StartTag { name, .. } if ["caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr"].contains(&name.as_str()) => {
+                if self.close_row() {
+                    self.process_token(token);
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if name == "table" => {
+                if self.close_row() {
+                    self.process_token(token);
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if ["tbody", "tfoot", "thead"].contains(&name.as_str()) => {
+                if self.has_element_in_scope(&[name], Scope::Table) && self.close_row() {
+                    self.process_token(token);
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if ["body", "caption", "col", "colgroup", "html", "td", "th"].contains(&name.as_str()) => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.handle_in_table(token);
+    }
+
+    fn handle_in_cell(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::EndTag { name } if name == "td" || name == "th" => {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_until(&[name]);
+                    self.clear_active_formatting_elements_up_to_last_marker();
+                    self.insertion_mode = InsertionMode::InRow;
+                }
+                return;
+            },
+            TreeToken::StartTag { name, .. } if ["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"].contains(&name.as_str()) => {
+                if self.has_element_in_scope(&["td", "th"], Scope::Table) {
+                    self.close_cell();
+                    self.process_token(token);
+                }
+                return;
+            },
+            TreeToken::EndTag { name } if ["body", "caption", "col", "colgroup", "html"].contains(&name.as_str()) => {
+                return;
+            },
+            TreeToken::EndTag { name } if ["table", "tbody", "tfoot", "thead", "tr"].contains(&name.as_str()) => {
+                if self.has_element_in_scope(&[name], Scope::Table) {
+                    self.close_cell();
+                    self.process_token(token);
+                }
+                return;
+            },
+            _ => {},
+        }
+
+        self.handle_in_body(token);
+    }
+
+    fn handle_after_body(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::Text(text) if is_whitespace_only(text) => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::StartTag { name, .. } if name == "html" => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::EndTag { name } if name == "html" => {
+                self.insertion_mode = InsertionMode::AfterAfterBody;
+                return;
+            },
+            TreeToken::EndOfFile => {
+                return;
+            },
+            _ => {},
+        }
+
+        //content after </body> still ends up in the body
+        self.insertion_mode = InsertionMode::InBody;
+        self.process_token(token);
+    }
+
+    fn handle_after_after_body(&mut self, token: TreeToken) {
+        match &token {
+            TreeToken::Text(text) if is_whitespace_only(text) => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::StartTag { name, .. } if name == "html" => {
+                self.handle_in_body(token);
+                return;
+            },
+            TreeToken::EndOfFile => {
+                return;
+            },
+            _ => {},
+        }
+
+        self.insertion_mode = InsertionMode::InBody;
+        self.process_token(token);
+    }
+
+    fn process_leading_whitespace(&mut self, token: TreeToken, insert_whitespace: bool) -> Option<TreeToken> {
+        //Several insertion modes handle whitespace differently from other text, so we split it off. We return the rest of the
+        //token, if there is any left to process.
+        match token {
+            TreeToken::Text(text) => {
+                let (whitespace, rest) = split_leading_whitespace(text);
+                if whitespace.is_some() && insert_whitespace {
+                    self.insert_text(whitespace.unwrap());
+                }
+                return rest.map(TreeToken::Text);
+            },
+            _ => {
+                return Some(token);
+            },
+        }
+    }
+
+    fn current_node(&self) -> DomNodeId {
+        return *self.open_elements.last().unwrap();
+    }
+
+    fn create_element(&mut self, name: &str, attributes: &Vec<(String, String)>) -> DomNodeId {
+        return self.nodes.insert_with(|id| {
+            let mut attribute_nodes = Vec::new();
+            for (attribute_name, attribute_value) in attributes {
+                attribute_nodes.push(AttributeDomNode {
+                    name: attribute_name.clone(),
+                    value: attribute_value.clone(),
+                    parent_id: id,
+                });
+            }
+
+            return ElementDomNode {
+                id,
+                parent: None, //this is set when the node is inserted into the tree
+                is_document_node: false,
+                dirty: false,
+                text: None,
+                name: Some(name.to_owned()),
+                name_for_layout: TagName::from_string(&name.to_owned()),
+                children: Some(Vec::new()),
+                attributes: Some(attribute_nodes),
+                image: None,
+                img_job_tracker: None,
+                scripts: None,
+                page_component: None,
+                default_value: None,
+                cached_styles: None,
+            };
+        });
+    }
+
+    fn insert_html_element(&mut self, attributes: &Vec<(String, String)>) {
+        let html_element = self.create_element("html", attributes);
+        self.insert_node_at(self.document_node, None, html_element);
+        self.open_elements.push(html_element);
+    }
+
+    fn insert_element(&mut self, name: &str, attributes: &Vec<(String, String)>) -> DomNodeId {
+        let node = self.create_element(name, attributes);
+        let (parent, index) = self.appropriate_insertion_place(None);
+        self.insert_node_at(parent, index, node);
+        self.open_elements.push(node);
+        return node;
+    }
+
+    fn insert_element_with_text_content(&mut self, name: &str, attributes: &Vec<(String, String)>) {
+        //elements like title and script only contain text, which we collect in the Text insertion mode
+        self.insert_element(name, attributes);
+        self.original_insertion_mode = self.insertion_mode;
+        self.insertion_mode = InsertionMode::Text;
+    }
+
+    fn insert_text(&mut self, text: DomText) {
+        let (parent, index) = self.appropriate_insertion_place(None);
+        if self.nodes[parent].is_document_node {
+            return;
+        }
+
+        let previous_sibling = {
+            let siblings = self.nodes[parent].children.as_ref().unwrap();
+            let previous_sibling_idx = if index.is_some() { index.unwrap().checked_sub(1) } else { siblings.len().checked_sub(1) };
+            previous_sibling_idx.map(|idx| siblings[idx])
+        };
+
+        if previous_sibling.is_some() && self.nodes[previous_sibling.unwrap()].text.is_some() {
+            //text directly after other text is added to the same text node
+            let previous_sibling = &mut self.nodes[previous_sibling.unwrap()];
+            let previous_text = previous_sibling.text.as_mut().unwrap();
+
+            let offset = previous_text.text_content.len();
+            if text.non_breaking_space_positions.is_some() {
+                if previous_text.non_breaking_space_positions.is_none() {
+                    previous_text.non_breaking_space_positions = Some(HashSet::new());
+                }
+                for position in text.non_breaking_space_positions.unwrap() {
+                    previous_text.non_breaking_space_positions.as_mut().unwrap().insert(position + offset);
+                }
+            }
+            previous_text.text_content.push_str(&text.text_content);
+            return;
+        }
+
+        let text_node = self.nodes.insert_with(|id| ElementDomNode {
+            id,
+            parent: None,
+            is_document_node: false,
+            dirty: false,
+            text: Some(text),
+            name: None,
+            name_for_layout: TagName::Other,
+            children: None,
+            attributes: None,
+            image: None,
+            img_job_tracker: None,
+            scripts: None,
+            page_component: None,
+            default_value: None,
+            cached_styles: None,
+        });
+        self.insert_node_at(parent, index, text_node);
+    }
+
+    fn appropriate_insertion_place(&self, override_target: Option<DomNodeId>) -> (DomNodeId, Option<usize>) {
+        //returns the parent to insert into, and the index in its children to insert at (or None to append)
+        let target = if override_target.is_some() { override_target.unwrap() } else { self.current_node() };
+
+        if !self.foster_parenting || !["table", "tbody", "tfoot", "thead", "tr"].contains(&self.node_name(target).as_str()) {
+            return (target, None);
+        }
+
+        let last_table_idx = self.open_elements.iter().rposition(|node| self.node_name(*node) == "table");
+        if last_table_idx.is_none() {
+            return (self.open_elements[0], None);
+        }
+        let last_table_idx = last_table_idx.unwrap();
+        let last_table = self.open_elements[last_table_idx];
+
+        let table_parent = self.nodes[last_table].parent;
+        if table_parent.is_some() {
+            let table_position = self.nodes[table_parent.unwrap()].children.as_ref().unwrap().iter().position(|child| *child == last_table);
+            if table_position.is_some() {
+                return (table_parent.unwrap(), table_position);
+            }
+        }
+
+        return (self.open_elements[last_table_idx - 1], None);
+    }
+
+    fn detach_from_parent(&mut self, node: DomNodeId) {
+        let parent = self.nodes[node].parent;
+        if parent.is_some() {
+            self.nodes[parent.unwrap()].children.as_mut().unwrap().retain(|child| *child != node);
+        }
+    }
+
+    fn has_element_in_scope(&self, names: &[&str], scope: Scope) -> bool {
+        for node in self.open_elements.iter().rev() {
+            let name = self.node_name(*node);
+            if names.contains(&name.as_str()) {
+                return true;
+            }
+            if is_scope_boundary(&name, scope) {
+                return false;
+            }
+        }
+        return false;
+    }
+
+    fn has_node_in_scope(&self, target_node: DomNodeId) -> bool {
+        for node in self.open_elements.iter().rev() {
+            if *node == target_node {
+                return true;
+            }
+            if is_scope_boundary(&self.node_name(*node), Scope::Default) {
+                return false;
+            }
+        }
+        return false;
+    }
+
+    fn pop_until(&mut self, names: &[&str]) {
+        while !self.open_elements.is_empty() {
+            let popped_node = self.open_elements.pop().unwrap();
+            if names.contains(&self.node_name(popped_node).as_str()) {
+                return;
+            }
+        }
+    }
+
+    fn clear_stack_back_to(&mut self, names: &[&str]) {
+        while !names.contains(&self.node_name(self.current_node()).as_str()) {
+            self.open_elements.pop();
+        }
+    }
+
+    fn generate_implied_end_tags(&mut self, except_for: Option<&str>) {
+        loop {
+            let current_name = self.node_name(self.current_node());
+            if !ELEMENTS_WITH_IMPLIED_END_TAG.contains(&current_name.as_str()) || Some(current_name.as_str()) == except_for {
+                return;
+            }
+            self.open_elements.pop();
+        }
+    }
+
+    fn close_p_element_in_button_scope(&mut self) {
+        if self.has_element_in_scope(&["p"], Scope::Button) {
+            self.generate_implied_end_tags(Some("p"));
+            self.pop_until(&["p"]);
+        }
+    }
+
+    fn close_caption(&mut self) -> bool {
+        //returns whether there was a caption to close
+        if !self.has_element_in_scope(&["caption"], Scope::Table) {
+            return false;
+        }
+
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["caption"]);
+        self.clear_active_formatting_elements_up_to_last_marker();
+        self.insertion_mode = InsertionMode::InTable;
+        return true;
+    }
+
+    fn close_table_body_and_reprocess(&mut self, token: TreeToken) {
+        if !self.has_element_in_scope(&["tbody", "thead", "tfoot"], Scope::Table) {
+            return;
+        }
+
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+        self.open_elements.pop();
+        self.insertion_mode = InsertionMode::InTable;
+        self.process_token(token);
+    }
+
+    fn close_row(&mut self) -> bool {
+        //returns whether there was a row to close
+        if !self.has_element_in_scope(&["tr"], Scope::Table) {
+            return false;
+        }
+
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+        self.open_elements.pop();
+        self.insertion_mode = InsertionMode::InTableBody;
+        return true;
+    }
+
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["td", "th"]);
+        self.clear_active_formatting_elements_up_to_last_marker();
+        self.insertion_mode = InsertionMode::InRow;
+    }
+
+    fn reset_insertion_mode(&mut self) {
+        for (idx, node) in self.open_elements.iter().enumerate().rev() {
+            let is_last = idx == 0;
+
+            match self.node_name(*node).as_str() {
+                "td" | "th" if !is_last => { self.insertion_mode = InsertionMode::InCell; },
+                "tr" => { self.insertion_mode = InsertionMode::InRow; },
+                "tbody" | "thead" | "tfoot" => { self.insertion_mode = InsertionMode::InTableBody; },
+                "caption" => { self.insertion_mode = InsertionMode::InCaption; },
+                "colgroup" => { self.insertion_mode = InsertionMode::InColumnGroup; },
+                "table" => { self.insertion_mode = InsertionMode::InTable; },
+                "template" => { self.insertion_mode = InsertionMode::InBody; }, //we parse the content of templates as we do in the body
+                "head" if !is_last => { self.insertion_mode = InsertionMode::InHead; },
+                "body" => { self.insertion_mode = InsertionMode::InBody; },
+                "html" => {
+                    self.insertion_mode = if self.head_element.is_none() { InsertionMode::BeforeHead } else { InsertionMode::AfterHead };
+                },
+                _ => {
+                    if is_last {
+                        self.insertion_mode = InsertionMode::InBody;
+                    } else {
+                        continue;
+                    }
+                },
+            }
+            return;
+        }
+    }
+
+    fn push_active_formatting_element(&mut self, node: DomNodeId, name: String, attributes: Vec<(String, String)>) {
+        //There can be at most 3 identical elements after the last marker, the earliest one is removed when adding a 4th (the "Noah's Ark" clause)
+        let mut identical_entry_positions = Vec::new();
+        for (idx, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+            match entry {
+                ActiveFormattingEntry::Marker => { break; },
+                ActiveFormattingEntry::Element { name: entry_name, attributes: entry_attributes, .. } => {
+                    if *entry_name == name && attributes_are_equal(entry_attributes, &attributes) {
+                        identical_entry_positions.push(idx);
+                    }
+                },
+            }
+        }
+        if identical_entry_positions.len() >= 3 {
+            self.active_formatting_elements.remove(*identical_entry_positions.last().unwrap());
+        }
+
+        self.active_formatting_elements.push(ActiveFormattingEntry::Element { node, name, attributes });
+    }
+
+    fn find_active_formatting_element_after_last_marker(&self, name: &str) -> Option<DomNodeId> {
+        let position = self.find_active_formatting_position_after_last_marker(name);
+        if position.is_none() {
+            return None;
+        }
+        match &self.active_formatting_elements[position.unwrap()] {
+            ActiveFormattingEntry::Element { node, .. } => { return Some(*node); },
+            ActiveFormattingEntry::Marker => { return None; },
+        }
+    }
+
+    fn find_active_formatting_position_after_last_marker(&self, name: &str) -> Option<usize> {
+        for (idx, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+            match entry {
+                ActiveFormattingEntry::Marker => { return None; },
+                ActiveFormattingEntry::Element { name: entry_name, .. } => {
+                    if entry_name == name {
+                        return Some(idx);
+                    }
+                },
+            }
+        }
+        return None;
+    }
+
+    fn active_formatting_position(&self, node: DomNodeId) -> Option<usize> {
+        return self.active_formatting_elements.iter().position(|entry| {
+            match entry {
+                ActiveFormattingEntry::Element { node: entry_node, .. } => *entry_node == node,
+                ActiveFormattingEntry::Marker => false,
+            }
+        });
+    }
+
+    fn remove_active_formatting_element(&mut self, node: DomNodeId) {
+        let position = self.active_formatting_position(node);
+        if position.is_some() {
+            self.active_formatting_elements.remove(position.unwrap());
+        }
+    }
+
+    fn clear_active_formatting_elements_up_to_last_marker(&mut self) {
+        while !self.active_formatting_elements.is_empty() {
+            let entry = self.active_formatting_elements.pop().unwrap();
+            if matches!(entry, ActiveFormattingEntry::Marker) {
+                return;
+            }
+        }
+    }
+
+    fn is_open_element(&self, node: DomNodeId) -> bool {
+        return self.open_elements.contains(&node);
+    }
+
+    fn reconstruct_active_formatting_elements(&mut self) {
+        //Formatting elements that were implicitly closed (for example by a </p>) are re-opened, so <b><p>a</p>b</b> makes both texts bold
+        let is_open_or_marker = |builder: &TreeBuilder, entry: &ActiveFormattingEntry| {
+            match entry {
+                ActiveFormattingEntry::Marker => true,
+                ActiveFormattingEntry::Element { node, .. } => builder.is_open_element(*node),
+            }
+        };
+
+        if self.active_formatting_elements.is_empty() || is_open_or_marker(self, self.active_formatting_elements.last().unwrap()) {
+            return;
+        }
+
+        let mut first_entry_to_reopen = self.active_formatting_elements.len() - 1;
+        while first_entry_to_reopen > 0 && !is_open_or_marker(self, &self.active_formatting_elements[first_entry_to_reopen - 1]) {
+            first_entry_to_reopen -= 1;
+        }
+
+        for entry_idx in first_entry_to_reopen..self.active_formatting_elements.len() {
+            let (name, attributes) = match &self.active_formatting_elements[entry_idx] {
+                ActiveFormattingEntry::Element { name, attributes, .. } => (name.clone(), attributes.clone()),
+                ActiveFormattingEntry::Marker => { continue; },
+            };
+
+            let node = self.insert_element(&name, &attributes);
+            self.active_formatting_elements[entry_idx] = ActiveFormattingEntry::Element { node, name, attributes };
+        }
+    }
+
+    fn run_adoption_agency(&mut self, subject: &str) {
+        //This handles misnested formatting elements, like in <b>1<p>2</b>3</p>, see https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+        let current_node = self.current_node();
+        if self.node_name(current_node) == subject && self.active_formatting_position(current_node).is_none() {
+            self.open_elements.pop();
+            return;
+        }
+
+        for _ in 0..MAX_ADOPTION_AGENCY_OUTER_LOOPS {
+            let formatting_entry_idx = self.find_active_formatting_position_after_last_marker(subject);
+            if formatting_entry_idx.is_none() {
+                self.handle_any_other_end_tag(subject);
+                return;
+            }
+            let formatting_entry_idx = formatting_entry_idx.unwrap();
+            let (formatting_element, formatting_name, formatting_attributes) = match &self.active_formatting_elements[formatting_entry_idx] {
+                ActiveFormattingEntry::Element { node, name, attributes } => (*node, name.clone(), attributes.clone()),
+                ActiveFormattingEntry::Marker => { panic!("the formatting element position should never point to a marker"); },
+            };
+
+            let formatting_stack_idx = self.open_elements.iter().position(|node| *node == formatting_element);
+            if formatting_stack_idx.is_none() {
+                self.active_formatting_elements.remove(formatting_entry_idx);
+                return;
+            }
+            let formatting_stack_idx = formatting_stack_idx.unwrap();
+            if !self.has_node_in_scope(formatting_element) {
+                return;
+            }
+
+            let furthest_block_idx = (formatting_stack_idx + 1..self.open_elements.len())
+                                         .find(|idx| is_special(&self.node_name(self.open_elements[*idx])));
+            if furthest_block_idx.is_none() {
+                //the simple case: everything in the formatting element is closed together with it
+                self.open_elements.truncate(formatting_stack_idx);
+                self.active_formatting_elements.remove(formatting_entry_idx);
+                return;
+            }
+            let furthest_block = self.open_elements[furthest_block_idx.unwrap()];
+            let common_ancestor = self.open_elements[formatting_stack_idx - 1];
+            let mut bookmark = formatting_entry_idx;
+
+            let mut node_idx = furthest_block_idx.unwrap();
+            let mut last_node = furthest_block;
+            let mut inner_loop_counter = 0;
+            loop {
+                inner_loop_counter += 1;
+                node_idx -= 1;
+                let node = self.open_elements[node_idx];
+                if node == formatting_element {
+                    break;
+                }
+
+                let mut node_entry_idx = self.active_formatting_position(node);
+                if inner_loop_counter > MAX_ADOPTION_AGENCY_INNER_LOOPS_KEEPING_FORMATTING_ELEMENTS && node_entry_idx.is_some() {
+                    self.active_formatting_elements.remove(node_entry_idx.unwrap());
+                    if node_entry_idx.unwrap() < bookmark {
+                        bookmark -= 1;
+                    }
+                    node_entry_idx = None;
+                }
+                if node_entry_idx.is_none() {
+                    self.open_elements.remove(node_idx);
+                    continue;
+                }
+                let node_entry_idx = node_entry_idx.unwrap();
+
+                let (name, attributes) = match &self.active_formatting_elements[node_entry_idx] {
+                    ActiveFormattingEntry::Element { name, attributes, .. } => (name.clone(), attributes.clone()),
+                    ActiveFormattingEntry::Marker => { panic!("an element position should never point to a marker"); },
+                };
+                let new_node = self.create_element(&name, &attributes);
+                self.active_formatting_elements[node_entry_idx] = ActiveFormattingEntry::Element { node: new_node, name, attributes };
+                self.open_elements[node_idx] = new_node;
+
+                if last_node == furthest_block {
+                    bookmark = node_entry_idx + 1;
+                }
+
+                self.detach_from_parent(last_node);
+                self.insert_node_at(new_node, None, last_node);
+                last_node = new_node;
+            }
+
+            self.detach_from_parent(last_node);
+            let (parent, index) = self.appropriate_insertion_place(Some(common_ancestor));
+            self.insert_node_at(parent, index, last_node);
+
+            //the children of the furthest block move into a new copy of the formatting element
+            let new_formatting_element = self.create_element(&formatting_name, &formatting_attributes);
+            let furthest_block_children = mem::take(self.nodes[furthest_block].children.as_mut().unwrap());
+            for child in &furthest_block_children {
+                self.nodes[*child].parent = Some(new_formatting_element);
+            }
+            self.nodes[new_formatting_element].children = Some(furthest_block_children);
+            self.insert_node_at(furthest_block, None, new_formatting_element);
+
+            let formatting_entry_idx = self.active_formatting_position(formatting_element).unwrap();
+            self.active_formatting_elements.remove(formatting_entry_idx);
+            if formatting_entry_idx < bookmark {
+                bookmark -= 1;
+            }
+            self.active_formatting_elements.insert(bookmark, ActiveFormattingEntry::Element {
+                node: new_formatting_element, name: formatting_name, attributes: formatting_attributes
+            });
+
+            self.open_elements.retain(|node| *node != formatting_element);
+            let furthest_block_idx = self.open_elements.iter().position(|node| *node == furthest_block).unwrap();
+            self.open_elements.insert(furthest_block_idx + 1, new_formatting_element);
+        }
+    }
+
+    fn handle_any_other_end_tag(&mut self, name: &str) {
+        for idx in (0..self.open_elements.len()).rev() {
+            let open_name = self.node_name(self.open_elements[idx]);
+            if open_name == name {
+                self.generate_implied_end_tags(Some(name));
+                self.open_elements.truncate(idx);
+                return;
+            }
+            if is_special(&open_name) {
+                //we don't close elements like div or p for an end tag of an element inside them that was never opened
+                return;
+            }
+        }
+    }
+
+    fn node_name(&self, node: DomNodeId) -> String {
+        return self.nodes[node].name.clone().unwrap_or_default();
+    }
+
+    fn insert_node_at(&mut self, parent: DomNodeId, index: Option<usize>, node: DomNodeId) {
+        self.nodes[node].parent = Some(parent);
+
+        let parent = &mut self.nodes[parent];
+        if parent.children.is_none() {
+            parent.children = Some(Vec::new());
+        }
+        let children = parent.children.as_mut().unwrap();
+        if index.is_some() {
+            children.insert(index.unwrap(), node);
+        } else {
+            children.push(node);
+        }
+    }
+
+    fn merge_attributes(&mut self, node: DomNodeId, attributes: &Vec<(String, String)>) {
+        //attributes on a second html or body tag are added to the existing element, if they are not there yet
+        let existing_attributes = self.nodes[node].attributes.as_mut().unwrap();
+
+        for (name, value) in attributes {
+            if existing_attributes.iter().any(|attribute| attribute.name == *name) {
+                continue;
+            }
+            existing_attributes.push(AttributeDomNode { name: name.clone(), value: value.clone(), parent_id: node });
+        }
+    }
+}
+
+
+fn is_special(name: &str) -> bool {
+    return SPECIAL_ELEMENTS.contains(&name);
+}
+
+
+fn is_scope_boundary(name: &str, scope: Scope) -> bool {
+    return match scope {
+        Scope::Default => DEFAULT_SCOPE_BOUNDARIES.contains(&name),
+        Scope::ListItem => DEFAULT_SCOPE_BOUNDARIES.contains(&name) || name == "ol" || name == "ul",
+        Scope::Button => DEFAULT_SCOPE_BOUNDARIES.contains(&name) || name == "button",
+        Scope::Table => TABLE_SCOPE_BOUNDARIES.contains(&name),
+    };
+}
+
+
+fn attributes_are_equal(first: &Vec<(String, String)>, second: &Vec<(String, String)>) -> bool {
+    return first.len() == second.len() && first.iter().all(|attribute| second.contains(attribute));
+}
+
+
+fn is_whitespace_only(text: &DomText) -> bool {
+    return text.non_breaking_space_positions.is_none() && text.text_content.trim().is_empty();
+}
+
+
+fn split_leading_whitespace(text: DomText) -> (Option<DomText>, Option<DomText>) {
+    //non breaking spaces are not stored as characters, but as positions in the text, so they end the whitespace as well
+    let is_non_breaking_space_position = |position: usize| {
+        return text.non_breaking_space_positions.is_some() && text.non_breaking_space_positions.as_ref().unwrap().contains(&position);
+    };
+
+    let mut split_idx = text.text_content.len();
+    for (idx, c) in text.text_content.char_indices() {
+        if !c.is_ascii_whitespace() || is_non_breaking_space_position(idx) {
+            split_idx = idx;
+            break;
+        }
+    }
+    if split_idx == 0 {
+        return (None, Some(text));
+    }
+
+    let whitespace = DomText { text_content: text.text_content[..split_idx].to_owned(), non_breaking_space_positions: None };
+
+    let rest_positions = text.non_breaking_space_positions.map(|positions| {
+        return positions.iter().map(|position| position - split_idx).collect::<HashSet<usize>>();
+    });
+    let rest = DomText { text_content: text.text_content[split_idx..].to_owned(), non_breaking_space_positions: rest_positions };
+
+    if rest.text_content.is_empty() && rest.non_breaking_space_positions.is_none() {
+        return (Some(whitespace), None);
+    }
+    return (Some(whitespace), Some(rest));
+}
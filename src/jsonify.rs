@@ -1,23 +1,22 @@
-use std::cell::RefCell;
-use std::iter::Peekable;
-use std::rc::Rc;
-use std::str::CharIndices;
+#[cfg(test)] use std::iter::Peekable;
+#[cfg(test)] use std::str::CharIndices;
 
 
 use crate::color::Color;
-use crate::dom::{
-    DomText,
-    ElementDomNode,
-    get_next_dom_node_interal_id
-};
+use crate::platform::fonts::{Font, FontFace};
+#[cfg(test)] use crate::dom::{DomText, ElementDomNode};
+use crate::dom::{Document, DomNodeId};
 use crate::layout::{
-    LayoutNode,
+    Display,
+    FullLayout,
     LayoutNodeContent,
+    LayoutNodeId,
     Rect,
     TextLayoutRect
 };
 
 //TODO: this function should have some tests by itself
+#[cfg(test)]
 pub fn compare_json(json1: &String, json2: &String) -> bool {
     //compare the strings, but ignore whitespace (when not in quotes)
 
@@ -65,12 +64,69 @@ pub fn compare_json(json1: &String, json2: &String) -> bool {
 
 
 
-pub fn layout_node_to_json(layout_node: &LayoutNode) -> String {
+pub fn page_to_json(document: &Document, full_layout: &FullLayout) -> String {
+    //this is what we write to disk for debugging (with Ctrl+Shift+J or --dump-json), the dom and layout trees of the page as they are now
+    return format!("{{\"url\": \"{}\", \"dom\": {}, \"layout\": {}}}", escape_json_string(&document.page_url.to_string()),
+                   dom_node_to_json(document, document.document_node), layout_node_to_json(full_layout, full_layout.root_node));
+}
+
+
+pub fn dom_node_to_json(document: &Document, dom_node_id: DomNodeId) -> String {
+    let dom_node = &document.nodes[dom_node_id];
+    let mut buffer = String::new();
+
+    buffer += format!("{{\"id\": {}", dom_node_id.index()).as_str();
+
+    if dom_node.text.is_some() {
+        buffer += ", \"text\": \"";
+        buffer += escape_json_string(&dom_node.text.as_ref().unwrap().text_content).as_str();
+        buffer.push('"');
+    } else {
+        buffer += ", \"name\": ";
+        buffer += match &dom_node.name {
+            Some(name) => format!("\"{}\"", escape_json_string(name)),
+            None => String::from("null"),
+        }.as_str();
+
+        buffer += ", \"attributes\": {";
+        if dom_node.attributes.is_some() {
+            let attributes: Vec<String> = dom_node.attributes.as_ref().unwrap().iter().map(|attribute| {
+                return format!("\"{}\": \"{}\"", escape_json_string(&attribute.name), escape_json_string(&attribute.value));
+            }).collect();
+            buffer += attributes.join(", ").as_str();
+        }
+        buffer.push('}');
+
+        buffer += ", \"childs\": [";
+        if dom_node.children.is_some() {
+            let childs: Vec<String> = dom_node.children.as_ref().unwrap().iter().map(|child_id| dom_node_to_json(document, *child_id)).collect();
+            buffer += childs.join(",").as_str();
+        }
+        buffer.push(']');
+    }
+
+    buffer.push('}');
+    return buffer;
+}
+
+
+pub fn layout_node_to_json(full_layout: &FullLayout, layout_node_id: LayoutNodeId) -> String {
+    let layout_node = &full_layout.nodes[layout_node_id];
 
     let mut buffer = String::new();
 
     buffer += "{";
 
+    buffer += "\"kind\": \"";
+    buffer += layout_node.content.kind_name();
+    buffer += "\", ";
+
+    buffer += "\"display\": \"";
+    buffer += if layout_node.display == Display::Block { "block" } else { "inline" };
+    buffer += "\", ";
+
+    buffer += format!("\"visible\": {}, ", layout_node.visible).as_str();
+
     match &layout_node.content {
         LayoutNodeContent::TextLayoutNode(text_layout_node) => {
             buffer += "\"color\":";
@@ -87,14 +143,20 @@ pub fn layout_node_to_json(layout_node: &LayoutNode) -> String {
             buffer += rect_to_json(&box_layout_node.location).as_str();
 
             buffer += ", \"childs\":";
-            buffer += childs_to_json(&layout_node.children).as_str();
+            buffer += childs_to_json(full_layout, &layout_node.children).as_str();
+        },
+        LayoutNodeContent::NoContent => {
+            buffer += "\"childs\":";
+            buffer += childs_to_json(full_layout, &layout_node.children).as_str();
+        },
+        _ => {
+            //for the other nodes we only write where they are, what is in them comes from the dom
+            buffer += "\"location\":";
+            buffer += rect_to_json(&layout_node.get_bounding_box().unwrap()).as_str();
+
+            buffer += ", \"childs\":";
+            buffer += childs_to_json(full_layout, &layout_node.children).as_str();
         },
-        LayoutNodeContent::ImageLayoutNode(_) => todo!(),  //TODO: implement
-        LayoutNodeContent::ButtonLayoutNode(_) => todo!(),  //TODO: implement
-        LayoutNodeContent::TextInputLayoutNode(_) => todo!(),  //TODO: implement
-        LayoutNodeContent::TableLayoutNode(_) => todo!(),  //TODO: implement
-        LayoutNodeContent::TableCellLayoutNode(_) => todo!(),  //TODO: implement
-        LayoutNodeContent::NoContent => { },
     }
 
     buffer += "}";
@@ -107,8 +169,9 @@ pub fn color_to_json(color: &Color) -> String {
     let r = color.r;
     let g = color.g;
     let b = color.b;
+    let a = color.a;
 
-    return format!("[{r}, {g}, {b}]");   
+    return format!("[{r}, {g}, {b}, {a}]");
 }
 
 
@@ -124,12 +187,18 @@ pub fn text_rects_to_json(rects: &Vec<TextLayoutRect>) -> String {
         }
 
         buffer += "{ \"text\": \"";
-        buffer += rect.text.as_str();
+        buffer += escape_json_string(&rect.text).as_str();
         buffer += "\", ";
 
         buffer += "\"position\":";
         buffer += rect_to_json(&rect.location).as_str();
 
+        buffer += ", \"font\":";
+        buffer += font_to_json(&rect.font).as_str();
+
+        buffer += ", \"font_color\":";
+        buffer += color_to_json(&rect.font_color).as_str();
+
         buffer.push('}');
         first = false;
     }
@@ -139,12 +208,105 @@ pub fn text_rects_to_json(rects: &Vec<TextLayoutRect>) -> String {
 }
 
 
+pub fn escape_json_string(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            _ => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
+
+pub fn pretty_print_json(json: &str) -> String {
+    //this puts every value on its own line, indented by how deep it is, json that is not valid is still printed, just maybe not as nicely
+    let mut pretty_json = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaping = false;
+    let mut chars = json.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            pretty_json.push(c);
+            if escaping {
+                escaping = false;
+            } else if c == '\\' {
+                escaping = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                pretty_json.push(c);
+            },
+            '{' | '[' => {
+                pretty_json.push(c);
+                while chars.peek().is_some() && chars.peek().unwrap().is_whitespace() {
+                    chars.next();
+                }
+                if chars.peek() == Some(&'}') || chars.peek() == Some(&']') {
+                    continue; //empty objects and arrays stay on one line
+                }
+                depth += 1;
+                push_json_newline(&mut pretty_json, depth);
+            },
+            '}' | ']' => {
+                if !pretty_json.ends_with('{') && !pretty_json.ends_with('[') {
+                    depth = if depth > 0 { depth - 1 } else { 0 };
+                    push_json_newline(&mut pretty_json, depth);
+                }
+                pretty_json.push(c);
+            },
+            ',' => {
+                pretty_json.push(c);
+                push_json_newline(&mut pretty_json, depth);
+            },
+            ':' => {
+                pretty_json.push_str(": ");
+            },
+            _ if c.is_whitespace() => {},
+            _ => {
+                pretty_json.push(c);
+            },
+        }
+    }
+    return pretty_json;
+}
+
+
+fn push_json_newline(pretty_json: &mut String, depth: usize) {
+    pretty_json.push('\n');
+    pretty_json.push_str(&"  ".repeat(depth));
+}
+
+
+fn font_to_json(font: &Font) -> String {
+    let face = match &font.face {
+        FontFace::TimesNewRomanRegular => String::from("default"),
+        FontFace::System(family) => escape_json_string(family),
+    };
+    return format!("{{\"face\": \"{}\", \"size\": {}, \"bold\": {}, \"italic\": {}}}", face, font.size, font.bold, font.italic);
+}
+
+
 fn rect_to_json(rect: &Rect) -> String {
     return format!("[{:.0}, {:.0}, {:.0}, {:.0}]", rect.x, rect.y, rect.width, rect.height);
 }
 
 
-fn childs_to_json(childs: &Option<Vec<Rc<RefCell<LayoutNode>>>>) -> String {
+fn childs_to_json(full_layout: &FullLayout, childs: &Option<Vec<LayoutNodeId>>) -> String {
     let mut buffer = String::new();
     buffer.push('[');
 
@@ -152,14 +314,13 @@ fn childs_to_json(childs: &Option<Vec<Rc<RefCell<LayoutNode>>>>) -> String {
 
         let mut first = true;
 
-        for child in childs.as_ref().unwrap() {
-            let our_child = child.borrow();
-            let node_json = layout_node_to_json(&our_child);
-            
-            buffer += node_json.as_str();
+        for child_id in childs.as_ref().unwrap() {
+            let node_json = layout_node_to_json(full_layout, *child_id);
+
             if !first {
                 buffer.push(',');
             }
+            buffer += node_json.as_str();
             first = false;
         }
 
@@ -171,12 +332,14 @@ fn childs_to_json(childs: &Option<Vec<Rc<RefCell<LayoutNode>>>>) -> String {
 
 
 
+#[cfg(test)]
 struct ParserState<'a>  {
     iterator: Peekable<CharIndices<'a>>,
     original_string: String,
     consumed_until_idx: usize,
     irrelevant_chars: [char;3]
 }
+#[cfg(test)]
 impl ParserState<'_> {
     fn make_for(text: &String) -> ParserState {
         return ParserState {
@@ -233,15 +396,17 @@ impl ParserState<'_> {
 }
 
 
-pub fn dom_node_from_json(json_data: &String) -> ElementDomNode {
+#[cfg(test)]
+pub fn dom_node_from_json(json_data: &String, id: DomNodeId) -> ElementDomNode {
     let mut parser_state = ParserState::make_for(&json_data);
-    return parse_dom_node_from_json(&mut parser_state);
+    return parse_dom_node_from_json(&mut parser_state, id);
 }
 
 
-fn parse_dom_node_from_json(parser_state: &mut ParserState) -> ElementDomNode {
-    let mut dom_node = ElementDomNode::new_empty();
-    dom_node.internal_id = get_next_dom_node_interal_id();
+#[cfg(test)]
+fn parse_dom_node_from_json(parser_state: &mut ParserState, id: DomNodeId) -> ElementDomNode {
+    let mut dom_node = ElementDomNode::new_document_node(id);
+    dom_node.children = None;
 
     parser_state.consume_until('{');
     parser_state.consume_until_next_relevant_char();
@@ -1,17 +1,19 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::ops::{Deref, DerefMut};
+use std::ops::{DerefMut, Index, IndexMut};
 use std::rc::Rc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use image::DynamicImage;
 
+use crate::arena::{Arena, NodeId};
 use crate::color::Color;
+use crate::config::SETTINGS;
 use crate::debug::debug_log_warn;
 use crate::dom::{
     Document,
+    DomNodeId,
     ElementDomNode,
-    NavigationAction,
     TagName,
 };
 use crate::platform::fonts::{
@@ -19,61 +21,781 @@ use crate::platform::fonts::{
     FontContext,
     FontFace,
 };
-use crate::ui_components::PageComponent;
+use crate::network::url::Url;
+use crate::platform::KeyCode;
+use crate::profiler::{self, FramePhase};
+use crate::resource_loader::{LoadedResource, ResourceRequestJobTracker, escape_html};
+use crate::script::js_interpreter::JsInterpreter;
+use crate::ui_components::{BUTTON_TEXT_OFFSET_FROM_BORDER, PageComponent};
 use crate::SCREEN_HEIGHT;
 use crate::style::{
+    background::{BackgroundStyle, resolve_background_style},
+    border::{Borders, resolve_borders},
+    compute_styles,
+    default_canvas_color,
     get_color_style_value,
+    get_opacity_style_value,
     get_property_from_computed_styles,
     has_style_value,
+    parse_font_family_list,
     resolve_css_numeric_type_value,
     resolve_full_styles_for_layout_node,
-    StyleContext,
+    shadow::{Shadow, resolve_box_shadows, resolve_text_shadows},
+    transition::{TransitionStyle, resolve_transitions},
 };
-use crate::ui::CONTENT_WIDTH;
+use crate::ui::{CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y};
 
 
+mod parallel;
 #[cfg(test)] mod tests;
 
 
-static NEXT_LAYOUT_NODE_INTERNAL: AtomicUsize = AtomicUsize::new(1);
-pub fn get_next_layout_node_interal_id() -> usize { NEXT_LAYOUT_NODE_INTERNAL.fetch_add(1, Ordering::Relaxed) }
+const IFRAME_DEFAULT_WIDTH: f32 = 300.0;
+const IFRAME_DEFAULT_HEIGHT: f32 = 150.0;
+pub const IFRAME_BORDER_WIDTH: f32 = 2.0;
+pub const DISCLOSURE_MARKER_SIZE: f32 = 8.0;
+const DISCLOSURE_MARKER_SPACE: f32 = 20.0; //the room in front of the content of a summary, in which we draw the marker
 
 
+pub type LayoutNodeId = NodeId<LayoutNode>;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
 pub struct FullLayout {
-    pub root_node: Rc<RefCell<LayoutNode>>,
-    pub nodes_in_selection_order: Vec<Rc<RefCell<LayoutNode>>>,
+    pub nodes: Arena<LayoutNode>,
+    pub root_node: LayoutNodeId,
+    pub nodes_in_selection_order: Vec<LayoutNodeId>,
+    pub keyboard_selection: Option<(usize, usize)>, //the anchor and focus (as indexes in the selection stops) when extending the selection with the keyboard
 }
 impl FullLayout {
     pub fn page_height(&self) -> f32 {
-        let node = RefCell::borrow(&self.root_node);
-        match &node.content {
+        match &self.nodes[self.root_node].content {
             LayoutNodeContent::BoxLayoutNode(box_node) => {
                 return box_node.location.height;
             },
             _ => { panic!("Root node always should be a box layout node"); }
         }
     }
-    pub fn new_empty() -> FullLayout {
-        //Note that we we create a 1x1 rect even for an empty layout, since we need a rect to render it (for example when the first page is still loading)
+    pub fn new_empty() -> FullLayout {
+        //Note that we we create a 1x1 rect even for an empty layout, since we need a rect to render it (for example when the first page is still loading)
+
+        let box_node = BoxLayoutNode {
+            location: Rect { x: 0.0, y: 0.0, width: 1.0, height: 1.0 },
+            background_color: Color::BLACK,
+            background_image: None,
+            box_shadows: Vec::new(),
+            borders: Borders::none(),
+            disclosure_marker: None,
+        };
+
+        let mut layout_node = LayoutNode::new_empty();
+        layout_node.content = LayoutNodeContent::BoxLayoutNode(box_node);
+
+        let mut nodes = Arena::new();
+        let root_node = nodes.insert(layout_node);
+        return FullLayout { nodes, root_node, nodes_in_selection_order: Vec::new(), keyboard_selection: None };
+    }
+
+    pub fn reset_selection(&mut self) {
+        self.reset_selection_in_subtree(self.root_node);
+        self.keyboard_selection = None;
+    }
+
+    pub fn extend_selection(&mut self, key_code: KeyCode) -> bool {
+        //this moves the focus side of the selection (where the cursor would be) and returns false when there is no selection to extend
+        let mut stops = Vec::new();
+        self.collect_selection_stops(self.root_node, &mut stops);
+        if stops.is_empty() {
+            return false;
+        }
+
+        if self.keyboard_selection.is_none() {
+            self.keyboard_selection = keyboard_selection_from_selected_rects(&stops);
+            if self.keyboard_selection.is_none() {
+                return false;
+            }
+        }
+
+        let (anchor, focus) = self.keyboard_selection.unwrap();
+        let anchor = usize::min(anchor, stops.len() - 1);
+        let focus = usize::min(focus, stops.len() - 1);
+        let current = &stops[focus];
+
+        let new_focus = match key_code {
+            KeyCode::LEFT => {
+                let mut idx = focus;
+                while idx > 0 {
+                    idx -= 1;
+                    if !stops[idx].is_at_same_position(current) {
+                        break;
+                    }
+                }
+                idx
+            },
+            KeyCode::RIGHT => {
+                let mut idx = focus;
+                while idx < stops.len() - 1 {
+                    idx += 1;
+                    if !stops[idx].is_at_same_position(current) {
+                        break;
+                    }
+                }
+                idx
+            },
+            KeyCode::UP | KeyCode::DOWN => {
+                let line_y = if key_code == KeyCode::UP {
+                    stops.iter().map(|stop| stop.y).filter(|y| *y < current.y).reduce(f32::max)
+                } else {
+                    stops.iter().map(|stop| stop.y).filter(|y| *y > current.y).reduce(f32::min)
+                };
+
+                if line_y.is_some() {
+                    let line_y = line_y.unwrap();
+                    let mut closest_idx = focus;
+                    let mut closest_distance = f32::MAX;
+                    for (idx, stop) in stops.iter().enumerate() {
+                        if stop.y == line_y && (stop.x - current.x).abs() < closest_distance {
+                            closest_idx = idx;
+                            closest_distance = (stop.x - current.x).abs();
+                        }
+                    }
+                    closest_idx
+                } else if key_code == KeyCode::UP {
+                    0
+                } else {
+                    stops.len() - 1
+                }
+            },
+            KeyCode::HOME => {
+                let mut line_start_idx = focus;
+                for (idx, stop) in stops.iter().enumerate() {
+                    if stop.y == current.y && stop.x < stops[line_start_idx].x {
+                        line_start_idx = idx;
+                    }
+                }
+                line_start_idx
+            },
+            KeyCode::END => {
+                let mut line_end_idx = focus;
+                for (idx, stop) in stops.iter().enumerate() {
+                    if stop.y == current.y && stop.x >= stops[line_end_idx].x {
+                        line_end_idx = idx;
+                    }
+                }
+                line_end_idx
+            },
+            KeyCode::BACKSPACE | KeyCode::PAGEDOWN | KeyCode::PAGEUP | KeyCode::RETURN | KeyCode::SPACE => {
+                return false;
+            },
+        };
+
+        self.reset_selection_in_subtree(self.root_node);
+        self.keyboard_selection = Some( (anchor, new_focus) );
+        self.select_between_stops(&stops, usize::min(anchor, new_focus), usize::max(anchor, new_focus));
+        return true;
+    }
+
+    fn collect_selection_stops(&self, node_id: LayoutNodeId, stops: &mut Vec<SelectionStop>) {
+        //these are in the same order as the text we select with select_all, so only for visible nodes
+        let layout_node = &self.nodes[node_id];
+        match &layout_node.content {
+            LayoutNodeContent::TextLayoutNode(text_layout_node) => {
+                for (rect_idx, rect) in text_layout_node.rects.iter().enumerate() {
+                    for char_idx in 0..(rect.char_position_mapping.len() + 1) {
+                        let char_offset = if char_idx == 0 { 0.0 } else { rect.char_position_mapping[char_idx - 1] };
+                        stops.push(SelectionStop { node: node_id, rect_idx, char_idx, selected_char_range: rect.selection_char_range,
+                                                  x: rect.location.x + char_offset, y: rect.location.y });
+                    }
+                }
+            },
+            _ => {}, //see reset_selection_in_subtree, only text can be selected for now
+        }
+
+        if layout_node.children.is_some() {
+            for child_id in layout_node.children.as_ref().unwrap() {
+                if self.nodes[*child_id].visible {
+                    self.collect_selection_stops(*child_id, stops);
+                }
+            }
+        }
+    }
+
+    fn select_between_stops(&mut self, stops: &Vec<SelectionStop>, from_idx: usize, to_idx: usize) {
+        //every stop selects the character after it, so the last stop does not select anything itself
+        let mut idx = from_idx;
+        while idx < to_idx {
+            let first_stop = &stops[idx];
+            let mut last_char_idx = first_stop.char_idx;
+            while idx + 1 < to_idx && stops[idx + 1].node == first_stop.node && stops[idx + 1].rect_idx == first_stop.rect_idx {
+                idx += 1;
+                last_char_idx = stops[idx].char_idx;
+            }
+            idx += 1;
+
+            match &mut self.nodes[first_stop.node].content {
+                LayoutNodeContent::TextLayoutNode(text_layout_node) => {
+                    let rect = &mut text_layout_node.rects[first_stop.rect_idx];
+                    if first_stop.char_idx >= rect.char_position_mapping.len() {
+                        continue; //this is only the stop at the end of the rect
+                    }
+                    let last_char_idx = usize::min(last_char_idx, rect.char_position_mapping.len() - 1);
+
+                    let start_offset = if first_stop.char_idx == 0 { 0.0 } else { rect.char_position_mapping[first_stop.char_idx - 1] };
+                    let end_offset = rect.char_position_mapping[last_char_idx];
+                    rect.selection_rect = Some(Rect { x: rect.location.x + start_offset, y: rect.location.y,
+                                                      width: end_offset - start_offset, height: rect.location.height });
+                    rect.selection_char_range = Some( (first_stop.char_idx, last_char_idx) );
+                },
+                _ => {},
+            }
+        }
+    }
+
+    pub fn find_bounding_box_of_dom_nodes(&self, node_id: LayoutNodeId, dom_node_ids: &HashSet<DomNodeId>) -> Option<Rect> {
+        //a layout node built from one of the dom nodes contains the layout of its children, so we only look further down when there is no match
+        //(inline elements don't get a layout node of their own, so for those we combine the boxes of the content inside them)
+        let node = &self.nodes[node_id];
+        if node.from_dom_node.is_some() && dom_node_ids.contains(node.from_dom_node.as_ref().unwrap()) {
+            let bounding_box = node.get_bounding_box();
+            if bounding_box.is_some() {
+                return bounding_box;
+            }
+        }
+
+        let mut bounding_box: Option<Rect> = None;
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                let child_bounding_box = self.find_bounding_box_of_dom_nodes(*child_id, dom_node_ids);
+                if child_bounding_box.is_some() {
+                    bounding_box = match bounding_box {
+                        Some(current_box) => Some(current_box.union(&child_bounding_box.unwrap())),
+                        None => child_bounding_box,
+                    };
+                }
+            }
+        }
+        return bounding_box;
+    }
+
+    pub fn find_dom_node_at_position(&self, node_id: LayoutNodeId, x: f32, y: f32) -> Option<DomNodeId> {
+        let node = &self.nodes[node_id];
+        if node.content.is_inside(x, y) {
+            if node.children.is_some() {
+                for child_id in node.children.as_ref().unwrap() {
+                    if self.nodes[*child_id].visible {
+                        let possible_node = self.find_dom_node_at_position(*child_id, x, y);
+                        if possible_node.is_some() {
+                            return possible_node;
+                        }
+                    }
+                }
+            }
+
+            if node.from_dom_node.is_some() {
+                return node.from_dom_node;
+            }
+        }
+
+        return None;
+    }
+
+    pub fn find_layout_node_at_position(&self, node_id: LayoutNodeId, x: f32, y: f32) -> Option<LayoutNodeId> {
+        //this is the deepest node with content on the position, so for text inside a box, that is the text
+        //TODO: this does not look inside frames yet
+        let node = &self.nodes[node_id];
+        if !node.content.is_inside(x, y) && !matches!(node.content, LayoutNodeContent::NoContent) {
+            return None;
+        }
+
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                if self.nodes[*child_id].visible {
+                    let possible_node = self.find_layout_node_at_position(*child_id, x, y);
+                    if possible_node.is_some() {
+                        return possible_node;
+                    }
+                }
+            }
+        }
+
+        if matches!(node.content, LayoutNodeContent::NoContent) {
+            return None;
+        }
+        return Some(node_id);
+    }
+
+    pub fn scroll_iframe_at_position(&mut self, node_id: LayoutNodeId, x: f32, y: f32, y_diff: f32) -> bool {
+        //returns whether a frame was scrolled, so the caller knows it should not scroll the page itself
+        if !self.nodes[node_id].content.is_inside(x, y) {
+            return false;
+        }
+
+        if let LayoutNodeContent::IframeLayoutNode(iframe_node) = &mut self.nodes[node_id].content {
+            if iframe_node.full_layout.is_none() {
+                return false;
+            }
+
+            //frames inside this frame get the first chance to scroll, the content of the frame is shifted by its own scroll position
+            let nested_layout = iframe_node.full_layout.as_mut().unwrap();
+            if nested_layout.scroll_iframe_at_position(nested_layout.root_node, x, y + iframe_node.scroll_y, y_diff) {
+                return true;
+            }
+            return iframe_node.scroll(y_diff);
+        }
+
+        let children = self.nodes[node_id].children.clone();
+        if children.is_some() {
+            for child_id in children.unwrap() {
+                if self.scroll_iframe_at_position(child_id, x, y, y_diff) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    pub fn reset_selection_in_subtree(&mut self, node_id: LayoutNodeId) {
+        match self.nodes[node_id].content {
+            LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
+                for rect in text_layout_node.rects.iter_mut() {
+                    rect.selection_rect = None;
+                    rect.selection_char_range = None;
+                }
+            },
+            LayoutNodeContent::ImageLayoutNode(_) => {
+                //For now you can't select images
+            },
+            LayoutNodeContent::ButtonLayoutNode(_) | LayoutNodeContent::FileInputLayoutNode(_) | LayoutNodeContent::IframeLayoutNode(_) => {}
+            LayoutNodeContent::TextInputLayoutNode(_) | LayoutNodeContent::TextAreaLayoutNode(_) => {
+                //It seems in other browers, when you select content with a text input in it, the content of the text box is not included
+                //   so for now we are not doing anything here...
+
+                //TODO: unsure if I also need to reset the selection _inside_ the text input here.
+            },
+            LayoutNodeContent::BoxLayoutNode(_) => {
+                //Note: this is a no-op for now, since there is nothing to select in a box node itself (just in its children)
+            },
+            LayoutNodeContent::TableLayoutNode(_) | LayoutNodeContent::TableCellLayoutNode(_) => {
+                //Note: for now this is a no-op. There is a usecase of selecing and copying tables, but we don't support it for now
+            },
+            LayoutNodeContent::NoContent => {},
+        }
+
+        let children = self.nodes[node_id].children.clone();
+        if children.is_some() {
+            for child_id in children.unwrap() {
+                self.reset_selection_in_subtree(child_id);
+            }
+        }
+    }
+
+    pub fn select_all(&mut self, node_id: LayoutNodeId) {
+        //this selects all text that can be selected by dragging the mouse, which is the text of the visible text nodes
+        match self.nodes[node_id].content {
+            LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
+                for rect in text_layout_node.rects.iter_mut() {
+                    rect.selection_rect = Some(rect.location.clone());
+                    rect.selection_char_range = Some( (0, rect.text.len()) );
+                }
+            },
+            _ => {}, //see reset_selection_in_subtree, there is nothing to select in the other nodes themselves
+        }
+
+        let children = self.nodes[node_id].children.clone();
+        if children.is_some() {
+            for child_id in children.unwrap() {
+                if self.nodes[child_id].visible {
+                    self.select_all(child_id);
+                }
+            }
+        }
+    }
+
+    pub fn get_selected_content(&self, dom_nodes: &Arena<ElementDomNode>, base_url: &Url) -> SelectedContent {
+        let mut serializer = SelectionSerializer {
+            full_layout: self,
+            dom_nodes,
+            base_url,
+            nr_of_selected_rects_left: self.count_selected_rects(self.root_node),
+            pending_separator: None,
+            content: SelectedContent { text: String::new(), html: String::new() },
+        };
+        serializer.serialize(self.root_node);
+        return serializer.content;
+    }
+
+    fn count_selected_rects(&self, node_id: LayoutNodeId) -> usize {
+        let node = &self.nodes[node_id];
+        if !node.visible {
+            return 0;
+        }
+
+        let mut count = match &node.content {
+            LayoutNodeContent::TextLayoutNode(text_layout_node) => {
+                text_layout_node.rects.iter().filter(|rect| rect.selection_char_range.is_some()).count()
+            },
+            _ => 0,
+        };
+
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                count += self.count_selected_rects(*child_id);
+            }
+        }
+        return count;
+    }
+
+    pub fn is_inside_selection(&self, node_id: LayoutNodeId, x: f32, y: f32) -> bool {
+        let node = &self.nodes[node_id];
+        match &node.content {
+            LayoutNodeContent::TextLayoutNode(text_layout_node) => {
+                let is_inside = text_layout_node.rects.iter().any(|rect| rect.selection_rect.is_some() && rect.selection_rect.as_ref().unwrap().is_inside(x, y));
+                if is_inside {
+                    return true;
+                }
+            },
+            _ => {}, //see reset_selection_in_subtree, only text can be selected for now
+        }
+
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                if self.nodes[*child_id].visible && self.is_inside_selection(*child_id, x, y) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    fn remove_subtree(&mut self, node_id: LayoutNodeId) {
+        //the layout nodes of a dom node that changed are built again, so the old ones are removed from the arena
+        let node = self.nodes.remove(node_id);
+        if node.is_some() && node.as_ref().unwrap().children.is_some() {
+            for child_id in node.unwrap().children.unwrap() {
+                self.remove_subtree(child_id);
+            }
+        }
+    }
+}
+
+
+//The layout nodes to lay out, which are all nodes in the arena of a layout, or (on the threads laying out the childs of a block in parallel) the
+//nodes in the subtree of one child.
+pub trait LayoutNodes: Index<LayoutNodeId, Output = LayoutNode> + IndexMut<LayoutNodeId> {
+    fn as_arena(&mut self) -> Option<&mut Arena<LayoutNode>> {
+        //the childs of a block can only be split over threads when we have all nodes, so not when we already are on one of those threads
+        return None;
+    }
+
+    fn is_dirty_anywhere<D: LayoutDocument>(&self, node_id: LayoutNodeId, document: &D) -> bool {
+        let node = &self[node_id];
+        if node.from_dom_node.is_some() && document.is_dirty(node.from_dom_node.unwrap()) {
+            return true;
+        }
+
+        if node.children.is_some() {
+            for child_id in node.children.as_ref().unwrap() {
+                if self.is_dirty_anywhere(*child_id, document) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    fn move_node_vertically(&mut self, node_id: LayoutNodeId, y_diff: f32) {
+        match &mut self[node_id].content {
+            LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
+                for rect in text_layout_node.rects.iter_mut() {
+                    rect.location.y += y_diff;
+                }
+            },
+            LayoutNodeContent::ImageLayoutNode(image_node) => { image_node.location.y += y_diff; }
+            LayoutNodeContent::ButtonLayoutNode(button_node) => { button_node.location.y += y_diff; }
+            LayoutNodeContent::TextInputLayoutNode(text_input_node) => { text_input_node.location.y += y_diff; }
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => { text_area_node.location.y += y_diff; }
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => { file_input_node.location.y += y_diff; }
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => {
+                iframe_node.location.y += y_diff;
+                if iframe_node.full_layout.is_some() {
+                    let nested_layout = iframe_node.full_layout.as_mut().unwrap();
+                    let nested_root_node = nested_layout.root_node;
+                    nested_layout.nodes.move_node_vertically(nested_root_node, y_diff);
+                }
+            }
+            LayoutNodeContent::BoxLayoutNode(box_node) => { box_node.location.y += y_diff; }
+            LayoutNodeContent::TableLayoutNode(table_node) => { table_node.location.y += y_diff; }
+            LayoutNodeContent::TableCellLayoutNode(table_cell_node) => { table_cell_node.location.y += y_diff; }
+            LayoutNodeContent::NoContent => { panic!("Cant adjust position of something without content"); }
+        }
+
+        let children = self[node_id].children.clone();
+        if children.is_some() {
+            for child_id in children.unwrap() {
+                self.move_node_vertically(child_id, y_diff);
+            }
+        }
+    }
+
+    fn all_childnodes_have_given_display(&self, node_id: LayoutNodeId, display: Display) -> bool {
+        let node = &self[node_id];
+        if node.children.is_none() {
+            return true;
+        }
+        return node.children.as_ref().unwrap().iter().all(|child_id| self[*child_id].display == display);
+    }
+}
+impl LayoutNodes for Arena<LayoutNode> {
+    fn as_arena(&mut self) -> Option<&mut Arena<LayoutNode>> {
+        return Some(self);
+    }
+}
+
+
+//What laying out needs from the document, which is the document itself, or (on the threads laying out the childs of a block in parallel) only
+//which of its nodes are dirty, since the document is not thread safe.
+pub trait LayoutDocument {
+    fn is_dirty(&self, dom_node_id: DomNodeId) -> bool;
+    fn page_component(&self, dom_node_id: DomNodeId) -> Option<&Rc<RefCell<PageComponent>>>;
+}
+impl LayoutDocument for Document {
+    fn is_dirty(&self, dom_node_id: DomNodeId) -> bool {
+        return self.nodes[dom_node_id].dirty;
+    }
+    fn page_component(&self, dom_node_id: DomNodeId) -> Option<&Rc<RefCell<PageComponent>>> {
+        return self.nodes[dom_node_id].page_component.as_ref();
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct SelectionStop {
+    //a place between two characters of a text layout rect, where a selection can start or end
+    node: LayoutNodeId,
+    rect_idx: usize,
+    char_idx: usize,
+    selected_char_range: Option<(usize, usize)>, //the part of the rect of this stop that is selected
+    x: f32,
+    y: f32,
+}
+impl SelectionStop {
+    fn is_at_same_position(&self, other: &SelectionStop) -> bool {
+        return self.x == other.x && self.y == other.y;
+    }
+}
+
+
+fn keyboard_selection_from_selected_rects(stops: &Vec<SelectionStop>) -> Option<(usize, usize)> {
+    //when the text was selected with the mouse, we start from the start of that selection, and extend its end
+    let mut anchor = None;
+    let mut focus = None;
+    for (idx, stop) in stops.iter().enumerate() {
+        let selected_char_range = stop.selected_char_range;
+        if selected_char_range.is_some() {
+            let (start_idx, end_idx) = selected_char_range.unwrap();
+            if anchor.is_none() && stop.char_idx == start_idx {
+                anchor = Some(idx);
+            }
+            if stop.char_idx > start_idx && stop.char_idx <= end_idx + 1 {
+                focus = Some(idx);
+            }
+        }
+    }
+
+    if anchor.is_none() || focus.is_none() {
+        return None;
+    }
+    return Some( (anchor.unwrap(), focus.unwrap()) );
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SelectedContent {
+    //the selection in both the flavors we put on the clipboard, so pasting it in a rich text editor keeps things like links and tables
+    pub text: String,
+    pub html: String,
+}
+
+
+struct SelectionSerializer<'a> {
+    full_layout: &'a FullLayout,
+    dom_nodes: &'a Arena<ElementDomNode>,
+    base_url: &'a Url,
+    nr_of_selected_rects_left: usize, //images are only part of the selection when they are in between selected text
+    pending_separator: Option<char>, //we only add newlines and tabs between text, so the result does not start or end with them
+    content: SelectedContent,
+}
+impl SelectionSerializer<'_> {
+    fn serialize(&mut self, node_id: LayoutNodeId) {
+        let full_layout = self.full_layout;
+        let node = &full_layout.nodes[node_id];
+        if !node.visible {
+            return;
+        }
+
+        let tag_name = if node.from_dom_node.is_some() { self.dom_nodes[node.from_dom_node.unwrap()].name.clone() } else { None };
+
+        match &node.content {
+            LayoutNodeContent::TextLayoutNode(text_layout_node) => {
+                for rect in &text_layout_node.rects {
+                    if rect.selection_char_range.is_some() {
+                        if text_layout_node.line_break {
+                            self.push_text("\n", "<br>");
+                        } else {
+                            let (start_idx, end_idx) = rect.selection_char_range.unwrap();
+                            let text = rect.text.chars().skip(start_idx).take(end_idx - start_idx + 1).collect::<String>();
+                            self.push_text(&text, &escape_html(&text));
+                        }
+                        self.nr_of_selected_rects_left -= 1;
+                    }
+                }
+                return;
+            },
+            LayoutNodeContent::ButtonLayoutNode(_) | LayoutNodeContent::TextInputLayoutNode(_) | LayoutNodeContent::TextAreaLayoutNode(_) |
+            LayoutNodeContent::FileInputLayoutNode(_) | LayoutNodeContent::IframeLayoutNode(_) => {
+                //see reset_selection, these are not part of the selection
+                return;
+            },
+            LayoutNodeContent::ImageLayoutNode(_) | LayoutNodeContent::BoxLayoutNode(_) if tag_name.as_deref() == Some("img") => {
+                //an image that is not loaded (yet) is a box, but its alt text is still copied
+                if self.is_in_selection() {
+                    self.push_image(node.from_dom_node.unwrap());
+                }
+                return;
+            },
+            LayoutNodeContent::ImageLayoutNode(_) | LayoutNodeContent::BoxLayoutNode(_) | LayoutNodeContent::TableLayoutNode(_) |
+            LayoutNodeContent::TableCellLayoutNode(_) | LayoutNodeContent::NoContent => {},
+        }
+
+        let is_block = node.display == Display::Block;
+        if is_block {
+            self.add_separator('\n', false);
+        }
+
+        //the document level elements are left out, since the html is pasted into another document
+        let wrap_in_tag = tag_name.is_some() && !["html", "head", "body"].contains(&tag_name.as_deref().unwrap());
+        let html_length_before_node = self.content.html.len();
+        if wrap_in_tag {
+            self.push_open_tag(node.from_dom_node.unwrap());
+        }
+        let html_length_before_children = self.content.html.len();
+
+        if node.children.is_some() {
+            match &node.content {
+                LayoutNodeContent::TableLayoutNode(_) => self.serialize_table_cells(node.children.as_ref().unwrap()),
+                _ => {
+                    for child_id in node.children.as_ref().unwrap() {
+                        self.serialize(*child_id);
+                    }
+                },
+            }
+        }
+
+        if self.content.html.len() == html_length_before_children {
+            //nothing in this node was selected, so we leave out its tags as well
+            self.content.html.truncate(html_length_before_node);
+        } else if wrap_in_tag {
+            self.content.html.push_str(&format!("</{}>", tag_name.unwrap()));
+        }
+
+        if is_block {
+            self.add_separator('\n', false);
+        }
+    }
+
+    fn serialize_table_cells(&mut self, cells: &Vec<LayoutNodeId>) {
+        //the rows don't have layout nodes, so we add them back here for the html, and we separate cells with tabs and rows with newlines in the text
+        let mut current_row_idx = None;
+        let mut html_length_before_row = 0;
+        for cell_id in cells {
+            let row_idx = match &self.full_layout.nodes[*cell_id].content {
+                LayoutNodeContent::TableCellLayoutNode(table_cell_node) => Some(table_cell_node.slot_y_idx),
+                _ => current_row_idx,
+            };
+
+            if row_idx != current_row_idx {
+                if current_row_idx.is_some() {
+                    self.close_table_row(html_length_before_row);
+                    self.add_separator('\n', true);
+                }
+                html_length_before_row = self.content.html.len();
+                self.content.html.push_str("<tr>");
+                current_row_idx = row_idx;
+            }
+
+            self.serialize(*cell_id);
+            self.add_separator('\t', true);
+        }
+
+        if current_row_idx.is_some() {
+            self.close_table_row(html_length_before_row);
+        }
+        self.add_separator('\n', true);
+    }
+
+    fn close_table_row(&mut self, html_length_before_row: usize) {
+        if self.content.html.len() == html_length_before_row + "<tr>".len() {
+            self.content.html.truncate(html_length_before_row);
+        } else {
+            self.content.html.push_str("</tr>");
+        }
+    }
+
+    fn push_open_tag(&mut self, dom_node_id: DomNodeId) {
+        let dom_node = &self.dom_nodes[dom_node_id];
+        self.content.html.push('<');
+        self.content.html.push_str(dom_node.name.as_ref().unwrap());
+        //we only keep the attributes that still mean something outside of the page, with the urls made absolute
+        for attribute_name in ["href", "src", "alt"] {
+            let attribute_value = dom_node.get_attribute_value(attribute_name);
+            if attribute_value.is_some() {
+                let attribute_value = if attribute_name == "alt" {
+                    attribute_value.unwrap()
+                } else {
+                    Url::from_base_url(&attribute_value.unwrap(), Some(self.base_url)).to_string()
+                };
+                self.content.html.push_str(&format!(" {}=\"{}\"", attribute_name, escape_html(&attribute_value)));
+            }
+        }
+        self.content.html.push('>');
+    }
+
+    fn push_image(&mut self, dom_node_id: DomNodeId) {
+        let alt_text = self.dom_nodes[dom_node_id].get_attribute_value("alt").unwrap_or(String::new());
+        self.push_open_tag(dom_node_id);
+        self.push_text(&alt_text, "");
+    }
 
-        let box_node = BoxLayoutNode {
-            location: Rect { x: 0.0, y: 0.0, width: 1.0, height: 1.0 },
-            background_color: Color::BLACK,
-        };
+    fn push_text(&mut self, text: &str, html: &str) {
+        if !text.is_empty() {
+            if self.pending_separator.is_some() && !self.content.text.is_empty() {
+                self.content.text.push(self.pending_separator.unwrap());
+            }
+            self.pending_separator = None;
+            self.content.text.push_str(text);
+        }
+        self.content.html.push_str(html);
+    }
 
-        let mut layout_node = LayoutNode::new_empty();
-        layout_node.content = LayoutNodeContent::BoxLayoutNode(box_node);
+    fn add_separator(&mut self, separator: char, replace_pending_separator: bool) {
+        if self.pending_separator.is_none() || replace_pending_separator {
+            self.pending_separator = Some(separator);
+        }
+    }
 
-        return FullLayout { root_node: Rc::from(RefCell::from(layout_node)), nodes_in_selection_order: Vec::new() };
+    fn is_in_selection(&self) -> bool {
+        return !self.content.text.is_empty() && self.nr_of_selected_rects_left > 0;
     }
 }
 
+
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct TextLayoutNode {
     pub line_break: bool,  //TODO: we should not need this. We just need an empty rect, or non layout node at all (as long as we generate the next text lower when layouting)
     pub rects: Vec<TextLayoutRect>,
     pub pre_wrap_rect_backup: Option<TextLayoutRect>,
     pub background_color: Color,
+    pub text_shadows: Vec<Shadow>,
 }
 
 impl TextLayoutNode {
@@ -89,7 +811,7 @@ impl TextLayoutNode {
 
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct ImageLayoutNode {
-    pub image: DynamicImage,
+    pub image: Arc<DynamicImage>, //this is shared with the dom node and the image cache
     pub location: Rect,
 }
 
@@ -103,10 +825,76 @@ pub struct TextInputLayoutNode {
     pub location: Rect,
 }
 
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct TextAreaLayoutNode {
+    pub location: Rect,
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct FileInputLayoutNode {
+    pub location: Rect,
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct IframeLayoutNode {
+    pub location: Rect,
+    pub url: Option<Url>,
+    pub job_tracker: Option<ResourceRequestJobTracker<LoadedResource>>,
+    pub document: Option<Rc<RefCell<Document>>>,
+    pub interpreter: Option<Box<JsInterpreter>>, //the scripts of the frame run from the main loop, like those of the page (see update_iframes)
+    pub full_layout: Option<FullLayout>,
+    pub scroll_y: f32, //the frame scrolls independently of the page it is in
+}
+impl IframeLayoutNode {
+    pub fn content_rect(&self) -> Rect {
+        return Rect { x: self.location.x + IFRAME_BORDER_WIDTH, y: self.location.y + IFRAME_BORDER_WIDTH,
+                      width: (self.location.width - (IFRAME_BORDER_WIDTH * 2.0)).max(0.0), height: (self.location.height - (IFRAME_BORDER_WIDTH * 2.0)).max(0.0) };
+    }
+
+    pub fn compute_content_layout(&mut self, font_context: &FontContext, force_full_layout: bool) {
+        if self.full_layout.is_none() {
+            return;
+        }
+
+        let content_rect = self.content_rect();
+        compute_layout(self.full_layout.as_mut().unwrap(), &mut self.document.as_ref().unwrap().borrow_mut(), content_rect.x, content_rect.y,
+                       font_context, 0.0, false, force_full_layout);
+    }
+
+    pub fn scroll(&mut self, y_diff: f32) -> bool {
+        //returns whether the frame scrolled, it does not when it is already scrolled all the way in that direction
+        if self.full_layout.is_none() {
+            return false;
+        }
+
+        let max_scroll_y = (self.full_layout.as_ref().unwrap().page_height() - self.content_rect().height).max(0.0);
+        let new_scroll_y = (self.scroll_y + y_diff).clamp(0.0, max_scroll_y);
+        let scrolled = new_scroll_y != self.scroll_y;
+        self.scroll_y = new_scroll_y;
+        return scrolled;
+    }
+}
+
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct BoxLayoutNode {
     pub location: Rect,
-    #[allow(dead_code)] pub background_color: Color,  //TODO: use
+    pub background_color: Color,
+    pub background_image: Option<BackgroundImage>,
+    pub box_shadows: Vec<Shadow>,
+    pub borders: Borders, //only block boxes have borders for now
+    pub disclosure_marker: Option<DisclosureMarker>, //this is only set for the summary of a details element
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct DisclosureMarker {
+    pub open: bool,
+    pub color: Color,
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct BackgroundImage {
+    pub image: Arc<DynamicImage>,
+    pub style: BackgroundStyle,
 }
 
 #[cfg_attr(debug_assertions, derive(Debug))]
@@ -128,6 +916,9 @@ pub enum LayoutNodeContent {
     ImageLayoutNode(ImageLayoutNode),
     ButtonLayoutNode(ButtonLayoutNode),
     TextInputLayoutNode(TextInputLayoutNode),
+    TextAreaLayoutNode(TextAreaLayoutNode),
+    FileInputLayoutNode(FileInputLayoutNode),
+    IframeLayoutNode(IframeLayoutNode),
     BoxLayoutNode(BoxLayoutNode),
     TableLayoutNode(TableLayoutNode),
     TableCellLayoutNode(TableCellLayoutNode),
@@ -154,6 +945,15 @@ impl LayoutNodeContent {
             LayoutNodeContent::TextInputLayoutNode(text_input_node) => {
                 return text_input_node.location.is_inside(x, y);
             }
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => {
+                return text_area_node.location.is_inside(x, y);
+            }
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => {
+                return file_input_node.location.is_inside(x, y);
+            }
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => {
+                return iframe_node.location.is_inside(x, y);
+            }
             LayoutNodeContent::TableLayoutNode(_) => {
                 todo!(); //TODO: implement
             },
@@ -163,29 +963,39 @@ impl LayoutNodeContent {
             LayoutNodeContent::NoContent => { return false; },
         }
     }
+
+    pub fn kind_name(&self) -> &'static str {
+        return match self {
+            LayoutNodeContent::TextLayoutNode(_) => "text",
+            LayoutNodeContent::ImageLayoutNode(_) => "image",
+            LayoutNodeContent::ButtonLayoutNode(_) => "button",
+            LayoutNodeContent::TextInputLayoutNode(_) => "text input",
+            LayoutNodeContent::TextAreaLayoutNode(_) => "text area",
+            LayoutNodeContent::FileInputLayoutNode(_) => "file input",
+            LayoutNodeContent::IframeLayoutNode(_) => "iframe",
+            LayoutNodeContent::BoxLayoutNode(_) => "box",
+            LayoutNodeContent::TableLayoutNode(_) => "table",
+            LayoutNodeContent::TableCellLayoutNode(_) => "table cell",
+            LayoutNodeContent::NoContent => "no content",
+        };
+    }
 }
 
 
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct LayoutNode {
-    pub internal_id: usize,
-    pub children: Option<Vec<Rc<RefCell<LayoutNode>>>>,
+    pub children: Option<Vec<LayoutNodeId>>,
 
-    pub from_dom_node: Option<Rc<RefCell<ElementDomNode>>>,
+    pub from_dom_node: Option<DomNodeId>,
 
     pub display: Display,
     pub visible: bool,
+    pub opacity: f32, //when this is below 1.0, the node and all its children are rendered together, and then blended as a whole
+    pub transitions: Vec<TransitionStyle>,
 
     pub content: LayoutNodeContent,
 }
 impl LayoutNode {
-    pub fn all_childnodes_have_given_display(&self, display: Display) -> bool {
-        if self.children.is_none() {
-            return true;
-        }
-        return self.children.as_ref().unwrap().iter().all(|node| RefCell::borrow(node).display == display);
-    }
-
     pub fn update_single_rect_location(&mut self, new_location: Rect) {
         match &mut self.content {
             LayoutNodeContent::TextLayoutNode(node) => {
@@ -195,6 +1005,9 @@ impl LayoutNode {
             LayoutNodeContent::ImageLayoutNode(node) => { node.location = new_location; },
             LayoutNodeContent::ButtonLayoutNode(node) => { node.location = new_location; },
             LayoutNodeContent::TextInputLayoutNode(node) => { node.location = new_location; },
+            LayoutNodeContent::TextAreaLayoutNode(node) => { node.location = new_location; },
+            LayoutNodeContent::FileInputLayoutNode(node) => { node.location = new_location; },
+            LayoutNodeContent::IframeLayoutNode(node) => { node.location = new_location; },
             LayoutNodeContent::BoxLayoutNode(node) => { node.location = new_location; },
             LayoutNodeContent::TableLayoutNode(node) => { node.location = new_location; }
             LayoutNodeContent::TableCellLayoutNode(node) => { node.location = new_location; }
@@ -212,6 +1025,9 @@ impl LayoutNode {
             LayoutNodeContent::ImageLayoutNode(image_node) => { image_node.location.y }
             LayoutNodeContent::ButtonLayoutNode(button_node) => { button_node.location.y }
             LayoutNodeContent::TextInputLayoutNode(text_input_node) => { text_input_node.location.y }
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => { text_area_node.location.y }
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => { file_input_node.location.y }
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => { iframe_node.location.y }
             LayoutNodeContent::BoxLayoutNode(box_node) => { box_node.location.y }
             LayoutNodeContent::TableLayoutNode(table_node) => { table_node.location.y }
             LayoutNodeContent::TableCellLayoutNode(cell_node) => { cell_node.location.y }
@@ -242,6 +1058,9 @@ impl LayoutNode {
             LayoutNodeContent::ImageLayoutNode(img_node) => { return (img_node.location.width, img_node.location.height); },
             LayoutNodeContent::ButtonLayoutNode(button_node)  => { return (button_node.location.width, button_node.location.height); },
             LayoutNodeContent::TextInputLayoutNode(input_node) => { return (input_node.location.width, input_node.location.height); },
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => { return (text_area_node.location.width, text_area_node.location.height); },
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => { return (file_input_node.location.width, file_input_node.location.height); },
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => { return (iframe_node.location.width, iframe_node.location.height); },
             LayoutNodeContent::BoxLayoutNode(box_node) => { return (box_node.location.width, box_node.location.height); },
             LayoutNodeContent::TableLayoutNode(table_node) => { return (table_node.location.width, table_node.location.height); }
             LayoutNodeContent::TableCellLayoutNode(cell_node) => { return (cell_node.location.width, cell_node.location.height); }
@@ -249,6 +1068,25 @@ impl LayoutNode {
         }
     }
 
+    pub fn get_bounding_box(&self) -> Option<Rect> {
+        //this is the box around all the content of the node, in page coordinates (nodes without content don't have one)
+        match &self.content {
+            LayoutNodeContent::TextLayoutNode(text_node) => {
+                return text_node.rects.iter().map(|rect| rect.location.clone()).reduce(|one, two| one.union(&two));
+            },
+            LayoutNodeContent::ImageLayoutNode(img_node) => { return Some(img_node.location.clone()); },
+            LayoutNodeContent::ButtonLayoutNode(button_node)  => { return Some(button_node.location.clone()); },
+            LayoutNodeContent::TextInputLayoutNode(input_node) => { return Some(input_node.location.clone()); },
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => { return Some(text_area_node.location.clone()); },
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => { return Some(file_input_node.location.clone()); },
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => { return Some(iframe_node.location.clone()); },
+            LayoutNodeContent::BoxLayoutNode(box_node) => { return Some(box_node.location.clone()); },
+            LayoutNodeContent::TableLayoutNode(table_node) => { return Some(table_node.location.clone()); }
+            LayoutNodeContent::TableCellLayoutNode(cell_node) => { return Some(cell_node.location.clone()); }
+            LayoutNodeContent::NoContent => { return None; },
+        }
+    }
+
     pub fn visible_on_y_location(&self, current_scroll_y: f32) -> bool {
         match &self.content {
             LayoutNodeContent::TextLayoutNode(text_node) => {
@@ -257,6 +1095,9 @@ impl LayoutNode {
             LayoutNodeContent::ImageLayoutNode(image_node) => { return image_node.location.is_visible_on_y_location(current_scroll_y); },
             LayoutNodeContent::ButtonLayoutNode(button_node) => { return button_node.location.is_visible_on_y_location(current_scroll_y); }
             LayoutNodeContent::TextInputLayoutNode(text_input_node) => { return text_input_node.location.is_visible_on_y_location(current_scroll_y); }
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => { return text_area_node.location.is_visible_on_y_location(current_scroll_y); }
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => { return file_input_node.location.is_visible_on_y_location(current_scroll_y); }
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => { return iframe_node.location.is_visible_on_y_location(current_scroll_y); }
             LayoutNodeContent::BoxLayoutNode(box_node) => { return box_node.location.is_visible_on_y_location(current_scroll_y); },
             LayoutNodeContent::TableLayoutNode(table_node) => { return table_node.location.is_visible_on_y_location(current_scroll_y); }
             LayoutNodeContent::TableCellLayoutNode(cell_node) => { return cell_node.location.is_visible_on_y_location(current_scroll_y); }
@@ -264,146 +1105,18 @@ impl LayoutNode {
         }
     }
 
-    pub fn find_dom_node_at_position(&self, x: f32, y: f32) -> Option<Rc<RefCell<ElementDomNode>>> {
-        if self.content.is_inside(x, y) {
-            if self.children.is_some() {
-                for child in self.children.as_ref().unwrap() {
-                    if RefCell::borrow(child).visible {
-                        let possible_node = child.borrow().find_dom_node_at_position(x, y);
-                        if possible_node.is_some() {
-                            return possible_node;
-                        }
-                    }
-                }
-            }
-
-            if self.from_dom_node.is_some() {
-                return Some(self.from_dom_node.as_ref().unwrap().clone());
-            }
-        }
-
-        return None;
-    }
-
-    pub fn click(&self, x: f32, y: f32, document: &Document) -> NavigationAction {
-        let possible_dom_node = self.find_dom_node_at_position(x, y);
-
-        if possible_dom_node.is_some() {
-            return possible_dom_node.unwrap().borrow().click(document);
-        }
-        return NavigationAction::None;
-    }
-
     pub fn new_empty() -> LayoutNode {
         return LayoutNode {
-            internal_id: 0,
             display: Display::Block,
             visible: true,
+            opacity: 1.0,
+            transitions: Vec::new(),
             children: None,
             from_dom_node: None,
             content: LayoutNodeContent::NoContent,
         };
     }
 
-    pub fn reset_selection(&mut self) {
-        match self.content {
-            LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
-                for rect in text_layout_node.rects.iter_mut() {
-                    rect.selection_rect = None;
-                    rect.selection_char_range = None;
-                }
-            },
-            LayoutNodeContent::ImageLayoutNode(_) => {
-                //For now you can't select images
-            },
-            LayoutNodeContent::ButtonLayoutNode(_) => {}
-            LayoutNodeContent::TextInputLayoutNode(_) => {
-                //It seems in other browers, when you select content with a text input in it, the content of the text box is not included
-                //   so for now we are not doing anything here...
-
-                //TODO: unsure if I also need to reset the selection _inside_ the text input here.
-            },
-            LayoutNodeContent::BoxLayoutNode(_) => {
-                //Note: this is a no-op for now, since there is nothing to select in a box node itself (just in its children)
-            },
-            LayoutNodeContent::TableLayoutNode(_) | LayoutNodeContent::TableCellLayoutNode(_) => {
-                //Note: for now this is a no-op. There is a usecase of selecing and copying tables, but we don't support it for now
-            },
-            LayoutNodeContent::NoContent => {},
-        }
-
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                RefCell::borrow_mut(child).reset_selection();
-            }
-        }
-    }
-
-    pub fn get_selected_text(&self, result: &mut String) {
-        match &self.content {
-            LayoutNodeContent::TextLayoutNode(text_layout_node) => {
-                for rect in &text_layout_node.rects {
-                    if rect.selection_char_range.is_some() {
-                        let (start_idx, end_idx) = rect.selection_char_range.unwrap();
-                        result.push_str(rect.text.chars().skip(start_idx).take(end_idx - start_idx + 1).collect::<String>().as_str());
-                    }
-                }
-            },
-            LayoutNodeContent::ImageLayoutNode(_) => todo!(),  //TODO: implement
-            LayoutNodeContent::ButtonLayoutNode(_) => todo!(),  //TODO: implement
-            LayoutNodeContent::TextInputLayoutNode(_) => todo!(),  //TODO: implement
-            LayoutNodeContent::TableLayoutNode(_) => todo!(),  //TODO: implement
-            LayoutNodeContent::TableCellLayoutNode(_) => todo!(),  //TODO: implement
-            LayoutNodeContent::BoxLayoutNode(_) => {},
-            LayoutNodeContent::NoContent => {},
-        }
-
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                RefCell::borrow_mut(child).get_selected_text(result);
-            }
-        }
-    }
-
-    pub fn is_dirty_anywhere(&self) -> bool {
-        if self.from_dom_node.is_some() && self.from_dom_node.as_ref().unwrap().borrow().dirty {
-            return true;
-        }
-
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                if child.borrow().is_dirty_anywhere() {
-                    return true;
-                }
-            }
-        }
-        return false;
-    }
-
-    pub fn move_node_vertically(&mut self, y_diff: f32) {
-        match &mut self.content {
-            LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
-                for rect in text_layout_node.rects.iter_mut() {
-                    rect.location.y += y_diff;
-                }
-            },
-            LayoutNodeContent::ImageLayoutNode(image_node) => { image_node.location.y += y_diff; }
-            LayoutNodeContent::ButtonLayoutNode(button_node) => { button_node.location.y += y_diff; }
-            LayoutNodeContent::TextInputLayoutNode(text_input_node) => { text_input_node.location.y += y_diff; }
-            LayoutNodeContent::BoxLayoutNode(box_node) => { box_node.location.y += y_diff; }
-            LayoutNodeContent::TableLayoutNode(table_node) => { table_node.location.y += y_diff; }
-            LayoutNodeContent::TableCellLayoutNode(table_cell_node) => { table_cell_node.location.y += y_diff; }
-            LayoutNodeContent::NoContent => { panic!("Cant adjust position of something without content"); }
-        }
-
-        if self.children.is_some() {
-            for child in self.children.as_ref().unwrap() {
-                let mut mut_child = RefCell::borrow_mut(child);
-                mut_child.move_node_vertically(y_diff);
-            }
-        }
-    }
-
 }
 
 
@@ -446,6 +1159,13 @@ impl Rect {
     pub fn empty() -> Rect {
         return Rect { x: 0.0, y: 0.0, width: 0.0, height: 0.0 };
     }
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        return Rect { x, y, width: right - x, height: bottom - y };
+    }
     pub fn is_visible_on_y_location(&self, y: f32) -> bool {
         let top_of_node = self.y;
         let top_of_view = y;
@@ -459,127 +1179,169 @@ impl Rect {
 
 struct LayoutBuildState {
     last_char_was_space: bool,
+    resolved_styles: HashMap<DomNodeId, HashMap<String, String>>, //the styles of the dom nodes we are building for, computed before we start
 }
 
 
-pub fn build_full_layout(document: &Document, font_context: &FontContext) -> FullLayout {
-    let mut top_level_layout_nodes: Vec<Rc<RefCell<LayoutNode>>> = Vec::new();
-
-    let id_of_node_being_built = get_next_layout_node_interal_id();
+pub fn build_full_layout(document: &mut Document, font_context: &FontContext) -> FullLayout {
+    let mut nodes = Arena::new();
+    let mut top_level_layout_nodes: Vec<LayoutNodeId> = Vec::new();
 
-    let mut state = LayoutBuildState { last_char_was_space: false };
+    let mut dom_node_ids = HashSet::new();
+    document.collect_node_ids(document.document_node, &mut dom_node_ids);
+    let resolved_styles = compute_styles(&dom_node_ids, &mut document.nodes, &document.style_context);
+    let mut state = LayoutBuildState { last_char_was_space: false, resolved_styles };
 
-    let layout_node = build_layout_tree(&document.document_node, document, font_context, &mut state, None);
+    let layout_node = build_layout_tree(&mut nodes, document.document_node, document, font_context, &mut state, None);
     top_level_layout_nodes.push(layout_node);
 
     //Note: we need a node above the first node actually containing any content or styles, since for updates to content or styles we re-assign
     //      children to the parent, so we need all nodes that could update to have a valid parent. That is this root_node for the toplevel node(s).
     let root_node = LayoutNode {
-        internal_id: id_of_node_being_built,
         display: Display::Block,
         visible: true,
+        opacity: 1.0,
+        transitions: Vec::new(),
         children: Some(top_level_layout_nodes),
         from_dom_node: None,
         content: LayoutNodeContent::BoxLayoutNode(BoxLayoutNode {
             location: Rect::empty(),
-            background_color: Color::WHITE,
+            background_color: default_canvas_color(document.style_context.media_environment().color_scheme),
+            background_image: None,
+            box_shadows: Vec::new(),
+            borders: Borders::none(),
+            disclosure_marker: None,
         }),
     };
+    let root_node = nodes.insert(root_node);
 
-    let rc_root_node = Rc::new(RefCell::from(root_node));
+    let mut full_layout = FullLayout { nodes, root_node, nodes_in_selection_order: Vec::new(), keyboard_selection: None };
+    full_layout.nodes_in_selection_order = collect_content_nodes_in_walk_order(&full_layout, root_node);
+    return full_layout;
+}
 
-    let mut nodes_in_selection_order = Vec::new();
-    collect_content_nodes_in_walk_order(&rc_root_node, &mut nodes_in_selection_order);
 
-    return FullLayout { root_node: rc_root_node, nodes_in_selection_order };
+pub fn collect_content_nodes_in_walk_order(full_layout: &FullLayout, node_id: LayoutNodeId) -> Vec<LayoutNodeId> {
+    let mut result = Vec::new();
+    collect_content_nodes_in_walk_order_into(full_layout, node_id, &mut result);
+    return result;
 }
 
 
-pub fn collect_content_nodes_in_walk_order(node: &Rc<RefCell<LayoutNode>>, result: &mut Vec<Rc<RefCell<LayoutNode>>>) {
+fn collect_content_nodes_in_walk_order_into(full_layout: &FullLayout, node_id: LayoutNodeId, result: &mut Vec<LayoutNodeId>) {
     //TODO: this is not correct, at least, not if we are using it for things like selection. Because absolutely positioned elements might have
     //      very different positions, regardless of their place in the tree. We need to base this on all (x, y) postions (and keep that updated)
-
-    match RefCell::borrow(node).content {
-        LayoutNodeContent::TextLayoutNode(_) => { result.push(Rc::clone(&node)); },
-        LayoutNodeContent::ImageLayoutNode(_) => { result.push(Rc::clone(&node)); },
-        LayoutNodeContent::ButtonLayoutNode(_) => { result.push(Rc::clone(&node)); },
-        LayoutNodeContent::TextInputLayoutNode(_) => { result.push(Rc::clone(&node)); },
+    let node = &full_layout.nodes[node_id];
+
+    match node.content {
+        LayoutNodeContent::TextLayoutNode(_) => { result.push(node_id); },
+        LayoutNodeContent::ImageLayoutNode(_) => { result.push(node_id); },
+        LayoutNodeContent::ButtonLayoutNode(_) => { result.push(node_id); },
+        LayoutNodeContent::TextInputLayoutNode(_) => { result.push(node_id); },
+        LayoutNodeContent::TextAreaLayoutNode(_) => { result.push(node_id); },
+        LayoutNodeContent::FileInputLayoutNode(_) => { result.push(node_id); },
+        LayoutNodeContent::IframeLayoutNode(_) => {},
         LayoutNodeContent::BoxLayoutNode(_) => {},
         LayoutNodeContent::TableLayoutNode(_) => {},
-        LayoutNodeContent::TableCellLayoutNode(_) => { result.push(Rc::clone(&node)); },
+        LayoutNodeContent::TableCellLayoutNode(_) => { result.push(node_id); },
         LayoutNodeContent::NoContent => {},
     }
 
-    if RefCell::borrow(node).children.as_ref().is_some() {
-        for child in RefCell::borrow(node).children.as_ref().unwrap() {
-            collect_content_nodes_in_walk_order(&child, result);
+    if node.children.is_some() {
+        for child_id in node.children.as_ref().unwrap() {
+            collect_content_nodes_in_walk_order_into(full_layout, *child_id, result);
         }
     }
 }
 
 
-pub fn compute_layout(node: &Rc<RefCell<LayoutNode>>, style_context: &StyleContext, top_left_x: f32, top_left_y: f32, font_context: &FontContext,
-                      current_scroll_y: f32, only_update_block_vertical_position: bool, force_full_layout: bool) {
-    compute_layout_for_node(node, style_context, top_left_x, top_left_y, font_context, current_scroll_y, only_update_block_vertical_position, force_full_layout);
+pub fn update_dirty_layout(full_layout: &RefCell<FullLayout>, document: &mut Document, font_context: &FontContext, current_scroll_y: f32) {
+    //this rebuilds the parts of the layout for dom nodes that changed, and lays out the page again
+    let mut full_layout = full_layout.borrow_mut();
+    let root_node = full_layout.root_node;
+    profiler::measure(FramePhase::Style, || rebuild_dirty_layout_childs(&mut full_layout, root_node, document, font_context));
+
+    full_layout.nodes_in_selection_order = collect_content_nodes_in_walk_order(&full_layout, root_node);
+    full_layout.keyboard_selection = None; //the selection stops might have changed
+
+    profiler::measure(FramePhase::Layout, || {
+        compute_layout(&mut full_layout, document, CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y, font_context, current_scroll_y, false, false);
+    });
+}
+
 
-    reset_dirtyness(node);
+pub fn compute_layout(full_layout: &mut FullLayout, document: &mut Document, top_left_x: f32, top_left_y: f32, font_context: &FontContext,
+                      current_scroll_y: f32, only_update_block_vertical_position: bool, force_full_layout: bool) {
+    let root_node = full_layout.root_node;
+    //the content is wrapped on the width of the viewport it is shown in, which is what media queries see as well
+    let right_edge = top_left_x + document.style_context.media_environment().width;
+    compute_layout_for_node(&mut full_layout.nodes, root_node, &*document, top_left_x, top_left_y, right_edge, font_context, current_scroll_y,
+                            only_update_block_vertical_position,
+                            force_full_layout);
+
+    reset_dirtyness(full_layout, root_node, document);
 }
 
-fn reset_dirtyness(node: &Rc<RefCell<LayoutNode>>) {
-    let node = node.borrow();
+fn reset_dirtyness(full_layout: &FullLayout, node_id: LayoutNodeId, document: &mut Document) {
+    let node = &full_layout.nodes[node_id];
 
     if node.from_dom_node.is_some() {
-        let dom_node = node.from_dom_node.as_ref().unwrap();
-        RefCell::borrow_mut(dom_node).dirty = false;
+        document.nodes[node.from_dom_node.unwrap()].dirty = false;
     }
 
     if node.children.is_some() {
-        for child in node.children.as_ref().unwrap() {
-            reset_dirtyness(child);
+        for child_id in node.children.as_ref().unwrap() {
+            reset_dirtyness(full_layout, *child_id, document);
         }
     }
 }
 
 //This function is responsible for setting the location rects on the node, and all its children, and updating content if needed (sync with DOM)
 //TODO: we now pass in top_left x and y, but I think we should compute the positions just for layout, and offset for UI in the render phase...
-fn compute_layout_for_node(node: &Rc<RefCell<LayoutNode>>, style_context: &StyleContext, top_left_x: f32, top_left_y: f32, font_context: &FontContext,
-                           current_scroll_y: f32, only_update_block_vertical_position: bool, force_full_layout: bool) {
-
-    let mut mut_node = RefCell::borrow_mut(node);
+fn compute_layout_for_node<N: LayoutNodes, D: LayoutDocument>(nodes: &mut N, node_id: LayoutNodeId, document: &D, top_left_x: f32, top_left_y: f32,
+                                                               right_edge: f32, font_context: &FontContext, current_scroll_y: f32, only_update_block_vertical_position: bool,
+                                                               force_full_layout: bool) {
 
     if only_update_block_vertical_position && !force_full_layout {
-        let y_diff = top_left_y - mut_node.y_position();
-        mut_node.move_node_vertically(y_diff);
+        let y_diff = top_left_y - nodes[node_id].y_position();
+        nodes.move_node_vertically(node_id, y_diff);
         return;
     }
 
-    if !mut_node.visible {
-        mut_node.update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: 0.0, height: 0.0 });
+    //for a dirty dom node we built new layout nodes, also for its children, so none of them are laid out yet
+    let from_dom_node = nodes[node_id].from_dom_node;
+    let dom_node_is_dirty = from_dom_node.is_some() && document.is_dirty(from_dom_node.unwrap());
+    let force_full_layout = force_full_layout || dom_node_is_dirty;
 
-    } else if mut_node.children.is_some() {
+    if !nodes[node_id].visible {
+        nodes[node_id].update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: 0.0, height: 0.0 });
 
-        if let LayoutNodeContent::TableLayoutNode(table_node) = &mut_node.content {
+    } else if nodes[node_id].children.is_some() {
+
+        if let LayoutNodeContent::TableLayoutNode(table_node) = &nodes[node_id].content {
             compute_layout_for_table(&table_node);
-        } else if mut_node.all_childnodes_have_given_display(Display::Block) {
-            apply_block_layout(&mut mut_node, style_context, top_left_x, top_left_y, current_scroll_y, font_context, force_full_layout);
-        } else if mut_node.all_childnodes_have_given_display(Display::Inline) {
-            apply_inline_layout(&mut mut_node, style_context, top_left_x, top_left_y, CONTENT_WIDTH - top_left_x, current_scroll_y, font_context, force_full_layout);
+        } else if let LayoutNodeContent::ButtonLayoutNode(_) = &nodes[node_id].content {
+            //the content of a <button> element gets room around it for the border of the button
+            let offsets = ContentOffsets { left: BUTTON_TEXT_OFFSET_FROM_BORDER, top: BUTTON_TEXT_OFFSET_FROM_BORDER,
+                                           right: BUTTON_TEXT_OFFSET_FROM_BORDER, bottom: BUTTON_TEXT_OFFSET_FROM_BORDER };
+            apply_layout_with_offsets(nodes, node_id, document, top_left_x, top_left_y, right_edge, offsets, current_scroll_y, font_context, force_full_layout);
+        } else if let Some(offsets) = box_content_offsets(&nodes[node_id]) {
+            apply_layout_with_offsets(nodes, node_id, document, top_left_x, top_left_y, right_edge, offsets, current_scroll_y, font_context, force_full_layout);
+        } else if nodes.all_childnodes_have_given_display(node_id, Display::Block) {
+            apply_block_layout(nodes, node_id, document, top_left_x, top_left_y, right_edge, current_scroll_y, font_context, force_full_layout);
+        } else if nodes.all_childnodes_have_given_display(node_id, Display::Inline) {
+            apply_inline_layout(nodes, node_id, document, top_left_x, top_left_y, right_edge - top_left_x, current_scroll_y, font_context,
+                                force_full_layout);
         } else {
             panic!("Not all children are either inline or block, earlier in the process this should already have been fixed with anonymous blocks");
         }
 
     } else {
 
-        let opt_dom_node = if mut_node.from_dom_node.is_some() {
-            Some(Rc::clone(&mut_node.from_dom_node.as_ref().unwrap()))
-        } else {
-            None
-        };
-
-        match &mut mut_node.content {
+        match &mut nodes[node_id].content {
             LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
 
-                if opt_dom_node.is_some() && opt_dom_node.as_ref().unwrap().borrow().dirty {
+                if dom_node_is_dirty {
                     text_layout_node.undo_split_rects();
                 }
 
@@ -593,13 +1355,20 @@ fn compute_layout_for_node(node: &Rc<RefCell<LayoutNode>>, style_context: &Style
                      Rect { x: top_left_x, y: top_left_y, width: image_layout_node.image.width() as f32, height: image_layout_node.image.height() as f32 };
             },
             LayoutNodeContent::ButtonLayoutNode(button_node) => {
+                let page_component = document.page_component(from_dom_node.unwrap());
+                if page_component.is_none() {
+                    //this is an empty <button> element, so it only has the room around its (missing) content
+                    button_node.location = Rect { x: top_left_x, y: top_left_y, width: BUTTON_TEXT_OFFSET_FROM_BORDER * 2.0,
+                                                  height: BUTTON_TEXT_OFFSET_FROM_BORDER * 2.0 };
+                    return;
+                }
+
                 //TODO: for now we are setting a default size here, but that should actually retreived from the DOM
                 let button_width = 100.0;  //TODO: this needs to be dependent on the text size. How do we do that? Compute it here?
                 let button_height = 40.0;
 
                 button_node.location = Rect { x: top_left_x, y: top_left_y, width: button_width, height: button_height };
-                let mut_dom_node = mut_node.from_dom_node.as_ref().unwrap().borrow();
-                let mut page_component = mut_dom_node.page_component.as_ref().unwrap().borrow_mut();
+                let mut page_component = page_component.unwrap().borrow_mut();
 
                 match page_component.deref_mut() {
                     PageComponent::Button(button) => {
@@ -608,7 +1377,7 @@ fn compute_layout_for_node(node: &Rc<RefCell<LayoutNode>>, style_context: &Style
                         let text_dimension = font_context.get_text_dimension(&button.text, &button.font);
                         button.update_position(top_left_x, top_left_y - current_scroll_y, text_dimension.0 + 10.0, text_dimension.1 + 10.0);
                     }
-                    PageComponent::TextField(_) => { panic!("Invalid state"); },
+                    PageComponent::TextField(_) | PageComponent::TextArea(_) | PageComponent::FileInput(_) => { panic!("Invalid state"); },
                 }
 
             }
@@ -618,19 +1387,64 @@ fn compute_layout_for_node(node: &Rc<RefCell<LayoutNode>>, style_context: &Style
                 let field_height = 40.0;
 
                 text_input_node.location = Rect { x: top_left_x, y: top_left_y, width: field_width, height: field_height };
-                let dom_node = mut_node.from_dom_node.as_ref().unwrap().borrow();
-                let mut page_component = dom_node.page_component.as_ref().unwrap().borrow_mut();
+                let mut page_component = document.page_component(from_dom_node.unwrap()).unwrap().borrow_mut();
 
                 match page_component.deref_mut() {
-                    PageComponent::Button(_) => { panic!("Invalid state"); },
+                    PageComponent::Button(_) | PageComponent::TextArea(_) | PageComponent::FileInput(_) => { panic!("Invalid state"); },
                     PageComponent::TextField(text_field) => {
                         text_field.update_position(top_left_x, top_left_y - current_scroll_y, field_width, field_height);
                     }
                 }
             },
+            LayoutNodeContent::TextAreaLayoutNode(text_area_node) => {
+                let mut page_component = document.page_component(from_dom_node.unwrap()).unwrap().borrow_mut();
+
+                match page_component.deref_mut() {
+                    PageComponent::Button(_) | PageComponent::TextField(_) | PageComponent::FileInput(_) => { panic!("Invalid state"); },
+                    PageComponent::TextArea(text_area) => {
+                        //the size of a text area depends on its rows and cols attributes, which the component knows about
+                        let (area_width, area_height) = text_area.compute_size(font_context);
+                        text_area_node.location = Rect { x: top_left_x, y: top_left_y, width: area_width, height: area_height };
+                        text_area.update_position(font_context, top_left_x, top_left_y - current_scroll_y, area_width, area_height);
+                    }
+                }
+            },
+            LayoutNodeContent::FileInputLayoutNode(file_input_node) => {
+                let mut page_component = document.page_component(from_dom_node.unwrap()).unwrap().borrow_mut();
+
+                match page_component.deref_mut() {
+                    PageComponent::Button(_) | PageComponent::TextField(_) | PageComponent::TextArea(_) => { panic!("Invalid state"); },
+                    PageComponent::FileInput(file_input) => {
+                        let (input_width, input_height) = file_input.compute_size(font_context);
+                        file_input_node.location = Rect { x: top_left_x, y: top_left_y, width: input_width, height: input_height };
+                        file_input.update_position(top_left_x, top_left_y - current_scroll_y, input_width, input_height);
+                    }
+                }
+            },
+            LayoutNodeContent::IframeLayoutNode(iframe_node) => {
+                //the size of the frame is already known when building the layout node, since it does not depend on its content
+                iframe_node.location = Rect { x: top_left_x, y: top_left_y, width: iframe_node.location.width, height: iframe_node.location.height };
+
+                //the content of the frame is a different document, which is not affected by the dirtyness of ours, so we fully lay it out
+                iframe_node.compute_content_layout(font_context, true);
+            },
             LayoutNodeContent::BoxLayoutNode(box_node) => {
                 //Note: this is a boxlayoutnode, but without children (because that is a seperate case above), so no content.
 
+                if box_node.disclosure_marker.is_some() {
+                    //an empty summary still shows its marker
+                    box_node.location = Rect { x: top_left_x, y: top_left_y, width: DISCLOSURE_MARKER_SPACE, height: DISCLOSURE_MARKER_SPACE };
+                    return;
+                }
+
+                if box_node.borders.has_any() {
+                    //an empty box with a border is only as big as its border
+                    let borders = &box_node.borders;
+                    box_node.location = Rect { x: top_left_x, y: top_left_y, width: borders.left.used_width() + borders.right.used_width(),
+                                               height: borders.top.used_width() + borders.bottom.used_width() };
+                    return;
+                }
+
                 //TODO: for now generating 1 by 1 sized, this might not be correct given styling.
                 box_node.location = Rect { x: top_left_x, y: top_left_y, width: 1.0, height: 1.0 };
             },
@@ -663,53 +1477,117 @@ fn compute_layout_for_table(table_dom_node: &TableLayoutNode) {
 
 
 
-pub fn get_font_given_styles(styles: &HashMap<String, String>) -> (Font, Color) {
+pub fn get_font_given_styles(styles: &HashMap<String, String>, font_context: &FontContext) -> (Font, Color) {
     let font_bold = has_style_value(&styles, "font-weight", &"bold".to_owned());
     let _font_underline = has_style_value(&styles, "text-decoration", &"underline".to_owned()); //TODO: we need to use this in a different way
     //TODO: we still need to parse italic (currently harcoded to false in the return below)
     let opt_font_size = get_property_from_computed_styles(&styles, "font-size");
     let font_size = resolve_css_numeric_type_value(&opt_font_size.unwrap()); //font-size has a default value, so this is a fatal error if not found
+    let font_size = f32::max(font_size, SETTINGS.lock().unwrap().minimum_font_size);
 
     let font_color_option = get_color_style_value(&styles, "color");
     let font_color = font_color_option.unwrap(); //color has a default value, so this is a fatal error if not found
 
-    let default_font_face = FontFace::TimesNewRomanRegular;
+    let font_families = get_property_from_computed_styles(&styles, "font-family");
+    let font_face = if font_families.is_some() {
+        font_context.resolve_font_face(&parse_font_family_list(&font_families.unwrap()))
+    } else {
+        FontFace::TimesNewRomanRegular
+    };
 
-    return (Font { face: default_font_face, bold: font_bold, italic: false, size: font_size as u16}, font_color);
+    return (Font { face: font_face, bold: font_bold, italic: false, size: font_size as u16}, font_color);
 }
 
 
-fn apply_block_layout(node: &mut LayoutNode, style_context: &StyleContext, top_left_x: f32, top_left_y: f32,
-                      current_scroll_y: f32, font_context: &FontContext, force_full_layout: bool) {
+fn apply_block_layout<N: LayoutNodes, D: LayoutDocument>(nodes: &mut N, node_id: LayoutNodeId, document: &D, top_left_x: f32, top_left_y: f32,
+                                                         right_edge: f32, current_scroll_y: f32, font_context: &FontContext, force_full_layout: bool) {
+    let child_ids = nodes[node_id].children.clone().unwrap();
+    let laid_out_in_parallel = parallel::lay_out_childs_in_parallel(nodes, &child_ids, document, top_left_x, right_edge, current_scroll_y, font_context,
+                                                                    force_full_layout);
+
     let mut cursor_y = top_left_y;
     let mut max_width: f32 = 0.0;
 
-    for child in node.children.as_ref().unwrap() {
-        let only_update_block_vertical_position = !child.borrow().is_dirty_anywhere(); //Since the parent node is block layout, we can shift the while block up and down if its not dirty
-        compute_layout_for_node(&child, style_context, top_left_x, cursor_y, font_context, current_scroll_y, only_update_block_vertical_position, force_full_layout);
-        let (bounding_box_width, bounding_box_height) = RefCell::borrow(child).get_size_of_bounding_box();
+    for child_id in child_ids {
+        if laid_out_in_parallel.contains(&child_id) {
+            //these are laid out at y 0, so we only need to move them down to where they go
+            nodes.move_node_vertically(child_id, cursor_y);
+        } else {
+            let only_update_block_vertical_position = !nodes.is_dirty_anywhere(child_id, document); //Since the parent node is block layout, we can shift the while block up and down if its not dirty
+            compute_layout_for_node(nodes, child_id, document, top_left_x, cursor_y, right_edge, font_context, current_scroll_y, only_update_block_vertical_position,
+                                    force_full_layout);
+        }
+        let (bounding_box_width, bounding_box_height) = nodes[child_id].get_size_of_bounding_box();
 
         cursor_y += bounding_box_height;
         max_width = max_width.max(bounding_box_width);
     }
 
     let our_height = cursor_y - top_left_y;
-    node.update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: max_width, height: our_height });
+    nodes[node_id].update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: max_width, height: our_height });
+}
+
+
+struct ContentOffsets {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+}
+
+
+fn box_content_offsets(node: &LayoutNode) -> Option<ContentOffsets> {
+    //the content of a box gets room around it for its border, and the content of a summary gets room in front of it for the marker
+    if let LayoutNodeContent::BoxLayoutNode(box_node) = &node.content {
+        if !box_node.borders.has_any() && box_node.disclosure_marker.is_none() {
+            return None;
+        }
+
+        let marker_space = if box_node.disclosure_marker.is_some() { DISCLOSURE_MARKER_SPACE } else { 0.0 };
+        let borders = &box_node.borders;
+        return Some(ContentOffsets { left: borders.left.used_width() + marker_space, top: borders.top.used_width(),
+                                     right: borders.right.used_width(), bottom: borders.bottom.used_width() });
+    }
+    return None;
+}
+
+
+fn apply_layout_with_offsets<N: LayoutNodes, D: LayoutDocument>(nodes: &mut N, node_id: LayoutNodeId, document: &D, top_left_x: f32, top_left_y: f32,
+                                                                right_edge: f32, offsets: ContentOffsets, current_scroll_y: f32, font_context: &FontContext, force_full_layout: bool) {
+    //The content is laid out as usual, but with room around it, that the node itself draws something in
+    let content_x = top_left_x + offsets.left;
+    let content_y = top_left_y + offsets.top;
+
+    if nodes.all_childnodes_have_given_display(node_id, Display::Block) {
+        apply_block_layout(nodes, node_id, document, content_x, content_y, right_edge, current_scroll_y, font_context, force_full_layout);
+    } else if nodes.all_childnodes_have_given_display(node_id, Display::Inline) {
+        let max_allowed_width = right_edge - content_x - offsets.right;
+        apply_inline_layout(nodes, node_id, document, content_x, content_y, max_allowed_width, current_scroll_y, font_context, force_full_layout);
+    } else {
+        panic!("Not all children are either inline or block, earlier in the process this should already have been fixed with anonymous blocks");
+    }
+
+    let node = &mut nodes[node_id];
+    let (content_width, content_height) = node.get_size_of_bounding_box();
+    node.update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: content_width + offsets.left + offsets.right,
+                                            height: content_height + offsets.top + offsets.bottom });
 }
 
 
-fn apply_inline_layout(node: &mut LayoutNode, style_context: &StyleContext, top_left_x: f32, top_left_y: f32, max_allowed_width: f32,
-                       current_scroll_y: f32, font_context: &FontContext, force_full_layout: bool) {
+fn apply_inline_layout<N: LayoutNodes, D: LayoutDocument>(nodes: &mut N, node_id: LayoutNodeId, document: &D, top_left_x: f32, top_left_y: f32,
+                                                          max_allowed_width: f32, current_scroll_y: f32, font_context: &FontContext, force_full_layout: bool) {
+    let right_edge = top_left_x + max_allowed_width;
     let mut cursor_x = top_left_x;
     let mut cursor_y = top_left_y;
     let mut max_width: f32 = 0.0;
     let mut max_height_of_line: f32 = 0.0;
 
-    for child in node.children.as_ref().unwrap() {
+    for child_id in nodes[node_id].children.clone().unwrap() {
         let only_update_block_vertical_position = false; //we can only do this if the parent is block layout, but in this case its inline. Inline might cause horizonal cascading changes.
-        compute_layout_for_node(&child, style_context, cursor_x, cursor_y, font_context, current_scroll_y, only_update_block_vertical_position, force_full_layout);
+        compute_layout_for_node(nodes, child_id, document, cursor_x, cursor_y, right_edge, font_context, current_scroll_y, only_update_block_vertical_position,
+                                force_full_layout);
 
-        let is_line_break = if let LayoutNodeContent::TextLayoutNode(text_node) = &RefCell::borrow(child).content {
+        let is_line_break = if let LayoutNodeContent::TextLayoutNode(text_node) = &nodes[child_id].content {
             text_node.line_break
         } else {
             false
@@ -733,27 +1611,27 @@ fn apply_inline_layout(node: &mut LayoutNode, style_context: &StyleContext, top_
                 child_height = random_char_height;
             }
 
-            RefCell::borrow_mut(child).update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: max_width, height: child_height });
+            nodes[child_id].update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: max_width, height: child_height });
 
             continue;
         }
 
-        if let LayoutNodeContent::TextLayoutNode(ref mut text_node) = RefCell::borrow_mut(child).content {
+        if let LayoutNodeContent::TextLayoutNode(ref mut text_node) = nodes[child_id].content {
             text_node.undo_split_rects();
         }
 
-        let child_borrow = RefCell::borrow(child);
-        let (child_width, child_height) = child_borrow.get_size_of_bounding_box();
+        let child = &nodes[child_id];
+        let (child_width, child_height) = child.get_size_of_bounding_box();
 
         if (cursor_x - top_left_x + child_width) > max_allowed_width {
 
-            if child_borrow.children.is_none() && child_borrow.can_wrap() {
+            if child.children.is_none() && child.can_wrap() {
                 // in this case, we might be able to split rects, and put part of the node on this line
 
                 let mut rects_for_child;
                 let rect_backup;
 
-                match &child_borrow.content {
+                match &child.content {
                     LayoutNodeContent::TextLayoutNode(text_layout_node) => {
                         let first_rect = text_layout_node.rects.iter().next().unwrap();
                         let font_color = first_rect.font_color;
@@ -802,9 +1680,7 @@ fn apply_inline_layout(node: &mut LayoutNode, style_context: &StyleContext, top_
                     }
                 }
 
-                drop(child_borrow);
-
-                match &mut RefCell::borrow_mut(child).content {
+                match &mut nodes[child_id].content {
                     LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
                         text_layout_node.pre_wrap_rect_backup = rect_backup;
                         text_layout_node.rects = rects_for_child.unwrap();
@@ -823,9 +1699,9 @@ fn apply_inline_layout(node: &mut LayoutNode, style_context: &StyleContext, top_
                     cursor_y += max_height_of_line;
 
                     let only_update_block_vertical_position = false; //we can only do this if the parent is block layout, but in this case its inline. Inline might cause horizonal cascading changes.
-                    drop(child_borrow);
-                    compute_layout_for_node(&child, style_context, cursor_x, cursor_y, font_context, current_scroll_y, only_update_block_vertical_position, force_full_layout);
-                    let (child_width, child_height) = RefCell::borrow(child).get_size_of_bounding_box();
+                    compute_layout_for_node(nodes, child_id, document, cursor_x, cursor_y, right_edge, font_context, current_scroll_y,
+                                            only_update_block_vertical_position, force_full_layout);
+                    let (child_width, child_height) = nodes[child_id].get_size_of_bounding_box();
 
                     cursor_x += child_width;
                     max_width = max_width.max(cursor_x);
@@ -850,7 +1726,7 @@ fn apply_inline_layout(node: &mut LayoutNode, style_context: &StyleContext, top_
 
     }
     let our_height = (cursor_y - top_left_y) + max_height_of_line;
-    node.update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: max_width, height: our_height });
+    nodes[node_id].update_single_rect_location(Rect { x: top_left_x, y: top_left_y, width: max_width, height: our_height });
 }
 
 
@@ -902,11 +1778,9 @@ fn wrap_text(text_layout_rect: &TextLayoutRect, max_width: f32, width_remaining_
 }
 
 
-fn get_display_type(node: &Rc<RefCell<ElementDomNode>>) -> Display {
+fn get_display_type(node: &ElementDomNode) -> Display {
     //TODO: eventually this needs the resolved styles as well, because that can influence the display type...
 
-    let node = RefCell::borrow(node);
-
     if node.name.is_some() {
         let node_name = node.name.as_ref().unwrap();
 
@@ -914,7 +1788,8 @@ fn get_display_type(node: &Rc<RefCell<ElementDomNode>>) -> Display {
            node_name == "b" ||
            node_name == "br" ||
            node_name == "img" ||
-           node_name == "span" {
+           node_name == "span" ||
+           node_name == "svg" {
                 return Display::Inline;
         }
         return Display::Block;
@@ -931,15 +1806,20 @@ fn get_display_type(node: &Rc<RefCell<ElementDomNode>>) -> Display {
 }
 
 
-fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Document, font_context: &FontContext, layout_state: &mut LayoutBuildState,
-                     optional_new_text: Option<String>) -> Rc<RefCell<LayoutNode>> {
+fn build_layout_tree(nodes: &mut Arena<LayoutNode>, main_node_id: DomNodeId, document: &Document, font_context: &FontContext,
+                     layout_state: &mut LayoutBuildState, optional_new_text: Option<String>) -> LayoutNodeId {
     let mut partial_node_visible = true;
     let mut partial_node_optional_img = None;
     let mut partial_node_line_break = false;
-    let mut partial_node_styles = resolve_full_styles_for_layout_node(&Rc::clone(main_node), &document.all_nodes, &document.style_context);
+    let mut partial_node_styles = layout_state.resolved_styles.remove(&main_node_id)
+                                      .unwrap_or_else(|| resolve_full_styles_for_layout_node(main_node_id, &document.nodes, &document.style_context));
     let mut partial_node_children = None;
-    let mut partial_node_is_submit_button = false;
+    let mut partial_node_is_button = false;
     let mut partial_node_is_text_input = false;
+    let mut partial_node_is_text_area = false;
+    let mut partial_node_is_file_input = false;
+    let mut partial_node_iframe = None;
+    let mut partial_node_disclosure_marker = None;
     let mut partial_node_text = None;
     let mut partial_node_font = None;
     let mut partial_node_font_color = None;
@@ -947,12 +1827,14 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
 
     let mut prebuilt_node = None; //TODO: I think it is a good idea to transition all cases to pre built the node? needs checking
 
-    let partial_node_background_color = get_color_style_value(&partial_node_styles, "background-color").unwrap_or(Color::WHITE);
+    let mut partial_node_background_color = get_color_style_value(&partial_node_styles, "background-color").unwrap_or(Color::TRANSPARENT);
+
+    let mut childs_to_recurse_on: Option<Vec<DomNodeId>> = None;
 
-    let mut childs_to_recurse_on: &Option<Vec<Rc<RefCell<ElementDomNode>>>> = &None;
+    let main_node = &document.nodes[main_node_id];
 
-    let main_node_refcell = main_node;
-    let main_node = RefCell::borrow(main_node);
+    //text nodes get all styles of their parent, but the opacity is already applied on the parent
+    let partial_node_opacity = if main_node.text.is_some() { 1.0 } else { get_opacity_style_value(&partial_node_styles) };
 
     if main_node.text.is_some() {
         partial_node_text = if optional_new_text.is_some() {
@@ -961,7 +1843,13 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
             Some(main_node.text.as_ref().unwrap().text_content.clone())
         };
 
-        let font = get_font_given_styles(&partial_node_styles);
+        //The background of text in a block element is already painted by the block itself, so we only paint it for text in an inline element.
+        //TODO: this only looks at the direct parent, text in nested inline elements should get the background of the closest one that has it
+        if main_node.parent.is_none() || get_display_type(&document.nodes[main_node.parent.unwrap()]) == Display::Block {
+            partial_node_background_color = Color::TRANSPARENT;
+        }
+
+        let font = get_font_given_styles(&partial_node_styles, font_context);
         partial_node_font = Some(font.0);
         partial_node_font_color = Some(font.1);
         partial_node_non_breaking_space_positions = main_node.text.as_ref().unwrap().non_breaking_space_positions.clone();
@@ -969,7 +1857,7 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
     } else if main_node.name.is_some() {
         debug_assert!(optional_new_text.is_none());
 
-        childs_to_recurse_on = &main_node.children;
+        childs_to_recurse_on = main_node.children.clone();
 
         match &main_node.name_for_layout {
 
@@ -984,18 +1872,33 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
 
                 partial_node_line_break = true;
 
-                let font = get_font_given_styles(&partial_node_styles);
+                let font = get_font_given_styles(&partial_node_styles, font_context);
                 partial_node_font = Some(font.0);
                 partial_node_font_color = Some(font.1);
             }
 
-            TagName::Img => {
+            TagName::Button => {
+                //the children of a button are laid out inside of it
+                partial_node_is_button = true;
+            }
+
+            TagName::Iframe => {
+                //TODO: the frame is loaded again every time its layout node is rebuilt, we might want to keep the nested document on the DOM node
+                let iframe_src = main_node.get_attribute_value("src");
+                let iframe_url = iframe_src.map(|src| Url::from_base_url(&src, Some(&document.base_url))).filter(|url| document.allows_subresource(url));
+                let width = parse_size_attribute(main_node.get_attribute_value("width")).unwrap_or(IFRAME_DEFAULT_WIDTH);
+                let height = parse_size_attribute(main_node.get_attribute_value("height")).unwrap_or(IFRAME_DEFAULT_HEIGHT);
+
+                partial_node_iframe = Some(IframeLayoutNode { location: Rect { x: 0.0, y: 0.0, width, height }, url: iframe_url, job_tracker: None,
+                                                              document: None, interpreter: None, full_layout: None, scroll_y: 0.0 });
+                childs_to_recurse_on = None; //the content of an iframe element is only for browsers that don't support frames
+            }
+
+            TagName::Img | TagName::Svg => {
                 if main_node.image.is_some() {
-                    //TODO: eventually it would be nice to point in some cache of resources somewhere (possibly indirectly via an id if
-                    //      ownership causes issues). For now we just clone every time we built the layout node.
-                    partial_node_optional_img = Some(main_node.image.as_ref().unwrap().deref().clone());
+                    partial_node_optional_img = Some(Arc::clone(main_node.image.as_ref().unwrap()));
                 }
-                childs_to_recurse_on = &None; //images should not have children (its a tag that does not have a close tag, formally)
+                childs_to_recurse_on = None; //images should not have children (its a tag that does not have a close tag, formally)
             }
 
             TagName::Input => {
@@ -1005,32 +1908,59 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
 
                 if input_type.is_none() || input_type.as_ref().unwrap() == "text" {
                     partial_node_is_text_input = true;
-                } else if input_type.is_some() && input_type.as_ref().unwrap() == "submit" {
-                    partial_node_is_submit_button = true;
+                } else if input_type.is_some() && (input_type.as_ref().unwrap() == "submit" || input_type.as_ref().unwrap() == "reset") {
+                    partial_node_is_button = true;
+                } else if input_type.is_some() && input_type.as_ref().unwrap() == "file" {
+                    partial_node_is_file_input = true;
                 } else {
                     debug_log_warn(format!("Unknown type of input element: {}", input_type.unwrap()));
                 }
             }
 
+            TagName::Textarea => {
+                partial_node_is_text_area = true;
+                childs_to_recurse_on = None; //the text inside the textarea is its initial value, which the text area component shows
+            }
+
             //TODO: this one might not be neccesary any more after we fix our html parser to not try to parse the javascript
             TagName::Script => { partial_node_visible = false; }
 
             //TODO: same as for "script", do these need nodes in the DOM? probably not
             TagName::Style => { partial_node_visible = false; }
 
-            //TODO: eventually we want to do something else with the title (update the window title or so)
+            //the title is shown as the window title, not in the page itself
             TagName::Title => { partial_node_visible = false; }
 
+            //the head only contains metadata, and the html parser now always creates one
+            TagName::Head => { partial_node_visible = false; }
+
+            TagName::Details => {
+                //a closed details element only shows its summary
+                if main_node.get_attribute_value("open").is_none() {
+                    childs_to_recurse_on = main_node.children.as_ref().map(|children| {
+                        return children.iter().filter(|child_id| document.nodes[**child_id].name.as_deref() == Some("summary")).take(1).cloned().collect();
+                    });
+                }
+            }
+
+            TagName::Summary => {
+                let possible_details = document.find_details_for_summary(main_node_id);
+                if possible_details.is_some() {
+                    let open = document.nodes[possible_details.unwrap()].get_attribute_value("open").is_some();
+                    let color = get_color_style_value(&partial_node_styles, "color").unwrap_or(Color::BLACK);
+                    partial_node_disclosure_marker = Some(DisclosureMarker { open, color });
+                }
+            }
+
             TagName::Table => {
-                childs_to_recurse_on = &None; // we handle the children in our own method //TODO: it would still be nice to re-use the block/inline logic below
-                drop(main_node);
-                prebuilt_node = Some(build_layout_tree_for_table(main_node_refcell, document, font_context, layout_state));
+                childs_to_recurse_on = None; // we handle the children in our own method //TODO: it would still be nice to re-use the block/inline logic below
+                prebuilt_node = Some(build_layout_tree_for_table(nodes, main_node_id, document, font_context, layout_state));
             }
 
             TagName::Other => {}
         }
     } else if main_node.is_document_node {
-        childs_to_recurse_on = &main_node.children;
+        childs_to_recurse_on = main_node.children.clone();
     }
 
     let has_mixed_inline_and_block = {
@@ -1040,8 +1970,8 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
             let mut block_seen = false;
             let mut inline_seen = false;
 
-            for child in childs_to_recurse_on.as_ref().unwrap() {
-                match get_display_type(&child) {
+            for child_id in childs_to_recurse_on.as_ref().unwrap() {
+                match get_display_type(&document.nodes[*child_id]) {
                     Display::Block => {
                         if inline_seen {
                             has_mixed_inline_and_block = true;
@@ -1070,43 +2000,39 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
 
         if has_mixed_inline_and_block {
             let mut temp_inline_child_buffer = Vec::new();
-            let background_color = partial_node_background_color;
 
-            for child in childs_to_recurse_on.as_ref().unwrap() {
+            for child_id in childs_to_recurse_on.as_ref().unwrap() {
 
-                if get_display_type(&child) == Display::Block {
+                if get_display_type(&document.nodes[*child_id]) == Display::Block {
                     if !temp_inline_child_buffer.is_empty() {
-                        let layout_childs = build_layout_for_inline_nodes(&temp_inline_child_buffer, document, font_context, layout_state);
+                        let layout_childs = build_layout_for_inline_nodes(nodes, &temp_inline_child_buffer, document, font_context, layout_state);
 
-                        let anon_block = build_anonymous_block_layout_node(true, layout_childs, background_color);
+                        let anon_block = build_anonymous_block_layout_node(nodes, true, layout_childs);
                         partial_node_children.as_mut().unwrap().push(anon_block);
 
                         temp_inline_child_buffer = Vec::new();
                     }
 
                     layout_state.last_char_was_space = false;
-                    let layout_child = build_layout_tree(child, document, font_context, layout_state, None);
+                    let layout_child = build_layout_tree(nodes, *child_id, document, font_context, layout_state, None);
                     partial_node_children.as_mut().unwrap().push(layout_child);
 
                 } else {
-                    temp_inline_child_buffer.push(child);
+                    temp_inline_child_buffer.push(*child_id);
                 }
             }
 
             if !temp_inline_child_buffer.is_empty() {
-                let layout_childs = build_layout_for_inline_nodes(&temp_inline_child_buffer, document, font_context, layout_state);
+                let layout_childs = build_layout_for_inline_nodes(nodes, &temp_inline_child_buffer, document, font_context, layout_state);
 
-                let anon_block = build_anonymous_block_layout_node(true, layout_childs, background_color);
+                let anon_block = build_anonymous_block_layout_node(nodes, true, layout_childs);
                 partial_node_children.as_mut().unwrap().push(anon_block);
             }
 
-        } else if get_display_type(&first_child) == Display::Inline {
+        } else if get_display_type(&document.nodes[*first_child]) == Display::Inline {
 
-            let mut inline_nodes_to_layout = Vec::new();
-            for child in childs_to_recurse_on.as_ref().unwrap() {
-                inline_nodes_to_layout.push(child);
-            }
-            let layout_childs = build_layout_for_inline_nodes(&inline_nodes_to_layout, document, font_context, layout_state);
+            let inline_nodes_to_layout = childs_to_recurse_on.as_ref().unwrap();
+            let layout_childs = build_layout_for_inline_nodes(nodes, inline_nodes_to_layout, document, font_context, layout_state);
 
             for layout_child in layout_childs {
                 partial_node_children.as_mut().unwrap().push(layout_child);
@@ -1114,9 +2040,9 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
 
         } else { //This means all childs are Display::Block
 
-            for child in childs_to_recurse_on.as_ref().unwrap() {
+            for child_id in childs_to_recurse_on.as_ref().unwrap() {
                 layout_state.last_char_was_space = false;
-                let layout_child = build_layout_tree(child, document, font_context, layout_state, None);
+                let layout_child = build_layout_tree(nodes, *child_id, document, font_context, layout_state, None);
                 partial_node_children.as_mut().unwrap().push(layout_child);
             }
         }
@@ -1125,9 +2051,10 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
 
     if prebuilt_node.is_some() {
         //TODO: we could just return this prebuilt_node everywhere we build it, but I want to investigate what to do with the inline/block child logic in between
-        return Rc::new(RefCell::from(prebuilt_node.unwrap()));
+        return prebuilt_node.unwrap();
     }
 
+    let display = get_display_type(main_node);
     let content = if partial_node_text.is_some() {
         let rect = TextLayoutRect {
             char_position_mapping: font_context.compute_char_position_mapping(&partial_node_font.as_ref().unwrap(), &partial_node_text.as_ref().unwrap()),
@@ -1145,6 +2072,7 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
             rects: vec![rect],
             pre_wrap_rect_backup: None,
             background_color: partial_node_background_color,
+            text_shadows: resolve_text_shadows(&partial_node_styles),
         };
         LayoutNodeContent::TextLayoutNode(text_node)
 
@@ -1152,63 +2080,118 @@ fn build_layout_tree(main_node: &Rc<RefCell<ElementDomNode>>, document: &Documen
         let img_node = ImageLayoutNode { image: partial_node_optional_img.unwrap(), location: Rect::empty() };
         LayoutNodeContent::ImageLayoutNode(img_node)
 
-    } else if partial_node_is_submit_button {
+    } else if partial_node_is_button {
         LayoutNodeContent::ButtonLayoutNode(ButtonLayoutNode { location: Rect::empty() })
 
     } else if partial_node_is_text_input {
         LayoutNodeContent::TextInputLayoutNode(TextInputLayoutNode { location: Rect::empty() })
 
+    } else if partial_node_is_text_area {
+        LayoutNodeContent::TextAreaLayoutNode(TextAreaLayoutNode { location: Rect::empty() })
+
+    } else if partial_node_iframe.is_some() {
+        LayoutNodeContent::IframeLayoutNode(partial_node_iframe.unwrap())
+
+    } else if partial_node_is_file_input {
+        LayoutNodeContent::FileInputLayoutNode(FileInputLayoutNode { location: Rect::empty() })
+
     } else {
-        LayoutNodeContent::BoxLayoutNode(BoxLayoutNode { location: Rect::empty(), background_color: partial_node_background_color })
+        let background_style = resolve_background_style(&partial_node_styles);
+        let mut background_image = None;
+        if background_style.is_some() {
+            let background_style = background_style.unwrap();
+            //if the image is not loaded (yet), the document will mark us dirty once it arrives
+            let image = document.background_images.get(&background_style.image_url);
+            if image.is_some() {
+                background_image = Some(BackgroundImage { image: Arc::clone(image.unwrap()), style: background_style });
+            }
+        }
+
+        //TODO: borders on inline boxes are not supported yet, they would need to be split over the lines the box is on
+        let borders = if display == Display::Block { resolve_borders(&partial_node_styles) } else { Borders::none() };
+
+        LayoutNodeContent::BoxLayoutNode(BoxLayoutNode { location: Rect::empty(), background_color: partial_node_background_color, background_image,
+                                                         box_shadows: resolve_box_shadows(&partial_node_styles), borders,
+                                                         disclosure_marker: partial_node_disclosure_marker })
     };
 
     let new_node = LayoutNode {
-        internal_id: get_next_layout_node_interal_id(),
-        display: get_display_type(main_node_refcell),
+        display,
         visible: partial_node_visible,
+        opacity: partial_node_opacity,
+        transitions: resolve_transitions(&partial_node_styles),
         children: partial_node_children,
-        from_dom_node: Some(Rc::clone(&main_node_refcell)),
+        from_dom_node: Some(main_node_id),
         content: content,
     };
 
-    return Rc::new(RefCell::from(new_node));
+    return nodes.insert(new_node);
+}
+
+
+fn parse_size_attribute(attribute_value: Option<String>) -> Option<f32> {
+    //sizes in attributes are in pixels, but some pages still add the unit
+    if attribute_value.is_none() {
+        return None;
+    }
+    let size = attribute_value.unwrap().trim().trim_end_matches("px").parse::<f32>().ok();
+    if size.is_none() || size.unwrap() < 0.0 {
+        return None;
+    }
+    return size;
 }
 
 
-fn build_layout_tree_for_table(table_dom_node: &Rc<RefCell<ElementDomNode>>, document: &Document,
-                               font_context: &FontContext, layout_state: &mut LayoutBuildState) -> LayoutNode {
+fn build_layout_tree_for_table(nodes: &mut Arena<LayoutNode>, table_dom_node_id: DomNodeId, document: &Document,
+                               font_context: &FontContext, layout_state: &mut LayoutBuildState) -> LayoutNodeId {
+    let table_dom_node = &document.nodes[table_dom_node_id];
     let mut layout_children = Vec::new();
-    if table_dom_node.borrow().children.is_some() {
+    if table_dom_node.children.is_some() {
 
         let mut slot_x_idx = 0;
         let mut slot_y_idx = 0;
 
-        for dom_table_child in table_dom_node.borrow().children.as_ref().unwrap() {
+        //the html parser puts rows in a table section (tbody, thead or tfoot), but we also accept them directly in the table
+        let mut dom_rows = Vec::new();
+        for dom_table_child_id in table_dom_node.children.as_ref().unwrap() {
+            let dom_table_child = &document.nodes[*dom_table_child_id];
+            let is_table_section = dom_table_child.name.is_some() && ["tbody", "thead", "tfoot"].contains(&dom_table_child.name.as_ref().unwrap().as_str());
+            if is_table_section && dom_table_child.children.is_some() {
+                for dom_section_child_id in dom_table_child.children.as_ref().unwrap() {
+                    dom_rows.push(*dom_section_child_id);
+                }
+            } else {
+                dom_rows.push(*dom_table_child_id);
+            }
+        }
+
+        for dom_table_child_id in dom_rows.iter() {
 
-            let dom_table_child = dom_table_child.borrow();
+            let dom_table_child = &document.nodes[*dom_table_child_id];
             if dom_table_child.name.is_some() && dom_table_child.name.as_ref().unwrap() == &String::from("tr") {
 
                 if dom_table_child.children.is_some() {
-                    for dom_row_child in dom_table_child.children.as_ref().unwrap() {
+                    for dom_row_child_id in dom_table_child.children.as_ref().unwrap() {
 
-                        let borr_dom_row_child = dom_row_child.borrow();
-                        if borr_dom_row_child.name.is_some() && borr_dom_row_child.name.as_ref().unwrap() == &String::from("td") {
+                        let dom_row_child = &document.nodes[*dom_row_child_id];
+                        if dom_row_child.name.is_some() && dom_row_child.name.as_ref().unwrap() == &String::from("td") {
 
                             let mut cell_children = Vec::new();
 
-                            if borr_dom_row_child.children.is_some() {
-                                for dom_cell_child in borr_dom_row_child.children.as_ref().unwrap() {
-                                    let layout_child = build_layout_tree(dom_cell_child, document, font_context, layout_state, None);
+                            if dom_row_child.children.is_some() {
+                                for dom_cell_child_id in dom_row_child.children.as_ref().unwrap() {
+                                    let layout_child = build_layout_tree(nodes, *dom_cell_child_id, document, font_context, layout_state, None);
                                     cell_children.push(layout_child);
                                 }
                             }
 
                             let cell_layout_node = LayoutNode {
-                                internal_id: get_next_layout_node_interal_id(),
                                 children: Some(cell_children),
-                                from_dom_node: Some(dom_row_child.clone()),
+                                from_dom_node: Some(*dom_row_child_id),
                                 display: Display::Block,
                                 visible: true,
+                                opacity: 1.0,
+                                transitions: Vec::new(),
                                 content: LayoutNodeContent::TableCellLayoutNode(TableCellLayoutNode {
                                     location: Rect::empty(),
                                     slot_x_idx,
@@ -1216,7 +2199,7 @@ fn build_layout_tree_for_table(table_dom_node: &Rc<RefCell<ElementDomNode>>, doc
                                 })
                             };
 
-                            layout_children.push(Rc::from(RefCell::from(cell_layout_node)));
+                            layout_children.push(nodes.insert(cell_layout_node));
                         }
 
                         //TODO: handle other cases, we at least also have table body, in which case we need to recurse somehow
@@ -1227,61 +2210,82 @@ fn build_layout_tree_for_table(table_dom_node: &Rc<RefCell<ElementDomNode>>, doc
                 }
             }
 
-            //TODO: handle other cases, we at least also have table body, in which case we need to recurse somehow
-            //      there might also be text (at the very least whitespace that we should ignore) in between rows and cells
+            //TODO: handle other cases, there might also be text (at the very least whitespace that we should ignore) in between rows and cells
 
             slot_y_idx += 1;
         }
     }
 
-    return LayoutNode {
-        internal_id: get_next_layout_node_interal_id(),
+    return nodes.insert(LayoutNode {
         children: Some(layout_children),
-        from_dom_node: Some(table_dom_node.clone()),
+        from_dom_node: Some(table_dom_node_id),
         display: Display::Block,
         visible: true,
+        opacity: 1.0,
+        transitions: Vec::new(),
         content: LayoutNodeContent::TableLayoutNode(TableLayoutNode {
             location: Rect::empty(),
         })
-    }
+    });
 }
 
 
-pub fn rebuild_dirty_layout_childs(main_node: &Rc<RefCell<LayoutNode>>, document: &Document, font_context: &FontContext) {
-    let mut main_node_mut = RefCell::borrow_mut(main_node);
-    let main_node_children = &mut main_node_mut.children;
+pub fn rebuild_dirty_layout_childs(full_layout: &mut FullLayout, node_id: LayoutNodeId, document: &mut Document, font_context: &FontContext) {
+    //we first find all the dirty childs, so we can compute the styles for everything we are going to build again at once
+    let mut dirty_childs = Vec::new();
+    collect_dirty_layout_childs(full_layout, node_id, document, &mut dirty_childs);
+    if dirty_childs.is_empty() {
+        return;
+    }
+
+    let mut dom_node_ids = HashSet::new();
+    for (_, _, dom_node_id) in &dirty_childs {
+        document.collect_node_ids(*dom_node_id, &mut dom_node_ids);
+    }
+    let resolved_styles = compute_styles(&dom_node_ids, &mut document.nodes, &document.style_context);
+    let mut layout_build_state = LayoutBuildState { last_char_was_space: false, resolved_styles };
+
+    for (parent_id, child_idx, dom_node_id) in dirty_childs {
+        layout_build_state.last_char_was_space = false; //TODO: is there ever a case where this needs to be not false?
+                                                        //      maybe when replacing in a series of inline nodes?
+        let old_child = full_layout.nodes[parent_id].children.as_ref().unwrap()[child_idx];
+        let new_child = build_layout_tree(&mut full_layout.nodes, dom_node_id, document, font_context, &mut layout_build_state, None);
+        full_layout.nodes[parent_id].children.as_mut().unwrap()[child_idx] = new_child;
+        full_layout.remove_subtree(old_child);
+    }
+}
 
-    if main_node_children.is_some() {
-        for child_idx in 0..main_node_children.as_ref().unwrap().len() {
-            let child = &main_node_children.as_ref().unwrap()[child_idx];
 
-            if child.borrow().from_dom_node.is_some() && child.borrow().from_dom_node.as_ref().unwrap().borrow().dirty {
-                let mut layout_build_state = LayoutBuildState { last_char_was_space: false }; //TODO: is there ever a case where this needs to be not false?
-                                                                                              //      maybe when replacing in a series of inline nodes?
-                let new_child = build_layout_tree(&child.borrow().from_dom_node.as_ref().unwrap(), document, font_context, &mut layout_build_state, None);
-                main_node_children.as_mut().unwrap()[child_idx] = new_child;
+fn collect_dirty_layout_childs(full_layout: &FullLayout, node_id: LayoutNodeId, document: &Document,
+                               dirty_childs: &mut Vec<(LayoutNodeId, usize, DomNodeId)>) {
+    //this collects the (parent, index in the parent, dom node) of the layout nodes that need to be built again. We don't look further into those.
+    if full_layout.nodes[node_id].children.is_none() {
+        return;
+    }
 
-            } else {
-                rebuild_dirty_layout_childs(&child, document, font_context);
-            }
+    for (child_idx, child_id) in full_layout.nodes[node_id].children.as_ref().unwrap().iter().enumerate() {
+        let from_dom_node = full_layout.nodes[*child_id].from_dom_node;
 
+        if from_dom_node.is_some() && document.nodes[from_dom_node.unwrap()].dirty {
+            dirty_childs.push((node_id, child_idx, from_dom_node.unwrap()));
+        } else {
+            collect_dirty_layout_childs(full_layout, *child_id, document, dirty_childs);
         }
     }
 }
 
 
-fn build_layout_for_inline_nodes(inline_nodes: &Vec<&Rc<RefCell<ElementDomNode>>>, document: &Document, font_context: &FontContext,
-                                 state: &mut LayoutBuildState) -> Vec<Rc<RefCell<LayoutNode>>> {
+fn build_layout_for_inline_nodes(nodes: &mut Arena<LayoutNode>, inline_nodes: &Vec<DomNodeId>, document: &Document, font_context: &FontContext,
+                                 state: &mut LayoutBuildState) -> Vec<LayoutNodeId> {
 
     let mut optional_new_text;
     let mut layout_nodes = Vec::new();
     let last_node_idx = inline_nodes.len();
 
-    for (node_idx, node) in inline_nodes.iter().enumerate() {
-
-        if RefCell::borrow(node).text.is_some() {
-            let node = RefCell::borrow(node);
+    for (node_idx, node_id) in inline_nodes.iter().enumerate() {
+        let node = &document.nodes[*node_id];
 
+        if node.text.is_some() {
             let node_text = &node.text.as_ref().unwrap().text_content;
             let mut new_text = String::new();
             for (char_idx, c) in node_text.chars().enumerate() {
@@ -1307,7 +2311,7 @@ fn build_layout_for_inline_nodes(inline_nodes: &Vec<&Rc<RefCell<ElementDomNode>>
             optional_new_text = None;
         }
 
-        let layout_child = build_layout_tree(node, document, font_context, state, optional_new_text);
+        let layout_child = build_layout_tree(nodes, *node_id, document, font_context, state, optional_new_text);
         layout_nodes.push(layout_child);
     }
 
@@ -1315,22 +2319,25 @@ fn build_layout_for_inline_nodes(inline_nodes: &Vec<&Rc<RefCell<ElementDomNode>>
 }
 
 
-fn build_anonymous_block_layout_node(visible: bool, inline_children: Vec<Rc<RefCell<LayoutNode>>>, background_color: Color) -> Rc<RefCell<LayoutNode>> {
-    let id_of_node_being_built = get_next_layout_node_interal_id();
-
+fn build_anonymous_block_layout_node(nodes: &mut Arena<LayoutNode>, visible: bool, inline_children: Vec<LayoutNodeId>) -> LayoutNodeId {
     let empty_box_layout_node = BoxLayoutNode {
         location: Rect::empty(),
-        background_color,
+        background_color: Color::TRANSPARENT, //the background is already painted by the parent of the anonymous block
+        background_image: None,
+        box_shadows: Vec::new(),
+        borders: Borders::none(),
+        disclosure_marker: None,
     };
 
     let anonymous_node = LayoutNode {
-        internal_id: id_of_node_being_built,
         display: Display::Block,
         visible: visible,
+        opacity: 1.0,
+        transitions: Vec::new(),
         children: Some(inline_children),
         from_dom_node: None,
         content: LayoutNodeContent::BoxLayoutNode(empty_box_layout_node),
     };
 
-    return Rc::new(RefCell::from(anonymous_node));
+    return nodes.insert(anonymous_node);
 }
@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+#[cfg(feature="timings")] use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::arena::Arena;
+use crate::dom::DomNodeId;
+use crate::layout::{
+    compute_layout_for_node,
+    LayoutDocument,
+    LayoutNode,
+    LayoutNodeContent,
+    LayoutNodeId,
+    LayoutNodes,
+};
+use crate::platform::fonts::FontContext;
+use crate::ui_components::PageComponent;
+
+
+//The childs of a block only depend on each other for their vertical position, so the ones that need to be laid out again are laid out at the
+//top, each on a thread of its own, and are moved down to their position afterwards. The document and the font context are not thread safe, so
+//the threads only know which dom nodes are dirty, and get a copy of the font context. This means childs that need more from the document (like
+//form fields, which need their page components, or iframes, which need the document in them) are laid out on the main thread as before.
+
+
+struct LayoutSubtree<'a> {
+    nodes: HashMap<LayoutNodeId, &'a mut LayoutNode>, //the nodes in the subtree of one child, which no other thread can get to
+}
+impl Index<LayoutNodeId> for LayoutSubtree<'_> {
+    type Output = LayoutNode;
+
+    fn index(&self, id: LayoutNodeId) -> &LayoutNode {
+        return self.nodes.get(&id).expect("layout node is not in the subtree laid out on this thread");
+    }
+}
+impl IndexMut<LayoutNodeId> for LayoutSubtree<'_> {
+    fn index_mut(&mut self, id: LayoutNodeId) -> &mut LayoutNode {
+        return self.nodes.get_mut(&id).expect("layout node is not in the subtree laid out on this thread");
+    }
+}
+impl LayoutNodes for LayoutSubtree<'_> {}
+//SAFETY: layout nodes are not Send, because an iframe node has the document of the frame, which is in an Rc. Subtrees are only made for childs
+//        without iframe nodes in them (see collect_subtree()), and the other content of layout nodes is Send.
+unsafe impl Send for LayoutSubtree<'_> {}
+
+
+struct DirtyDomNodes {
+    nodes: HashSet<DomNodeId>,
+}
+impl LayoutDocument for DirtyDomNodes {
+    fn is_dirty(&self, dom_node_id: DomNodeId) -> bool {
+        return self.nodes.contains(&dom_node_id);
+    }
+    fn page_component(&self, _: DomNodeId) -> Option<&Rc<RefCell<PageComponent>>> {
+        panic!("childs with page components should not be laid out on other threads");
+    }
+}
+
+
+pub fn lay_out_childs_in_parallel<N: LayoutNodes, D: LayoutDocument>(nodes: &mut N, child_ids: &Vec<LayoutNodeId>, document: &D, top_left_x: f32,
+                                                                     right_edge: f32, current_scroll_y: f32, font_context: &FontContext,
+                                                                     force_full_layout: bool) -> HashSet<LayoutNodeId> {
+    //returns the childs that were laid out, at y 0, the others still need to be laid out
+    let arena = nodes.as_arena();
+    if arena.is_none() {
+        //we already are on one of the threads, which lays out the whole subtree of its child
+        return HashSet::new();
+    }
+    let arena = arena.unwrap();
+
+    let mut childs_to_lay_out = Vec::new();
+    let mut child_of_node = HashMap::new(); //the index of the child (in childs_to_lay_out) whose subtree the node is in
+    let mut dirty_dom_nodes = HashSet::new();
+    let mut fonts = HashSet::new();
+
+    for child_id in child_ids {
+        if !force_full_layout && !arena.is_dirty_anywhere(*child_id, document) {
+            //this child is only moved (see compute_layout_for_node), which is not worth a thread
+            continue;
+        }
+
+        let mut subtree_node_ids = Vec::new();
+        if !collect_subtree(arena, *child_id, &mut subtree_node_ids) {
+            continue;
+        }
+
+        for node_id in &subtree_node_ids {
+            let node = &arena[*node_id];
+            if node.from_dom_node.is_some() && document.is_dirty(node.from_dom_node.unwrap()) {
+                dirty_dom_nodes.insert(node.from_dom_node.unwrap());
+            }
+            if let LayoutNodeContent::TextLayoutNode(text_node) = &node.content {
+                fonts.extend(text_node.rects.iter().map(|rect| rect.font.to_font_key()));
+            }
+            child_of_node.insert(*node_id, childs_to_lay_out.len());
+        }
+        childs_to_lay_out.push(*child_id);
+    }
+
+    if childs_to_lay_out.len() < 2 {
+        return HashSet::new();
+    }
+
+    //the fonts are loaded here, so the copies of the font context all have them, instead of each thread loading them again
+    for font_key in fonts {
+        font_context.get_font_data(&font_key);
+    }
+
+    let mut subtrees: Vec<LayoutSubtree> = childs_to_lay_out.iter().map(|_| LayoutSubtree { nodes: HashMap::new() }).collect();
+    for (node_id, node) in arena.iter_mut() {
+        let child_idx = child_of_node.get(&node_id);
+        if child_idx.is_some() {
+            subtrees[*child_idx.unwrap()].nodes.insert(node_id, node);
+        }
+    }
+
+    #[cfg(feature="timings")] let start_instant = Instant::now();
+    let dirty_dom_nodes = DirtyDomNodes { nodes: dirty_dom_nodes };
+    let jobs: Vec<(LayoutNodeId, LayoutSubtree, FontContext)> = childs_to_lay_out.iter().zip(subtrees)
+                                                                    .map(|(child_id, subtree)| (*child_id, subtree, font_context.copy_for_other_thread())).collect();
+    jobs.into_par_iter().for_each(|(child_id, mut subtree, font_context)| {
+        compute_layout_for_node(&mut subtree, child_id, &dirty_dom_nodes, top_left_x, 0.0, right_edge, &font_context, current_scroll_y, false, force_full_layout);
+    });
+    #[cfg(feature="timings")] println!("laying out {} block childs in parallel elapsed millis: {}", childs_to_lay_out.len(), start_instant.elapsed().as_millis());
+
+    return childs_to_lay_out.into_iter().collect();
+}
+
+
+fn collect_subtree(arena: &Arena<LayoutNode>, node_id: LayoutNodeId, node_ids: &mut Vec<LayoutNodeId>) -> bool {
+    //returns false when the subtree can't be laid out on another thread
+    let node = &arena[node_id];
+    match &node.content {
+        LayoutNodeContent::TextLayoutNode(_) | LayoutNodeContent::ImageLayoutNode(_) | LayoutNodeContent::BoxLayoutNode(_) => {},
+        _ => { return false; }, //these need their page component or their document (or are tables, which we can't lay out yet)
+    }
+    node_ids.push(node_id);
+
+    if node.children.is_some() {
+        for child_id in node.children.as_ref().unwrap() {
+            if !collect_subtree(arena, *child_id, node_ids) {
+                return false;
+            }
+        }
+    }
+    return true;
+}
@@ -1,17 +1,28 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::dom::{Document, ElementDomNode};
+use crate::arena::Arena;
+use crate::dom::{Document, DocumentReadyState};
+use crate::html_lexer;
+use crate::html_parser;
 use crate::jsonify::{
     compare_json,
     dom_node_from_json,
     layout_node_to_json,
+    pretty_print_json,
 };
-use crate::layout::{build_full_layout, compute_layout};
+use crate::layout::{build_full_layout, compute_layout, FullLayout};
+use crate::network::CacheMode;
 use crate::network::url::Url;
+use crate::platform::KeyCode;
 use crate::platform::fonts::FontContext;
-use crate::style::StyleContext;
+use crate::style::{
+    media_query::MediaEnvironment,
+    StyleContext,
+};
+use crate::test_util::compare_with_snapshot;
 
 
 #[test]
@@ -23,54 +34,231 @@ fn test_basic_paragraph_layout() {
         { "text": "this is a test" }
     "#.to_owned();
 
-    let main_dom_node = Rc::from(RefCell::from(dom_node_from_json(&dom_json)));
-
-    let mut all_nodes = HashMap::new();
-    build_all_nodes_from_document_node(&main_dom_node, &mut all_nodes);
+    let mut nodes = Arena::new();
+    let main_dom_node = nodes.insert_with(|id| dom_node_from_json(&dom_json, id));
 
-    let style_context = StyleContext { user_agent_sheet: Vec::new() , author_sheet: Vec::new() };
+    let style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
     let font_context = FontContext::new();
 
-    let document = Document {
-        all_nodes: all_nodes,
+    let mut document = Document {
+        nodes: nodes,
         document_node: main_dom_node,
         style_context: style_context,
+        pending_style_imports: Vec::new(),
+        background_images: HashMap::new(),
+        background_image_job_trackers: HashMap::new(),
+        background_images_need_scheduling: false,
+        nodes_to_post_construct: Vec::new(),
+        ready_state: DocumentReadyState::Complete,
+        cache_mode: CacheMode::Default,
+        page_url: Url::empty(),
         base_url: Url::empty(),
     };
 
     let expected_layout_tree_json = r#"
         {
-            "color": [255, 255, 255],
+            "kind": "box",
+            "display": "block",
+            "visible": true,
+            "color": [255, 255, 255, 255],
             "location": [0, 0, 87, 19],
             "childs": [
                 {
-                    "color": [255, 255, 255],
+                    "kind": "text",
+                    "display": "inline",
+                    "visible": true,
+                    "color": [0, 0, 0, 0],
                     "rects": [
                         {
                             "text": "this is a test",
-                            "position": [0, 0, 87, 19]
+                            "position": [0, 0, 87, 19],
+                            "font": {"face": "default", "size": 18, "bold": false, "italic": false},
+                            "font_color": [0, 0, 0, 255]
                         }
                     ]
                 }
             ]
         }"#;
 
-    let tree = build_full_layout(&document, &font_context);
-    compute_layout(&tree.root_node, &document.style_context, 0.0, 0.0, &font_context, 0.0, false, true);
-    let tree_json = layout_node_to_json(&tree.root_node.borrow());
+    let mut tree = build_full_layout(&mut document, &font_context);
+    compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+    let tree_json = layout_node_to_json(&tree, tree.root_node);
 
     assert!(compare_json(&tree_json, &String::from(expected_layout_tree_json)));
 }
 
 
-fn build_all_nodes_from_document_node(dom_node: &Rc<RefCell<ElementDomNode>>, all_nodes_map: &mut HashMap<usize, Rc<RefCell<ElementDomNode>>>) {
+#[test]
+fn test_layout_snapshots() {
+    //every html file in testinput/layout is laid out, and its layout tree is compared to the json file with the same name
+    let snapshot_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("testinput").join("layout");
+    let mut html_paths: Vec<PathBuf> = fs::read_dir(&snapshot_directory).unwrap().map(|entry| entry.unwrap().path())
+                                                                          .filter(|path| path.extension() == Some(OsStr::new("html"))).collect();
+    html_paths.sort();
+    assert!(!html_paths.is_empty());
+    let font_context = FontContext::new();
+
+    let mut differences = Vec::new();
+    for html_path in html_paths {
+        let html = fs::read_to_string(&html_path).unwrap();
+        let mut document = html_parser::parse(html_lexer::lex_html(&html), &Url::empty());
+        let document_node = document.document_node;
+        document.post_construct(document_node, &font_context);
+
+        let mut tree = build_full_layout(&mut document, &font_context);
+        compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+        let tree_json = pretty_print_json(&layout_node_to_json(&tree, tree.root_node)) + "\n";
 
-    if dom_node.borrow().children.is_some() {
-        for child in dom_node.borrow().children.as_ref().unwrap() {
-            build_all_nodes_from_document_node(&child, all_nodes_map);
+        let difference = compare_with_snapshot(&html_path.with_extension("json"), &tree_json);
+        if difference.is_some() {
+            differences.push(difference.unwrap());
         }
     }
+    assert!(differences.is_empty(), "{}", differences.join("\n"));
+}
+
+
+#[test]
+fn test_block_childs_laid_out_in_parallel() {
+    //the paragraphs are laid out on other threads, and the text field (which needs its page component) on the main thread, in between them
+    let html = r#"<html><body><p>first</p><p>second line</p><input type="text"><div><p>nested</p><p>paragraphs</p></div><p>last</p></body></html>"#;
+    let mut document = html_parser::parse(html_lexer::lex_html(html), &Url::empty());
+    let font_context = FontContext::new();
+    let document_node = document.document_node;
+    document.post_construct(document_node, &font_context);
+
+    let mut tree = build_full_layout(&mut document, &font_context);
+    compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+    let content_boxes = |tree: &FullLayout| tree.nodes_in_selection_order.iter().map(|node_id| {
+        let bounding_box = tree.nodes[*node_id].get_bounding_box().unwrap();
+        return (tree.nodes[*node_id].content.kind_name(), bounding_box.y, bounding_box.height);
+    }).collect::<Vec<(&str, f32, f32)>>();
+
+    //everything is below what comes before it, with the text field between the second and the nested paragraph
+    let first_layout = content_boxes(&tree);
+    let kinds: Vec<&str> = first_layout.iter().map(|(kind, _, _)| *kind).collect();
+    assert_eq!(kinds, vec!["text", "text", "text input", "text", "text", "text"]);
+    for idx in 1..first_layout.len() {
+        assert!(first_layout[idx].1 >= first_layout[idx - 1].1 + first_layout[idx - 1].2);
+    }
+
+    //when some of the paragraphs change, the layout is the same as when we laid out everything
+    for node_id in [tree.nodes_in_selection_order[0], tree.nodes_in_selection_order[5]] {
+        let dom_node_id = tree.nodes[node_id].from_dom_node.unwrap();
+        document.nodes[dom_node_id].dirty = true;
+    }
+    compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, false);
+    assert_eq!(content_boxes(&tree), first_layout);
+}
+
+
+#[test]
+fn test_text_wraps_on_the_width_of_the_viewport() {
+    //when the window gets smaller, the page is laid out again for the smaller viewport, and the text needs more lines
+    let html = format!("<html><body><p>{}</p></body></html>", "some words to wrap ".repeat(40));
+    let mut document = html_parser::parse(html_lexer::lex_html(&html), &Url::empty());
+    let font_context = FontContext::new();
+    let document_node = document.document_node;
+    document.post_construct(document_node, &font_context);
+
+    let mut page_size_for_width = |width: f32| -> (f32, f32) {
+        document.style_context.set_media_environment(MediaEnvironment { width, ..MediaEnvironment::new_default() });
+        let mut tree = build_full_layout(&mut document, &font_context);
+        compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+        let bounding_box = tree.nodes[tree.root_node].get_bounding_box().unwrap();
+        return (bounding_box.width, bounding_box.height);
+    };
+
+    let (wide_width, wide_height) = page_size_for_width(1000.0);
+    let (narrow_width, narrow_height) = page_size_for_width(400.0);
+    assert!(wide_width <= 1000.0 && narrow_width <= 400.0);
+    assert!(narrow_height > wide_height * 2.0);
+}
+
+
+#[test]
+fn test_select_all() {
+    let html = r#"<html><body><p>first</p><script>var hidden = true;</script><p>second <img src="image.png"> third</p></body></html>"#;
+    let mut document = html_parser::parse(html_lexer::lex_html(html), &Url::empty());
+    let font_context = FontContext::new();
+
+    let mut tree = build_full_layout(&mut document, &font_context);
+    compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+
+    tree.select_all(tree.root_node);
+    assert_eq!(tree.get_selected_content(&document.nodes, &Url::empty()).text, "first\nsecond third");
+
+    tree.reset_selection();
+    assert!(tree.get_selected_content(&document.nodes, &Url::empty()).text.is_empty());
+}
+
+
+#[test]
+fn test_find_layout_node_at_position() {
+    let html = r#"<html><body><p>first</p><p>second</p></body></html>"#;
+    let mut document = html_parser::parse(html_lexer::lex_html(html), &Url::empty());
+    let font_context = FontContext::new();
+
+    let mut tree = build_full_layout(&mut document, &font_context);
+    compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+    let kind_at = |x: f32, y: f32| tree.find_layout_node_at_position(tree.root_node, x, y).map(|node_id| tree.nodes[node_id].content.kind_name());
+
+    //the deepest node is found, which is the text, not the box around it
+    let text_box = tree.nodes[tree.nodes_in_selection_order[0]].get_bounding_box().unwrap();
+    assert_eq!(kind_at(text_box.x + 1.0, text_box.y + 1.0), Some("text"));
+    assert_eq!(kind_at(text_box.x + text_box.width + 5.0, text_box.y + 1.0), Some("box"));
+    assert_eq!(kind_at(10000.0, 10000.0), None);
+}
+
+
+#[test]
+fn test_extend_selection() {
+    let html = r#"<html><body><p>first</p><p>second</p></body></html>"#;
+    let mut document = html_parser::parse(html_lexer::lex_html(html), &Url::empty());
+    let font_context = FontContext::new();
+
+    let mut tree = build_full_layout(&mut document, &font_context);
+    compute_layout(&mut tree, &mut document, 0.0, 0.0, &font_context, 0.0, false, true);
+    let selected_text = |tree: &FullLayout| tree.get_selected_content(&document.nodes, &Url::empty()).text;
+
+    assert!(!tree.extend_selection(KeyCode::RIGHT)); //there is nothing selected to extend yet
+
+    tree.select_all(tree.root_node);
+    assert!(tree.extend_selection(KeyCode::LEFT));
+    assert_eq!(selected_text(&tree), "first\nsecon");
+
+    assert!(tree.extend_selection(KeyCode::HOME));
+    assert_eq!(selected_text(&tree), "first");
+
+    assert!(tree.extend_selection(KeyCode::UP));
+    assert!(selected_text(&tree).is_empty());
+
+    assert!(tree.extend_selection(KeyCode::END));
+    assert_eq!(selected_text(&tree), "first");
+
+    assert!(tree.extend_selection(KeyCode::DOWN));
+    assert!(tree.extend_selection(KeyCode::END));
+    assert_eq!(selected_text(&tree), "first\nsecond");
+
+    tree.reset_selection();
+    assert!(selected_text(&tree).is_empty());
+}
+
+
+#[test]
+fn test_selected_content() {
+    let html = r#"<html><body><p>a <a href="/page">link</a></p><table><tr><td>one</td><td>two <img src="image.png" alt="picture"> end</td></tr>"#.to_owned()
+             + r#"<tr><td>three</td><td>four</td></tr></table></body></html>"#;
+    let mut document = html_parser::parse(html_lexer::lex_html(&html), &Url::empty());
+    let font_context = FontContext::new();
+
+    //the positions don't matter for what is copied, so we don't compute the layout (which is not implemented for tables yet)
+    let mut tree = build_full_layout(&mut document, &font_context);
+    tree.select_all(tree.root_node);
 
-    let new_rc = Rc::clone(dom_node);
-    all_nodes_map.insert(dom_node.borrow().internal_id, new_rc);
+    let selected_content = tree.get_selected_content(&document.nodes, &Url::from(&String::from("https://example.com/dir/")));
+    assert_eq!(selected_content.text, "a link\none\ttwo picture end\nthree\tfour");
+    assert_eq!(selected_content.html, concat!("<p>a <a href=\"https://example.com/page\">link</a></p>",
+                                              "<table><tr><td>one</td><td>two <img src=\"https://example.com/dir/image.png\" alt=\"picture\"> end</td></tr>",
+                                              "<tr><td>three</td><td>four</td></tr></table>"));
 }
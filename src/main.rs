@@ -1,70 +1,88 @@
+mod address_bar;
+mod animation;
+mod arena;
+mod browsing_history;
 mod color;
+mod config;
+mod date;
 mod debug;
+mod display_list;
 mod dom;
 mod html_lexer;
 mod html_parser;
-#[cfg(test)] mod jsonify; //TODO: would also like to use it for debug, not sure how to configure that. feature flag on the crate maybe?
+mod jsonify;
 mod layout;
 mod macros;
 mod network;
+mod page;
+mod page_cache;
 mod platform;
+mod profiler;
 mod renderer;
 mod resource_loader;
 mod script;
 mod style;
+mod svg;
 mod ui;
 mod ui_components;
+mod wpt;
 #[cfg(test)] mod test_util; //TODO: is there a better (test-specific) place to define this?
 
 use std::{
-    cell::RefCell,
     cmp,
     env,
-    ops::DerefMut,
-    rc::Rc,
+    fs,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
     thread,
     time::{Duration, Instant},
 };
 
 use arboard::Clipboard;
 use sdl2::{
-    event::Event as SdlEvent,
+    event::{Event as SdlEvent, WindowEvent},
     keyboard::{Keycode, Mod as SdlKeyMod},
     mouse::MouseButton,
 };
-use threadpool::ThreadPool;
 
 use crate::debug::debug_log_warn;
-use crate::dom::{Document, NavigationAction};
+use crate::display_list::DisplayList;
+use crate::dom::{DocumentReadyState, NavigationAction};
 use crate::layout::{
-    collect_content_nodes_in_walk_order,
-    compute_layout,
     FullLayout,
-    LayoutNode,
-    rebuild_dirty_layout_childs,
+    LayoutNodeId,
     Rect,
+    SelectedContent,
     TextLayoutRect,
 };
+use crate::config::{Config, SETTINGS};
+use crate::network::{host_overrides_from_config, CacheMode, HttpClientSettings};
 use crate::network::url::Url;
+use crate::page::Page;
 use crate::platform::Platform;
-use crate::resource_loader::{ResourceRequestJobTracker, ResourceThreadPool};
-use crate::renderer::render;
-use crate::script::js_interpreter;
+use crate::profiler::{FramePhase, Measurement, PROFILER};
+use crate::resource_loader::ResourceThreadPool;
+use crate::renderer::{render, render_page_to_image};
+use crate::script::js_events::DomEvent;
+use crate::style::media_query::ColorScheme;
 use crate::ui::{
-    CONTENT_HEIGHT,
-    CONTENT_TOP_LEFT_X,
     CONTENT_TOP_LEFT_Y,
     FocusTarget,
     HEADER_HEIGHT,
     History,
-    MAIN_SCROLLBAR_HEIGHT,
-    MAIN_SCROLLBAR_X_POS,
+    MAIN_SCROLLBAR_WIDTH,
+    Tooltip,
     UIState,
+    WindowDimensions,
 };
 use crate::ui_components::{
+    ContextMenuAction,
+    ContextMenuTarget,
     NavigationButton,
+    ReloadButton,
     TextField,
     Scrollbar,
+    SuggestionList,
 };
 
 
@@ -72,9 +90,18 @@ use crate::ui_components::{
 const TARGET_FPS: u32 = if cfg!(debug_assertions) { 20 } else { 60 };
 const SCREEN_WIDTH: f32 = 1400.0;
 const SCREEN_HEIGHT: f32 = 800.0;
-const DEFAULT_LOCATION_TO_LOAD: &str = "about:home";
 const SCROLL_SPEED: i32 = 25;
+const ADDRESSBAR_HEIGHT: f32 = 35.0;
 const NR_RESOURCE_LOADING_THREADS: usize = 4;
+const MAX_CONCURRENT_RESOURCE_LOADS: usize = 16;
+const MAX_CONCURRENT_IMAGE_DECODES: usize = 2;
+const HTTP_MAX_IDLE_CONNECTIONS_PER_HOST: usize = NR_RESOURCE_LOADING_THREADS;
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_IFRAME_DEPTH: usize = 3;
+const MAX_CACHED_PAGES: usize = 5;
+const HEADLESS_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+const SCRIPT_BUDGET_PER_FRAME: Duration = Duration::from_millis(TARGET_MS_PER_FRAME as u64 / 2);
 
 
 //Non-config constants:
@@ -93,94 +120,20 @@ fn frame_time_check(start_instant: &Instant) {
 }
 
 
-fn handle_left_click(ui_state: &mut UIState, x: f32, y: f32, page_relative_mouse_y: f32, full_layout: &FullLayout, document: &Document) -> NavigationAction {
-    let possible_url = ui::handle_possible_ui_click(ui_state, x, y);
-    if possible_url.is_some() {
-        return NavigationAction::Get(possible_url.unwrap());
-    }
-
-    return full_layout.root_node.borrow().click(x, page_relative_mouse_y, document);
-}
-
-
 pub struct MouseState {
     x: i32,
     y: i32,
     click_start_x: i32,
     click_start_y: i32,
     left_down: bool,
+    dragged_content: Option<DraggedContent>, //when the mouse went down on a link or on selected text, dragging moves that instead of selecting
 }
 
 
-pub fn start_navigate(navigation_action: &NavigationAction, platform: &Platform, ui_state: &mut UIState,
-                      resource_thread_pool: &mut ResourceThreadPool) -> ResourceRequestJobTracker<String> {
-
-    let tracker = match navigation_action {
-        NavigationAction::None => {
-            panic!("Illegal state"); // we should not get in this method if we have nothing to navigate to...
-        },
-        NavigationAction::Get(url) => {
-            ui_state.addressbar.set_text(platform, url.to_string());
-
-            if !ui_state.history.currently_navigating_from_history {
-                ui::register_in_history(ui_state, url);
-            }
-
-            resource_loader::schedule_load_text(&url, resource_thread_pool) //TODO: should this be a different thread pool, or rename it?
-        },
-        NavigationAction::Post(post_data) => {
-            ui_state.addressbar.set_text(platform, post_data.url.to_string());
-
-            if !ui_state.history.currently_navigating_from_history {
-                //TODO: we should actually record the postdata in the history. Or actually the whole page, and not request again? How do other browsers do this?
-                ui::register_in_history(ui_state, &post_data.url);
-            }
-
-            resource_loader::submit_post(&post_data.url, &post_data.fields, resource_thread_pool) //TODO: should this be a different thread pool, or rename it?
-        }
-    };
-
-
-    ui_state.currently_loading_page = true;
-    ui_state.history.currently_navigating_from_history = false;
-    ui::update_history_buttons(ui_state);
-
-    return tracker;
-}
-
-
-fn finish_navigate(navigation_action: &NavigationAction, ui_state: &mut UIState, page_content: &String, document: &RefCell<Document>,
-                   full_layout: &RefCell<FullLayout>, platform: &mut Platform, resource_thread_pool: &mut ResourceThreadPool) {
-
-    let url = match navigation_action {
-        NavigationAction::None => {
-            panic!("Illegal state"); // we should not get in this method if we have nothing to navigate to...
-        },
-        NavigationAction::Get(url) => { url },
-        NavigationAction::Post(post_data) => { &post_data.url },
-    };
-
-    let lex_result = html_lexer::lex_html(&page_content);
-    document.replace(html_parser::parse(lex_result, &url));
-
-    document.borrow_mut().document_node.borrow_mut().post_construct(platform);
-    document.borrow_mut().update_all_dom_nodes(resource_thread_pool);
-
-    //for now we run scripts here, because we don't want to always run them fully in the main loop, and we need to have the DOM before we run
-    //but I'm not sure this is really the correct place
-    let mut interpreter = js_interpreter::JsInterpreter::new();
-    interpreter.run_scripts_in_document(document);
-
-    #[cfg(feature="timings")] let start_layout_instant = Instant::now();
-    full_layout.replace(layout::build_full_layout(&document.borrow(), &platform.font_context));
-
-    ui_state.current_scroll_y = 0.0;
-    ui_state.currently_loading_page = false;
-
-    compute_layout(&full_layout.borrow().root_node, &document.borrow().style_context, CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y,
-                   &platform.font_context, ui_state.current_scroll_y, false, true);
-
-    #[cfg(feature="timings")] println!("layout elapsed millis: {}", start_layout_instant.elapsed().as_millis());
+#[cfg_attr(debug_assertions, derive(Debug))]
+enum DraggedContent {
+    Link(Url),
+    Text(String),
 }
 
 
@@ -206,8 +159,8 @@ fn build_selection_rect_on_text_layout_rect(text_layout_rect: &mut TextLayoutRec
 }
 
 
-fn compute_selection_regions(layout_node: &Rc<RefCell<LayoutNode>>, selection_rect: &Rect, current_scroll_y: f32, nodes_in_selection_order: &Vec<Rc<RefCell<LayoutNode>>>) {
-    if !layout_node.borrow().visible_on_y_location(current_scroll_y) {
+fn compute_selection_regions(full_layout: &mut FullLayout, layout_node_id: LayoutNodeId, selection_rect: &Rect, current_scroll_y: f32) {
+    if !full_layout.nodes[layout_node_id].visible_on_y_location(current_scroll_y) {
         return;
     }
 
@@ -218,7 +171,7 @@ fn compute_selection_regions(layout_node: &Rc<RefCell<LayoutNode>>, selection_re
     let selection_end_x = selection_rect.x + selection_rect.width;
     let selection_end_y = selection_rect.y + selection_rect.height;
 
-    match &mut layout_node.borrow_mut().content {
+    match &mut full_layout.nodes[layout_node_id].content {
         layout::LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
             let mut start_x_for_selection_rect_on_layout_rect = 0.0;
             let mut start_idx_for_selection = 0;
@@ -271,28 +224,32 @@ fn compute_selection_regions(layout_node: &Rc<RefCell<LayoutNode>>, selection_re
         }
         layout::LayoutNodeContent::ButtonLayoutNode(_) => {}
         layout::LayoutNodeContent::TextInputLayoutNode(_) => {}
+        layout::LayoutNodeContent::TextAreaLayoutNode(_) => {}
+        layout::LayoutNodeContent::FileInputLayoutNode(_) => {}
+        layout::LayoutNodeContent::IframeLayoutNode(_) => {}
         layout::LayoutNodeContent::BoxLayoutNode(_) => {
             //Note: this is a no-op for now, since there is nothing to select in a box node itself (just in its children)
         },
         layout::LayoutNodeContent::NoContent => {},
-        layout::LayoutNodeContent::TableLayoutNode(_) => todo!(), //TODO: implement
-        layout::LayoutNodeContent::TableCellLayoutNode(_) => todo!(), //TODO: implement
+        layout::LayoutNodeContent::TableLayoutNode(_) | layout::LayoutNodeContent::TableCellLayoutNode(_) => {
+            //the text in a table is in the children of its cells, which we visit below
+        },
     }
 
     if selection_start_found {
         //Now we are going to walk the layout nodes to find the node where the selection ends, and all nodes in between
 
         let mut starting_node_found = false;
-        for next_selection_node in nodes_in_selection_order {
+        for next_selection_node_id in full_layout.nodes_in_selection_order.clone() {
 
             if !starting_node_found {
-                if next_selection_node.borrow().internal_id == layout_node.borrow().internal_id {
+                if next_selection_node_id == layout_node_id {
                     starting_node_found = true;
                 }
                 continue;
             } else {
 
-                match &mut next_selection_node.borrow_mut().content {
+                match &mut full_layout.nodes[next_selection_node_id].content {
                     layout::LayoutNodeContent::TextLayoutNode(ref mut text_layout_node) => {
 
                         for mut layout_rect in text_layout_node.rects.iter_mut() {
@@ -312,89 +269,230 @@ fn compute_selection_regions(layout_node: &Rc<RefCell<LayoutNode>>, selection_re
                     layout::LayoutNodeContent::ImageLayoutNode(_) => {},
                     layout::LayoutNodeContent::ButtonLayoutNode(_) => {},
                     layout::LayoutNodeContent::TextInputLayoutNode(_) => {},
+                    layout::LayoutNodeContent::TextAreaLayoutNode(_) => {},
+                    layout::LayoutNodeContent::FileInputLayoutNode(_) => {},
+                    layout::LayoutNodeContent::IframeLayoutNode(_) => {},
                     layout::LayoutNodeContent::BoxLayoutNode(_) => {},
                     layout::LayoutNodeContent::NoContent => {},
-                    layout::LayoutNodeContent::TableLayoutNode(_) => todo!(), //TODO: implement
-                    layout::LayoutNodeContent::TableCellLayoutNode(_) => todo!(), //TODO: implement
+                    layout::LayoutNodeContent::TableLayoutNode(_) => {},
+                    layout::LayoutNodeContent::TableCellLayoutNode(_) => {},
                 }
             }
         }
     }
 
-    if layout_node.borrow().children.is_some() {
-        for child in layout_node.borrow().children.as_ref().unwrap() {
-            compute_selection_regions(&child, selection_rect, current_scroll_y, nodes_in_selection_order);
+    if full_layout.nodes[layout_node_id].children.is_some() {
+        for child_id in full_layout.nodes[layout_node_id].children.clone().unwrap() {
+            compute_selection_regions(full_layout, child_id, selection_rect, current_scroll_y);
         }
     }
 }
 
 
+fn drop_dragged_content(dragged_content: DraggedContent, page: &mut Page, platform: &mut Platform, ui_state: &mut UIState,
+                        resource_thread_pool: &mut ResourceThreadPool, x: f32, y: f32) {
+    //TODO: SDL has no way to start a drag to other programs, so for now links and text can only be dropped on the address bar and on text fields
+    if ui_state.addressbar.is_inside(x, y) {
+        let url = match dragged_content {
+            DraggedContent::Link(url) => url,
+            DraggedContent::Text(text) => address_bar::url_for_typed_text(&text, &SETTINGS.lock().unwrap().search_url),
+        };
+        page.navigate(NavigationAction::Get(url), platform, ui_state, resource_thread_pool);
+        return;
+    }
+
+    if y >= CONTENT_TOP_LEFT_Y && y < ui_state.window_dimensions.status_bar_y() {
+        let text = match dragged_content {
+            DraggedContent::Link(url) => url.to_string(),
+            DraggedContent::Text(text) => text,
+        };
+        ui::drop_text(page, platform, ui_state, x, y, &text);
+    }
+}
+
+
+fn copy_selected_content(selected_content: SelectedContent) {
+    //other programs pick the flavor they support, so rich text editors get the html, and plain text editors the text
+    Clipboard::new().unwrap().set_html(selected_content.html, Some(selected_content.text)).expect("Unhandled clipboard error");
+}
+
+
+fn handle_context_menu_action(action: ContextMenuAction, target: &ContextMenuTarget, page: &mut Page, platform: &mut Platform, ui_state: &mut UIState,
+                              resource_thread_pool: &mut ResourceThreadPool) {
+    match action {
+        ContextMenuAction::Back | ContextMenuAction::Forward => {
+            let possible_url = if action == ContextMenuAction::Back { ui_state.history.go_back() } else { ui_state.history.go_forward() };
+            if possible_url.is_some() {
+                page.navigate(NavigationAction::Get(possible_url.unwrap()), platform, ui_state, resource_thread_pool);
+            }
+        },
+        ContextMenuAction::Reload => {
+            page.reload(CacheMode::Default, platform, ui_state, resource_thread_pool);
+        },
+        ContextMenuAction::Copy => {
+            copy_selected_content(page.selected_content());
+        },
+        ContextMenuAction::CopyLinkAddress => {
+            Clipboard::new().unwrap().set_text(target.link_url.as_ref().unwrap().to_string()).expect("Unhandled clipboard error");
+        },
+        ContextMenuAction::OpenLinkInNewTab => {
+            //the item is disabled until we have tabs, opening the link here would replace the current page instead
+        },
+        ContextMenuAction::SaveImageAs => {
+            let (image, image_url) = target.image.as_ref().unwrap();
+            let suggested_file_name = image_url.as_ref().and_then(|url| url.path.last().cloned()).filter(|name| !name.is_empty())
+                                               .unwrap_or(String::from("image.png"));
+            let path = platform::pick_save_file(&suggested_file_name);
+            if path.is_some() {
+                //the format is picked based on the extension of the file
+                let save_result = image.save(path.as_ref().unwrap());
+                if save_result.is_err() {
+                    debug_log_warn(format!("could not save the image to {:?}: {}", path.unwrap(), save_result.err().unwrap()));
+                }
+            }
+        },
+        ContextMenuAction::InspectElement => {
+            debug::print_dom_node_for_inspection(&page.document.borrow(), target.dom_node.unwrap());
+        },
+        ContextMenuAction::ViewPageSource => {
+            let source_url = format!("view-source:{}", page.document.borrow().page_url.without_fragment().to_string());
+            page.navigate(NavigationAction::Get(Url::from(&source_url)), platform, ui_state, resource_thread_pool);
+        },
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+enum HeadlessOutput {
+    Screenshot(PathBuf),
+    Json(PathBuf),
+}
+
+
+fn save_screenshot(platform: &mut Platform, page_display_list: &DisplayList, scroll_y: f32, window_dimensions: WindowDimensions,
+                   path: &PathBuf) -> Result<(), String> {
+    let image = render_page_to_image(platform, page_display_list, scroll_y, window_dimensions);
+    if image.is_none() {
+        return Err(String::from("could not render the page to an image"));
+    }
+    return image.unwrap().save(path).map_err(|error| format!("could not save the screenshot to {:?}: {}", path, error));
+}
+
+
 fn main() -> Result<(), String> {
+    //with --screenshot <url> <file> the page is loaded without showing the window, and what it looks like is saved to the file
+    //with --dump-json <url> <file> the same happens, but the dom and layout trees of the page are saved to the file as json
+    //TODO: dialogs (like alerts) are still shown in this mode, and wait for the user
+    let args: Vec<String> = env::args().collect();
+
+    //with --wpt <wpt directory> [<test directories>...] the web platform tests are run, without a window, and the results are printed
+    if args.len() > 1 && args[1] == "--wpt" {
+        if args.len() < 3 {
+            return Err(String::from("usage: webcrustacean --wpt <wpt directory> [<test directories>...]"));
+        }
+        return wpt::run_wpt(&PathBuf::from(&args[2]), &args[3..]);
+    }
+
+    let headless_output = if args.len() > 1 && (args[1] == "--screenshot" || args[1] == "--dump-json") {
+        if args.len() != 4 {
+            return Err(format!("usage: webcrustacean {} <url> <file>", args[1]));
+        }
+        let path = PathBuf::from(&args[3]);
+        Some(if args[1] == "--screenshot" { HeadlessOutput::Screenshot(path) } else { HeadlessOutput::Json(path) })
+    } else {
+        None
+    };
+    let url_arg = if headless_output.is_some() { args.get(2) } else { args.get(1) };
+
     let sdl_context = sdl2::init()?;
-    let mut platform = platform::init_platform(sdl_context).unwrap();
+    let mut platform = platform::init_platform(sdl_context, headless_output.is_some()).unwrap();
 
-    let mut resource_thread_pool = ResourceThreadPool { pool: ThreadPool::new(NR_RESOURCE_LOADING_THREADS) };
+    let config = Config::load();
+    let configured_color_scheme = config.get("ui.color_scheme").and_then(|value| ColorScheme::from_str(value));
+    if configured_color_scheme.is_some() {
+        //the setting overrides what the OS uses, which is detected when the platform is initialized
+        platform.color_scheme.set(configured_color_scheme.unwrap());
+    }
+    let http_client_settings = HttpClientSettings { max_idle_connections_per_host: HTTP_MAX_IDLE_CONNECTIONS_PER_HOST,
+                                                    connect_timeout: HTTP_CONNECT_TIMEOUT, request_timeout: HTTP_REQUEST_TIMEOUT,
+                                                    user_agent: SETTINGS.lock().unwrap().user_agent.clone(), proxy: SETTINGS.lock().unwrap().proxy.clone(),
+                                                    host_overrides: host_overrides_from_config(&config) };
+    let mut resource_thread_pool = ResourceThreadPool::new(NR_RESOURCE_LOADING_THREADS, MAX_CONCURRENT_RESOURCE_LOADS, MAX_CONCURRENT_IMAGE_DECODES,
+                                                           &http_client_settings);
 
-    let mut mouse_state = MouseState { x: 0, y: 0, click_start_x: 0, click_start_y: 0, left_down: false };
+    let mut mouse_state = MouseState { x: 0, y: 0, click_start_x: 0, click_start_y: 0, left_down: false, dragged_content: None };
 
-    let addressbar_text_field = TextField::new(100.0, 10.0, SCREEN_WIDTH - 200.0, 35.0, true);
+    let window_dimensions = WindowDimensions::new_default();
+    let (addressbar_x, addressbar_y) = (135.0, 10.0);
+    let addressbar_width = ui::addressbar_width(window_dimensions, addressbar_x);
+    let addressbar_text_field = TextField::new(addressbar_x, addressbar_y, addressbar_width, ADDRESSBAR_HEIGHT, true);
 
     //TODO: this setting up of components should happen in the ui module eventually
     let main_scrollbar = Scrollbar {
-        x: MAIN_SCROLLBAR_X_POS,
+        x: window_dimensions.content_width(),
         y: HEADER_HEIGHT,
-        width: SCREEN_WIDTH,
-        height: MAIN_SCROLLBAR_HEIGHT,
+        width: MAIN_SCROLLBAR_WIDTH,
+        height: window_dimensions.content_height(),
         content_size: 0.0,
-        content_visible_height: CONTENT_HEIGHT,
-        block_height: MAIN_SCROLLBAR_HEIGHT,
+        content_visible_height: window_dimensions.content_height(),
+        block_height: window_dimensions.content_height(),
         block_y: HEADER_HEIGHT,
         enabled: false,
     };
 
     let mut ui_state = UIState {
+        window_dimensions,
         addressbar: addressbar_text_field,
-        current_scroll_y: 0.0,
         back_button: NavigationButton { x: 15.0, y: 15.0, forward: false, enabled: false },
         forward_button: NavigationButton { x: 55.0, y: 15.0, forward: true, enabled: false },
-        history: History { list: Vec::new(), position: 0, currently_navigating_from_history: false },
+        reload_button: ReloadButton { x: 95.0, y: 15.0 },
+        history: History { list: Vec::new(), position: 0, currently_navigating_from_history: false, shown_entry_id: None },
         currently_loading_page: false,
+        loading_progress: None,
         animation_tick: 0,
         focus_target: FocusTarget::None,
+        focus_visible: false,
         main_scrollbar: main_scrollbar,
+        context_menu: None,
+        address_bar_suggestions: SuggestionList::new(addressbar_x, addressbar_y + ADDRESSBAR_HEIGHT, addressbar_width),
+        bookmarks: config.get_list("bookmarks").iter().map(|bookmark| Url::from(bookmark)).collect(),
+        hovered_link: None,
+        resource_counts: (0, 0),
+        nr_of_script_errors: 0,
+        layout_overlay: false,
+        layout_overlay_tooltip: None,
+        profiler_overlay: false,
     };
 
-    let document = RefCell::from(Document::new_empty());
-    let full_layout_tree = RefCell::from(FullLayout::new_empty());
+    let mut page = Page::new(MAX_CACHED_PAGES);
 
-    let args: Vec<String> = env::args().collect();
-    let start_url = if args.len() < 2 {
-        Url::from(&DEFAULT_LOCATION_TO_LOAD.to_owned())
+    let start_url = if url_arg.is_none() {
+        Url::from(&SETTINGS.lock().unwrap().start_url)
     } else {
-        Url::from(&args[1])
+        Url::from(url_arg.unwrap())
     };
-    document.borrow_mut().base_url = start_url.clone();
-    let mut ongoing_navigation = Some(NavigationAction::Get(start_url));
+    page.document.borrow_mut().page_url = start_url.clone();
+    page.document.borrow_mut().base_url = start_url.clone();
+    page.start_loading(NavigationAction::Get(start_url), &platform, &mut ui_state, &mut resource_thread_pool);
+    let start_instant = Instant::now();
 
-    let mut main_page_job_tracker = start_navigate(&ongoing_navigation.as_ref().unwrap(), &platform, &mut ui_state, &mut resource_thread_pool);
-
-    let mut event_pump = platform.sdl_context.event_pump()?;
     'main_loop: loop {
         let start_loop_instant = Instant::now();
 
-        if ongoing_navigation.is_some() {
-            let try_recv_result = main_page_job_tracker.receiver.try_recv();
-            if try_recv_result.is_ok() {
-                finish_navigate(&ongoing_navigation.unwrap(), &mut ui_state, &try_recv_result.ok().unwrap(), &document, &full_layout_tree, &mut platform, &mut resource_thread_pool);
-                ongoing_navigation = None;
-            }
-        }
-
-        ui_state.current_scroll_y = ui_state.main_scrollbar.update_content_size(full_layout_tree.borrow().page_height(), ui_state.current_scroll_y);
+        page.update_loading(&mut platform, &mut ui_state, &mut resource_thread_pool);
+        page.update_scroll(&mut ui_state.main_scrollbar, Instant::now());
 
-        #[cfg(feature="timings")] let start_event_pump_instant = Instant::now();
-        for event in event_pump.poll_iter() {
+        let events_measurement = Measurement::start();
+        //we take the events out of the event pump first, since scripts that run for an event can open dialogs, which use the event pump as well
+        let events: Vec<SdlEvent> = platform.event_pump.borrow_mut().poll_iter().collect();
+        for event in events {
             match event {
+                SdlEvent::KeyDown { keycode: Some(Keycode::Escape), .. } if ui_state.context_menu.is_some() => {
+                    ui_state.context_menu = None;
+                },
+                SdlEvent::KeyDown { keycode: Some(Keycode::Escape), .. } if !ui_state.address_bar_suggestions.suggestions.is_empty() => {
+                    ui_state.address_bar_suggestions.clear();
+                },
                 SdlEvent::Quit {..} | SdlEvent::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'main_loop;
                 },
@@ -402,26 +500,39 @@ fn main() -> Result<(), String> {
                     mouse_state.x = mouse_x;
                     mouse_state.y = mouse_y;
 
-                    if mouse_state.left_down {
+                    if ui_state.context_menu.is_some() {
+                        ui_state.context_menu.as_mut().unwrap().mouse_move(mouse_x as f32, mouse_y as f32);
+                    }
+
+                    let is_over_content = mouse_y as f32 >= CONTENT_TOP_LEFT_Y && (mouse_y as f32) < ui_state.window_dimensions.status_bar_y();
+                    ui_state.hovered_link = if is_over_content { page.link_at_position(mouse_x as f32, mouse_y as f32) } else { None };
+                    if ui_state.layout_overlay {
+                        let description = if is_over_content { page.describe_layout_node_at_position(mouse_x as f32, mouse_y as f32) } else { None };
+                        ui_state.layout_overlay_tooltip = description.map(|text| Tooltip { text, x: mouse_x as f32, y: mouse_y as f32 });
+                    }
+
+                    if mouse_state.left_down && mouse_state.dragged_content.is_none() {
                         let top_left_x = cmp::min(mouse_state.click_start_x, mouse_x) as f32;
-                        let top_left_y = cmp::min(mouse_state.click_start_y, mouse_y) as f32 + ui_state.current_scroll_y;
+                        let top_left_y = cmp::min(mouse_state.click_start_y, mouse_y) as f32 + page.scroll_y;
                         let bottom_right_x = cmp::max(mouse_state.click_start_x, mouse_x) as f32;
-                        let bottom_right_y = cmp::max(mouse_state.click_start_y, mouse_y) as f32 + ui_state.current_scroll_y;
+                        let bottom_right_y = cmp::max(mouse_state.click_start_y, mouse_y) as f32 + page.scroll_y;
                         let selection_rect = Rect { x: top_left_x, y: top_left_y, width: bottom_right_x - top_left_x, height: bottom_right_y - top_left_y };
 
                         match ui_state.focus_target {
                             FocusTarget::None => {},
                             FocusTarget::MainContent => {
-                                RefCell::borrow_mut(&full_layout_tree.borrow_mut().root_node).reset_selection();
-                                let full_layout_tree = full_layout_tree.borrow();
-                                compute_selection_regions(&full_layout_tree.root_node, &selection_rect, ui_state.current_scroll_y,
-                                                          &full_layout_tree.nodes_in_selection_order);
+                                let mut full_layout = page.full_layout.borrow_mut();
+                                let root_node = full_layout.root_node;
+                                full_layout.reset_selection_in_subtree(root_node);
+                                compute_selection_regions(&mut full_layout, root_node, &selection_rect, page.scroll_y);
                             },
                             FocusTarget::AddressBar => {
                                 ui_state.addressbar.update_selection(&selection_rect);
                             },
+                            FocusTarget::Link(_) => {},
                             FocusTarget::ScrollBlock => {
-                                ui_state.current_scroll_y = ui_state.main_scrollbar.scroll(yrel as f32, ui_state.current_scroll_y);
+                                page.scroll_animation = None;
+                                page.scroll_y = ui_state.main_scrollbar.scroll(yrel as f32, page.scroll_y);
                             },
                             FocusTarget::Component(ref component) => {
                                 match component.borrow_mut().deref_mut() {
@@ -429,11 +540,31 @@ fn main() -> Result<(), String> {
                                     ui_components::PageComponent::TextField(text_field) => {
                                         text_field.update_selection(&selection_rect);
                                     },
+                                    ui_components::PageComponent::TextArea(_) => {
+                                        //TODO: implement selecting text inside a text area
+                                    },
+                                    ui_components::PageComponent::FileInput(_) => {},
                                 }
                             }
                         }
                     }
                 },
+                SdlEvent::MouseButtonDown { mouse_btn: MouseButton::Right, x: mouse_x, y: mouse_y, .. } => {
+                    //the context menu is only for the page, not for the header and the status bar
+                    if mouse_y as f32 >= CONTENT_TOP_LEFT_Y && (mouse_y as f32) < ui_state.window_dimensions.status_bar_y() {
+                        ui::open_context_menu(&page, &mut ui_state, mouse_x as f32, mouse_y as f32);
+                    }
+                },
+                SdlEvent::MouseButtonDown { mouse_btn: MouseButton::Left, .. } if ui_state.context_menu.is_some() => {
+                    //while the context menu is open, clicks are for the menu (which is handled on mouse up), or close it when outside of it
+                },
+                SdlEvent::MouseButtonDown { mouse_btn: MouseButton::Left, x: mouse_x, y: mouse_y, .. }
+                        if ui_state.address_bar_suggestions.is_inside(mouse_x as f32, mouse_y as f32) => {
+                    //the suggestion is picked when the mouse is released, so the click does not go to the page below the suggestions
+                },
+                SdlEvent::MouseButtonDown { mouse_btn: MouseButton::Left, y: mouse_y, .. } if mouse_y as f32 >= ui_state.window_dimensions.status_bar_y() => {
+                    //the status bar is on top of the page, so clicks on it should not go to the page below it
+                },
                 SdlEvent::MouseButtonDown { mouse_btn: MouseButton::Left, x: mouse_x, y: mouse_y, .. } => {
                     mouse_state.x = mouse_x;
                     mouse_state.y = mouse_y;
@@ -441,9 +572,36 @@ fn main() -> Result<(), String> {
                     mouse_state.click_start_y = mouse_y;
                     mouse_state.left_down = true;
 
-                    RefCell::borrow_mut(&full_layout_tree.borrow_mut().root_node).reset_selection();
+                    let is_over_content = mouse_y as f32 >= CONTENT_TOP_LEFT_Y;
+                    let possible_link = if is_over_content { page.link_at_position(mouse_x as f32, mouse_y as f32) } else { None };
+                    mouse_state.dragged_content = if possible_link.is_some() {
+                        Some(DraggedContent::Link(possible_link.unwrap()))
+                    } else if is_over_content && page.is_on_selection(mouse_x as f32, mouse_y as f32) {
+                        Some(DraggedContent::Text(page.selected_text()))
+                    } else {
+                        None
+                    };
+
+                    //the selection is kept while we might be dragging it, when it turns out to be a click, it is reset on mouse up
+                    if !matches!(mouse_state.dragged_content, Some(DraggedContent::Text(_))) {
+                        page.full_layout.borrow_mut().reset_selection();
+                    }
 
-                    ui::handle_possible_ui_mouse_down(&full_layout_tree.borrow().root_node, &document, &mut platform, &mut ui_state, mouse_x as f32, mouse_y as f32);
+                    ui::handle_possible_ui_mouse_down(&page, &mut platform, &mut ui_state, mouse_x as f32, mouse_y as f32);
+                    page.dispatch_mouse_event("mousedown", mouse_x as f32, mouse_y as f32);
+                },
+                SdlEvent::MouseButtonUp { mouse_btn: MouseButton::Left, x: mouse_x, y: mouse_y, .. } if ui_state.context_menu.is_some() => {
+                    let context_menu = ui_state.context_menu.take().unwrap();
+                    let action = context_menu.click(mouse_x as f32, mouse_y as f32);
+                    if action.is_some() {
+                        handle_context_menu_action(action.unwrap(), &context_menu.target, &mut page, &mut platform, &mut ui_state, &mut resource_thread_pool);
+                    }
+                },
+                SdlEvent::MouseButtonUp { mouse_btn: MouseButton::Left, x: mouse_x, y: mouse_y, .. }
+                        if ui_state.address_bar_suggestions.is_inside(mouse_x as f32, mouse_y as f32) => {
+                    let url = ui_state.address_bar_suggestions.click(mouse_x as f32, mouse_y as f32).unwrap().url.clone();
+                    ui_state.address_bar_suggestions.clear();
+                    page.navigate(NavigationAction::Get(url), &mut platform, &mut ui_state, &mut resource_thread_pool);
                 },
                 SdlEvent::MouseButtonUp { mouse_btn: MouseButton::Left, x: mouse_x, y: mouse_y, .. } => {
                     mouse_state.x = mouse_x;
@@ -455,26 +613,69 @@ fn main() -> Result<(), String> {
                         _ => {}
                     };
 
+                    page.dispatch_mouse_event("mouseup", mouse_x as f32, mouse_y as f32);
+
                     let abs_movement = (mouse_state.x - mouse_state.click_start_x).abs() + (mouse_state.y - mouse_state.click_start_y).abs();
                     let was_dragging = abs_movement > 4;
+                    let is_on_status_bar = mouse_y as f32 >= ui_state.window_dimensions.status_bar_y();
+                    let dragged_content = mouse_state.dragged_content.take();
 
-                    if !was_dragging {
-                        let page_relative_mouse_y = mouse_y as f32 + ui_state.current_scroll_y;
-                        let navigation_action = handle_left_click(&mut ui_state, mouse_x as f32, mouse_y as f32, page_relative_mouse_y, &full_layout_tree.borrow(), &document.borrow());
+                    if !was_dragging && matches!(dragged_content, Some(DraggedContent::Text(_))) {
+                        page.full_layout.borrow_mut().reset_selection();
+                    }
+
+                    if was_dragging && dragged_content.is_some() {
+                        drop_dragged_content(dragged_content.unwrap(), &mut page, &mut platform, &mut ui_state, &mut resource_thread_pool,
+                                             mouse_x as f32, mouse_y as f32);
+                    } else if !was_dragging && ui_state.reload_button.is_inside(mouse_x as f32, mouse_y as f32) {
+                        if ui_state.loading_progress.is_some() {
+                            page.stop(&platform, &mut ui_state, &mut resource_thread_pool);
+                        } else {
+                            page.reload(CacheMode::Default, &platform, &mut ui_state, &mut resource_thread_pool);
+                        }
+                    } else if !was_dragging && !is_on_status_bar {
+                        let navigation_action = page.handle_left_click(&mut ui_state, mouse_x as f32, mouse_y as f32, &platform);
 
                         //TODO: we should do this above in the next loop, just schedule the action for the next loop?
                         if navigation_action != NavigationAction::None {
-                            main_page_job_tracker = start_navigate(&navigation_action, &platform, &mut ui_state, &mut resource_thread_pool);
-                            ongoing_navigation = Some(navigation_action);
+                            page.navigate(navigation_action, &mut platform, &mut ui_state, &mut resource_thread_pool);
                         }
                     }
                 },
                 SdlEvent::MouseWheel { y, direction, .. } => {
+                    ui_state.context_menu = None;
                     match direction {
                         sdl2::mouse::MouseWheelDirection::Normal => {
-                            //TODO: someday it might be nice to implement smooth scrolling (animate the movement over frames)
-                            let new_page_scroll_y = ui_state.current_scroll_y - (y * SCROLL_SPEED) as f32;
-                            ui_state.current_scroll_y = ui_state.main_scrollbar.update_scroll(new_page_scroll_y);
+                            //a focused text area under the mouse scrolls its own content instead of the page
+                            let mut scrolled_text_area = false;
+                            match ui_state.focus_target {
+                                FocusTarget::Component(ref component) => {
+                                    match component.borrow_mut().deref_mut() {
+                                        ui_components::PageComponent::TextArea(text_area) => {
+                                            if text_area.is_inside(mouse_state.x as f32, mouse_state.y as f32) {
+                                                text_area.scroll(-y);
+                                                scrolled_text_area = true;
+                                            }
+                                        },
+                                        _ => {},
+                                    }
+                                },
+                                _ => {},
+                            }
+
+                            //the same goes for a frame under the mouse, as long as it can still scroll in that direction
+                            let mut scrolled_iframe = false;
+                            if !scrolled_text_area {
+                                let page_relative_mouse_y = mouse_state.y as f32 + page.scroll_y;
+                                let mut full_layout = page.full_layout.borrow_mut();
+                                let root_node = full_layout.root_node;
+                                scrolled_iframe = full_layout.scroll_iframe_at_position(root_node, mouse_state.x as f32, page_relative_mouse_y,
+                                                                                        -(y * SCROLL_SPEED) as f32);
+                            }
+
+                            if !scrolled_text_area && !scrolled_iframe {
+                                page.smooth_scroll_by(&ui_state.main_scrollbar, -(y * SCROLL_SPEED) as f32);
+                            }
                         },
                         sdl2::mouse::MouseWheelDirection::Flipped => {},
                         sdl2::mouse::MouseWheelDirection::Unknown(_) => debug_log_warn("Unknown mousewheel direction!"),
@@ -482,84 +683,230 @@ fn main() -> Result<(), String> {
                 },
                 SdlEvent::KeyDown { keycode, keymod, .. } => {
                     if keycode.is_some() {
+                        //when a script cancels the keydown, we don't handle the key ourselves
+                        //TODO: this should also prevent the text input for the key, which is a separate event for us
+                        let dom_key = platform.convert_key_to_dom_key(&keycode.unwrap());
+                        if !page.dispatch_keyboard_event("keydown", dom_key, &ui_state) {
+                            continue;
+                        }
+
+                        if keycode.unwrap() == Keycode::Tab {
+                            let backwards = keymod.intersects(SdlKeyMod::LSHIFTMOD | SdlKeyMod::RSHIFTMOD);
+                            ui::move_focus(&mut page, &mut platform, &mut ui_state, backwards);
+                            continue;
+                        }
+
+                        if keymod.contains(SdlKeyMod::LCTRLMOD) && (keycode.unwrap() == Keycode::Left || keycode.unwrap() == Keycode::Right) {
+                            if ui::move_cursor_by_word(&mut ui_state, keycode.unwrap() == Keycode::Right) {
+                                continue;
+                            }
+                        }
+
                         let key_code = platform.convert_key_code(&keycode.unwrap());
+                        if keymod.intersects(SdlKeyMod::LSHIFTMOD | SdlKeyMod::RSHIFTMOD) && key_code.is_some() {
+                            if ui::extend_selection(&page, &mut ui_state, key_code.unwrap()) {
+                                continue;
+                            }
+                        }
                         ui::handle_keyboard_input(&mut platform, None, key_code, &mut ui_state);
 
+                        if keycode.unwrap() == Keycode::F5 {
+                            //as in other browsers, ctrl+F5 is a hard reload, that does not use the http cache
+                            let cache_mode = if keymod.contains(SdlKeyMod::LCTRLMOD) { CacheMode::Reload } else { CacheMode::Default };
+                            page.reload(cache_mode, &platform, &mut ui_state, &mut resource_thread_pool);
+                        }
+
                         if keymod.contains(SdlKeyMod::LCTRLMOD) {
+                            if keycode.unwrap().name() == "R" {
+                                let cache_mode = if keymod.contains(SdlKeyMod::LSHIFTMOD) { CacheMode::Reload } else { CacheMode::Default };
+                                page.reload(cache_mode, &platform, &mut ui_state, &mut resource_thread_pool);
+                            }
+
                             if keycode.unwrap().name() == "C" {
-                                let mut text_for_clipboard = String::new();
-                                full_layout_tree.borrow().root_node.borrow().get_selected_text(&mut text_for_clipboard);
-                                if text_for_clipboard.is_empty() && ui_state.addressbar.has_selection_active() {
-                                    text_for_clipboard = ui_state.addressbar.get_selected_text();
+                                let selected_content = page.selected_content();
+                                if !selected_content.text.is_empty() {
+                                    copy_selected_content(selected_content);
+                                } else {
+                                    let text_for_clipboard = ui::selected_text_in_text_field(&ui_state);
+                                    if !text_for_clipboard.is_empty() {
+                                        Clipboard::new().unwrap().set_text(text_for_clipboard).expect("Unhandled clipboard error");
+                                    }
                                 }
+                            }
 
+                            if keycode.unwrap().name() == "X" {
+                                let text_for_clipboard = ui::cut_selected_text(&platform, &mut ui_state);
                                 if !text_for_clipboard.is_empty() {
-                                    let mut clipboard = Clipboard::new().unwrap();
-                                    clipboard.set_text(text_for_clipboard).expect("Unhandled clipboard error");
+                                    Clipboard::new().unwrap().set_text(text_for_clipboard).expect("Unhandled clipboard error");
+                                }
+                            }
+
+                            if keycode.unwrap().name() == "A" {
+                                ui::select_all(&page, &mut ui_state);
+                            }
+
+                            if keycode.unwrap().name() == "L" {
+                                ui::focus_address_bar(&page, &mut platform, &mut ui_state);
+                            }
+
+                            if keycode.unwrap().name() == "O" {
+                                let path = platform::pick_page_file();
+                                if path.is_some() {
+                                    //a path without a scheme is parsed as a file url, like the path of a dropped file
+                                    let url = Url::from(&path.unwrap().to_string_lossy().into_owned());
+                                    page.navigate(NavigationAction::Get(url), &mut platform, &mut ui_state, &mut resource_thread_pool);
+                                }
+                            }
+
+                            if keymod.contains(SdlKeyMod::LSHIFTMOD) && keycode.unwrap().name() == "D" {
+                                //TODO: frames keep the color scheme they were loaded with, until they are loaded again
+                                platform.color_scheme.set(platform.color_scheme.get().toggled());
+                                page.relayout(&ui_state, &platform);
+                            }
+
+                            if keymod.contains(SdlKeyMod::LSHIFTMOD) && keycode.unwrap().name() == "B" {
+                                //the tooltip shows up once the mouse moves
+                                ui_state.layout_overlay = !ui_state.layout_overlay;
+                                ui_state.layout_overlay_tooltip = None;
+                            }
+
+                            if keymod.contains(SdlKeyMod::LSHIFTMOD) && keycode.unwrap().name() == "P" {
+                                ui_state.profiler_overlay = !ui_state.profiler_overlay;
+                            }
+
+                            if keymod.contains(SdlKeyMod::LSHIFTMOD) && keycode.unwrap().name() == "J" {
+                                let path = resource_loader::save_in_download_directory(&String::from("webcrustacean-page.json"), page.to_json().as_bytes());
+                                if path.is_some() {
+                                    println!("saved the dom and layout of the page to {:?}", path.unwrap());
                                 }
                             }
 
                             if keycode.unwrap().name() == "V" {
-                                match ui_state.focus_target {
-                                    FocusTarget::AddressBar => {
-                                        let clipboard_text = Clipboard::new().unwrap().get_text().expect("Unhandled clipboard error");
-                                        ui_state.addressbar.insert_text(&platform, &clipboard_text);
-                                    },
-                                    _ => {},
+                                //when there is no text on the clipboard (but for example an image), there is nothing to paste
+                                let clipboard_text = Clipboard::new().unwrap().get_text();
+                                if clipboard_text.is_ok() {
+                                    ui::paste_text(&platform, &mut ui_state, &clipboard_text.unwrap());
                                 }
                             }
                         }
 
                         match ui_state.focus_target {
                             FocusTarget::None => {},
-                            FocusTarget::MainContent => {},
+                            FocusTarget::MainContent => {
+                                let key_code = platform.convert_key_code(&keycode.unwrap());
+                                if key_code.is_some() {
+                                    let shift_pressed = keymod.intersects(SdlKeyMod::LSHIFTMOD | SdlKeyMod::RSHIFTMOD);
+                                    page.scroll_for_key(&ui_state.main_scrollbar, key_code.unwrap(), shift_pressed);
+                                }
+                            },
                             FocusTarget::ScrollBlock => {},
+                            FocusTarget::Link(link) => {
+                                if keycode.unwrap().name() == "Return" {
+                                    let navigation_action = page.activate(link, &platform);
+                                    if navigation_action != NavigationAction::None {
+                                        page.navigate(navigation_action, &mut platform, &mut ui_state, &mut resource_thread_pool);
+                                    }
+                                } else {
+                                    //the page can still be scrolled with the keyboard while a link has focus
+                                    let key_code = platform.convert_key_code(&keycode.unwrap());
+                                    if key_code.is_some() {
+                                        let shift_pressed = keymod.intersects(SdlKeyMod::LSHIFTMOD | SdlKeyMod::RSHIFTMOD);
+                                        page.scroll_for_key(&ui_state.main_scrollbar, key_code.unwrap(), shift_pressed);
+                                    }
+                                }
+                            },
                             FocusTarget::AddressBar => {
                                 //TODO: I still don't understand how this interacts with TextInput below. Why only handle enter here?s
                                 if keycode.unwrap().name() == "Return" {
-                                    let navigation_action = NavigationAction::Get(Url::from(&ui_state.addressbar.text));
-                                    main_page_job_tracker = start_navigate(&navigation_action, &platform, &mut ui_state, &mut resource_thread_pool);
-                                    ongoing_navigation = Some(navigation_action);
+                                    let selected_suggestion = ui_state.address_bar_suggestions.selected().map(|suggestion| suggestion.url.clone());
+                                    let url = if selected_suggestion.is_some() {
+                                        selected_suggestion.unwrap()
+                                    } else {
+                                        address_bar::url_for_typed_text(&ui_state.addressbar.text, &SETTINGS.lock().unwrap().search_url)
+                                    };
+                                    ui_state.address_bar_suggestions.clear();
+                                    page.navigate(NavigationAction::Get(url), &mut platform, &mut ui_state, &mut resource_thread_pool);
                                 }
                             },
 
                             FocusTarget::Component(ref component) => {
-                                if keycode.unwrap().name() == "Return" {
-                                    let dom_node = dom::find_dom_node_for_component(&component.borrow(), &document.borrow());
-                                    let navigation_action = dom_node.borrow().submit_form(&document.borrow());
-                                    main_page_job_tracker = start_navigate(&navigation_action, &platform, &mut ui_state, &mut resource_thread_pool);
-                                    ongoing_navigation = Some(navigation_action);
+                                //in a text area, return inserts a newline instead of submitting the form, and buttons do what they do when clicked
+                                let (is_text_area, is_activatable) = match component.borrow().deref() {
+                                    ui_components::PageComponent::TextArea(_) => (true, false),
+                                    ui_components::PageComponent::Button(_) | ui_components::PageComponent::FileInput(_) => (false, true),
+                                    ui_components::PageComponent::TextField(_) => (false, false),
+                                };
+
+                                if keycode.unwrap().name() == "Return" && !is_text_area {
+                                    let dom_node_id = page.document.borrow().find_node_for_component(&component.borrow());
+                                    let navigation_action = if is_activatable {
+                                        page.activate(dom_node_id, &platform)
+                                    } else {
+                                        let navigation_action = page.document.borrow().submit_form(dom_node_id);
+                                        page.resolve_form_submission(navigation_action)
+                                    };
+                                    if navigation_action != NavigationAction::None {
+                                        page.start_loading(navigation_action, &platform, &mut ui_state, &mut resource_thread_pool);
+                                    }
                                 }
                             },
                         }
 
                     }
                 },
+                SdlEvent::DropFile { filename, .. } => {
+                    //files are opened like a path given on the command line, so the loader decides how to show them
+                    page.navigate(NavigationAction::Get(Url::from(&filename)), &mut platform, &mut ui_state, &mut resource_thread_pool);
+                },
+                SdlEvent::DropText { filename: dropped_text, .. } => {
+                    let possible_url = address_bar::url_for_dropped_text(&dropped_text);
+                    if possible_url.is_some() {
+                        page.navigate(NavigationAction::Get(possible_url.unwrap()), &mut platform, &mut ui_state, &mut resource_thread_pool);
+                    }
+                },
+                SdlEvent::KeyUp { keycode: Some(keycode), .. } => {
+                    page.dispatch_keyboard_event("keyup", platform.convert_key_to_dom_key(&keycode), &ui_state);
+                },
+                SdlEvent::Window { win_event: WindowEvent::Resized(new_width, new_height), .. } => {
+                    //media queries, innerWidth and innerHeight follow the size of the page, so the page is laid out again before it gets the event
+                    ui::handle_window_resize(&mut ui_state, new_width as f32, new_height as f32);
+                    page.relayout(&ui_state, &platform);
+                    page.interpreter.dispatch_window_event(DomEvent::new("resize", false, false));
+                },
                 SdlEvent::TextInput { text, .. } => {
                     ui::handle_keyboard_input(&mut platform, Some(&text), None, &mut ui_state);
                 },
                 _ => {},
             }
         }
-        #[cfg(feature="timings")] println!("event pump elapsed millis: {}", start_event_pump_instant.elapsed().as_millis());
-
-        let document_has_dirty_nodes = document.borrow_mut().update_all_dom_nodes(&mut resource_thread_pool);
-
-        if document_has_dirty_nodes {
-            rebuild_dirty_layout_childs(&full_layout_tree.borrow().root_node, &document.borrow(), &platform.font_context);
-
-            let mut nodes_in_selection_order = Vec::new();
-            collect_content_nodes_in_walk_order(&full_layout_tree.borrow().root_node, &mut nodes_in_selection_order);
-            full_layout_tree.borrow_mut().nodes_in_selection_order = nodes_in_selection_order;
-
-            compute_layout(&full_layout_tree.borrow().root_node, &document.borrow().style_context, CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y,
-                           &platform.font_context, ui_state.current_scroll_y, false, false);
+        events_measurement.finish(FramePhase::Events);
+
+        page.update(&mut platform, &mut resource_thread_pool);
+        ui::update_loading_progress(&mut ui_state, &resource_thread_pool.queue_stats());
+        ui_state.nr_of_script_errors = page.interpreter.nr_of_errors;
+
+        let render_measurement = Measurement::start();
+        //TODO: the display list only needs to be built again when the layout, the animations or a focused component changed, not every frame
+        let focused_dom_node = if ui_state.focus_visible { ui::focused_dom_node(&ui_state, &page.document.borrow()) } else { None };
+        let page_display_list = page.build_display_list(&platform, ui_state.animation_tick, focused_dom_node, ui_state.layout_overlay);
+        render(&mut platform, &page_display_list, page.scroll_y, &mut ui_state);
+        render_measurement.finish(FramePhase::Render);
+
+        if headless_output.is_some() && page.ongoing_navigation.is_none() {
+            //we wait for the resources of the page, but not forever, since some might never finish loading
+            if page.document.borrow().ready_state == DocumentReadyState::Complete || start_instant.elapsed() > HEADLESS_LOAD_TIMEOUT {
+                return match headless_output.as_ref().unwrap() {
+                    HeadlessOutput::Screenshot(path) => save_screenshot(&mut platform, &page_display_list, page.scroll_y, ui_state.window_dimensions, path),
+                    HeadlessOutput::Json(path) => fs::write(path, page.to_json()).map_err(|error| format!("could not save the json to {:?}: {}", path, error)),
+                };
+            }
         }
 
-        #[cfg(feature="timings")] let start_render_instant = Instant::now();
-        render(&mut platform, &full_layout_tree.borrow(), &mut ui_state);
-        #[cfg(feature="timings")] println!("render elapsed millis: {}", start_render_instant.elapsed().as_millis());
+        //scripts of the page run after it is rendered, so it is shown before they are done, their changes show up in the next frame
+        //(while we navigate away they don't run anymore, they are dropped with the page once the new one is loaded)
+        page.run_scripts(&platform.font_context);
 
+        PROFILER.lock().unwrap().finish_frame(start_loop_instant.elapsed());
         frame_time_check(&start_loop_instant);
     }
 
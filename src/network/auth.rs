@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use crate::network::url::Url;
+
+
+//credentials the user entered are kept (per origin) until the browser is closed, so we don't need to ask again for every request
+static CREDENTIALS: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| {
+    return Mutex::new(HashMap::new());
+});
+
+
+pub fn store_credentials(url: &Url, username: &String, password: &String) {
+    let header_value = basic_authorization_header(username, password);
+    CREDENTIALS.lock().unwrap().insert(origin_key(url), header_value);
+}
+
+
+pub fn authorization_header_for(url: &Url) -> Option<String> {
+    return CREDENTIALS.lock().unwrap().get(&origin_key(url)).cloned();
+}
+
+
+pub fn basic_authorization_header(username: &String, password: &String) -> String {
+    return format!("Basic {}", encode_base64(format!("{}:{}", username, password).as_bytes()));
+}
+
+
+pub fn basic_auth_realm(www_authenticate: &str) -> Option<String> {
+    //returns the realm when the server asks for basic authentication, which is a header like: Basic realm="Our site", charset="UTF-8"
+    //TODO: a server can offer multiple schemes in one header, we only look at the first one
+    let www_authenticate = www_authenticate.trim();
+    if www_authenticate.len() < 5 || !www_authenticate[..5].eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    for parameter in www_authenticate[5..].split(',') {
+        let parameter = parameter.trim();
+        if parameter.len() > 6 && parameter[..6].eq_ignore_ascii_case("realm=") {
+            return Some(parameter[6..].trim_matches('"').to_owned());
+        }
+    }
+    return Some(String::new());
+}
+
+
+fn origin_key(url: &Url) -> String {
+    return url.origin().serialize();
+}
+
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(ALPHABET[(value >> (18 - idx * 6)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    return encoded;
+}
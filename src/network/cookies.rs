@@ -0,0 +1,337 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::date::days_from_civil;
+use crate::debug::debug_log_warn;
+use crate::network::http_cache::{create_private_directory, user_cache_directory};
+use crate::network::url::Url;
+
+
+//this is shared by all threads loading resources, and is saved to disk every time a cookie that outlives the session changes
+pub static COOKIE_STORE: LazyLock<Mutex<CookieStore>> = LazyLock::new(|| {
+    return Mutex::new(CookieStore::new(user_cache_directory().map(|directory| directory.join("cookies"))));
+});
+
+
+//we don't keep cookies longer than this, like https://httpwg.org/http-extensions/draft-ietf-httpbis-rfc6265bis.html#section-5.6.1 allows
+const MAX_COOKIE_LIFETIME: Duration = Duration::from_secs(400 * 24 * 60 * 60);
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub host_only: bool, //when there was no Domain attribute, the cookie is only sent to exactly the host that set it (not to subdomains)
+    pub path: String,
+    pub expires: Option<SystemTime>, //None means this is a session cookie, which is not saved to disk
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: SameSite,
+}
+impl Cookie {
+    pub fn parse(set_cookie: &str, url: &Url, now: SystemTime) -> Option<Cookie> {
+        //this follows https://httpwg.org/specs/rfc6265.html#storage-model, returning None for cookies we should ignore
+        let mut parts = set_cookie.split(';');
+        let name_value = parts.next().unwrap();
+        let equals_idx = name_value.find('=')?;
+        let name = name_value[..equals_idx].trim().to_owned();
+        let value = name_value[equals_idx + 1..].trim().to_owned();
+        if name.is_empty() {
+            return None;
+        }
+
+        let host = url.host.to_lowercase();
+        let mut cookie = Cookie { name, value, domain: host.clone(), host_only: true, path: default_path(url), expires: None,
+                                  secure: false, http_only: false, same_site: SameSite::Lax };
+        let mut max_age: Option<i64> = None;
+        let mut expires: Option<SystemTime> = None;
+
+        for attribute in parts {
+            let (attribute_name, attribute_value) = match attribute.find('=') {
+                Some(idx) => (attribute[..idx].trim().to_lowercase(), attribute[idx + 1..].trim()),
+                None => (attribute.trim().to_lowercase(), ""),
+            };
+
+            match attribute_name.as_str() {
+                "expires" => {
+                    let parsed = parse_cookie_date(attribute_value);
+                    if parsed.is_some() {
+                        expires = parsed;
+                    }
+                },
+                "max-age" => {
+                    let parsed = attribute_value.parse::<i64>();
+                    if parsed.is_ok() {
+                        max_age = Some(parsed.unwrap());
+                    }
+                },
+                "domain" => {
+                    let domain = attribute_value.trim_start_matches('.').to_lowercase();
+                    if !domain.is_empty() {
+                        //TODO: we should also reject domains that are public suffixes (like "com" or "co.uk")
+                        if !domain_matches(&host, &domain) {
+                            return None;
+                        }
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                },
+                "path" => {
+                    if attribute_value.starts_with('/') {
+                        cookie.path = attribute_value.to_owned();
+                    }
+                },
+                "secure" => { cookie.secure = true; },
+                "httponly" => { cookie.http_only = true; },
+                "samesite" => {
+                    cookie.same_site = match attribute_value.to_lowercase().as_str() {
+                        "strict" => SameSite::Strict,
+                        "none" => SameSite::None,
+                        _ => SameSite::Lax,
+                    };
+                },
+                _ => {},
+            }
+        }
+
+        //Max-Age wins over Expires when both are there, and a Max-Age of 0 or less means the cookie should be removed right away
+        let latest_expiry = now.checked_add(MAX_COOKIE_LIFETIME).unwrap_or(now);
+        if max_age.is_some() {
+            let max_age = max_age.unwrap();
+            let lifetime = Duration::from_secs(max_age as u64).min(MAX_COOKIE_LIFETIME);
+            cookie.expires = if max_age <= 0 { Some(UNIX_EPOCH) } else { Some(now.checked_add(lifetime).unwrap_or(latest_expiry)) };
+        } else {
+            cookie.expires = expires.map(|expires| expires.min(latest_expiry));
+        }
+
+        if cookie.secure && url.scheme != "https" {
+            return None; //insecure sites can't set secure cookies
+        }
+        if cookie.same_site == SameSite::None && !cookie.secure {
+            return None; //other browsers also require SameSite=None cookies to be secure
+        }
+
+        return Some(cookie);
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        return self.expires.is_some() && self.expires.unwrap() <= now;
+    }
+
+    pub fn should_send_to(&self, url: &Url) -> bool {
+        //TODO: we don't know yet which site started the request, so SameSite is not checked, it needs to be once we do
+        let host = url.host.to_lowercase();
+        let host_matches = if self.host_only { host == self.domain } else { domain_matches(&host, &self.domain) };
+        if !host_matches || !path_matches(&request_path(url), &self.path) {
+            return false;
+        }
+        return !self.secure || url.scheme == "https";
+    }
+
+    fn serialize(&self) -> String {
+        let expires_secs = self.expires.unwrap().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        let same_site = match self.same_site { SameSite::Strict => "strict", SameSite::Lax => "lax", SameSite::None => "none" };
+        let flag = |value: bool| if value { "1" } else { "0" };
+        return [self.name.as_str(), self.value.as_str(), self.domain.as_str(), flag(self.host_only), self.path.as_str(),
+                expires_secs.to_string().as_str(), flag(self.secure), flag(self.http_only), same_site].join("\t");
+    }
+
+    fn deserialize(line: &str) -> Option<Cookie> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != NR_OF_SERIALIZED_FIELDS {
+            return None;
+        }
+
+        let same_site = match fields[8] {
+            "strict" => SameSite::Strict,
+            "none" => SameSite::None,
+            _ => SameSite::Lax,
+        };
+        return Some(Cookie {
+            name: fields[0].to_owned(),
+            value: fields[1].to_owned(),
+            domain: fields[2].to_owned(),
+            host_only: fields[3] == "1",
+            path: fields[4].to_owned(),
+            expires: Some(UNIX_EPOCH.checked_add(Duration::from_secs(fields[5].parse::<u64>().ok()?))?),
+            secure: fields[6] == "1",
+            http_only: fields[7] == "1",
+            same_site,
+        });
+    }
+}
+const NR_OF_SERIALIZED_FIELDS: usize = 9;
+
+
+pub struct CookieStore {
+    cookies: Vec<Cookie>,
+    file: Option<PathBuf>, //when this is None, cookies are only kept in memory
+}
+impl CookieStore {
+    pub fn new(file: Option<PathBuf>) -> CookieStore {
+        let mut cookies = Vec::new();
+        if file.is_some() {
+            let read_result = fs::read_to_string(file.as_ref().unwrap());
+            if read_result.is_ok() {
+                let now = SystemTime::now();
+                cookies = read_result.unwrap().lines().filter_map(Cookie::deserialize).filter(|cookie| !cookie.is_expired(now)).collect();
+            }
+        }
+        return CookieStore { cookies, file };
+    }
+
+    pub fn store_from_response(&mut self, url: &Url, set_cookie_headers: &Vec<String>, now: SystemTime) {
+        let mut needs_save = false;
+
+        for set_cookie in set_cookie_headers {
+            let cookie = Cookie::parse(set_cookie, url, now);
+            if cookie.is_none() {
+                continue;
+            }
+            let cookie = cookie.unwrap();
+
+            //a new cookie replaces the one with the same name, domain and path, and an expired one just removes it
+            let existing_idx = self.cookies.iter().position(|existing| existing.name == cookie.name && existing.domain == cookie.domain && existing.path == cookie.path);
+            if existing_idx.is_some() {
+                let removed = self.cookies.remove(existing_idx.unwrap());
+                needs_save = needs_save || removed.expires.is_some();
+            }
+
+            if !cookie.is_expired(now) {
+                needs_save = needs_save || cookie.expires.is_some();
+                self.cookies.push(cookie);
+            }
+        }
+
+        if needs_save {
+            self.save(now);
+        }
+    }
+
+    pub fn cookie_header_for(&mut self, url: &Url, now: SystemTime) -> Option<String> {
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: Vec<&Cookie> = self.cookies.iter().filter(|cookie| cookie.should_send_to(url)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+
+        //cookies with longer paths are sent first, otherwise they are in the order they were set in
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        return Some(matching.iter().map(|cookie| format!("{}={}", cookie.name, cookie.value)).collect::<Vec<_>>().join("; "));
+    }
+
+    fn save(&self, now: SystemTime) {
+        if self.file.is_none() {
+            return;
+        }
+
+        let lines: Vec<String> = self.cookies.iter().filter(|cookie| cookie.expires.is_some() && !cookie.is_expired(now)).map(|cookie| cookie.serialize()).collect();
+        let path = self.file.as_ref().unwrap();
+        let write_result = create_private_directory(path.parent().unwrap()).and_then(|_| fs::write(path, lines.join("\n")));
+        if write_result.is_err() {
+            debug_log_warn(format!("Could not write the cookies to: {:?}", path));
+        }
+    }
+}
+
+
+fn request_path(url: &Url) -> String {
+    return format!("/{}", url.path.join("/"));
+}
+
+
+fn default_path(url: &Url) -> String {
+    //this is the "directory" of the url, so a cookie set on /a/b is sent to everything in /a
+    let path = request_path(url);
+    let last_slash_idx = path.rfind('/').unwrap();
+    if last_slash_idx == 0 {
+        return String::from("/");
+    }
+    return path[..last_slash_idx].to_owned();
+}
+
+
+pub fn domain_matches(host: &String, domain: &String) -> bool {
+    if host == domain {
+        return true;
+    }
+    //TODO: when the host is an ip address, only the exact match above should be allowed
+    return host.ends_with(domain.as_str()) && host[..host.len() - domain.len()].ends_with('.');
+}
+
+
+pub fn path_matches(request_path: &String, cookie_path: &String) -> bool {
+    if request_path == cookie_path {
+        return true;
+    }
+    if !request_path.starts_with(cookie_path.as_str()) {
+        return false;
+    }
+    return cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/');
+}
+
+
+pub fn parse_cookie_date(text: &str) -> Option<SystemTime> {
+    //this is a forgiving parser like https://httpwg.org/specs/rfc6265.html#cookie-date, it finds the time, day, month and year in any order
+    let mut time: Option<(u64, u64, u64)> = None;
+    let mut day: Option<u64> = None;
+    let mut month: Option<u64> = None;
+    let mut year: Option<u64> = None;
+
+    for token in text.split(|c: char| c == ' ' || c == ',' || c == '-' || c == '\t').filter(|token| !token.is_empty()) {
+        if time.is_none() && token.contains(':') {
+            let numbers: Vec<Option<u64>> = token.split(':').map(|number| number.parse::<u64>().ok()).collect();
+            if numbers.len() == 3 && numbers.iter().all(|number| number.is_some()) {
+                time = Some((numbers[0].unwrap(), numbers[1].unwrap(), numbers[2].unwrap()));
+                continue;
+            }
+        }
+
+        let number = token.parse::<u64>();
+        if number.is_ok() {
+            if day.is_none() && token.len() <= 2 {
+                day = Some(number.unwrap());
+            } else if year.is_none() && (token.len() == 2 || token.len() == 4) {
+                year = Some(number.unwrap());
+            }
+            continue;
+        }
+
+        if month.is_none() && token.len() >= 3 {
+            let month_names = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+            let month_idx = month_names.iter().position(|name| token[..3].eq_ignore_ascii_case(name));
+            if month_idx.is_some() {
+                month = Some(month_idx.unwrap() as u64 + 1);
+            }
+        }
+    }
+
+    let (hours, minutes, seconds) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+    if year < 70 {
+        year += 2000;
+    } else if year < 100 {
+        year += 1900;
+    }
+    if day < 1 || day > 31 || year < 1970 || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+
+    let days_since_epoch = days_from_civil(year as i64, month as i64, day as i64) as u64;
+    let seconds_since_epoch = days_since_epoch * 24 * 60 * 60 + hours * 60 * 60 + minutes * 60 + seconds;
+    return UNIX_EPOCH.checked_add(Duration::from_secs(seconds_since_epoch));
+}
@@ -0,0 +1,253 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::debug::debug_log_warn;
+use crate::network::url::Url;
+
+
+const MEMORY_BUDGET: usize = 64 * 1024 * 1024; //the size of the bodies we keep in memory, the least recently used ones are only kept on disk
+
+
+//this is shared by all threads loading resources
+pub static HTTP_CACHE: LazyLock<HttpCache> = LazyLock::new(|| {
+    return HttpCache::new(user_cache_directory(), MEMORY_BUDGET);
+});
+
+
+pub fn user_cache_directory() -> Option<PathBuf> {
+    //the cache is in a directory of the user, so other users on the machine can't read what was loaded, or put their own entries there
+    let base_directory = if cfg!(windows) {
+        env::var("LOCALAPPDATA").ok().map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var("HOME").ok().map(|home_directory| PathBuf::from(home_directory).join("Library").join("Caches"))
+    } else {
+        let xdg_cache_home = env::var("XDG_CACHE_HOME").ok().filter(|directory| !directory.is_empty());
+        xdg_cache_home.map(PathBuf::from).or(env::var("HOME").ok().map(|home_directory| PathBuf::from(home_directory).join(".cache")))
+    };
+    return base_directory.map(|base_directory| base_directory.join("webcrustacean"));
+}
+
+
+pub fn create_private_directory(directory: &Path) -> std::io::Result<()> {
+    //on windows, the directories in LOCALAPPDATA are already only accessible by the user
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)] {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    return builder.create(directory);
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct CacheControl {
+    pub no_store: bool, //the response should not be stored at all
+    pub no_cache: bool, //the response can be stored, but should be revalidated every time before we use it
+    pub max_age: Option<Duration>,
+}
+impl CacheControl {
+    pub fn parse(header_value: Option<&str>) -> CacheControl {
+        let mut cache_control = CacheControl { no_store: false, no_cache: false, max_age: None };
+        if header_value.is_none() {
+            return cache_control;
+        }
+
+        for directive in header_value.unwrap().split(',') {
+            let directive = directive.trim().to_lowercase();
+            if directive == "no-store" {
+                cache_control.no_store = true;
+            } else if directive == "no-cache" {
+                cache_control.no_cache = true;
+            } else if directive.starts_with("max-age=") {
+                let seconds = directive["max-age=".len()..].trim_matches('"').parse::<u64>();
+                if seconds.is_ok() {
+                    cache_control.max_age = Some(Duration::from_secs(seconds.unwrap()));
+                }
+            }
+        }
+        return cache_control;
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct CacheEntry {
+    pub url: Url, //where the response came from in the end, which is different from the url we store it for when there were redirects
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+    pub stored_at: SystemTime, //when we last got the response, or confirmed it was not modified
+    pub max_age: Option<Duration>,
+    pub no_cache: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+impl CacheEntry {
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        //TODO: without a max-age, other browsers guess how long the entry stays fresh (from Last-Modified or Expires), we always revalidate
+        if self.no_cache || self.max_age.is_none() {
+            return false;
+        }
+        let age = now.duration_since(self.stored_at).unwrap_or(Duration::ZERO);
+        return age < self.max_age.unwrap();
+    }
+
+    pub fn conditional_request_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if self.etag.is_some() {
+            headers.push(("If-None-Match", self.etag.clone().unwrap()));
+        }
+        if self.last_modified.is_some() {
+            headers.push(("If-Modified-Since", self.last_modified.clone().unwrap()));
+        }
+        return headers;
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        //the metadata is on the first lines (header values can't contain newlines), and the body is everything after them
+        let stored_at_secs = self.stored_at.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        let max_age = self.max_age.map(|max_age| max_age.as_secs().to_string());
+
+        let mut data = Vec::new();
+        for line in [Some(self.url.to_string()), self.content_type.clone(), Some(stored_at_secs.to_string()), max_age,
+                     Some(String::from(if self.no_cache { "1" } else { "0" })), self.etag.clone(), self.last_modified.clone()] {
+            //empty lines mean the value is missing
+            data.extend_from_slice(line.unwrap_or(String::new()).as_bytes());
+            data.push(b'\n');
+        }
+        data.extend_from_slice(&self.body);
+        return data;
+    }
+
+    pub fn deserialize(data: &[u8]) -> Option<CacheEntry> {
+        let mut lines = Vec::new();
+        let mut rest = data;
+        for _ in 0..NR_OF_SERIALIZED_METADATA_LINES {
+            let newline_idx = rest.iter().position(|byte| *byte == b'\n')?;
+            let line = String::from_utf8(rest[..newline_idx].to_vec()).ok()?;
+            lines.push(if line.is_empty() { None } else { Some(line) });
+            rest = &rest[newline_idx + 1..];
+        }
+
+        let stored_at_secs = lines[2].as_ref()?.parse::<u64>().ok()?;
+        let max_age = match &lines[3] {
+            Some(max_age) => Some(Duration::from_secs(max_age.parse::<u64>().ok()?)),
+            None => None,
+        };
+
+        return Some(CacheEntry {
+            url: Url::from(lines[0].as_ref()?),
+            content_type: lines[1].clone(),
+            body: rest.to_vec(),
+            stored_at: UNIX_EPOCH + Duration::from_secs(stored_at_secs),
+            max_age,
+            no_cache: lines[4].as_deref() == Some("1"),
+            etag: lines[5].clone(),
+            last_modified: lines[6].clone(),
+        });
+    }
+}
+const NR_OF_SERIALIZED_METADATA_LINES: usize = 7;
+
+
+struct MemoryEntries {
+    entries: HashMap<String, (CacheEntry, u64)>, //the entries by their key, with when they were last used
+    total_size: usize,
+    last_use: u64, //this counts up on every use, to know which entries were used least recently
+}
+impl MemoryEntries {
+    fn get(&mut self, key: &String) -> Option<CacheEntry> {
+        self.last_use += 1;
+        let (entry, last_used) = self.entries.get_mut(key)?;
+        *last_used = self.last_use;
+        return Some(entry.clone());
+    }
+
+    fn insert(&mut self, key: String, entry: CacheEntry, budget: usize) {
+        self.last_use += 1;
+        self.total_size += entry.body.len();
+        let replaced_entry = self.entries.insert(key, (entry, self.last_use));
+        if replaced_entry.is_some() {
+            self.total_size -= replaced_entry.unwrap().0.body.len();
+        }
+
+        while self.total_size > budget && !self.entries.is_empty() {
+            let least_recently_used_key = self.entries.iter().min_by_key(|(_, (_, last_used))| *last_used).map(|(key, _)| key.clone()).unwrap();
+            let (evicted_entry, _) = self.entries.remove(&least_recently_used_key).unwrap();
+            self.total_size -= evicted_entry.body.len();
+        }
+    }
+}
+
+
+pub struct HttpCache {
+    //the lock is only held while looking up or storing entries in memory, not while reading or writing files, or loading
+    memory_entries: Mutex<MemoryEntries>,
+    memory_budget: usize,
+    directory: Option<PathBuf>, //when this is None, we only keep entries in memory
+    //TODO: we never remove entries from disk, we need a size limit for that as well
+}
+impl HttpCache {
+    pub fn new(directory: Option<PathBuf>, memory_budget: usize) -> HttpCache {
+        let memory_entries = MemoryEntries { entries: HashMap::new(), total_size: 0, last_use: 0 };
+        return HttpCache { memory_entries: Mutex::new(memory_entries), memory_budget, directory };
+    }
+
+    pub fn lookup(&self, url: &Url) -> Option<CacheEntry> {
+        let key = url.without_fragment().to_string();
+        let memory_entry = self.memory_entries.lock().unwrap().get(&key);
+        if memory_entry.is_some() {
+            return memory_entry;
+        }
+
+        let path = self.path_for_key(&key)?;
+        let data = fs::read(path).ok()?;
+        let entry = CacheEntry::deserialize(&data);
+        if entry.is_some() {
+            self.memory_entries.lock().unwrap().insert(key, entry.clone().unwrap(), self.memory_budget);
+        }
+        return entry;
+    }
+
+    pub fn store(&self, url: &Url, entry: CacheEntry) {
+        let key = url.without_fragment().to_string();
+        let path = self.path_for_key(&key);
+        let data = if path.is_some() { Some(entry.serialize()) } else { None };
+
+        self.memory_entries.lock().unwrap().insert(key, entry, self.memory_budget);
+
+        if path.is_some() {
+            let path = path.unwrap();
+            let write_result = create_private_directory(path.parent().unwrap()).and_then(|_| fs::write(&path, data.unwrap()));
+            if write_result.is_err() {
+                debug_log_warn(format!("Could not write to the http cache: {:?}", path));
+            }
+        }
+    }
+
+    pub fn mark_revalidated(&self, url: &Url, cache_control: &CacheControl, now: SystemTime) -> Option<CacheEntry> {
+        //the server told us (with a 304) that our entry is still good, so it is fresh again from now on
+        let mut entry = self.lookup(url)?;
+        entry.stored_at = now;
+        if cache_control.max_age.is_some() {
+            entry.max_age = cache_control.max_age;
+        }
+        self.store(url, entry.clone());
+        return Some(entry);
+    }
+
+    fn path_for_key(&self, key: &String) -> Option<PathBuf> {
+        //the file name is a 64 bit hash of the url, different urls getting the same one is unlikely enough to not worry about
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        return self.directory.as_ref().map(|directory| directory.join(format!("{:016x}", hasher.finish())));
+    }
+}
@@ -1,111 +1,640 @@
+use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(feature="timings")] use std::time::Instant;
 
-use image::DynamicImage;
+use reqwest::{Client, NoProxy, Proxy, RequestBuilder, Response};
+use reqwest::redirect::Policy;
 
+use crate::config::Config;
 use crate::debug::debug_log_warn;
+use crate::network::auth::{authorization_header_for, basic_auth_realm};
+use crate::network::cookies::COOKIE_STORE;
+use crate::network::http_cache::{CacheControl, CacheEntry, HTTP_CACHE};
 use crate::network::url::Url;
 
+pub mod auth;
+pub mod cookies;
+pub mod http_cache;
+pub mod request_log;
 pub mod url;
+pub mod websocket;
 #[cfg(test)] mod tests;
 
 
-const UA_FIREFOX_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/118.0";
+pub const UA_FIREFOX_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/118.0";
+const MAX_REDIRECTS: usize = 20; //this is the same limit as the fetch spec has
 
 
 #[derive(Clone, Debug)] //note: debug here is not conditional on the debug build attribute, because we also need to print errors in release mode
-pub struct ResourceNotLoadedError(String); //TODO: eventually we should be more specific, i.e. NetworkError, DecodingError etc.
+pub struct ResourceNotLoadedError {
+    pub url: String,
+    pub kind: LoadErrorKind,
+    pub authentication_realm: Option<String>, //this is set when the kind is AuthenticationRequired, and can be shown to the user
+    pub panic_message: Option<String>, //this is set when the kind is Crashed
+}
+impl ResourceNotLoadedError {
+    pub fn new(url: &Url, kind: LoadErrorKind) -> ResourceNotLoadedError {
+        return ResourceNotLoadedError { url: url.to_string(), kind, authentication_realm: None, panic_message: None };
+    }
+}
 impl fmt::Display for ResourceNotLoadedError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "ResourceNotLoadedError: could not load {}", self.0)
+        write!(f, "ResourceNotLoadedError: could not load {} ({:?})", self.url, self.kind)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadErrorKind {
+    DnsFailure,
+    ConnectionFailed,
+    TlsError,
+    Timeout,
+    HttpStatus(u16), //the server responded, but with an error status (4xx or 5xx)
+    AuthenticationRequired, //the server responded with a 401 for basic authentication, so we can retry after asking for credentials
+    TooManyRedirects,
+    FileNotFound, //for file: and about: urls
+    Other, //for example content that could not be decoded
+    Crashed, //the page was loaded, but showing it got the browser in a state it does not handle yet (this does not come from the network)
+    Blocked, //for about: pages that a page tried to load, only the user can open those
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    Default,
+    Reload, //this is for a hard reload, we don't use what is in the http cache, but the new response is still stored in it
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(PartialEq)]
+pub struct Redirect {
+    pub url: Url,
+    pub switch_to_get: bool, //when this is true, the body of a POST is not sent again, and the redirect is followed with a GET
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct HttpClientSettings {
+    pub max_idle_connections_per_host: usize,
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration, //this is for the whole request, including reading the body
+    pub user_agent: String,
+    pub proxy: ProxySettings,
+    pub host_overrides: Vec<(String, IpAddr)>, //these hosts are connected to on the given address, instead of the one from DNS
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct ProxySettings {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub all: Option<String>, //this is used for both http and https requests
+    pub no_proxy: Vec<String>, //hosts we connect to directly, a host starting with a dot (like ".example.com") also matches its subdomains
+}
+impl ProxySettings {
+    pub fn from_config(config: &Config) -> ProxySettings {
+        return ProxySettings {
+            http: config.get("proxy.http").cloned().filter(|proxy| !proxy.is_empty()),
+            https: config.get("proxy.https").cloned().filter(|proxy| !proxy.is_empty()),
+            all: config.get("proxy.all").cloned().filter(|proxy| !proxy.is_empty()),
+            no_proxy: config.get_list("proxy.no_proxy"),
+        };
+    }
+
+    pub fn is_configured(&self) -> bool {
+        return self.http.is_some() || self.https.is_some() || self.all.is_some();
+    }
+}
+
+
+pub fn host_overrides_from_config(config: &Config) -> Vec<(String, IpAddr)> {
+    //these are lines like "host_override.example.com = 127.0.0.1"
+    let mut host_overrides = Vec::new();
+    for key in config.keys_with_prefix("host_override.") {
+        let address = config.get(&key).unwrap().parse::<IpAddr>();
+        if address.is_err() {
+            debug_log_warn(format!("Ignoring host override with an invalid ip address: {}", key));
+            continue;
+        }
+        host_overrides.push((key["host_override.".len()..].to_owned(), address.unwrap()));
     }
+    return host_overrides;
 }
 
 
-pub fn http_get_text(url: &Url) -> Result<String, ResourceNotLoadedError>  {
-    //TODO: not sure if I really need a seperate one for text, should I not just never call the .text() method from reqwest,
-    //      and just decode myself based on the situation?
-    //TODO: in any case we need to de-duplicate between http_get_text() and http_get_image()
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct HttpResponse {
+    pub url: Url, //the url we ended up at after following redirects
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+    pub from_cache: bool,
+}
+impl HttpResponse {
+    fn from_cache_entry(entry: CacheEntry, status: u16) -> HttpResponse {
+        return HttpResponse { url: entry.url, status, content_type: entry.content_type, body: entry.body, from_cache: true };
+    }
+}
+
+
+pub async fn http_get(client: &Client, url: &Url, cache_mode: CacheMode) -> Result<HttpResponse, ResourceNotLoadedError> {
+    //GET requests go through the http cache, we use fresh entries without asking the server, and ask the server if stale ones are still good
+    let cached_entry = if cache_mode == CacheMode::Reload { None } else { HTTP_CACHE.lookup(url) };
+    if cached_entry.is_some() && cached_entry.as_ref().unwrap().is_fresh(SystemTime::now()) {
+        return Ok(HttpResponse::from_cache_entry(cached_entry.unwrap(), 200));
+    }
+    let conditional_headers = cached_entry.as_ref().map(|entry| entry.conditional_request_headers()).unwrap_or(Vec::new());
+
+    let response_result = send_following_redirects(client, url, None, conditional_headers).await;
+    if !response_result.is_ok() {
+        return Err(response_result.err().unwrap());
+    }
+    let (final_url, response) = response_result.unwrap();
+
+    let status = response.status().as_u16();
+    let cache_control = CacheControl::parse(get_header_value(&response, "Cache-Control").as_deref());
+
+    if status == 304 {
+        let revalidated_entry = HTTP_CACHE.mark_revalidated(url, &cache_control, SystemTime::now());
+        if revalidated_entry.is_none() {
+            //we only ask for a 304 when we have an entry, so it should not be gone, but we don't have a body to show if it is
+            return Err(ResourceNotLoadedError::new(&final_url, LoadErrorKind::Other));
+        }
+        return Ok(HttpResponse::from_cache_entry(revalidated_entry.unwrap(), 304));
+    }
+
+    let etag = get_header_value(&response, "ETag");
+    let last_modified = get_header_value(&response, "Last-Modified");
+    let response_result = read_response(final_url, response).await;
+    if !response_result.is_ok() {
+        return response_result;
+    }
+    let http_response = response_result.unwrap();
+
+    //responses we can't use without asking the server again, and can't ask about either, are not worth storing
+    let is_useful_to_store = cache_control.max_age.is_some() || etag.is_some() || last_modified.is_some();
+    if status == 200 && !cache_control.no_store && is_useful_to_store {
+        let entry = CacheEntry { url: http_response.url.clone(), content_type: http_response.content_type.clone(), body: http_response.body.clone(),
+                                 stored_at: SystemTime::now(), max_age: cache_control.max_age, no_cache: cache_control.no_cache, etag, last_modified };
+        HTTP_CACHE.store(url, entry);
+    }
 
-    //TODO: should I cache the client somewhere for performance?
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(UA_FIREFOX_WINDOWS)  //TODO: make this configurable, and use an actual webcrustacean useragent normally
-        .build().unwrap();
+    return Ok(http_response);
+}
 
-    let bytes_result = client.get(url.to_string()).send();
 
+async fn read_response(url: Url, response: Response) -> Result<HttpResponse, ResourceNotLoadedError> {
+    let status = response.status().as_u16();
+    let content_type = get_header_value(&response, "Content-Type");
+    #[cfg(feature="timings")] let start_read_instant = Instant::now();
+    let bytes_result = response.bytes().await;
+    #[cfg(feature="timings")] println!("reading body of {} elapsed millis: {}", url.to_string(), start_read_instant.elapsed().as_millis());
     if !bytes_result.is_ok() {
-        return Err(ResourceNotLoadedError(url.to_string()));
+        //the request timeout also covers reading the body, so this can be a timeout
+        return Err(ResourceNotLoadedError::new(&url, error_kind_for_request_error(&bytes_result.err().unwrap())));
     }
+    return Ok(HttpResponse { url, status, content_type, body: bytes_result.unwrap().to_vec(), from_cache: false });
+}
 
-    let text_result = bytes_result.unwrap().text();
 
-    if text_result.is_ok() {
-        return Ok(text_result.unwrap());
-    } else {
-        debug_log_warn(format!("Could not load text: {}", url.to_string()));
-        return Err(ResourceNotLoadedError(url.to_string()));
+fn get_header_value(response: &Response, name: &str) -> Option<String> {
+    return response.headers().get(name).and_then(|value| value.to_str().ok()).map(|value| value.to_owned());
+}
+
+
+pub fn decode_text(bytes: &[u8], content_type: Option<&String>) -> String {
+    //TODO: we only decode utf-8 (the default) and latin-1 for now
+    let charset = content_type.and_then(|content_type| {
+        return content_type.split(';').map(|part| part.trim().to_lowercase()).find(|part| part.starts_with("charset="));
+    }).map(|charset| charset["charset=".len()..].trim_matches('"').to_owned());
+
+    if charset.is_some() && matches!(charset.as_ref().unwrap().as_str(), "iso-8859-1" | "latin1" | "windows-1252") {
+        //latin-1 bytes are the same as the first 256 unicode code points
+        //TODO: windows-1252 (which browsers use for all of these) has different characters in the range 0x80 to 0x9F
+        return bytes.iter().map(|byte| *byte as char).collect();
+    }
+    return String::from_utf8_lossy(bytes).into_owned();
+}
+
+
+pub fn build_http_client(settings: &HttpClientSettings) -> Client {
+    //the client keeps a pool of open connections, so it should be shared between requests (it can be cloned cheaply to do that)
+    let mut builder = Client::builder()
+        .user_agent(&settings.user_agent)  //TODO: use an actual webcrustacean useragent by default
+        .redirect(Policy::none()) //we follow redirects ourselves, so we know at which url we end up
+        .pool_max_idle_per_host(settings.max_idle_connections_per_host)
+        .connect_timeout(settings.connect_timeout)
+        .timeout(settings.request_timeout);
+
+    //when we don't configure proxies ourselves, the ones in the environment variables (like HTTPS_PROXY) are used
+    if settings.proxy.is_configured() {
+        let no_proxy = NoProxy::from_string(&settings.proxy.no_proxy.join(","));
+        for (proxy_url, scheme) in [(&settings.proxy.http, "http"), (&settings.proxy.https, "https"), (&settings.proxy.all, "all")] {
+            if proxy_url.is_some() {
+                let proxy = build_proxy(proxy_url.as_ref().unwrap(), scheme, &no_proxy);
+                if proxy.is_some() {
+                    builder = builder.proxy(proxy.unwrap());
+                }
+            }
+        }
+    }
+
+    for (host, address) in &settings.host_overrides {
+        //the port is ignored here, the one from the url is always used
+        builder = builder.resolve(host, SocketAddr::new(*address, 0));
     }
 
+    return builder.build().unwrap();
 }
 
 
-//TODO: there is too much duplication here with the get case...
-pub fn http_post(url: &Url, body: String) -> Result<String, ResourceNotLoadedError>  {
+fn build_proxy(proxy_url: &String, scheme: &str, no_proxy: &Option<NoProxy>) -> Option<Proxy> {
+    let proxy_result = match scheme {
+        "http" => Proxy::http(proxy_url),
+        "https" => Proxy::https(proxy_url),
+        _ => Proxy::all(proxy_url),
+    };
 
-    //TODO: should I cache the client somewhere for performance?
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(UA_FIREFOX_WINDOWS)  //TODO: make this configurable, and use an actual webcrustacean useragent normally
-        .build().unwrap();
+    if proxy_result.is_err() {
+        //TODO: socks proxies end up here, because we don't build reqwest with socks support yet
+        debug_log_warn(format!("Could not use proxy {}: {}", proxy_url, proxy_result.err().unwrap()));
+        return None;
+    }
+    return Some(proxy_result.unwrap().no_proxy(no_proxy.clone()));
+}
 
-    let body_len = body.len();
 
-    let bytes_result = client.post(url.to_string()).body(body)
+async fn send_following_redirects(client: &Client, url: &Url, post_body: Option<(Vec<u8>, String)>,
+                            conditional_headers: Vec<(&'static str, String)>) -> Result<(Url, Response), ResourceNotLoadedError> {
+    //the post body is the content and its content type, when it is None we do a GET request
+    //the conditional headers (to revalidate a cache entry) are only for the url itself, so we don't send them when we are redirected
+    let mut current_url = url.clone();
+    let mut post_body = post_body;
+    let mut conditional_headers = conditional_headers;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let mut request = build_request(client, &current_url, &post_body);
+        for (name, value) in conditional_headers.drain(..) {
+            request = request.header(name, value);
+        }
+        let cookie_header = COOKIE_STORE.lock().unwrap().cookie_header_for(&current_url, SystemTime::now());
+        if cookie_header.is_some() {
+            request = request.header("Cookie", cookie_header.unwrap());
+        }
+        //credentials are only sent to the origin they were entered for, not to where that origin redirects us to
+        let authorization_header = authorization_header_for(&current_url);
+        if authorization_header.is_some() {
+            request = request.header("Authorization", authorization_header.unwrap());
+        }
+
+        #[cfg(feature="timings")] let start_request_instant = Instant::now();
+        let response_result = request.send().await;
+        #[cfg(feature="timings")] println!("request to {} elapsed millis (until the headers): {}", current_url.to_string(), start_request_instant.elapsed().as_millis());
+        if !response_result.is_ok() {
+            let error_kind = error_kind_for_request_error(&response_result.err().unwrap());
+            return Err(ResourceNotLoadedError::new(&current_url, error_kind));
+        }
+        let response = response_result.unwrap();
+
+        //redirects can also set cookies, so we store them for every response, not just the final one
+        let set_cookie_headers: Vec<String> = response.headers().get_all("Set-Cookie").iter()
+                                                      .filter_map(|value| value.to_str().ok()).map(|value| value.to_owned()).collect();
+        if !set_cookie_headers.is_empty() {
+            COOKIE_STORE.lock().unwrap().store_from_response(&current_url, &set_cookie_headers, SystemTime::now());
+        }
+
+        let location = response.headers().get("Location").and_then(|location| location.to_str().ok()).map(|location| location.to_owned());
+        let redirect = redirect_for_response(response.status().as_u16(), location.as_ref(), &current_url);
+        if redirect.is_none() {
+            //TODO: other browsers show the page the server sent with an error status, we show our own error page instead for now
+            let status = response.status().as_u16();
+            if status == 401 {
+                let realm = get_header_value(&response, "WWW-Authenticate").and_then(|header| basic_auth_realm(&header));
+                if realm.is_some() {
+                    let mut error = ResourceNotLoadedError::new(&current_url, LoadErrorKind::AuthenticationRequired);
+                    error.authentication_realm = realm;
+                    return Err(error);
+                }
+            }
+            if status >= 400 {
+                return Err(ResourceNotLoadedError::new(&current_url, LoadErrorKind::HttpStatus(status)));
+            }
+            return Ok((current_url, response));
+        }
+        let redirect = redirect.unwrap();
+
+        if redirect.switch_to_get {
+            post_body = None;
+        }
+        current_url = redirect.url;
+    }
 
-        .header("Content-Length", body_len.to_string())
-        .header("Content-Type", "application/x-www-form-urlencoded")  //TODO: not sure if this is always correct for all posts
-                                                                      //   (probably not in general, but for forms it might be)
+    debug_log_warn(format!("Too many redirects when loading: {}", url.to_string()));
+    return Err(ResourceNotLoadedError::new(url, LoadErrorKind::TooManyRedirects));
+}
 
-        .send();
 
-    if !bytes_result.is_ok() {
-        return Err(ResourceNotLoadedError(url.to_string()));
+fn error_kind_for_request_error(error: &reqwest::Error) -> LoadErrorKind {
+    if error.is_timeout() {
+        return LoadErrorKind::Timeout;
     }
 
-    //TODO: we might receive other things than text, so split this out to another method
-    let text_result = bytes_result.unwrap().text();
+    //reqwest does not tell us why connecting failed, so we look at the descriptions of the errors that caused it
+    let mut cause: Option<&dyn Error> = Some(error);
+    while cause.is_some() {
+        let description = cause.unwrap().to_string().to_lowercase();
+        if description.contains("dns error") || description.contains("failed to lookup address") {
+            return LoadErrorKind::DnsFailure;
+        }
+        if description.contains("certificate") || description.contains("tls") || description.contains("ssl") {
+            return LoadErrorKind::TlsError;
+        }
+        cause = cause.unwrap().source();
+    }
 
-    if text_result.is_ok() {
-        return Ok(text_result.unwrap());
-    } else {
-        debug_log_warn(format!("Could not load text: {}", url.to_string()));
-        return Err(ResourceNotLoadedError(url.to_string()));
+    if error.is_connect() {
+        return LoadErrorKind::ConnectionFailed;
     }
+    return LoadErrorKind::Other;
 }
 
 
-//TODO: eventually this should be a http_get_binary, and the image stuff should be seperated out, because we will load other binary resources.
-pub fn http_get_image(url: &Url) -> Result<DynamicImage, ResourceNotLoadedError> {
+fn build_request(client: &Client, url: &Url, post_body: &Option<(Vec<u8>, String)>) -> RequestBuilder {
+    //the fragment is only used by the browser itself, so it is not sent to the server
+    let request_url = url.without_fragment().to_string();
+    if post_body.is_none() {
+        return client.get(request_url);
+    }
+    let (content, content_type) = post_body.as_ref().unwrap();
 
-    //TODO: should I cache the client somewhere for performance?
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(UA_FIREFOX_WINDOWS)  //TODO: make this configurable, and use an actual webcrustacean useragent normally
-        .build().unwrap();
+    return client.post(request_url).body(content.clone())
+        .header("Content-Length", content.len().to_string())
+        .header("Content-Type", content_type);
+}
 
-    let response = client.get(url.to_string()).send().unwrap();
 
-    let bytes_result = response.bytes();
+pub fn redirect_for_response(status: u16, location: Option<&String>, current_url: &Url) -> Option<Redirect> {
+    //returns where to go next when the response is a redirect we should follow
+    if !matches!(status, 301 | 302 | 303 | 307 | 308) || location.is_none() {
+        return None; //a redirect without a location is shown as it is, like other browsers do
+    }
 
-    if !bytes_result.is_ok() {
-        return Err(ResourceNotLoadedError(url.to_string()));
+    let url = Url::from_base_url(location.unwrap(), Some(current_url));
+    if url.scheme != "http" && url.scheme != "https" {
+        debug_log_warn(format!("Not following a redirect to a different scheme: {}", url.to_string()));
+        return None;
+    }
+
+    //a 303 should always be followed with a GET, for 301 and 302 all browsers do the same for a POST (the spec allows this for historical reasons)
+    let switch_to_get = matches!(status, 301 | 302 | 303);
+    return Some(Redirect { url, switch_to_get });
+}
+
+
+pub fn is_mixed_content(page_url: &Url, resource_url: &Url) -> bool {
+    //resources for a page loaded over https should not be loaded over plain http, since anyone on the network could read or change them
+    return page_url.scheme == "https" && !resource_url.is_potentially_trustworthy();
+}
+
+
+pub fn is_cross_scheme_file_access(page_url: &Url, resource_url: &Url) -> bool {
+    //only local pages can use local files, otherwise any website could show the files (and folder listings) of the user in a frame
+    return resource_url.scheme == "file" && page_url.scheme != "file";
+}
+
+
+pub fn decode_data_url(url: &Url) -> Option<(String, Vec<u8>)> {
+    //returns the content type and the content of a data: url, following https://fetch.spec.whatwg.org/#data-url-processor
+    let mut data = url.path.join("/");
+    if !url.query.is_empty() {
+        data.push('?');
+        data.push_str(&url.query);
+    }
+
+    let comma_idx = data.find(',');
+    if comma_idx.is_none() {
+        return None;
     }
+    let comma_idx = comma_idx.unwrap();
 
-    //TODO: we would like to return the bytes, for now making an image though, eventually this should be somewhere else (in the resource loader maybe?)
-    let image_result = image::load_from_memory(&bytes_result.unwrap());
+    let mut content_type = data[..comma_idx].trim().to_owned();
+    let body = percent_decode(&data[comma_idx + 1..]);
 
-    if image_result.is_ok() {
-        return Ok(image_result.unwrap());
+    let is_base64 = content_type.to_lowercase().trim_end().ends_with(";base64");
+    let body = if is_base64 {
+        content_type = content_type[..content_type.rfind(';').unwrap()].trim().to_owned();
+        decode_base64(&body)?
     } else {
-        return Err(ResourceNotLoadedError(url.to_string()));
+        body
+    };
+
+    if content_type.is_empty() || content_type.starts_with(';') {
+        content_type = format!("text/plain{}", if content_type.is_empty() { ";charset=US-ASCII" } else { &content_type });
+    }
+    return Some((content_type, body));
+}
+
+
+pub fn percent_decode(text: &str) -> Vec<u8> {
+    //percent signs that are not followed by two hex digits are kept as they are
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex_value = if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[idx + 1..idx + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        if hex_value.is_some() {
+            decoded.push(hex_value.unwrap());
+            idx += 3;
+        } else {
+            decoded.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+    return decoded;
+}
+
+
+fn decode_base64(encoded: &[u8]) -> Option<Vec<u8>> {
+    //this is the forgiving base64 decode from the infra spec, so whitespace is ignored and the padding is optional
+    let mut values: Vec<u8> = Vec::new();
+    for byte in encoded {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' | b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' => { continue; },
+            _ => { return None; },
+        };
+        values.push(value);
+    }
+    if values.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::new();
+    for chunk in values.chunks(4) {
+        //every 4 characters are 3 bytes, a shorter chunk at the end has fewer bytes
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, value)| bits | ((*value as u32) << (18 - 6 * idx)));
+        for byte_idx in 0..(chunk.len() - 1) {
+            decoded.push((bits >> (16 - 8 * byte_idx)) as u8);
+        }
+    }
+    return Some(decoded);
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum PostBody {
+    UrlEncoded(String),
+    //the files are only read when the request is sent, so we don't block the main thread on reading them
+    MultipartFormData { fields: HashMap<String, String>, files: HashMap<String, Option<PathBuf>> },
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct MultipartFile {
+    pub field_name: String,
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+
+//TODO: there is too much duplication here with the get case...
+pub async fn http_post(client: &Client, url: &Url, body: PostBody) -> Result<HttpResponse, ResourceNotLoadedError>  {
+
+    let (body, content_type) = match body {
+        PostBody::UrlEncoded(text) => {
+            //TODO: not sure if this is always correct for all posts (probably not in general, but for forms it might be)
+            (text.into_bytes(), String::from("application/x-www-form-urlencoded"))
+        },
+        PostBody::MultipartFormData { fields, files } => {
+            let boundary = generate_multipart_boundary();
+            let files = read_files_for_multipart(&files);
+            (encode_multipart_form_data(&fields, &files, &boundary), format!("multipart/form-data; boundary={}", boundary))
+        },
+    };
+
+    //responses to a POST are never cached, since sending the POST again could give a different result
+    let response_result = send_following_redirects(client, url, Some((body, content_type)), Vec::new()).await;
+
+    if !response_result.is_ok() {
+        return Err(response_result.err().unwrap());
+    }
+    let (final_url, response) = response_result.unwrap();
+
+    return read_response(final_url, response).await;
+}
+
+
+pub fn encode_multipart_form_data(fields: &HashMap<String, String>, files: &Vec<MultipartFile>, boundary: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for (name, value) in fields {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", escape_multipart_name(name)).as_bytes());
+        body.extend_from_slice(value.as_bytes());
+        body.extend_from_slice(b"\r\n");
+    }
+
+    for file in files {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+                                       escape_multipart_name(&file.field_name), escape_multipart_name(&file.file_name)).as_bytes());
+        //TODO: we could guess a better content type based on the file extension
+        body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(&file.content);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    return body;
+}
+
+
+pub fn encode_form_urlencoded(fields: &HashMap<String, String>) -> String {
+    //this is the application/x-www-form-urlencoded serializer, used both for the body of posted forms and for the query of GET forms
+    return fields.iter().map(|(name, value)| format!("{}={}", encode_form_urlencoded_text(name), encode_form_urlencoded_text(value)))
+                        .collect::<Vec<String>>().join("&");
+}
+
+
+pub fn decode_form_urlencoded(text: &str) -> HashMap<String, String> {
+    //this is the application/x-www-form-urlencoded parser, when a name is in there more than once, we keep the last value
+    let mut fields = HashMap::new();
+    for name_value in text.split('&').filter(|name_value| !name_value.is_empty()) {
+        let (name, value) = name_value.split_once('=').unwrap_or((name_value, ""));
+        let decode = |text: &str| String::from_utf8_lossy(&percent_decode(&text.replace('+', " "))).into_owned();
+        fields.insert(decode(name), decode(value));
+    }
+    return fields;
+}
+
+
+pub fn encode_form_urlencoded_text(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b' ' => { encoded.push('+'); },
+            b'*' | b'-' | b'.' | b'_' | b'0'..=b'9' | b'A'..=b'Z' | b'a'..=b'z' => { encoded.push(byte as char); },
+            _ => { encoded.push_str(&format!("%{:02X}", byte)); },
+        }
     }
+    return encoded;
+}
+
 
+fn escape_multipart_name(name: &str) -> String {
+    //names and filenames are put in quotes, so these are escaped the same way as other browsers do
+    return name.replace('\n', "%0A").replace('\r', "%0D").replace('"', "%22");
 }
+
+
+fn read_files_for_multipart(files: &HashMap<String, Option<PathBuf>>) -> Vec<MultipartFile> {
+    let mut multipart_files = Vec::new();
+
+    for (field_name, path) in files {
+        if path.is_none() {
+            //a file input without a selected file is still sent, with an empty filename and content
+            multipart_files.push(MultipartFile { field_name: field_name.clone(), file_name: String::new(), content: Vec::new() });
+            continue;
+        }
+        let path = path.as_ref().unwrap();
+
+        let content = fs::read(path);
+        if content.is_err() {
+            debug_log_warn(format!("Could not read file for upload: {:?}", path));
+        }
+
+        let file_name = path.file_name().map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or(String::new());
+        multipart_files.push(MultipartFile { field_name: field_name.clone(), file_name, content: content.unwrap_or(Vec::new()) });
+    }
+
+    return multipart_files;
+}
+
+
+fn generate_multipart_boundary() -> String {
+    //TODO: we don't check that the boundary does not occur in the content, but with the time in it that is very unlikely
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos()).unwrap_or(0);
+    return format!("----WebCrustaceanFormBoundary{:x}", nanos);
+}
+
+
+//TODO: eventually this should be a http_get_binary, and the image stuff should be seperated out, because we will load other binary resources.
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::date::civil_from_days;
+use crate::jsonify::escape_json_string;
+use crate::network::LoadErrorKind;
+
+
+const MAX_LOGGED_REQUESTS: usize = 500;
+
+
+//this is shared by all threads loading resources, and shown on about:network
+pub static REQUEST_LOG: LazyLock<Mutex<RequestLog>> = LazyLock::new(|| {
+    return Mutex::new(RequestLog::new(MAX_LOGGED_REQUESTS));
+});
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct LoggedRequest {
+    pub url: String,
+    pub method: &'static str,
+    pub status: Option<u16>, //this is None when there was no response, for example when we could not connect
+    pub mime_type: Option<String>,
+    pub size: usize, //the size of the body we got, after decompressing
+    pub from_cache: bool,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub initiator: &'static str, //what the resource was loaded for, like "document" or "image"
+    pub error: Option<LoadErrorKind>,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct RequestLog {
+    //this is a ring buffer, once it is full the oldest requests make room for new ones
+    requests: VecDeque<LoggedRequest>,
+    max_requests: usize,
+}
+impl RequestLog {
+    pub fn new(max_requests: usize) -> RequestLog {
+        return RequestLog { requests: VecDeque::new(), max_requests };
+    }
+
+    pub fn record(&mut self, request: LoggedRequest) {
+        if self.requests.len() >= self.max_requests {
+            self.requests.pop_front();
+        }
+        self.requests.push_back(request);
+    }
+
+    pub fn requests(&self) -> &VecDeque<LoggedRequest> {
+        return &self.requests;
+    }
+
+    pub fn clear(&mut self) {
+        self.requests.clear();
+    }
+
+    pub fn to_har(&self) -> String {
+        //this is the http archive format (https://w3c.github.io/web-performance/specs/HAR/Overview.html), which other browsers and tools can open
+        //TODO: we don't keep the headers and cookies of requests yet, so those are always empty
+        let entries: Vec<String> = self.requests.iter().map(|request| {
+            let time = request.duration.as_secs_f64() * 1000.0;
+            let mut entry = format!("{{\"startedDateTime\":\"{}\",\"time\":{:.3},", format_iso_8601(request.started_at), time);
+            entry += format!("\"request\":{{\"method\":\"{}\",\"url\":\"{}\",\"httpVersion\":\"\",\"headers\":[],\"queryString\":[],\"cookies\":[],\
+                              \"headersSize\":-1,\"bodySize\":-1}},", request.method, escape_json_string(&request.url)).as_str();
+            entry += format!("\"response\":{{\"status\":{},\"statusText\":\"\",\"httpVersion\":\"\",\"headers\":[],\"cookies\":[],\
+                              \"content\":{{\"size\":{},\"mimeType\":\"{}\"}},\"redirectURL\":\"\",\"headersSize\":-1,\"bodySize\":{}}},",
+                             request.status.unwrap_or(0), request.size, escape_json_string(request.mime_type.as_deref().unwrap_or("")),
+                             if request.from_cache { 0 } else { request.size }).as_str();
+            entry += format!("\"cache\":{{}},\"timings\":{{\"send\":0,\"wait\":{:.3},\"receive\":0}},\"_initiator\":\"{}\"", time, request.initiator).as_str();
+            if request.error.is_some() {
+                entry += format!(",\"_error\":\"{:?}\"", request.error.unwrap()).as_str();
+            }
+            entry.push('}');
+            return entry;
+        }).collect();
+
+        return format!("{{\"log\":{{\"version\":\"1.2\",\"creator\":{{\"name\":\"webcrustacean\",\"version\":\"{}\"}},\"entries\":[{}]}}}}",
+                       env!("CARGO_PKG_VERSION"), entries.join(","));
+    }
+}
+
+
+fn format_iso_8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / (24 * 60 * 60)) as i64);
+    let seconds_of_day = seconds % (24 * 60 * 60);
+    return format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day, seconds_of_day / 3600, (seconds_of_day / 60) % 60, seconds_of_day % 60,
+                   since_epoch.subsec_millis());
+}
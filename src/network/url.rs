@@ -1,20 +1,22 @@
 use std::cmp::max;
 
+use crate::debug::debug_log_warn;
+use crate::network::percent_decode;
+
 
 #[cfg_attr(debug_assertions, derive(Debug))]
 enum UrlParsingState {
     SchemeStartState,
     SchemeState,
     NoSchemeState,
-    //SpecialRelativeOrAuthorityState, //present in the spec, but not currently implemented
-    //PathOrAuthorityState, //present in the spec, but not currently implemented
+    SpecialRelativeOrAuthorityState,
+    PathOrAuthorityState,
     RelativeState,
     RelativeSlashState,
     SpecialAuthoritySlashesState,
     SpecialAuthorityIgnoreSlashesState,
     AuthorityState,
-    HostState,
-    //HostnameState, //present in the spec, but not currently implemented
+    HostState, //the spec also has a HostnameState, but that is the same as this one when not parsing with a state override
     PortState,
     FileState,
     FileSlashState,
@@ -28,6 +30,32 @@ enum UrlParsingState {
 
 
 
+#[derive(PartialEq, Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum Origin {
+    //implementation of https://html.spec.whatwg.org/multipage/browsers.html#concept-origin
+    Tuple(String, String, String), //the scheme, host and port, where the port is empty when it is the default for the scheme
+    Opaque, //opaque origins (like the ones of data: urls) are never the same as any other origin
+}
+impl Origin {
+    pub fn is_same_origin(&self, other: &Origin) -> bool {
+        return match (self, other) {
+            (Origin::Tuple(..), Origin::Tuple(..)) => { self == other },
+            _ => { false },
+        };
+    }
+
+    pub fn serialize(&self) -> String {
+        return match self {
+            Origin::Tuple(scheme, host, port) => {
+                if port.is_empty() { format!("{}://{}", scheme, host) } else { format!("{}://{}:{}", scheme, host, port) }
+            },
+            Origin::Opaque => { String::from("null") },
+        };
+    }
+}
+
+
 #[derive(PartialEq, Clone)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct Url {
@@ -35,9 +63,9 @@ pub struct Url {
     pub scheme: String,
     pub username: String,
     pub password: String,
-    pub host: String,
-    pub port: String,
-    pub path: Vec<String>,
+    pub host: String, //this is already serialized, so for example punycode for international domains, and with brackets for ipv6
+    pub port: String, //this is empty when it is the default port for the scheme
+    pub path: Vec<String>, //the path, query and fragment are stored percent-encoded
     pub query: String,
     pub fragment: String,
     pub blob: String,
@@ -62,12 +90,18 @@ impl Url {
     }
 
     pub fn from_base_url(url_str: &String, base_url: Option<&Url>) -> Url {
+        //TODO: urls that are not valid should give an error, for now we return an empty url for them (which will fail to load)
+
+        //leading and trailing spaces and control characters are removed, and tabs and newlines are removed everywhere
+        let input: String = url_str.trim_matches(|c: char| c <= ' ').chars().filter(|c| *c != '\t' && *c != '\n' && *c != '\r').collect();
+        let input_length = input.chars().count() as i32;
+
         let mut state = UrlParsingState::SchemeStartState;
         let mut buffer = String::new();
         let mut pointer: i32 = 0; // the pointer schould point in the string, so normally won't be negative, but we need to decrease it
                                   // in the loop sometimes so it can later be increased to 0 again at the end of the loop. So we need it signed here.
         let mut previous_pointer: i32 = 0;
-        let mut url_str_iter = url_str.chars().peekable();
+        let mut url_str_iter = input.chars().peekable();
 
         let mut scheme = String::new();
         let mut username = String::new();
@@ -79,13 +113,20 @@ impl Url {
         let mut fragment = String::new();
         let blob = String::new();
 
+        let mut at_sign_seen = false;
+        let mut inside_brackets = false;
+        let mut password_token_seen = false;
+
         let mut next_char = url_str_iter.next();
 
 
         loop {
+            //special schemes also accept a backslash where a slash is expected
+            let is_slash = next_char == Some('/') || (Url::scheme_is_special(&scheme) && next_char == Some('\\'));
+
             match state {
                 UrlParsingState::SchemeStartState => {
-                    if next_char.is_some() && next_char.unwrap().is_ascii_alphanumeric() {
+                    if next_char.is_some() && next_char.unwrap().is_ascii_alphabetic() {
                         buffer.push(next_char.unwrap().to_ascii_lowercase());
                         state = UrlParsingState::SchemeState;
                     } else {
@@ -95,7 +136,7 @@ impl Url {
                 },
 
                 UrlParsingState::SchemeState => {
-                    if next_char.is_some() && next_char.unwrap().is_ascii_alphanumeric() {
+                    if next_char.is_some() && (next_char.unwrap().is_ascii_alphanumeric() || matches!(next_char.unwrap(), '+' | '-' | '.')) {
                         buffer.push(next_char.unwrap().to_ascii_lowercase());
                     } else if next_char == Some(':') {
                         scheme = buffer;
@@ -103,8 +144,13 @@ impl Url {
 
                         if scheme == "file" {
                             state = UrlParsingState::FileState;
+                        } else if Url::scheme_is_special(&scheme) && base_url.is_some() && base_url.unwrap().scheme == scheme {
+                            state = UrlParsingState::SpecialRelativeOrAuthorityState;
                         } else if Url::scheme_is_special(&scheme) {
                             state = UrlParsingState::SpecialAuthoritySlashesState;
+                        } else if url_str_iter.peek() == Some(&'/') {
+                            state = UrlParsingState::PathOrAuthorityState;
+                            pointer = pointer + 1;
                         } else {
                             state = UrlParsingState::OpaquePathState;
                         }
@@ -115,10 +161,212 @@ impl Url {
                     }
                 },
 
+                UrlParsingState::NoSchemeState => {
+                    if base_url.is_none() {
+                        //TODO: the spec says this is an error, we treat it as a local file for now (for example for what is typed in the address bar)
+                        pointer = max(pointer - 1, -1);
+                        state = UrlParsingState::FileState;
+                    } else if base_url.unwrap().has_opaque_path() {
+                        if next_char != Some('#') {
+                            return Url::invalid(url_str);
+                        }
+                        let base_url = base_url.unwrap();
+                        scheme = base_url.scheme.clone();
+                        path = base_url.path.clone();
+                        query = base_url.query.clone();
+                        fragment = String::new();
+                        state = UrlParsingState::FragmentState;
+                    } else if base_url.unwrap().scheme != "file" {
+                        pointer = max(pointer - 1, -1);
+                        state = UrlParsingState::RelativeState;
+                    } else {
+                        pointer = max(pointer - 1, -1);
+                        state = UrlParsingState::FileState;
+                    }
+                },
+
+                UrlParsingState::SpecialRelativeOrAuthorityState => {
+                    if next_char == Some('/') && url_str_iter.peek() == Some(&'/') {
+                        state = UrlParsingState::SpecialAuthorityIgnoreSlashesState;
+                        pointer = pointer + 1;
+                    } else {
+                        state = UrlParsingState::RelativeState;
+                        pointer = max(pointer - 1, -1);
+                    }
+                },
+
+                UrlParsingState::PathOrAuthorityState => {
+                    if next_char == Some('/') {
+                        state = UrlParsingState::AuthorityState;
+                    } else {
+                        state = UrlParsingState::PathState;
+                        pointer = max(pointer - 1, -1);
+                    }
+                },
+
+                UrlParsingState::RelativeState =>  {
+                    scheme = base_url.unwrap().scheme.clone();
+                    if is_slash {
+                        state = UrlParsingState::RelativeSlashState;
+                    } else {
+                        let base_url = base_url.unwrap();
+                        username = base_url.username.clone();
+                        password = base_url.password.clone();
+                        host = base_url.host.clone();
+                        port = base_url.port.clone();
+                        path = base_url.path.clone();
+                        query = base_url.query.clone();
+                        if next_char == Some('?') {
+                            query = String::new();
+                            state = UrlParsingState::QueryState;
+                        } else if next_char == Some('#') {
+                            fragment = String::new();
+                            state = UrlParsingState::FragmentState;
+                        } else if next_char != None {
+                            query = String::new();
+                            if !path.is_empty() {
+                                path.remove(path.len() - 1);
+                            }
+                            state = UrlParsingState::PathState;
+                            pointer = max(pointer - 1, -1);
+                        }
+                    }
+
+                },
+
+                UrlParsingState::RelativeSlashState => {
+                    if Url::scheme_is_special(&scheme) && is_slash {
+                        state = UrlParsingState::SpecialAuthorityIgnoreSlashesState;
+                    } else if next_char == Some('/') {
+                        state = UrlParsingState::AuthorityState;
+                    } else {
+                        let base_url = base_url.unwrap();
+                        username = base_url.username.clone();
+                        password = base_url.password.clone();
+                        host = base_url.host.clone();
+                        port = base_url.port.clone();
+                        state = UrlParsingState::PathState;
+                        pointer = max(pointer - 1, -1);
+                    }
+                },
+
+                UrlParsingState::SpecialAuthoritySlashesState =>  {
+                    //when the slashes are missing (like in "http:example.com") we still parse the authority, the spec calls that a validation error
+                    state = UrlParsingState::SpecialAuthorityIgnoreSlashesState;
+                    if next_char == Some('/') && url_str_iter.peek() == Some(&'/') {
+                        pointer = pointer + 1;
+                    } else {
+                        pointer = max(pointer - 1, -1);
+                    }
+                },
+
+                UrlParsingState::SpecialAuthorityIgnoreSlashesState => {
+                    //extra slashes are ignored
+                    if next_char != Some('/') && next_char != Some('\\') {
+                        state = UrlParsingState::AuthorityState;
+                        pointer = max(pointer - 1, -1);
+                    }
+                },
+
+                UrlParsingState::AuthorityState => {
+                    if next_char == Some('@') {
+                        //everything before the last @ is the username and password, an @ in them should have been encoded, but we accept it
+                        if at_sign_seen {
+                            buffer.insert_str(0, "%40");
+                        }
+                        at_sign_seen = true;
+
+                        for char in buffer.chars() {
+                            if char == ':' && !password_token_seen {
+                                password_token_seen = true;
+                            } else if password_token_seen {
+                                password.push_str(&percent_encode_char(char, in_userinfo_percent_encode_set));
+                            } else {
+                                username.push_str(&percent_encode_char(char, in_userinfo_percent_encode_set));
+                            }
+                        }
+                        buffer = String::new();
+                    } else if next_char == None || is_slash || next_char == Some('?') || next_char == Some('#') {
+                        if at_sign_seen && buffer.is_empty() {
+                            return Url::invalid(url_str);
+                        }
+                        //we go back to the start of the host, and parse it again in the host state
+                        let buffer_length_plus_one = buffer.chars().count() + 1;
+                        pointer = pointer - buffer_length_plus_one as i32;
+                        pointer = max(pointer, -1);
+                        buffer = String::new();
+                        state = UrlParsingState::HostState;
+                    } else {
+                        buffer.push(next_char.unwrap());
+                    }
+                },
+
+                UrlParsingState::HostState => {
+                    if next_char == Some(':') && !inside_brackets {
+                        if buffer.is_empty() {
+                            return Url::invalid(url_str);
+                        }
+                        let parsed_host = Url::parse_host(&buffer, !Url::scheme_is_special(&scheme));
+                        if parsed_host.is_none() {
+                            return Url::invalid(url_str);
+                        }
+                        host = parsed_host.unwrap();
+                        buffer = String::new();
+                        state = UrlParsingState::PortState;
+                    } else if next_char == None || is_slash || next_char == Some('?') || next_char == Some('#') {
+                        if buffer.is_empty() && Url::scheme_is_special(&scheme) {
+                            return Url::invalid(url_str);
+                        }
+                        if !buffer.is_empty() {
+                            let parsed_host = Url::parse_host(&buffer, !Url::scheme_is_special(&scheme));
+                            if parsed_host.is_none() {
+                                return Url::invalid(url_str);
+                            }
+                            host = parsed_host.unwrap();
+                        }
+                        buffer = String::new();
+                        state = UrlParsingState::PathStartState;
+                        if next_char != None {
+                            pointer = max(pointer - 1, -1);
+                        }
+                    } else {
+                        if next_char == Some('[') {
+                            inside_brackets = true;
+                        } else if next_char == Some(']') {
+                            inside_brackets = false;
+                        }
+                        buffer.push(next_char.unwrap());
+                    }
+                },
+
+                UrlParsingState::PortState => {
+                    if next_char.is_some() && next_char.unwrap().is_ascii_digit() {
+                        buffer.push(next_char.unwrap());
+                    } else if next_char == None || is_slash || next_char == Some('?') || next_char == Some('#') {
+                        if !buffer.is_empty() {
+                            let port_number = buffer.parse::<u32>();
+                            if port_number.is_err() || port_number.as_ref().unwrap() > &65535 {
+                                return Url::invalid(url_str);
+                            }
+                            let port_number = port_number.unwrap();
+
+                            //the default port is left out, so urls with and without it are the same
+                            port = if Url::default_port(&scheme) == Some(port_number) { String::new() } else { port_number.to_string() };
+                            buffer = String::new();
+                        }
+                        state = UrlParsingState::PathStartState;
+                        if next_char != None {
+                            pointer = max(pointer - 1, -1);
+                        }
+                    } else {
+                        return Url::invalid(url_str);
+                    }
+                },
+
                 UrlParsingState::FileState => {
                     scheme = String::from("file");
                     host = String::new();
-                    if next_char == Some('/') {
+                    if is_slash {
                         state = UrlParsingState::FileSlashState;
                     } else if base_url.is_some() && base_url.unwrap().scheme == "file" {
                         host = base_url.unwrap().host.clone();
@@ -147,13 +395,13 @@ impl Url {
                 }
 
                 UrlParsingState::FileSlashState => {
-                    if next_char == Some('/') {
+                    if is_slash {
                         state = UrlParsingState::FileHostState;
                     } else {
                         if base_url.is_some() && base_url.unwrap().scheme == "file" {
                             host = base_url.unwrap().host.clone();
                         }
-                        state = UrlParsingState::FileState;
+                        state = UrlParsingState::PathState;
                         pointer = max(pointer - 1, -1);
                     }
                 },
@@ -162,11 +410,18 @@ impl Url {
                     if next_char == None || next_char == Some('/') || next_char == Some('\\') || next_char == Some('?') || next_char == Some('#') {
                         pointer = max(pointer - 1, -1);
 
-                        if buffer == "" {
+                        if Url::is_windows_drive_letter(&buffer) {
+                            //this is a path like file://C:/folder, which we parse as if the host is empty
+                            state = UrlParsingState::PathState;
+                        } else if buffer == "" {
                             host = String::new();
                             state = UrlParsingState::PathStartState;
                         } else {
-                            host = Url::parse_host(&buffer);
+                            let parsed_host = Url::parse_host(&buffer, false);
+                            if parsed_host.is_none() {
+                                return Url::invalid(url_str);
+                            }
+                            host = parsed_host.unwrap();
                             if host == "localhost" {
                                 host = String::new();
                             }
@@ -182,7 +437,7 @@ impl Url {
                 UrlParsingState::PathStartState => {
                     if Url::scheme_is_special(&scheme) {
                         state = UrlParsingState::PathState;
-                        if next_char != Some('/') {
+                        if !is_slash {
                             pointer = max(pointer - 1, -1);
                         }
                     } else if next_char == Some('?') {
@@ -197,50 +452,30 @@ impl Url {
                     }
                 },
 
-                UrlParsingState::SpecialAuthorityIgnoreSlashesState => {
-                    if next_char != Some('/') && next_char != Some('\\') {
-                        state = UrlParsingState::AuthorityState;
-                        pointer = max(pointer - 1, -1);
-                    } else {
-                        todo!(); //this should be an error
-                    }
-                },
-
-                UrlParsingState::SpecialAuthoritySlashesState =>  {
-                    if next_char == Some('/') && url_str_iter.peek() == Some(&'/') {
-                        state = UrlParsingState::SpecialAuthorityIgnoreSlashesState;
-                        pointer = pointer + 1;
-                    } else {
-                        todo!(); //this should be an error (but it also moves to a new state?)
-                    }
-                },
-
-                UrlParsingState::AuthorityState => {
-                    if next_char == Some('@') {
-                        todo!(); //Auth url's are not yet implemented
-                    } else if next_char == None || next_char == Some('/') || next_char == Some('?') || next_char == Some('#') {
-                        let buffer_length_plus_one = buffer.len() + 1;
-                        pointer = pointer - buffer_length_plus_one as i32;
-                        pointer = max(pointer, -1);
-                        buffer = String::new();
-                        state = UrlParsingState::HostState;
-                    } else {
-                        buffer.push(next_char.unwrap());
-                    }
-                },
-
                 UrlParsingState::PathState =>  {
-                    if next_char == None || next_char == Some('/') || next_char == Some('?') || next_char == Some('#') {
-                        //TODO: check single and double dot path segment
-                        path.push(buffer);
+                    if next_char == None || is_slash || next_char == Some('?') || next_char == Some('#') {
+                        if Url::is_double_dot_segment(&buffer) {
+                            //".." removes the last part of the path, and like "." it means the path ends with a slash when it is the last part
+                            path.pop();
+                            if !is_slash {
+                                path.push(String::new());
+                            }
+                        } else if Url::is_single_dot_segment(&buffer) {
+                            if !is_slash {
+                                path.push(String::new());
+                            }
+                        } else {
+                            path.push(buffer);
+                        }
                         buffer = String::new();
+
                         if next_char == Some('?') {
                             state = UrlParsingState::QueryState;
                         } else if next_char == Some('#') {
                             state = UrlParsingState::FragmentState;
                         }
                     } else {
-                        buffer.push(next_char.unwrap());
+                        buffer.push_str(&percent_encode_char(next_char.unwrap(), in_path_percent_encode_set));
                     }
                 },
 
@@ -252,95 +487,19 @@ impl Url {
                         fragment = String::new();
                         state = UrlParsingState::FragmentState;
                     } else if next_char != None {
-                        //TODO: there is something about encoding in the spec that we don't do yet
                         if path.is_empty() {
                             path.push(String::new());
                         }
-                        path.last_mut().unwrap().push(next_char.unwrap());
-                    }
-                },
-
-                UrlParsingState::HostState => {
-                    if next_char == Some(':') {
-                        host = buffer;
-                        buffer = String::new();
-                        state = UrlParsingState::PortState;
-                    } else if next_char == None || next_char == Some('/') || next_char == Some('?') || next_char == Some('#') {
-                        host = buffer;
-                        buffer = String::new();
-                        state = UrlParsingState::PathStartState;
-                    } else {
-                        buffer.push(next_char.unwrap());
-                    }
-                },
-
-                UrlParsingState::NoSchemeState => {
-                    if next_char == Some('#') && base_url.is_some() && !base_url.unwrap().path.is_empty() {
-                        let base_url = base_url.unwrap();
-                        scheme = base_url.scheme.clone();
-                        path = base_url.path.clone();
-                        query = base_url.query.clone();
-                        fragment = base_url.fragment.clone();
-                        state = UrlParsingState::FragmentState;
-                    } else if base_url.is_some() && base_url.unwrap().scheme != "file" {
-                        pointer = max(pointer - 1, -1);
-                        state = UrlParsingState::RelativeState;
-                    } else {
-                        pointer = max(pointer - 1, -1);
-                        state = UrlParsingState::FileState;
-                    }
-                },
-
-                UrlParsingState::RelativeState =>  {
-                    scheme = base_url.unwrap().scheme.clone();
-                    if next_char == Some('/') {
-                        state = UrlParsingState::RelativeSlashState;
-                    } else {
-                        let base_url = base_url.unwrap();
-                        username = base_url.username.clone();
-                        password = base_url.password.clone();
-                        host = base_url.host.clone();
-                        port = base_url.port.clone();
-                        path = base_url.path.clone();
-                        query = base_url.query.clone();
-                        if next_char == Some('?') {
-                            query = String::new();
-                            state = UrlParsingState::QueryState;
-                        } else if next_char == Some('#') {
-                            fragment = String::new();
-                            state = UrlParsingState::FragmentState;
-                        } else if next_char != None {
-                            query = String::new();
-                            if !path.is_empty() {
-                                path.remove(path.len() - 1);
-                            }
-                            state = UrlParsingState::PathState;
-                            pointer = max(pointer - 1, -1);
-                        }
-                    }
-
-                },
-
-                UrlParsingState::RelativeSlashState => {
-                    if Url::scheme_is_special(&scheme) && next_char == Some('/') {
-                        state = UrlParsingState::SpecialAuthorityIgnoreSlashesState;
-                    } else if next_char == Some('/') {
-                        state = UrlParsingState::AuthorityState;
-                    } else {
-                        let base_url = base_url.unwrap();
-                        username = base_url.username.clone();
-                        password = base_url.password.clone();
-                        host = base_url.host.clone();
-                        port = base_url.port.clone();
-                        state = UrlParsingState::PathState;
-                        pointer = max(pointer - 1, -1);
+                        path.last_mut().unwrap().push_str(&percent_encode_char(next_char.unwrap(), in_c0_control_percent_encode_set));
                     }
                 },
 
                 UrlParsingState::QueryState => {
-                    //TODO: we are currently ignoring a lot of percentage encoding here that the spec requires
                     if next_char == Some('#') || next_char == None {
-                        query.push_str(&buffer);
+                        let encode_set = if Url::scheme_is_special(&scheme) { in_special_query_percent_encode_set } else { in_query_percent_encode_set };
+                        for char in buffer.chars() {
+                            query.push_str(&percent_encode_char(char, encode_set));
+                        }
                         buffer = String::new();
                         if next_char == Some('#') {
                             state = UrlParsingState::FragmentState;
@@ -352,15 +511,12 @@ impl Url {
 
                 UrlParsingState::FragmentState => {
                     if next_char != None {
-                        fragment.push(next_char.unwrap());
+                        fragment.push_str(&percent_encode_char(next_char.unwrap(), in_fragment_percent_encode_set));
                     }
                 },
-
-                UrlParsingState::PortState => todo!(), //TODO: add test and implement
-
             }
 
-            if pointer >= url_str.len() as i32 {
+            if pointer >= input_length {
                 //we don't check next_char, because we still need to update it to pointer, but we don't do that first because we still need to
                 //increase the pointer, and if it then points to EOF, we still need to do 1 loop...
                 break;
@@ -375,7 +531,7 @@ impl Url {
                 url_str_iter.next()
             } else {
                 previous_pointer = pointer;
-                url_str_iter = url_str.chars().peekable();
+                url_str_iter = input.chars().peekable();
                 url_str_iter.nth(pointer as usize)
             };
         }
@@ -383,29 +539,128 @@ impl Url {
         return Url { scheme, username, password, host, port, path, query, fragment, blob }
     }
 
-    fn parse_host(host_text: &String) -> String {
-        //TODO: this should actually implement https://url.spec.whatwg.org/#concept-host-parser
-        return host_text.clone();
+    fn invalid(url_str: &String) -> Url {
+        debug_log_warn(format!("Could not parse url: {}", url_str));
+        return Url::empty();
+    }
+
+    fn parse_host(host_text: &String, is_opaque: bool) -> Option<String> {
+        //this implements https://url.spec.whatwg.org/#concept-host-parser, returning the host as we serialize it
+        if host_text.starts_with('[') {
+            //TODO: we don't parse ipv6 addresses, so they are not compressed to their shortest form, we only check the characters
+            if !host_text.ends_with(']') || !host_text[1..host_text.len() - 1].chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.') {
+                return None;
+            }
+            return Some(host_text.to_ascii_lowercase());
+        }
+
+        if is_opaque {
+            //hosts of schemes that are not special are kept as they are, apart from percent-encoding
+            if host_text.chars().any(|c| is_forbidden_host_code_point(c)) {
+                return None;
+            }
+            return Some(host_text.chars().map(|c| percent_encode_char(c, in_c0_control_percent_encode_set)).collect());
+        }
+
+        let domain = String::from_utf8_lossy(&percent_decode(host_text)).into_owned();
+        let ascii_domain = domain_to_ascii(&domain)?;
+        if ascii_domain.chars().any(|c| is_forbidden_host_code_point(c) || c <= '\u{1f}' || c == '%' || c == '\u{7f}') {
+            return None;
+        }
+
+        if ends_in_a_number(&ascii_domain) {
+            return parse_ipv4(&ascii_domain);
+        }
+        return Some(ascii_domain);
     }
 
     pub fn to_string(&self) -> String {
+        //this implements https://url.spec.whatwg.org/#concept-url-serializer
         let mut full_string = String::new();
 
         full_string.push_str(&self.scheme);
-        if self.scheme != "about" {  //TODO: this is a hack, I'm missing something in the URL spec to make this work I think (about: should not have slashes)
-            full_string.push_str("://");
+        full_string.push(':');
+
+        if !self.host.is_empty() || self.scheme == "file" || (!self.has_opaque_path() && Url::scheme_is_special(&self.scheme)) {
+            full_string.push_str("//");
+            if !self.username.is_empty() || !self.password.is_empty() {
+                full_string.push_str(&self.username);
+                if !self.password.is_empty() {
+                    full_string.push(':');
+                    full_string.push_str(&self.password);
+                }
+                full_string.push('@');
+            }
+            full_string.push_str(&self.host);
+            if !self.port.is_empty() {
+                full_string.push(':');
+                full_string.push_str(&self.port);
+            }
+        }
+
+        if self.has_opaque_path() {
+            full_string.push_str(self.path.join("/").as_str());
         } else {
-            full_string.push_str(":");
+            full_string.push('/');
+            full_string.push_str(self.path.join("/").as_str());
+        }
+
+        if !self.query.is_empty() {
+            full_string.push('?');
+            full_string.push_str(&self.query);
         }
-        full_string.push_str(&self.host);
-        if self.scheme != "about" {  //TODO: this is a hack, I'm missing something in the URL spec to make this work I think (about: should not have slashes)
-            full_string.push_str("/");
+        if !self.fragment.is_empty() {
+            full_string.push('#');
+            full_string.push_str(&self.fragment);
         }
-        full_string.push_str(self.path.join("/").as_str());
 
         return full_string;
     }
 
+    pub fn without_fragment(&self) -> Url {
+        let mut url = self.clone();
+        url.fragment = String::new();
+        return url;
+    }
+
+    pub fn origin(&self) -> Origin {
+        //TODO: blob: urls should get the origin of the url inside them, and file: urls are opaque, which means local pages can't access each other
+        if Url::default_port(&self.scheme).is_some() {
+            return Origin::Tuple(self.scheme.clone(), self.host.clone(), self.port.clone());
+        }
+        return Origin::Opaque;
+    }
+
+    #[allow(dead_code)] //TODO: only used in tests for now, we will need it when we check the origin of scripts and frames
+    pub fn is_same_origin(&self, other: &Url) -> bool {
+        return self.origin().is_same_origin(&other.origin());
+    }
+
+    pub fn view_source_target(&self) -> Option<Url> {
+        //a view-source: url contains the url of the page we show the source of, the fragment is for the source page itself
+        if self.scheme != "view-source" {
+            return None;
+        }
+        let full_url = self.without_fragment().to_string();
+        return Some(Url::from(&full_url["view-source:".len()..].to_owned()));
+    }
+
+    pub fn is_potentially_trustworthy(&self) -> bool {
+        //this is a simplified version of https://w3c.github.io/webappsec-secure-contexts/#is-url-trustworthy, we don't check for local hosts
+        return matches!(self.scheme.as_str(), "https" | "wss" | "file" | "data" | "about");
+    }
+
+    pub fn is_same_document(&self, other: &Url) -> bool {
+        //urls that only differ in their fragment point to (different parts of) the same page
+        return self.without_fragment() == other.without_fragment();
+    }
+
+    pub fn has_opaque_path(&self) -> bool {
+        //TODO: the parser should keep track of this (see https://url.spec.whatwg.org/#url-opaque-path), for now we assume urls of schemes that
+        //      are not special and have no host have an opaque path (like about:, data: and mailto:), which is not true for urls like "foo:/bar"
+        return !Url::scheme_is_special(&self.scheme) && self.host.is_empty() && !self.scheme.is_empty();
+    }
+
     pub fn file_extension(&self) -> Option<String> {
         let last_path_part = self.path.last();
         if last_path_part.is_none() {
@@ -421,8 +676,215 @@ impl Url {
 
     fn scheme_is_special(scheme: &str) -> bool {
         //Special is meant in the definition of https://url.spec.whatwg.org/#is-special
-        // the spec also checks for ws and wss, but we are not implementing those
-        return scheme == "http" || scheme == "https" || scheme == "ftp" || scheme == "file";
+        return Url::default_port(scheme).is_some() || scheme == "file";
+    }
+
+    fn default_port(scheme: &str) -> Option<u32> {
+        return match scheme {
+            "http" | "ws" => Some(80),
+            "https" | "wss" => Some(443),
+            "ftp" => Some(21),
+            _ => None,
+        };
+    }
+
+    fn is_single_dot_segment(segment: &String) -> bool {
+        return segment == "." || segment.eq_ignore_ascii_case("%2e");
+    }
+
+    fn is_double_dot_segment(segment: &String) -> bool {
+        return matches!(segment.to_ascii_lowercase().as_str(), ".." | ".%2e" | "%2e." | "%2e%2e");
+    }
+
+    fn is_windows_drive_letter(text: &String) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        return chars.len() == 2 && chars[0].is_ascii_alphabetic() && (chars[1] == ':' || chars[1] == '|');
+    }
+}
+
+
+fn in_c0_control_percent_encode_set(c: char) -> bool {
+    return c < ' ' || c > '~';
+}
+fn in_fragment_percent_encode_set(c: char) -> bool {
+    return in_c0_control_percent_encode_set(c) || matches!(c, ' ' | '"' | '<' | '>' | '`');
+}
+fn in_query_percent_encode_set(c: char) -> bool {
+    return in_c0_control_percent_encode_set(c) || matches!(c, ' ' | '"' | '#' | '<' | '>');
+}
+fn in_special_query_percent_encode_set(c: char) -> bool {
+    return in_query_percent_encode_set(c) || c == '\'';
+}
+fn in_path_percent_encode_set(c: char) -> bool {
+    return in_query_percent_encode_set(c) || matches!(c, '?' | '^' | '`' | '{' | '}');
+}
+fn in_userinfo_percent_encode_set(c: char) -> bool {
+    return in_path_percent_encode_set(c) || matches!(c, '/' | ':' | ';' | '=' | '@' | '[' | '\\' | ']' | '|');
+}
+
+
+fn percent_encode_char(c: char, in_encode_set: fn(char) -> bool) -> String {
+    if !in_encode_set(c) {
+        return c.to_string();
+    }
+    let mut utf8_bytes = [0; 4];
+    return c.encode_utf8(&mut utf8_bytes).bytes().map(|byte| format!("%{:02X}", byte)).collect();
+}
+
+
+fn is_forbidden_host_code_point(c: char) -> bool {
+    return matches!(c, '\0' | '\t' | '\n' | '\r' | ' ' | '#' | '/' | ':' | '<' | '>' | '?' | '@' | '[' | '\\' | ']' | '^' | '|');
+}
+
+
+fn domain_to_ascii(domain: &String) -> Option<String> {
+    //TODO: this is a simplified version of https://www.unicode.org/reports/tr46/#ToASCII, we only lowercase, and don't do the other mappings
+    let labels: Vec<String> = domain.to_lowercase().split(|c| c == '.' || c == '\u{3002}').map(|label| {
+        if label.is_ascii() {
+            return label.to_owned();
+        }
+        return format!("xn--{}", punycode_encode(label));
+    }).collect();
+
+    let ascii_domain = labels.join(".");
+    if ascii_domain.is_empty() {
+        return None;
+    }
+    return Some(ascii_domain);
+}
+
+
+fn punycode_encode(label: &str) -> String {
+    //this is the encoding from https://www.rfc-editor.org/rfc/rfc3492#section-6.3, with the parameters for domain names
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+    const SKEW: u32 = 38;
+    const DAMP: u32 = 700;
+    const INITIAL_BIAS: u32 = 72;
+    const INITIAL_N: u32 = 128;
+
+    fn adapt(delta: u32, nr_of_points: u32, first_time: bool) -> u32 {
+        let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+        delta += delta / nr_of_points;
+        let mut k = 0;
+        while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+            delta /= BASE - T_MIN;
+            k += BASE;
+        }
+        return k + (((BASE - T_MIN + 1) * delta) / (delta + SKEW));
+    }
+    fn encode_digit(digit: u32) -> char {
+        return if digit < 26 { (b'a' + digit as u8) as char } else { (b'0' + (digit - 26) as u8) as char };
+    }
+
+    let code_points: Vec<u32> = label.chars().map(|c| c as u32).collect();
+    let mut output: String = label.chars().filter(|c| c.is_ascii()).collect();
+    let nr_of_basic_points = output.len() as u32;
+    if nr_of_basic_points > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut handled_points = nr_of_basic_points;
+
+    while (handled_points as usize) < code_points.len() {
+        let next_code_point = *code_points.iter().filter(|code_point| **code_point >= n).min().unwrap();
+        delta += (next_code_point - n) * (handled_points + 1);
+        n = next_code_point;
+
+        for code_point in &code_points {
+            if *code_point < n {
+                delta += 1;
+            }
+            if *code_point == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = if k <= bias { T_MIN } else if k >= bias + T_MAX { T_MAX } else { k - bias };
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + ((q - t) % (BASE - t))));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled_points + 1, handled_points == nr_of_basic_points);
+                delta = 0;
+                handled_points += 1;
+            }
+        }
+
+        delta += 1;
+        n += 1;
+    }
+
+    return output;
+}
+
+
+fn ends_in_a_number(domain: &String) -> bool {
+    let mut labels: Vec<&str> = domain.split('.').collect();
+    if labels.last() == Some(&"") {
+        if labels.len() == 1 {
+            return false;
+        }
+        labels.pop();
+    }
+
+    let last_label = labels.last().unwrap();
+    if !last_label.is_empty() && last_label.chars().all(|c| c.is_ascii_digit()) {
+        return true;
     }
+    return parse_ipv4_number(last_label).is_some();
 }
 
+
+fn parse_ipv4_number(text: &str) -> Option<u64> {
+    //numbers in ipv4 addresses can also be written in hex (starting with 0x) or octal (starting with 0)
+    if text.is_empty() {
+        return None;
+    }
+    let (digits, radix) = if text.len() >= 2 && (text.starts_with("0x") || text.starts_with("0X")) {
+        (&text[2..], 16)
+    } else if text.len() >= 2 && text.starts_with('0') {
+        (&text[1..], 8)
+    } else {
+        (text, 10)
+    };
+
+    if digits.is_empty() {
+        return Some(0);
+    }
+    return u64::from_str_radix(digits, radix).ok();
+}
+
+
+fn parse_ipv4(domain: &String) -> Option<String> {
+    //this implements https://url.spec.whatwg.org/#concept-ipv4-parser, so for example "0x7f.1" becomes "127.0.0.1"
+    let mut parts: Vec<&str> = domain.split('.').collect();
+    if parts.last() == Some(&"") && parts.len() > 1 {
+        parts.pop();
+    }
+    if parts.len() > 4 {
+        return None;
+    }
+
+    let mut numbers = Vec::new();
+    for part in parts {
+        numbers.push(parse_ipv4_number(part)?);
+    }
+    let last_number = numbers.pop().unwrap();
+    if numbers.iter().any(|number| *number > 255) || last_number >= 256_u64.pow(4 - numbers.len() as u32) {
+        return None;
+    }
+
+    let mut address = last_number;
+    for (idx, number) in numbers.iter().enumerate() {
+        address += number * 256_u64.pow(3 - idx as u32);
+    }
+    return Some(format!("{}.{}.{}.{}", address >> 24, (address >> 16) & 255, (address >> 8) & 255, address & 255));
+}
@@ -0,0 +1,158 @@
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::{Error as WsError, Message, WebSocket};
+use tungstenite::protocol::CloseFrame;
+use tungstenite::stream::MaybeTlsStream;
+
+use crate::debug::debug_log_warn;
+use crate::network::url::Url;
+
+
+const READ_TIMEOUT: Duration = Duration::from_millis(20); //how long we wait for a message, before checking whether there is something to send
+
+//these are the close codes from https://www.rfc-editor.org/rfc/rfc6455#section-7.4.1 that we use ourselves
+pub const CLOSE_CODE_NO_STATUS: u16 = 1005;
+pub const CLOSE_CODE_ABNORMAL: u16 = 1006;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum WebSocketEvent {
+    Open,
+    Message(String),
+    Error,
+    Close(u16, String, bool), //the code and the reason, and whether the connection was closed cleanly
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+enum WebSocketCommand {
+    Send(String),
+    Close(Option<(u16, String)>), //the code and reason are optional, without them the close frame has no status
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct WebSocketConnection {
+    //the connection runs on its own thread, when this is dropped (because the page is gone) that thread closes the connection
+    commands: Sender<WebSocketCommand>,
+    events: Receiver<WebSocketEvent>,
+}
+impl WebSocketConnection {
+    pub fn connect(url: &Url) -> WebSocketConnection {
+        let (command_sender, command_receiver) = channel();
+        let (event_sender, event_receiver) = channel();
+
+        let url = url.to_string();
+        thread::spawn(move || run_connection(url, command_receiver, event_sender));
+
+        return WebSocketConnection { commands: command_sender, events: event_receiver };
+    }
+
+    pub fn send(&self, text: String) {
+        //when the thread is already gone, we will get (or already got) the close event, so we don't need to report anything here
+        let _ = self.commands.send(WebSocketCommand::Send(text));
+    }
+
+    pub fn close(&self, code_and_reason: Option<(u16, String)>) {
+        let _ = self.commands.send(WebSocketCommand::Close(code_and_reason));
+    }
+
+    pub fn try_receive_event(&self) -> Option<WebSocketEvent> {
+        return self.events.try_recv().ok();
+    }
+}
+
+
+fn run_connection(url: String, commands: Receiver<WebSocketCommand>, events: Sender<WebSocketEvent>) {
+    //sending the events fails when the page that opened the connection is gone, we ignore that here, since we notice it on the commands
+    //TODO: we don't use the proxy settings and the cookies here yet
+    let connect_result = tungstenite::connect(url.as_str());
+    if connect_result.is_err() {
+        debug_log_warn(format!("Could not connect to websocket {}: {}", url, connect_result.err().unwrap()));
+        let _ = events.send(WebSocketEvent::Error);
+        let _ = events.send(WebSocketEvent::Close(CLOSE_CODE_ABNORMAL, String::new(), false));
+        return;
+    }
+    let (mut socket, _) = connect_result.unwrap();
+
+    //reading blocks, so we only wait shortly for a message, and then check if there are messages to send
+    set_read_timeout(&socket, READ_TIMEOUT);
+    let _ = events.send(WebSocketEvent::Open);
+
+    let mut close_frame: Option<CloseFrame> = None;
+    loop {
+        loop {
+            let command = commands.try_recv();
+            let send_result = match command {
+                Ok(WebSocketCommand::Send(text)) => socket.send(Message::text(text)),
+                Ok(WebSocketCommand::Close(code_and_reason)) => {
+                    socket.close(code_and_reason.map(|(code, reason)| CloseFrame { code: code.into(), reason: reason.into() }))
+                },
+                Err(TryRecvError::Empty) => { break; },
+                Err(TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    return;
+                },
+            };
+            if send_result.is_err() && !is_timeout(send_result.as_ref().err().unwrap()) {
+                debug_log_warn(format!("Could not send on websocket {}: {}", url, send_result.err().unwrap()));
+            }
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let _ = events.send(WebSocketEvent::Message(text.to_string()));
+            },
+            Ok(Message::Binary(_)) => {
+                //TODO: scripts should get these as a Blob or ArrayBuffer, which we don't have yet
+                debug_log_warn(format!("Ignoring binary message on websocket {}", url));
+            },
+            Ok(Message::Close(frame)) => {
+                //the reply to the close is sent for us, and the next read tells us the connection is closed
+                close_frame = frame;
+            },
+            Ok(_) => {}, //pings are answered for us
+            Err(error) if is_timeout(&error) => {
+                //messages that did not fit in the socket before are sent when we flush
+                let _ = socket.flush();
+            },
+            Err(WsError::ConnectionClosed) => {
+                let (code, reason) = match close_frame {
+                    Some(frame) => (u16::from(frame.code), frame.reason.to_string()),
+                    None => (CLOSE_CODE_NO_STATUS, String::new()),
+                };
+                let _ = events.send(WebSocketEvent::Close(code, reason, true));
+                return;
+            },
+            Err(error) => {
+                debug_log_warn(format!("Websocket {} failed: {}", url, error));
+                let _ = events.send(WebSocketEvent::Error);
+                let _ = events.send(WebSocketEvent::Close(CLOSE_CODE_ABNORMAL, String::new(), false));
+                return;
+            },
+        }
+    }
+}
+
+
+fn set_read_timeout(socket: &WebSocket<MaybeTlsStream<TcpStream>>, timeout: Duration) {
+    let tcp_stream = match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream,
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref(),
+        _ => { return; },
+    };
+    let _ = tcp_stream.set_read_timeout(Some(timeout));
+}
+
+
+fn is_timeout(error: &WsError) -> bool {
+    return match error {
+        WsError::Io(io_error) => matches!(io_error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+        _ => false,
+    };
+}
@@ -0,0 +1,960 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use image::DynamicImage;
+
+use crate::{MAX_IFRAME_DEPTH, SCRIPT_BUDGET_PER_FRAME};
+use crate::animation::AnimationState;
+use crate::browsing_history::BROWSING_HISTORY;
+use crate::debug;
+use crate::display_list::DisplayList;
+use crate::dom::{Document, DocumentReadyState, DomNodeId, NavigationAction};
+use crate::html_lexer;
+use crate::html_parser;
+use crate::jsonify;
+use crate::layout::{
+    self,
+    compute_layout,
+    FullLayout,
+    LayoutNodeContent,
+    LayoutNodeId,
+    rebuild_dirty_layout_childs,
+    SelectedContent,
+    update_dirty_layout,
+};
+use crate::network::{auth, CacheMode, LoadErrorKind};
+use crate::network::url::Url;
+use crate::page_cache::{self, CachedPage, PageCache};
+use crate::platform::{fonts::FontContext, KeyCode, Platform};
+use crate::profiler::{self, FramePhase, PROFILER};
+use crate::renderer::build_display_list;
+use crate::resource_loader::{self, LoadedResource, RequestSource, ResourcePriority, ResourceRequestJobTracker, ResourceThreadPool};
+use crate::script::js_events::DomEvent;
+use crate::script::js_interpreter::{JsInterpreter, JsLayoutContext};
+use crate::style::{self, media_query::MediaEnvironment};
+use crate::style::transition::TimingFunction;
+use crate::ui::{
+    self,
+    CONTENT_TOP_LEFT_X,
+    CONTENT_TOP_LEFT_Y,
+    FocusTarget,
+    UIState,
+    UiColors,
+};
+use crate::ui_components::{ContextMenuTarget, Scrollbar};
+
+
+const SMOOTH_SCROLL_DURATION: Duration = Duration::from_millis(200);
+const KEYBOARD_SCROLL_STEP: f32 = 40.0;
+const KEYBOARD_PAGE_SCROLL_OVERLAP: f32 = 40.0; //when scrolling by a page, a bit of the previous page stays visible, so it is clear where we were
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ScrollAnimation {
+    //the page moves to the target over a few frames, slowing down towards the end
+    from_y: f32,
+    target_y: f32,
+    start: Instant,
+}
+
+
+pub struct Page {
+    //this is the page shown in the window, with everything needed to load, lay out and script it, and to navigate to the next one
+    pub document: Rc<RefCell<Document>>,
+    pub interpreter: Box<JsInterpreter>,
+    pub full_layout: Rc<RefCell<FullLayout>>,
+    pub animation_state: AnimationState,
+    pub scroll_y: f32,
+    pub scroll_animation: Option<ScrollAnimation>,
+    pub ongoing_navigation: Option<NavigationAction>, //while this is set, the page we navigate to is loading, and the current one is still shown
+    navigation_job_tracker: Option<ResourceRequestJobTracker<LoadedResource>>,
+    navigation_cache_mode: CacheMode, //the page we navigate to loads its subresources in the same way as the page itself
+    favicon_job_tracker: Option<ResourceRequestJobTracker<Arc<DynamicImage>>>,
+    scheduled_refresh: Option<(Instant, NavigationAction)>,
+    page_cache: PageCache,
+    crash_message: Option<String>, //when this is set, the page panicked, and it is replaced by an error page at the start of the next frame
+}
+impl Page {
+    pub fn new(max_cached_pages: usize) -> Page {
+        return Page {
+            document: Rc::from(RefCell::from(Document::new_empty())),
+            interpreter: JsInterpreter::new(),
+            full_layout: Rc::new(RefCell::from(FullLayout::new_empty())),
+            animation_state: AnimationState::new(),
+            scroll_y: 0.0,
+            scroll_animation: None,
+            ongoing_navigation: None,
+            navigation_job_tracker: None,
+            navigation_cache_mode: CacheMode::Default,
+            favicon_job_tracker: None,
+            scheduled_refresh: None,
+            page_cache: PageCache::new(max_cached_pages),
+            crash_message: None,
+        };
+    }
+
+    pub fn navigate(&mut self, navigation_action: NavigationAction, platform: &mut Platform, ui_state: &mut UIState,
+                    resource_thread_pool: &mut ResourceThreadPool) {
+        //a fragment of the page we are on, or a page we still have from going back or forward, is shown without loading anything
+        if self.navigate_to_fragment(&navigation_action, platform, ui_state) {
+            return;
+        }
+        if self.restore_from_page_cache(ui_state, platform, resource_thread_pool) {
+            return;
+        }
+        self.start_loading(navigation_action, platform, ui_state, resource_thread_pool);
+    }
+
+    pub fn start_loading(&mut self, navigation_action: NavigationAction, platform: &Platform, ui_state: &mut UIState,
+                         resource_thread_pool: &mut ResourceThreadPool) {
+        self.start_loading_with_cache_mode(navigation_action, CacheMode::Default, platform, ui_state, resource_thread_pool);
+    }
+
+    fn start_loading_with_cache_mode(&mut self, navigation_action: NavigationAction, cache_mode: CacheMode, platform: &Platform, ui_state: &mut UIState,
+                                     resource_thread_pool: &mut ResourceThreadPool) {
+        //whatever the current page is still loading (like images and scripts) is not needed anymore, and neither is an earlier navigation
+        resource_thread_pool.cancel_all_jobs();
+
+        //settings are changed with the query of about:config, which only its own form may do, otherwise any page could link to a changed proxy
+        //the same goes for clearing the history on about:history, and the request log on about:network
+        let current_page_url = self.document.borrow().page_url.clone();
+        let navigation_action = match navigation_action {
+            NavigationAction::Get(mut url) if is_config_page(&url) && !is_same_about_page(&current_page_url, &url) => {
+                url.query = String::new();
+                NavigationAction::Get(url)
+            },
+            _ => navigation_action,
+        };
+
+        let tracker = match &navigation_action {
+            NavigationAction::None => {
+                panic!("Illegal state"); // we should not get in this method if we have nothing to navigate to...
+            },
+            NavigationAction::Get(url) => {
+                ui_state.addressbar.set_text(&platform.font_context, url.to_string());
+
+                if !ui_state.history.currently_navigating_from_history {
+                    ui::register_in_history(ui_state, url);
+                }
+
+                let source = if is_same_about_page(&current_page_url, url) { RequestSource::SameAboutPage } else { RequestSource::Navigation };
+                resource_loader::schedule_load_text(&url, ResourcePriority::Document, cache_mode, source, resource_thread_pool) //TODO: should this be a different thread pool, or rename it?
+            },
+            NavigationAction::SubmitForm(..) => {
+                panic!("Illegal state"); //form submissions should have been resolved (by sending the submit event) before navigating
+            },
+            NavigationAction::Post(post_data) => {
+                ui_state.addressbar.set_text(&platform.font_context, post_data.url.to_string());
+
+                if !ui_state.history.currently_navigating_from_history {
+                    //TODO: we don't record the postdata in the history, so going back to this page when it is no longer in the page cache requests it with a GET
+                    ui::register_in_history(ui_state, &post_data.url);
+                }
+
+                resource_loader::submit_post(&post_data, resource_thread_pool) //TODO: should this be a different thread pool, or rename it?
+            }
+        };
+
+        ui_state.currently_loading_page = true;
+        ui_state.loading_progress = None; //the progress of the page we navigate away from does not count anymore
+        ui_state.history.currently_navigating_from_history = false;
+        ui::update_history_buttons(ui_state);
+
+        self.navigation_job_tracker = Some(tracker);
+        self.navigation_cache_mode = cache_mode;
+        self.ongoing_navigation = Some(navigation_action);
+        self.interpreter.navigation_interrupted = true;
+    }
+
+    pub fn update_loading(&mut self, platform: &mut Platform, ui_state: &mut UIState, resource_thread_pool: &mut ResourceThreadPool) {
+        if self.crash_message.is_some() {
+            let url = self.document.borrow().page_url.clone();
+            let crash_message = self.crash_message.take().unwrap();
+            self.show_crash_page(&url, crash_message, platform, ui_state, resource_thread_pool);
+        }
+
+        //this checks (without waiting) if what we are loading for the page has arrived
+        if self.ongoing_navigation.is_some() {
+            let try_recv_result = self.navigation_job_tracker.as_ref().unwrap().receiver.try_recv();
+            let loaded_page = try_recv_result.ok();
+            let authentication_error = loaded_page.as_ref().and_then(|page| page.error.as_ref()).filter(|error| error.kind == LoadErrorKind::AuthenticationRequired);
+            let credentials = authentication_error.and_then(|error| {
+                let credentials = ui::ask_for_credentials(&platform, &error.url, error.authentication_realm.as_ref());
+                return credentials.map(|credentials| (Url::from(&error.url), credentials));
+            });
+
+            if credentials.is_some() {
+                //we load the same page again with the credentials, which should not be added to the history again
+                let (url, (username, password)) = credentials.unwrap();
+                auth::store_credentials(&url, &username, &password);
+                ui_state.history.currently_navigating_from_history = true;
+                let navigation_action = self.ongoing_navigation.take().unwrap();
+                self.start_loading(navigation_action, platform, ui_state, resource_thread_pool);
+            } else if loaded_page.is_some() {
+                let navigation_action = self.ongoing_navigation.take().unwrap();
+                let loaded_page = loaded_page.unwrap();
+                self.navigation_job_tracker = None;
+                let load_result = catch_crash(|| self.finish_load(&navigation_action, &loaded_page, platform, ui_state, resource_thread_pool));
+                if load_result.is_err() {
+                    self.show_crash_page(&loaded_page.url, load_result.unwrap_err(), platform, ui_state, resource_thread_pool);
+                }
+            }
+        }
+
+        if self.scheduled_refresh.is_some() && self.ongoing_navigation.is_none() && Instant::now() >= self.scheduled_refresh.as_ref().unwrap().0 {
+            //TODO: a refresh should replace the current entry in the history, instead of adding a new one
+            let navigation_action = self.scheduled_refresh.take().unwrap().1;
+            self.start_loading(navigation_action, platform, ui_state, resource_thread_pool);
+        }
+
+        if self.favicon_job_tracker.is_some() {
+            let try_recv_result = self.favicon_job_tracker.as_ref().unwrap().receiver.try_recv();
+            if try_recv_result.is_ok() {
+                let favicon = try_recv_result.unwrap();
+                //when loading fails we get a 1 by 1 fallback image, which we don't want to use as an icon
+                if favicon.width() > 1 && favicon.height() > 1 {
+                    platform.set_window_icon(&favicon);
+                }
+                self.favicon_job_tracker = None;
+            }
+        }
+    }
+
+    pub fn finish_load(&mut self, navigation_action: &NavigationAction, loaded_page: &LoadedResource, platform: &mut Platform, ui_state: &mut UIState,
+                       resource_thread_pool: &mut ResourceThreadPool) {
+        let requested_url = match navigation_action {
+            NavigationAction::None => {
+                panic!("Illegal state"); // we should not get in this method if we have nothing to navigate to...
+            },
+            NavigationAction::SubmitForm(..) => {
+                panic!("Illegal state"); //form submissions should have been resolved (by sending the submit event) before navigating
+            },
+            NavigationAction::Get(url) => { url },
+            NavigationAction::Post(post_data) => { &post_data.url },
+        };
+
+        if loaded_page.url != *requested_url {
+            //we were redirected, so we show (and remember in the history) where we ended up
+            ui_state.addressbar.set_text(&platform.font_context, loaded_page.url.to_string());
+            let history_position = ui_state.history.position;
+            ui_state.history.list[history_position].url = loaded_page.url.clone();
+        }
+
+        let media_environment = current_media_environment(ui_state, platform);
+        let (new_document, new_interpreter) = build_document(&loaded_page.page_html(), &loaded_page.url, media_environment, self.navigation_cache_mode, platform,
+                                                             resource_thread_pool);
+        let previous_document = std::mem::replace(&mut self.document, new_document); //the interpreter of the page keeps the document as well, so we replace it rather than its content
+        let previous_interpreter = std::mem::replace(&mut self.interpreter, new_interpreter); //this also stops the timers of the previous page
+        platform.set_window_title(self.document.borrow().get_title());
+
+        //about: pages are part of the browser (like about:history itself), so they are not in the browsing history
+        if loaded_page.error.is_none() && loaded_page.url.scheme != "about" {
+            BROWSING_HISTORY.lock().unwrap().record_visit(&loaded_page.url, self.document.borrow().get_title(), SystemTime::now());
+            update_visited_links(&mut self.document.borrow_mut()); //links to the page itself are visited now
+        }
+
+        let new_layout = profiler::measure(FramePhase::Style, || layout::build_full_layout(&mut self.document.borrow_mut(), &platform.font_context));
+        let previous_layout = self.full_layout.replace(new_layout);
+        let previous_page = CachedPage { document: previous_document, interpreter: previous_interpreter, full_layout: previous_layout, scroll_y: self.scroll_y };
+        self.cache_shown_page(ui_state, previous_page);
+        ui_state.history.shown_entry_id = ui_state.history.current_entry_id();
+        ui::clear_page_focus(ui_state);
+
+        self.scroll_y = 0.0;
+        self.scroll_animation = None;
+        ui_state.currently_loading_page = false;
+
+        profiler::measure(FramePhase::Layout, || {
+            compute_layout(&mut self.full_layout.borrow_mut(), &mut self.document.borrow_mut(), CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y,
+                           &platform.font_context, self.scroll_y, false, true);
+        });
+
+        //from now on scripts can measure the page, scripts running while the page is built see everything at size 0
+        self.interpreter.layout_context = Some(JsLayoutContext { full_layout: Rc::clone(&self.full_layout), font_context: Rc::clone(&platform.font_context),
+                                                                 current_scroll_y: self.scroll_y });
+
+        self.scroll_to_fragment(&loaded_page.url, ui_state, false);
+        self.animation_state.clear();
+
+        //a refresh only belongs to the page that asked for it, so any navigation replaces the one we had
+        self.scheduled_refresh = self.document.borrow().get_meta_refresh().map(|(delay, url)| (Instant::now() + delay, NavigationAction::Get(url)));
+
+        //TODO: when the new page has no icon, we still show the one of the previous page
+        let favicon_url = self.document.borrow().get_favicon_url().filter(|url| self.document.borrow().allows_subresource(url));
+        self.favicon_job_tracker = if favicon_url.is_some() {
+            Some(resource_loader::schedule_load_image(&favicon_url.unwrap(), self.navigation_cache_mode, resource_thread_pool))
+        } else {
+            None
+        };
+    }
+
+    fn show_crash_page(&mut self, url: &Url, crash_message: String, platform: &mut Platform, ui_state: &mut UIState,
+                       resource_thread_pool: &mut ResourceThreadPool) {
+        //the error page replaces the page that crashed, which might be half built, and the browser (with its history) keeps running
+        //TODO: going back and forward can still show the page that crashed from the page cache, it then crashes again
+        resource_thread_pool.cancel_all_jobs();
+        self.crash_message = None;
+        self.finish_load(&NavigationAction::Get(url.clone()), &LoadedResource::crashed(url, crash_message), platform, ui_state, resource_thread_pool);
+    }
+
+    pub fn reload(&mut self, cache_mode: CacheMode, platform: &Platform, ui_state: &mut UIState, resource_thread_pool: &mut ResourceThreadPool) {
+        //the page is loaded again, but it keeps its place in the history
+        //TODO: pages that were the result of a form POST are loaded again with a GET
+        let url = self.document.borrow().page_url.clone();
+        ui_state.history.currently_navigating_from_history = true;
+        self.start_loading_with_cache_mode(NavigationAction::Get(url), cache_mode, platform, ui_state, resource_thread_pool);
+    }
+
+    pub fn stop(&mut self, platform: &Platform, ui_state: &mut UIState, resource_thread_pool: &mut ResourceThreadPool) {
+        //this stops loading the page we navigate to, and whatever the page on screen is still loading (like images), as the stop button does
+        resource_thread_pool.cancel_all_jobs();
+        self.ongoing_navigation = None;
+        self.navigation_job_tracker = None;
+        self.scheduled_refresh = None;
+        self.interpreter.navigation_interrupted = false;
+        ui_state.currently_loading_page = false;
+        ui_state.loading_progress = None;
+
+        //the page on screen stays, so the address bar and the place in the history go back to it (the stopped page can still be reached with forward)
+        let shown_entry_id = ui_state.history.shown_entry_id;
+        if shown_entry_id.is_some() {
+            let shown_position = ui_state.history.list.iter().position(|entry| entry.id == shown_entry_id.unwrap());
+            if shown_position.is_some() {
+                ui_state.history.position = shown_position.unwrap();
+            }
+            ui_state.addressbar.set_text(&platform.font_context, self.document.borrow().page_url.to_string());
+        }
+        ui_state.history.currently_navigating_from_history = false;
+        ui::update_history_buttons(ui_state);
+    }
+
+    pub fn relayout(&mut self, ui_state: &UIState, platform: &Platform) {
+        //media queries can now give different results, and styles are resolved when building the layout tree, so we fully rebuild it
+        self.document.borrow_mut().style_context.set_media_environment(current_media_environment(ui_state, platform));
+        self.contain_crash(|page| {
+            let new_layout = profiler::measure(FramePhase::Style, || layout::build_full_layout(&mut page.document.borrow_mut(), &platform.font_context));
+            page.full_layout.replace(new_layout);
+            profiler::measure(FramePhase::Layout, || {
+                compute_layout(&mut page.full_layout.borrow_mut(), &mut page.document.borrow_mut(), CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y,
+                               &platform.font_context, page.scroll_y, false, true);
+            });
+        });
+    }
+
+    pub fn update(&mut self, platform: &mut Platform, resource_thread_pool: &mut ResourceThreadPool) {
+        self.contain_crash(|page| page.update_document(platform, resource_thread_pool));
+    }
+
+    fn update_document(&mut self, platform: &mut Platform, resource_thread_pool: &mut ResourceThreadPool) {
+        //timers run after the events are handled and before the layout is updated, so the changes they make are shown in this frame
+        self.interpreter.run_expired_timers(Instant::now());
+        self.interpreter.run_websocket_events();
+        update_document_readiness(&self.document, &mut self.interpreter);
+        self.document.borrow_mut().post_construct_new_nodes(&platform.font_context);
+
+        let document_has_dirty_nodes = profiler::measure(FramePhase::Style, || self.document.borrow_mut().update_all_dom_nodes(resource_thread_pool));
+
+        if document_has_dirty_nodes {
+            update_dirty_layout(&self.full_layout, &mut self.document.borrow_mut(), &platform.font_context, self.scroll_y);
+        }
+
+        let mut full_layout = self.full_layout.borrow_mut();
+        let root_node = full_layout.root_node;
+        update_iframes(&mut full_layout, root_node, platform, resource_thread_pool, 0);
+        drop(full_layout);
+
+        //we render every frame anyway, so running transitions only need their values updated here
+        self.animation_state.update(&self.full_layout.borrow(), Instant::now());
+    }
+
+    pub fn run_scripts(&mut self, font_context: &FontContext) {
+        //(while we navigate away they don't run anymore, they are dropped with the page once the new one is loaded)
+        if self.ongoing_navigation.is_none() {
+            self.contain_crash(|page| {
+                profiler::measure(FramePhase::Scripts, || {
+                    run_document_scripts(&page.document, &mut page.interpreter, SCRIPT_BUDGET_PER_FRAME, font_context);
+
+                    let mut full_layout = page.full_layout.borrow_mut();
+                    let root_node = full_layout.root_node;
+                    run_iframe_scripts(&mut full_layout, root_node, SCRIPT_BUDGET_PER_FRAME, font_context);
+                });
+            });
+        }
+    }
+
+    pub fn build_display_list(&self, platform: &Platform, animation_tick: u32, focused_dom_node: Option<DomNodeId>,
+                              layout_overlay: bool) -> DisplayList {
+        return build_display_list(&self.full_layout.borrow(), &self.document.borrow(), &platform.font_context, &self.animation_state, animation_tick,
+                                  UiColors::for_color_scheme(platform.color_scheme.get()), focused_dom_node, layout_overlay);
+    }
+
+    pub fn update_scroll(&mut self, main_scrollbar: &mut Scrollbar, now: Instant) {
+        self.update_scroll_animation(main_scrollbar, now);
+        self.scroll_y = main_scrollbar.update_content_size(self.full_layout.borrow().page_height(), self.scroll_y);
+        if self.interpreter.layout_context.is_some() {
+            self.interpreter.layout_context.as_mut().unwrap().current_scroll_y = self.scroll_y;
+        }
+    }
+
+    pub fn smooth_scroll_to(&mut self, main_scrollbar: &Scrollbar, scroll_y: f32) {
+        let target_y = main_scrollbar.clamp_scroll_position(scroll_y);
+        self.scroll_animation = Some(ScrollAnimation { from_y: self.scroll_y, target_y, start: Instant::now() });
+    }
+
+    pub fn smooth_scroll_by(&mut self, main_scrollbar: &Scrollbar, distance: f32) {
+        //while we are still moving, the distance is added to where we were going, so quickly turning the wheel makes the page go further and faster
+        let current_target_y = if self.scroll_animation.is_some() { self.scroll_animation.as_ref().unwrap().target_y } else { self.scroll_y };
+        self.smooth_scroll_to(main_scrollbar, current_target_y + distance);
+    }
+
+    pub fn scroll_for_key(&mut self, main_scrollbar: &Scrollbar, key_code: KeyCode, shift_pressed: bool) {
+        let page_distance = main_scrollbar.content_visible_height - KEYBOARD_PAGE_SCROLL_OVERLAP;
+        match key_code {
+            KeyCode::DOWN => { self.smooth_scroll_by(main_scrollbar, KEYBOARD_SCROLL_STEP); },
+            KeyCode::UP => { self.smooth_scroll_by(main_scrollbar, -KEYBOARD_SCROLL_STEP); },
+            KeyCode::PAGEDOWN => { self.smooth_scroll_by(main_scrollbar, page_distance); },
+            KeyCode::PAGEUP => { self.smooth_scroll_by(main_scrollbar, -page_distance); },
+            KeyCode::SPACE => { self.smooth_scroll_by(main_scrollbar, if shift_pressed { -page_distance } else { page_distance }); },
+            KeyCode::HOME => { self.smooth_scroll_to(main_scrollbar, 0.0); },
+            KeyCode::END => { self.smooth_scroll_to(main_scrollbar, main_scrollbar.content_size); }, //this is clamped to the last position we can scroll to
+            _ => {},
+        }
+    }
+
+    fn update_scroll_animation(&mut self, main_scrollbar: &mut Scrollbar, now: Instant) {
+        if self.scroll_animation.is_none() {
+            return;
+        }
+        let scroll_animation = self.scroll_animation.as_ref().unwrap();
+
+        let progress = now.duration_since(scroll_animation.start).as_secs_f32() / SMOOTH_SCROLL_DURATION.as_secs_f32();
+        let eased_progress = TimingFunction::ease_out().apply(progress);
+        let new_scroll_y = scroll_animation.from_y + ((scroll_animation.target_y - scroll_animation.from_y) * eased_progress);
+
+        self.scroll_y = main_scrollbar.update_scroll(new_scroll_y);
+        if progress >= 1.0 {
+            self.scroll_animation = None;
+        }
+    }
+
+    pub fn handle_left_click(&mut self, ui_state: &mut UIState, x: f32, y: f32, platform: &Platform) -> NavigationAction {
+        let possible_url = ui::handle_possible_ui_click(ui_state, x, y);
+        if possible_url.is_some() {
+            return NavigationAction::Get(possible_url.unwrap());
+        }
+
+        //the layout is not borrowed while the event is dispatched, since scripts might update it (when they measure elements)
+        let possible_dom_node = self.find_dom_node_at_position(x, y);
+        if possible_dom_node.is_none() {
+            return NavigationAction::None;
+        }
+        let clicked_node = possible_dom_node.unwrap();
+
+        //when a script cancels the click, the default action (like following a link or submitting a form) does not happen
+        let event_target = find_event_target(clicked_node, &self.document.borrow());
+        if event_target.is_some() && !self.dispatch_event(event_target.unwrap(), DomEvent::mouse("click", x - CONTENT_TOP_LEFT_X, y - CONTENT_TOP_LEFT_Y)) {
+            return NavigationAction::None;
+        }
+
+        //the clicked node might have been removed by the script handling the event
+        if !self.document.borrow().nodes.contains(clicked_node) {
+            return NavigationAction::None;
+        }
+        let navigation_action = self.document.borrow_mut().click(clicked_node, &platform.font_context);
+        return self.resolve_form_submission(navigation_action);
+    }
+
+    pub fn focus_order(&self) -> Vec<DomNodeId> {
+        //this is the order of the content on the page (as for selecting text), with each link in there once, even when it has more content
+        let document = self.document.borrow();
+        let full_layout = self.full_layout.borrow();
+        let mut focus_order: Vec<DomNodeId> = Vec::new();
+        for layout_node_id in &full_layout.nodes_in_selection_order {
+            let layout_node = &full_layout.nodes[*layout_node_id];
+            if layout_node.from_dom_node.is_none() || !layout_node.visible {
+                continue;
+            }
+            let dom_node_id = layout_node.from_dom_node.unwrap();
+
+            let focusable_dom_node = if document.nodes[dom_node_id].page_component.is_some() {
+                Some(dom_node_id).filter(|dom_node_id| document.nodes[*dom_node_id].get_attribute_value("disabled").is_none())
+            } else {
+                document.find_parent_with_name(dom_node_id, "a").filter(|link_id| style::is_link(&document.nodes[*link_id]))
+            };
+            if focusable_dom_node.is_some() && !focus_order.contains(focusable_dom_node.as_ref().unwrap()) {
+                focus_order.push(focusable_dom_node.unwrap());
+            }
+        }
+        return focus_order;
+    }
+
+    pub fn scroll_into_view(&mut self, dom_node_id: DomNodeId, main_scrollbar: &Scrollbar) {
+        //we only scroll when (part of) the element is not on screen, and then just far enough to show it
+        let mut node_ids = HashSet::new();
+        self.document.borrow().collect_node_ids(dom_node_id, &mut node_ids);
+        let full_layout = self.full_layout.borrow();
+        let bounding_box = full_layout.find_bounding_box_of_dom_nodes(full_layout.root_node, &node_ids);
+        drop(full_layout);
+        if bounding_box.is_none() {
+            return;
+        }
+        let bounding_box = bounding_box.unwrap();
+
+        let top_scroll_y = bounding_box.y - CONTENT_TOP_LEFT_Y;
+        let bottom_scroll_y = bounding_box.y + bounding_box.height - CONTENT_TOP_LEFT_Y - main_scrollbar.content_visible_height;
+        if top_scroll_y < self.scroll_y {
+            self.smooth_scroll_to(main_scrollbar, top_scroll_y);
+        } else if bottom_scroll_y > self.scroll_y {
+            self.smooth_scroll_to(main_scrollbar, f32::min(bottom_scroll_y, top_scroll_y));
+        }
+    }
+
+    pub fn activate(&mut self, dom_node_id: DomNodeId, platform: &Platform) -> NavigationAction {
+        //this is what enter does on a focused link or button, which is the same as clicking it
+        if !self.dispatch_event(dom_node_id, DomEvent::new("click", true, true)) || !self.document.borrow().nodes.contains(dom_node_id) {
+            return NavigationAction::None;
+        }
+
+        let is_link = style::is_link(&self.document.borrow().nodes[dom_node_id]);
+        let navigation_action = if is_link {
+            let document = self.document.borrow();
+            let href = document.nodes[dom_node_id].get_attribute_value("href").unwrap();
+            NavigationAction::Get(Url::from_base_url(&href, Some(&document.base_url)))
+        } else {
+            self.document.borrow_mut().click(dom_node_id, &platform.font_context)
+        };
+        return self.resolve_form_submission(navigation_action);
+    }
+
+    pub fn context_menu_target(&self, x: f32, y: f32) -> ContextMenuTarget {
+        let possible_dom_node = self.find_dom_node_at_position(x, y);
+        if possible_dom_node.is_none() {
+            return ContextMenuTarget { dom_node: None, link_url: None, image: None };
+        }
+        let dom_node_id = possible_dom_node.unwrap();
+        let document = self.document.borrow();
+        let link_url = find_link_url(dom_node_id, &document);
+
+        let dom_node = &document.nodes[dom_node_id];
+        let image = dom_node.image.clone().map(|image| {
+            let image_url = dom_node.get_attribute_value("src").map(|src| Url::from_base_url(&src, Some(&document.base_url)));
+            return (image, image_url);
+        });
+
+        return ContextMenuTarget { dom_node: Some(dom_node_id), link_url, image };
+    }
+
+    pub fn link_at_position(&self, x: f32, y: f32) -> Option<Url> {
+        let possible_dom_node = self.find_dom_node_at_position(x, y);
+        return possible_dom_node.and_then(|dom_node_id| find_link_url(dom_node_id, &self.document.borrow()));
+    }
+
+    pub fn find_dom_node_at_position(&self, x: f32, y: f32) -> Option<DomNodeId> {
+        let full_layout = self.full_layout.borrow();
+        return full_layout.find_dom_node_at_position(full_layout.root_node, x, y + self.scroll_y);
+    }
+
+    pub fn describe_layout_node_at_position(&self, x: f32, y: f32) -> Option<String> {
+        let full_layout = self.full_layout.borrow();
+        let layout_node_id = full_layout.find_layout_node_at_position(full_layout.root_node, x, y + self.scroll_y);
+        return layout_node_id.map(|layout_node_id| debug::describe_layout_node(&full_layout.nodes[layout_node_id], &self.document.borrow()));
+    }
+
+    pub fn to_json(&self) -> String {
+        return jsonify::page_to_json(&self.document.borrow(), &self.full_layout.borrow());
+    }
+
+    pub fn is_on_selection(&self, x: f32, y: f32) -> bool {
+        let full_layout = self.full_layout.borrow();
+        return full_layout.is_inside_selection(full_layout.root_node, x, y + self.scroll_y);
+    }
+
+    pub fn selected_content(&self) -> SelectedContent {
+        let document = self.document.borrow();
+        return self.full_layout.borrow().get_selected_content(&document.nodes, &document.base_url);
+    }
+
+    pub fn selected_text(&self) -> String {
+        return self.selected_content().text;
+    }
+
+    pub fn dispatch_mouse_event(&mut self, event_type: &str, x: f32, y: f32) {
+        let possible_dom_node = self.find_dom_node_at_position(x, y);
+        if possible_dom_node.is_none() {
+            return;
+        }
+
+        //TODO: cancelling these should for example prevent focussing the element and starting a selection
+        let event_target = find_event_target(possible_dom_node.unwrap(), &self.document.borrow());
+        if event_target.is_some() {
+            self.dispatch_event(event_target.unwrap(), DomEvent::mouse(event_type, x - CONTENT_TOP_LEFT_X, y - CONTENT_TOP_LEFT_Y));
+        }
+    }
+
+    pub fn dispatch_keyboard_event(&mut self, event_type: &str, key: String, ui_state: &UIState) -> bool {
+        //keyboard events go to the focused element, or to the body when nothing on the page is focused
+        let event_target = match ui_state.focus_target {
+            FocusTarget::Component(ref component) => Some(self.document.borrow().find_node_for_component(&component.borrow())),
+            FocusTarget::Link(link) => Some(link),
+            FocusTarget::None | FocusTarget::MainContent => self.document.borrow().find_body(),
+            FocusTarget::AddressBar | FocusTarget::ScrollBlock => None,
+        };
+
+        if event_target.is_none() {
+            return true;
+        }
+        return self.dispatch_event(event_target.unwrap(), DomEvent::key(event_type, key));
+    }
+
+    pub fn resolve_form_submission(&mut self, navigation_action: NavigationAction) -> NavigationAction {
+        //before a form is submitted, it gets a submit event, which scripts can cancel
+        if let NavigationAction::SubmitForm(form_id, form_navigation_action) = navigation_action {
+            let form_in_document = self.document.borrow().nodes.contains(form_id);
+            if form_in_document && !self.dispatch_event(form_id, DomEvent::new("submit", true, true)) {
+                return NavigationAction::None;
+            }
+            return *form_navigation_action;
+        }
+        return navigation_action;
+    }
+
+    fn dispatch_event(&mut self, target: DomNodeId, event: DomEvent) -> bool {
+        //a script that crashed did not get to do what it wanted, so we also don't do the default action (the page is replaced anyway)
+        return self.contain_crash(|page| page.interpreter.dispatch_event(target, event)).unwrap_or(false);
+    }
+
+    fn contain_crash<T, F: FnOnce(&mut Page) -> T>(&mut self, work: F) -> Option<T> {
+        //after a crash we don't touch the page anymore, until the error page replaces it
+        if self.crash_message.is_some() {
+            return None;
+        }
+        return match catch_crash(|| work(self)) {
+            Ok(result) => Some(result),
+            Err(crash_message) => {
+                self.crash_message = Some(crash_message);
+                None
+            },
+        };
+    }
+
+    fn cache_shown_page(&mut self, ui_state: &UIState, page: CachedPage) {
+        //the page we navigate away from is kept (when it can be), so going back to it shows it as it was, without loading it and running its scripts again
+        if ui_state.history.shown_entry_id.is_some() && page_cache::can_be_cached(&page.document.borrow(), &page.interpreter) {
+            self.page_cache.store(ui_state.history.shown_entry_id.unwrap(), page);
+        }
+    }
+
+    fn restore_from_page_cache(&mut self, ui_state: &mut UIState, platform: &mut Platform, resource_thread_pool: &mut ResourceThreadPool) -> bool {
+        //going back or forward to a page we still have shows it right away, returns false when it needs to be loaded instead
+        let entry_id = ui_state.history.current_entry_id();
+        if !ui_state.history.currently_navigating_from_history || entry_id.is_none() {
+            return false;
+        }
+        let cached_page = self.page_cache.take(entry_id.unwrap());
+        if cached_page.is_none() {
+            return false;
+        }
+        let cached_page = cached_page.unwrap();
+
+        //a page that was still loading is not needed anymore
+        resource_thread_pool.cancel_all_jobs();
+        self.ongoing_navigation = None;
+        self.navigation_job_tracker = None;
+        self.scheduled_refresh = None;
+        self.animation_state.clear();
+
+        let previous_document = std::mem::replace(&mut self.document, cached_page.document);
+        let previous_interpreter = std::mem::replace(&mut self.interpreter, cached_page.interpreter);
+        self.interpreter.navigation_interrupted = false; //we navigated away from it before it was cached
+        let previous_layout = self.full_layout.replace(cached_page.full_layout);
+        let previous_page = CachedPage { document: previous_document, interpreter: previous_interpreter, full_layout: previous_layout, scroll_y: self.scroll_y };
+        self.cache_shown_page(ui_state, previous_page);
+        ui_state.history.shown_entry_id = entry_id;
+        ui::clear_page_focus(ui_state);
+
+        ui_state.addressbar.set_text(&platform.font_context, self.document.borrow().page_url.to_string());
+        ui_state.history.currently_navigating_from_history = false;
+        ui_state.currently_loading_page = false;
+        ui::update_history_buttons(ui_state);
+        platform.set_window_title(self.document.borrow().get_title());
+
+        //the page was laid out for the window size and color scheme it had when we left it, and links on it might have been visited since
+        let laid_out_environment = self.document.borrow().style_context.media_environment();
+        let current_environment = current_media_environment(ui_state, platform);
+        let visited_links_changed = update_visited_links(&mut self.document.borrow_mut());
+        if laid_out_environment.width != current_environment.width || laid_out_environment.height != current_environment.height ||
+           laid_out_environment.color_scheme != current_environment.color_scheme || visited_links_changed {
+            self.relayout(ui_state, platform);
+        }
+        self.scroll_y = ui_state.main_scrollbar.update_content_size(self.full_layout.borrow().page_height(), cached_page.scroll_y);
+        self.scroll_animation = None;
+        return true;
+    }
+
+    fn navigate_to_fragment(&mut self, navigation_action: &NavigationAction, platform: &Platform, ui_state: &mut UIState) -> bool {
+        //navigating to another fragment of the page we are on only scrolls, the page is not loaded again
+        let url = match navigation_action {
+            NavigationAction::Get(url) => { url },
+            _ => { return false; },
+        };
+        if url.fragment.is_empty() || ui_state.currently_loading_page || !url.is_same_document(&self.document.borrow().page_url) {
+            return false;
+        }
+
+        ui_state.addressbar.set_text(&platform.font_context, url.to_string());
+        if !ui_state.history.currently_navigating_from_history {
+            ui::register_in_history(ui_state, url);
+        }
+        ui_state.history.currently_navigating_from_history = false;
+        ui::update_history_buttons(ui_state);
+
+        ui_state.history.shown_entry_id = ui_state.history.current_entry_id();
+
+        self.document.borrow_mut().page_url = url.clone();
+        self.scroll_to_fragment(url, ui_state, true);
+        return true;
+    }
+
+    fn scroll_to_fragment(&mut self, url: &Url, ui_state: &mut UIState, smooth: bool) {
+        //on the page we are on, we scroll to the fragment smoothly, so it is clear where on the page we went
+        if url.fragment.is_empty() {
+            return;
+        }
+        let target_node = self.document.borrow().find_fragment_target(&url.fragment);
+        if target_node.is_none() {
+            return;
+        }
+
+        let mut node_ids = HashSet::new();
+        self.document.borrow().collect_node_ids(target_node.unwrap(), &mut node_ids);
+        let full_layout = self.full_layout.borrow();
+        let bounding_box = full_layout.find_bounding_box_of_dom_nodes(full_layout.root_node, &node_ids);
+        drop(full_layout);
+        if bounding_box.is_some() {
+            let target_scroll_y = bounding_box.unwrap().y - CONTENT_TOP_LEFT_Y;
+            let page_height = self.full_layout.borrow().page_height();
+
+            //the scrollbar might not know the size of this page yet, and we need that to clamp the position
+            if smooth {
+                self.scroll_y = ui_state.main_scrollbar.update_content_size(page_height, self.scroll_y);
+                self.smooth_scroll_to(&ui_state.main_scrollbar, target_scroll_y);
+            } else {
+                self.scroll_y = ui_state.main_scrollbar.update_content_size(page_height, target_scroll_y);
+                self.scroll_animation = None;
+            }
+        }
+    }
+}
+
+
+fn current_media_environment(ui_state: &UIState, platform: &Platform) -> MediaEnvironment {
+    //media queries are about the viewport, which is the part of the window below the browser ui, and next to the scrollbar
+    let window_dimensions = ui_state.window_dimensions;
+    return MediaEnvironment { width: window_dimensions.content_width(), height: window_dimensions.content_height(), color_scheme: platform.color_scheme.get() };
+}
+
+
+fn update_visited_links(document: &mut Document) -> bool {
+    //links are styled with :visited when they go to a page in the browsing history, this returns if that changed for any of them
+    //TODO: links that scripts add later are only checked the next time we do this
+    let browsing_history = BROWSING_HISTORY.lock().unwrap();
+    let mut visited_links = HashSet::new();
+    for (_, dom_node) in document.nodes.iter() {
+        if !style::is_link(dom_node) {
+            continue;
+        }
+        let href = dom_node.get_attribute_value("href").unwrap();
+        if browsing_history.find(&Url::from_base_url(&href, Some(&document.base_url))).is_some() {
+            visited_links.insert(href);
+        }
+    }
+
+    let visited_links_changed = visited_links != *document.style_context.visited_links();
+    document.style_context.set_visited_links(visited_links);
+    return visited_links_changed;
+}
+
+
+fn find_link_url(dom_node_id: DomNodeId, document: &Document) -> Option<Url> {
+    //links are found in the same way as when clicking, so this is the link we would follow
+    return document.find_parent_with_name(dom_node_id, "a")
+                   .and_then(|link_node_id| document.nodes[link_node_id].get_attribute_value("href"))
+                   .map(|href| Url::from_base_url(&href, Some(&document.base_url)));
+}
+
+
+fn is_config_page(url: &Url) -> bool {
+    return url.scheme == "about" && url.path.len() == 1 && url.path[0] == "config";
+}
+
+
+fn is_same_about_page(page_url: &Url, url: &Url) -> bool {
+    return page_url.scheme == "about" && url.scheme == "about" && page_url.path == url.path;
+}
+
+
+pub fn catch_crash<T, F: FnOnce() -> T>(work: F) -> Result<T, String> {
+    //pages can get us in states we don't handle yet (like javascript or css we can't parse), which panic. We show an error page for those
+    //instead of closing the browser. The panic is still printed by the default panic hook, with where it happened.
+    let running_measurements = PROFILER.lock().unwrap().running_measurements();
+    let result = panic::catch_unwind(AssertUnwindSafe(work));
+    if result.is_err() {
+        PROFILER.lock().unwrap().abandon_measurements(running_measurements);
+    }
+
+    return result.map_err(|payload| {
+        if payload.is::<&str>() {
+            return String::from(*payload.downcast_ref::<&str>().unwrap());
+        }
+        if payload.is::<String>() {
+            return payload.downcast_ref::<String>().unwrap().clone();
+        }
+        return String::from("unknown error");
+    });
+}
+
+
+fn find_event_target(dom_node_id: DomNodeId, document: &Document) -> Option<DomNodeId> {
+    //events go to elements, so for text we use the element the text is in
+    if document.nodes[dom_node_id].text.is_some() {
+        return document.get_parent(dom_node_id);
+    }
+    return Some(dom_node_id);
+}
+
+
+fn build_document(page_content: &String, url: &Url, media_environment: MediaEnvironment, cache_mode: CacheMode, platform: &mut Platform,
+                  resource_thread_pool: &mut ResourceThreadPool) -> (Rc<RefCell<Document>>, Box<JsInterpreter>) {
+    let lex_result = html_lexer::lex_html(&page_content);
+    let document = Rc::from(RefCell::from(html_parser::parse(lex_result, &url)));
+    document.borrow_mut().style_context.set_media_environment(media_environment);
+    document.borrow_mut().cache_mode = cache_mode;
+    update_visited_links(&mut document.borrow_mut());
+
+    let document_node = document.borrow().document_node;
+    document.borrow_mut().post_construct(document_node, &platform.font_context);
+    document.borrow_mut().update_all_dom_nodes(resource_thread_pool);
+
+    //the scripts only run from the main loop, a few per frame, so the page is shown (and the browser responds) while they run
+    let mut interpreter = JsInterpreter::new();
+    interpreter.screen_dimensions = platform.get_screen_dimensions();
+    interpreter.dialog_handler = Some(ui::make_dialog_handler(platform));
+    interpreter.queue_scripts_in_document(&document);
+
+    return (document, interpreter);
+}
+
+
+fn run_document_scripts(document: &Rc<RefCell<Document>>, interpreter: &mut JsInterpreter, budget: Duration, font_context: &FontContext) {
+    if document.borrow().ready_state != DocumentReadyState::Loading || !interpreter.run_queued_scripts(budget) {
+        return;
+    }
+
+    //all scripts run after parsing for now (like deferred scripts do), so the document is interactive once they are done
+    document.borrow_mut().ready_state = DocumentReadyState::Interactive;
+    let document_node = document.borrow().document_node;
+    interpreter.dispatch_event(document_node, DomEvent::new("DOMContentLoaded", true, false));
+
+    document.borrow_mut().post_construct_new_nodes(font_context);
+}
+
+
+fn update_document_readiness(document: &Rc<RefCell<Document>>, interpreter: &mut JsInterpreter) {
+    //the document is complete (and the window gets the load event) once the images and styles it needs are loaded
+    if document.borrow().ready_state != DocumentReadyState::Interactive || document.borrow().has_pending_resources() {
+        return;
+    }
+
+    document.borrow_mut().ready_state = DocumentReadyState::Complete;
+    interpreter.dispatch_window_event(DomEvent::new("load", false, false));
+}
+
+
+fn update_iframes(full_layout: &mut FullLayout, layout_node_id: LayoutNodeId, platform: &mut Platform, resource_thread_pool: &mut ResourceThreadPool,
+                  depth: usize) {
+    //this loads the documents of frames, and keeps them up to date, in the same way as we do for the main document in the main loop
+    if let LayoutNodeContent::IframeLayoutNode(iframe_node) = &mut full_layout.nodes[layout_node_id].content {
+        if iframe_node.document.is_none() {
+            if iframe_node.url.is_none() || depth >= MAX_IFRAME_DEPTH {
+                //we limit the depth, since a page could (indirectly) contain a frame with itself
+                return;
+            }
+
+            if iframe_node.job_tracker.is_none() {
+                iframe_node.job_tracker = Some(resource_loader::schedule_load_text(iframe_node.url.as_ref().unwrap(), ResourcePriority::Document, CacheMode::Default,
+                                                                                   RequestSource::Subresource, resource_thread_pool));
+                return;
+            }
+
+            let try_recv_result = iframe_node.job_tracker.as_ref().unwrap().receiver.try_recv();
+            if try_recv_result.is_ok() {
+                let content_rect = iframe_node.content_rect();
+                let media_environment = MediaEnvironment { width: content_rect.width, height: content_rect.height, color_scheme: platform.color_scheme.get() };
+                let frame_page = try_recv_result.unwrap();
+                let (document, interpreter) = build_document(&frame_page.page_html(), &frame_page.url, media_environment, CacheMode::Default, platform,
+                                                             resource_thread_pool);
+
+                //the scripts of the frame run from the main loop (see run_iframe_scripts), like the ones of the page
+                //TODO: scripts in the frame can't measure elements yet, they don't get a layout context
+                iframe_node.full_layout = Some(layout::build_full_layout(&mut document.borrow_mut(), &platform.font_context));
+                iframe_node.document = Some(document);
+                iframe_node.interpreter = Some(interpreter);
+                iframe_node.job_tracker = None;
+                iframe_node.compute_content_layout(&platform.font_context, true);
+            }
+            return;
+        }
+
+        let document = Rc::clone(iframe_node.document.as_ref().unwrap());
+        let interpreter = iframe_node.interpreter.as_mut().unwrap();
+        interpreter.run_expired_timers(Instant::now());
+        interpreter.run_websocket_events();
+        update_document_readiness(&document, interpreter);
+        document.borrow_mut().post_construct_new_nodes(&platform.font_context);
+
+        let nested_full_layout = iframe_node.full_layout.as_mut().unwrap();
+        let nested_root_node = nested_full_layout.root_node;
+
+        let document_has_dirty_nodes = document.borrow_mut().update_all_dom_nodes(resource_thread_pool);
+        if document_has_dirty_nodes {
+            rebuild_dirty_layout_childs(nested_full_layout, nested_root_node, &mut document.borrow_mut(), &platform.font_context);
+            iframe_node.compute_content_layout(&platform.font_context, false);
+        }
+
+        update_iframes(iframe_node.full_layout.as_mut().unwrap(), nested_root_node, platform, resource_thread_pool, depth + 1);
+        return;
+    }
+
+    if full_layout.nodes[layout_node_id].children.is_some() {
+        for child_id in full_layout.nodes[layout_node_id].children.clone().unwrap() {
+            update_iframes(full_layout, child_id, platform, resource_thread_pool, depth);
+        }
+    }
+}
+
+
+fn run_iframe_scripts(full_layout: &mut FullLayout, layout_node_id: LayoutNodeId, budget: Duration, font_context: &FontContext) {
+    //every frame gets the same budget as the page itself, so their scripts run a few at a time as well
+    if let LayoutNodeContent::IframeLayoutNode(iframe_node) = &mut full_layout.nodes[layout_node_id].content {
+        if iframe_node.document.is_none() {
+            return;
+        }
+
+        run_document_scripts(iframe_node.document.as_ref().unwrap(), iframe_node.interpreter.as_mut().unwrap(), budget, font_context);
+
+        let nested_full_layout = iframe_node.full_layout.as_mut().unwrap();
+        let nested_root_node = nested_full_layout.root_node;
+        run_iframe_scripts(nested_full_layout, nested_root_node, budget, font_context);
+        return;
+    }
+
+    if full_layout.nodes[layout_node_id].children.is_some() {
+        for child_id in full_layout.nodes[layout_node_id].children.clone().unwrap() {
+            run_iframe_scripts(full_layout, child_id, budget, font_context);
+        }
+    }
+}
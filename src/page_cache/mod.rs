@@ -0,0 +1,54 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::dom::{Document, DocumentReadyState};
+use crate::layout::FullLayout;
+use crate::script::js_interpreter::JsInterpreter;
+
+#[cfg(test)] mod tests;
+
+
+pub struct CachedPage {
+    //this is everything we need to show the page again as the user left it, including the state of its scripts
+    pub document: Rc<RefCell<Document>>,
+    pub interpreter: Box<JsInterpreter>,
+    pub full_layout: FullLayout,
+    pub scroll_y: f32,
+}
+
+
+pub struct PageCache {
+    //pages we navigated away from, by the id of their history entry, so going back or forward to them does not load them again
+    pages: VecDeque<(usize, CachedPage)>, //the page we left last is at the back
+    max_pages: usize,
+}
+impl PageCache {
+    pub fn new(max_pages: usize) -> PageCache {
+        return PageCache { pages: VecDeque::new(), max_pages };
+    }
+
+    pub fn store(&mut self, history_entry_id: usize, page: CachedPage) {
+        //pages for entries that are no longer in the history are not removed here, they are the first to go when the cache is full
+        self.pages.retain(|(entry_id, _)| *entry_id != history_entry_id);
+        self.pages.push_back((history_entry_id, page));
+        while self.pages.len() > self.max_pages {
+            self.pages.pop_front();
+        }
+    }
+
+    pub fn take(&mut self, history_entry_id: usize) -> Option<CachedPage> {
+        let idx = self.pages.iter().position(|(entry_id, _)| *entry_id == history_entry_id);
+        if idx.is_none() {
+            return None;
+        }
+        return self.pages.remove(idx.unwrap()).map(|(_, page)| page);
+    }
+}
+
+
+pub fn can_be_cached(document: &Document, interpreter: &JsInterpreter) -> bool {
+    //resources that are still loading are cancelled when we navigate away, so the page would never finish loading when it is shown again
+    //open websockets would keep running in the background, so those pages are not kept either (like other browsers do)
+    return document.ready_state == DocumentReadyState::Complete && interpreter.websockets.is_empty();
+}
@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::dom::{Document, DocumentReadyState};
+use crate::layout::FullLayout;
+use crate::page_cache::{can_be_cached, CachedPage, PageCache};
+use crate::script::js_interpreter::JsInterpreter;
+
+
+fn build_cached_page(scroll_y: f32) -> CachedPage {
+    let mut document = Document::new_empty();
+    document.ready_state = DocumentReadyState::Complete;
+    return CachedPage { document: Rc::new(RefCell::new(document)), interpreter: JsInterpreter::new(), full_layout: FullLayout::new_empty(),
+                        scroll_y };
+}
+
+
+#[test]
+fn test_page_cache_keeps_the_pages_left_most_recently() {
+    let mut page_cache = PageCache::new(2);
+    page_cache.store(1, build_cached_page(0.0));
+    page_cache.store(2, build_cached_page(0.0));
+    page_cache.store(3, build_cached_page(0.0));
+
+    //the page we left first is the first to go when the cache is full
+    assert!(page_cache.take(1).is_none());
+    assert!(page_cache.take(2).is_some());
+    assert!(page_cache.take(3).is_some());
+
+    //a page is only shown from the cache once, when we leave it again it is stored again
+    assert!(page_cache.take(3).is_none());
+
+    //storing a page for an entry we already have replaces it, and counts as leaving it most recently
+    page_cache.store(4, build_cached_page(0.0));
+    page_cache.store(5, build_cached_page(0.0));
+    page_cache.store(4, build_cached_page(0.0));
+    page_cache.store(6, build_cached_page(0.0));
+    assert!(page_cache.take(5).is_none());
+    assert!(page_cache.take(4).is_some());
+    assert!(page_cache.take(6).is_some());
+}
+
+
+#[test]
+fn test_cached_page_keeps_its_state() {
+    let mut page_cache = PageCache::new(3);
+    let page = build_cached_page(350.0);
+    let document = Rc::clone(&page.document);
+    page_cache.store(1, page);
+    page_cache.store(2, build_cached_page(20.0));
+
+    let restored_page = page_cache.take(1).unwrap();
+    assert_eq!(restored_page.scroll_y, 350.0);
+    assert!(Rc::ptr_eq(&restored_page.document, &document));
+    assert_eq!(page_cache.take(2).unwrap().scroll_y, 20.0);
+}
+
+
+#[test]
+fn test_only_loaded_pages_can_be_cached() {
+    let page = build_cached_page(0.0);
+    assert!(can_be_cached(&page.document.borrow(), &page.interpreter));
+
+    page.document.borrow_mut().ready_state = DocumentReadyState::Loading;
+    assert!(!can_be_cached(&page.document.borrow(), &page.interpreter));
+}
@@ -1,15 +1,35 @@
+use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
 
 use rusttype::{
     point,
     Font as RustTypeFont,
+    GlyphId,
     Scale,
+    ScaledGlyph,
 };
 
+use crate::debug::debug_log_warn;
+use crate::platform::system_fonts::{SystemFont, find_system_fonts};
+
 
 //TODO: we need to add the bold, italic etc. versions and thefore name this more specific
 static FONT_DATA: [u8; include_bytes!("../../ubuntu_fonts/Ubuntu-Regular.ttf").len()] =
     *include_bytes!("../../ubuntu_fonts/Ubuntu-Regular.ttf");
+const BUNDLED_FONT_FAMILY: &str = "ubuntu";
+
+//these are tried in order for the generic css font families, when none of them is installed we use our bundled font
+const SERIF_FONT_FAMILIES: [&str; 5] = ["times new roman", "dejavu serif", "liberation serif", "noto serif", "georgia"];
+const SANS_SERIF_FONT_FAMILIES: [&str; 6] = ["arial", "helvetica", "dejavu sans", "liberation sans", "noto sans", "ubuntu"];
+const MONOSPACE_FONT_FAMILIES: [&str; 6] = ["courier new", "dejavu sans mono", "liberation mono", "noto sans mono", "ubuntu mono", "consolas"];
+
+pub const GLYPH_ATLAS_WIDTH: u32 = 1024;
+const GLYPH_ATLAS_INITIAL_HEIGHT: u32 = 128;
+const GLYPH_ATLAS_MAX_HEIGHT: u32 = 4096;
+const GLYPH_ATLAS_PADDING: u32 = 1; //empty pixels between the glyphs, so they don't bleed into each other when blended
+const GLYPH_SUBPIXEL_POSITIONS: f32 = 4.0; //glyphs are rasterized for this many horizontal offsets within a pixel, so spacing stays even
 
 
 #[cfg_attr(debug_assertions, derive(Debug))]
@@ -35,11 +55,13 @@ impl Font {
 pub enum FontFace {
     //TODO: we want bold and italic to be seperate fonts here (because they are loaded from different files)
     //      but it would be nice if the enum items then have properties with those flags (bool)
-    TimesNewRomanRegular,
+    TimesNewRomanRegular, //this is the font we bundle, which is used when no font family is given, or none of the given ones are found
+    System(String), //a font family installed on the system, by its lowercased name
 }
 
 
-#[derive(Eq, PartialEq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Eq, PartialEq, Hash, Clone)]
 pub struct FontKey {
     face: FontFace,
     bold: bool,
@@ -47,21 +69,230 @@ pub struct FontKey {
 }
 
 
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy)]
+pub struct AtlasGlyph {
+    //where the rasterized glyph is in the atlas, and where it goes relative to the top left of the text it is part of
+    pub atlas_x: u32,
+    pub atlas_y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+
+pub struct GlyphAtlas {
+    //the rasterized glyphs of one font at one size, packed in rows (shelves) in one bitmap, so text can be copied from it instead of rasterized
+    pub coverage: Vec<u8>, //how much of every pixel is covered by a glyph, row after row, with GLYPH_ATLAS_WIDTH pixels per row
+    pub height: u32,
+    pub version: u32, //this goes up every time the bitmap changes, so the textures made from it know when to update
+    glyphs: HashMap<(GlyphId, u8), Option<AtlasGlyph>>, //by glyph and subpixel position, glyphs without pixels (like spaces) are None
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+}
+impl GlyphAtlas {
+    fn new() -> GlyphAtlas {
+        return GlyphAtlas { coverage: vec![0; (GLYPH_ATLAS_WIDTH * GLYPH_ATLAS_INITIAL_HEIGHT) as usize], height: GLYPH_ATLAS_INITIAL_HEIGHT, version: 0,
+                            glyphs: HashMap::new(), shelf_x: 0, shelf_y: 0, shelf_height: 0 };
+    }
+
+    fn clear(&mut self) {
+        self.coverage.fill(0);
+        self.glyphs.clear();
+        self.shelf_x = 0;
+        self.shelf_y = 0;
+        self.shelf_height = 0;
+        self.version += 1;
+    }
+
+    fn add_text(&mut self, font: &RustTypeFont<'static>, text: &String, scale: Scale) -> Option<Vec<AtlasGlyph>> {
+        //returns where the glyphs of the text are, adding the ones we don't have yet, or None when they don't all fit anymore
+        let baseline_y = font.v_metrics(scale).ascent;
+
+        let mut atlas_glyphs = Vec::new();
+        for glyph in font.layout(text, scale, point(0.0, baseline_y)) {
+            let x = glyph.position().x.floor();
+            let subpixel_position = ((glyph.position().x - x) * GLYPH_SUBPIXEL_POSITIONS) as u8;
+
+            let key = (glyph.id(), subpixel_position);
+            if !self.glyphs.contains_key(&key) && !self.add_glyph(glyph.unpositioned().clone(), subpixel_position, baseline_y) {
+                return None;
+            }
+            if let Some(atlas_glyph) = self.glyphs[&key] {
+                atlas_glyphs.push(AtlasGlyph { x: atlas_glyph.x + x as i32, ..atlas_glyph });
+            }
+        }
+        return Some(atlas_glyphs);
+    }
+
+    fn add_glyph(&mut self, glyph: ScaledGlyph, subpixel_position: u8, baseline_y: f32) -> bool {
+        //returns false when there is no space left for the glyph
+        let key = (glyph.id(), subpixel_position);
+        let positioned_glyph = glyph.positioned(point(subpixel_position as f32 / GLYPH_SUBPIXEL_POSITIONS, baseline_y));
+        let bounding_box = positioned_glyph.pixel_bounding_box();
+        if bounding_box.is_none() {
+            self.glyphs.insert(key, None);
+            return true;
+        }
+        let bounding_box = bounding_box.unwrap();
+        let (width, height) = (bounding_box.width() as u32, bounding_box.height() as u32);
+
+        let position = self.find_space(width, height);
+        if position.is_none() {
+            return false;
+        }
+        let (atlas_x, atlas_y) = position.unwrap();
+
+        positioned_glyph.draw(|glyph_x, glyph_y, glyph_coverage| {
+            let idx = (atlas_y + glyph_y) * GLYPH_ATLAS_WIDTH + atlas_x + glyph_x;
+            self.coverage[idx as usize] = (glyph_coverage * 255.0) as u8;
+        });
+        self.version += 1;
+
+        self.glyphs.insert(key, Some(AtlasGlyph { atlas_x, atlas_y, width, height, x: bounding_box.min.x, y: bounding_box.min.y }));
+        return true;
+    }
+
+    fn find_space(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width + GLYPH_ATLAS_PADDING > GLYPH_ATLAS_WIDTH {
+            return None;
+        }
+        if self.shelf_x + width + GLYPH_ATLAS_PADDING > GLYPH_ATLAS_WIDTH {
+            //the glyph goes on a new shelf, below the current one
+            self.shelf_y += self.shelf_height + GLYPH_ATLAS_PADDING;
+            self.shelf_x = 0;
+            self.shelf_height = 0;
+        }
+
+        let needed_height = self.shelf_y + height + GLYPH_ATLAS_PADDING;
+        if needed_height > GLYPH_ATLAS_MAX_HEIGHT {
+            return None;
+        }
+        while needed_height > self.height {
+            //since all rows have the same width, the bitmap grows by adding rows at the bottom
+            self.height = (self.height * 2).min(GLYPH_ATLAS_MAX_HEIGHT);
+            self.coverage.resize((GLYPH_ATLAS_WIDTH * self.height) as usize, 0);
+        }
+
+        let position = (self.shelf_x, self.shelf_y);
+        self.shelf_x += width + GLYPH_ATLAS_PADDING;
+        self.shelf_height = self.shelf_height.max(height);
+        return Some(position);
+    }
+}
+
+
 pub struct FontContext {
-    pub font_data: HashMap<FontKey, RustTypeFont<'static>>,
+    font_data: RefCell<HashMap<FontKey, Arc<RustTypeFont<'static>>>>,
+    system_fonts: RefCell<Option<Vec<SystemFont>>>, //this is only filled when a page asks for a font family, since scanning is slow
+    glyph_atlases: RefCell<HashMap<(FontKey, u16), GlyphAtlas>>, //by font and size
 }
 impl FontContext {
     pub fn new() -> FontContext {
 
-        let mut font_context = FontContext { font_data: HashMap::new() };
+        let font_context = FontContext { font_data: RefCell::new(HashMap::new()), system_fonts: RefCell::new(None), glyph_atlases: RefCell::new(HashMap::new()) };
 
         //TODO: load the other font variants (bold, italic etc.)
         let font = RustTypeFont::try_from_bytes(&FONT_DATA).expect("Failure loading font data");
-        font_context.font_data.insert(Font::default().to_font_key(), font);
+        font_context.font_data.borrow_mut().insert(Font::default().to_font_key(), Arc::new(font));
 
         return font_context;
     }
 
+    pub fn copy_for_other_thread(&self) -> FontContext {
+        //the font context is not thread safe, so a thread measuring text gets a copy, with the fonts loaded so far (but without the glyph atlases)
+        return FontContext { font_data: RefCell::new(self.font_data.borrow().clone()), system_fonts: RefCell::new(self.system_fonts.borrow().clone()),
+                             glyph_atlases: RefCell::new(HashMap::new()) };
+    }
+
+    pub fn resolve_font_face(&self, font_families: &Vec<String>) -> FontFace {
+        //we take the first family in the list that we can find, and otherwise fall back to our bundled font
+
+        for font_family in font_families {
+            let candidates = match font_family.as_str() {
+                "serif" => SERIF_FONT_FAMILIES.to_vec(),
+                "sans-serif" | "system-ui" => SANS_SERIF_FONT_FAMILIES.to_vec(),
+                "monospace" => MONOSPACE_FONT_FAMILIES.to_vec(),
+                _ => vec![font_family.as_str()],
+            };
+
+            for candidate in candidates {
+                if candidate == BUNDLED_FONT_FAMILY {
+                    return FontFace::TimesNewRomanRegular;
+                }
+                if self.system_font_family_exists(candidate) {
+                    return FontFace::System(candidate.to_owned());
+                }
+            }
+        }
+
+        return FontFace::TimesNewRomanRegular;
+    }
+
+    fn system_font_family_exists(&self, font_family: &str) -> bool {
+        if self.system_fonts.borrow().is_none() {
+            *self.system_fonts.borrow_mut() = Some(find_system_fonts());
+        }
+        return self.system_fonts.borrow().as_ref().unwrap().iter().any(|system_font| system_font.family == font_family);
+    }
+
+    pub fn get_font_data(&self, font_key: &FontKey) -> Arc<RustTypeFont<'static>> {
+        let cached_font = self.font_data.borrow().get(font_key).map(|font| Arc::clone(font));
+        if cached_font.is_some() {
+            return cached_font.unwrap();
+        }
+
+        let mut font = None;
+        match &font_key.face {
+            FontFace::System(font_family) => {
+                font = self.load_system_font(font_family, font_key.bold, font_key.italic);
+            },
+            FontFace::TimesNewRomanRegular => {},
+        }
+
+        if font.is_none() {
+            //TODO: we don't have bold and italic versions of our bundled font loaded yet, so we use the regular one for those as well
+            font = Some(Arc::clone(&self.font_data.borrow()[&Font::default().to_font_key()]));
+        }
+
+        let font = font.unwrap();
+        self.font_data.borrow_mut().insert(font_key.clone(), Arc::clone(&font));
+        return font;
+    }
+
+    fn load_system_font(&self, font_family: &String, bold: bool, italic: bool) -> Option<Arc<RustTypeFont<'static>>> {
+        let system_fonts = self.system_fonts.borrow();
+        let fonts_in_family: Vec<&SystemFont> = system_fonts.as_ref().unwrap().iter().filter(|system_font| system_font.family == *font_family).collect();
+
+        //we prefer the exact variant, but otherwise take the closest one we have (a regular font for bold text is better than another family)
+        let mut best_match = fonts_in_family.iter().find(|system_font| system_font.bold == bold && system_font.italic == italic);
+        if best_match.is_none() {
+            best_match = fonts_in_family.iter().find(|system_font| system_font.italic == italic);
+        }
+        if best_match.is_none() {
+            best_match = fonts_in_family.first();
+        }
+        if best_match.is_none() {
+            return None;
+        }
+
+        let path = &best_match.unwrap().path;
+        let font_bytes = fs::read(path);
+        if font_bytes.is_err() {
+            debug_log_warn(format!("could not read font file: {:?}", path));
+            return None;
+        }
+
+        let font = RustTypeFont::try_from_vec(font_bytes.unwrap());
+        if font.is_none() {
+            debug_log_warn(format!("could not parse font file: {:?}", path));
+            return None;
+        }
+        return Some(Arc::new(font.unwrap()));
+    }
+
     pub fn get_text_dimension(&self, text: &String, font: &Font) -> (f32, f32) {
         return self.get_text_dimension_str(text.as_str(), font);
     }
@@ -71,7 +302,7 @@ impl FontContext {
             return (0.0, 0.0);
         }
 
-        let rust_type_font = &self.font_data[&font.to_font_key()];
+        let rust_type_font = self.get_font_data(&font.to_font_key());
 
         let scale = Scale::uniform(font.size as f32);
         let v_metrics = rust_type_font.v_metrics(scale);
@@ -84,12 +315,40 @@ impl FontContext {
         return (glyphs_width, glyphs_height);
     }
 
+    pub fn get_atlas_glyphs(&self, text: &String, font: &Font) -> Vec<AtlasGlyph> {
+        //returns where the glyphs of the text are in the atlas for the font (which is in glyph_atlas()), and where they go
+        let rust_type_font = self.get_font_data(&font.to_font_key());
+        let scale = Scale::uniform(font.size as f32);
+
+        let mut glyph_atlases = self.glyph_atlases.borrow_mut();
+        let glyph_atlas = glyph_atlases.entry((font.to_font_key(), font.size)).or_insert_with(GlyphAtlas::new);
+
+        let atlas_glyphs = glyph_atlas.add_text(&rust_type_font, text, scale);
+        if atlas_glyphs.is_some() {
+            return atlas_glyphs.unwrap();
+        }
+
+        //the atlas is full, so we start over with only the glyphs of this text, the others are added again when they are used
+        glyph_atlas.clear();
+        let atlas_glyphs = glyph_atlas.add_text(&rust_type_font, text, scale);
+        if atlas_glyphs.is_none() {
+            debug_log_warn(format!("the glyphs for text of size {} don't fit in the glyph atlas", font.size));
+            return Vec::new();
+        }
+        return atlas_glyphs.unwrap();
+    }
+
+    pub fn glyph_atlas(&self, font: &Font) -> Option<Ref<'_, GlyphAtlas>> {
+        let glyph_atlases = self.glyph_atlases.borrow();
+        return Ref::filter_map(glyph_atlases, |glyph_atlases| glyph_atlases.get(&(font.to_font_key(), font.size))).ok();
+    }
+
     pub fn compute_char_position_mapping(&self, font: &Font, text: &String) -> Vec<f32> {
         //This returns the relative ending x positions of each character in the text
 
         let mut char_position_mapping = Vec::new();
 
-        let rust_type_font = &self.font_data[&font.to_font_key()];
+        let rust_type_font = self.get_font_data(&font.to_font_key());
 
         let scale = Scale::uniform(font.size as f32);
         let v_metrics = rust_type_font.v_metrics(scale);
@@ -1,21 +1,40 @@
 pub mod fonts;
+pub mod system_fonts;
 
-use image::DynamicImage;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
 
-use rusttype::{point, Scale};
+use image::{DynamicImage, RgbaImage};
+
+use rfd::FileDialog;
 use sdl2::{
     image::{self as SdlImage, Sdl2ImageContext},
     keyboard::Keycode as SdlKeycode,
     pixels::{Color as SdlColor, PixelFormatEnum},
     rect::{Point as SdlPoint, Rect as SdlRect},
-    render::{BlendMode, TextureAccess, WindowCanvas},
+    render::{BlendMode, Texture, TextureAccess, WindowCanvas},
+    surface::Surface,
+    sys::{SDL_GetRenderTarget, SDL_SetRenderTarget},
+    EventPump,
     Sdl,
     VideoSubsystem,
 };
 
 use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::color::Color;
-use crate::platform::fonts::{Font, FontContext};
+use crate::debug::debug_log_warn;
+use crate::platform::fonts::{Font, FontContext, FontKey, GLYPH_ATLAS_WIDTH};
+use crate::style::media_query::ColorScheme;
+use crate::ui::WindowDimensions;
+
+
+const WINDOW_TITLE: &str = "Webcrustacean";
+const MINIMUM_WINDOW_WIDTH: u32 = 400; //below this, the buttons and the address bar in the header don't fit anymore
+const MINIMUM_WINDOW_HEIGHT: u32 = 200;
 
 
 #[cfg_attr(debug_assertions, derive(Debug))]
@@ -31,23 +50,43 @@ impl Position {
 }
 
 
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
 pub enum KeyCode {
     BACKSPACE,
+    DOWN,
+    END,
+    HOME,
     LEFT,
+    PAGEDOWN,
+    PAGEUP,
     RETURN,
-    RIGHT
+    RIGHT,
+    SPACE,
+    UP,
+}
+
+
+struct GlyphAtlasTexture {
+    //the glyph atlas of a font on the GPU, as white pixels with the coverage of the glyphs as alpha, so we can give it any color when copying
+    texture: Texture,
+    version: u32,
+    height: u32,
 }
 
 
 pub struct Platform {
     pub sdl_context: Sdl,
-    pub font_context: FontContext,
+    pub font_context: Rc<FontContext>, //this is shared with the javascript interpreter, which needs it to lay out the page for scripts
+    pub event_pump: Rc<RefCell<EventPump>>, //SDL only allows one event pump, so this is shared with the windows of dialogs
+    pub color_scheme: Rc<Cell<ColorScheme>>, //shared with the windows of dialogs as well, so they follow when it is toggled
 
     canvas: WindowCanvas,
     video_subsystem: VideoSubsystem,
+    glyph_atlas_textures: HashMap<(FontKey, u16), GlyphAtlasTexture>, //textures belong to the canvas, so every window has its own
 
-    //the image_context is not used by our code, but needs to be kept alive in order to work with images in SDL2:
-    _image_context: Sdl2ImageContext,
+    //the image_context is not used by our code, but needs to be kept alive in order to work with images in SDL2 (dialogs use the one of the main window):
+    _image_context: Option<Sdl2ImageContext>,
 }
 impl Platform {
     pub fn present(&mut self) {
@@ -69,32 +108,51 @@ impl Platform {
             return;
         }
 
-        let rust_type_font = &self.font_context.font_data[&font.to_font_key()];
-
-        let scale = Scale::uniform(font.size as f32);
-        let v_metrics = rust_type_font.v_metrics(scale);
-        let glyphs: Vec<_> = rust_type_font.layout(text, scale, point(0.0, v_metrics.ascent)).collect();
+        let atlas_glyphs = self.font_context.get_atlas_glyphs(text, font);
+        if atlas_glyphs.is_empty() {
+            return;
+        }
 
-        self.enable_blending(); //TODO: what if we always have blending on? Maybe more expensive?
+        self.update_glyph_atlas_texture(font);
+        let glyph_atlas_texture = self.glyph_atlas_textures.get_mut(&(font.to_font_key(), font.size)).unwrap();
+        glyph_atlas_texture.texture.set_color_mod(color.r, color.g, color.b);
+        glyph_atlas_texture.texture.set_alpha_mod(color.a);
 
-        //TODO: to speed this up, we would need to save the resulting bitmap including alpha somewhere
-        for glyph in glyphs {
-            if let Some(bounding_box) = glyph.pixel_bounding_box() {
-                glyph.draw(|g_x, g_y, g_v| {
+        for glyph in atlas_glyphs {
+            let source_rect = SdlRect::new(glyph.atlas_x as i32, glyph.atlas_y as i32, glyph.width, glyph.height);
+            let target_rect = SdlRect::new(x as i32 + glyph.x, y as i32 + glyph.y, glyph.width, glyph.height);
+            self.canvas.copy(&glyph_atlas_texture.texture, source_rect, target_rect).expect("error rendering text");
+        }
+    }
 
-                    let absolute_x = g_x as i32 + bounding_box.min.x + x as i32;
-                    let absolute_y = g_y as i32 + bounding_box.min.y + y as i32;
+    fn update_glyph_atlas_texture(&mut self, font: &Font) {
+        //the texture is only uploaded again when glyphs were added to the atlas since we last used it
+        //TODO: we now upload the whole atlas, while we could upload only the rows that changed
+        let font_context = Rc::clone(&self.font_context);
+        let glyph_atlas = font_context.glyph_atlas(font).unwrap();
+        let key = (font.to_font_key(), font.size);
 
-                    //TODO: it is probably slow to set pixels individually on the full surface, instead
-                    //      of render a smaller surface first. But lets first move to openGL instead of
-                    //      optimizing for SDL, the optimization might be different on openGL
-                    self.set_pixel(absolute_x, absolute_y, color, (g_v * 255.0) as u8);
+        let current_texture = self.glyph_atlas_textures.get(&key);
+        if current_texture.is_some() && current_texture.unwrap().version == glyph_atlas.version {
+            return;
+        }
 
-                });
+        if current_texture.is_none() || current_texture.unwrap().height != glyph_atlas.height {
+            let old_texture = self.glyph_atlas_textures.remove(&key);
+            if old_texture.is_some() {
+                unsafe { old_texture.unwrap().texture.destroy(); }
             }
+
+            let texture_creator = self.canvas.texture_creator();
+            let mut texture = texture_creator.create_texture_static(PixelFormatEnum::ABGR8888, GLYPH_ATLAS_WIDTH, glyph_atlas.height).unwrap();
+            texture.set_blend_mode(BlendMode::Blend);
+            self.glyph_atlas_textures.insert(key.clone(), GlyphAtlasTexture { texture, version: glyph_atlas.version, height: glyph_atlas.height });
         }
 
-        self.disable_blending();
+        let pixels: Vec<u8> = glyph_atlas.coverage.iter().flat_map(|coverage| [255, 255, 255, *coverage]).collect();
+        let glyph_atlas_texture = self.glyph_atlas_textures.get_mut(&key).unwrap();
+        glyph_atlas_texture.texture.update(None, &pixels, GLYPH_ATLAS_WIDTH as usize * 4).unwrap();
+        glyph_atlas_texture.version = glyph_atlas.version;
     }
 
     pub fn enable_blending(&mut self) {
@@ -106,23 +164,37 @@ impl Platform {
     }
 
     pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color, alpha: u8) {
-        self.canvas.set_draw_color(to_sdl_color(color, alpha));
+        let sdl_color = to_sdl_color(color, alpha);
+        self.canvas.set_draw_color(sdl_color);
+
+        let needs_blending = sdl_color.a < 255;
+        if needs_blending {
+            self.enable_blending();
+        }
 
         let rect = SdlRect::new(x as i32, y as i32, width as u32, height as u32);
         self.canvas.fill_rect(rect).expect("error filling rect");
-    }
 
-    pub fn set_pixel(&mut self, x: i32, y: i32, color: Color, alpha: u8) {
-        self.canvas.set_draw_color(to_sdl_color(color, alpha)); //TODO: we might want to extract this out of the platform calls, and make it a platform call
-                                                                //      by itself, so we don't need to call it as much...
-        self.canvas.draw_point(SdlPoint::new(x, y)).expect("error drawing point");
+        if needs_blending {
+            self.disable_blending();
+        }
     }
 
     pub fn draw_square(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color, alpha: u8) {
-        self.canvas.set_draw_color(to_sdl_color(color, alpha));
+        let sdl_color = to_sdl_color(color, alpha);
+        self.canvas.set_draw_color(sdl_color);
+
+        let needs_blending = sdl_color.a < 255;
+        if needs_blending {
+            self.enable_blending();
+        }
 
         let rect = SdlRect::new(x as i32, y as i32, width as u32, height as u32);
         self.canvas.draw_rect(rect).expect("error drawing square");
+
+        if needs_blending {
+            self.disable_blending();
+        }
     }
 
     pub fn render_image(&mut self, image: &DynamicImage, x: f32, y: f32) {
@@ -136,6 +208,115 @@ impl Platform {
         //self.canvas.set_blend_mode(BlendMode::Blend); //TODO: this does not work, but we need to fix blending somehow (for png alpha)
 
         self.canvas.copy(&texture, None, Some(SdlRect::new(x as i32, y as i32, image.width(), image.height()))).expect("error rendering image");
+        unsafe { texture.destroy(); } //TODO: we could keep the textures of images between frames, like we do for the glyph atlases
+    }
+    pub fn render_image_tiles(&mut self, image: &DynamicImage, tile_positions: &Vec<Position>, tile_width: f32, tile_height: f32,
+                              clip_x: f32, clip_y: f32, clip_width: f32, clip_height: f32) {
+        //renders the same image (scaled to the tile size) on all positions, only drawing the part that falls within the clip rect
+        if tile_positions.is_empty() || tile_width < 1.0 || tile_height < 1.0 || clip_width < 1.0 || clip_height < 1.0 {
+            return;
+        }
+
+        let texture_creator = self.canvas.texture_creator();
+        let mut texture = texture_creator.create_texture(find_pixel_format(image), TextureAccess::Target, image.width(), image.height()).unwrap();
+        let bytes_per_pixel = image.color().bytes_per_pixel();
+        texture.update(None, image.as_bytes(), image.width() as usize * bytes_per_pixel as usize).unwrap();
+
+        self.render_clipped(clip_x, clip_y, clip_width, clip_height, |platform| {
+            for position in tile_positions {
+                let target_rect = SdlRect::new(position.x as i32, position.y as i32, tile_width as u32, tile_height as u32);
+                platform.canvas.copy(&texture, None, Some(target_rect)).expect("error rendering image");
+            }
+        });
+        unsafe { texture.destroy(); }
+    }
+    pub fn render_clipped<F: FnOnce(&mut Platform)>(&mut self, x: f32, y: f32, width: f32, height: f32, render_function: F) {
+        //only the part of what is rendered in render_function that falls within the given rect ends up on the screen. We keep the clip
+        //rect that was already set into account, and restore it afterwards, so this can be nested.
+        let previous_clip_rect = self.canvas.clip_rect();
+        let mut clip_rect = SdlRect::new(x as i32, y as i32, width.max(1.0) as u32, height.max(1.0) as u32);
+        if previous_clip_rect.is_some() {
+            let intersection = clip_rect.intersection(previous_clip_rect.unwrap());
+            if intersection.is_none() {
+                return;
+            }
+            clip_rect = intersection.unwrap();
+        }
+
+        self.canvas.set_clip_rect(Some(clip_rect));
+        render_function(self);
+        self.canvas.set_clip_rect(previous_clip_rect);
+    }
+    pub fn render_with_opacity<F: FnOnce(&mut Platform)>(&mut self, opacity: f32, render_function: F) {
+        //everything rendered in render_function goes to an offscreen texture first, which is then blended onto the current target as a whole
+        let texture_creator = self.canvas.texture_creator();
+        let (width, height) = self.canvas.output_size().unwrap();
+        let mut texture = texture_creator.create_texture_target(PixelFormatEnum::RGBA8888, width, height).unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+        texture.set_alpha_mod((opacity.clamp(0.0, 1.0) * 255.0).round() as u8);
+
+        //The sdl2 crate only allows rendering to a texture from a closure that borrows the canvas, which would mean we could not use our
+        //own render methods inside, so we switch the render target ourselves. We restore the previous one, so this can be nested.
+        //TODO: the texture is not premultiplied, so semi-transparent pixels (like the edges of glyphs) get their alpha applied twice
+        let previous_target = unsafe { SDL_GetRenderTarget(self.canvas.raw()) };
+        unsafe { SDL_SetRenderTarget(self.canvas.raw(), texture.raw()); }
+
+        self.canvas.set_draw_color(SdlColor::RGBA(0, 0, 0, 0));
+        self.canvas.clear();
+
+        render_function(self);
+
+        unsafe { SDL_SetRenderTarget(self.canvas.raw(), previous_target); }
+        self.canvas.copy(&texture, None, None).expect("error rendering opacity layer");
+        unsafe { texture.destroy(); }
+    }
+    pub fn read_pixels(&self, x: f32, y: f32, width: f32, height: f32) -> Option<RgbaImage> {
+        //this reads back what was rendered since the last present, so it should be called before presenting
+        let pixels = self.canvas.read_pixels(SdlRect::new(x as i32, y as i32, width as u32, height as u32), PixelFormatEnum::ABGR8888);
+        if pixels.is_err() {
+            debug_log_warn(format!("could not read the rendered pixels: {}", pixels.err().unwrap()));
+            return None;
+        }
+        return RgbaImage::from_raw(width as u32, height as u32, pixels.unwrap());
+    }
+    pub fn set_window_title(&mut self, page_title: Option<String>) {
+        let title = if page_title.is_some() { format!("{} - {}", page_title.unwrap(), WINDOW_TITLE) } else { WINDOW_TITLE.to_owned() };
+
+        let result = self.canvas.window_mut().set_title(&title);
+        if result.is_err() {
+            debug_log_warn(format!("could not set the window title: {}", title));
+        }
+    }
+    pub fn get_window_dimensions(&self) -> WindowDimensions {
+        let (width, height) = self.canvas.window().size();
+        return WindowDimensions { width: width as f32, height: height as f32 };
+    }
+    pub fn get_screen_dimensions(&self) -> WindowDimensions {
+        //this is the size of the display the window is on
+        let display_mode = self.canvas.window().display_index().and_then(|display_index| self.video_subsystem.desktop_display_mode(display_index));
+        if display_mode.is_err() {
+            debug_log_warn(format!("could not get the size of the screen: {}", display_mode.err().unwrap()));
+            return WindowDimensions { width: SCREEN_WIDTH, height: SCREEN_HEIGHT };
+        }
+        let display_mode = display_mode.unwrap();
+        return WindowDimensions { width: display_mode.w as f32, height: display_mode.h as f32 };
+    }
+    pub fn set_window_icon(&mut self, image: &DynamicImage) {
+        let mut pixels = image.to_rgba8();
+        let (width, height) = (pixels.width(), pixels.height());
+
+        let surface = Surface::from_data(&mut pixels, width, height, width * 4, PixelFormatEnum::ABGR8888);
+        if surface.is_err() {
+            debug_log_warn(format!("could not create the window icon: {}", surface.err().unwrap()));
+            return;
+        }
+        self.canvas.window_mut().set_icon(surface.unwrap());
+    }
+    pub fn window_id(&self) -> u32 {
+        return self.canvas.window().id();
+    }
+    pub fn text_input_enabled(&self) -> bool {
+        return self.video_subsystem.text_input().is_active();
     }
     pub fn enable_text_input(&self) {
         self.video_subsystem.text_input().start();
@@ -146,12 +327,80 @@ impl Platform {
     pub fn convert_key_code(&self, keycode: &SdlKeycode) -> Option<KeyCode> {
         return match keycode.name().as_str() {
             "Backspace" => Some(KeyCode::BACKSPACE),
+            "Down" => Some(KeyCode::DOWN),
+            "End" => Some(KeyCode::END),
+            "Home" => Some(KeyCode::HOME),
             "Left" => Some(KeyCode::LEFT),
+            "PageDown" => Some(KeyCode::PAGEDOWN),
+            "PageUp" => Some(KeyCode::PAGEUP),
             "Return" => Some(KeyCode::RETURN),
             "Right" => Some(KeyCode::RIGHT),
+            "Space" => Some(KeyCode::SPACE),
+            "Up" => Some(KeyCode::UP),
             _ => None,
         }
     }
+    pub fn convert_key_to_dom_key(&self, keycode: &SdlKeycode) -> String {
+        //this is the name of the key that scripts see in the key property of keyboard events
+        //TODO: for characters, this should take the keyboard layout and modifiers (like shift) into account
+        let name = keycode.name();
+        let dom_key = match name.as_str() {
+            "Return" => "Enter",
+            "Space" => " ",
+            "Up" => "ArrowUp",
+            "Down" => "ArrowDown",
+            "Left" => "ArrowLeft",
+            "Right" => "ArrowRight",
+            "Left Shift" | "Right Shift" => "Shift",
+            "Left Ctrl" | "Right Ctrl" => "Control",
+            "Left Alt" | "Right Alt" => "Alt",
+            _ if name.chars().count() == 1 => { return name.to_lowercase(); },
+            _ => { return name; },
+        };
+        return dom_key.to_owned();
+    }
+}
+
+
+pub fn pick_file() -> Option<PathBuf> {
+    //this opens the native file picker of the OS, and blocks until the user has chosen a file (or cancelled)
+    return FileDialog::new().pick_file();
+}
+
+
+pub fn pick_page_file() -> Option<PathBuf> {
+    //like pick_file, but html files are shown first, the other files can still be chosen, since we can show text and images as well
+    return FileDialog::new().add_filter("HTML files", &["html", "htm", "xhtml"]).add_filter("All files", &["*"]).pick_file();
+}
+
+
+pub fn pick_save_file(suggested_file_name: &str) -> Option<PathBuf> {
+    //like pick_file, but for choosing where to save something, the user can change the suggested name
+    return FileDialog::new().set_file_name(suggested_file_name).save_file();
+}
+
+
+pub fn detect_color_scheme() -> ColorScheme {
+    //there is no portable way to ask for this (and SDL2 does not know), so we try the settings of the common desktops, and use light otherwise
+    let gtk_theme = env::var("GTK_THEME");
+    if gtk_theme.is_ok() {
+        return if gtk_theme.unwrap().to_lowercase().contains("dark") { ColorScheme::Dark } else { ColorScheme::Light };
+    }
+
+    let settings_commands: [(&str, &[&str], &str); 3] = [
+        ("gsettings", &["get", "org.gnome.desktop.interface", "color-scheme"], "prefer-dark"),
+        ("defaults", &["read", "-g", "AppleInterfaceStyle"], "Dark"), //this fails in light mode, since the setting is not there then
+        ("reg", &["query", "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize", "/v", "AppsUseLightTheme"], "0x0"),
+    ];
+    for (program, args, dark_value) in settings_commands {
+        let output = Command::new(program).args(args).output();
+        if output.is_ok() && output.as_ref().unwrap().status.success() {
+            let output_text = String::from_utf8_lossy(&output.unwrap().stdout).to_string();
+            return if output_text.contains(dark_value) { ColorScheme::Dark } else { ColorScheme::Light };
+        }
+    }
+
+    return ColorScheme::Light;
 }
 
 
@@ -173,29 +422,71 @@ pub fn find_pixel_format(image: &DynamicImage) -> PixelFormatEnum {
 
 
 pub fn to_sdl_color(color: Color, alpha: u8) -> SdlColor {
-    return SdlColor::RGBA(color.r, color.g, color.b, alpha);
+    //the alpha of the color itself is combined with the alpha of the drawing operation
+    let combined_alpha = (color.a as u16 * alpha as u16) / 255;
+    return SdlColor::RGBA(color.r, color.g, color.b, combined_alpha as u8);
 }
 
 
-pub fn init_platform(sdl_context: Sdl) -> Result<Platform, String> {
+pub fn init_platform(sdl_context: Sdl, hidden: bool) -> Result<Platform, String> {
     let video_subsystem = sdl_context.video()
         .expect("Could not get the video subsystem");
 
     let image_context = SdlImage::init(SdlImage::InitFlag::PNG | SdlImage::InitFlag::JPG)?;
 
-    let window = video_subsystem.window("Webcrustacean", SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
-        .position_centered()
-        .build()
+    let mut window_builder = video_subsystem.window(WINDOW_TITLE, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    window_builder.position_centered();
+    window_builder.resizable();
+    if hidden {
+        //we can still render to a hidden window, which is used to render pages without showing them (like for screenshots)
+        window_builder.hidden();
+    }
+    let mut window = window_builder.build()
         .expect("could not initialize video subsystem");
+    let result = window.set_minimum_size(MINIMUM_WINDOW_WIDTH, MINIMUM_WINDOW_HEIGHT);
+    if result.is_err() {
+        debug_log_warn(format!("could not set the minimum size of the window: {}", result.err().unwrap()));
+    }
 
     let canvas = window.into_canvas().build()
         .expect("could not make a canvas");
 
+    let event_pump = sdl_context.event_pump()?;
+
     return Result::Ok(Platform {
         canvas,
         sdl_context,
-        font_context: FontContext::new(),
+        font_context: Rc::new(FontContext::new()),
+        event_pump: Rc::new(RefCell::new(event_pump)),
+        color_scheme: Rc::new(Cell::new(detect_color_scheme())),
+        video_subsystem,
+        glyph_atlas_textures: HashMap::new(),
+        _image_context: Some(image_context),
+    });
+}
+
+
+pub fn init_dialog_platform(sdl_context: &Sdl, font_context: &Rc<FontContext>, event_pump: &Rc<RefCell<EventPump>>,
+                            color_scheme: &Rc<Cell<ColorScheme>>, title: &str, width: f32, height: f32) -> Result<Platform, String> {
+    //dialogs get a window of their own, which uses the same fonts and events as the main window
+    let video_subsystem = sdl_context.video()?;
+
+    let window = video_subsystem.window(title, width as u32, height as u32)
+        .position_centered()
+        .build()
+        .map_err(|error| error.to_string())?;
+
+    let canvas = window.into_canvas().build()
+        .map_err(|error| error.to_string())?;
+
+    return Result::Ok(Platform {
+        canvas,
+        sdl_context: sdl_context.clone(),
+        font_context: Rc::clone(font_context),
+        event_pump: Rc::clone(event_pump),
+        color_scheme: Rc::clone(color_scheme),
         video_subsystem,
-        _image_context: image_context,
+        glyph_atlas_textures: HashMap::new(),
+        _image_context: None,
     });
 }
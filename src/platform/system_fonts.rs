@@ -0,0 +1,176 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::debug::debug_log_warn;
+
+
+const MAX_FONT_DIRECTORY_DEPTH: usize = 6;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct SystemFont {
+    pub family: String, //lowercased, since font family names in css are matched case insensitive
+    pub bold: bool,
+    pub italic: bool,
+    pub path: PathBuf,
+}
+
+
+pub fn find_system_fonts() -> Vec<SystemFont> {
+    let mut system_fonts = Vec::new();
+
+    for font_directory in get_system_font_directories() {
+        collect_fonts_in_directory(&font_directory, 0, &mut system_fonts);
+    }
+
+    return system_fonts;
+}
+
+
+fn get_system_font_directories() -> Vec<PathBuf> {
+    let mut directories = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+        PathBuf::from("/System/Library/Fonts"),
+        PathBuf::from("/Library/Fonts"),
+    ];
+
+    let home = env::var("HOME");
+    if home.is_ok() {
+        let home = PathBuf::from(home.unwrap());
+        directories.push(home.join(".fonts"));
+        directories.push(home.join(".local/share/fonts"));
+        directories.push(home.join("Library/Fonts"));
+    }
+
+    let windows_directory = env::var("WINDIR");
+    if windows_directory.is_ok() {
+        directories.push(PathBuf::from(windows_directory.unwrap()).join("Fonts"));
+    }
+
+    return directories;
+}
+
+
+fn collect_fonts_in_directory(directory: &Path, depth: usize, system_fonts: &mut Vec<SystemFont>) {
+    if depth > MAX_FONT_DIRECTORY_DEPTH {
+        return;
+    }
+
+    let entries = fs::read_dir(directory);
+    if entries.is_err() {
+        //this is not an error, most of the directories we check only exist on some platforms
+        return;
+    }
+
+    for entry in entries.unwrap() {
+        if entry.is_err() {
+            continue;
+        }
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            collect_fonts_in_directory(&path, depth + 1, system_fonts);
+            continue;
+        }
+
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        if extension != Some("ttf".to_owned()) && extension != Some("otf".to_owned()) {
+            //TODO: we don't support font collections (.ttc) yet
+            continue;
+        }
+
+        let font_data = fs::read(&path);
+        if font_data.is_err() {
+            debug_log_warn(format!("could not read font file: {:?}", path));
+            continue;
+        }
+
+        let names = read_font_names(&font_data.unwrap());
+        if names.is_none() {
+            debug_log_warn(format!("could not read the names in font file: {:?}", path));
+            continue;
+        }
+        let (family, subfamily) = names.unwrap();
+        let subfamily = subfamily.to_lowercase();
+
+        system_fonts.push(SystemFont {
+            family: family.to_lowercase(),
+            bold: subfamily.contains("bold"),
+            italic: subfamily.contains("italic") || subfamily.contains("oblique"),
+            path,
+        });
+    }
+}
+
+
+fn read_font_names(font_data: &Vec<u8>) -> Option<(String, String)> {
+    //We read the family and subfamily name from the "name" table of the font, see https://learn.microsoft.com/en-us/typography/opentype/spec/name
+    const FAMILY_NAME_ID: u16 = 1;
+    const SUBFAMILY_NAME_ID: u16 = 2;
+
+    let number_of_tables = read_u16(font_data, 4)?;
+
+    let mut name_table_offset = None;
+    for table_idx in 0..number_of_tables as usize {
+        let record_offset = 12 + (table_idx * 16);
+        if font_data.get(record_offset..record_offset + 4)? == b"name" {
+            name_table_offset = Some(read_u32(font_data, record_offset + 8)? as usize);
+            break;
+        }
+    }
+    let name_table_offset = name_table_offset?;
+
+    let number_of_names = read_u16(font_data, name_table_offset + 2)?;
+    let string_storage_offset = name_table_offset + read_u16(font_data, name_table_offset + 4)? as usize;
+
+    let mut family = None;
+    let mut subfamily = None;
+    for name_idx in 0..number_of_names as usize {
+        let record_offset = name_table_offset + 6 + (name_idx * 12);
+        let platform_id = read_u16(font_data, record_offset)?;
+        let name_id = read_u16(font_data, record_offset + 6)?;
+        let length = read_u16(font_data, record_offset + 8)? as usize;
+        let offset = read_u16(font_data, record_offset + 10)? as usize;
+
+        if name_id != FAMILY_NAME_ID && name_id != SUBFAMILY_NAME_ID {
+            continue;
+        }
+
+        let bytes = font_data.get(string_storage_offset + offset..string_storage_offset + offset + length)?;
+        let name = match platform_id {
+            0 | 3 => {
+                //the unicode and windows platforms store names as UTF-16 big endian
+                let utf16: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+                String::from_utf16_lossy(&utf16)
+            },
+            1 => {
+                //TODO: this is Mac Roman, which is only the same as ascii for the first 128 characters
+                String::from_utf8_lossy(bytes).to_string()
+            },
+            _ => { continue; }
+        };
+
+        if name_id == FAMILY_NAME_ID && family.is_none() {
+            family = Some(name);
+        } else if name_id == SUBFAMILY_NAME_ID && subfamily.is_none() {
+            subfamily = Some(name);
+        }
+    }
+
+    return Some((family?, subfamily.unwrap_or(String::from("Regular"))));
+}
+
+
+fn read_u16(data: &Vec<u8>, offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    return Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+}
+
+
+fn read_u32(data: &Vec<u8>, offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    return Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+}
@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::color::Color;
+
+
+const NR_OF_RECORDED_FRAMES: usize = 120;
+pub const NR_OF_FRAME_PHASES: usize = 5;
+
+
+//this is only used from the main thread, but it is measured in many places (like deep in layout), so it is a global like the caches
+pub static PROFILER: LazyLock<Mutex<Profiler>> = LazyLock::new(|| {
+    return Mutex::new(Profiler::new(NR_OF_RECORDED_FRAMES));
+});
+
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum FramePhase {
+    //the order here is the order in which the phases are stacked in the graph
+    Events,
+    Style, //this includes rebuilding the layout tree, since that is where styles are resolved
+    Layout,
+    Render,
+    Scripts,
+}
+impl FramePhase {
+    pub const ALL: [FramePhase; NR_OF_FRAME_PHASES] = [FramePhase::Events, FramePhase::Style, FramePhase::Layout, FramePhase::Render, FramePhase::Scripts];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            FramePhase::Events => "events",
+            FramePhase::Style => "style",
+            FramePhase::Layout => "layout",
+            FramePhase::Render => "render",
+            FramePhase::Scripts => "scripts",
+        };
+    }
+
+    pub fn color(&self) -> Color {
+        return match self {
+            FramePhase::Events => Color::new(120, 120, 120),
+            FramePhase::Style => Color::new(160, 60, 220),
+            FramePhase::Layout => Color::new(0, 120, 255),
+            FramePhase::Render => Color::new(0, 170, 0),
+            FramePhase::Scripts => Color::new(240, 170, 0),
+        };
+    }
+}
+
+
+#[derive(Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct FrameTimings {
+    pub phase_durations: [Duration; NR_OF_FRAME_PHASES], //indexed by the phase
+    pub total: Duration, //the whole frame, without the time we sleep to keep to the target frame rate
+}
+impl FrameTimings {
+    fn new() -> FrameTimings {
+        return FrameTimings { phase_durations: [Duration::ZERO; NR_OF_FRAME_PHASES], total: Duration::ZERO };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Profiler {
+    frames: VecDeque<FrameTimings>, //the last frames, the oldest first
+    current_frame: FrameTimings,
+    max_frames: usize,
+    nested_durations: Vec<Duration>, //for each measurement that is running, how much of it was spent in measurements inside it
+}
+impl Profiler {
+    pub fn new(max_frames: usize) -> Profiler {
+        return Profiler { frames: VecDeque::new(), current_frame: FrameTimings::new(), max_frames, nested_durations: Vec::new() };
+    }
+
+    pub fn record(&mut self, phase: FramePhase, duration: Duration) {
+        //a phase can happen more than once in a frame (like layout for a page and its frames), so we add them up
+        self.current_frame.phase_durations[phase as usize] += duration;
+    }
+
+    pub fn finish_frame(&mut self, total: Duration) {
+        self.current_frame.total = total;
+        #[cfg(feature="timings")] println!("frame timings: {}", describe_frame(&self.current_frame));
+
+        if self.frames.len() >= self.max_frames {
+            self.frames.pop_front();
+        }
+        let finished_frame = std::mem::replace(&mut self.current_frame, FrameTimings::new());
+        self.frames.push_back(finished_frame);
+    }
+
+    pub fn running_measurements(&self) -> usize {
+        return self.nested_durations.len();
+    }
+
+    pub fn abandon_measurements(&mut self, keep: usize) {
+        //measurements that never finished (because the work panicked) should not count for the ones they were running in
+        self.nested_durations.truncate(keep);
+    }
+
+    pub fn frames(&self) -> &VecDeque<FrameTimings> {
+        return &self.frames;
+    }
+
+    pub fn average_frame(&self) -> FrameTimings {
+        let mut average = FrameTimings::new();
+        if self.frames.is_empty() {
+            return average;
+        }
+
+        for frame in self.frames.iter() {
+            for phase_idx in 0..NR_OF_FRAME_PHASES {
+                average.phase_durations[phase_idx] += frame.phase_durations[phase_idx];
+            }
+            average.total += frame.total;
+        }
+        for phase_idx in 0..NR_OF_FRAME_PHASES {
+            average.phase_durations[phase_idx] /= self.frames.len() as u32;
+        }
+        average.total /= self.frames.len() as u32;
+        return average;
+    }
+}
+
+
+pub struct Measurement {
+    start_instant: Instant,
+}
+impl Measurement {
+    pub fn start() -> Measurement {
+        //the lock is not held while measuring, so the work can measure parts of itself (like the layout for a page we navigate to while
+        //handling events), those parts only count for their own phase, so the phases of a frame add up to the time we measured
+        PROFILER.lock().unwrap().nested_durations.push(Duration::ZERO);
+        return Measurement { start_instant: Instant::now() };
+    }
+
+    pub fn finish(self, phase: FramePhase) {
+        let duration = self.start_instant.elapsed();
+        let mut profiler = PROFILER.lock().unwrap();
+        let nested_duration = profiler.nested_durations.pop().unwrap();
+        profiler.record(phase, duration.saturating_sub(nested_duration));
+        if profiler.nested_durations.last().is_some() {
+            *profiler.nested_durations.last_mut().unwrap() += duration;
+        }
+    }
+}
+
+
+pub fn measure<T, F: FnOnce() -> T>(phase: FramePhase, work: F) -> T {
+    let measurement = Measurement::start();
+    let result = work();
+    measurement.finish(phase);
+    return result;
+}
+
+
+#[cfg(feature="timings")]
+pub fn describe_frame(frame: &FrameTimings) -> String {
+    let phases: Vec<String> = FramePhase::ALL.iter().map(|phase| format!("{} {:.1}", phase.name(), frame.phase_durations[*phase as usize].as_secs_f32() * 1000.0))
+                                                    .collect();
+    return format!("{} (total {:.1} ms)", phases.join(", "), frame.total.as_secs_f32() * 1000.0);
+}
@@ -1,20 +1,58 @@
+use std::collections::HashSet;
 use std::ops::Deref;
 
+use image::RgbaImage;
+
+use crate::animation::{AnimatedProperty, AnimationState};
 use crate::color::Color;
+use crate::display_list::DisplayList;
+use crate::dom::{Document, DomNodeId};
 use crate::layout::{
+    BackgroundImage,
+    DisclosureMarker,
+    Display,
+    DISCLOSURE_MARKER_SIZE,
     FullLayout,
-    LayoutNode,
-    LayoutNodeContent
+    IFRAME_BORDER_WIDTH,
+    LayoutNodeContent,
+    LayoutNodeId,
+    Rect,
+    TextLayoutRect,
+};
+use crate::platform::{fonts::FontContext, Platform, Position};
+use crate::style::background::compute_background_tiles;
+use crate::style::border::{BorderSide, BorderStyle, Borders};
+use crate::style::default_canvas_color;
+use crate::style::shadow::Shadow;
+use crate::ui::{
+    CONTENT_TOP_LEFT_X,
+    CONTENT_TOP_LEFT_Y,
+    UIState,
+    UiColors,
+    WindowDimensions,
+    render_ui,
 };
-use crate::platform::Platform;
-use crate::ui::{UIState, render_ui};
 use crate::ui_components::PageComponent;
 
 
-pub fn render(platform: &mut Platform, full_layout: &FullLayout, ui_state: &mut UIState) {
-    platform.render_clear(Color::WHITE);
+const MAX_BOX_SHADOW_BLUR_LAYERS: usize = 8;
+const FOCUS_RING_WIDTH: usize = 2;
+//the colors of the layout overlay, by what is in the layout node
+const LAYOUT_OVERLAY_TEXT_COLOR: Color = Color::new(0, 90, 255);
+const LAYOUT_OVERLAY_BOX_COLOR: Color = Color::new(0, 170, 0);
+const LAYOUT_OVERLAY_TABLE_COLOR: Color = Color::new(255, 140, 0);
+const LAYOUT_OVERLAY_IMAGE_COLOR: Color = Color::new(220, 0, 220);
+const LAYOUT_OVERLAY_OTHER_COLOR: Color = Color::new(230, 0, 0); //form controls and frames
+
+
+pub fn render(platform: &mut Platform, page_display_list: &DisplayList, scroll_y: f32, ui_state: &mut UIState) {
+    platform.render_clear(default_canvas_color(platform.color_scheme.get()));
 
-    render_layout_node(platform, ui_state, &full_layout.root_node.borrow());
+    //the page is clipped to the part of the window it is shown in, so it can't paint over the ui around it
+    let window_dimensions = ui_state.window_dimensions;
+    platform.render_clipped(CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y, window_dimensions.content_width(), window_dimensions.content_height(), |platform| {
+        page_display_list.replay(platform, scroll_y);
+    });
 
     render_ui(platform, ui_state);
 
@@ -22,55 +60,236 @@ pub fn render(platform: &mut Platform, full_layout: &FullLayout, ui_state: &mut
 }
 
 
-fn render_layout_node(platform: &mut Platform, ui_state: &mut UIState, layout_node: &LayoutNode) {
-    let scroll_y = ui_state.current_scroll_y;
+pub fn render_page_to_image(platform: &mut Platform, page_display_list: &DisplayList, scroll_y: f32, window_dimensions: WindowDimensions) -> Option<RgbaImage> {
+    //this renders only the visible part of the page, without the ui around it, and does not show it on the screen
+    platform.render_clear(default_canvas_color(platform.color_scheme.get()));
+    page_display_list.replay(platform, scroll_y);
+    return platform.read_pixels(CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y, window_dimensions.content_width(), window_dimensions.content_height());
+}
+
+
+pub fn build_display_list(full_layout: &FullLayout, document: &Document, font_context: &FontContext, animation_state: &AnimationState, animation_tick: u32,
+                          ui_colors: &UiColors, focused_dom_node: Option<DomNodeId>, layout_overlay: bool) -> DisplayList {
+    //the whole page goes in the list, not only what is on screen, so it does not depend on the scroll position
+    let mut display_list = DisplayList::new();
+    paint_layout_node(&mut display_list, font_context, animation_state, animation_tick, ui_colors, full_layout, document, full_layout.root_node);
+    if focused_dom_node.is_some() {
+        paint_focus_ring(&mut display_list, full_layout, document, focused_dom_node.unwrap(), ui_colors);
+    }
+    if layout_overlay {
+        paint_layout_overlay(&mut display_list, full_layout, full_layout.root_node);
+    }
+    return display_list;
+}
+
+
+fn paint_layout_overlay(display_list: &mut DisplayList, full_layout: &FullLayout, layout_node_id: LayoutNodeId) {
+    //this outlines every layout node on top of the page, to see where layout puts things without having to print the layout tree
+    let layout_node = &full_layout.nodes[layout_node_id];
+    let color = match &layout_node.content {
+        LayoutNodeContent::TextLayoutNode(text_layout_node) => {
+            //text can be on multiple lines, so we outline each part of it
+            for layout_rect in text_layout_node.rects.iter() {
+                let location = &layout_rect.location;
+                display_list.draw_square(location.x, location.y, location.width, location.height, LAYOUT_OVERLAY_TEXT_COLOR);
+            }
+            None
+        },
+        LayoutNodeContent::BoxLayoutNode(_) => Some(LAYOUT_OVERLAY_BOX_COLOR),
+        LayoutNodeContent::TableLayoutNode(_) | LayoutNodeContent::TableCellLayoutNode(_) => Some(LAYOUT_OVERLAY_TABLE_COLOR),
+        LayoutNodeContent::ImageLayoutNode(_) => Some(LAYOUT_OVERLAY_IMAGE_COLOR),
+        LayoutNodeContent::ButtonLayoutNode(_) | LayoutNodeContent::TextInputLayoutNode(_) | LayoutNodeContent::TextAreaLayoutNode(_) |
+        LayoutNodeContent::FileInputLayoutNode(_) | LayoutNodeContent::IframeLayoutNode(_) => Some(LAYOUT_OVERLAY_OTHER_COLOR),
+        LayoutNodeContent::NoContent => None,
+    };
+
+    if color.is_some() {
+        let location = layout_node.get_bounding_box().unwrap();
+        display_list.draw_square(location.x, location.y, location.width, location.height, color.unwrap());
+    }
+
+    if layout_node.children.is_some() {
+        for child_id in layout_node.children.as_ref().unwrap() {
+            if full_layout.nodes[*child_id].visible {
+                paint_layout_overlay(display_list, full_layout, *child_id);
+            }
+        }
+    }
+}
+
+
+fn paint_focus_ring(display_list: &mut DisplayList, full_layout: &FullLayout, document: &Document, focused_dom_node: DomNodeId, ui_colors: &UiColors) {
+    //the ring goes around everything the element covers (for a link over more lines, that is one box around all of them)
+    let mut node_ids = HashSet::new();
+    document.collect_node_ids(focused_dom_node, &mut node_ids);
+    let bounding_box = full_layout.find_bounding_box_of_dom_nodes(full_layout.root_node, &node_ids);
+    if bounding_box.is_none() {
+        return;
+    }
+    let bounding_box = bounding_box.unwrap();
+
+    for offset in 1..=FOCUS_RING_WIDTH {
+        let offset = offset as f32;
+        display_list.draw_square(bounding_box.x - offset, bounding_box.y - offset, bounding_box.width + (offset * 2.0), bounding_box.height + (offset * 2.0),
+                                 ui_colors.focus_ring);
+    }
+}
+
+
+fn paint_layout_node(display_list: &mut DisplayList, font_context: &FontContext, animation_state: &AnimationState, animation_tick: u32, ui_colors: &UiColors,
+                     full_layout: &FullLayout, document: &Document, layout_node_id: LayoutNodeId) {
+    let layout_node = &full_layout.nodes[layout_node_id];
+    let dom_node_id = layout_node.from_dom_node;
+    let mut opacity = layout_node.opacity;
+    if dom_node_id.is_some() {
+        opacity = animation_state.get_current_number(dom_node_id.unwrap(), AnimatedProperty::Opacity).unwrap_or(opacity);
+    }
 
-    if !layout_node.visible_on_y_location(scroll_y) {
+    if opacity <= 0.0 {
+        //the node still takes up space in the layout, but there is nothing to render
         return;
     }
+    if opacity < 1.0 {
+        display_list.push_opacity(opacity);
+        paint_layout_node_content_and_children(display_list, font_context, animation_state, animation_tick, ui_colors, full_layout, document, layout_node_id);
+        display_list.pop();
+        return;
+    }
+
+    paint_layout_node_content_and_children(display_list, font_context, animation_state, animation_tick, ui_colors, full_layout, document, layout_node_id);
+}
+
+
+fn paint_layout_node_content_and_children(display_list: &mut DisplayList, font_context: &FontContext, animation_state: &AnimationState, animation_tick: u32,
+                                          ui_colors: &UiColors, full_layout: &FullLayout, document: &Document, layout_node_id: LayoutNodeId) {
+    let layout_node = &full_layout.nodes[layout_node_id];
+    let dom_node_id = layout_node.from_dom_node;
+
+    //colors that are in a transition are taken from the animation state, instead of from the layout node
+    let animated_color = |property: AnimatedProperty, layout_color: Color| {
+        if dom_node_id.is_none() {
+            return layout_color;
+        }
+        return animation_state.get_current_color(dom_node_id.unwrap(), property).unwrap_or(layout_color);
+    };
 
     match &layout_node.content {
         LayoutNodeContent::TextLayoutNode(text_layout_node) => {
-            for layout_rect in text_layout_node.rects.iter() {
+            let background_color = animated_color(AnimatedProperty::BackgroundColor, text_layout_node.background_color);
 
-                if text_layout_node.background_color != Color::WHITE {
+            for layout_rect in text_layout_node.rects.iter() {
+                if !background_color.is_transparent() {
                     let location = &layout_rect.location;
-                    platform.fill_rect(location.x, location.y - scroll_y, location.width, location.height, text_layout_node.background_color, 255);
+                    display_list.fill_rect(location.x, location.y, location.width, location.height, background_color);
+                }
+
+                //shadows are painted in reverse order, since the first shadow in the list should end up on top
+                for text_shadow in text_layout_node.text_shadows.iter().rev() {
+                    paint_text_shadow(display_list, layout_rect, text_shadow);
                 }
 
                 if layout_rect.selection_rect.is_some() {
                     let selection_rect = layout_rect.selection_rect.as_ref().unwrap();
-                    platform.fill_rect(selection_rect.x, selection_rect.y - scroll_y, selection_rect.width, selection_rect.height, Color::DEFAULT_SELECTION_COLOR, 255);
+                    display_list.fill_rect(selection_rect.x, selection_rect.y, selection_rect.width, selection_rect.height, Color::DEFAULT_SELECTION_COLOR);
                 }
 
-                let render_y = layout_rect.location.y - scroll_y;
-                platform.render_text(&layout_rect.text, layout_rect.location.x, render_y, &layout_rect.font, layout_rect.font_color);
+                let font_color = animated_color(AnimatedProperty::Color, layout_rect.font_color);
+                display_list.render_text(&layout_rect.text, layout_rect.location.x, layout_rect.location.y, &layout_rect.font, font_color);
             }
         },
         LayoutNodeContent::ImageLayoutNode(image_layout_node) => {
-            platform.render_image(&image_layout_node.image, image_layout_node.location.x, image_layout_node.location.y - scroll_y);
+            display_list.render_image(&image_layout_node.image, image_layout_node.location.x, image_layout_node.location.y);
+        },
+        LayoutNodeContent::ButtonLayoutNode(button_node) => {
+            let dom_node = &document.nodes[layout_node.from_dom_node.unwrap()];
+            if dom_node.page_component.is_none() {
+                //this is a <button> element, its content is rendered as our children
+                let location = &button_node.location;
+                display_list.draw_square(location.x, location.y, location.width, location.height, ui_colors.foreground);
+            } else {
+                let component = dom_node.page_component.as_ref().unwrap().borrow();
+                match component.deref() {
+                    PageComponent::Button(button) => { button.render(display_list, ui_colors); }
+                    PageComponent::TextField(_) | PageComponent::TextArea(_) | PageComponent::FileInput(_) => { panic!("Invalid state"); }
+                }
+            }
         },
-        LayoutNodeContent::ButtonLayoutNode(_) => {
-            let dom_node = layout_node.from_dom_node.as_ref().unwrap().borrow();
+        LayoutNodeContent::TextInputLayoutNode(_) => {
+            let dom_node = &document.nodes[layout_node.from_dom_node.unwrap()];
             let component = dom_node.page_component.as_ref().unwrap().borrow();
             match component.deref() {
-                PageComponent::Button(button) => { button.render(platform, scroll_y); }
-                PageComponent::TextField(_) => { panic!("Invalid state"); }
+                PageComponent::Button(_) | PageComponent::TextArea(_) | PageComponent::FileInput(_) => { panic!("Invalid state"); }
+                PageComponent::TextField(text_field) => { text_field.render(animation_tick, display_list, ui_colors); }
             }
         },
-        LayoutNodeContent::TextInputLayoutNode(_) => {
-            let dom_node = layout_node.from_dom_node.as_ref().unwrap().borrow();
+        LayoutNodeContent::TextAreaLayoutNode(_) => {
+            let dom_node = &document.nodes[layout_node.from_dom_node.unwrap()];
             let component = dom_node.page_component.as_ref().unwrap().borrow();
             match component.deref() {
-                PageComponent::Button(_) => { panic!("Invalid state"); }
-                PageComponent::TextField(text_field) => { text_field.render(ui_state, platform, scroll_y); }
+                PageComponent::Button(_) | PageComponent::TextField(_) | PageComponent::FileInput(_) => { panic!("Invalid state"); }
+                PageComponent::TextArea(text_area) => { text_area.render(animation_tick, display_list, ui_colors); }
+            }
+        },
+        LayoutNodeContent::FileInputLayoutNode(_) => {
+            let dom_node = &document.nodes[layout_node.from_dom_node.unwrap()];
+            let component = dom_node.page_component.as_ref().unwrap().borrow();
+            match component.deref() {
+                PageComponent::Button(_) | PageComponent::TextField(_) | PageComponent::TextArea(_) => { panic!("Invalid state"); }
+                PageComponent::FileInput(file_input) => { file_input.render(font_context, display_list, ui_colors); }
+            }
+        },
+        LayoutNodeContent::IframeLayoutNode(iframe_node) => {
+            let location = &iframe_node.location;
+            for border_offset in 0..(IFRAME_BORDER_WIDTH as usize) {
+                let border_offset = border_offset as f32;
+                display_list.draw_square(location.x + border_offset, location.y + border_offset, location.width - (border_offset * 2.0),
+                                         location.height - (border_offset * 2.0), Color::GRAY);
+            }
+
+            if iframe_node.full_layout.is_some() {
+                display_list.push_clip(iframe_node.content_rect());
+                //the frame has its own scroll position, on top of that of the page it is in
+                display_list.push_scroll_offset(iframe_node.scroll_y);
+                let nested_full_layout = iframe_node.full_layout.as_ref().unwrap();
+                paint_layout_node(display_list, font_context, animation_state, animation_tick, ui_colors, nested_full_layout,
+                                  &iframe_node.document.as_ref().unwrap().borrow(), nested_full_layout.root_node);
+                display_list.pop();
+                display_list.pop();
             }
         },
         LayoutNodeContent::BoxLayoutNode(box_node) => {
-            if box_node.background_color != Color::WHITE { //TODO: don't think this check is correct (also for text nodes,
-                                                           //      because you can have this inside another colored node)
+            if layout_node.display == Display::Block {
+                //TODO: shadows on inline boxes are not supported yet
+                for box_shadow in box_node.box_shadows.iter().rev() {
+                    paint_box_shadow(display_list, &box_node.location, box_shadow);
+                }
+            }
+
+            //The background of inline boxes is painted by the text inside them, because their box can span multiple lines
+            let background_color = animated_color(AnimatedProperty::BackgroundColor, box_node.background_color);
+            if layout_node.display == Display::Block && !background_color.is_transparent() {
                 let location = &box_node.location;
-                platform.fill_rect(location.x, location.y - scroll_y, location.width, location.height, box_node.background_color, 255);
+                display_list.fill_rect(location.x, location.y, location.width, location.height, background_color);
+            }
+
+            if layout_node.display == Display::Block && box_node.background_image.is_some() {
+                //TODO: background images on inline elements are not supported yet
+                paint_background_image(display_list, &box_node.location, box_node.background_image.as_ref().unwrap());
+            }
+
+            if box_node.borders.has_any() {
+                paint_borders(display_list, &box_node.location, &box_node.borders);
+            }
+
+            if box_node.disclosure_marker.is_some() {
+                //the marker is centered on the first line of the summary
+                let first_line_height = if layout_node.children.is_some() {
+                    full_layout.nodes[layout_node.children.as_ref().unwrap()[0]].get_size_of_bounding_box().1
+                } else {
+                    box_node.location.height
+                };
+                let marker_y = box_node.location.y + ((first_line_height - DISCLOSURE_MARKER_SIZE) / 2.0);
+                paint_disclosure_marker(display_list, box_node.location.x + DISCLOSURE_MARKER_SIZE / 2.0, marker_y, box_node.disclosure_marker.as_ref().unwrap());
             }
         },
         LayoutNodeContent::TableLayoutNode(_) => {
@@ -85,10 +304,150 @@ fn render_layout_node(platform: &mut Platform, ui_state: &mut UIState, layout_no
     }
 
     if layout_node.children.is_some() {
-        for child in layout_node.children.as_ref().unwrap() {
-            if child.borrow().visible {
-                render_layout_node(platform, ui_state, &child.borrow());
+        for child_id in layout_node.children.as_ref().unwrap() {
+            if full_layout.nodes[*child_id].visible {
+                paint_layout_node(display_list, font_context, animation_state, animation_tick, ui_colors, full_layout, document, *child_id);
             }
         }
     }
 }
+
+
+fn paint_background_image(display_list: &mut DisplayList, location: &Rect, background_image: &BackgroundImage) {
+    let image = &background_image.image;
+    let tiles = compute_background_tiles(location.x, location.y, location.width, location.height,
+                                         image.width() as f32, image.height() as f32, &background_image.style);
+    if tiles.is_empty() {
+        return;
+    }
+
+    //all tiles have the same size, so we only need to pass the positions
+    let tile_width = tiles[0].width;
+    let tile_height = tiles[0].height;
+    let tile_positions = tiles.iter().map(|tile| Position { x: tile.x, y: tile.y }).collect();
+
+    display_list.render_image_tiles(image, tile_positions, tile_width, tile_height, location.clone());
+}
+
+
+fn paint_borders(display_list: &mut DisplayList, location: &Rect, borders: &Borders) {
+    //the top and bottom border go over the full width, the left and right border fit in between them
+    let top_width = borders.top.used_width();
+    let bottom_width = borders.bottom.used_width();
+    let side_height = (location.height - top_width - bottom_width).max(0.0);
+
+    paint_border_side(display_list, location.x, location.y, location.width, top_width, &borders.top, true);
+    paint_border_side(display_list, location.x, location.y + location.height - bottom_width, location.width, bottom_width, &borders.bottom, true);
+    paint_border_side(display_list, location.x, location.y + top_width, borders.left.used_width(), side_height, &borders.left, false);
+    paint_border_side(display_list, location.x + location.width - borders.right.used_width(), location.y + top_width, borders.right.used_width(),
+                      side_height, &borders.right, false);
+}
+
+
+fn paint_border_side(display_list: &mut DisplayList, x: f32, y: f32, width: f32, height: f32, side: &BorderSide, horizontal: bool) {
+    if width <= 0.0 || height <= 0.0 || side.color.is_transparent() {
+        return;
+    }
+
+    //dashes are 3 times as long as the border is wide, dots are squares of the border width (we don't draw round dots yet)
+    let (segment_length, gap_length) = match side.style {
+        BorderStyle::None => { return; },
+        BorderStyle::Solid => {
+            display_list.fill_rect(x, y, width, height, side.color);
+            return;
+        },
+        BorderStyle::Dashed => (side.width * 3.0, side.width * 3.0),
+        BorderStyle::Dotted => (side.width, side.width),
+    };
+
+    let length = if horizontal { width } else { height };
+    let mut offset = 0.0;
+    while offset < length {
+        let current_segment_length = segment_length.min(length - offset);
+        if horizontal {
+            display_list.fill_rect(x + offset, y, current_segment_length, height, side.color);
+        } else {
+            display_list.fill_rect(x, y + offset, width, current_segment_length, side.color);
+        }
+        offset += segment_length + gap_length;
+    }
+}
+
+
+fn paint_disclosure_marker(display_list: &mut DisplayList, x: f32, y: f32, marker: &DisclosureMarker) {
+    //we draw a filled triangle line by line, pointing down when the details are open, and to the right otherwise
+    let size = DISCLOSURE_MARKER_SIZE;
+    for step in 0..(size as i32) {
+        let offset = step as f32;
+        if marker.open {
+            display_list.draw_line(Position { x: x + offset / 2.0, y: y + offset }, Position { x: x + size - offset / 2.0, y: y + offset }, marker.color);
+        } else {
+            display_list.draw_line(Position { x: x + offset, y: y + offset / 2.0 }, Position { x: x + offset, y: y + size - offset / 2.0 }, marker.color);
+        }
+    }
+}
+
+
+fn paint_box_shadow(display_list: &mut DisplayList, location: &Rect, shadow: &Shadow) {
+    if shadow.inset || shadow.color.is_transparent() {
+        //TODO: inset shadows are not supported yet
+        return;
+    }
+
+    let x = location.x + shadow.offset_x - shadow.spread_radius;
+    let y = location.y + shadow.offset_y - shadow.spread_radius;
+    let width = location.width + (2.0 * shadow.spread_radius);
+    let height = location.height + (2.0 * shadow.spread_radius);
+
+    //TODO: the shadow should only be visible outside of the box, now it shows through boxes with a (semi) transparent background
+    if shadow.blur_radius < 1.0 {
+        display_list.fill_rect(x, y, width, height, shadow.color);
+        return;
+    }
+
+    //We approximate the blur with translucent rects, going from the outside of the blurred edge (blur radius outside the shadow)
+    //to the inside of it (blur radius inside the shadow). The alpha per layer is chosen so that where all layers overlap, we get the
+    //alpha of the shadow color.
+    let number_of_layers = (shadow.blur_radius.ceil() as usize).min(MAX_BOX_SHADOW_BLUR_LAYERS);
+    let full_alpha = shadow.color.a as f32 / 255.0;
+    let layer_alpha = 1.0 - (1.0 - full_alpha).powf(1.0 / number_of_layers as f32);
+    let layer_color = Color::new_with_alpha(shadow.color.r, shadow.color.g, shadow.color.b, (layer_alpha * 255.0).round() as u8);
+
+    for layer_idx in 0..number_of_layers {
+        let expansion = shadow.blur_radius * (1.0 - (2.0 * (layer_idx as f32 + 0.5) / number_of_layers as f32));
+        let layer_width = width + (2.0 * expansion);
+        let layer_height = height + (2.0 * expansion);
+        if layer_width < 1.0 || layer_height < 1.0 {
+            continue;
+        }
+        display_list.fill_rect(x - expansion, y - expansion, layer_width, layer_height, layer_color);
+    }
+}
+
+
+fn paint_text_shadow(display_list: &mut DisplayList, layout_rect: &TextLayoutRect, shadow: &Shadow) {
+    if shadow.color.is_transparent() {
+        return;
+    }
+
+    let x = layout_rect.location.x + shadow.offset_x;
+    let y = layout_rect.location.y + shadow.offset_y;
+
+    if shadow.blur_radius < 1.0 {
+        display_list.render_text(&layout_rect.text, x, y, &layout_rect.font, shadow.color);
+        return;
+    }
+
+    //We approximate the blur by rendering the text a few times around the shadow position with a lower alpha. Glyph strokes are thin,
+    //so a pixel is typically only covered by a few of the copies, which is why we don't divide the alpha over all of them.
+    let sample_distance = shadow.blur_radius / 2.0;
+    let full_alpha = shadow.color.a as f32 / 255.0;
+    let sample_alpha = 1.0 - (1.0 - full_alpha).powf(1.0 / 3.0);
+    let sample_color = Color::new_with_alpha(shadow.color.r, shadow.color.g, shadow.color.b, (sample_alpha * 255.0).round() as u8);
+
+    for offset_y in [-sample_distance, 0.0, sample_distance] {
+        for offset_x in [-sample_distance, 0.0, sample_distance] {
+            display_list.render_text(&layout_rect.text, x + offset_x, y + offset_y, &layout_rect.font, sample_color);
+        }
+    }
+}
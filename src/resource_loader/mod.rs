@@ -0,0 +1,949 @@
+#[cfg(test)] mod tests;
+
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs::{self, metadata};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::sync::atomic::{Ordering, AtomicUsize};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Instant, SystemTime};
+
+use image::DynamicImage;
+use reqwest::Client;
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
+
+use crate::browsing_history::BROWSING_HISTORY;
+use crate::config::{config_file_path, save_config_changes, Config, Settings, SETTINGS, SETTING_DEFINITIONS};
+use crate::debug::debug_log_warn;
+use crate::dom::PostData;
+use crate::jsonify::pretty_print_json;
+use crate::network::url::Url;
+use crate::network::{
+    build_http_client,
+    decode_data_url,
+    decode_form_urlencoded,
+    decode_text,
+    encode_form_urlencoded,
+    encode_form_urlencoded_text,
+    http_get,
+    http_post,
+    percent_decode,
+    CacheMode,
+    HttpClientSettings,
+    HttpResponse,
+    LoadErrorKind,
+    PostBody,
+    ResourceNotLoadedError,
+};
+use crate::network::request_log::{LoggedRequest, REQUEST_LOG};
+use crate::svg;
+
+
+const IMAGE_CACHE_SIZE_BUDGET: usize = 256 * 1024 * 1024; //in bytes of decoded pixels
+
+
+static NEXT_JOB_ID: AtomicUsize = AtomicUsize::new(1);
+pub fn get_next_job_id() -> usize { NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed) }
+
+
+//this is shared by all threads loading images, and by all pages
+static IMAGE_CACHE: LazyLock<Mutex<ImageCache>> = LazyLock::new(|| {
+    return Mutex::new(ImageCache::new(IMAGE_CACHE_SIZE_BUDGET));
+});
+
+
+#[derive(PartialEq)]
+enum RequestType {
+    Get,
+    Post,
+}
+
+//The about: pages are part of the browser, and some of them change things (like the settings), so pages can't load them as a subresource, and only
+//about:config itself may save settings
+#[derive(Clone, Copy, PartialEq)]
+pub enum RequestSource {
+    Navigation, //a page the user navigates to
+    SameAboutPage, //a navigation from an about: page to itself, with what to change (like the settings to save) in the query
+    Subresource, //something a page loads, like an image, a style sheet or a frame
+}
+
+
+struct ResourceRequestJob<T> {
+    #[allow(dead_code)] job_id: usize, //TODO: check if we want to use this (probably for logging / debugging?)
+    url: Url,
+    sender: Sender<T>,
+    request_type: RequestType,
+    body: Option<PostBody>,
+    cache_mode: CacheMode,
+    priority: ResourcePriority,
+    source: RequestSource,
+}
+#[derive(Debug)]
+pub struct ResourceRequestJobTracker<T> {
+    #[allow(dead_code)] pub job_id: usize, //TODO: check if we want to use this (probably for logging / debugging?)
+    pub receiver: Receiver<T>,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct LoadedResource {
+    pub url: Url, //this is where the resource was loaded from in the end, which is different from the requested url when we were redirected
+    pub content_type: Option<String>,
+    pub body: Vec<u8>, //this is empty when loading failed
+    pub error: Option<ResourceNotLoadedError>,
+    pub status: Option<u16>, //this is only set for resources loaded over http
+    pub from_cache: bool,
+}
+impl LoadedResource {
+    fn loaded(url: &Url, content_type: Option<String>, body: Vec<u8>) -> LoadedResource {
+        return LoadedResource { url: url.clone(), content_type, body, error: None, status: None, from_cache: false };
+    }
+    fn failed(url: &Url, error: ResourceNotLoadedError) -> LoadedResource {
+        debug_log_warn(format!("Could not load text: {}", error));
+        return LoadedResource { url: url.clone(), content_type: None, body: Vec::new(), error: Some(error), status: None, from_cache: false };
+    }
+    pub fn crashed(url: &Url, panic_message: String) -> LoadedResource {
+        //this is what we show instead of a page that made us panic
+        let mut error = ResourceNotLoadedError::new(url, LoadErrorKind::Crashed);
+        error.panic_message = Some(panic_message);
+        return LoadedResource { url: url.clone(), content_type: None, body: Vec::new(), error: Some(error), status: None, from_cache: false };
+    }
+    fn from_http_response(response: HttpResponse) -> LoadedResource {
+        return LoadedResource { url: response.url, content_type: response.content_type, body: response.body, error: None, status: Some(response.status),
+                                from_cache: response.from_cache };
+    }
+    pub fn text(&self) -> String {
+        return decode_text(&self.body, self.content_type.as_ref());
+    }
+    pub fn mime_type(&self) -> String {
+        //this is the content type without its parameters (like the charset), we treat resources without a content type as html
+        let content_type = self.content_type.as_ref().map(|content_type| content_type.split(';').next().unwrap().trim().to_lowercase());
+        return content_type.unwrap_or(String::from("text/html"));
+    }
+    pub fn page_html(&self) -> String {
+        return self.page_html_with_downloads_in(&download_directory());
+    }
+    fn page_html_with_downloads_in(&self, download_directory: &Path) -> String {
+        //this is the html we show when the resource is loaded as a page, for anything that is not html itself we make a page to show it
+        if self.error.is_some() {
+            return build_error_page(self.error.as_ref().unwrap());
+        }
+
+        let mime_type = self.mime_type();
+        if mime_type == "text/html" || mime_type == "application/xhtml+xml" {
+            return self.text();
+        }
+        if mime_type == "application/json" || mime_type.ends_with("+json") {
+            return build_text_page(&self.url, &pretty_print_json(&self.text()));
+        }
+        if mime_type.starts_with("text/") || mime_type == "application/javascript" || mime_type == "application/xml" {
+            return build_text_page(&self.url, &self.text());
+        }
+        if mime_type.starts_with("image/") {
+            //TODO: the image is loaded again by the page, it would be better to use the data we already have
+            let url = escape_html(&self.url.to_string());
+            return format!("<html><head><title>{url}</title></head><body><img src=\"{url}\" /></body></html>");
+        }
+
+        return save_download(self, download_directory);
+    }
+}
+
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum ResourcePriority {
+    //the order here is the order in which queued resources are loaded
+    Document,
+    Stylesheet,
+    #[allow(dead_code)] Script, //TODO: use this (and Font) when we load external scripts and fonts
+    #[allow(dead_code)] Font,
+    Image,
+}
+impl ResourcePriority {
+    fn initiator_name(&self) -> &'static str {
+        return match self {
+            ResourcePriority::Document => "document",
+            ResourcePriority::Stylesheet => "stylesheet",
+            ResourcePriority::Script => "script",
+            ResourcePriority::Font => "font",
+            ResourcePriority::Image => "image",
+        };
+    }
+}
+const NR_OF_RESOURCE_PRIORITIES: usize = 5;
+
+
+enum QueuedJob {
+    Text(ResourceRequestJob<LoadedResource>),
+    Image(ResourceRequestJob<Arc<DynamicImage>>),
+}
+impl QueuedJob {
+    fn priority(&self) -> ResourcePriority {
+        return match self {
+            QueuedJob::Text(job) => job.priority,
+            QueuedJob::Image(job) => job.priority,
+        };
+    }
+}
+
+
+struct JobQueue {
+    queued_jobs: Vec<VecDeque<QueuedJob>>, //there is a queue for each priority
+    running_jobs: Vec<AbortHandle>,
+    nr_of_running_jobs: usize,
+    generation: usize, //this is increased when all jobs are cancelled, so jobs that were already running don't change the count for newer jobs
+    nr_of_scheduled_jobs_in_generation: usize,
+    nr_of_finished_jobs_in_generation: usize,
+}
+impl JobQueue {
+    fn new() -> JobQueue {
+        return JobQueue {
+            queued_jobs: (0..NR_OF_RESOURCE_PRIORITIES).map(|_| VecDeque::new()).collect(),
+            running_jobs: Vec::new(),
+            nr_of_running_jobs: 0,
+            generation: 0,
+            nr_of_scheduled_jobs_in_generation: 0,
+            nr_of_finished_jobs_in_generation: 0,
+        };
+    }
+
+    fn pop_next_job(&mut self) -> Option<QueuedJob> {
+        for queue in self.queued_jobs.iter_mut() {
+            if !queue.is_empty() {
+                return queue.pop_front();
+            }
+        }
+        return None;
+    }
+}
+
+
+struct JobScheduler {
+    http_client: Client, //all tasks share this client, so they can reuse open connections
+    queue: Mutex<JobQueue>,
+    max_running_jobs: usize,
+    max_image_decodes: usize,
+    image_decode_permits: Semaphore, //decoding images takes a lot of cpu and memory, so we only decode a few at the same time
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[allow(dead_code)] //TODO: only the job counts are used (for the loading progress), the rest could be shown somewhere for debugging
+pub struct ResourceQueueStats {
+    pub queued_jobs: [usize; NR_OF_RESOURCE_PRIORITIES], //indexed by the priority
+    pub running_jobs: usize,
+    pub decoding_images: usize,
+    pub scheduled_jobs: usize, //this and finished_jobs count from when all jobs were last cancelled, which happens when a page starts loading
+    pub finished_jobs: usize,
+}
+
+
+pub struct ResourceThreadPool {
+    runtime: Runtime, //resources are loaded as async tasks on the threads of this runtime, so one thread can wait for many requests
+    scheduler: Arc<JobScheduler>,
+}
+impl ResourceThreadPool {
+    pub fn new(nr_of_threads: usize, max_running_jobs: usize, max_image_decodes: usize, http_client_settings: &HttpClientSettings) -> ResourceThreadPool {
+        let runtime = Builder::new_multi_thread().worker_threads(nr_of_threads).enable_all().build().unwrap();
+
+        let scheduler = JobScheduler {
+            http_client: build_http_client(http_client_settings),
+            queue: Mutex::new(JobQueue::new()),
+            max_running_jobs,
+            max_image_decodes,
+            image_decode_permits: Semaphore::new(max_image_decodes),
+        };
+
+        return ResourceThreadPool { runtime, scheduler: Arc::new(scheduler) };
+    }
+
+    pub fn cancel_all_jobs(&mut self) {
+        //the trackers for cancelled jobs never get a result, because the sender is dropped with the task (or with the queue)
+        let mut queue = self.scheduler.queue.lock().unwrap();
+        for job in queue.running_jobs.drain(..) {
+            job.abort();
+        }
+        for jobs_with_priority in queue.queued_jobs.iter_mut() {
+            jobs_with_priority.clear();
+        }
+        queue.nr_of_running_jobs = 0;
+        queue.generation += 1;
+        queue.nr_of_scheduled_jobs_in_generation = 0;
+        queue.nr_of_finished_jobs_in_generation = 0;
+    }
+
+    pub fn queue_stats(&self) -> ResourceQueueStats {
+        let queue = self.scheduler.queue.lock().unwrap();
+        let mut queued_jobs = [0; NR_OF_RESOURCE_PRIORITIES];
+        for (idx, jobs_with_priority) in queue.queued_jobs.iter().enumerate() {
+            queued_jobs[idx] = jobs_with_priority.len();
+        }
+
+        let decoding_images = self.scheduler.max_image_decodes - self.scheduler.image_decode_permits.available_permits();
+        return ResourceQueueStats { queued_jobs, running_jobs: queue.nr_of_running_jobs, decoding_images,
+                                    scheduled_jobs: queue.nr_of_scheduled_jobs_in_generation, finished_jobs: queue.nr_of_finished_jobs_in_generation };
+    }
+
+    fn queue_job(&mut self, job: QueuedJob) {
+        {
+            let mut queue = self.scheduler.queue.lock().unwrap();
+            let priority = job.priority();
+            queue.queued_jobs[priority as usize].push_back(job);
+            queue.nr_of_scheduled_jobs_in_generation += 1;
+        }
+        start_queued_jobs(&self.scheduler, self.runtime.handle());
+    }
+}
+
+
+fn start_queued_jobs(scheduler: &Arc<JobScheduler>, runtime: &Handle) {
+    //this is called when a job is queued, and when a job is done, so there is always a job running while there are jobs in the queue
+    let mut queue = scheduler.queue.lock().unwrap();
+    queue.running_jobs.retain(|running_job| !running_job.is_finished());
+
+    while queue.nr_of_running_jobs < scheduler.max_running_jobs {
+        let next_job = queue.pop_next_job();
+        if next_job.is_none() {
+            break;
+        }
+
+        let running_job = RunningJob { scheduler: Arc::clone(scheduler), generation: queue.generation };
+        let task = runtime.spawn(async move {
+            run_job(&running_job.scheduler, next_job.unwrap()).await;
+            drop(running_job);
+        });
+
+        queue.nr_of_running_jobs += 1;
+        queue.running_jobs.push(task.abort_handle());
+    }
+}
+
+
+struct RunningJob {
+    scheduler: Arc<JobScheduler>,
+    generation: usize,
+}
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        //we give back the slot of the job here, and not at the end of the task, so a job that panics or is aborted does not keep its slot
+        {
+            let mut queue = self.scheduler.queue.lock().unwrap();
+            if queue.generation == self.generation {
+                queue.nr_of_running_jobs -= 1;
+                queue.nr_of_finished_jobs_in_generation += 1;
+            }
+        }
+
+        //there is no runtime when the task is dropped because the runtime shuts down, and then we don't need to start anything
+        let runtime = Handle::try_current();
+        if runtime.is_ok() {
+            start_queued_jobs(&self.scheduler, &runtime.unwrap());
+        }
+    }
+}
+
+
+async fn run_job(scheduler: &JobScheduler, job: QueuedJob) {
+    //the receiver is gone when the page that wanted this resource is gone, that is fine, so we ignore errors when sending
+    match job {
+        QueuedJob::Text(job) => {
+            let result = load_text(&scheduler.http_client, &job.url, job.request_type, job.body, job.cache_mode, job.priority, job.source).await;
+            let _ = job.sender.send(result);
+        },
+        QueuedJob::Image(job) => {
+            let result = load_image(&scheduler.http_client, &scheduler.image_decode_permits, &job.url, job.cache_mode).await;
+            let _ = job.sender.send(result);
+        },
+    }
+}
+
+
+pub fn schedule_load_text(url: &Url, priority: ResourcePriority, cache_mode: CacheMode, source: RequestSource, resource_thread_pool: &mut ResourceThreadPool)
+                          -> ResourceRequestJobTracker<LoadedResource> {
+    let (sender, receiver) = channel::<LoadedResource>();
+    let job_id = get_next_job_id();
+
+    let job = ResourceRequestJob { job_id, url: url.clone(), sender, request_type: RequestType::Get, body: None, cache_mode, priority, source };
+    let job_tracker = ResourceRequestJobTracker { job_id, receiver };
+
+    resource_thread_pool.queue_job(QueuedJob::Text(job));
+
+    return job_tracker;
+}
+
+
+pub fn submit_post(post_data: &PostData, resource_thread_pool: &mut ResourceThreadPool) -> ResourceRequestJobTracker<LoadedResource> {
+    let (sender, receiver) = channel::<LoadedResource>();
+    let job_id = get_next_job_id();
+
+    let body = if post_data.multipart {
+        PostBody::MultipartFormData { fields: post_data.fields.clone(), files: post_data.files.clone() }
+    } else {
+        PostBody::UrlEncoded(encode_form_urlencoded(&post_data.fields))
+    };
+
+    let job = ResourceRequestJob { job_id, url: post_data.url.clone(), sender, request_type: RequestType::Post, body: Some(body), cache_mode: CacheMode::Default,
+                                   priority: ResourcePriority::Document, source: RequestSource::Navigation };
+    let job_tracker = ResourceRequestJobTracker { job_id, receiver };
+
+    resource_thread_pool.queue_job(QueuedJob::Text(job));
+
+    return job_tracker;
+}
+
+
+async fn load_text(http_client: &Client, url: &Url, request_type: RequestType, body: Option<PostBody>, cache_mode: CacheMode, priority: ResourcePriority,
+                   source: RequestSource) -> LoadedResource { //TODO: this should not be text specific, we need to refactor this a bit
+    //about: pages are part of the browser, so they are not requests we show in the request log (that would include about:network itself)
+    if url.scheme == "about" {
+        return load_resource(http_client, url, request_type, body, cache_mode, source).await;
+    }
+
+    let started_at = SystemTime::now();
+    let start_instant = Instant::now();
+    let method = if request_type == RequestType::Post { "POST" } else { "GET" };
+    let resource = load_resource(http_client, url, request_type, body, cache_mode, source).await;
+
+    let error_kind = resource.error.as_ref().map(|error| error.kind);
+    let status = match error_kind {
+        Some(LoadErrorKind::HttpStatus(status)) => Some(status),
+        Some(LoadErrorKind::AuthenticationRequired) => Some(401),
+        _ => resource.status,
+    };
+    let mime_type = if resource.error.is_some() { None } else { Some(resource.mime_type()) };
+    REQUEST_LOG.lock().unwrap().record(LoggedRequest { url: url.to_string(), method, status, mime_type, size: resource.body.len(), from_cache: resource.from_cache,
+                                                       started_at, duration: start_instant.elapsed(), initiator: priority.initiator_name(), error: error_kind });
+    return resource;
+}
+
+
+async fn load_resource(http_client: &Client, url: &Url, request_type: RequestType, body: Option<PostBody>, cache_mode: CacheMode,
+                       source: RequestSource) -> LoadedResource {
+    let view_source_target = url.view_source_target();
+    if view_source_target.is_some() {
+        //we load the page itself, and show what we got as text, the same way we show text files
+        //TODO: this requests the page again, it would be nicer to show the source we already have (and POST results can't be shown this way)
+        //the view-source: url is not the page of the settings itself, so it never saves them
+        let view_source_request = if source == RequestSource::Subresource { RequestSource::Subresource } else { RequestSource::Navigation };
+        let page_source = Box::pin(load_resource(http_client, &view_source_target.unwrap(), RequestType::Get, None, cache_mode, view_source_request)).await;
+        if page_source.error.is_some() {
+            return LoadedResource::failed(url, page_source.error.unwrap());
+        }
+        return LoadedResource::loaded(url, Some(String::from("text/plain;charset=utf-8")), page_source.text().into_bytes());
+    }
+
+    if url.scheme == "about" {
+        //otherwise any page could clear the history with <img src="about:history?clear=1">, or change the settings
+        if source == RequestSource::Subresource || request_type != RequestType::Get {
+            return LoadedResource::failed(url, ResourceNotLoadedError::new(url, LoadErrorKind::Blocked));
+        }
+        return build_about_page(&url, source == RequestSource::SameAboutPage);
+    }
+
+    if url.scheme == "file" {
+        //we don't know the page here, the document only requests local files as subresources when the page itself is a local file
+        if request_type == RequestType::Get {
+            let mut local_path = String::from("//");
+            local_path.push_str(&url.path.join("/"));
+            let local_path = PathBuf::from(String::from_utf8_lossy(&percent_decode(&local_path)).into_owned());
+
+            if local_path.is_dir() {
+                return LoadedResource::loaded(url, Some(String::from("text/html")), build_directory_listing(url, &local_path).into_bytes());
+            }
+
+            let read_result = fs::read(local_path);
+            if read_result.is_err() {
+                return LoadedResource::failed(url, ResourceNotLoadedError::new(url, LoadErrorKind::FileNotFound));
+            }
+
+            let content_type = url.file_extension().and_then(|extension| content_type_for_extension(&extension));
+            return LoadedResource::loaded(url, content_type.map(String::from), read_result.unwrap());
+        } else {
+            todo!(); //TODO: report some kind of non-crashing error
+        }
+    }
+
+    if url.scheme == "data" {
+        //the content is in the url itself, so it does not matter whether this is a GET or a POST
+        let data_url_result = decode_data_url(url);
+        if data_url_result.is_none() {
+            return LoadedResource::failed(url, ResourceNotLoadedError::new(url, LoadErrorKind::Other));
+        }
+        let (content_type, content) = data_url_result.unwrap();
+        return LoadedResource::loaded(url, Some(content_type), content);
+    }
+
+    let response_result = match request_type {
+        RequestType::Get => http_get(http_client, url, cache_mode).await,
+        RequestType::Post => http_post(http_client, url, body.unwrap_or(PostBody::UrlEncoded(String::new()))).await,
+    };
+
+    if response_result.is_err() {
+        return LoadedResource::failed(url, response_result.err().unwrap());
+    }
+
+    return LoadedResource::from_http_response(response_result.unwrap());
+}
+
+
+fn build_directory_listing(url: &Url, directory: &Path) -> String {
+    //folders come first, and both folders and files are sorted by name
+    let read_result = fs::read_dir(directory);
+    let mut entries: Vec<(bool, String)> = match read_result {
+        Ok(read_dir) => read_dir.filter_map(|entry| entry.ok())
+                                .map(|entry| (!entry.path().is_dir(), entry.file_name().to_string_lossy().into_owned())).collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+
+    let mut directory_url = url.to_string();
+    if !directory_url.ends_with('/') {
+        directory_url.push('/');
+    }
+    let title = escape_html(&format!("Index of {}", directory.to_string_lossy()));
+
+    let mut html = format!("<html><head><title>{title}</title></head><body><h1>{title}</h1>");
+    if directory.parent().is_some() {
+        html += format!("<a href=\"{}../\">..</a><br />", escape_html(&directory_url)).as_str();
+    }
+    for (is_file, name) in entries {
+        let link_name = if is_file { name.clone() } else { format!("{}/", name) };
+        //TODO: names with characters that have a meaning in urls (like # or ?) should be percent-encoded in the link
+        html += format!("<a href=\"{}{}\">{}</a><br />", escape_html(&directory_url), escape_html(&link_name), escape_html(&link_name)).as_str();
+    }
+    html += "</body></html>";
+    return html;
+}
+
+
+fn content_type_for_extension(extension: &String) -> Option<&'static str> {
+    //local files don't have a content type, so we guess it from the extension, files we don't know are shown as html
+    return match extension.to_lowercase().as_str() {
+        "html" | "htm" => Some("text/html"),
+        "txt" | "md" | "rs" => Some("text/plain"),
+        "css" => Some("text/css"),
+        "js" => Some("text/javascript"),
+        "json" => Some("application/json"),
+        "xml" => Some("application/xml"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        "pdf" => Some("application/pdf"),
+        "zip" => Some("application/zip"),
+        _ => None,
+    };
+}
+
+
+pub fn escape_html(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+}
+
+
+fn build_text_page(url: &Url, text: &str) -> String {
+    //TODO: we don't support white-space: pre yet, so we keep the lines and spaces ourselves, instead of putting the text in a <pre>
+    let lines: Vec<String> = text.lines().map(|line| escape_html(line).replace(' ', "&nbsp;")).collect();
+    return format!("<html><head><title>{}</title></head><body><div style=\"font-family: monospace\">{}</div></body></html>",
+                   escape_html(&url.to_string()), lines.join("<br />"));
+}
+
+
+fn save_download(resource: &LoadedResource, download_directory: &Path) -> String {
+    //content we can't show is saved as a file, and we show a page with where we saved it
+    //TODO: other browsers keep showing the page we came from when downloading, and show the download somewhere else
+    let file_name = resource.url.path.iter().rev().find(|part| !part.is_empty()).cloned().unwrap_or(String::from("download"));
+    let path = save_in_directory(download_directory, &file_name, &resource.body);
+    let url = escape_html(&resource.url.to_string());
+    if path.is_none() {
+        return format!("<html><head><title>Download failed</title></head><body><h1>Download failed</h1><p>{url} could not be saved.</p></body></html>");
+    }
+
+    let path = escape_html(&path.unwrap().to_string_lossy());
+    return format!("<html><head><title>Downloaded {url}</title></head><body><h1>Download complete</h1><p>{url} was saved to {path}</p></body></html>");
+}
+
+
+pub fn save_in_download_directory(file_name: &String, content: &[u8]) -> Option<PathBuf> {
+    return save_in_directory(&download_directory(), file_name, content);
+}
+
+
+fn download_directory() -> PathBuf {
+    let home_directory = env::var("HOME").or(env::var("USERPROFILE")).map(PathBuf::from);
+    let download_directory = home_directory.map(|home_directory| home_directory.join("Downloads")).unwrap_or(PathBuf::from("."));
+    if !download_directory.is_dir() {
+        return PathBuf::from(".");
+    }
+    return download_directory;
+}
+
+
+fn save_in_directory(directory: &Path, file_name: &String, content: &[u8]) -> Option<PathBuf> {
+    let path = unused_download_path(directory, file_name);
+    if fs::write(&path, content).is_err() {
+        debug_log_warn(format!("Could not save download: {:?}", path));
+        return None;
+    }
+    return Some(path);
+}
+
+
+fn unused_download_path(directory: &Path, file_name: &String) -> PathBuf {
+    //we don't overwrite earlier downloads, but add a number to the name, like "file (1).zip"
+    let path = directory.join(file_name);
+    if !path.exists() {
+        return path;
+    }
+
+    let (stem, extension) = match file_name.rfind('.') {
+        Some(dot_idx) if dot_idx > 0 => (&file_name[..dot_idx], &file_name[dot_idx..]),
+        _ => (file_name.as_str(), ""),
+    };
+    let mut number = 1;
+    loop {
+        let path = directory.join(format!("{} ({}){}", stem, number, extension));
+        if !path.exists() {
+            return path;
+        }
+        number += 1;
+    }
+}
+
+
+fn build_error_page(error: &ResourceNotLoadedError) -> String {
+    let (title, explanation) = match error.kind {
+        LoadErrorKind::DnsFailure => ("Server not found", String::from("We could not find the server of this address.")),
+        LoadErrorKind::ConnectionFailed => ("Unable to connect", String::from("The server could not be reached.")),
+        LoadErrorKind::TlsError => ("Secure connection failed", String::from("The secure connection to the server could not be set up.")),
+        LoadErrorKind::Timeout => ("The connection timed out", String::from("The server took too long to respond.")),
+        LoadErrorKind::HttpStatus(404) => ("Page not found", String::from("The server could not find this page (404).")),
+        LoadErrorKind::HttpStatus(status) if status >= 500 => ("Server error", format!("Something went wrong on the server ({}).", status)),
+        LoadErrorKind::HttpStatus(status) => ("Could not load the page", format!("The server responded with status {}.", status)),
+        LoadErrorKind::AuthenticationRequired => ("Authentication required", String::from("You need to log in to see this page.")),
+        LoadErrorKind::TooManyRedirects => ("Too many redirects", String::from("The server keeps redirecting, so the page never loads.")),
+        LoadErrorKind::FileNotFound => ("File not found", String::from("This file does not exist, or could not be read.")),
+        LoadErrorKind::Other => ("Could not load the page", String::from("Something went wrong while loading this page.")),
+        LoadErrorKind::Blocked => ("This page is blocked", String::from("Pages of the browser itself can only be opened directly.")),
+        LoadErrorKind::Crashed => ("This page crashed", format!("The page uses something the browser does not support yet: {}",
+                                                                escape_html(error.panic_message.as_deref().unwrap_or("unknown error")))),
+    };
+
+    let url = escape_html(&error.url);
+    return format!("<html><head><title>{title}</title></head><body><h1>{title}</h1><p>{explanation}</p><p>{url}</p>\
+                    <a href=\"{url}\">Try again</a></body></html>");
+}
+
+
+fn build_about_page(url: &Url, from_same_page: bool) -> LoadedResource {
+    //the query can only change things (like clearing the history) when it comes from the page itself, otherwise any website could link to it
+
+    if url.path.len() == 1 && url.path.iter().next().unwrap().as_str() == "home" {
+        let our_path = env::current_dir().unwrap();
+        let mut local_file_urls = Vec::new();
+
+        get_all_html_in_folder(our_path, &mut local_file_urls);
+
+        let mut html = String::from("<html><h1>Webcrustacean Home<h1><br />");
+        for local_file_url in local_file_urls {
+            let file_url = &local_file_url.into_os_string().into_string().unwrap();
+            html += format!("<a href=\"file://{file_url}\">{file_url}</a><br />").as_str();
+        }
+
+        return LoadedResource::loaded(url, Some(String::from("text/html")), html.into_bytes());
+    }
+
+    if url.path.len() == 1 && url.path.iter().next().unwrap().as_str() == "history" {
+        return LoadedResource::loaded(url, Some(String::from("text/html")), build_history_page(url, from_same_page).into_bytes());
+    }
+
+    if url.path.len() == 1 && url.path.iter().next().unwrap().as_str() == "network" {
+        return LoadedResource::loaded(url, Some(String::from("text/html")), build_network_page(url, from_same_page).into_bytes());
+    }
+
+    if url.path.len() == 1 && url.path.iter().next().unwrap().as_str() == "config" {
+        return LoadedResource::loaded(url, Some(String::from("text/html")), build_config_page(url, from_same_page).into_bytes());
+    }
+
+    return LoadedResource::failed(url, ResourceNotLoadedError::new(url, LoadErrorKind::FileNotFound));
+}
+
+
+fn build_history_page(url: &Url, may_change_history: bool) -> String {
+    //deleting is done with links back to this page, that have what to delete in the query (next to what was searched for)
+    let query = decode_form_urlencoded(&url.query);
+    let search_text = query.get("q").cloned().unwrap_or(String::new());
+    let mut browsing_history = BROWSING_HISTORY.lock().unwrap();
+    if may_change_history && query.contains_key("clear") {
+        browsing_history.clear();
+    }
+    if may_change_history && query.contains_key("delete") {
+        browsing_history.remove(&Url::from(query.get("delete").unwrap()));
+    }
+
+    let mut html = format!("<html><head><title>History</title></head><body><h1>History</h1>\
+                            <form action=\"about:history\"><input type=\"text\" name=\"q\" value=\"{}\"> <input type=\"submit\" value=\"Search\"></form>\
+                            <p><a href=\"about:history?clear=1\">Clear all history</a></p>", escape_html(&search_text));
+
+    let now = SystemTime::now();
+    let found_pages = browsing_history.search(&search_text);
+    if found_pages.is_empty() {
+        html += "<p>No visited pages found.</p>";
+    }
+    for page in found_pages {
+        let page_url = page.url.to_string();
+        let title = if page.title.is_empty() { &page_url } else { &page.title };
+        let last_visit = match page.days_since_last_visit(now) {
+            0 => String::from("today"),
+            1 => String::from("yesterday"),
+            days => format!("{} days ago", days),
+        };
+        let delete_url = format!("about:history?q={}&delete={}", encode_form_urlencoded_text(&search_text), encode_form_urlencoded_text(&page_url));
+        html += format!("<a href=\"{}\">{}</a> {} (visited {} times, last {}) <a href=\"{}\">delete</a><br />", escape_html(&page_url), escape_html(title),
+                        escape_html(&page_url), page.visit_count, last_visit, escape_html(&delete_url)).as_str();
+    }
+    html += "</body></html>";
+
+    return html;
+}
+
+
+fn build_network_page(url: &Url, may_change_log: bool) -> String {
+    //this shows the requests we made (newest first), clearing and exporting is done with links back to this page
+    let query = decode_form_urlencoded(&url.query);
+    let mut request_log = REQUEST_LOG.lock().unwrap();
+    let mut html = String::from("<html><head><title>Network</title></head><body><h1>Network</h1>\
+                                 <p><a href=\"about:network\">Refresh</a> <a href=\"about:network?clear=1\">Clear</a> \
+                                 <a href=\"about:network?export=har\">Export as HAR</a></p>");
+
+    if may_change_log && query.contains_key("clear") {
+        request_log.clear();
+    }
+    if may_change_log && query.contains_key("export") {
+        let path = save_in_download_directory(&String::from("webcrustacean.har"), request_log.to_har().as_bytes());
+        if path.is_some() {
+            html += format!("<p>The requests are saved to {}</p>", escape_html(&path.unwrap().to_string_lossy())).as_str();
+        } else {
+            html += "<p>The requests could not be saved.</p>";
+        }
+    }
+
+    if request_log.requests().is_empty() {
+        html += "<p>No requests made yet.</p>";
+    }
+    for request in request_log.requests().iter().rev() {
+        let status = if request.status.is_some() {
+            request.status.unwrap().to_string()
+        } else if request.error.is_some() {
+            format!("{:?}", request.error.unwrap())
+        } else {
+            String::from("done")
+        };
+        let cache_note = if request.from_cache { ", from cache" } else { "" };
+        let size = if request.size >= 1024 { format!("{:.1} kB", request.size as f32 / 1024.0) } else { format!("{} B", request.size) };
+        let url = escape_html(&request.url);
+        html += format!("{} <b>{}</b> <a href=\"{}\">{}</a> ({}, {}, {}, {} ms{})<br />", request.method, status, url, url, request.initiator,
+                        escape_html(request.mime_type.as_deref().unwrap_or("")), size, request.duration.as_millis(), cache_note).as_str();
+    }
+    html += "</body></html>";
+
+    return html;
+}
+
+
+fn build_config_page(url: &Url, may_save_settings: bool) -> String {
+    //saving is done by submitting the form back to this page, with the settings in the query
+    let query = if may_save_settings { decode_form_urlencoded(&url.query) } else { HashMap::new() };
+    let mut config = Config::load();
+    let changes: Vec<(String, Option<String>)> = SETTING_DEFINITIONS.iter().filter(|definition| query.contains_key(definition.key)).map(|definition| {
+        let value = query.get(definition.key).unwrap().trim();
+        //settings that are (set back to) their default are removed from the file, so a changed default is picked up later
+        let value = if value.is_empty() || value == definition.default_value { None } else { Some(value.to_owned()) };
+        return (definition.key.to_owned(), value);
+    }).collect();
+    if !changes.is_empty() {
+        config = save_config_changes(&changes);
+        *SETTINGS.lock().unwrap() = Settings::from_config(&config);
+    }
+
+    let config_path = config_file_path().map(|path| path.to_string_lossy().into_owned()).unwrap_or(String::from("(no home folder found)"));
+    let mut html = format!("<html><head><title>Config</title></head><body><h1>Config</h1><p>Settings are saved in {}.</p>", escape_html(&config_path));
+    if !changes.is_empty() {
+        html += "<p>The settings are saved. Changes to the user agent and proxy settings are used after restarting.</p>";
+    }
+
+    html += "<form action=\"about:config\">";
+    for definition in SETTING_DEFINITIONS.iter() {
+        html += format!("<b>{}</b> <input type=\"text\" name=\"{}\" value=\"{}\"> {}<br />", definition.key, definition.key,
+                        escape_html(&config.get_setting(definition.key)), escape_html(definition.description)).as_str();
+    }
+    html += "<input type=\"submit\" value=\"Save\"></form>";
+
+    let other_values: Vec<(String, String)> = config.all_values().into_iter()
+                                                    .filter(|(key, _)| !SETTING_DEFINITIONS.iter().any(|definition| definition.key == key)).collect();
+    if !other_values.is_empty() {
+        html += "<h2>Other values</h2><p>These can only be changed in the config file, and are used after restarting.</p>";
+        for (key, value) in other_values {
+            html += format!("<b>{}</b> = {}<br />", escape_html(&key), escape_html(&value)).as_str();
+        }
+    }
+    html += "</body></html>";
+
+    return html;
+}
+
+
+fn get_all_html_in_folder(folder_path: PathBuf, local_file_urls: &mut Vec<PathBuf>) {
+    //TODO: test the folder walking code on windows
+    let files_in_current_folder = fs::read_dir(folder_path).unwrap();
+    for file in files_in_current_folder {
+        let path = file.as_ref().unwrap().path();
+        if metadata(&path).unwrap().is_dir() {
+            get_all_html_in_folder(path, local_file_urls);
+        } else {
+            if path.extension().is_some() && path.extension().unwrap().to_str().unwrap() == "html" {
+                local_file_urls.push(path);
+            }
+        }
+    }
+}
+
+
+
+pub fn schedule_load_image(url: &Url, cache_mode: CacheMode, resource_thread_pool: &mut ResourceThreadPool) -> ResourceRequestJobTracker<Arc<DynamicImage>> {
+    let (sender, receiver) = channel::<Arc<DynamicImage>>();
+    let job_id = get_next_job_id();
+
+    if !SETTINGS.lock().unwrap().images_enabled {
+        //images that are not loaded are shown in the same way as images that could not be loaded
+        sender.send(fallback_image()).unwrap();
+        return ResourceRequestJobTracker { job_id, receiver };
+    }
+
+    //on a hard reload we don't use the decoded image we have, the image we load replaces it in the cache
+    let cached_image = if cache_mode == CacheMode::Reload { None } else { IMAGE_CACHE.lock().unwrap().get(url) };
+    if cached_image.is_some() {
+        //we don't need to queue anything, the image is ready when the tracker is checked
+        sender.send(cached_image.unwrap()).unwrap();
+        return ResourceRequestJobTracker { job_id, receiver };
+    }
+
+    let job = ResourceRequestJob { job_id, url: url.clone(), sender, request_type: RequestType::Get, body: None, cache_mode,
+                                   priority: ResourcePriority::Image, source: RequestSource::Subresource };
+    let job_tracker = ResourceRequestJobTracker { job_id, receiver };
+
+    resource_thread_pool.queue_job(QueuedJob::Image(job));
+
+    return job_tracker;
+}
+
+
+async fn load_image(http_client: &Client, image_decode_permits: &Semaphore, url: &Url, cache_mode: CacheMode) -> Arc<DynamicImage> {
+    //the same image might have been queued multiple times, so it could be loaded by an earlier job by now
+    //TODO: on a hard reload we can't tell an image loaded by an earlier job from the one we had before, so the same image is loaded again
+    if cache_mode != CacheMode::Reload {
+        let cached_image = IMAGE_CACHE.lock().unwrap().get(url);
+        if cached_image.is_some() {
+            return cached_image.unwrap();
+        }
+    }
+
+    #[cfg(debug_assertions)] println!("loading {}", url.to_string());
+
+    let resource = load_text(http_client, url, RequestType::Get, None, cache_mode, ResourcePriority::Image, RequestSource::Subresource).await;
+    if resource.error.is_some() {
+        debug_log_warn(format!("Could not load image: {}", url.to_string()));
+        return fallback_image();
+    }
+
+    //decoding takes a while for big images, so we do that outside of the threads that handle the network, and only a few at the same time
+    let extension = url.file_extension();
+    let is_svg = (extension.is_some() && extension.unwrap() == "svg") || resource.mime_type() == "image/svg+xml";
+    let _decode_permit = image_decode_permits.acquire().await;
+    let decode_result = tokio::task::spawn_blocking(move || decode_image(&resource, is_svg)).await;
+
+    if decode_result.is_err() || decode_result.as_ref().unwrap().is_none() {
+        return fallback_image();
+    }
+    let image = Arc::new(decode_result.unwrap().unwrap());
+    IMAGE_CACHE.lock().unwrap().insert(url, Arc::clone(&image));
+    return image;
+}
+
+
+fn decode_image(resource: &LoadedResource, is_svg: bool) -> Option<DynamicImage> {
+    if is_svg {
+        let svg_image = svg::render_svg(&resource.text());
+        if svg_image.is_none() {
+            debug_log_warn(format!("Could not render svg: {}", resource.url.to_string()));
+        }
+        return svg_image;
+    }
+
+    let image_result = image::load_from_memory(&resource.body);
+    if image_result.is_err() {
+        debug_log_warn(format!("Could not decode image: {}", resource.url.to_string()));
+        return None;
+    }
+    return Some(image_result.unwrap());
+}
+
+
+pub fn fallback_image() -> Arc<DynamicImage> {
+    //TODO: this should become one of those "broken image"-images
+    return Arc::new(DynamicImage::new_rgb8(1, 1));
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct CachedImage {
+    image: Arc<DynamicImage>,
+    size: usize, //the number of bytes the decoded pixels take
+    last_used: usize,
+}
+
+
+pub struct ImageCache {
+    //decoded images are kept by their url, and shared by everything that shows them, the least recently used ones are removed when
+    //the images together take more memory than the budget (images that are still shown stay in memory until they are not used anymore)
+    entries: HashMap<String, CachedImage>,
+    total_size: usize,
+    size_budget: usize,
+    use_counter: usize,
+}
+impl ImageCache {
+    pub fn new(size_budget: usize) -> ImageCache {
+        return ImageCache { entries: HashMap::new(), total_size: 0, size_budget, use_counter: 0 };
+    }
+
+    pub fn get(&mut self, url: &Url) -> Option<Arc<DynamicImage>> {
+        self.use_counter += 1;
+        let entry = self.entries.get_mut(&url.without_fragment().to_string())?;
+        entry.last_used = self.use_counter;
+        return Some(Arc::clone(&entry.image));
+    }
+
+    pub fn insert(&mut self, url: &Url, image: Arc<DynamicImage>) {
+        let size = image.as_bytes().len();
+        if size > self.size_budget {
+            return;
+        }
+
+        self.use_counter += 1;
+        let previous_entry = self.entries.insert(url.without_fragment().to_string(), CachedImage { image, size, last_used: self.use_counter });
+        if previous_entry.is_some() {
+            self.total_size -= previous_entry.unwrap().size;
+        }
+        self.total_size += size;
+
+        while self.total_size > self.size_budget {
+            let least_recently_used = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone()).unwrap();
+            let removed_entry = self.entries.remove(&least_recently_used).unwrap();
+            self.total_size -= removed_entry.size;
+        }
+    }
+}
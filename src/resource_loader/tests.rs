@@ -0,0 +1,230 @@
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use std::sync::mpsc::channel;
+use std::time::{Duration, SystemTime};
+
+use image::DynamicImage;
+
+use crate::config::Config;
+use crate::network::{CacheMode, HttpClientSettings, LoadErrorKind, ProxySettings, ResourceNotLoadedError, UA_FIREFOX_WINDOWS};
+use crate::network::url::Url;
+use crate::resource_loader::{schedule_load_image, schedule_load_text, JobQueue, QueuedJob, RequestSource, RequestType, ResourcePriority, ResourceRequestJob};
+use crate::network::request_log::{LoggedRequest, REQUEST_LOG};
+use crate::resource_loader::{build_about_page, ImageCache, LoadedResource, ResourceThreadPool, RunningJob, IMAGE_CACHE};
+
+
+fn build_thread_pool(max_running_jobs: usize) -> ResourceThreadPool {
+    let settings = HttpClientSettings { max_idle_connections_per_host: 1, connect_timeout: Duration::from_secs(1), request_timeout: Duration::from_secs(1),
+                                        user_agent: String::from(UA_FIREFOX_WINDOWS), proxy: ProxySettings::from_config(&Config::parse(&String::new())),
+                                        host_overrides: Vec::new() };
+    return ResourceThreadPool::new(1, max_running_jobs, 1, &settings);
+}
+
+
+fn build_queued_job(url: &str, priority: ResourcePriority) -> QueuedJob {
+    let (sender, _) = channel();
+    return QueuedJob::Text(ResourceRequestJob { job_id: 0, url: Url::from(&String::from(url)), sender, request_type: RequestType::Get, body: None,
+                                                cache_mode: CacheMode::Default, priority, source: RequestSource::Subresource });
+}
+
+
+fn queued_job_url(job: Option<QueuedJob>) -> String {
+    return match job.unwrap() {
+        QueuedJob::Text(job) => job.url.to_string(),
+        QueuedJob::Image(job) => job.url.to_string(),
+    };
+}
+
+
+#[test]
+fn test_jobs_are_started_by_priority() {
+    let mut queue = JobQueue::new();
+    for (url, priority) in [("http://a.com/1.png", ResourcePriority::Image), ("http://a.com/", ResourcePriority::Document),
+                            ("http://a.com/2.png", ResourcePriority::Image), ("http://a.com/a.css", ResourcePriority::Stylesheet)] {
+        let job = build_queued_job(url, priority);
+        queue.queued_jobs[priority as usize].push_back(job);
+    }
+
+    //jobs with a higher priority go first, and jobs with the same priority in the order they were queued
+    assert_eq!(queued_job_url(queue.pop_next_job()), "http://a.com/");
+    assert_eq!(queued_job_url(queue.pop_next_job()), "http://a.com/a.css");
+    assert_eq!(queued_job_url(queue.pop_next_job()), "http://a.com/1.png");
+    assert_eq!(queued_job_url(queue.pop_next_job()), "http://a.com/2.png");
+    assert!(queue.pop_next_job().is_none());
+}
+
+
+#[test]
+fn test_cancel_all_jobs_resets_the_counts() {
+    //without running slots the jobs stay in the queue, so we know exactly what is queued
+    let mut thread_pool = build_thread_pool(0);
+    let url = Url::from(&String::from("http://a.com/a.css"));
+    let _tracker = schedule_load_text(&url, ResourcePriority::Stylesheet, CacheMode::Default, RequestSource::Subresource, &mut thread_pool);
+    let _tracker = schedule_load_text(&url, ResourcePriority::Document, CacheMode::Default, RequestSource::Navigation, &mut thread_pool);
+    thread_pool.scheduler.queue.lock().unwrap().nr_of_running_jobs = 2;
+
+    let stats = thread_pool.queue_stats();
+    assert_eq!(stats.queued_jobs, [1, 1, 0, 0, 0]);
+    assert_eq!(stats.running_jobs, 2);
+    assert_eq!(stats.scheduled_jobs, 2);
+
+    thread_pool.cancel_all_jobs();
+    let stats = thread_pool.queue_stats();
+    assert_eq!(stats.queued_jobs, [0, 0, 0, 0, 0]);
+    assert_eq!(stats.running_jobs, 0);
+    assert_eq!(stats.scheduled_jobs, 0);
+    assert_eq!(stats.finished_jobs, 0);
+    assert_eq!(thread_pool.scheduler.queue.lock().unwrap().generation, 1);
+
+    //a job that was running before the cancel does not change the counts when it is done
+    drop(RunningJob { scheduler: Arc::clone(&thread_pool.scheduler), generation: 0 });
+    assert_eq!(thread_pool.queue_stats().running_jobs, 0);
+    assert_eq!(thread_pool.queue_stats().finished_jobs, 0);
+}
+
+
+#[test]
+fn test_a_panicking_job_gives_back_its_slot() {
+    let thread_pool = build_thread_pool(1);
+    thread_pool.scheduler.queue.lock().unwrap().nr_of_running_jobs = 1;
+
+    let running_job = RunningJob { scheduler: Arc::clone(&thread_pool.scheduler), generation: 0 };
+    let task = thread_pool.runtime.spawn(async move {
+        let _running_job = running_job;
+        panic!("the job failed");
+    });
+    assert!(thread_pool.runtime.block_on(task).unwrap_err().is_panic());
+
+    let stats = thread_pool.queue_stats();
+    assert_eq!(stats.running_jobs, 0);
+    assert_eq!(stats.finished_jobs, 1);
+}
+
+
+#[test]
+fn test_image_cache_evicts_least_recently_used_images() {
+    //each of these images takes 400 bytes, so 3 of them fit in the budget
+    let mut image_cache = ImageCache::new(1200);
+    let urls: Vec<Url> = (1..=4).map(|idx| Url::from(&format!("http://a.com/{idx}.png"))).collect();
+    for url in &urls[0..3] {
+        image_cache.insert(url, Arc::new(DynamicImage::new_rgba8(10, 10)));
+    }
+    assert_eq!(image_cache.total_size, 1200);
+
+    //using the first image makes the second one the least recently used
+    assert!(image_cache.get(&urls[0]).is_some());
+    image_cache.insert(&urls[3], Arc::new(DynamicImage::new_rgba8(10, 10)));
+    assert!(image_cache.get(&urls[0]).is_some());
+    assert!(image_cache.get(&urls[1]).is_none());
+    assert!(image_cache.get(&urls[2]).is_some());
+    assert!(image_cache.get(&urls[3]).is_some());
+    assert_eq!(image_cache.total_size, 1200);
+
+    //an image that replaces one with the same url only counts once, and the fragment of the url does not matter
+    image_cache.insert(&Url::from(&String::from("http://a.com/4.png#part")), Arc::new(DynamicImage::new_rgba8(5, 10)));
+    assert_eq!(image_cache.total_size, 1000);
+    assert_eq!(image_cache.get(&urls[3]).unwrap().width(), 5);
+
+    //an image bigger than the whole budget is not cached, and does not remove the others
+    image_cache.insert(&urls[1], Arc::new(DynamicImage::new_rgba8(20, 20)));
+    assert!(image_cache.get(&urls[1]).is_none());
+    assert_eq!(image_cache.entries.len(), 3);
+    assert_eq!(image_cache.total_size, 1000);
+}
+
+
+#[test]
+fn test_hard_reload_does_not_use_cached_images() {
+    //without running slots nothing is loaded, so we can see if the image came from the cache, or a job was queued for it
+    let mut thread_pool = build_thread_pool(0);
+    let url = Url::from(&String::from("http://image-cache-test.example.com/image.png"));
+    IMAGE_CACHE.lock().unwrap().insert(&url, Arc::new(DynamicImage::new_rgba8(3, 3)));
+
+    let tracker = schedule_load_image(&url, CacheMode::Default, &mut thread_pool);
+    assert_eq!(tracker.receiver.try_recv().unwrap().width(), 3);
+    assert_eq!(thread_pool.queue_stats().queued_jobs[ResourcePriority::Image as usize], 0);
+
+    let tracker = schedule_load_image(&url, CacheMode::Reload, &mut thread_pool);
+    assert!(tracker.receiver.try_recv().is_err());
+    assert_eq!(thread_pool.queue_stats().queued_jobs[ResourcePriority::Image as usize], 1);
+}
+
+
+#[test]
+fn test_load_errors_give_an_error_page() {
+    let url = Url::from(&String::from("https://www.example.com/page?a=1&b=2"));
+    let cases = [
+        (LoadErrorKind::DnsFailure, "Server not found"),
+        (LoadErrorKind::TlsError, "Secure connection failed"),
+        (LoadErrorKind::Timeout, "The connection timed out"),
+        (LoadErrorKind::HttpStatus(404), "Page not found"),
+        (LoadErrorKind::HttpStatus(500), "Server error"),
+        (LoadErrorKind::HttpStatus(503), "Server error"),
+        (LoadErrorKind::HttpStatus(418), "Could not load the page"),
+    ];
+
+    for (kind, title) in cases {
+        let html = LoadedResource::failed(&url, ResourceNotLoadedError::new(&url, kind)).page_html();
+        assert!(html.contains(&format!("<title>{}</title>", title)), "{:?}", kind);
+
+        //the page says which address failed (escaped, since it is put in the html), and has a link to try it again
+        assert!(html.contains("<a href=\"https://www.example.com/page?a=1&amp;b=2\">Try again</a>"), "{:?}", kind);
+    }
+
+    let html = LoadedResource::failed(&url, ResourceNotLoadedError::new(&url, LoadErrorKind::HttpStatus(500))).page_html();
+    assert!(html.contains("(500)"));
+}
+
+
+#[test]
+fn test_pages_for_content_types() {
+    let url = Url::from(&String::from("https://www.example.com/files/data"));
+    let directory = env::temp_dir().join(format!("webcrustacean_download_test_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let page_html = |content_type: Option<&str>, body: &str| {
+        let resource = LoadedResource::loaded(&url, content_type.map(String::from), body.as_bytes().to_vec());
+        return resource.page_html_with_downloads_in(&directory);
+    };
+
+    //html is shown as it is, also without a content type, and the parameters and casing of the content type don't matter
+    for content_type in [None, Some("text/html"), Some("Text/HTML; charset=UTF-8"), Some("application/xhtml+xml")] {
+        assert_eq!(page_html(content_type, "<p>hi</p>"), "<p>hi</p>");
+    }
+
+    for content_type in ["text/plain", "TEXT/PLAIN;charset=utf-8", "text/css", "application/javascript"] {
+        let html = page_html(Some(content_type), "a <b>\n  c");
+        assert!(html.contains("<div style=\"font-family: monospace\">a&nbsp;&lt;b&gt;<br />&nbsp;&nbsp;c</div>"), "{content_type}");
+    }
+
+    for content_type in ["application/json", "Application/JSON; charset=utf-8", "application/ld+json"] {
+        let html = page_html(Some(content_type), "{\"a\":[1,2]}");
+        assert!(html.contains("{<br />&nbsp;&nbsp;&quot;a&quot;:"), "{content_type}: {html}");
+    }
+
+    for content_type in ["image/png", "IMAGE/SVG+XML; charset=utf-8"] {
+        let html = page_html(Some(content_type), "");
+        assert!(html.contains("<img src=\"https://www.example.com/files/data\" />"), "{content_type}");
+    }
+
+    //content we can't show is saved, and the page says where
+    let html = page_html(Some("Application/Octet-Stream; name=data"), "binary content");
+    let saved_file = directory.join("data");
+    assert!(html.contains("Download complete"));
+    assert!(html.contains(&saved_file.to_string_lossy().to_string()));
+    assert_eq!(fs::read_to_string(&saved_file).unwrap(), "binary content");
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+
+#[test]
+fn test_about_pages_only_change_things_from_the_page_itself() {
+    let logged_url = String::from("http://about-page-test.example.com/");
+    REQUEST_LOG.lock().unwrap().record(LoggedRequest { url: logged_url.clone(), method: "GET", status: Some(200), mime_type: None, size: 0, from_cache: false,
+                                                       started_at: SystemTime::now(), duration: Duration::ZERO, initiator: "document", error: None });
+
+    //a link from another page can show the request log, but not clear it
+    build_about_page(&Url::from(&String::from("about:network?clear=1")), false);
+    assert!(REQUEST_LOG.lock().unwrap().requests().iter().any(|request| request.url == logged_url));
+}
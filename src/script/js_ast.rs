@@ -1,16 +1,31 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::rc::Rc;
 
+use super::js_async;
+use super::js_builtins;
 use super::js_console;
+use super::js_dom;
+use super::js_events;
 use super::js_execution_context::{
+    JsAccessor,
+    JsAddress,
+    JsBoundFunction,
     JsBuiltinFunction,
+    JsEnvironment,
     JsError,
     JsExecutionContext,
     JsFunction,
+    JsInternalSlot,
     JsObject,
     JsValue,
+    JsVariableAssignment,
+    JsVariableLookup,
 };
-use super::js_interpreter::JsInterpreter;
+use super::js_interpreter::{JsInterpreter, MAX_CALL_DEPTH};
+use super::js_promise;
+use super::js_regex::JsRegex;
+use super::js_websocket;
 
 
 pub type Script = Vec<JsAstStatement>;
@@ -20,15 +35,25 @@ pub type Script = Vec<JsAstStatement>;
 pub enum JsAstStatement {
     Expression(JsAstExpression),
     Assign(JsAstAssign),
+    DestructuringAssign(JsAstDestructuringAssign),
     Declaration(JsAstDeclaration),
     FunctionDeclaration(JsAstFunctionDeclaration),  //TODO: a function declaration is not a statement, technically, but we pretend it is for now
                                                     //      (it actually is a "source element", a statement is also a source element)
-    Return(JsAstExpression),
+    Return(Option<JsAstExpression>),
+    Block(Script),
+    If(JsAstIf),
+    While(JsAstWhile),
+    For(JsAstFor),
+    ForIn(JsAstForIn),
+    Break,
+    Continue,
+    Throw(JsAstExpression),
+    Try(JsAstTry),
 }
 impl JsAstStatement {
 
-    pub fn execute(&self, js_interpreter: &mut JsInterpreter) -> bool {
-        //returns a boolean saying whether to run the next statement
+    pub fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsCompletion {
+        //the completion says how the statement ended, anything other than normal means the next statements should not be run
 
         match self {
             JsAstStatement::Expression(expression) => {
@@ -37,19 +62,437 @@ impl JsAstStatement {
             JsAstStatement::Assign(assign) => {
                 assign.execute(js_interpreter)
             },
+            JsAstStatement::DestructuringAssign(assign) => {
+                let value = assign.right.execute(js_interpreter);
+                if !js_interpreter.has_pending_exception() {
+                    assign.pattern.bind(js_interpreter, value, None);
+                }
+            },
             JsAstStatement::Declaration(declaration) => {
                 declaration.execute(js_interpreter)
             },
-            JsAstStatement::FunctionDeclaration(function_declaration) => {
-                function_declaration.execute(js_interpreter);
+            JsAstStatement::FunctionDeclaration(_) => {
+                //the function was already created when the scope it is in was entered, see hoist_lexical_declarations()
             },
             JsAstStatement::Return(return_expression) => {
-                let value = return_expression.execute(js_interpreter);
-                js_interpreter.register_return_value(value);
-                return false;
+                let value = match return_expression {
+                    Some(expression) => expression.execute(js_interpreter),
+                    None => JsValue::Undefined,
+                };
+                return completion_after_expression(js_interpreter, JsCompletion::Return(value));
+            },
+            JsAstStatement::Block(script) => {
+                return js_interpreter.run_block(script);
+            },
+            JsAstStatement::If(if_statement) => {
+                return if_statement.execute(js_interpreter);
+            },
+            JsAstStatement::While(while_loop) => {
+                return while_loop.execute(js_interpreter);
+            },
+            JsAstStatement::For(for_loop) => {
+                return for_loop.execute(js_interpreter);
+            },
+            JsAstStatement::ForIn(for_in_loop) => {
+                return for_in_loop.execute(js_interpreter);
+            },
+            JsAstStatement::Break => {
+                return JsCompletion::Break;
+            },
+            JsAstStatement::Continue => {
+                return JsCompletion::Continue;
+            },
+            JsAstStatement::Throw(expression) => {
+                let value = expression.execute(js_interpreter);
+                return completion_after_expression(js_interpreter, JsCompletion::Throw(value));
+            },
+            JsAstStatement::Try(try_statement) => {
+                return try_statement.execute(js_interpreter);
+            },
+        }
+        return completion_after_expression(js_interpreter, JsCompletion::Normal);
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum JsCompletion {
+    Normal,
+    Return(JsValue),
+    Break,
+    Continue,
+    Throw(JsValue),
+}
+
+
+fn completion_after_expression(js_interpreter: &mut JsInterpreter, completion: JsCompletion) -> JsCompletion {
+    //expressions can't return a completion themselves, so when they throw, they leave the exception on the interpreter for us to pick up
+    let exception = js_interpreter.take_pending_exception();
+    if exception.is_some() {
+        return JsCompletion::Throw(exception.unwrap());
+    }
+    return completion;
+}
+
+
+pub fn hoist_var_declarations(js_interpreter: &mut JsInterpreter, script: &Script) {
+    //var declarations anywhere in a function (outside of nested functions) declare their variable for the whole function, as undefined,
+    //and so do function declarations in blocks, which get their function value when their block is entered
+    let mut names = Vec::new();
+    collect_var_names_in_script(script, &mut names);
+
+    let var_environment = js_interpreter.current_var_environment();
+    for name in names {
+        //a var declaration does not change variables (or arguments) that are already there
+        if !var_environment.borrow().has_own_variable(&name) {
+            let address = js_interpreter.add_new_value(JsValue::Undefined);
+            var_environment.borrow_mut().declare_variable(name, address);
+        }
+    }
+}
+
+
+fn collect_var_names_in_script(script: &Script, names: &mut Vec<String>) {
+    for statement in script {
+        collect_var_names(statement, names);
+    }
+}
+
+
+fn collect_var_names(statement: &JsAstStatement, names: &mut Vec<String>) {
+    match statement {
+        JsAstStatement::Declaration(declaration) if declaration.decl_type == JsDeclType::Var => { declaration.target.collect_bound_names(names); },
+        JsAstStatement::FunctionDeclaration(function_declaration) => { names.push(function_declaration.name.clone()); },
+        JsAstStatement::Block(script) => { collect_var_names_in_script(script, names); },
+        JsAstStatement::If(if_statement) => {
+            collect_var_names_in_script(&if_statement.then_script, names);
+            if if_statement.else_script.is_some() {
+                collect_var_names_in_script(if_statement.else_script.as_ref().unwrap(), names);
+            }
+        },
+        JsAstStatement::While(while_loop) => { collect_var_names_in_script(&while_loop.body, names); },
+        JsAstStatement::For(for_loop) => {
+            if for_loop.init.is_some() {
+                collect_var_names(for_loop.init.as_ref().unwrap(), names);
+            }
+            collect_var_names_in_script(&for_loop.body, names);
+        },
+        JsAstStatement::ForIn(for_in_loop) => {
+            if for_in_loop.decl_type == Some(JsDeclType::Var) {
+                names.push(for_in_loop.variable.name.clone());
+            }
+            collect_var_names_in_script(&for_in_loop.body, names);
+        },
+        JsAstStatement::Try(try_statement) => {
+            collect_var_names_in_script(&try_statement.try_script, names);
+            for script in [&try_statement.catch_script, &try_statement.finally_script] {
+                if script.is_some() {
+                    collect_var_names_in_script(script.as_ref().unwrap(), names);
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+
+pub fn hoist_lexical_declarations(js_interpreter: &mut JsInterpreter, script: &Script) {
+    //the let and const variables of a scope exist from the start of the scope, but can't be used before their declaration runs,
+    //functions declared directly in the scope can already be called before their declaration
+    for statement in script {
+        match statement {
+            JsAstStatement::Declaration(declaration) if declaration.decl_type != JsDeclType::Var => {
+                let is_const = declaration.decl_type == JsDeclType::Const;
+                let mut names = Vec::new();
+                declaration.target.collect_bound_names(&mut names);
+                for name in names {
+                    js_interpreter.current_environment().borrow_mut().declare_uninitialized_variable(name, is_const);
+                }
+            },
+            JsAstStatement::FunctionDeclaration(function_declaration) => {
+                let function_address = function_declaration.instantiate(js_interpreter);
+
+                //TODO: in strict mode, a function declared in a block is only visible in that block
+                let var_environment = js_interpreter.current_var_environment();
+                if !Rc::ptr_eq(&var_environment, &js_interpreter.current_environment()) {
+                    var_environment.borrow_mut().declare_variable(function_declaration.name.clone(), function_address);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+
+fn assign_to_variable(js_interpreter: &mut JsInterpreter, name: &String, value: JsValue) {
+    let target_address = js_interpreter.store_value(value);
+    let assignment = js_interpreter.current_environment().borrow_mut().assign_variable(name.clone(), target_address);
+    match assignment {
+        JsVariableAssignment::Assigned => {},
+        JsVariableAssignment::Uninitialized => {
+            js_interpreter.throw_error(JsError::ReferenceError, format!("cannot access '{}' before initialization", name).as_str());
+        },
+        JsVariableAssignment::Constant => {
+            js_interpreter.throw_error(JsError::TypeError, format!("assignment to constant variable '{}'", name).as_str());
+        },
+        JsVariableAssignment::ReadOnly => {
+            //TODO: in strict mode, this should throw a TypeError
+        },
+        JsVariableAssignment::NotDeclared => {
+            //assigning to a variable that is not declared anywhere creates a global variable
+            js_interpreter.global_environment.borrow_mut().declare_variable(name.clone(), target_address);
+        },
+    }
+}
+
+
+fn execute_condition(condition: &JsAstExpression, js_interpreter: &mut JsInterpreter) -> Result<bool, JsCompletion> {
+    let value = condition.execute(js_interpreter);
+    let is_truthy = value.is_truthy(js_interpreter);
+
+    match completion_after_expression(js_interpreter, JsCompletion::Normal) {
+        JsCompletion::Normal => { return Ok(is_truthy); },
+        completion => { return Err(completion); },
+    }
+}
+
+
+fn execute_loop_body(body: &Script, js_interpreter: &mut JsInterpreter) -> Option<JsCompletion> {
+    //returns the completion of the whole loop when the loop should stop, and None when it should do the next iteration
+    match js_interpreter.run_script_with_context_stack(body) {
+        JsCompletion::Normal | JsCompletion::Continue => { return None; },
+        JsCompletion::Break => { return Some(JsCompletion::Normal); },
+        completion => { return Some(completion); },
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstIf {
+    pub condition: JsAstExpression,
+    pub then_script: Script,
+    pub else_script: Option<Script>,
+}
+impl JsAstIf {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsCompletion {
+        match execute_condition(&self.condition, js_interpreter) {
+            Ok(true) => { return js_interpreter.run_script_with_context_stack(&self.then_script); },
+            Ok(false) => {},
+            Err(completion) => { return completion; },
+        }
+
+        if self.else_script.is_some() {
+            return js_interpreter.run_script_with_context_stack(self.else_script.as_ref().unwrap());
+        }
+        return JsCompletion::Normal;
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstWhile {
+    pub condition: JsAstExpression,
+    pub body: Script,
+    pub check_condition_first: bool, //this is false for a do-while loop, which always runs the body at least once
+}
+impl JsAstWhile {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsCompletion {
+        let mut check_condition = self.check_condition_first;
+
+        loop {
+            if check_condition {
+                match execute_condition(&self.condition, js_interpreter) {
+                    Ok(true) => {},
+                    Ok(false) => { return JsCompletion::Normal; },
+                    Err(completion) => { return completion; },
+                }
+            }
+            check_condition = true;
+
+            let loop_completion = execute_loop_body(&self.body, js_interpreter);
+            if loop_completion.is_some() {
+                return loop_completion.unwrap();
+            }
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstFor {
+    pub init: Option<Box<JsAstStatement>>,
+    pub condition: Option<JsAstExpression>,
+    pub update: Option<Box<JsAstStatement>>,
+    pub body: Script,
+}
+impl JsAstFor {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsCompletion {
+        //let and const variables declared in the loop head are only visible in the loop
+        let declares_lexical_variables = match self.init.as_deref() {
+            Some(JsAstStatement::Declaration(declaration)) => declaration.decl_type != JsDeclType::Var,
+            _ => false,
+        };
+        if !declares_lexical_variables {
+            return self.execute_iterations(js_interpreter, false);
+        }
+
+        let outer_environment = js_interpreter.enter_block_environment();
+        let completion = self.execute_iterations(js_interpreter, true);
+        js_interpreter.leave_block_environment(outer_environment);
+        return completion;
+    }
+
+    fn execute_iterations(&self, js_interpreter: &mut JsInterpreter, declares_lexical_variables: bool) -> JsCompletion {
+        if self.init.is_some() {
+            let init_completion = self.init.as_ref().unwrap().execute(js_interpreter);
+            if !matches!(init_completion, JsCompletion::Normal) {
+                return init_completion;
+            }
+        }
+
+        loop {
+            if self.condition.is_some() {
+                match execute_condition(self.condition.as_ref().unwrap(), js_interpreter) {
+                    Ok(true) => {},
+                    Ok(false) => { return JsCompletion::Normal; },
+                    Err(completion) => { return completion; },
+                }
+            }
+
+            let loop_completion = execute_loop_body(&self.body, js_interpreter);
+            if loop_completion.is_some() {
+                return loop_completion.unwrap();
+            }
+
+            if declares_lexical_variables {
+                //every iteration gets its own copy of the let variables, so functions made in the body keep the value of their iteration
+                let iteration_environment = JsEnvironment::new_copy(&js_interpreter.current_environment());
+                js_interpreter.set_current_environment(iteration_environment);
+            }
+
+            if self.update.is_some() {
+                let update_completion = self.update.as_ref().unwrap().execute(js_interpreter);
+                if !matches!(update_completion, JsCompletion::Normal) {
+                    return update_completion;
+                }
+            }
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum JsIterationKind {
+    In, //iterates over the keys of an object
+    Of, //iterates over the values of an iterable
+}
+
+
+#[derive(Debug)]
+pub struct JsAstForIn {
+    pub decl_type: Option<JsDeclType>, //this is None when the loop assigns to an existing variable, as in "for (x in y)"
+    pub variable: JsAstIdentifier,
+    pub kind: JsIterationKind,
+    pub iterated_expression: JsAstExpression,
+    pub body: Script,
+}
+impl JsAstForIn {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsCompletion {
+        let iterated_value = self.iterated_expression.execute(js_interpreter).deref(js_interpreter);
+        let completion = completion_after_expression(js_interpreter, JsCompletion::Normal);
+        if !matches!(completion, JsCompletion::Normal) {
+            return completion;
+        }
+
+        //we collect all values up front, so changes to the object in the body don't influence the iteration
+        //TODO: the spec says that properties that are deleted before they are visited should not be visited
+        let values_to_visit: Vec<JsValue> = match (&self.kind, iterated_value) {
+            (JsIterationKind::In, JsValue::Object(object)) => {
+                object.member_names().iter().map(|name| JsValue::String(name.clone())).collect()
             },
+            (JsIterationKind::In, JsValue::String(string)) => {
+                (0..string.chars().count()).map(|idx| JsValue::String(idx.to_string())).collect()
+            },
+            (JsIterationKind::In, _) => {
+                Vec::new() //for-in over values without properties (like undefined) just does not run the body
+            },
+            (JsIterationKind::Of, iterable) => {
+                let values = iterate_values(js_interpreter, iterable);
+                let completion = completion_after_expression(js_interpreter, JsCompletion::Normal);
+                if !matches!(completion, JsCompletion::Normal) {
+                    return completion;
+                }
+                values
+            },
+        };
+
+        for value in values_to_visit {
+            let loop_completion = match self.decl_type {
+                None => {
+                    assign_to_variable(js_interpreter, &self.variable.name, value);
+                    let completion = completion_after_expression(js_interpreter, JsCompletion::Normal);
+                    if !matches!(completion, JsCompletion::Normal) {
+                        return completion;
+                    }
+                    execute_loop_body(&self.body, js_interpreter)
+                },
+                Some(JsDeclType::Var) => {
+                    let address = js_interpreter.store_value(value);
+                    js_interpreter.current_var_environment().borrow_mut().declare_variable(self.variable.name.clone(), address);
+                    execute_loop_body(&self.body, js_interpreter)
+                },
+                Some(decl_type) => {
+                    //with let and const, every iteration has its own variable
+                    let outer_environment = js_interpreter.enter_block_environment();
+                    let address = js_interpreter.store_value(value);
+                    js_interpreter.current_environment().borrow_mut().initialize_variable(self.variable.name.clone(), address, decl_type == JsDeclType::Const);
+                    let loop_completion = execute_loop_body(&self.body, js_interpreter);
+                    js_interpreter.leave_block_environment(outer_environment);
+                    loop_completion
+                },
+            };
+            if loop_completion.is_some() {
+                return loop_completion.unwrap();
+            }
         }
-        return true;
+
+        return JsCompletion::Normal;
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstTry {
+    pub try_script: Script,
+    pub catch_variable: Option<JsAstIdentifier>, //the variable can be left out, as in "catch { ... }"
+    pub catch_script: Option<Script>,
+    pub finally_script: Option<Script>,
+}
+impl JsAstTry {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsCompletion {
+        let mut completion = js_interpreter.run_block(&self.try_script);
+
+        if self.catch_script.is_some() {
+            if let JsCompletion::Throw(exception) = completion {
+                //the catch variable is only visible inside the catch block
+                let outer_environment = js_interpreter.enter_block_environment();
+                if self.catch_variable.is_some() {
+                    js_interpreter.declare_variable(self.catch_variable.as_ref().unwrap().name.clone(), exception);
+                }
+                completion = js_interpreter.run_block(self.catch_script.as_ref().unwrap());
+                js_interpreter.leave_block_environment(outer_environment);
+            }
+        }
+
+        if self.finally_script.is_some() {
+            //the finally block always runs, and when it ends abruptly itself (for example by returning), that overrides how the try ended
+            let finally_completion = js_interpreter.run_block(self.finally_script.as_ref().unwrap());
+            if !matches!(finally_completion, JsCompletion::Normal) {
+                return finally_completion;
+            }
+        }
+
+        return completion;
     }
 }
 
@@ -58,18 +501,65 @@ impl JsAstStatement {
 pub struct JsAstFunctionDeclaration {
     pub name: String,
     pub arguments: Vec<JsAstIdentifier>,
+    pub rest_argument: Option<JsAstIdentifier>,
     pub script: Rc<Script>,
+    pub is_async: bool,
 }
 impl JsAstFunctionDeclaration {
-    fn execute(&self, js_interpreter: &mut JsInterpreter) {
-        let global_context = js_interpreter.context_stack.iter_mut().next().unwrap();
+    fn instantiate(&self, js_interpreter: &mut JsInterpreter) -> JsAddress {
+        //declares the function in the current environment, and returns where the function is stored
+        let value = make_function_value(js_interpreter, &self.arguments, &self.rest_argument, &self.script, self.is_async, js_interpreter.current_environment());
+        let address = js_interpreter.store_value(value);
+        js_interpreter.current_environment().borrow_mut().declare_variable(self.name.clone(), address);
+        return address;
+    }
+}
 
-        let argument_names = self.arguments.iter().map(|arg| arg.name.clone()).collect();
-        let value = JsFunction { script: Some(self.script.clone()), argument_names: argument_names, builtin: None };
 
-        let target_address = global_context.add_new_value(JsValue::Function(value));
-        global_context.update_variable(self.name.clone(), target_address);
+#[derive(Debug)]
+pub struct JsAstFunctionExpression {
+    pub name: Option<String>,
+    pub arguments: Vec<JsAstIdentifier>,
+    pub rest_argument: Option<JsAstIdentifier>,
+    pub script: Rc<Script>,
+    pub is_async: bool,
+}
+impl JsAstFunctionExpression {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        if self.name.is_none() {
+            return make_function_value(js_interpreter, &self.arguments, &self.rest_argument, &self.script, self.is_async, js_interpreter.current_environment());
+        }
+
+        //the name of a function expression can only be used inside the function itself, so we put it in an environment of its own
+        let function_environment = JsEnvironment::new(Some(js_interpreter.current_environment()));
+        let value = make_function_value(js_interpreter, &self.arguments, &self.rest_argument, &self.script, self.is_async, function_environment.clone());
+        let address = js_interpreter.store_value(value.clone());
+        function_environment.borrow_mut().declare_variable(self.name.as_ref().unwrap().clone(), address);
+
+        return value;
+    }
+}
+
+
+fn make_function_value(js_interpreter: &mut JsInterpreter, arguments: &Vec<JsAstIdentifier>, rest_argument: &Option<JsAstIdentifier>, script: &Rc<Script>, is_async: bool,
+                       environment: Rc<RefCell<JsEnvironment>>) -> JsValue {
+    //every function gets a prototype object for the objects it constructs, which points back to the function via "constructor"
+    //(except async functions, which can't be used as a constructor)
+    let prototype_address = if is_async { None } else { Some(js_interpreter.add_new_value(JsValue::Object(JsObject::new()))) };
+
+    let argument_names = arguments.iter().map(|arg| arg.name.clone()).collect();
+    let rest_argument_name = rest_argument.as_ref().map(|arg| arg.name.clone());
+    let function = JsFunction { script: Some(script.clone()), argument_names: argument_names, rest_argument_name, builtin: None, environment: Some(environment), bound: None,
+                                prototype: prototype_address, properties: JsObject::new(), is_async };
+    let function_address = js_interpreter.add_new_value(JsValue::Function(function));
+
+    if prototype_address.is_some() {
+        if let Some(JsValue::Object(prototype)) = js_interpreter.get_value_mut(prototype_address.unwrap()) {
+            prototype.set_member(String::from("constructor"), function_address);
+        }
     }
+
+    return JsValue::Address(function_address);
 }
 
 
@@ -82,241 +572,716 @@ pub struct JsAstBinOp {
 }
 impl JsAstBinOp {
     fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
-        let mut left_val = self.left.execute(js_interpreter);
+        let left_val = self.left.execute(js_interpreter);
 
         match self.op {
             JsBinOp::Plus => {
-                let mut right_val = self.right.execute(js_interpreter);
-
-                left_val = left_val.deref(js_interpreter);
-                right_val = right_val.deref(js_interpreter);
-
-                match left_val {
-                    JsValue::Number(left_number) => {
-                        match right_val {
-                            JsValue::Number(right_number) => {
-                                return JsValue::Number(left_number + right_number);
-                            },
-                            _ => { todo!() }
+                //when one of the sides is a string (or an object, which becomes a string), this is string concatenation
+                let left_val = left_val.deref(js_interpreter).to_primitive(js_interpreter);
+                let right_val = self.right.execute(js_interpreter).deref(js_interpreter).to_primitive(js_interpreter);
+
+                if matches!(left_val, JsValue::String(_)) || matches!(right_val, JsValue::String(_)) {
+                    return JsValue::String(left_val.to_js_string() + &right_val.to_js_string());
+                }
+                return JsValue::Number(left_val.to_number() + right_val.to_number());
+            },
+            JsBinOp::Minus | JsBinOp::Times | JsBinOp::Divide => {
+                let left_number = left_val.deref(js_interpreter).to_primitive(js_interpreter).to_number();
+                let right_number = self.right.execute(js_interpreter).deref(js_interpreter).to_primitive(js_interpreter).to_number();
+
+                return JsValue::Number(match self.op {
+                    JsBinOp::Minus => left_number - right_number,
+                    JsBinOp::Times => left_number * right_number,
+                    _ => left_number / right_number,
+                });
+            },
+            JsBinOp::Equal | JsBinOp::NotEqual | JsBinOp::StrictEqual | JsBinOp::StrictNotEqual => {
+                let right_val = self.right.execute(js_interpreter);
+                let is_same_address = matches!((&left_val, &right_val), (JsValue::Address(left), JsValue::Address(right)) if left == right);
+                let left_val = left_val.deref(js_interpreter);
+                let right_val = right_val.deref(js_interpreter);
+
+                let equal = match (&left_val, &right_val) {
+                    //objects and functions are only equal to themselves, which we know when they are stored at the same address
+                    (JsValue::Object(_) | JsValue::Function(_), JsValue::Object(_) | JsValue::Function(_)) => is_same_address,
+                    _ => {
+                        match self.op {
+                            JsBinOp::Equal | JsBinOp::NotEqual => left_val.loosely_equals(&right_val),
+                            _ => left_val.strictly_equals(&right_val),
                         }
-                    },
-                    _ => { todo!() }
+                    }
+                };
+                let negate = matches!(self.op, JsBinOp::NotEqual | JsBinOp::StrictNotEqual);
+                return JsValue::Boolean(equal != negate);
+            },
+            JsBinOp::Smaller | JsBinOp::Bigger | JsBinOp::SmallerOrEqual | JsBinOp::BiggerOrEqual => {
+                let left_val = left_val.deref(js_interpreter);
+                let right_val = self.right.execute(js_interpreter).deref(js_interpreter);
+
+                let ordering = match (&left_val, &right_val) {
+                    (JsValue::String(left_string), JsValue::String(right_string)) => Some(left_string.cmp(right_string)),
+                    _ => left_val.to_number().partial_cmp(&right_val.to_number()), //this is None for NaN, so comparing with NaN is always false
+                };
+                if ordering.is_none() {
+                    return JsValue::Boolean(false);
+                }
+
+                let ordering = ordering.unwrap();
+                return JsValue::Boolean(match self.op {
+                    JsBinOp::Smaller => ordering == Ordering::Less,
+                    JsBinOp::Bigger => ordering == Ordering::Greater,
+                    JsBinOp::SmallerOrEqual => ordering != Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                });
+            },
+            JsBinOp::LogicalAnd | JsBinOp::LogicalOr => {
+                //these return one of their operands (not a boolean), and the right side is only executed when it decides the result
+                let left_is_truthy = left_val.clone().is_truthy(js_interpreter);
+
+                let is_decided_by_left = match self.op {
+                    JsBinOp::LogicalAnd => !left_is_truthy,
+                    _ => left_is_truthy,
+                };
+                if is_decided_by_left {
+                    return left_val;
+                }
+                return self.right.execute(js_interpreter);
+            },
+            JsBinOp::NullishCoalescing => {
+                if left_val.clone().deref(js_interpreter).is_nullish() {
+                    return self.right.execute(js_interpreter);
+                }
+                return left_val;
+            },
+            JsBinOp::In => {
+                let property_key = left_val.deref(js_interpreter).to_primitive(js_interpreter).to_js_string();
+                let object = self.right.execute(js_interpreter).deref(js_interpreter);
+                if js_interpreter.has_pending_exception() {
+                    return JsValue::Undefined;
+                }
+                if !matches!(object, JsValue::Object(_) | JsValue::Function(_)) {
+                    js_interpreter.throw_error(JsError::TypeError, format!("cannot use 'in' operator to search for '{}' in a value that is not an object",
+                                                                          property_key).as_str());
+                    return JsValue::Undefined;
+                }
+
+                return JsValue::Boolean(has_property(js_interpreter, &object, &property_key));
+            },
+            JsBinOp::InstanceOf => {
+                let object = left_val.deref(js_interpreter);
+                let constructor = self.right.execute(js_interpreter).deref(js_interpreter);
+                if js_interpreter.has_pending_exception() {
+                    return JsValue::Undefined;
+                }
+
+                let constructor = match constructor {
+                    JsValue::Function(function) => resolve_bound_function(function).0,
+                    _ => {
+                        js_interpreter.throw_error(JsError::TypeError, "right-hand side of 'instanceof' is not callable");
+                        return JsValue::Undefined;
+                    }
+                };
+                if constructor.prototype.is_none() {
+                    return JsValue::Boolean(false);
+                }
+
+                //the object is an instance when the prototype of the constructor is somewhere in its prototype chain
+                let mut current_prototype = match object {
+                    JsValue::Object(object) => object.prototype(),
+                    _ => None,
+                };
+                while current_prototype.is_some() {
+                    if current_prototype == constructor.prototype {
+                        return JsValue::Boolean(true);
+                    }
+                    current_prototype = match js_interpreter.get_value(current_prototype.unwrap()) {
+                        Some(JsValue::Object(prototype)) => prototype.prototype(),
+                        _ => None,
+                    };
                 }
+                return JsValue::Boolean(false);
             },
-            JsBinOp::Minus => {
-                let mut right_val = self.right.execute(js_interpreter);
+            JsBinOp::PropertyAccess | JsBinOp::OptionalPropertyAccess => {
+                let property_key = self.execute_property_key(js_interpreter);
+
+                //an optional access on null or undefined gives undefined for the rest of the chain, so "a?.b.c" does not fail when a is undefined
+                if left_val.clone().deref(js_interpreter).is_nullish() && self.is_in_optional_chain() {
+                    return JsValue::Undefined;
+                }
+
+                return get_property(js_interpreter, left_val, &property_key);
+            },
+        }
+    }
+
+    fn execute_property_key(&self, js_interpreter: &mut JsInterpreter) -> String {
+        match self.right.as_ref() {
+            //the parser gives the name in "a.b" as a string literal, since a.b is equivalent to a["b"]
+            JsAstExpression::StringLiteral(name) => { return name.clone(); },
+            _ => {
+                //other values are converted to strings, so x[3] is the same as x["3"]
+                return self.right.execute(js_interpreter).deref(js_interpreter).to_primitive(js_interpreter).to_js_string();
+            }
+        }
+    }
+
+    fn is_in_optional_chain(&self) -> bool {
+        return matches!(self.op, JsBinOp::OptionalPropertyAccess) || self.left.is_in_optional_chain();
+    }
+}
+
+
+pub fn get_property(js_interpreter: &mut JsInterpreter, object: JsValue, property_key: &String) -> JsValue {
+    //the object does not need to be dereferenced, but when it is stored somewhere, getters can only get it as "this" via its address
+    let receiver = object.clone();
+
+    match object.deref(js_interpreter) {
+        JsValue::Object(object) => {
+            if let Some(JsInternalSlot::Array(length)) = object.internal_slot() {
+                if property_key == "length" {
+                    return JsValue::Number(*length as f64);
+                }
+            }
+
+            //members the object does not have itself are looked up in its prototype, then in the prototype of that, and so on
+            let mut current_object = &object;
+            loop {
+                let member = current_object.get_member(property_key);
+                if member.is_some() {
+                    return JsValue::Address(*member.unwrap());
+                }
+                let accessor = current_object.get_accessor(property_key);
+                if accessor.is_some() {
+                    //the getter gets the object we read from as "this", also when the getter was found on its prototype
+                    return match accessor.unwrap().getter.clone() {
+                        Some(getter) => call_function(js_interpreter, getter, receiver, Vec::new()),
+                        None => JsValue::Undefined,
+                    };
+                }
+                if current_object.prototype().is_none() {
+                    break;
+                }
+                match js_interpreter.get_value(current_object.prototype().unwrap()) {
+                    Some(JsValue::Object(prototype)) => { current_object = prototype; },
+                    _ => { break; },
+                }
+            }
+
+            //the style of an element has a property for every css property (like backgroundColor), which reads its inline style
+            if let Some(JsInternalSlot::DomStyle(node_id)) = object.internal_slot() {
+                if js_interpreter.document.is_some() {
+                    return js_dom::get_style_property(&js_interpreter.document.as_ref().unwrap().borrow(), *node_id, property_key);
+                }
+            }
+            return JsValue::Undefined;
+        },
+        JsValue::Function(function) => {
+            if property_key == "prototype" && function.prototype.is_some() {
+                return JsValue::Address(function.prototype.unwrap());
+            }
+            let member = function.properties.get_member(property_key);
+            if member.is_some() {
+                return JsValue::Address(*member.unwrap());
+            }
+
+            //TODO: these should come from Function.prototype
+            let builtin = match property_key.as_str() {
+                "call" => JsBuiltinFunction::FunctionCall,
+                "apply" => JsBuiltinFunction::FunctionApply,
+                "bind" => JsBuiltinFunction::FunctionBind,
+                _ => { return JsValue::Undefined; }
+            };
+            return JsValue::Function(JsFunction::new_builtin(builtin));
+        },
+        nullish @ (JsValue::Undefined | JsValue::Null) => {
+            js_interpreter.throw_error(JsError::TypeError, format!("cannot read properties of {} (reading '{}')", nullish.to_js_string(), property_key).as_str());
+            return JsValue::Undefined;
+        },
+        JsValue::String(string) => {
+            //TODO: these should come from String.prototype
+            let builtin = match property_key.as_str() {
+                "length" => { return JsValue::Number(string.chars().count() as f64); },
+                "match" => JsBuiltinFunction::StringMatch,
+                "replace" => JsBuiltinFunction::StringReplace,
+                _ => { return JsValue::Undefined; }
+            };
+            return JsValue::Function(JsFunction::new_builtin(builtin));
+        },
+        _ => {
+            //TODO: numbers and other primitives have properties as well (via their prototype)
+            return JsValue::Undefined;
+        }
+    }
+}
+
+
+fn has_property(js_interpreter: &JsInterpreter, object: &JsValue, property_key: &String) -> bool {
+    //like get_property, but without calling getters (the object should already be dereferenced)
+    match object {
+        JsValue::Object(object) => {
+            if matches!(object.internal_slot(), Some(JsInternalSlot::Array(_))) && property_key == "length" {
+                return true;
+            }
+            let mut current_object = object;
+            loop {
+                if current_object.get_member(property_key).is_some() || current_object.get_accessor(property_key).is_some() {
+                    return true;
+                }
+                match current_object.prototype().map(|prototype| js_interpreter.get_value(prototype)) {
+                    Some(Some(JsValue::Object(prototype))) => { current_object = prototype; },
+                    _ => { return false; },
+                }
+            }
+        },
+        JsValue::Function(function) => {
+            if property_key == "prototype" && function.prototype.is_some() {
+                return true;
+            }
+            return function.properties.get_member(property_key).is_some() || ["call", "apply", "bind"].contains(&property_key.as_str());
+        },
+        _ => { return false; },
+    }
+}
+
+
+fn find_setter(js_interpreter: &JsInterpreter, object_address: JsAddress, property_key: &String) -> Option<JsAccessor> {
+    //returns the accessor when the object (or its prototype) has one for this property, and not a normal member that comes before it
+    let mut current_address = Some(object_address);
+    while current_address.is_some() {
+        match js_interpreter.get_value(current_address.unwrap()) {
+            Some(JsValue::Object(object)) => {
+                if object.get_member(property_key).is_some() {
+                    return None;
+                }
+                if object.get_accessor(property_key).is_some() {
+                    return object.get_accessor(property_key).cloned();
+                }
+                current_address = object.prototype();
+            },
+            _ => { return None; },
+        }
+    }
+    return None;
+}
+
+
+#[derive(Debug)]
+pub struct JsAstAssign {
+    pub left: JsAstExpression,
+    pub right: JsAstExpression,
+}
+impl JsAstAssign {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) {
+        match &self.left {
+            JsAstExpression::Identifier(identifier) => {
+                let value = self.right.execute(js_interpreter);
+                if js_interpreter.has_pending_exception() {
+                    return;
+                }
+                assign_to_variable(js_interpreter, &identifier.name, value);
+            },
+            JsAstExpression::BinOp(binop) if matches!(binop.op, JsBinOp::PropertyAccess) => {
+                let object = binop.left.execute(js_interpreter);
+                let property_key = binop.execute_property_key(js_interpreter);
+                let value = self.right.execute(js_interpreter);
+                if js_interpreter.has_pending_exception() {
+                    return;
+                }
+                assign_to_property(js_interpreter, object, property_key, value);
+            },
+            _ => {
+                panic!("invalid assignment target"); //the parser should have already rejected these
+            }
+        }
+    }
+}
+
+
+fn assign_to_property(js_interpreter: &mut JsInterpreter, object: JsValue, property_key: String, value: JsValue) {
+    let dereferenced_object = object.clone().deref(js_interpreter);
+    if dereferenced_object.is_nullish() {
+        js_interpreter.throw_error(JsError::TypeError, format!("cannot set properties of {} (setting '{}')", dereferenced_object.to_js_string(), property_key).as_str());
+        return;
+    }
+
+    //when the property has a setter, we call that instead (and without a setter, assigning to an accessor does nothing)
+    if let JsValue::Address(object_address) = object {
+        let accessor = find_setter(js_interpreter, object_address, &property_key);
+        if accessor.is_some() {
+            if let Some(setter) = accessor.unwrap().setter {
+                call_function(js_interpreter, setter, object, vec![value]);
+            }
+            return;
+        }
+    }
+
+    //assigning to a css property on the style of an element changes its inline style
+    if let JsValue::Address(object_address) = object {
+        if let Some(JsValue::Object(object)) = js_interpreter.get_value(object_address) {
+            if let Some(JsInternalSlot::DomStyle(node_id)) = object.internal_slot() {
+                let node_id = *node_id;
+                let value = value.deref(js_interpreter).to_js_string();
+                if js_interpreter.document.is_some() {
+                    js_dom::set_style_property(&mut js_interpreter.document.as_ref().unwrap().borrow_mut(), node_id, &property_key, &value);
+                }
+                return;
+            }
+        }
+    }
+    let target_address = js_interpreter.store_value(value);
+
+    //only objects that are stored somewhere can be changed, assigning to a property of a temporary object does nothing
+    if let JsValue::Address(object_address) = object {
+        match js_interpreter.get_value_mut(object_address) {
+            Some(JsValue::Object(object)) => {
+                if let Some(JsInternalSlot::Array(length)) = object.internal_slot() {
+                    //setting a member past the end of an array makes the array longer
+                    if let Ok(index) = property_key.parse::<usize>() {
+                        if index.to_string() == property_key && index >= *length {
+                            object.set_internal_slot(JsInternalSlot::Array(index + 1));
+                        }
+                    }
+                    //TODO: assigning to the length of an array should change the length (and remove members past it)
+                }
+                object.set_member(property_key, target_address);
+            },
+            Some(JsValue::Function(function)) if property_key == "prototype" => { function.prototype = Some(target_address); },
+            Some(JsValue::Function(function)) => { function.properties.set_member(property_key, target_address); },
+            _ => {},
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsDeclType {
+    Var, //declares the variable for the whole function
+    Let, //declares the variable for the block it is in
+    Const, //like let, but the variable can't be assigned to
+}
+
+
+#[derive(Debug)]
+pub struct JsAstDeclaration {
+    pub decl_type: JsDeclType,
+    pub target: JsAstPattern,
+    pub initial_value: Option<JsAstExpression>,
+}
+impl JsAstDeclaration {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) {
+        //var variables are already declared when the function starts, so "var x;" does nothing (and does not make x undefined again)
+        if self.decl_type == JsDeclType::Var && self.initial_value.is_none() {
+            return;
+        }
+
+        let initial_value = if self.initial_value.is_some() {
+            self.initial_value.as_ref().unwrap().execute(js_interpreter)
+        } else {
+            JsValue::Undefined
+        };
+        if js_interpreter.has_pending_exception() {
+            return;
+        }
+        self.target.bind(js_interpreter, initial_value, Some(self.decl_type));
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstDestructuringAssign {
+    pub pattern: JsAstPattern,
+    pub right: JsAstExpression,
+}
 
-                left_val = left_val.deref(js_interpreter);
-                right_val = right_val.deref(js_interpreter);
 
-                match left_val {
-                    JsValue::Number(left_number) => {
-                        match right_val {
-                            JsValue::Number(right_number) => {
-                                return JsValue::Number(left_number - right_number);
-                            },
-                            _ => { todo!() }
-                        }
-                    },
-                    _ => { todo!() }
+#[derive(Debug)]
+pub enum JsAstPattern {
+    //the target of a declaration or assignment, which can take values out of objects and arrays, as in "var {a, b: [c, d]} = x"
+    Identifier(JsAstIdentifier),
+    Object(Vec<(JsAstExpression, JsAstPatternElement)>), //the key is a string literal, or an expression for computed keys
+    Array(Vec<Option<JsAstPatternElement>>, Option<Box<JsAstPattern>>), //elements can be skipped, as in "[a, , b]", and the rest goes in an array
+}
+impl JsAstPattern {
+    pub fn collect_bound_names(&self, names: &mut Vec<String>) {
+        match self {
+            JsAstPattern::Identifier(identifier) => { names.push(identifier.name.clone()); },
+            JsAstPattern::Object(properties) => {
+                for (_, element) in properties {
+                    element.target.collect_bound_names(names);
                 }
             },
-            JsBinOp::Times => {
-                let mut right_val = self.right.execute(js_interpreter);
-
-                left_val = left_val.deref(js_interpreter);
-                right_val = right_val.deref(js_interpreter);
-
-                match left_val {
-                    JsValue::Number(left_number) => {
-                        match right_val {
-                            JsValue::Number(right_number) => {
-                                return JsValue::Number(left_number * right_number);
-                            },
-                            _ => { todo!() }
-                        }
-                    },
-                    _ => { todo!() }
+            JsAstPattern::Array(elements, rest) => {
+                for element in elements.iter().flatten() {
+                    element.target.collect_bound_names(names);
+                }
+                if rest.is_some() {
+                    rest.as_ref().unwrap().collect_bound_names(names);
                 }
             },
-            JsBinOp::Divide => {
-                let mut right_val = self.right.execute(js_interpreter);
-
-                left_val = left_val.deref(js_interpreter);
-                right_val = right_val.deref(js_interpreter);
+        }
+    }
 
-                match left_val {
-                    JsValue::Number(left_number) => {
-                        match right_val {
-                            JsValue::Number(right_number) => {
-                                return JsValue::Number(left_number / right_number);
-                            },
-                            _ => { todo!() }
-                        }
+    fn bind(&self, js_interpreter: &mut JsInterpreter, value: JsValue, decl_type: Option<JsDeclType>) {
+        //puts the value in the variables of the pattern, declaring them, or assigning to them when decl_type is None
+        match self {
+            JsAstPattern::Identifier(identifier) => {
+                match decl_type {
+                    None => {
+                        assign_to_variable(js_interpreter, &identifier.name, value);
+                    },
+                    Some(JsDeclType::Var) => {
+                        let address = js_interpreter.store_value(value);
+                        js_interpreter.current_var_environment().borrow_mut().declare_variable(identifier.name.clone(), address);
+                    },
+                    Some(decl_type) => {
+                        let address = js_interpreter.store_value(value);
+                        js_interpreter.current_environment().borrow_mut().initialize_variable(identifier.name.clone(), address, decl_type == JsDeclType::Const);
                     },
-                    _ => { todo!() }
                 }
             },
-            JsBinOp::PropertyAccess => {
-                let property = match self.right.as_ref() {
-                    // when the right hand side of our accessor is an identifier, we don't execute, but just take its name as a string
-                    // this is because a.b is equivalent to a["b"]
-                    JsAstExpression::Identifier(ident) => { JsValue::String(ident.name.clone()) }
-                    _ => { self.right.execute(js_interpreter) }
-                };
+            JsAstPattern::Object(properties) => {
+                if matches!(value.clone().deref(js_interpreter), JsValue::Undefined) {
+                    js_interpreter.throw_error(JsError::TypeError, "cannot destructure undefined");
+                    return;
+                }
+                for (key, element) in properties {
+                    let property_key = key.execute(js_interpreter).deref(js_interpreter).to_primitive(js_interpreter).to_js_string();
+                    let property_value = get_property(js_interpreter, value.clone(), &property_key);
+                    if js_interpreter.has_pending_exception() {
+                        return;
+                    }
+                    element.bind(js_interpreter, property_value, decl_type);
+                    if js_interpreter.has_pending_exception() {
+                        return;
+                    }
+                }
+            },
+            JsAstPattern::Array(elements, rest) => {
+                let values = iterate_values(js_interpreter, value.deref(js_interpreter));
+                if js_interpreter.has_pending_exception() {
+                    return;
+                }
 
-                let object = JsValue::deref(left_val, js_interpreter);
-
-                match object {
-                    JsValue::Object(object) => {
-                        match property {
-                            JsValue::String(property_value) => {
-                                match object.members.get(&property_value) {
-                                    Some(address) => { JsValue::Address(*address) },
-                                    None => {
-                                        //TODO: handle error
-                                        todo!()
-                                    }
-                                }
-                            },
-                            _ => {
-                                //TODO: some of these are invalid, others (like number) are valid (for example for "x[3]")
-                                todo!();
-                            }
+                let mut values = values.into_iter();
+                for element in elements {
+                    let element_value = values.next().unwrap_or(JsValue::Undefined);
+                    if element.is_some() {
+                        element.as_ref().unwrap().bind(js_interpreter, element_value, decl_type);
+                        if js_interpreter.has_pending_exception() {
+                            return;
                         }
-
-                    },
-                    _ => {
-                        todo!();
                     }
                 }
+                if rest.is_some() {
+                    let rest_array = js_interpreter.make_array(values.collect());
+                    rest.as_ref().unwrap().bind(js_interpreter, rest_array, decl_type);
+                }
             },
         }
     }
+}
 
-    fn build_var_path(&self, path: &mut Vec<String>) {
-        match self.op {
-            JsBinOp::Plus => todo!(),  //TODO: not sure yet if there is a valid case for this (there might be and we then need to execute())
-            JsBinOp::Minus => todo!(),  //TODO: not sure yet if there is a valid case for this (there might be and we then need to execute())
-            JsBinOp::Times => todo!(),  //TODO: not sure yet if there is a valid case for this (there might be and we then need to execute())
-            JsBinOp::Divide => todo!(),  //TODO: not sure yet if there is a valid case for this (there might be and we then need to execute())
-            JsBinOp::PropertyAccess => {
-                self.left.build_var_path(path);
-                self.right.build_var_path(path);
-            },
+
+#[derive(Debug)]
+pub struct JsAstPatternElement {
+    pub target: JsAstPattern,
+    pub default_value: Option<JsAstExpression>, //used when the value is undefined, as in "var {a = 1} = {}"
+}
+impl JsAstPatternElement {
+    fn bind(&self, js_interpreter: &mut JsInterpreter, value: JsValue, decl_type: Option<JsDeclType>) {
+        let mut value = value;
+        if self.default_value.is_some() && matches!(value.clone().deref(js_interpreter), JsValue::Undefined) {
+            value = self.default_value.as_ref().unwrap().execute(js_interpreter);
+            if js_interpreter.has_pending_exception() {
+                return;
+            }
         }
+        self.target.bind(js_interpreter, value, decl_type);
     }
 }
 
 
-#[derive(Debug)]
-pub struct JsAstAssign {
-    pub left: JsAstExpression,
-    pub right: JsAstExpression,
+pub fn iterate_values(js_interpreter: &mut JsInterpreter, iterable: JsValue) -> Vec<JsValue> {
+    //returns the values for for-of loops, spreading and destructuring (the value should already be dereferenced)
+    //TODO: this should use the iterator protocol (Symbol.iterator), when we have symbols
+    match iterable {
+        JsValue::String(string) => {
+            return string.chars().map(|character| JsValue::String(character.to_string())).collect();
+        },
+        JsValue::Object(ref object) if matches!(object.internal_slot(), Some(JsInternalSlot::Array(_))) => {
+            return list_from_array_like(js_interpreter, iterable);
+        },
+        _ => {
+            js_interpreter.throw_error(JsError::TypeError, "value is not iterable");
+            return Vec::new();
+        },
+    }
 }
-impl JsAstAssign {
-    fn execute(&self, js_interpreter: &mut JsInterpreter) {
-        let value = self.right.execute(js_interpreter);
 
-        //TODO: not all actions might need to be in the current stack frame. Some might be globals, or from outer scopes
-        let current_context = js_interpreter.context_stack.iter_mut().last().unwrap();
 
+#[derive(Debug)]
+pub enum JsBinOp {
+    Plus,
+    Minus,
+    Times,
+    Divide,
+    Equal,
+    NotEqual,
+    StrictEqual,
+    StrictNotEqual,
+    Smaller,
+    Bigger,
+    SmallerOrEqual,
+    BiggerOrEqual,
+    LogicalAnd,
+    LogicalOr,
+    NullishCoalescing,
+    InstanceOf,
+    In,
+    PropertyAccess,
+    OptionalPropertyAccess,
+}
 
-        let target_address = current_context.add_new_value(value);
 
-        let mut variable_path = Vec::new();
-        self.left.build_var_path(&mut variable_path);
+#[derive(Debug)]
+pub enum JsUnaryOp {
+    Not,
+    TypeOf,
+    Delete,
+    Void,
+}
 
-        let mut first = true;
-        let mut current_object_address = None;
 
-        for idx in 0..variable_path.len() {
-            let last = idx == variable_path.len() - 1;
+#[derive(Debug)]
+pub struct JsAstUnaryOp {
+    pub op: JsUnaryOp,
+    pub expression: Rc<JsAstExpression>,
+}
+impl JsAstUnaryOp {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        match self.op {
+            JsUnaryOp::TypeOf => { return self.execute_typeof(js_interpreter); },
+            JsUnaryOp::Delete => { return self.execute_delete(js_interpreter); },
+            _ => {},
+        }
 
-            if first {
-                if last {
-                    current_context.update_variable(variable_path[idx].clone(), target_address);
-                } else {
-                    match current_context.get_var_address(&variable_path[idx]) {
-                        Some(address) => {
-                            current_object_address = Some(*address);
-                        },
-                        None => {
-                            js_console::log_js_error(format!("Variable not found: {}", variable_path[idx]).as_str());
-                            return;
-                        }
-                    }
-                }
+        let value = self.expression.execute(js_interpreter);
 
-                first = false;
+        match self.op {
+            JsUnaryOp::Not => { return JsValue::Boolean(!value.is_truthy(js_interpreter)); },
+            JsUnaryOp::Void => { return JsValue::Undefined; },
+            JsUnaryOp::TypeOf | JsUnaryOp::Delete => { panic!("handled above"); },
+        }
+    }
 
-            } else {  //not the first element in the path, so we need to keep looking up members in objects
+    fn execute_typeof(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        //typeof does not throw for variables that don't exist, so scripts can use it to check if something is available
+        if let JsAstExpression::Identifier(identifier) = self.expression.as_ref() {
+            if matches!(js_interpreter.lookup_variable(&identifier.name), JsVariableLookup::NotDeclared) {
+                return JsValue::String(String::from("undefined"));
+            }
+        }
 
-                let object = current_context.get_value(&current_object_address.unwrap());
+        let type_name = match self.expression.execute(js_interpreter).deref(js_interpreter) {
+            JsValue::Number(_) => "number",
+            JsValue::String(_) => "string",
+            JsValue::Boolean(_) => "boolean",
+            JsValue::Object(_) | JsValue::Null => "object",
+            JsValue::Function(_) => "function",
+            JsValue::Undefined | JsValue::Address(_) => "undefined",
+        };
+        return JsValue::String(String::from(type_name));
+    }
 
-                if last {
-                    match object.unwrap() {
-                        JsValue::Object(ref mut obj) => {
-                            obj.members.insert(variable_path[idx].clone(), target_address);
-                        },
-                        _ => {
-                            todo!();  //TODO: are there valid cases here? Don't think so....
-                        }
-                    }
-                } else {
+    fn execute_delete(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        let property_access = match self.expression.as_ref() {
+            JsAstExpression::BinOp(binop) if matches!(binop.op, JsBinOp::PropertyAccess | JsBinOp::OptionalPropertyAccess) => binop,
+            JsAstExpression::Identifier(_) => {
+                //variables can't be deleted
+                return JsValue::Boolean(false);
+            },
+            _ => {
+                //deleting anything else than a property does nothing, but the expression still runs
+                let _ = self.expression.execute(js_interpreter);
+                return JsValue::Boolean(true);
+            },
+        };
 
-                    match object.unwrap() {
-                        JsValue::Object(obj) => {
-                            let next_address = obj.members.get(&variable_path[idx]);
-
-                            match next_address {
-                                Some(address) => {
-                                    current_object_address = Some(*address);
-                                },
-                                None => {
-                                    todo!(); //TODO: report error that the member is not found
-                                }
-                            }
-
-                        },
-                        _ => {
-                            todo!();  //TODO: are there valid cases here? Don't think so....
-                        }
-                    }
-                }
+        let object = property_access.left.execute(js_interpreter);
+        let property_key = property_access.execute_property_key(js_interpreter);
+        if js_interpreter.has_pending_exception() {
+            return JsValue::Undefined;
+        }
+        let dereferenced_object = object.clone().deref(js_interpreter);
+        if dereferenced_object.is_nullish() {
+            if property_access.is_in_optional_chain() {
+                return JsValue::Boolean(true);
+            }
+            js_interpreter.throw_error(JsError::TypeError, format!("cannot delete properties of {} (deleting '{}')", dereferenced_object.to_js_string(), property_key).as_str());
+            return JsValue::Undefined;
+        }
+
+        //only the object itself loses the property, not its prototype
+        if let JsValue::Address(object_address) = object {
+            match js_interpreter.get_value_mut(object_address) {
+                Some(JsValue::Object(object)) => { object.remove_member(&property_key); },
+                Some(JsValue::Function(function)) => { function.properties.remove_member(&property_key); },
+                _ => {},
             }
         }
+        return JsValue::Boolean(true);
     }
 }
 
 
 #[derive(Debug)]
-pub struct JsAstDeclaration {
-    pub variable: JsAstIdentifier,
-    pub initial_value: Option<JsAstExpression>,
+pub enum JsUpdateOp {
+    Increment,
+    Decrement,
 }
-impl JsAstDeclaration {
-    fn execute(&self, js_interpreter: &mut JsInterpreter) {
-        let initial_value = if self.initial_value.is_some() {
-            self.initial_value.as_ref().unwrap().execute(js_interpreter)
-        } else {
-            JsValue::Undefined
-        };
-        let current_context = js_interpreter.context_stack.iter_mut().last().unwrap();
-        let new_address = current_context.add_new_value(initial_value);
 
-        current_context.update_variable(self.variable.name.clone(), new_address);
-    }
+
+#[derive(Debug)]
+pub struct JsAstUpdate {
+    pub op: JsUpdateOp,
+    pub is_prefix: bool, //"++x" gives the new value, and "x++" gives the value from before the update
+    pub target: Rc<JsAstExpression>, //a variable or a property, like the target of an assignment
 }
+impl JsAstUpdate {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        //the object and property key are only executed once, so "a[f()]++" calls f once
+        let (object, property_key) = match self.target.as_ref() {
+            JsAstExpression::BinOp(binop) if matches!(binop.op, JsBinOp::PropertyAccess) => {
+                let object = binop.left.execute(js_interpreter);
+                let property_key = binop.execute_property_key(js_interpreter);
+                (Some(object), Some(property_key))
+            },
+            _ => (None, None),
+        };
+        if js_interpreter.has_pending_exception() {
+            return JsValue::Undefined;
+        }
 
+        let old_value = match &object {
+            Some(object) => get_property(js_interpreter, object.clone(), property_key.as_ref().unwrap()),
+            None => self.target.execute(js_interpreter),
+        };
+        if js_interpreter.has_pending_exception() {
+            return JsValue::Undefined;
+        }
 
-#[derive(Debug)]
-pub enum JsBinOp {
-    Plus,
-    Minus,
-    Times,
-    Divide,
-    PropertyAccess,
+        let old_number = old_value.deref(js_interpreter).to_primitive(js_interpreter).to_number();
+        let new_number = match self.op {
+            JsUpdateOp::Increment => old_number + 1.0,
+            JsUpdateOp::Decrement => old_number - 1.0,
+        };
+
+        match self.target.as_ref() {
+            JsAstExpression::Identifier(identifier) => { assign_to_variable(js_interpreter, &identifier.name, JsValue::Number(new_number)); },
+            _ => { assign_to_property(js_interpreter, object.unwrap(), property_key.unwrap(), JsValue::Number(new_number)); },
+        }
+        return JsValue::Number(if self.is_prefix { new_number } else { old_number });
+    }
 }
 
 
@@ -326,13 +1291,15 @@ pub struct JsAstIdentifier {
 }
 impl JsAstIdentifier {
     fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
-        let opt_address = js_interpreter.get_var_address(&self.name);
-        if opt_address.is_some() {
-            return JsValue::Address(*opt_address.unwrap());
+        match js_interpreter.lookup_variable(&self.name) {
+            JsVariableLookup::Found(address) => { return JsValue::Address(address); },
+            JsVariableLookup::Uninitialized => {
+                js_interpreter.throw_error(JsError::ReferenceError, format!("cannot access '{}' before initialization", self.name).as_str());
+            },
+            JsVariableLookup::NotDeclared => {
+                js_interpreter.throw_error(JsError::ReferenceError, format!("{} is not defined", self.name).as_str());
+            },
         }
-        js_interpreter.set_error(JsError::ReferenceError);
-        js_console::log_js_error(format!("variable not found: {}", self.name).as_str()); //TODO: eventually we want to trigger the logging of the error
-                                                                                         //      from setting it (so we can also show stack etc.)
         return JsValue::Undefined;
     }
 }
@@ -341,118 +1308,116 @@ impl JsAstIdentifier {
 #[derive(Debug)]
 pub enum JsAstExpression {
     BinOp(JsAstBinOp),
+    UnaryOp(JsAstUnaryOp),
+    Update(JsAstUpdate),
+    Conditional(JsAstConditional),
     NumericLiteral(String),
     StringLiteral(String),
+    BooleanLiteral(bool),
+    NullLiteral,
     FunctionCall(JsAstFunctionCall),
     Identifier(JsAstIdentifier),
     ObjectLiteral(JsAstObjectLiteral),
+    Function(JsAstFunctionExpression),
+    ObjectCreation(JsAstObjectCreation),
+    RegexLiteral(Rc<JsRegex>),
+    ArrayLiteral(Vec<Option<JsAstExpression>>), //None for holes, as in "[1, , 3]"
+    Spread(Rc<JsAstExpression>), //only in function arguments and array literals, as in "f(...xs)"
+    Await(Rc<JsAstExpression>),
+    This,
 }
 impl JsAstExpression {
     fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
         match self {
             JsAstExpression::BinOp(binop) => { return binop.execute(js_interpreter) },
-            JsAstExpression::Identifier(variable) => { return JsValue::deref(variable.execute(js_interpreter), js_interpreter) },
+            JsAstExpression::UnaryOp(unary_op) => { return unary_op.execute(js_interpreter) },
+            JsAstExpression::Update(update) => { return update.execute(js_interpreter) },
+            JsAstExpression::Conditional(conditional) => { return conditional.execute(js_interpreter) },
+            JsAstExpression::Identifier(variable) => { return variable.execute(js_interpreter) },
+            JsAstExpression::This => { return js_interpreter.context_stack.iter().last().unwrap().this_value.clone() },
             JsAstExpression::ObjectLiteral(obj) => { return obj.execute(js_interpreter) },
+            JsAstExpression::Function(function) => { return function.execute(js_interpreter) },
+            JsAstExpression::ObjectCreation(object_creation) => { return object_creation.execute(js_interpreter) },
+            JsAstExpression::ArrayLiteral(elements) => {
+                let mut values = Vec::new();
+                for element in elements {
+                    match element {
+                        Some(element) => { execute_list_element(js_interpreter, element, &mut values); },
+                        None => { values.push(JsValue::Undefined); },
+                    }
+                    if js_interpreter.has_pending_exception() {
+                        return JsValue::Undefined;
+                    }
+                }
+                return js_interpreter.make_array(values);
+            },
+            JsAstExpression::Spread(_) => {
+                panic!("spread should only be parsed in function arguments and array literals");
+            },
+            JsAstExpression::Await(expression) => {
+                let value = expression.execute(js_interpreter);
+                if js_interpreter.has_pending_exception() {
+                    return JsValue::Undefined;
+                }
+                return js_async::await_value(js_interpreter, value);
+            },
+            JsAstExpression::RegexLiteral(regex) => {
+                //every time a regex literal runs, it makes a new object (with its own lastIndex), but they can share the compiled regex
+                let prototype = js_interpreter.regexp_prototype_address;
+                let object_address = js_interpreter.add_new_value(JsValue::Object(JsObject::new_with_prototype(Some(prototype))));
+                js_builtins::init_regexp_object(js_interpreter, object_address, regex.clone());
+                return JsValue::Address(object_address);
+            },
 
             JsAstExpression::NumericLiteral(numeric_literal) => {
                 //TODO: we might want to cache the JsValue somehow, and we need to support more numeric types...
 
-                let parsed_value = numeric_literal.parse();
+                let parsed_value = parse_numeric_literal(numeric_literal);
                 match parsed_value {
-                    Ok(value) => {
+                    Some(value) => {
                         return JsValue::Number(value);
                     },
-                    Err(_e) => {
-                        panic!("could not convert number in string to JsValue::Number");
+                    None => {
+                        panic!("could not convert number in string to JsValue::Number"); //the parser should have already rejected these
                     }
                 }
             },
             JsAstExpression::StringLiteral(string_literal) => {
                 return JsValue::String(string_literal.clone()); //TODO: do we want to make a new string ever time this expression is run?
             },
+            JsAstExpression::BooleanLiteral(boolean) => { return JsValue::Boolean(*boolean); },
+            JsAstExpression::NullLiteral => { return JsValue::Null; },
+            JsAstExpression::FunctionCall(function_call) => { return function_call.execute(js_interpreter) },
+        }
+    }
+
+    fn is_in_optional_chain(&self) -> bool {
+        match self {
+            JsAstExpression::BinOp(binop) => {
+                return matches!(binop.op, JsBinOp::PropertyAccess | JsBinOp::OptionalPropertyAccess) && binop.is_in_optional_chain();
+            },
             JsAstExpression::FunctionCall(function_call) => {
-                //TODO: all this code should be moved to the JsAstFunctionCall object
-
-                let mut function = function_call.function_expression.execute(js_interpreter);
-                function = function.deref(js_interpreter);
-
-                match function {
-                    JsValue::Function(function) => {
-                        if function.builtin.is_some() {
-                            match function.builtin.as_ref().unwrap() {
-                                JsBuiltinFunction::ConsoleLog => {
-                                    let to_log = function_call.arguments.get(0); //TODO: handle there being to little or to many arguments
-
-                                    let to_log = to_log.unwrap().execute(js_interpreter);
-                                    let to_log = to_log.deref(js_interpreter);
-
-                                    let to_log = match to_log {
-                                        JsValue::String(string) =>  { string }
-                                        JsValue::Number(number) => { number.to_string() },
-                                        JsValue::Boolean(_) => todo!(), //TODO: implement
-                                        JsValue::Object(_) => todo!(), //TODO: implement
-                                        JsValue::Function(_) => todo!(), //TODO: implement
-                                        JsValue::Undefined => { "undefined".to_owned() },
-                                        JsValue::Address(_) => todo!(), //TODO: implement
-                                    };
-
-                                    js_console::print(to_log.as_str());
-                                    return JsValue::Undefined;
-                                },
-                                #[cfg(test)] JsBuiltinFunction::TesterExport => {
-                                    let data_ast = function_call.arguments.get(0);
-                                    let data = data_ast.unwrap().execute(js_interpreter); //TODO: even for tests, we probably want to handle the unwrap here
-                                    let data = data.deref(js_interpreter);
-                                    js_interpreter.export_test_data(data);
-                                    return JsValue::Undefined;
-                                }
-                            }
-                        } else {
-
-                            let mut args = Vec::new();
-                            for (idx, argument_name) in function.argument_names.into_iter().enumerate() {
-                                let arg_ast = function_call.arguments.get(idx);
-                                let arg_value = arg_ast.unwrap().execute(js_interpreter); //TODO: we need to properly handle the unwrap here
-                                args.push( (argument_name, arg_value));
-                            }
-
-                            let mut new_context = JsExecutionContext::new();
-                            for (arg_name, arg_value) in args {
-                                let address = new_context.add_new_value(arg_value);
-                                new_context.update_variable(arg_name, address);
-                            }
-                            js_interpreter.context_stack.push(new_context);
-
-                            js_interpreter.run_script_with_context_stack(&function.script.unwrap());
-
-                            js_interpreter.context_stack.pop();
-                            let return_value = js_interpreter.return_value.clone();
-                            js_interpreter.return_value = None;
-
-                            if return_value.is_some() {
-                                return return_value.unwrap();
-                            }
-                            return JsValue::Undefined;
-                        }
-                    },
-                    _ => {
-                        //TODO: report an error (variable is not a function)
-                        return JsValue::Undefined;
-                    },
-                }
+                return function_call.optional || function_call.function_expression.is_in_optional_chain();
             },
+            _ => { return false; }
         }
     }
+}
 
-    fn build_var_path(&self, path: &mut Vec<String>) {
-        match self {
-            JsAstExpression::BinOp(binop) => { binop.build_var_path(path) },
-            JsAstExpression::Identifier(ident) => { path.push(ident.name.clone()) },
-            _ => {
-                //TODO: I think this should always be an error
-                todo!();
+
+fn execute_list_element(js_interpreter: &mut JsInterpreter, element: &JsAstExpression, values: &mut Vec<JsValue>) {
+    //evaluates an argument or array element, where a spread element adds all the values of what is spread
+    match element {
+        JsAstExpression::Spread(iterable) => {
+            let iterable = iterable.execute(js_interpreter).deref(js_interpreter);
+            if js_interpreter.has_pending_exception() {
+                return;
             }
-        }
+            values.extend(iterate_values(js_interpreter, iterable));
+        },
+        _ => {
+            values.push(element.execute(js_interpreter));
+        },
     }
 }
 
@@ -461,37 +1426,436 @@ impl JsAstExpression {
 pub struct JsAstFunctionCall {
     pub function_expression: Rc<JsAstExpression>,
     pub arguments: Vec<JsAstExpression>,
+    pub optional: bool, //for calls like "f?.()", which don't call anything when f is undefined
+}
+impl JsAstFunctionCall {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        //when the function is called as a property of an object (as in "object.method()"), the object becomes "this" in the function
+        let (function, this_value) = match self.function_expression.as_ref() {
+            JsAstExpression::BinOp(binop) if matches!(binop.op, JsBinOp::PropertyAccess | JsBinOp::OptionalPropertyAccess) => {
+                let object = binop.left.execute(js_interpreter);
+                let property_key = binop.execute_property_key(js_interpreter);
+                let dereferenced_object = object.clone().deref(js_interpreter);
+
+                if dereferenced_object.is_nullish() && binop.is_in_optional_chain() {
+                    (JsValue::Undefined, JsValue::Undefined)
+                } else {
+                    (get_property(js_interpreter, object.clone(), &property_key), object)
+                }
+            },
+            _ => {
+                (self.function_expression.execute(js_interpreter), JsValue::Undefined)
+            }
+        };
+        let function = function.deref(js_interpreter);
+        if js_interpreter.has_pending_exception() {
+            return JsValue::Undefined;
+        }
+
+        match function {
+            JsValue::Function(function) => {
+                let mut arguments = Vec::new();
+                for argument in self.arguments.iter() {
+                    execute_list_element(js_interpreter, argument, &mut arguments);
+                    if js_interpreter.has_pending_exception() {
+                        return JsValue::Undefined;
+                    }
+                }
+
+                return call_function(js_interpreter, function, this_value, arguments);
+            },
+            JsValue::Undefined | JsValue::Null if self.optional || self.function_expression.is_in_optional_chain() => {
+                return JsValue::Undefined;
+            },
+            _ => {
+                js_interpreter.throw_error(JsError::TypeError, "value is not a function");
+                return JsValue::Undefined;
+            },
+        }
+    }
 }
 
 
 #[derive(Debug)]
-pub struct JsAstObjectLiteral {
-    //NOTE: for now, we only support strings as member names, but we keep expressions here as key, because eventually we need to support
-    //      computed property names (using square brackets)
-    pub members: Vec<(JsAstExpression, JsAstExpression)>,
+pub struct JsAstObjectCreation {
+    pub constructor: Rc<JsAstExpression>,
+    pub arguments: Vec<JsAstExpression>,
 }
-impl JsAstObjectLiteral {
+impl JsAstObjectCreation {
     fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
-        let mut members = HashMap::new();
+        let constructor = self.constructor.execute(js_interpreter).deref(js_interpreter);
+        if js_interpreter.has_pending_exception() {
+            return JsValue::Undefined;
+        }
+
+        //a bound function constructs like its target, with the bound arguments in front (but the bound "this" is ignored)
+        let (constructor, mut arguments) = match constructor {
+            JsValue::Function(function) => resolve_bound_function(function),
+            _ => {
+                js_interpreter.throw_error(JsError::TypeError, "value is not a constructor");
+                return JsValue::Undefined;
+            }
+        };
+        if constructor.is_async || (constructor.builtin.is_some() && !constructor.builtin.as_ref().unwrap().is_constructor()) {
+            js_interpreter.throw_error(JsError::TypeError, "value is not a constructor");
+            return JsValue::Undefined;
+        }
+
+        for argument in self.arguments.iter() {
+            execute_list_element(js_interpreter, argument, &mut arguments);
+            if js_interpreter.has_pending_exception() {
+                return JsValue::Undefined;
+            }
+        }
+
+        //the new object gets the "prototype" property of the constructor as its prototype, if that is an object
+        let prototype = match constructor.prototype {
+            Some(address) if matches!(js_interpreter.get_value(address), Some(JsValue::Object(_))) => Some(address),
+            _ => None,
+        };
+        let object_address = js_interpreter.add_new_value(JsValue::Object(JsObject::new_with_prototype(prototype)));
+
+        if constructor.builtin.is_some() {
+            return js_builtins::construct_builtin_object(js_interpreter, constructor.builtin.unwrap(), object_address, arguments);
+        }
+
+        let return_value = call_function(js_interpreter, constructor, JsValue::Address(object_address), arguments);
+        if js_interpreter.has_pending_exception() {
+            return JsValue::Undefined;
+        }
+
+        //when the constructor returns an object itself, that is the result, otherwise it is the new object
+        if matches!(return_value.clone().deref(js_interpreter), JsValue::Object(_) | JsValue::Function(_)) {
+            return return_value;
+        }
+        return JsValue::Address(object_address);
+    }
+}
+
+
+fn resolve_bound_function(function: JsFunction) -> (JsFunction, Vec<JsValue>) {
+    //returns the function a bound function will eventually call, with all the arguments bound along the way
+    if function.bound.is_none() {
+        return (function, Vec::new());
+    }
+    let bound = function.bound.unwrap();
+    let (target, mut arguments) = resolve_bound_function(bound.target.clone());
+    arguments.extend(bound.arguments.iter().cloned());
+    return (target, arguments);
+}
+
 
-        for (key_ast, value_ast) in self.members.iter() {
+pub fn call_function(js_interpreter: &mut JsInterpreter, function: JsFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    //when the function throws, the exception is left pending on the interpreter, and we return undefined
 
-            match key_ast.execute(js_interpreter) {
-                JsValue::String(property_name) => {
+    if function.bound.is_some() {
+        let bound = function.bound.unwrap();
+        let mut all_arguments = bound.arguments.clone();
+        all_arguments.extend(arguments);
+        return call_function(js_interpreter, bound.target.clone(), bound.this_value.clone(), all_arguments);
+    }
+
+    if function.builtin.is_some() {
+        return call_builtin_function(js_interpreter, function.builtin.unwrap(), this_value, arguments);
+    }
+
+    //without a limit, a script recursing too deep would overflow the rust stack and crash the browser, now it gets an exception it can catch
+    if js_interpreter.call_depth >= MAX_CALL_DEPTH {
+        js_interpreter.throw_error(JsError::RangeError, "Maximum call stack size exceeded");
+        return JsValue::Undefined;
+    }
+
+    //functions that are not called on an object get the global object as "this"
+    //TODO: in strict mode, "this" should stay undefined
+    let this_value = match this_value {
+        JsValue::Undefined | JsValue::Null => JsValue::Address(js_interpreter.global_object_address),
+        _ => this_value,
+    };
+
+    //the function gets its own environment, which can also see the variables where the function was created
+    let parent_environment = function.environment.unwrap_or(js_interpreter.global_environment.clone());
+    let function_environment = JsEnvironment::new(Some(parent_environment));
+    let new_context = JsExecutionContext { environment: function_environment.clone(), var_environment: function_environment, this_value };
+    js_interpreter.context_stack.push(new_context);
+
+    let mut arguments = arguments.into_iter();
+    for argument_name in function.argument_names {
+        //arguments that are not passed are undefined
+        let argument_value = arguments.next().unwrap_or(JsValue::Undefined);
+        js_interpreter.declare_variable(argument_name, argument_value);
+    }
+    if function.rest_argument_name.is_some() {
+        let rest_array = js_interpreter.make_array(arguments.collect());
+        js_interpreter.declare_variable(function.rest_argument_name.unwrap(), rest_array);
+    }
+
+    let script = function.script.unwrap();
+    let run_function = move |js_interpreter: &mut JsInterpreter| {
+        hoist_var_declarations(js_interpreter, &script);
+        hoist_lexical_declarations(js_interpreter, &script);
+        let completion = js_interpreter.run_script_with_context_stack(&script);
+        js_interpreter.context_stack.pop();
+        return completion;
+    };
+
+    if function.is_async {
+        //the context of the function goes along to the stack the function runs on
+        let function_context = js_interpreter.context_stack.pop().unwrap();
+        js_interpreter.call_depth += 1;
+        let promise = js_async::call_async_function(js_interpreter, function_context, run_function);
+        js_interpreter.call_depth -= 1;
+        return promise;
+    }
+    js_interpreter.call_depth += 1;
+    let completion = run_function(js_interpreter);
+    js_interpreter.call_depth -= 1;
+
+    match completion {
+        JsCompletion::Return(return_value) => { return return_value; },
+        JsCompletion::Throw(exception) => {
+            js_interpreter.throw_value(exception);
+            return JsValue::Undefined;
+        },
+        _ => { return JsValue::Undefined; },
+    }
+}
 
-                    let value = value_ast.execute(js_interpreter);
-                    let current_context = js_interpreter.context_stack.iter_mut().last().unwrap();
-                    let address = current_context.add_new_value(value);
 
+fn call_builtin_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    //the arguments are not dereferenced yet, because some builtins need to keep the objects themselves (like "this" for bind)
+    let mut arguments = arguments;
+
+    match builtin {
+        JsBuiltinFunction::ConsoleLog => {
+            //TODO: objects should be shown with their members
+            let to_log: Vec<String> = arguments.into_iter().map(|argument| argument.deref(js_interpreter).to_js_string()).collect();
+            js_console::print(to_log.join(" ").as_str());
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::FunctionCall | JsBuiltinFunction::FunctionApply | JsBuiltinFunction::FunctionBind => {
+            //the function these are called on is "this", as in "f.call(x)"
+            let target = match this_value.deref(js_interpreter) {
+                JsValue::Function(target) => target,
+                _ => {
+                    js_interpreter.throw_error(JsError::TypeError, "value is not a function");
+                    return JsValue::Undefined;
+                }
+            };
+            let this_argument = if arguments.is_empty() { JsValue::Undefined } else { arguments.remove(0) };
 
-                    members.insert(property_name, address);
+            match builtin {
+                JsBuiltinFunction::FunctionCall => {
+                    return call_function(js_interpreter, target, this_argument, arguments);
+                },
+                JsBuiltinFunction::FunctionApply => {
+                    let arguments = match arguments.into_iter().next() {
+                        Some(array_like) => list_from_array_like(js_interpreter, array_like.deref(js_interpreter)),
+                        None => Vec::new(),
+                    };
+                    return call_function(js_interpreter, target, this_argument, arguments);
                 },
                 _ => {
-                    todo!(); //TODO: this should be an error
+                    let bound = JsBoundFunction { target, this_value: this_argument, arguments };
+                    return JsValue::Function(JsFunction { script: None, argument_names: Vec::new(), rest_argument_name: None, builtin: None, environment: None, bound: Some(Rc::from(bound)),
+                                                            prototype: None, properties: JsObject::new(), is_async: false });
                 }
             }
+        },
+        JsBuiltinFunction::MathFloor | JsBuiltinFunction::MathCeil | JsBuiltinFunction::MathRound | JsBuiltinFunction::MathRandom |
+        JsBuiltinFunction::MathMin | JsBuiltinFunction::MathMax | JsBuiltinFunction::MathAbs | JsBuiltinFunction::MathPow |
+        JsBuiltinFunction::NumberIsNaN | JsBuiltinFunction::ParseInt | JsBuiltinFunction::ParseFloat | JsBuiltinFunction::IsNaN => {
+            let arguments = arguments.into_iter().map(|argument| argument.deref(js_interpreter)).collect();
+            return js_builtins::call_number_function(js_interpreter, builtin, arguments);
+        },
+        JsBuiltinFunction::DateConstructor | JsBuiltinFunction::DateNow | JsBuiltinFunction::DateGetTime | JsBuiltinFunction::DateGetFullYear |
+        JsBuiltinFunction::DateGetMonth | JsBuiltinFunction::DateGetDate | JsBuiltinFunction::DateGetHours | JsBuiltinFunction::DateToISOString => {
+            return js_builtins::call_date_function(js_interpreter, builtin, this_value);
+        },
+        JsBuiltinFunction::RegExpConstructor | JsBuiltinFunction::RegExpTest | JsBuiltinFunction::RegExpExec |
+        JsBuiltinFunction::StringMatch | JsBuiltinFunction::StringReplace => {
+            return js_builtins::call_regexp_function(js_interpreter, builtin, this_value, arguments);
+        },
+        JsBuiltinFunction::SetTimeout | JsBuiltinFunction::SetInterval | JsBuiltinFunction::ClearTimer => {
+            return js_builtins::call_timer_function(js_interpreter, builtin, arguments);
+        },
+        JsBuiltinFunction::ErrorConstructor(error) => {
+            //calling an error constructor without new makes an error as well
+            let prototype = js_interpreter.error_prototype_addresses.get(&error).copied();
+            let object_address = js_interpreter.add_new_value(JsValue::Object(JsObject::new_with_prototype(prototype)));
+            return js_builtins::construct_builtin_object(js_interpreter, builtin, object_address, arguments);
+        },
+        JsBuiltinFunction::PromiseConstructor | JsBuiltinFunction::PromiseResolver(..) | JsBuiltinFunction::PromiseThen | JsBuiltinFunction::PromiseCatch |
+        JsBuiltinFunction::PromiseStaticResolve | JsBuiltinFunction::PromiseStaticReject => {
+            return js_promise::call_promise_function(js_interpreter, builtin, this_value, arguments);
+        },
+        JsBuiltinFunction::AsyncFunctionResume(id, is_rejection) => {
+            let awaited_value = arguments.into_iter().next().unwrap_or(JsValue::Undefined);
+            let awaited_result = if is_rejection { Err(awaited_value) } else { Ok(awaited_value) };
+            js_async::resume_suspended_async_function(js_interpreter, id, awaited_result);
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::DocumentGetElementById | JsBuiltinFunction::DocumentQuerySelector | JsBuiltinFunction::DocumentQuerySelectorAll |
+        JsBuiltinFunction::ElementQuerySelector | JsBuiltinFunction::ElementQuerySelectorAll | JsBuiltinFunction::ElementGetTagName |
+        JsBuiltinFunction::ElementGetId | JsBuiltinFunction::DocumentCreateElement | JsBuiltinFunction::DocumentCreateTextNode |
+        JsBuiltinFunction::DocumentGetReadyState | JsBuiltinFunction::ElementAppendChild | JsBuiltinFunction::ElementInsertBefore |
+        JsBuiltinFunction::ElementRemoveChild | JsBuiltinFunction::ElementGetInnerHtml | JsBuiltinFunction::ElementSetInnerHtml |
+        JsBuiltinFunction::ElementGetTextContent | JsBuiltinFunction::ElementSetTextContent | JsBuiltinFunction::ElementGetAttribute |
+        JsBuiltinFunction::ElementSetAttribute | JsBuiltinFunction::ElementRemoveAttribute | JsBuiltinFunction::ElementHasAttribute | JsBuiltinFunction::ElementSetId |
+        JsBuiltinFunction::ElementGetClassName | JsBuiltinFunction::ElementSetClassName | JsBuiltinFunction::ElementGetClassList |
+        JsBuiltinFunction::ElementGetStyle | JsBuiltinFunction::ClassListAdd | JsBuiltinFunction::ClassListRemove |
+        JsBuiltinFunction::ClassListToggle | JsBuiltinFunction::ClassListContains | JsBuiltinFunction::StyleGetPropertyValue |
+        JsBuiltinFunction::StyleSetProperty | JsBuiltinFunction::StyleRemoveProperty | JsBuiltinFunction::StyleGetCssText |
+        JsBuiltinFunction::StyleSetCssText | JsBuiltinFunction::ElementGetBoundingClientRect | JsBuiltinFunction::GetComputedStyle |
+        JsBuiltinFunction::ComputedStyleGetPropertyValue | JsBuiltinFunction::WindowGetInnerWidth | JsBuiltinFunction::WindowGetInnerHeight |
+        JsBuiltinFunction::WindowGetDevicePixelRatio | JsBuiltinFunction::ScreenGetWidth | JsBuiltinFunction::ScreenGetHeight |
+        JsBuiltinFunction::WindowAlert | JsBuiltinFunction::WindowConfirm | JsBuiltinFunction::WindowPrompt => {
+            return js_dom::call_dom_function(js_interpreter, builtin, this_value, arguments);
+        },
+        JsBuiltinFunction::AddEventListener | JsBuiltinFunction::RemoveEventListener | JsBuiltinFunction::EventPreventDefault |
+        JsBuiltinFunction::EventStopPropagation | JsBuiltinFunction::EventStopImmediatePropagation | JsBuiltinFunction::EventGetType |
+        JsBuiltinFunction::EventGetTarget | JsBuiltinFunction::EventGetCurrentTarget | JsBuiltinFunction::EventGetEventPhase |
+        JsBuiltinFunction::EventGetBubbles | JsBuiltinFunction::EventGetCancelable | JsBuiltinFunction::EventGetDefaultPrevented => {
+            return js_events::call_event_function(js_interpreter, builtin, this_value, arguments);
+        },
+        JsBuiltinFunction::WebSocketConstructor | JsBuiltinFunction::WebSocketSend | JsBuiltinFunction::WebSocketClose |
+        JsBuiltinFunction::WebSocketGetReadyState | JsBuiltinFunction::WebSocketGetUrl => {
+            return js_websocket::call_websocket_function(js_interpreter, builtin, this_value, arguments);
+        },
+        JsBuiltinFunction::WptReport => {
+            let arguments: Vec<JsValue> = arguments.into_iter().map(|argument| argument.deref(js_interpreter)).collect();
+            if arguments.len() >= 4 {
+                js_interpreter.record_wpt_result(arguments[0].to_number() as usize, arguments[1].to_js_string(), &arguments[2].to_js_string(),
+                                                 arguments[3].to_js_string());
+            }
+            return JsValue::Undefined;
+        },
+        #[cfg(test)] JsBuiltinFunction::TesterExport => {
+            let data = if arguments.is_empty() { JsValue::Undefined } else { arguments.remove(0).deref(js_interpreter) };
+            js_interpreter.export_test_data(data);
+            return JsValue::Undefined;
+        }
+    }
+}
+
+
+fn list_from_array_like(js_interpreter: &mut JsInterpreter, array_like: JsValue) -> Vec<JsValue> {
+    //the value should already be dereferenced
+    //this works for arrays, but also for other objects with a length and numbered members
+    match array_like {
+        JsValue::Undefined | JsValue::Null => { return Vec::new(); },
+        JsValue::Object(ref object) => {
+            let length = get_property(js_interpreter, array_like.clone(), &String::from("length")).deref(js_interpreter).to_number();
+            let length = if length.is_finite() && length > 0.0 { length as usize } else { 0 };
+
+            let mut list = Vec::new();
+            for idx in 0..length {
+                match object.get_member(&idx.to_string()) {
+                    Some(address) => { list.push(JsValue::Address(*address)); },
+                    None => { list.push(JsValue::Undefined); },
+                }
+            }
+            return list;
+        },
+        _ => {
+            js_interpreter.throw_error(JsError::TypeError, "the arguments list should be an array-like object");
+            return Vec::new();
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstConditional {
+    pub condition: Rc<JsAstExpression>,
+    pub then_expression: Rc<JsAstExpression>,
+    pub else_expression: Rc<JsAstExpression>,
+}
+impl JsAstConditional {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        let condition = self.condition.execute(js_interpreter);
+
+        if condition.is_truthy(js_interpreter) {
+            return self.then_expression.execute(js_interpreter);
+        }
+        return self.else_expression.execute(js_interpreter);
+    }
+}
+
+
+#[derive(Debug)]
+pub struct JsAstObjectLiteral {
+    pub members: Vec<JsAstObjectLiteralMember>,
+}
+impl JsAstObjectLiteral {
+    fn execute(&self, js_interpreter: &mut JsInterpreter) -> JsValue {
+        let mut object = JsObject::new();
+
+        for member in self.members.iter() {
+            //the key is a string literal for normal keys, and any expression for computed keys (as in "{[key]: value}")
+            let property_name = member.key.execute(js_interpreter).deref(js_interpreter).to_primitive(js_interpreter).to_js_string();
+            let value = member.value.execute(js_interpreter);
+            if js_interpreter.has_pending_exception() {
+                return JsValue::Undefined;
+            }
+
+            match member.kind {
+                JsPropertyKind::Value => {
+                    let address = js_interpreter.store_value(value);
+                    object.set_member(property_name, address);
+                },
+                JsPropertyKind::Getter | JsPropertyKind::Setter => {
+                    let function = match value.deref(js_interpreter) {
+                        JsValue::Function(function) => function,
+                        _ => { panic!("the parser only makes functions for getters and setters"); }
+                    };
+
+                    //a getter and a setter for the same name together make one accessor
+                    let mut accessor = object.get_accessor(&property_name).cloned().unwrap_or(JsAccessor { getter: None, setter: None });
+                    if matches!(member.kind, JsPropertyKind::Getter) {
+                        accessor.getter = Some(function);
+                    } else {
+                        accessor.setter = Some(function);
+                    }
+                    object.set_accessor(property_name, accessor);
+                },
+            }
+        }
+        return JsValue::Object(object);
+    }
+}
+
+
+#[derive(Debug)]
+pub enum JsPropertyKind {
+    Value,
+    Getter,
+    Setter,
+}
 
+
+#[derive(Debug)]
+pub struct JsAstObjectLiteralMember {
+    pub key: JsAstExpression,
+    pub value: JsAstExpression,
+    pub kind: JsPropertyKind,
+}
+
+
+pub fn parse_numeric_literal(literal: &str) -> Option<f64> {
+    //numeric separators (like in 1_000) are allowed in literals, but don't mean anything
+    let literal = literal.replace('_', "");
+
+    let radix_prefixes = [("0x", 16), ("0X", 16), ("0o", 8), ("0O", 8), ("0b", 2), ("0B", 2)];
+    for (prefix, radix) in radix_prefixes {
+        if let Some(digits) = literal.strip_prefix(prefix) {
+            //we parse to u128 rather than f64 directly, so very large literals might not be exact
+            return u128::from_str_radix(digits, radix).ok().map(|number| number as f64);
         }
-        return JsValue::Object(JsObject { members });
     }
+
+    //rust also parses things like "inf" and "NaN", which are not numeric literals in javascript
+    if !literal.chars().all(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-') {
+        return None;
+    }
+    return literal.parse().ok();
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use corosensei::{Coroutine, CoroutineResult, Yielder};
+use corosensei::stack::DefaultStack;
+
+use super::js_ast::JsCompletion;
+use super::js_execution_context::{JsAddress, JsBuiltinFunction, JsExecutionContext, JsFunction, JsValue};
+use super::js_interpreter::JsInterpreter;
+use super::js_promise;
+
+
+//Async functions run on a stack of their own (a coroutine), so an await can suspend them while the code that called them continues. Since we
+//execute the ast directly, everything the function was doing is on the rust stack, and this is how we keep that while the function waits.
+
+
+const ASYNC_FUNCTION_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+
+struct JsAsyncResume {
+    interpreter: *mut JsInterpreter,
+    awaited_result: Result<JsValue, JsValue>, //what the awaited promise fulfilled with, or the reason it was rejected with
+}
+
+
+type JsAsyncYielder = Yielder<JsAsyncResume, JsAddress>; //an async function yields the promise it awaits
+
+
+struct JsSuspendedAsyncFunction {
+    coroutine: Coroutine<JsAsyncResume, JsAddress, JsCompletion, DefaultStack>,
+    contexts: Vec<JsExecutionContext>, //the part of the call stack that belongs to the function, which is put back when it continues
+    promise: JsAddress, //the promise the function returned, which settles when the function is done
+    interpreter: *const JsInterpreter,
+}
+
+
+pub struct JsAsyncFunctions {
+    suspended: HashMap<u32, JsSuspendedAsyncFunction>, //the functions waiting for a promise, by the id their resume functions have
+    running: Vec<*const JsAsyncYielder>, //the async functions that are running now, the innermost last, which is the one await suspends
+    next_id: u32,
+}
+impl JsAsyncFunctions {
+    pub fn new() -> JsAsyncFunctions {
+        return JsAsyncFunctions { suspended: HashMap::new(), running: Vec::new(), next_id: 1 };
+    }
+}
+
+
+pub fn call_async_function<F>(js_interpreter: &mut JsInterpreter, function_context: JsExecutionContext, run_function: F) -> JsValue
+    where F: FnOnce(&mut JsInterpreter) -> JsCompletion + 'static {
+    //the function runs until its first await, and then we return the promise for its result, which settles when the rest of it has run
+    let promise = js_promise::new_promise(js_interpreter);
+
+    let stack = DefaultStack::new(ASYNC_FUNCTION_STACK_SIZE).expect("could not allocate the stack for an async function");
+    let coroutine = Coroutine::with_stack(stack, move |yielder: &JsAsyncYielder, resume: JsAsyncResume| {
+        //SAFETY: the interpreter resumes us with a pointer to itself, which it does not use until we yield or return, and it can't move
+        //        while we exist, because resume_async_function() checks that. We don't use this reference after we return from run_function(),
+        //        the yielder is taken off the running functions by resume_async_function() instead.
+        let js_interpreter = unsafe { &mut *resume.interpreter };
+
+        js_interpreter.async_functions.running.push(yielder);
+        return run_function(js_interpreter);
+    });
+
+    let function = JsSuspendedAsyncFunction { coroutine, contexts: vec![function_context], promise, interpreter: js_interpreter };
+    resume_async_function(js_interpreter, function, Ok(JsValue::Undefined));
+    return JsValue::Address(promise);
+}
+
+
+pub fn await_value(js_interpreter: &mut JsInterpreter, value: JsValue) -> JsValue {
+    //this suspends the async function we are in, until the promise settles, the code that called or resumed the function continues meanwhile
+    let promise = js_promise::promise_resolve(js_interpreter, value);
+    let yielder = js_interpreter.async_functions.running.pop().expect("await should only be parsed in async functions");
+
+    //SAFETY: the yielder is on the stack of the coroutine we are running on, so it is there until the function is done
+    let resume = unsafe { &*yielder }.suspend(promise);
+
+    //SAFETY: the code that resumed us used the interpreter while we were suspended, so we continue with the pointer we are resumed with, instead
+    //        of the reference we had before suspending
+    let js_interpreter = unsafe { &mut *resume.interpreter };
+    js_interpreter.async_functions.running.push(yielder);
+
+    match resume.awaited_result {
+        Ok(value) => { return value; },
+        Err(reason) => {
+            js_interpreter.throw_value(reason);
+            return JsValue::Undefined;
+        },
+    }
+}
+
+
+pub fn resume_suspended_async_function(js_interpreter: &mut JsInterpreter, id: u32, awaited_result: Result<JsValue, JsValue>) {
+    //this is called (from a microtask) when the promise the function awaits settled
+    let function = js_interpreter.async_functions.suspended.remove(&id);
+    if function.is_none() {
+        return;
+    }
+    resume_async_function(js_interpreter, function.unwrap(), awaited_result);
+}
+
+
+fn resume_async_function(js_interpreter: &mut JsInterpreter, mut function: JsSuspendedAsyncFunction, awaited_result: Result<JsValue, JsValue>) {
+    //the stack of the function refers to the interpreter, so when that moved, continuing the function would use freed memory
+    assert!(std::ptr::eq(function.interpreter, js_interpreter), "the interpreter was moved while an async function was suspended");
+
+    let context_depth = js_interpreter.context_stack.len();
+    js_interpreter.context_stack.append(&mut function.contexts);
+
+    let interpreter_pointer: *mut JsInterpreter = js_interpreter;
+    match function.coroutine.resume(JsAsyncResume { interpreter: interpreter_pointer, awaited_result }) {
+        CoroutineResult::Yield(awaited_promise) => {
+            function.contexts = js_interpreter.context_stack.split_off(context_depth);
+
+            let id = js_interpreter.async_functions.next_id;
+            js_interpreter.async_functions.next_id += 1;
+            js_interpreter.async_functions.suspended.insert(id, function);
+
+            let on_fulfilled = JsFunction::new_builtin(JsBuiltinFunction::AsyncFunctionResume(id, false));
+            let on_rejected = JsFunction::new_builtin(JsBuiltinFunction::AsyncFunctionResume(id, true));
+            js_promise::add_reactions(js_interpreter, awaited_promise, on_fulfilled, on_rejected);
+        },
+        CoroutineResult::Return(completion) => {
+            js_interpreter.async_functions.running.pop();
+            js_promise::settle_with_completion(js_interpreter, function.promise, completion);
+        },
+    }
+}
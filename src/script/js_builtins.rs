@@ -0,0 +1,815 @@
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::date::{civil_from_days, days_from_civil};
+
+use super::js_ast::call_function;
+use super::js_execution_context::{JsAddress, JsBuiltinFunction, JsError, JsInternalSlot, JsObject, JsValue};
+use super::js_interpreter::JsInterpreter;
+use super::js_promise;
+use super::js_regex::{JsRegex, JsRegexCaptures};
+use super::js_websocket;
+
+
+const MILLISECONDS_PER_DAY: f64 = 86_400_000.0;
+const MAX_TIME_VALUE: f64 = 8.64e15; //dates can be at most 100 million days away from 1970
+
+
+pub fn call_number_function(js_interpreter: &JsInterpreter, builtin: JsBuiltinFunction, arguments: Vec<JsValue>) -> JsValue {
+    //the arguments should already be dereferenced
+    let number_argument = |idx: usize| -> f64 {
+        return arguments.get(idx).map(|argument| argument.clone().to_primitive(js_interpreter).to_number()).unwrap_or(f64::NAN);
+    };
+
+    match builtin {
+        JsBuiltinFunction::MathFloor => { return JsValue::Number(number_argument(0).floor()); },
+        JsBuiltinFunction::MathCeil => { return JsValue::Number(number_argument(0).ceil()); },
+        JsBuiltinFunction::MathAbs => { return JsValue::Number(number_argument(0).abs()); },
+        JsBuiltinFunction::MathRound => {
+            //javascript rounds halves up (so -2.5 becomes -2), where rust rounds them away from zero
+            let number = number_argument(0);
+            let floor = number.floor();
+            return JsValue::Number(if number - floor >= 0.5 { floor + 1.0 } else { floor });
+        },
+        JsBuiltinFunction::MathRandom => { return JsValue::Number(fastrand::f64()); },
+        JsBuiltinFunction::MathMin | JsBuiltinFunction::MathMax => {
+            //without arguments, min is Infinity and max is -Infinity, and if any argument is NaN, so is the result
+            let is_min = matches!(builtin, JsBuiltinFunction::MathMin);
+            let mut result = if is_min { f64::INFINITY } else { f64::NEG_INFINITY };
+            for idx in 0..arguments.len() {
+                let number = number_argument(idx);
+                if number.is_nan() {
+                    return JsValue::Number(f64::NAN);
+                }
+                result = if is_min { result.min(number) } else { result.max(number) };
+            }
+            return JsValue::Number(result);
+        },
+        JsBuiltinFunction::MathPow => {
+            let base = number_argument(0);
+            let exponent = number_argument(1);
+
+            //rust gives 1 for these, but javascript gives NaN
+            if exponent.is_nan() || (base.abs() == 1.0 && exponent.is_infinite()) {
+                return JsValue::Number(f64::NAN);
+            }
+            return JsValue::Number(base.powf(exponent));
+        },
+        JsBuiltinFunction::NumberIsNaN => {
+            //unlike the global isNaN, this does not convert its argument, so only the number NaN itself is NaN
+            return JsValue::Boolean(matches!(arguments.first(), Some(JsValue::Number(number)) if number.is_nan()));
+        },
+        JsBuiltinFunction::IsNaN => { return JsValue::Boolean(number_argument(0).is_nan()); },
+        JsBuiltinFunction::ParseInt => {
+            let string = arguments.first().map(|argument| argument.clone().to_primitive(js_interpreter).to_js_string()).unwrap_or(String::from("undefined"));
+            let radix = if arguments.len() > 1 { number_argument(1) } else { 0.0 };
+            return JsValue::Number(parse_int(&string, radix));
+        },
+        JsBuiltinFunction::ParseFloat => {
+            let string = arguments.first().map(|argument| argument.clone().to_primitive(js_interpreter).to_js_string()).unwrap_or(String::from("undefined"));
+            return JsValue::Number(parse_float(&string));
+        },
+        _ => { panic!("not a number function"); },
+    }
+}
+
+
+fn split_sign(string: &str) -> (f64, &str) {
+    if let Some(rest) = string.strip_prefix('-') {
+        return (-1.0, rest);
+    }
+    return (1.0, string.strip_prefix('+').unwrap_or(string));
+}
+
+
+fn parse_int(string: &str, radix: f64) -> f64 {
+    //parseInt reads as many digits as it can from the start of the string, and ignores the rest
+    let (sign, mut digits) = split_sign(string.trim_start());
+
+    //a radix of 0 (or NaN, or a missing radix) means 10, unless the string starts with 0x
+    let mut radix = if radix.is_finite() { radix.trunc() as i64 } else { 0 };
+    let has_hex_prefix = digits.starts_with("0x") || digits.starts_with("0X");
+    if radix == 0 {
+        radix = if has_hex_prefix { 16 } else { 10 };
+    }
+    if radix < 2 || radix > 36 {
+        return f64::NAN;
+    }
+    if radix == 16 && has_hex_prefix {
+        digits = &digits[2..];
+    }
+
+    let mut result: Option<f64> = None;
+    for char in digits.chars() {
+        let digit = char.to_digit(radix as u32);
+        if digit.is_none() {
+            break;
+        }
+        result = Some(result.unwrap_or(0.0) * radix as f64 + digit.unwrap() as f64);
+    }
+
+    return result.map(|number| sign * number).unwrap_or(f64::NAN);
+}
+
+
+fn parse_float(string: &str) -> f64 {
+    //parseFloat reads the longest decimal number at the start of the string, and ignores the rest
+    let (sign, rest) = split_sign(string.trim_start());
+    if rest.starts_with("Infinity") {
+        return sign * f64::INFINITY;
+    }
+
+    let chars: Vec<char> = rest.chars().collect();
+    let count_digits = |from: usize| -> usize { return chars[from..].iter().take_while(|char| char.is_ascii_digit()).count(); };
+
+    let mut length = count_digits(0);
+    let mut has_digits = length > 0;
+    if chars.get(length) == Some(&'.') {
+        let fraction_length = count_digits(length + 1);
+        has_digits = has_digits || fraction_length > 0;
+        length += 1 + fraction_length;
+    }
+    if !has_digits {
+        return f64::NAN;
+    }
+
+    //the exponent is only part of the number when it has digits, so "1e" is just 1
+    if chars.get(length) == Some(&'e') || chars.get(length) == Some(&'E') {
+        let mut exponent_start = length + 1;
+        if chars.get(exponent_start) == Some(&'+') || chars.get(exponent_start) == Some(&'-') {
+            exponent_start += 1;
+        }
+        let exponent_length = count_digits(exponent_start);
+        if exponent_length > 0 {
+            length = exponent_start + exponent_length;
+        }
+    }
+
+    let number_text: String = chars[..length].iter().collect();
+    return sign * number_text.parse::<f64>().unwrap_or(f64::NAN);
+}
+
+
+pub fn construct_builtin_object(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, object_address: JsAddress, arguments: Vec<JsValue>) -> JsValue {
+    //the object is already made (with the right prototype), here we set up the internal state it gets from its constructor
+    let arguments: Vec<JsValue> = arguments.into_iter().map(|argument| argument.deref(js_interpreter)).collect();
+
+    match builtin {
+        JsBuiltinFunction::DateConstructor => {
+            let date_value = date_value_from_arguments(js_interpreter, &arguments);
+            if let Some(JsValue::Object(object)) = js_interpreter.get_value_mut(object_address) {
+                object.set_internal_slot(JsInternalSlot::DateValue(date_value));
+            }
+        },
+        JsBuiltinFunction::RegExpConstructor => {
+            match regex_from_arguments(js_interpreter, &arguments) {
+                Ok(regex) => { init_regexp_object(js_interpreter, object_address, Rc::from(regex)); },
+                Err(message) => {
+                    js_interpreter.throw_error(JsError::SyntaxError, format!("invalid regular expression: {}", message).as_str());
+                    return JsValue::Undefined;
+                }
+            }
+        },
+        JsBuiltinFunction::PromiseConstructor => {
+            let executor = arguments.into_iter().next().unwrap_or(JsValue::Undefined);
+            return js_promise::init_promise_object(js_interpreter, object_address, executor);
+        },
+        JsBuiltinFunction::WebSocketConstructor => {
+            return js_websocket::init_websocket_object(js_interpreter, object_address, arguments);
+        },
+        JsBuiltinFunction::ErrorConstructor(_) => {
+            //the name comes from the prototype, and so does the (empty) message when there is none
+            //TODO: the options argument can have a cause as well
+            let message = arguments.first().cloned().unwrap_or(JsValue::Undefined);
+            if !matches!(message, JsValue::Undefined) {
+                let message = message.to_primitive(js_interpreter).to_js_string();
+                set_member_value(js_interpreter, object_address, "message", JsValue::String(message));
+            }
+        },
+        _ => { panic!("not a constructor"); },
+    }
+    return JsValue::Address(object_address);
+}
+
+
+fn get_member_value(js_interpreter: &JsInterpreter, object_address: JsAddress, name: &str) -> JsValue {
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value(object_address) {
+        if let Some(member_address) = object.get_member(&String::from(name)) {
+            return JsValue::Address(*member_address).deref(js_interpreter);
+        }
+    }
+    return JsValue::Undefined;
+}
+
+
+fn set_member_value(js_interpreter: &mut JsInterpreter, object_address: JsAddress, name: &str, value: JsValue) {
+    let value_address = js_interpreter.add_new_value(value);
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value_mut(object_address) {
+        object.set_member(String::from(name), value_address);
+    }
+}
+
+
+pub fn call_date_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue) -> JsValue {
+    match builtin {
+        //calling Date as a function (instead of with new) just gives the current time as a string
+        JsBuiltinFunction::DateConstructor => { return JsValue::String(date_to_string(current_time())); },
+        JsBuiltinFunction::DateNow => { return JsValue::Number(current_time()); },
+        _ => {},
+    }
+
+    let time = match this_value.deref(js_interpreter) {
+        JsValue::Object(object) => {
+            match object.internal_slot() {
+                Some(JsInternalSlot::DateValue(time)) => *time,
+                _ => {
+                    js_interpreter.throw_error(JsError::TypeError, "this is not a Date object");
+                    return JsValue::Undefined;
+                },
+            }
+        },
+        _ => {
+            js_interpreter.throw_error(JsError::TypeError, "this is not a Date object");
+            return JsValue::Undefined;
+        },
+    };
+
+    if time.is_nan() {
+        if matches!(builtin, JsBuiltinFunction::DateToISOString) {
+            js_interpreter.throw_error(JsError::RangeError, "invalid time value");
+            return JsValue::Undefined;
+        }
+        return JsValue::Number(f64::NAN);
+    }
+
+    //TODO: these should be in the local timezone, but for now we treat all times as UTC
+    let components = DateComponents::from_time(time);
+    match builtin {
+        JsBuiltinFunction::DateGetTime => { return JsValue::Number(time); },
+        JsBuiltinFunction::DateGetFullYear => { return JsValue::Number(components.year as f64); },
+        JsBuiltinFunction::DateGetMonth => { return JsValue::Number(components.month as f64); },
+        JsBuiltinFunction::DateGetDate => { return JsValue::Number(components.day as f64); },
+        JsBuiltinFunction::DateGetHours => { return JsValue::Number(components.hours as f64); },
+        JsBuiltinFunction::DateToISOString => { return JsValue::String(components.to_iso_string()); },
+        _ => { panic!("not a date function"); },
+    }
+}
+
+
+fn current_time() -> f64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    return since_epoch.as_millis() as f64;
+}
+
+
+fn time_clip(time: f64) -> f64 {
+    if !time.is_finite() || time.abs() > MAX_TIME_VALUE {
+        return f64::NAN;
+    }
+    return time.trunc() + 0.0; //adding zero turns -0 into 0
+}
+
+
+fn date_value_from_arguments(js_interpreter: &JsInterpreter, arguments: &Vec<JsValue>) -> f64 {
+    //the arguments should already be dereferenced
+    if arguments.is_empty() {
+        return current_time();
+    }
+
+    if arguments.len() == 1 {
+        //a single argument is another date to copy, a string to parse, or the time itself
+        if let JsValue::Object(object) = &arguments[0] {
+            if let Some(JsInternalSlot::DateValue(time)) = object.internal_slot() {
+                return *time;
+            }
+        }
+        return match arguments[0].clone().to_primitive(js_interpreter) {
+            JsValue::String(string) => parse_date_string(&string),
+            primitive => time_clip(primitive.to_number()),
+        };
+    }
+
+    //with multiple arguments, they are the year, month, day, hours, minutes, seconds and milliseconds (and only year and month are required)
+    let mut numbers = [f64::NAN, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+    for (idx, argument) in arguments.iter().take(numbers.len()).enumerate() {
+        numbers[idx] = argument.clone().to_primitive(js_interpreter).to_number();
+    }
+    if numbers.iter().any(|number| !number.is_finite()) {
+        return f64::NAN;
+    }
+
+    //years 0 up to 99 mean 1900 up to 1999
+    let year = numbers[0].trunc();
+    let year = if year >= 0.0 && year <= 99.0 { 1900.0 + year } else { year };
+    return time_clip(make_time(year, numbers[1], numbers[2], numbers[3], numbers[4], numbers[5], numbers[6]));
+}
+
+
+fn make_time(year: f64, month: f64, day: f64, hours: f64, minutes: f64, seconds: f64, milliseconds: f64) -> f64 {
+    //the month and day can be out of range, they then move the date forward or backward (so month 12 is january of the next year)
+    let month = month.trunc();
+    let year = year.trunc() + (month / 12.0).floor();
+    let month = month.rem_euclid(12.0);
+    if year.abs() > 1_000_000.0 {
+        return f64::NAN;
+    }
+
+    let days = days_from_civil(year as i64, month as i64 + 1, 1) as f64 + day.trunc() - 1.0;
+    let milliseconds_in_day = hours.trunc() * 3_600_000.0 + minutes.trunc() * 60_000.0 + seconds.trunc() * 1000.0 + milliseconds.trunc();
+    return days * MILLISECONDS_PER_DAY + milliseconds_in_day;
+}
+
+
+fn parse_date_string(string: &str) -> f64 {
+    //we support the format from the spec, which looks like "2024-02-29T13:45:10.123Z", where everything after the year is optional
+    //TODO: browsers also accept a lot of other formats, like "Feb 29 2024"
+    let chars: Vec<char> = string.trim().chars().collect();
+    let mut idx = 0;
+
+    let read_number = |digit_count: usize, idx: &mut usize| -> Option<f64> {
+        if *idx + digit_count > chars.len() || !chars[*idx..*idx + digit_count].iter().all(|char| char.is_ascii_digit()) {
+            return None;
+        }
+        let number: String = chars[*idx..*idx + digit_count].iter().collect();
+        *idx += digit_count;
+        return number.parse::<f64>().ok();
+    };
+    let read_separator = |separator: char, idx: &mut usize| -> bool {
+        if chars.get(*idx) == Some(&separator) {
+            *idx += 1;
+            return true;
+        }
+        return false;
+    };
+
+    //years can also be written with a sign and 6 digits, for years before 0 or after 9999
+    let year = match chars.first() {
+        Some('+') | Some('-') => {
+            let sign = if chars[0] == '-' { -1.0 } else { 1.0 };
+            idx += 1;
+            read_number(6, &mut idx).map(|year| sign * year)
+        },
+        _ => read_number(4, &mut idx),
+    };
+    if year.is_none() {
+        return f64::NAN;
+    }
+
+    let mut numbers = [year.unwrap(), 1.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+    for (number_idx, separator) in [(1, '-'), (2, '-')] {
+        if read_separator(separator, &mut idx) {
+            match read_number(2, &mut idx) {
+                Some(number) => { numbers[number_idx] = number; },
+                None => { return f64::NAN; },
+            }
+        }
+    }
+
+    if read_separator('T', &mut idx) {
+        for (number_idx, separator) in [(3, None), (4, Some(':')), (5, Some(':'))] {
+            if separator.is_some() && !read_separator(separator.unwrap(), &mut idx) {
+                if number_idx == 5 {
+                    break; //the seconds are optional
+                }
+                return f64::NAN;
+            }
+            match read_number(2, &mut idx) {
+                Some(number) => { numbers[number_idx] = number; },
+                None => { return f64::NAN; },
+            }
+        }
+        if read_separator('.', &mut idx) {
+            match read_number(3, &mut idx) {
+                Some(number) => { numbers[6] = number; },
+                None => { return f64::NAN; },
+            }
+        }
+    }
+
+    //an offset like +02:00 means the time is 2 hours ahead of UTC, so we subtract it
+    let mut offset = 0.0;
+    if idx < chars.len() && (chars[idx] == '+' || chars[idx] == '-') {
+        let sign = if chars[idx] == '-' { -1.0 } else { 1.0 };
+        idx += 1;
+        let offset_hours = read_number(2, &mut idx);
+        if offset_hours.is_none() || !read_separator(':', &mut idx) {
+            return f64::NAN;
+        }
+        let offset_minutes = read_number(2, &mut idx);
+        if offset_minutes.is_none() {
+            return f64::NAN;
+        }
+        offset = sign * (offset_hours.unwrap() * 3_600_000.0 + offset_minutes.unwrap() * 60_000.0);
+    } else {
+        read_separator('Z', &mut idx);
+    }
+    if idx != chars.len() {
+        return f64::NAN;
+    }
+
+    let [year, month, day, hours, minutes, seconds, milliseconds] = numbers;
+    if month < 1.0 || month > 12.0 || day < 1.0 || day > days_in_month(year as i64, month as i64) as f64 || hours > 24.0 || minutes > 59.0 || seconds > 59.0 {
+        return f64::NAN;
+    }
+    return time_clip(make_time(year, month - 1.0, day, hours, minutes, seconds, milliseconds) - offset);
+}
+
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    return days_from_civil(year + month / 12, month % 12 + 1, 1) - days_from_civil(year, month, 1);
+}
+
+
+struct DateComponents {
+    year: i64,
+    month: i64, //like in javascript, the month is 0-based
+    day: i64,
+    week_day: i64, //0 is sunday
+    hours: i64,
+    minutes: i64,
+    seconds: i64,
+    milliseconds: i64,
+}
+impl DateComponents {
+    fn from_time(time: f64) -> DateComponents {
+        let days = (time / MILLISECONDS_PER_DAY).floor() as i64;
+        let milliseconds_in_day = (time - days as f64 * MILLISECONDS_PER_DAY) as i64;
+        let (year, month, day) = civil_from_days(days);
+
+        return DateComponents {
+            year,
+            month: month - 1,
+            day,
+            week_day: (days + 4).rem_euclid(7), //1970-01-01 was a thursday
+            hours: milliseconds_in_day / 3_600_000,
+            minutes: (milliseconds_in_day / 60_000) % 60,
+            seconds: (milliseconds_in_day / 1000) % 60,
+            milliseconds: milliseconds_in_day % 1000,
+        };
+    }
+
+    fn to_iso_string(&self) -> String {
+        let year = if self.year >= 0 && self.year <= 9999 {
+            format!("{:04}", self.year)
+        } else {
+            format!("{}{:06}", if self.year < 0 { "-" } else { "+" }, self.year.abs())
+        };
+        return format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, self.month + 1, self.day, self.hours, self.minutes, self.seconds, self.milliseconds);
+    }
+}
+
+
+fn date_to_string(time: f64) -> String {
+    const WEEK_DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let components = DateComponents::from_time(time);
+    return format!("{} {} {:02} {:04} {:02}:{:02}:{:02} GMT+0000 (Coordinated Universal Time)", WEEK_DAYS[components.week_day as usize],
+                   MONTHS[components.month as usize], components.day, components.year, components.hours, components.minutes, components.seconds);
+}
+
+
+pub fn init_regexp_object(js_interpreter: &mut JsInterpreter, object_address: JsAddress, regex: Rc<JsRegex>) {
+    //TODO: source, flags and global should be getters on RegExp.prototype, but for now they are normal members
+    set_member_value(js_interpreter, object_address, "lastIndex", JsValue::Number(0.0));
+    set_member_value(js_interpreter, object_address, "source", JsValue::String(regex.source.clone()));
+    set_member_value(js_interpreter, object_address, "flags", JsValue::String(regex.flags.clone()));
+    set_member_value(js_interpreter, object_address, "global", JsValue::Boolean(regex.global));
+
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value_mut(object_address) {
+        object.set_internal_slot(JsInternalSlot::RegExp(regex));
+    }
+}
+
+
+fn regex_from_arguments(js_interpreter: &JsInterpreter, arguments: &Vec<JsValue>) -> Result<JsRegex, String> {
+    //the arguments should already be dereferenced
+    let mut source = String::from("(?:)"); //this is the pattern of an empty regex, which matches everything
+    let mut flags = String::new();
+
+    //the pattern can also be another regex, which is copied (with its flags, unless we get new ones)
+    let copied_regex = match arguments.first() {
+        Some(JsValue::Object(object)) => {
+            match object.internal_slot() {
+                Some(JsInternalSlot::RegExp(regex)) => Some(regex.clone()),
+                _ => None,
+            }
+        },
+        _ => None,
+    };
+    if copied_regex.is_some() {
+        source = copied_regex.as_ref().unwrap().source.clone();
+        flags = copied_regex.as_ref().unwrap().flags.clone();
+    } else if arguments.first().is_some() && !matches!(arguments[0], JsValue::Undefined) {
+        source = arguments[0].clone().to_primitive(js_interpreter).to_js_string();
+    }
+    match arguments.get(1) {
+        Some(JsValue::Undefined) | None => {},
+        Some(new_flags) => { flags = new_flags.clone().to_primitive(js_interpreter).to_js_string(); },
+    }
+
+    return JsRegex::new(&source, &flags);
+}
+
+
+fn regexp_from_value(js_interpreter: &JsInterpreter, value: &JsValue) -> Option<(Option<JsAddress>, Rc<JsRegex>)> {
+    //we also return the address of the regex object (when it is stored), so we can update its lastIndex
+    let address = match value {
+        JsValue::Address(address) => Some(*address),
+        _ => None,
+    };
+    if let JsValue::Object(object) = value.clone().deref(js_interpreter) {
+        if let Some(JsInternalSlot::RegExp(regex)) = object.internal_slot() {
+            return Some((address, regex.clone()));
+        }
+    }
+    return None;
+}
+
+
+pub fn call_regexp_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    let string_argument = |js_interpreter: &JsInterpreter, idx: usize| -> String {
+        return arguments.get(idx).cloned().unwrap_or(JsValue::Undefined).deref(js_interpreter).to_primitive(js_interpreter).to_js_string();
+    };
+
+    match builtin {
+        JsBuiltinFunction::RegExpConstructor => {
+            //calling RegExp without new makes a regex as well
+            let prototype = js_interpreter.regexp_prototype_address;
+            let object_address = js_interpreter.add_new_value(JsValue::Object(JsObject::new_with_prototype(Some(prototype))));
+            return construct_builtin_object(js_interpreter, builtin, object_address, arguments);
+        },
+        JsBuiltinFunction::RegExpTest | JsBuiltinFunction::RegExpExec => {
+            let regexp = regexp_from_value(js_interpreter, &this_value);
+            if regexp.is_none() {
+                js_interpreter.throw_error(JsError::TypeError, "this is not a RegExp object");
+                return JsValue::Undefined;
+            }
+            let (regexp_address, regex) = regexp.unwrap();
+            let input = string_argument(js_interpreter, 0);
+            let chars = input.chars().collect();
+
+            let captures = regexp_exec(js_interpreter, regexp_address, &regex, &chars);
+            if matches!(builtin, JsBuiltinFunction::RegExpTest) {
+                return JsValue::Boolean(captures.is_some());
+            }
+            if captures.is_none() {
+                return JsValue::Null;
+            }
+            return make_match_object(js_interpreter, &chars, &captures.unwrap(), &input, &regex.group_names);
+        },
+        JsBuiltinFunction::StringMatch | JsBuiltinFunction::StringReplace => {
+            let string = this_value.deref(js_interpreter).to_primitive(js_interpreter).to_js_string();
+            let chars: Vec<char> = string.chars().collect();
+            let pattern = arguments.first().cloned().unwrap_or(JsValue::Undefined);
+
+            //for match, a pattern that is not a regex is made into one, but replace looks for it as a plain string
+            let mut regexp = regexp_from_value(js_interpreter, &pattern);
+            if regexp.is_none() && matches!(builtin, JsBuiltinFunction::StringMatch) {
+                match regex_from_arguments(js_interpreter, &vec![pattern.clone().deref(js_interpreter)]) {
+                    Ok(regex) => { regexp = Some((None, Rc::from(regex))); },
+                    Err(message) => {
+                        js_interpreter.throw_error(JsError::SyntaxError, format!("invalid regular expression: {}", message).as_str());
+                        return JsValue::Undefined;
+                    }
+                }
+            }
+
+            let all_captures = match &regexp {
+                Some((regexp_address, regex)) if regex.global => {
+                    if regexp_address.is_some() {
+                        set_member_value(js_interpreter, regexp_address.unwrap(), "lastIndex", JsValue::Number(0.0));
+                    }
+                    find_all_matches(regex, &chars)
+                },
+                Some((regexp_address, regex)) => regexp_exec(js_interpreter, *regexp_address, regex, &chars).into_iter().collect(),
+                None => {
+                    let search = string_argument(js_interpreter, 0);
+                    match string.find(&search) {
+                        Some(byte_idx) => {
+                            let start = string[..byte_idx].chars().count();
+                            vec![vec![Some((start, start + search.chars().count()))]]
+                        },
+                        None => Vec::new(),
+                    }
+                },
+            };
+
+            if matches!(builtin, JsBuiltinFunction::StringMatch) {
+                if all_captures.is_empty() {
+                    return JsValue::Null;
+                }
+                let regex = regexp.unwrap().1;
+                if !regex.global {
+                    return make_match_object(js_interpreter, &chars, &all_captures[0], &string, &regex.group_names);
+                }
+
+                //a global match gives all the matched strings, without the groups
+                let mut matched_strings = JsObject::new();
+                for (idx, captures) in all_captures.iter().enumerate() {
+                    let (start, end) = captures[0].unwrap();
+                    let match_address = js_interpreter.add_new_value(JsValue::String(chars[start..end].iter().collect()));
+                    matched_strings.set_member(idx.to_string(), match_address);
+                }
+                let length_address = js_interpreter.add_new_value(JsValue::Number(all_captures.len() as f64));
+                matched_strings.set_member(String::from("length"), length_address);
+                return JsValue::Object(matched_strings); //TODO: this should be an array
+            }
+
+            let replacement = arguments.get(1).cloned().unwrap_or(JsValue::Undefined).deref(js_interpreter);
+            let mut result = String::new();
+            let mut previous_end = 0;
+            for captures in all_captures {
+                let (start, end) = captures[0].unwrap();
+                result.extend(&chars[previous_end..start]);
+
+                match &replacement {
+                    JsValue::Function(function) => {
+                        //the function gets the match, the groups, where the match starts and the whole string
+                        let mut replacer_arguments: Vec<JsValue> = captures.iter().map(|capture| {
+                            return capture.map(|(start, end)| JsValue::String(chars[start..end].iter().collect())).unwrap_or(JsValue::Undefined);
+                        }).collect();
+                        replacer_arguments.push(JsValue::Number(start as f64));
+                        replacer_arguments.push(JsValue::String(string.clone()));
+
+                        let replaced = call_function(js_interpreter, function.clone(), JsValue::Undefined, replacer_arguments);
+                        if js_interpreter.has_pending_exception() {
+                            return JsValue::Undefined;
+                        }
+                        result.push_str(&replaced.deref(js_interpreter).to_primitive(js_interpreter).to_js_string());
+                    },
+                    _ => {
+                        result.push_str(&expand_replacement(&replacement.clone().to_primitive(js_interpreter).to_js_string(), &chars, &captures));
+                    },
+                }
+                previous_end = end;
+            }
+            result.extend(&chars[previous_end..]);
+            return JsValue::String(result);
+        },
+        _ => { panic!("not a regexp function"); },
+    }
+}
+
+
+fn regexp_exec(js_interpreter: &mut JsInterpreter, regexp_address: Option<JsAddress>, regex: &JsRegex, chars: &Vec<char>) -> Option<JsRegexCaptures> {
+    //global and sticky regexes continue from where their last match ended, and remember where the next search should start in lastIndex
+    let uses_last_index = (regex.global || regex.sticky) && regexp_address.is_some();
+
+    let mut start = 0;
+    if uses_last_index {
+        let last_index = get_member_value(js_interpreter, regexp_address.unwrap(), "lastIndex").to_primitive(js_interpreter).to_number();
+        start = if last_index.is_finite() && last_index > 0.0 { last_index as usize } else { 0 };
+    }
+
+    let captures = if start <= chars.len() { regex.find(chars, start) } else { None };
+
+    if uses_last_index {
+        let next_last_index = captures.as_ref().map(|captures| captures[0].unwrap().1).unwrap_or(0);
+        set_member_value(js_interpreter, regexp_address.unwrap(), "lastIndex", JsValue::Number(next_last_index as f64));
+    }
+    return captures;
+}
+
+
+fn find_all_matches(regex: &JsRegex, chars: &Vec<char>) -> Vec<JsRegexCaptures> {
+    let mut all_captures = Vec::new();
+    let mut start = 0;
+
+    while start <= chars.len() {
+        let captures = regex.find(chars, start);
+        if captures.is_none() {
+            break;
+        }
+        let captures = captures.unwrap();
+        let (match_start, match_end) = captures[0].unwrap();
+
+        //after an empty match we move one further, otherwise we would find the same empty match again
+        start = if match_end > match_start { match_end } else { match_end + 1 };
+        all_captures.push(captures);
+    }
+    return all_captures;
+}
+
+
+fn make_match_object(js_interpreter: &mut JsInterpreter, chars: &Vec<char>, captures: &JsRegexCaptures, input: &String,
+                     group_names: &Vec<(String, usize)>) -> JsValue {
+    //the result has the whole match, and then the groups, as numbered members, and the index where the match starts
+    //TODO: this should be an array
+    let mut match_object = JsObject::new();
+    let capture_value = |idx: usize| captures[idx].map(|(start, end)| JsValue::String(chars[start..end].iter().collect())).unwrap_or(JsValue::Undefined);
+
+    for idx in 0..captures.len() {
+        let value_address = js_interpreter.add_new_value(capture_value(idx));
+        match_object.set_member(idx.to_string(), value_address);
+    }
+
+    //the named groups are also on the groups object by their name, which is undefined when the regex has no named groups
+    let groups = if group_names.is_empty() {
+        JsValue::Undefined
+    } else {
+        let mut groups_object = JsObject::new();
+        for (name, idx) in group_names {
+            let value_address = js_interpreter.add_new_value(capture_value(*idx));
+            groups_object.set_member(name.clone(), value_address);
+        }
+        JsValue::Object(groups_object)
+    };
+
+    let members = [
+        ("length", JsValue::Number(captures.len() as f64)),
+        ("index", JsValue::Number(captures[0].unwrap().0 as f64)),
+        ("input", JsValue::String(input.clone())),
+        ("groups", groups),
+    ];
+    for (name, value) in members {
+        let value_address = js_interpreter.add_new_value(value);
+        match_object.set_member(String::from(name), value_address);
+    }
+    return JsValue::Object(match_object);
+}
+
+
+fn expand_replacement(template: &String, chars: &Vec<char>, captures: &JsRegexCaptures) -> String {
+    //replacement strings can refer to the match with $& (or $` and $' for the parts before and after it), and to the groups with $1 etc.
+    let template: Vec<char> = template.chars().collect();
+    let (match_start, match_end) = captures[0].unwrap();
+    let group_count = captures.len() - 1;
+    let capture_text = |group_idx: usize| -> String {
+        return captures[group_idx].map(|(start, end)| chars[start..end].iter().collect()).unwrap_or(String::new());
+    };
+
+    let mut result = String::new();
+    let mut idx = 0;
+    while idx < template.len() {
+        if template[idx] != '$' || idx + 1 == template.len() {
+            result.push(template[idx]);
+            idx += 1;
+            continue;
+        }
+
+        match template[idx + 1] {
+            '$' => { result.push('$'); },
+            '&' => { result.extend(&chars[match_start..match_end]); },
+            '`' => { result.extend(&chars[..match_start]); },
+            '\'' => { result.extend(&chars[match_end..]); },
+            digit if digit.is_ascii_digit() => {
+                //we prefer two digit group numbers (like $12) when there are that many groups
+                let first_digit = digit.to_digit(10).unwrap() as usize;
+                let second_digit = template.get(idx + 2).and_then(|char| char.to_digit(10)).map(|digit| digit as usize);
+                if second_digit.is_some() && first_digit * 10 + second_digit.unwrap() >= 1 && first_digit * 10 + second_digit.unwrap() <= group_count {
+                    result.push_str(&capture_text(first_digit * 10 + second_digit.unwrap()));
+                    idx += 3;
+                    continue;
+                }
+                if first_digit >= 1 && first_digit <= group_count {
+                    result.push_str(&capture_text(first_digit));
+                } else {
+                    result.push('$');
+                    result.push(digit);
+                }
+            },
+            other => {
+                result.push('$');
+                result.push(other);
+            },
+        }
+        idx += 2;
+    }
+    return result;
+}
+
+
+pub fn call_timer_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, arguments: Vec<JsValue>) -> JsValue {
+    let mut arguments = arguments.into_iter();
+    let first_argument = arguments.next().unwrap_or(JsValue::Undefined).deref(js_interpreter);
+
+    match builtin {
+        JsBuiltinFunction::SetTimeout | JsBuiltinFunction::SetInterval => {
+            let callback = match first_argument {
+                JsValue::Function(function) => function,
+                _ => {
+                    //TODO: browsers also accept a string here, which they run as code
+                    js_interpreter.throw_error(JsError::TypeError, "the callback of a timer should be a function");
+                    return JsValue::Undefined;
+                }
+            };
+
+            //a missing, negative or invalid delay means the callback runs as soon as possible, the other arguments are passed to the callback
+            let delay = arguments.next().unwrap_or(JsValue::Undefined).deref(js_interpreter).to_primitive(js_interpreter).to_number();
+            let delay = if delay.is_finite() && delay > 0.0 { Duration::from_millis(delay as u64) } else { Duration::ZERO };
+
+            let repeats = matches!(builtin, JsBuiltinFunction::SetInterval);
+            let timer_id = js_interpreter.add_timer(callback, arguments.collect(), delay, repeats);
+            return JsValue::Number(timer_id as f64);
+        },
+        JsBuiltinFunction::ClearTimer => {
+            let timer_id = first_argument.to_primitive(js_interpreter).to_number();
+            if timer_id.is_finite() && timer_id > 0.0 {
+                js_interpreter.remove_timer(timer_id as u32);
+            }
+            return JsValue::Undefined;
+        },
+        _ => { panic!("not a timer function"); },
+    }
+}
@@ -6,6 +6,11 @@ pub fn log_js_error(error: &str) {
 }
 
 
+pub fn log_warning(warning: &str) {
+    println!("[JS console] [WARNING] {}", warning);
+}
+
+
 pub fn print(text: &str) {
     println!("[JS console] {}", text);
 }
@@ -0,0 +1,600 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use crate::dom::{Document, DocumentReadyState, DomNodeId};
+use crate::html_lexer;
+use crate::html_parser;
+use crate::layout::Rect;
+use crate::style::{
+    check_selector_for_match,
+    media_query::MediaEnvironment,
+    parse_inline_style,
+    resolve_full_styles_for_layout_node,
+    serialize_inline_style,
+    Selector,
+};
+use crate::ui::{CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y};
+
+use super::js_execution_context::{
+    JsBuiltinFunction,
+    JsError,
+    JsInternalSlot,
+    JsObject,
+    JsValue,
+};
+use super::js_interpreter::{JsDialog, JsInterpreter};
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum JsDomObjectKind {
+    Element,
+    Style,
+    ClassList,
+}
+
+
+fn dom_object_for_node(js_interpreter: &mut JsInterpreter, node_id: DomNodeId, kind: JsDomObjectKind) -> JsValue {
+    //every DOM node gets one object of each kind, so looking up the same element twice gives the same object (and members set on it stay there)
+    let key = (node_id, kind);
+    if let Some(address) = js_interpreter.dom_objects.get(&key) {
+        return JsValue::Address(*address);
+    }
+
+    let (prototype_address, internal_slot) = match kind {
+        JsDomObjectKind::Element => (js_interpreter.element_prototype_address, JsInternalSlot::DomElement(node_id)),
+        JsDomObjectKind::Style => (js_interpreter.style_prototype_address, JsInternalSlot::DomStyle(node_id)),
+        JsDomObjectKind::ClassList => (js_interpreter.class_list_prototype_address, JsInternalSlot::DomClassList(node_id)),
+    };
+    let mut dom_object = JsObject::new_with_prototype(Some(prototype_address));
+    dom_object.set_internal_slot(internal_slot);
+    let address = js_interpreter.add_new_value(JsValue::Object(dom_object));
+    js_interpreter.dom_objects.insert(key, address);
+    return JsValue::Address(address);
+}
+
+
+pub fn element_to_js_value(js_interpreter: &mut JsInterpreter, node_id: DomNodeId) -> JsValue {
+    return dom_object_for_node(js_interpreter, node_id, JsDomObjectKind::Element);
+}
+
+
+fn js_value_to_node(js_interpreter: &JsInterpreter, value: &JsValue, kind: JsDomObjectKind) -> Option<DomNodeId> {
+    //the node ids are only looked up in the document of the interpreter, so without one there are no nodes
+    if js_interpreter.document.is_none() {
+        return None;
+    }
+
+    let object = match value {
+        JsValue::Address(address) => js_interpreter.get_value(*address),
+        _ => Some(value),
+    };
+
+    if let Some(JsValue::Object(object)) = object {
+        match (object.internal_slot(), kind) {
+            (Some(JsInternalSlot::DomElement(node_id)), JsDomObjectKind::Element) |
+            (Some(JsInternalSlot::DomStyle(node_id)), JsDomObjectKind::Style) |
+            (Some(JsInternalSlot::DomClassList(node_id)), JsDomObjectKind::ClassList) => { return Some(*node_id); },
+            _ => {},
+        }
+    }
+    return None;
+}
+
+
+fn js_value_to_element(js_interpreter: &JsInterpreter, value: &JsValue) -> Option<DomNodeId> {
+    return js_value_to_node(js_interpreter, value, JsDomObjectKind::Element);
+}
+
+
+fn css_property_name(property_key: &str) -> String {
+    //scripts use camel case for css properties (as in style.backgroundColor), but also accept the css name itself
+    if property_key == "cssFloat" {
+        return String::from("float");
+    }
+
+    let mut css_name = String::new();
+    for c in property_key.chars() {
+        if c.is_ascii_uppercase() {
+            css_name.push('-');
+        }
+        css_name.push(c.to_ascii_lowercase());
+    }
+    return css_name;
+}
+
+
+fn camel_case_property_name(css_name: &str) -> String {
+    //this is the other way around from css_property_name, custom properties (starting with --) are only available by their css name
+    if css_name == "float" {
+        return String::from("cssFloat");
+    }
+    if css_name.starts_with("--") {
+        return css_name.to_owned();
+    }
+
+    let mut property_key = String::new();
+    let mut uppercase_next = false;
+    for c in css_name.chars() {
+        if c == '-' {
+            uppercase_next = !property_key.is_empty();
+            continue;
+        }
+        property_key.push(if uppercase_next { c.to_ascii_uppercase() } else { c });
+        uppercase_next = false;
+    }
+    return property_key;
+}
+
+
+pub fn get_style_property(document: &Document, node_id: DomNodeId, property_key: &str) -> JsValue {
+    //properties that are not in the inline style are the empty string, also when a style sheet sets them
+    let property = css_property_name(property_key);
+    let declarations = parse_inline_style(&document.nodes[node_id].get_attribute_value("style").unwrap_or_default());
+    let value = declarations.into_iter().rev().find(|(declared_property, _)| *declared_property == property).map(|(_, value)| value);
+    return JsValue::String(value.unwrap_or_default());
+}
+
+
+pub fn set_style_property(document: &mut Document, node_id: DomNodeId, property_key: &str, value: &str) {
+    //setting a property to the empty string removes it from the inline style
+    //TODO: invalid values should be ignored, but we don't validate css values here yet
+    let property = css_property_name(property_key);
+    let mut declarations = parse_inline_style(&document.nodes[node_id].get_attribute_value("style").unwrap_or_default());
+
+    let value = value.trim();
+    let existing_index = declarations.iter().position(|(declared_property, _)| *declared_property == property);
+    declarations.retain(|(declared_property, _)| *declared_property != property);
+    if !value.is_empty() {
+        let index = existing_index.unwrap_or(declarations.len());
+        declarations.insert(index, (property, value.to_owned()));
+    }
+
+    //changing the style attribute marks the node dirty, so its styles are resolved again when its layout is rebuilt
+    document.nodes[node_id].set_attribute("style", &serialize_inline_style(&declarations));
+}
+
+
+fn get_class_names(document: &Document, node_id: DomNodeId) -> Vec<String> {
+    let class_attribute = document.nodes[node_id].get_attribute_value("class").unwrap_or_default();
+    let mut class_names: Vec<String> = Vec::new();
+    for class_name in class_attribute.split_ascii_whitespace() {
+        if !class_names.iter().any(|existing| existing == class_name) {
+            class_names.push(class_name.to_owned());
+        }
+    }
+    return class_names;
+}
+
+
+fn rect_to_js_value(js_interpreter: &mut JsInterpreter, rect: &Rect) -> JsValue {
+    //TODO: this should be a DOMRect, for now it is a plain object with the same properties
+    let properties = [
+        ("x", rect.x), ("y", rect.y), ("width", rect.width), ("height", rect.height),
+        ("top", rect.y), ("right", rect.x + rect.width), ("bottom", rect.y + rect.height), ("left", rect.x),
+    ];
+
+    let mut rect_object = JsObject::new();
+    for (name, value) in properties {
+        let value_address = js_interpreter.add_new_value(JsValue::Number(value as f64));
+        rect_object.set_member(String::from(name), value_address);
+    }
+    return JsValue::Address(js_interpreter.add_new_value(JsValue::Object(rect_object)));
+}
+
+
+fn collect_matching_descendants(document: &Document, node_id: DomNodeId, selector: &Selector, only_first: bool, matching_nodes: &mut Vec<DomNodeId>) {
+    //the nodes are collected in tree order, and the node itself is not included
+    let node = &document.nodes[node_id];
+    if node.children.is_none() {
+        return;
+    }
+
+    for child_id in node.children.as_ref().unwrap() {
+        if only_first && !matching_nodes.is_empty() {
+            return;
+        }
+        //scripts can't find out which pages were visited, so for them all links are unvisited, as in other browsers
+        if check_selector_for_match(selector, &document.nodes[*child_id], &HashSet::new()) {
+            matching_nodes.push(*child_id);
+        }
+        collect_matching_descendants(document, *child_id, selector, only_first, matching_nodes);
+    }
+}
+
+
+pub fn call_dom_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    let arguments: Vec<JsValue> = arguments.into_iter().map(|argument| argument.deref(js_interpreter)).collect();
+    let first_argument = arguments.first().cloned().unwrap_or(JsValue::Undefined).to_js_string();
+
+    match builtin {
+        JsBuiltinFunction::DocumentGetElementById => {
+            if js_interpreter.document.is_none() {
+                return JsValue::Null;
+            }
+
+            //when multiple elements have the same id, the first one in the document is used
+            let found_node_id = js_interpreter.document.as_ref().unwrap().borrow().find_node_with_id(&first_argument);
+            if found_node_id.is_none() {
+                return JsValue::Null;
+            }
+            return element_to_js_value(js_interpreter, found_node_id.unwrap());
+        },
+        JsBuiltinFunction::DocumentQuerySelector | JsBuiltinFunction::DocumentQuerySelectorAll |
+        JsBuiltinFunction::ElementQuerySelector | JsBuiltinFunction::ElementQuerySelectorAll => {
+            let root_node = if matches!(builtin, JsBuiltinFunction::DocumentQuerySelector | JsBuiltinFunction::DocumentQuerySelectorAll) {
+                js_interpreter.document.as_ref().map(|document| document.borrow().document_node)
+            } else {
+                js_value_to_element(js_interpreter, &this_value)
+            };
+            let only_first = matches!(builtin, JsBuiltinFunction::DocumentQuerySelector | JsBuiltinFunction::ElementQuerySelector);
+
+            let mut matching_nodes = Vec::new();
+            if root_node.is_some() {
+                let selector = Selector { nodes: Some(vec![first_argument]) };
+                let document = js_interpreter.document.as_ref().unwrap().borrow();
+                collect_matching_descendants(&document, root_node.unwrap(), &selector, only_first, &mut matching_nodes);
+            }
+
+            if only_first {
+                if matching_nodes.is_empty() {
+                    return JsValue::Null;
+                }
+                return element_to_js_value(js_interpreter, matching_nodes[0]);
+            }
+
+            //TODO: this should be a NodeList, not an array
+            let elements = matching_nodes.into_iter().map(|node_id| element_to_js_value(js_interpreter, node_id)).collect();
+            return js_interpreter.make_array(elements);
+        },
+        JsBuiltinFunction::ElementGetTagName | JsBuiltinFunction::ElementGetId => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let document = js_interpreter.document.as_ref().unwrap().borrow();
+            let node = &document.nodes[node_id.unwrap()];
+
+            if matches!(builtin, JsBuiltinFunction::ElementGetTagName) {
+                //the tag name of html elements is in uppercase
+                return JsValue::String(node.name.as_ref().map(|name| name.to_uppercase()).unwrap_or_default());
+            }
+            return JsValue::String(node.get_attribute_value("id").unwrap_or_default());
+        },
+        JsBuiltinFunction::DocumentGetReadyState => {
+            //scripts run while the document is parsed, so without a document we are still loading
+            let ready_state = js_interpreter.document.as_ref().map(|document| document.borrow().ready_state).unwrap_or(DocumentReadyState::Loading);
+            return JsValue::String(String::from(ready_state.name()));
+        },
+        JsBuiltinFunction::DocumentCreateElement | JsBuiltinFunction::DocumentCreateTextNode => {
+            let document = js_interpreter.document.clone();
+            if document.is_none() {
+                return JsValue::Undefined;
+            }
+
+            let node_id = if matches!(builtin, JsBuiltinFunction::DocumentCreateElement) {
+                document.unwrap().borrow_mut().create_node(Some(&first_argument), None)
+            } else {
+                document.unwrap().borrow_mut().create_node(None, Some(&first_argument))
+            };
+            return element_to_js_value(js_interpreter, node_id);
+        },
+        JsBuiltinFunction::ElementAppendChild | JsBuiltinFunction::ElementInsertBefore | JsBuiltinFunction::ElementRemoveChild => {
+            let document = js_interpreter.document.clone();
+            let parent = js_value_to_element(js_interpreter, &this_value);
+            let child = arguments.first().and_then(|argument| js_value_to_element(js_interpreter, argument));
+            if document.is_none() || parent.is_none() || child.is_none() {
+                js_interpreter.throw_error(JsError::TypeError, "the argument is not a node");
+                return JsValue::Undefined;
+            }
+            let (document, parent, child) = (document.unwrap(), parent.unwrap(), child.unwrap());
+
+            let result = match builtin {
+                JsBuiltinFunction::ElementAppendChild => document.borrow_mut().insert_child(parent, child, None),
+                JsBuiltinFunction::ElementInsertBefore => {
+                    //without a node to insert before (or with null), the node is added at the end
+                    let reference_child = arguments.get(1).and_then(|argument| js_value_to_element(js_interpreter, argument));
+                    document.borrow_mut().insert_child(parent, child, reference_child)
+                },
+                _ => document.borrow_mut().remove_child(parent, child),
+            };
+
+            //TODO: these should be DOMExceptions (like a HierarchyRequestError), but we don't have those yet
+            if result.is_err() {
+                js_interpreter.throw_error(JsError::TypeError, &result.unwrap_err());
+                return JsValue::Undefined;
+            }
+            return element_to_js_value(js_interpreter, child);
+        },
+        JsBuiltinFunction::ElementGetInnerHtml | JsBuiltinFunction::ElementGetTextContent => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let document = js_interpreter.document.as_ref().unwrap().borrow();
+
+            if matches!(builtin, JsBuiltinFunction::ElementGetInnerHtml) {
+                return JsValue::String(document.get_inner_html(node_id.unwrap()));
+            }
+            return JsValue::String(document.get_text_content(node_id.unwrap()));
+        },
+        JsBuiltinFunction::ElementSetInnerHtml | JsBuiltinFunction::ElementSetTextContent => {
+            let document = js_interpreter.document.clone();
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if document.is_none() || node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let (document, node_id) = (document.unwrap(), node_id.unwrap());
+            let mut document = document.borrow_mut();
+
+            if document.nodes[node_id].text.is_some() {
+                //for text nodes, both change the text itself
+                document.nodes[node_id].text.as_mut().unwrap().text_content = first_argument;
+                let parent_id = document.get_parent(node_id);
+                if parent_id.is_some() {
+                    document.nodes[parent_id.unwrap()].dirty = true;
+                }
+                return JsValue::Undefined;
+            }
+
+            let new_children = if matches!(builtin, JsBuiltinFunction::ElementSetInnerHtml) {
+                //scripts in the new html are not run, like in other browsers
+                let new_node_ids = html_parser::parse_fragment(html_lexer::lex_html(&first_argument), &mut document);
+                document.add_parsed_nodes(&new_node_ids);
+                new_node_ids
+            } else if first_argument.is_empty() {
+                Vec::new()
+            } else {
+                vec![document.create_node(None, Some(&first_argument))]
+            };
+            document.replace_children(node_id, new_children);
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::ElementGetAttribute | JsBuiltinFunction::ElementSetAttribute |
+        JsBuiltinFunction::ElementRemoveAttribute | JsBuiltinFunction::ElementHasAttribute => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let document = js_interpreter.document.clone().unwrap();
+            let mut document = document.borrow_mut();
+            let node = &mut document.nodes[node_id.unwrap()];
+
+            //attribute names of html elements are case insensitive, and we store them in lowercase
+            let attribute_name = first_argument.to_ascii_lowercase();
+            match builtin {
+                JsBuiltinFunction::ElementGetAttribute => {
+                    let attribute_value = node.get_attribute_value(&attribute_name);
+                    return attribute_value.map(JsValue::String).unwrap_or(JsValue::Null);
+                },
+                JsBuiltinFunction::ElementSetAttribute => {
+                    let attribute_value = arguments.get(1).cloned().unwrap_or(JsValue::Undefined).to_js_string();
+                    node.set_attribute(&attribute_name, &attribute_value);
+                },
+                JsBuiltinFunction::ElementRemoveAttribute => {
+                    node.remove_attribute(&attribute_name);
+                },
+                _ => {
+                    return JsValue::Boolean(node.get_attribute_value(&attribute_name).is_some());
+                },
+            }
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::ElementSetId | JsBuiltinFunction::ElementSetClassName => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_some() {
+                let attribute_name = if matches!(builtin, JsBuiltinFunction::ElementSetId) { "id" } else { "class" };
+                js_interpreter.document.as_ref().unwrap().borrow_mut().nodes[node_id.unwrap()].set_attribute(attribute_name, &first_argument);
+            }
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::ElementGetClassName => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            return JsValue::String(js_interpreter.document.as_ref().unwrap().borrow().nodes[node_id.unwrap()].get_attribute_value("class").unwrap_or_default());
+        },
+        JsBuiltinFunction::ElementGetClassList | JsBuiltinFunction::ElementGetStyle => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let kind = if matches!(builtin, JsBuiltinFunction::ElementGetClassList) { JsDomObjectKind::ClassList } else { JsDomObjectKind::Style };
+            return dom_object_for_node(js_interpreter, node_id.unwrap(), kind);
+        },
+        JsBuiltinFunction::ClassListAdd | JsBuiltinFunction::ClassListRemove |
+        JsBuiltinFunction::ClassListToggle | JsBuiltinFunction::ClassListContains => {
+            let node_id = js_value_to_node(js_interpreter, &this_value, JsDomObjectKind::ClassList);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let (document, node_id) = (js_interpreter.document.clone().unwrap(), node_id.unwrap());
+            let mut class_names = get_class_names(&document.borrow(), node_id);
+
+            //TODO: class names with whitespace in them should throw an InvalidCharacterError, but we don't have DOMExceptions yet
+            let result = match builtin {
+                JsBuiltinFunction::ClassListContains => {
+                    return JsValue::Boolean(class_names.contains(&first_argument));
+                },
+                JsBuiltinFunction::ClassListAdd => {
+                    for argument in &arguments {
+                        let class_name = argument.to_js_string();
+                        if !class_names.contains(&class_name) {
+                            class_names.push(class_name);
+                        }
+                    }
+                    JsValue::Undefined
+                },
+                JsBuiltinFunction::ClassListRemove => {
+                    let removed_class_names: Vec<String> = arguments.iter().map(|argument| argument.to_js_string()).collect();
+                    class_names.retain(|class_name| !removed_class_names.contains(class_name));
+                    JsValue::Undefined
+                },
+                _ => {
+                    //with a second argument, toggle only adds (when it is truthy) or only removes the class
+                    let has_class = class_names.contains(&first_argument);
+                    let should_have_class = match arguments.get(1) {
+                        Some(force) => force.clone().is_truthy(js_interpreter),
+                        None => !has_class,
+                    };
+                    if should_have_class && !has_class {
+                        class_names.push(first_argument);
+                    } else if !should_have_class {
+                        class_names.retain(|class_name| *class_name != first_argument);
+                    }
+                    JsValue::Boolean(should_have_class)
+                },
+            };
+
+            document.borrow_mut().nodes[node_id].set_attribute("class", &class_names.join(" "));
+            return result;
+        },
+        JsBuiltinFunction::StyleGetPropertyValue | JsBuiltinFunction::StyleSetProperty | JsBuiltinFunction::StyleRemoveProperty => {
+            let node_id = js_value_to_node(js_interpreter, &this_value, JsDomObjectKind::Style);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let (document, node_id) = (js_interpreter.document.clone().unwrap(), node_id.unwrap());
+            let mut document = document.borrow_mut();
+
+            //these take the css name of the property (like "background-color"), unlike the properties on the style object
+            let property = first_argument.to_ascii_lowercase();
+            let old_value = get_style_property(&document, node_id, &property);
+            match builtin {
+                JsBuiltinFunction::StyleGetPropertyValue => { return old_value; },
+                JsBuiltinFunction::StyleSetProperty => {
+                    //TODO: the third argument can be "important", but we don't support !important yet
+                    let value = arguments.get(1).cloned().unwrap_or(JsValue::Undefined).to_js_string();
+                    set_style_property(&mut document, node_id, &property, &value);
+                    return JsValue::Undefined;
+                },
+                _ => {
+                    set_style_property(&mut document, node_id, &property, "");
+                    return old_value;
+                },
+            }
+        },
+        JsBuiltinFunction::StyleGetCssText | JsBuiltinFunction::StyleSetCssText => {
+            let node_id = js_value_to_node(js_interpreter, &this_value, JsDomObjectKind::Style);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            let document = js_interpreter.document.clone().unwrap();
+            let node = &mut document.borrow_mut().nodes[node_id.unwrap()];
+
+            if matches!(builtin, JsBuiltinFunction::StyleGetCssText) {
+                let declarations = parse_inline_style(&node.get_attribute_value("style").unwrap_or_default());
+                return JsValue::String(serialize_inline_style(&declarations));
+            }
+            node.set_attribute("style", &serialize_inline_style(&parse_inline_style(&first_argument)));
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::ElementGetBoundingClientRect => {
+            let node_id = js_value_to_element(js_interpreter, &this_value);
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+            js_interpreter.flush_layout();
+
+            let mut node_ids = HashSet::new();
+            js_interpreter.document.as_ref().unwrap().borrow().collect_node_ids(node_id.unwrap(), &mut node_ids);
+
+            //elements that are not laid out (because they are not in the document, or the page is not laid out yet) get an empty rect
+            let mut client_rect = Rect::empty();
+            if js_interpreter.layout_context.is_some() {
+                let layout_context = js_interpreter.layout_context.as_ref().unwrap();
+                let full_layout = layout_context.full_layout.borrow();
+                let bounding_box = full_layout.find_bounding_box_of_dom_nodes(full_layout.root_node, &node_ids);
+
+                if bounding_box.is_some() {
+                    //the layout is in page coordinates, but these are relative to the part of the page that is shown
+                    let bounding_box = bounding_box.unwrap();
+                    client_rect = Rect { x: bounding_box.x - CONTENT_TOP_LEFT_X, y: bounding_box.y - CONTENT_TOP_LEFT_Y - layout_context.current_scroll_y,
+                                         width: bounding_box.width, height: bounding_box.height };
+                }
+            }
+            return rect_to_js_value(js_interpreter, &client_rect);
+        },
+        JsBuiltinFunction::GetComputedStyle => {
+            //TODO: this should throw a TypeError when the argument is not an element
+            let node_id = js_value_to_element(js_interpreter, &arguments.first().cloned().unwrap_or(JsValue::Undefined));
+            if node_id.is_none() {
+                return JsValue::Undefined;
+            }
+
+            //TODO: these are the values from the style sheets, not the computed values (so for example em sizes are not converted to pixels), and
+            //      properties that are not set on the element (or inherited from its parents) are missing
+            let resolved_styles = {
+                let document = js_interpreter.document.as_ref().unwrap().borrow();
+                resolve_full_styles_for_layout_node(node_id.unwrap(), &document.nodes, &document.style_context)
+            };
+            let mut resolved_styles: Vec<(String, String)> = resolved_styles.into_iter().collect();
+            resolved_styles.sort();
+
+            //the properties are on the object with their css name and in camel case, like on element.style
+            let mut computed_style = JsObject::new_with_prototype(Some(js_interpreter.computed_style_prototype_address));
+            for (property, value) in resolved_styles {
+                let value_address = js_interpreter.add_new_value(JsValue::String(value));
+                let property_key = camel_case_property_name(&property);
+                if property_key != property {
+                    computed_style.set_member(property_key, value_address);
+                }
+                computed_style.set_member(property, value_address);
+            }
+            return JsValue::Address(js_interpreter.add_new_value(JsValue::Object(computed_style)));
+        },
+        JsBuiltinFunction::ComputedStyleGetPropertyValue => {
+            let computed_style = match &this_value {
+                JsValue::Address(address) => js_interpreter.get_value(*address),
+                _ => Some(&this_value),
+            };
+
+            if let Some(JsValue::Object(computed_style)) = computed_style {
+                let value_address = computed_style.get_member(&first_argument.to_ascii_lowercase());
+                if value_address.is_some() {
+                    return js_interpreter.get_value(*value_address.unwrap()).cloned().unwrap_or(JsValue::String(String::new()));
+                }
+            }
+            return JsValue::String(String::new());
+        },
+        JsBuiltinFunction::WindowGetInnerWidth | JsBuiltinFunction::WindowGetInnerHeight => {
+            //the window is as large as the environment for media queries, which is updated when the browser window is resized
+            let media_environment = js_interpreter.document.as_ref().map(|document| document.borrow().style_context.media_environment())
+                                                                    .unwrap_or(MediaEnvironment::new_default());
+            let size = if matches!(builtin, JsBuiltinFunction::WindowGetInnerWidth) { media_environment.width } else { media_environment.height };
+            return JsValue::Number(size as f64);
+        },
+        JsBuiltinFunction::WindowGetDevicePixelRatio => {
+            //TODO: we always render one pixel per css pixel, also on high density displays
+            return JsValue::Number(1.0);
+        },
+        JsBuiltinFunction::ScreenGetWidth => {
+            return JsValue::Number(js_interpreter.screen_dimensions.width as f64);
+        },
+        JsBuiltinFunction::ScreenGetHeight => {
+            return JsValue::Number(js_interpreter.screen_dimensions.height as f64);
+        },
+        JsBuiltinFunction::WindowAlert | JsBuiltinFunction::WindowConfirm | JsBuiltinFunction::WindowPrompt => {
+            let message = if arguments.is_empty() { String::new() } else { first_argument };
+            let dialog = match builtin {
+                JsBuiltinFunction::WindowAlert => JsDialog::Alert(message),
+                JsBuiltinFunction::WindowConfirm => JsDialog::Confirm(message),
+                _ => JsDialog::Prompt(message, arguments.get(1).map(|default_text| default_text.to_js_string()).unwrap_or_default()),
+            };
+
+            //the script waits until the dialog is closed, without a way to show dialogs (like in tests) they are cancelled right away
+            let dialog_start_instant = Instant::now();
+            let result = js_interpreter.dialog_handler.as_ref().and_then(|dialog_handler| dialog_handler(&dialog));
+            js_interpreter.pause_time_limit(dialog_start_instant.elapsed());
+            return match dialog {
+                JsDialog::Alert(_) => JsValue::Undefined,
+                JsDialog::Confirm(_) => JsValue::Boolean(result.is_some()),
+                JsDialog::Prompt(..) => result.map(JsValue::String).unwrap_or(JsValue::Null),
+            };
+        },
+        _ => {
+            panic!("not a DOM function");
+        },
+    }
+}
@@ -0,0 +1,354 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::dom::DomNodeId;
+
+use super::js_ast::get_property;
+use super::js_dom::element_to_js_value;
+use super::js_execution_context::{
+    JsAddress,
+    JsBuiltinFunction,
+    JsFunction,
+    JsInternalSlot,
+    JsObject,
+    JsValue,
+};
+use super::js_interpreter::JsInterpreter;
+use super::js_lexer;
+use super::js_parser;
+
+
+//these are the values of eventPhase
+const PHASE_NONE: u8 = 0;
+const PHASE_CAPTURING: u8 = 1;
+const PHASE_AT_TARGET: u8 = 2;
+const PHASE_BUBBLING: u8 = 3;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct JsEventListener {
+    event_type: String,
+    callback: JsAddress, //the function is kept by its address, so removeEventListener can find it again
+    capture: bool,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct JsEventState {
+    event_type: String,
+    target: JsAddress,
+    bubbles: bool,
+    cancelable: bool,
+    current_target: Cell<Option<JsAddress>>,
+    phase: Cell<u8>,
+    default_prevented: Cell<bool>,
+    propagation_stopped: Cell<bool>,
+    immediate_propagation_stopped: Cell<bool>,
+}
+
+
+pub enum DomEventDetails {
+    None,
+    Mouse(f32, f32), //the position of the mouse in the window, for clientX and clientY
+    Key(String), //the name of the key, as in the key property (like "a" or "Enter")
+    Message(String), //the data of a message, for the data property
+    Close(u16, String, bool), //the code and reason a connection was closed with, and whether it was closed cleanly
+}
+
+
+pub struct DomEvent {
+    pub event_type: String,
+    pub bubbles: bool,
+    pub cancelable: bool,
+    pub details: DomEventDetails,
+}
+impl DomEvent {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> DomEvent {
+        return DomEvent { event_type: event_type.to_owned(), bubbles, cancelable, details: DomEventDetails::None };
+    }
+    pub fn mouse(event_type: &str, client_x: f32, client_y: f32) -> DomEvent {
+        return DomEvent { event_type: event_type.to_owned(), bubbles: true, cancelable: true, details: DomEventDetails::Mouse(client_x, client_y) };
+    }
+    pub fn key(event_type: &str, key: String) -> DomEvent {
+        return DomEvent { event_type: event_type.to_owned(), bubbles: true, cancelable: true, details: DomEventDetails::Key(key) };
+    }
+}
+
+
+pub fn dispatch_event(js_interpreter: &mut JsInterpreter, target: DomNodeId, event: DomEvent) -> bool {
+    //returns whether the default action of the event (like following a link) should happen, which is not the case when it was cancelled
+
+    //the event goes from the window down to the target and back up again, the path is kept from the target upwards
+    let mut path_nodes = vec![(target, false)];
+    if js_interpreter.document.is_some() {
+        let document = js_interpreter.document.clone().unwrap();
+        let document = document.borrow();
+        path_nodes[0].1 = document.nodes[target].is_document_node;
+        loop {
+            let parent_id = document.get_parent(path_nodes.last().unwrap().0);
+            if parent_id.is_none() {
+                break;
+            }
+            path_nodes.push((parent_id.unwrap(), document.nodes[parent_id.unwrap()].is_document_node));
+        }
+    }
+
+    let mut path = Vec::new();
+    for (node_id, is_document_node) in path_nodes {
+        if is_document_node {
+            //nodes that are not in the document (anymore) don't get the event on the document and window
+            path.push((js_interpreter.document_object_address, None));
+            path.push((js_interpreter.global_object_address, None));
+            break;
+        }
+        let element_address = match element_to_js_value(js_interpreter, node_id) {
+            JsValue::Address(address) => address,
+            _ => { panic!("elements should always be stored"); },
+        };
+        path.push((element_address, Some(node_id)));
+    }
+    return dispatch_event_along_path(js_interpreter, path, event);
+}
+
+
+pub fn dispatch_window_event(js_interpreter: &mut JsInterpreter, event: DomEvent) -> bool {
+    //this is for events that only go to the window itself, like load
+
+    //the event handler attributes of the body (like onload) are the handlers for the window
+    let body = js_interpreter.document.as_ref().and_then(|document| document.borrow().find_body());
+    let path = vec![(js_interpreter.global_object_address, body)];
+    return dispatch_event_along_path(js_interpreter, path, event);
+}
+
+
+pub fn dispatch_object_event(js_interpreter: &mut JsInterpreter, target: JsAddress, event: DomEvent) -> bool {
+    //this is for events on objects that are not in the document, like a websocket
+    return dispatch_event_along_path(js_interpreter, vec![(target, None)], event);
+}
+
+
+fn dispatch_event_along_path(js_interpreter: &mut JsInterpreter, path: Vec<(JsAddress, Option<DomNodeId>)>, event: DomEvent) -> bool {
+    let state = Rc::new(JsEventState {
+        event_type: event.event_type,
+        target: path[0].0,
+        bubbles: event.bubbles,
+        cancelable: event.cancelable,
+        current_target: Cell::new(None),
+        phase: Cell::new(PHASE_NONE),
+        default_prevented: Cell::new(false),
+        propagation_stopped: Cell::new(false),
+        immediate_propagation_stopped: Cell::new(false),
+    });
+
+    let mut event_object = JsObject::new_with_prototype(Some(js_interpreter.event_prototype_address));
+    event_object.set_internal_slot(JsInternalSlot::Event(state.clone()));
+    let detail_members = match event.details {
+        DomEventDetails::None => Vec::new(),
+        DomEventDetails::Mouse(client_x, client_y) => vec![
+            ("clientX", JsValue::Number(client_x as f64)),
+            ("clientY", JsValue::Number(client_y as f64)),
+            ("button", JsValue::Number(0.0)), //TODO: we only send events for the left mouse button for now
+        ],
+        DomEventDetails::Key(key) => vec![("key", JsValue::String(key))],
+        DomEventDetails::Message(data) => vec![("data", JsValue::String(data))],
+        DomEventDetails::Close(code, reason, was_clean) => vec![
+            ("code", JsValue::Number(code as f64)),
+            ("reason", JsValue::String(reason)),
+            ("wasClean", JsValue::Boolean(was_clean)),
+        ],
+    };
+    for (name, value) in detail_members {
+        let value_address = js_interpreter.add_new_value(value);
+        event_object.set_member(String::from(name), value_address);
+    }
+    let event_value = JsValue::Address(js_interpreter.add_new_value(JsValue::Object(event_object)));
+
+    for (address, node) in path.iter().skip(1).rev() {
+        invoke_event_listeners(js_interpreter, *address, *node, &event_value, &state, PHASE_CAPTURING);
+    }
+    invoke_event_listeners(js_interpreter, path[0].0, path[0].1, &event_value, &state, PHASE_AT_TARGET);
+    if state.bubbles {
+        for (address, node) in path.iter().skip(1) {
+            invoke_event_listeners(js_interpreter, *address, *node, &event_value, &state, PHASE_BUBBLING);
+        }
+    }
+
+    state.current_target.set(None);
+    state.phase.set(PHASE_NONE);
+    return !state.default_prevented.get();
+}
+
+
+fn invoke_event_listeners(js_interpreter: &mut JsInterpreter, current_target: JsAddress, node_id: Option<DomNodeId>, event_value: &JsValue,
+                          state: &Rc<JsEventState>, phase: u8) {
+    if state.propagation_stopped.get() {
+        return;
+    }
+    state.current_target.set(Some(current_target));
+    state.phase.set(phase);
+
+    //capturing listeners only run in the capturing phase, the others only in the bubbling phase, and both run on the target itself
+    let listeners: Vec<(JsAddress, bool)> = match js_interpreter.event_listeners.get(&current_target) {
+        Some(listeners) => listeners.iter()
+                                    .filter(|listener| listener.event_type == state.event_type)
+                                    .filter(|listener| phase == PHASE_AT_TARGET || listener.capture == (phase == PHASE_CAPTURING))
+                                    .map(|listener| (listener.callback, listener.capture))
+                                    .collect(),
+        None => Vec::new(),
+    };
+
+    for (callback, capture) in listeners {
+        if state.immediate_propagation_stopped.get() {
+            return;
+        }
+
+        //a listener that was removed by an earlier listener does not run anymore (and ones added during the dispatch only run for the next event)
+        if find_event_listener(js_interpreter, current_target, &state.event_type, callback, capture).is_none() {
+            continue;
+        }
+        //TODO: listeners can also be objects with a handleEvent method
+        if let Some(JsValue::Function(function)) = js_interpreter.get_value(callback).cloned() {
+            js_interpreter.run_callback(function, JsValue::Address(current_target), vec![event_value.clone()]);
+        }
+    }
+
+    //event handlers (like onclick) never capture, and run after the listeners
+    //TODO: handlers should run in the order they were set in, between the listeners that were added before and after them
+    if phase == PHASE_CAPTURING || state.immediate_propagation_stopped.get() {
+        return;
+    }
+    let handler = find_event_handler(js_interpreter, current_target, node_id, &state.event_type);
+    if handler.is_some() {
+        let result = js_interpreter.run_callback(handler.unwrap(), JsValue::Address(current_target), vec![event_value.clone()]);
+
+        //returning false from a handler cancels the event, like preventDefault does
+        if matches!(result.deref(js_interpreter), JsValue::Boolean(false)) && state.cancelable {
+            state.default_prevented.set(true);
+        }
+    }
+}
+
+
+fn find_event_handler(js_interpreter: &mut JsInterpreter, current_target: JsAddress, node_id: Option<DomNodeId>,
+                      event_type: &str) -> Option<JsFunction> {
+    let handler_name = format!("on{}", event_type);
+
+    //a handler set by a script (as in element.onclick = ...) replaces the one in the attribute
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value(current_target) {
+        if let Some(handler_address) = object.get_member(&handler_name) {
+            return match JsValue::Address(*handler_address).deref(js_interpreter) {
+                JsValue::Function(function) => Some(function),
+                _ => None,
+            };
+        }
+    }
+
+    if node_id.is_some() && js_interpreter.document.is_some() {
+        let handler_source = js_interpreter.document.as_ref().unwrap().borrow().nodes[node_id.unwrap()].get_attribute_value(&handler_name);
+        if handler_source.is_some() {
+            //the code in the attribute is the body of a function that gets the event as "event"
+            //TODO: the code should also see the members of the element, its form and the document as variables
+            let script = js_parser::parse_js(&js_lexer::lex_js(&handler_source.unwrap(), 1, 1));
+            return Some(JsFunction { script: Some(Rc::new(script)), argument_names: vec![String::from("event")], rest_argument_name: None, builtin: None,
+                                     environment: None, bound: None, prototype: None, properties: JsObject::new(), is_async: false });
+        }
+    }
+    return None;
+}
+
+
+fn find_event_listener(js_interpreter: &JsInterpreter, target: JsAddress, event_type: &str, callback: JsAddress, capture: bool) -> Option<usize> {
+    let listeners = js_interpreter.event_listeners.get(&target);
+    if listeners.is_none() {
+        return None;
+    }
+    return listeners.unwrap().iter().position(|listener| listener.event_type == event_type && listener.callback == callback && listener.capture == capture);
+}
+
+
+fn get_event_state(js_interpreter: &JsInterpreter, value: &JsValue) -> Option<Rc<JsEventState>> {
+    if let JsValue::Address(address) = value {
+        if let Some(JsValue::Object(object)) = js_interpreter.get_value(*address) {
+            if let Some(JsInternalSlot::Event(state)) = object.internal_slot() {
+                return Some(state.clone());
+            }
+        }
+    }
+    return None;
+}
+
+
+pub fn call_event_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    match builtin {
+        JsBuiltinFunction::AddEventListener | JsBuiltinFunction::RemoveEventListener => {
+            //addEventListener can be called without an object, which means the window (as it is the global object)
+            let target = match this_value {
+                JsValue::Address(address) => address,
+                JsValue::Undefined | JsValue::Null => js_interpreter.global_object_address,
+                _ => { return JsValue::Undefined; },
+            };
+
+            let event_type = arguments.first().cloned().unwrap_or(JsValue::Undefined).deref(js_interpreter).to_js_string();
+            let callback = match arguments.get(1) {
+                Some(JsValue::Address(address)) => *address,
+                _ => { return JsValue::Undefined; },
+            };
+
+            //the third argument is either whether the listener captures, or an object with options
+            //TODO: support the once and passive options
+            let options = arguments.get(2).cloned().unwrap_or(JsValue::Undefined);
+            let capture = match options.clone().deref(js_interpreter) {
+                JsValue::Object(_) => get_property(js_interpreter, options, &String::from("capture")).is_truthy(js_interpreter),
+                other => other.is_truthy(js_interpreter),
+            };
+
+            //adding the same listener twice does nothing, it is still called only once
+            let existing_idx = find_event_listener(js_interpreter, target, &event_type, callback, capture);
+            if matches!(builtin, JsBuiltinFunction::AddEventListener) {
+                if existing_idx.is_none() {
+                    js_interpreter.event_listeners.entry(target).or_default().push(JsEventListener { event_type, callback, capture });
+                }
+            } else if existing_idx.is_some() {
+                js_interpreter.event_listeners.get_mut(&target).unwrap().remove(existing_idx.unwrap());
+            }
+            return JsValue::Undefined;
+        },
+        _ => {},
+    }
+
+    let state = get_event_state(js_interpreter, &this_value);
+    if state.is_none() {
+        return JsValue::Undefined;
+    }
+    let state = state.unwrap();
+
+    match builtin {
+        JsBuiltinFunction::EventPreventDefault => {
+            //this does nothing for events that can't be cancelled
+            if state.cancelable {
+                state.default_prevented.set(true);
+            }
+        },
+        JsBuiltinFunction::EventStopPropagation => {
+            state.propagation_stopped.set(true);
+        },
+        JsBuiltinFunction::EventStopImmediatePropagation => {
+            state.propagation_stopped.set(true);
+            state.immediate_propagation_stopped.set(true);
+        },
+        JsBuiltinFunction::EventGetType => { return JsValue::String(state.event_type.clone()); },
+        JsBuiltinFunction::EventGetTarget => { return JsValue::Address(state.target); },
+        JsBuiltinFunction::EventGetCurrentTarget => {
+            //outside of the dispatch there is no current target
+            return state.current_target.get().map(JsValue::Address).unwrap_or(JsValue::Null);
+        },
+        JsBuiltinFunction::EventGetEventPhase => { return JsValue::Number(state.phase.get() as f64); },
+        JsBuiltinFunction::EventGetBubbles => { return JsValue::Boolean(state.bubbles); },
+        JsBuiltinFunction::EventGetCancelable => { return JsValue::Boolean(state.cancelable); },
+        JsBuiltinFunction::EventGetDefaultPrevented => { return JsValue::Boolean(state.default_prevented.get()); },
+        _ => {
+            panic!("not an event function");
+        },
+    }
+    return JsValue::Undefined;
+}
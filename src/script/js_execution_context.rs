@@ -1,9 +1,16 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use super::js_ast::Script;
+use crate::dom::DomNodeId;
+
+use super::js_ast::{parse_numeric_literal, Script};
+use super::js_events::JsEventState;
 use super::js_interpreter::JsInterpreter;
+use super::js_promise::JsPromiseState;
+use super::js_regex::JsRegex;
+use super::js_websocket::JsWebSocketState;
 
 
 pub type JsAddress = usize;
@@ -13,116 +20,414 @@ static NEXT_JS_VALUE_ADDRESS: AtomicUsize = AtomicUsize::new(1);
 pub fn get_next_js_value_address() -> JsAddress { NEXT_JS_VALUE_ADDRESS.fetch_add(1, Ordering::Relaxed) }
 
 
-pub struct JsExecutionContext {
-    variables: HashMap<String, JsAddress>,
-    values: HashMap<JsAddress, JsValue>,
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy)]
+struct JsVariable {
+    address: Option<JsAddress>, //let and const variables have no value until their declaration runs, and can't be used before that
+    is_const: bool,
+    is_read_only: bool, //this is for the globals undefined, NaN and Infinity, which can't be changed, but assigning to them is no error either
 }
-impl JsExecutionContext {
-    pub fn new() -> JsExecutionContext {
-        //TODO: I don't think we need to create the objects on every new context, we should just set references to objects
-        //      we create in the interpreter (assuming we need to have the names available at all, scoping rules would probably
-        //      require us to look into higher stack frames when a var is not found anyway...)
 
-        let mut variables = HashMap::new();
-        let mut values = HashMap::new();
 
-        let console_log_function = JsValue::Function(JsFunction {
-            argument_names: Vec::new(), //Note that this function _does_ take an argument, but it does not have a name
-            script: None,
-            builtin: Some(JsBuiltinFunction::ConsoleLog),
-        });
+pub enum JsVariableLookup {
+    Found(JsAddress),
+    Uninitialized,
+    NotDeclared,
+}
 
-        let console_log_address = get_next_js_value_address();
-        values.insert(console_log_address, console_log_function);
 
-        let console_builtin = JsValue::Object(JsObject {
-            members: HashMap::from([(String::from("log"), console_log_address)])
-        });
-        let console_object_address = get_next_js_value_address();
-        values.insert(console_object_address, console_builtin);
+pub enum JsVariableAssignment {
+    Assigned,
+    Uninitialized,
+    Constant,
+    ReadOnly,
+    NotDeclared,
+}
 
-        variables.insert(String::from("console"), console_object_address);
 
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct JsEnvironment {
+    //an environment holds the variables of a scope, and is kept alive by the functions defined in it (so they can use them later)
+    variables: HashMap<String, JsVariable>,
+    parent: Option<Rc<RefCell<JsEnvironment>>>,
+}
+impl JsEnvironment {
+    pub fn new(parent: Option<Rc<RefCell<JsEnvironment>>>) -> Rc<RefCell<JsEnvironment>> {
+        return Rc::from(RefCell::from(JsEnvironment { variables: HashMap::new(), parent }));
+    }
 
-        #[cfg(test)] {
-            let tester_export_function = JsValue::Function(JsFunction {
-                argument_names: Vec::new(), //Note that this function _does_ take an argument, but it does not have a name
-                script: None,
-                builtin: Some(JsBuiltinFunction::TesterExport),
-            });
+    pub fn new_copy(environment: &Rc<RefCell<JsEnvironment>>) -> Rc<RefCell<JsEnvironment>> {
+        //a new environment with the same variables and parent, which loops use to give every iteration its own let variables
+        let environment = environment.borrow();
+        return Rc::from(RefCell::from(JsEnvironment { variables: environment.variables.clone(), parent: environment.parent.clone() }));
+    }
 
-            let tester_export_address = get_next_js_value_address();
-            values.insert(tester_export_address, tester_export_function);
+    pub fn lookup_variable(&self, name: &String) -> JsVariableLookup {
+        let variable = self.variables.get(name);
+        if variable.is_some() {
+            return match variable.unwrap().address {
+                Some(address) => JsVariableLookup::Found(address),
+                None => JsVariableLookup::Uninitialized,
+            };
+        }
+        if self.parent.is_some() {
+            return self.parent.as_ref().unwrap().borrow().lookup_variable(name);
+        }
+        return JsVariableLookup::NotDeclared;
+    }
 
-            let tester_builtin = JsValue::Object(JsObject {
-                members: HashMap::from([(String::from("export"), tester_export_address)])
-            });
-            let tester_object_address = get_next_js_value_address();
-            values.insert(tester_object_address, tester_builtin);
+    pub fn has_own_variable(&self, name: &String) -> bool {
+        return self.variables.contains_key(name);
+    }
 
-            variables.insert(String::from("tester"), tester_object_address);
+    pub fn declare_variable(&mut self, name: String, address: JsAddress) {
+        //declaring a read only variable again (like with "var undefined = 1;") does not change it
+        if self.variables.get(&name).is_some_and(|variable| variable.is_read_only) {
+            return;
         }
-
-        return JsExecutionContext {
-            variables,
-            values,
-        };
+        self.variables.insert(name, JsVariable { address: Some(address), is_const: false, is_read_only: false });
     }
 
-    pub fn get_var_address(&self, name: &String) -> Option<&JsAddress> {
-        return self.variables.get(name);
+    pub fn declare_read_only_variable(&mut self, name: String, address: JsAddress) {
+        self.variables.insert(name, JsVariable { address: Some(address), is_const: false, is_read_only: true });
     }
 
-    pub fn get_value(&mut self, address: &JsAddress) -> Option<&mut JsValue> {
-        return self.values.get_mut(address);
+    pub fn declare_uninitialized_variable(&mut self, name: String, is_const: bool) {
+        self.variables.insert(name, JsVariable { address: None, is_const, is_read_only: false });
     }
 
-    pub fn update_variable(&mut self, name: String, address: usize) {
-        self.variables.insert(name, address);
+    pub fn initialize_variable(&mut self, name: String, address: JsAddress, is_const: bool) {
+        self.variables.insert(name, JsVariable { address: Some(address), is_const, is_read_only: false });
     }
 
-    pub fn add_new_value(&mut self, value: JsValue) -> JsAddress {
-        let new_address = get_next_js_value_address();
-        self.values.insert(new_address, value);
-        return new_address;
+    pub fn assign_variable(&mut self, name: String, address: JsAddress) -> JsVariableAssignment {
+        //assigns to the variable in the closest scope that has it
+        let variable = self.variables.get_mut(&name);
+        if variable.is_some() {
+            let variable = variable.unwrap();
+            if variable.address.is_none() {
+                return JsVariableAssignment::Uninitialized;
+            }
+            if variable.is_const {
+                return JsVariableAssignment::Constant;
+            }
+            if variable.is_read_only {
+                return JsVariableAssignment::ReadOnly;
+            }
+            variable.address = Some(address);
+            return JsVariableAssignment::Assigned;
+        }
+        if self.parent.is_some() {
+            return self.parent.as_ref().unwrap().borrow_mut().assign_variable(name, address);
+        }
+        return JsVariableAssignment::NotDeclared;
     }
 }
 
 
+pub struct JsExecutionContext {
+    //an execution context is an entry on the call stack
+    pub environment: Rc<RefCell<JsEnvironment>>, //this changes when entering and leaving blocks, for their let and const variables
+    pub var_environment: Rc<RefCell<JsEnvironment>>, //the environment of the function (or script) itself, where var declares variables
+    pub this_value: JsValue,
+}
+
+
 #[cfg_attr(debug_assertions, derive(Debug))]
 #[derive(Clone)]
 pub enum JsValue {
-    Number(i32), //TODO: number type is wrong here, we need different rust types depending on what kind of number it is? (floats?)
-                 //      or a more complex type maybe?
+    Number(f64),
     String(String),
-    #[allow(dead_code)] Boolean(bool), //TODO: use
+    Boolean(bool),
     Object(JsObject),
     Function(JsFunction),
     Address(JsAddress),
     Undefined,
+    Null,
 }
 impl JsValue {
     pub fn deref(self, js_interpreter: &JsInterpreter) -> JsValue {
         match self {
             JsValue::Address(variable) => {
 
-                //TODO: we might also need to look into higher stack items (for globals), not sure if this is always the case
-                let current_context = js_interpreter.context_stack.iter().last().unwrap();
-
                 //TODO: unwrap() here is wrong, we need to report an error that a variable or property does not exist
                 //      or maybe we should return an option or result here, and handle it on the recieving side...
-                return current_context.values.get(&variable).unwrap().clone();
+                return js_interpreter.get_value(variable).unwrap().clone();
             },
             _ => { return self }
         }
     }
+
+    pub fn to_primitive(self, js_interpreter: &JsInterpreter) -> JsValue {
+        //the value should already be dereferenced
+        //TODO: this should call valueOf and toString on the object, when we have those
+        match self {
+            JsValue::Object(object) if matches!(object.internal_slot(), Some(JsInternalSlot::Array(_))) => {
+                return JsValue::String(join_array(&object, js_interpreter, &mut Vec::new()));
+            },
+            JsValue::Object(_) | JsValue::Function(_) => { return JsValue::String(self.to_js_string()); },
+            _ => { return self; },
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        //the value should already be dereferenced
+        match self {
+            JsValue::Number(number) => { return *number; },
+            JsValue::String(string) => { return string_to_number(string); },
+            JsValue::Boolean(boolean) => { return if *boolean { 1.0 } else { 0.0 }; },
+            JsValue::Object(_) | JsValue::Function(_) => { return string_to_number(&self.to_js_string()); }, //arrays should go via to_primitive() first
+            JsValue::Null => { return 0.0; },
+            JsValue::Undefined | JsValue::Address(_) => { return f64::NAN; },
+        }
+    }
+
+    pub fn to_js_string(&self) -> String {
+        //the value should already be dereferenced
+        match self {
+            JsValue::Number(number) => { return number_to_string(*number); },
+            JsValue::String(string) => { return string.clone(); },
+            JsValue::Boolean(boolean) => { return boolean.to_string(); },
+            JsValue::Object(_) => { return String::from("[object Object]"); }, //arrays should go via to_primitive() first, which joins their elements
+            JsValue::Function(_) => { return String::from("function () { [native code] }"); }, //TODO: this should be the source of the function
+            JsValue::Undefined => { return String::from("undefined"); },
+            JsValue::Null => { return String::from("null"); },
+            JsValue::Address(_) => { panic!("values should be dereferenced before converting them"); },
+        }
+    }
+
+    pub fn strictly_equals(&self, other: &JsValue) -> bool {
+        //both values should already be dereferenced
+        match (self, other) {
+            (JsValue::Number(one), JsValue::Number(two)) => { return one == two; },
+            (JsValue::String(one), JsValue::String(two)) => { return one == two; },
+            (JsValue::Boolean(one), JsValue::Boolean(two)) => { return one == two; },
+            (JsValue::Undefined, JsValue::Undefined) | (JsValue::Null, JsValue::Null) => { return true; },
+            //NOTE: objects are only equal to themselves, which we can't see here, because we don't know where they are stored
+            _ => { return false; },
+        }
+    }
+
+    pub fn loosely_equals(&self, other: &JsValue) -> bool {
+        //values of different types are converted to numbers before comparing them (and undefined converts to NaN, so it is never equal)
+        //TODO: objects should be converted to primitives first (via valueOf and toString)
+        if self.is_nullish() || other.is_nullish() {
+            return self.is_nullish() && other.is_nullish(); //null and undefined are only equal to each other, so null == 0 is false
+        }
+        match (self, other) {
+            (JsValue::Number(_), JsValue::String(_)) | (JsValue::String(_), JsValue::Number(_)) |
+            (JsValue::Boolean(_), _) | (_, JsValue::Boolean(_)) => {
+                return self.to_number() == other.to_number();
+            },
+            _ => { return self.strictly_equals(other); },
+        }
+    }
+
+    pub fn is_nullish(&self) -> bool {
+        //the value should already be dereferenced
+        return matches!(self, JsValue::Undefined | JsValue::Null);
+    }
+
+    pub fn is_truthy(self, js_interpreter: &JsInterpreter) -> bool {
+        match self.deref(js_interpreter) {
+            JsValue::Number(number) => { return number != 0.0 && !number.is_nan(); },
+            JsValue::String(string) => { return !string.is_empty(); },
+            JsValue::Boolean(boolean) => { return boolean; },
+            JsValue::Object(_) | JsValue::Function(_) => { return true; },
+            JsValue::Undefined | JsValue::Null => { return false; },
+            JsValue::Address(address) => { return JsValue::Address(address).is_truthy(js_interpreter); }, //values can point to other values
+        }
+    }
+}
+
+
+fn join_array(array: &JsObject, js_interpreter: &JsInterpreter, arrays_being_joined: &mut Vec<JsAddress>) -> String {
+    //the elements are joined with commas, with null and undefined (and holes) as empty strings, like Array.prototype.join() does
+    let length = match array.internal_slot() {
+        Some(JsInternalSlot::Array(length)) => *length,
+        _ => { panic!("only arrays can be joined"); },
+    };
+
+    let mut element_strings = Vec::with_capacity(length);
+    for idx in 0..length {
+        let element_address = array.get_member(&idx.to_string());
+        if element_address.is_none() {
+            element_strings.push(String::new());
+            continue;
+        }
+        let mut element_address = *element_address.unwrap();
+        let mut element = JsValue::Address(element_address).deref(js_interpreter);
+        while let JsValue::Address(address) = element {
+            //values can point to other values
+            element_address = address;
+            element = JsValue::Address(address).deref(js_interpreter);
+        }
+
+        let element_string = match &element {
+            JsValue::Undefined | JsValue::Null => String::new(),
+            JsValue::Object(object) if matches!(object.internal_slot(), Some(JsInternalSlot::Array(_))) => {
+                //an array that contains itself (directly or deeper down) is empty the second time, instead of being joined forever
+                if arrays_being_joined.contains(&element_address) {
+                    String::new()
+                } else {
+                    arrays_being_joined.push(element_address);
+                    let nested_string = join_array(object, js_interpreter, arrays_being_joined);
+                    arrays_being_joined.pop();
+                    nested_string
+                }
+            },
+            _ => element.to_primitive(js_interpreter).to_js_string(),
+        };
+        element_strings.push(element_string);
+    }
+    return element_strings.join(",");
+}
+
+
+fn string_to_number(string: &str) -> f64 {
+    let trimmed = string.trim();
+    if trimmed.is_empty() {
+        return 0.0;
+    }
+    match trimmed {
+        "Infinity" | "+Infinity" => { return f64::INFINITY; },
+        "-Infinity" => { return f64::NEG_INFINITY; },
+        _ => {},
+    }
+
+    //unlike literals in the source, numbers in strings can't contain separators, but can have a sign
+    if trimmed.contains('_') || trimmed.starts_with("-0x") || trimmed.starts_with("+0x") {
+        return f64::NAN;
+    }
+    let (sign, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    return parse_numeric_literal(unsigned).map(|number| sign * number).unwrap_or(f64::NAN);
+}
+
+
+fn number_to_string(number: f64) -> String {
+    if number.is_nan() {
+        return String::from("NaN");
+    }
+    if number.is_infinite() {
+        return String::from(if number > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    if number == 0.0 {
+        return String::from("0"); //this also makes sure -0 is shown as 0
+    }
+
+    //rust formats floats with the shortest representation that parses back to the same number, which is also what javascript does,
+    //  but javascript switches to exponential notation for very big or small numbers, and writes the exponent with a sign
+    let magnitude = number.abs();
+    if magnitude >= 1e21 || magnitude < 1e-6 {
+        let formatted = format!("{:e}", number);
+        if formatted.contains("e-") {
+            return formatted;
+        }
+        return formatted.replace('e', "e+");
+    }
+    return format!("{}", number);
 }
 
 
 #[cfg_attr(debug_assertions, derive(Debug))]
 #[derive(Clone)]
 pub struct JsObject {
-    pub members: HashMap<String, JsAddress>,
+    members: HashMap<String, JsAddress>,
+    accessors: HashMap<String, JsAccessor>, //properties with a getter and/or setter instead of a value, a name is never in both maps
+    member_order: Vec<String>, //we keep the order in which the members were added, because that is the order we iterate over them
+    prototype: Option<JsAddress>, //members the object does not have itself are looked up in its prototype (the [[Prototype]] in the spec)
+    internal_slot: Option<JsInternalSlot>,
+}
+impl JsObject {
+    pub fn new() -> JsObject {
+        //TODO: objects without an explicit prototype should get Object.prototype, when we have that
+        return JsObject::new_with_prototype(None);
+    }
+
+    pub fn new_with_prototype(prototype: Option<JsAddress>) -> JsObject {
+        return JsObject { members: HashMap::new(), accessors: HashMap::new(), member_order: Vec::new(), prototype, internal_slot: None };
+    }
+
+    pub fn prototype(&self) -> Option<JsAddress> {
+        return self.prototype;
+    }
+
+    pub fn internal_slot(&self) -> Option<&JsInternalSlot> {
+        return self.internal_slot.as_ref();
+    }
+
+    pub fn internal_slot_mut(&mut self) -> Option<&mut JsInternalSlot> {
+        return self.internal_slot.as_mut();
+    }
+
+    pub fn set_prototype(&mut self, prototype: Option<JsAddress>) {
+        self.prototype = prototype;
+    }
+
+    pub fn set_internal_slot(&mut self, internal_slot: JsInternalSlot) {
+        self.internal_slot = Some(internal_slot);
+    }
+
+    pub fn get_member(&self, name: &String) -> Option<&JsAddress> {
+        return self.members.get(name);
+    }
+
+    pub fn set_member(&mut self, name: String, address: JsAddress) {
+        if !self.members.contains_key(&name) && !self.accessors.contains_key(&name) {
+            self.member_order.push(name.clone());
+        }
+        self.accessors.remove(&name);
+        self.members.insert(name, address);
+    }
+
+    pub fn get_accessor(&self, name: &String) -> Option<&JsAccessor> {
+        return self.accessors.get(name);
+    }
+
+    pub fn set_accessor(&mut self, name: String, accessor: JsAccessor) {
+        if !self.members.contains_key(&name) && !self.accessors.contains_key(&name) {
+            self.member_order.push(name.clone());
+        }
+        self.members.remove(&name);
+        self.accessors.insert(name, accessor);
+    }
+
+    pub fn remove_member(&mut self, name: &String) {
+        self.members.remove(name);
+        self.accessors.remove(name);
+        self.member_order.retain(|member_name| member_name != name);
+    }
+
+    pub fn member_names(&self) -> &Vec<String> {
+        return &self.member_order;
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct JsAccessor {
+    pub getter: Option<JsFunction>,
+    pub setter: Option<JsFunction>,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub enum JsInternalSlot {
+    //state of builtin objects that scripts can't access directly, only via the methods of the object
+    DateValue(f64), //milliseconds since the unix epoch (in UTC), or NaN for an invalid date
+    RegExp(Rc<JsRegex>),
+    Promise(Box<JsPromiseState>), //boxed, because the state contains values itself
+    Array(usize), //arrays are objects with numbered members, this marks them as arrays and keeps their length
+    DomElement(DomNodeId), //the node in the document that an element object stands for
+    DomStyle(DomNodeId), //the object for element.style, which reads and writes the inline style of the node
+    DomClassList(DomNodeId), //the object for element.classList, which reads and writes the class attribute of the node
+    Event(Rc<JsEventState>), //shared with the dispatch of the event, which needs to see when a listener cancels it
+    WebSocket(Rc<JsWebSocketState>), //shared with the interpreter, which delivers the events of the connection
 }
 
 
@@ -131,7 +436,27 @@ pub struct JsObject {
 pub struct JsFunction {
     pub script: Option<Rc<Script>>,
     pub argument_names: Vec<String>,
+    pub rest_argument_name: Option<String>, //for "function f(a, ...rest)", where rest gets the remaining arguments as an array
     pub builtin: Option<JsBuiltinFunction>,
+    pub environment: Option<Rc<RefCell<JsEnvironment>>>, //the environment the function was created in, which it can still use when it is called
+    pub bound: Option<Rc<JsBoundFunction>>, //for functions made with bind(), which call another function with a fixed "this" and arguments
+    pub prototype: Option<JsAddress>, //the "prototype" property, which becomes the prototype of objects made with "new" on this function
+    pub properties: JsObject, //functions are objects as well, so they can have other properties (like Date.now)
+    pub is_async: bool,
+}
+impl JsFunction {
+    pub fn new_builtin(builtin: JsBuiltinFunction) -> JsFunction {
+        return JsFunction { script: None, argument_names: Vec::new(), rest_argument_name: None, builtin: Some(builtin), environment: None, bound: None, prototype: None,
+                            properties: JsObject::new(), is_async: false };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct JsBoundFunction {
+    pub target: JsFunction,
+    pub this_value: JsValue,
+    pub arguments: Vec<JsValue>,
 }
 
 
@@ -139,11 +464,147 @@ pub struct JsFunction {
 #[derive(Clone)]
 pub enum JsBuiltinFunction {
     ConsoleLog,
+    FunctionCall,
+    FunctionApply,
+    FunctionBind,
+    MathFloor,
+    MathCeil,
+    MathRound,
+    MathRandom,
+    MathMin,
+    MathMax,
+    MathAbs,
+    MathPow,
+    NumberIsNaN,
+    ParseInt,
+    ParseFloat,
+    IsNaN,
+    DateConstructor,
+    DateNow,
+    DateGetTime,
+    DateGetFullYear,
+    DateGetMonth,
+    DateGetDate,
+    DateGetHours,
+    DateToISOString,
+    RegExpConstructor,
+    RegExpTest,
+    RegExpExec,
+    StringMatch,
+    StringReplace,
+    SetTimeout,
+    SetInterval,
+    ClearTimer,
+    ErrorConstructor(JsError), //Error itself and its subclasses, like TypeError
+    PromiseConstructor,
+    PromiseResolver(JsAddress, bool, Rc<Cell<bool>>), //resolves (or with true, rejects) the promise, the cell is shared by the pair
+    PromiseThen,
+    PromiseCatch,
+    PromiseStaticResolve,
+    PromiseStaticReject,
+    AsyncFunctionResume(u32, bool), //continues the suspended async function with this id, with true it throws the value at its await
+    DocumentGetElementById,
+    DocumentQuerySelector,
+    DocumentQuerySelectorAll,
+    ElementQuerySelector,
+    ElementQuerySelectorAll,
+    ElementGetTagName,
+    ElementGetId,
+    DocumentCreateElement,
+    DocumentCreateTextNode,
+    DocumentGetReadyState,
+    ElementAppendChild,
+    ElementInsertBefore,
+    ElementRemoveChild,
+    ElementGetInnerHtml,
+    ElementSetInnerHtml,
+    ElementGetTextContent,
+    ElementSetTextContent,
+    ElementGetAttribute,
+    ElementSetAttribute,
+    ElementRemoveAttribute,
+    ElementHasAttribute,
+    ElementSetId,
+    ElementGetClassName,
+    ElementSetClassName,
+    ElementGetClassList,
+    ElementGetStyle,
+    ClassListAdd,
+    ClassListRemove,
+    ClassListToggle,
+    ClassListContains,
+    StyleGetPropertyValue,
+    StyleSetProperty,
+    StyleRemoveProperty,
+    StyleGetCssText,
+    StyleSetCssText,
+    ElementGetBoundingClientRect,
+    GetComputedStyle,
+    ComputedStyleGetPropertyValue,
+    WindowGetInnerWidth,
+    WindowGetInnerHeight,
+    WindowGetDevicePixelRatio,
+    ScreenGetWidth,
+    ScreenGetHeight,
+    WindowAlert,
+    WindowConfirm,
+    WindowPrompt,
+    AddEventListener,
+    RemoveEventListener,
+    EventPreventDefault,
+    EventStopPropagation,
+    EventStopImmediatePropagation,
+    EventGetType,
+    EventGetTarget,
+    EventGetCurrentTarget,
+    EventGetEventPhase,
+    EventGetBubbles,
+    EventGetCancelable,
+    EventGetDefaultPrevented,
+    WebSocketConstructor,
+    WebSocketSend,
+    WebSocketClose,
+    WebSocketGetReadyState,
+    WebSocketGetUrl,
+    WptReport,
     #[cfg(test)] TesterExport,
 }
+impl JsBuiltinFunction {
+    pub fn is_constructor(&self) -> bool {
+        return matches!(self, JsBuiltinFunction::DateConstructor | JsBuiltinFunction::RegExpConstructor | JsBuiltinFunction::PromiseConstructor |
+                       JsBuiltinFunction::WebSocketConstructor | JsBuiltinFunction::ErrorConstructor(_));
+    }
+}
 
 
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum JsError {
     //NOTE: these are runtime errors, not parse-time errors (i.e. these are errors you can catch in a script)
-    ReferenceError, //TODO: give the specific errors extra information (like here, what reference, and on what position in the script etc)
+    Error,
+    EvalError,
+    ReferenceError,
+    TypeError,
+    RangeError,
+    SyntaxError,
+    URIError,
+    InvalidStateError,
+    InvalidAccessError,
+    SecurityError,
+}
+impl JsError {
+    pub fn name(&self) -> &str {
+        match self {
+            JsError::Error => { return "Error"; },
+            JsError::EvalError => { return "EvalError"; },
+            JsError::ReferenceError => { return "ReferenceError"; },
+            JsError::TypeError => { return "TypeError"; },
+            JsError::RangeError => { return "RangeError"; },
+            JsError::SyntaxError => { return "SyntaxError"; },
+            JsError::URIError => { return "URIError"; },
+            JsError::InvalidStateError => { return "InvalidStateError"; },
+            JsError::InvalidAccessError => { return "InvalidAccessError"; },
+            JsError::SecurityError => { return "SecurityError"; },
+        }
+    }
 }
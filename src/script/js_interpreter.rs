@@ -1,102 +1,875 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+#[cfg(debug_assertions)] use std::fmt;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::dom::{Document, ElementDomNode};
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::config::SETTINGS;
+use crate::dom::{Document, DomNodeId};
+use crate::layout::{update_dirty_layout, FullLayout};
+use crate::platform::fonts::FontContext;
+use crate::ui::WindowDimensions;
+use crate::wpt::{SubtestResult, SubtestStatus};
 
-use super::js_ast::Script;
+use super::js_ast::{call_function, hoist_lexical_declarations, hoist_var_declarations, JsCompletion, Script};
+use super::js_async::JsAsyncFunctions;
+use super::js_console;
+use super::js_dom::JsDomObjectKind;
+use super::js_events::{self, DomEvent, JsEventListener};
 use super::js_execution_context::{
+    get_next_js_value_address,
     JsAddress,
+    JsBuiltinFunction,
+    JsEnvironment,
     JsError,
     JsExecutionContext,
+    JsAccessor,
+    JsFunction,
+    JsInternalSlot,
+    JsObject,
     JsValue,
+    JsVariableLookup,
 };
+use super::js_promise::{self, JsMicrotask};
+use super::js_websocket::{self, JsWebSocketState, READY_STATE_CLOSED, READY_STATE_CLOSING, READY_STATE_CONNECTING, READY_STATE_OPEN};
 
 
+const DEFAULT_SCRIPT_TIME_LIMIT: Duration = Duration::from_secs(5);
+const INTERRUPTED_SCRIPT_MESSAGE: &str = "script interrupted";
+pub const MAX_CALL_DEPTH: usize = 64; //every call uses the rust stack, without optimizations this many (with room to spare) fit on the 2 MB stacks of async functions
+
+
+struct JsTimer {
+    id: u32,
+    due: Instant,
+    callback: JsFunction,
+    arguments: Vec<JsValue>,
+    repeat_interval: Option<Duration>, //for setInterval, the timer is scheduled again every time it runs
+}
+
+
+pub enum JsDialog {
+    Alert(String),
+    Confirm(String),
+    Prompt(String, String), //the message, and the text the field starts with
+}
+
+
+pub struct JsLayoutContext {
+    //this is what scripts need to measure elements on the page, it is only there once the page has been laid out
+    pub full_layout: Rc<RefCell<FullLayout>>,
+    pub font_context: Rc<FontContext>,
+    pub current_scroll_y: f32,
+}
+
 
 pub struct JsInterpreter {
     pub context_stack: Vec<JsExecutionContext>,
-    current_error: Option<JsError>,
-    pub return_value: Option<JsValue>,
+    pub global_environment: Rc<RefCell<JsEnvironment>>, //this is shared by all scripts on the page
+    pub global_object_address: JsAddress, //the window object, which is "this" at the top level
+    pub regexp_prototype_address: JsAddress, //regex literals get this as their prototype, even when RegExp itself is overwritten
+    pub promise_prototype_address: JsAddress, //the prototype of the promises we make ourselves, like the ones async functions return
+    pub element_prototype_address: JsAddress, //the prototype of the objects for elements in the document
+    pub style_prototype_address: JsAddress, //the prototype of the element.style objects
+    pub class_list_prototype_address: JsAddress, //the prototype of the element.classList objects
+    pub event_prototype_address: JsAddress, //the prototype of the event objects that listeners get
+    pub computed_style_prototype_address: JsAddress, //the prototype of the objects getComputedStyle returns
+    pub error_prototype_addresses: HashMap<JsError, JsAddress>, //the prototypes of Error and its subclasses, the errors we throw ourselves get them as well
+    pub document_object_address: JsAddress, //the document object, which gets the events for the document node
+    pub document: Option<Rc<RefCell<Document>>>, //the document the scripts run in, set when running the scripts of a document
+    pub layout_context: Option<JsLayoutContext>,
+    pub screen_dimensions: WindowDimensions, //the size of the display the browser is on, for screen.width and screen.height
+    pub dialog_handler: Option<Box<dyn Fn(&JsDialog) -> Option<String>>>, //this shows a dialog until the user closes it, giving the entered text for ok
+    pub dom_objects: HashMap<(DomNodeId, JsDomObjectKind), JsAddress>, //the objects made for nodes so far, by the internal id of their node
+    pub event_listeners: HashMap<JsAddress, Vec<JsEventListener>>, //the listeners added with addEventListener, by the object they were added to
+    pub script_time_limit: Duration, //scripts (and callbacks) that run longer than this are stopped, so they can't hang the browser
+    pub websockets: Vec<(JsAddress, Rc<JsWebSocketState>)>, //the websockets that are not closed yet, with their objects, to deliver their events to
+    pub nr_of_errors: usize, //the errors logged to the console while running the scripts of the page, which the status bar shows
+    pub wpt_results: Vec<SubtestResult>, //the results the web platform tests harness reported, by the id of the test
+    pub async_functions: JsAsyncFunctions, //the async functions that are running or waiting for a promise
+    pub call_depth: usize, //the number of script functions that are being called now, nested in each other
+    queued_scripts: VecDeque<Rc<Script>>, //the scripts of the document that did not run yet, they run a few at a time from the main loop
+    time_limit_end: Option<Instant>, //when the script that is running now should be stopped
+    pub navigation_interrupted: bool, //set when the page navigates away, so what its scripts still try to run is stopped as well
+    interrupted: bool, //this is set when the running script went over its time limit, everything it still tries to run is stopped
+    values: HashMap<JsAddress, JsValue>, //TODO: we never remove values, we need garbage collection for that
+    pending_exception: Option<JsValue>, //an exception thrown while executing an expression, that the statement executing it should pick up
+    timers: Vec<JsTimer>,
+    next_timer_id: u32,
+    microtasks: VecDeque<JsMicrotask>, //these run as soon as the script or callback that is running is done, before any timer
+    possibly_unhandled_rejections: Vec<JsAddress>, //promises that were rejected without a handler, checked after running the microtasks
     #[cfg(test)] pub last_test_data: Option<JsValue>,
 }
-
+#[cfg(debug_assertions)]
+impl fmt::Debug for JsInterpreter {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        //the values of the scripts are too much to print, this is there so things that have an interpreter (like frames) can still be printed
+        return formatter.debug_struct("JsInterpreter").finish_non_exhaustive();
+    }
+}
 impl JsInterpreter {
-    pub fn new() -> JsInterpreter {
-        return JsInterpreter {
+    pub fn new() -> Box<JsInterpreter> {
+        //the interpreter is boxed, because suspended async functions refer to it, so it should not move when the page it is on moves
+        let mut interpreter = Box::new(JsInterpreter {
             context_stack: Vec::new(),
-            current_error: None,
-            return_value: None,
+            global_environment: JsEnvironment::new(None),
+            global_object_address: 0,
+            regexp_prototype_address: 0,
+            promise_prototype_address: 0,
+            element_prototype_address: 0,
+            style_prototype_address: 0,
+            class_list_prototype_address: 0,
+            event_prototype_address: 0,
+            computed_style_prototype_address: 0,
+            error_prototype_addresses: HashMap::new(),
+            document_object_address: 0,
+            document: None,
+            layout_context: None,
+            screen_dimensions: WindowDimensions { width: SCREEN_WIDTH, height: SCREEN_HEIGHT },
+            dialog_handler: None,
+            dom_objects: HashMap::new(),
+            event_listeners: HashMap::new(),
+            script_time_limit: DEFAULT_SCRIPT_TIME_LIMIT,
+            websockets: Vec::new(),
+            queued_scripts: VecDeque::new(),
+            time_limit_end: None,
+            navigation_interrupted: false,
+            interrupted: false,
+            values: HashMap::new(),
+            pending_exception: None,
+            timers: Vec::new(),
+            next_timer_id: 1, //browsers never give out 0 as a timer id, so scripts can use it to mean "no timer"
+            microtasks: VecDeque::new(),
+            possibly_unhandled_rejections: Vec::new(),
+            nr_of_errors: 0,
+            wpt_results: Vec::new(),
+            async_functions: JsAsyncFunctions::new(),
+            call_depth: 0,
             #[cfg(test)] last_test_data: None,
-        };
+        });
+        interpreter.add_builtins();
+        return interpreter;
+    }
+
+    fn add_builtins(&mut self) {
+        //TODO: global variables should also be members of the global object, and the other way around
+        let mut global_object = JsObject::new();
+
+        let console_address = self.add_builtin_object("console", vec![("log", JsBuiltinFunction::ConsoleLog)]);
+        global_object.set_member(String::from("console"), console_address);
+
+        let math_address = self.add_builtin_object("Math", vec![
+            ("floor", JsBuiltinFunction::MathFloor),
+            ("ceil", JsBuiltinFunction::MathCeil),
+            ("round", JsBuiltinFunction::MathRound),
+            ("random", JsBuiltinFunction::MathRandom),
+            ("min", JsBuiltinFunction::MathMin),
+            ("max", JsBuiltinFunction::MathMax),
+            ("abs", JsBuiltinFunction::MathAbs),
+            ("pow", JsBuiltinFunction::MathPow),
+        ]);
+        global_object.set_member(String::from("Math"), math_address);
+
+        //TODO: Number should be a function as well, that converts its argument to a number
+        let number_address = self.add_builtin_object("Number", vec![("isNaN", JsBuiltinFunction::NumberIsNaN), ("parseFloat", JsBuiltinFunction::ParseFloat)]);
+        global_object.set_member(String::from("Number"), number_address);
+
+        let (date_address, _) = self.add_builtin_constructor("Date", JsBuiltinFunction::DateConstructor, vec![
+            ("getTime", JsBuiltinFunction::DateGetTime),
+            ("getFullYear", JsBuiltinFunction::DateGetFullYear),
+            ("getMonth", JsBuiltinFunction::DateGetMonth),
+            ("getDate", JsBuiltinFunction::DateGetDate),
+            ("getHours", JsBuiltinFunction::DateGetHours),
+            ("toISOString", JsBuiltinFunction::DateToISOString),
+        ], vec![("now", JsBuiltinFunction::DateNow)]);
+        global_object.set_member(String::from("Date"), date_address);
+
+        let (regexp_address, regexp_prototype_address) = self.add_builtin_constructor("RegExp", JsBuiltinFunction::RegExpConstructor, vec![
+            ("test", JsBuiltinFunction::RegExpTest),
+            ("exec", JsBuiltinFunction::RegExpExec),
+        ], Vec::new());
+        self.regexp_prototype_address = regexp_prototype_address;
+        global_object.set_member(String::from("RegExp"), regexp_address);
+
+        let (promise_address, promise_prototype_address) = self.add_builtin_constructor("Promise", JsBuiltinFunction::PromiseConstructor, vec![
+            ("then", JsBuiltinFunction::PromiseThen),
+            ("catch", JsBuiltinFunction::PromiseCatch),
+        ], vec![("resolve", JsBuiltinFunction::PromiseStaticResolve), ("reject", JsBuiltinFunction::PromiseStaticReject)]);
+        self.promise_prototype_address = promise_prototype_address;
+        global_object.set_member(String::from("Promise"), promise_address);
+
+        //the prototypes of the subclasses have the prototype of Error as their prototype, so every error is an instance of Error
+        let (error_address, error_prototype_address) = self.add_error_constructor(JsError::Error, None);
+        global_object.set_member(String::from("Error"), error_address);
+        for error in [JsError::EvalError, JsError::RangeError, JsError::ReferenceError, JsError::SyntaxError, JsError::TypeError, JsError::URIError] {
+            let (error_subclass_address, _) = self.add_error_constructor(error, Some(error_prototype_address));
+            global_object.set_member(String::from(error.name()), error_subclass_address);
+        }
+
+        let mut element_prototype = self.make_builtin_object(vec![
+            ("querySelector", JsBuiltinFunction::ElementQuerySelector),
+            ("querySelectorAll", JsBuiltinFunction::ElementQuerySelectorAll),
+            ("appendChild", JsBuiltinFunction::ElementAppendChild),
+            ("insertBefore", JsBuiltinFunction::ElementInsertBefore),
+            ("removeChild", JsBuiltinFunction::ElementRemoveChild),
+            ("getAttribute", JsBuiltinFunction::ElementGetAttribute),
+            ("setAttribute", JsBuiltinFunction::ElementSetAttribute),
+            ("removeAttribute", JsBuiltinFunction::ElementRemoveAttribute),
+            ("hasAttribute", JsBuiltinFunction::ElementHasAttribute),
+            ("getBoundingClientRect", JsBuiltinFunction::ElementGetBoundingClientRect),
+            ("addEventListener", JsBuiltinFunction::AddEventListener),
+            ("removeEventListener", JsBuiltinFunction::RemoveEventListener),
+        ]);
+        add_builtin_accessors(&mut element_prototype, vec![
+            ("tagName", JsBuiltinFunction::ElementGetTagName, None),
+            ("id", JsBuiltinFunction::ElementGetId, Some(JsBuiltinFunction::ElementSetId)),
+            ("className", JsBuiltinFunction::ElementGetClassName, Some(JsBuiltinFunction::ElementSetClassName)),
+            ("classList", JsBuiltinFunction::ElementGetClassList, None),
+            ("style", JsBuiltinFunction::ElementGetStyle, None),
+            ("innerHTML", JsBuiltinFunction::ElementGetInnerHtml, Some(JsBuiltinFunction::ElementSetInnerHtml)),
+            ("textContent", JsBuiltinFunction::ElementGetTextContent, Some(JsBuiltinFunction::ElementSetTextContent)),
+        ]);
+        self.element_prototype_address = self.add_new_value(JsValue::Object(element_prototype));
+
+        let class_list_prototype = self.make_builtin_object(vec![
+            ("add", JsBuiltinFunction::ClassListAdd),
+            ("remove", JsBuiltinFunction::ClassListRemove),
+            ("toggle", JsBuiltinFunction::ClassListToggle),
+            ("contains", JsBuiltinFunction::ClassListContains),
+        ]);
+        self.class_list_prototype_address = self.add_new_value(JsValue::Object(class_list_prototype));
+
+        let mut style_prototype = self.make_builtin_object(vec![
+            ("getPropertyValue", JsBuiltinFunction::StyleGetPropertyValue),
+            ("setProperty", JsBuiltinFunction::StyleSetProperty),
+            ("removeProperty", JsBuiltinFunction::StyleRemoveProperty),
+        ]);
+        add_builtin_accessors(&mut style_prototype, vec![("cssText", JsBuiltinFunction::StyleGetCssText, Some(JsBuiltinFunction::StyleSetCssText))]);
+        self.style_prototype_address = self.add_new_value(JsValue::Object(style_prototype));
+
+        let computed_style_prototype = self.make_builtin_object(vec![("getPropertyValue", JsBuiltinFunction::ComputedStyleGetPropertyValue)]);
+        self.computed_style_prototype_address = self.add_new_value(JsValue::Object(computed_style_prototype));
+
+        let document_address = self.add_builtin_object("document", vec![
+            ("getElementById", JsBuiltinFunction::DocumentGetElementById),
+            ("querySelector", JsBuiltinFunction::DocumentQuerySelector),
+            ("querySelectorAll", JsBuiltinFunction::DocumentQuerySelectorAll),
+            ("createElement", JsBuiltinFunction::DocumentCreateElement),
+            ("createTextNode", JsBuiltinFunction::DocumentCreateTextNode),
+            ("addEventListener", JsBuiltinFunction::AddEventListener),
+            ("removeEventListener", JsBuiltinFunction::RemoveEventListener),
+        ]);
+        if let Some(JsValue::Object(document_object)) = self.get_value_mut(document_address) {
+            add_builtin_accessors(document_object, vec![("readyState", JsBuiltinFunction::DocumentGetReadyState, None)]);
+        }
+        global_object.set_member(String::from("document"), document_address);
+        self.document_object_address = document_address;
+
+        let mut event_prototype = self.make_builtin_object(vec![
+            ("preventDefault", JsBuiltinFunction::EventPreventDefault),
+            ("stopPropagation", JsBuiltinFunction::EventStopPropagation),
+            ("stopImmediatePropagation", JsBuiltinFunction::EventStopImmediatePropagation),
+        ]);
+        add_builtin_accessors(&mut event_prototype, vec![
+            ("type", JsBuiltinFunction::EventGetType, None),
+            ("target", JsBuiltinFunction::EventGetTarget, None),
+            ("currentTarget", JsBuiltinFunction::EventGetCurrentTarget, None),
+            ("eventPhase", JsBuiltinFunction::EventGetEventPhase, None),
+            ("bubbles", JsBuiltinFunction::EventGetBubbles, None),
+            ("cancelable", JsBuiltinFunction::EventGetCancelable, None),
+            ("defaultPrevented", JsBuiltinFunction::EventGetDefaultPrevented, None),
+        ]);
+        self.event_prototype_address = self.add_new_value(JsValue::Object(event_prototype));
+
+        let (websocket_address, websocket_prototype_address) = self.add_builtin_constructor("WebSocket", JsBuiltinFunction::WebSocketConstructor, vec![
+            ("send", JsBuiltinFunction::WebSocketSend),
+            ("close", JsBuiltinFunction::WebSocketClose),
+            ("addEventListener", JsBuiltinFunction::AddEventListener),
+            ("removeEventListener", JsBuiltinFunction::RemoveEventListener),
+        ], Vec::new());
+        let ready_states = [
+            ("CONNECTING", READY_STATE_CONNECTING),
+            ("OPEN", READY_STATE_OPEN),
+            ("CLOSING", READY_STATE_CLOSING),
+            ("CLOSED", READY_STATE_CLOSED),
+        ];
+        let ready_state_addresses: Vec<(&str, JsAddress)> = ready_states.iter().map(|(name, ready_state)| {
+            (*name, self.add_new_value(JsValue::Number(*ready_state as f64)))
+        }).collect();
+        if let Some(JsValue::Object(websocket_prototype)) = self.get_value_mut(websocket_prototype_address) {
+            add_builtin_accessors(websocket_prototype, vec![
+                ("readyState", JsBuiltinFunction::WebSocketGetReadyState, None),
+                ("url", JsBuiltinFunction::WebSocketGetUrl, None),
+            ]);
+            for (name, address) in ready_state_addresses.iter() {
+                websocket_prototype.set_member(String::from(*name), *address);
+            }
+        }
+        if let Some(JsValue::Function(websocket_constructor)) = self.get_value_mut(websocket_address) {
+            for (name, address) in ready_state_addresses.iter() {
+                websocket_constructor.properties.set_member(String::from(*name), *address);
+            }
+        }
+        global_object.set_member(String::from("WebSocket"), websocket_address);
+
+        //TODO: scripts should be able to use these without "window." as well, but global variables are not backed by the global object yet
+        add_builtin_accessors(&mut global_object, vec![
+            ("innerWidth", JsBuiltinFunction::WindowGetInnerWidth, None),
+            ("innerHeight", JsBuiltinFunction::WindowGetInnerHeight, None),
+            ("devicePixelRatio", JsBuiltinFunction::WindowGetDevicePixelRatio, None),
+        ]);
+
+        let navigator_address = self.add_builtin_object("navigator", Vec::new());
+        let user_agent_address = self.add_new_value(JsValue::String(SETTINGS.lock().unwrap().user_agent.clone()));
+        if let Some(JsValue::Object(navigator_object)) = self.get_value_mut(navigator_address) {
+            navigator_object.set_member(String::from("userAgent"), user_agent_address);
+        }
+        global_object.set_member(String::from("navigator"), navigator_address);
+
+        let screen_address = self.add_builtin_object("screen", Vec::new());
+        if let Some(JsValue::Object(screen_object)) = self.get_value_mut(screen_address) {
+            add_builtin_accessors(screen_object, vec![
+                ("width", JsBuiltinFunction::ScreenGetWidth, None),
+                ("height", JsBuiltinFunction::ScreenGetHeight, None),
+            ]);
+        }
+        global_object.set_member(String::from("screen"), screen_address);
+
+        let global_functions = [
+            ("parseInt", JsBuiltinFunction::ParseInt),
+            ("parseFloat", JsBuiltinFunction::ParseFloat),
+            ("isNaN", JsBuiltinFunction::IsNaN),
+            ("setTimeout", JsBuiltinFunction::SetTimeout),
+            ("setInterval", JsBuiltinFunction::SetInterval),
+            ("clearTimeout", JsBuiltinFunction::ClearTimer),
+            ("clearInterval", JsBuiltinFunction::ClearTimer),
+            ("addEventListener", JsBuiltinFunction::AddEventListener),
+            ("removeEventListener", JsBuiltinFunction::RemoveEventListener),
+            ("getComputedStyle", JsBuiltinFunction::GetComputedStyle),
+            ("alert", JsBuiltinFunction::WindowAlert),
+            ("confirm", JsBuiltinFunction::WindowConfirm),
+            ("prompt", JsBuiltinFunction::WindowPrompt),
+        ];
+        for (name, builtin) in global_functions {
+            let function_address = self.add_new_value(JsValue::Function(JsFunction::new_builtin(builtin)));
+            self.global_environment.borrow_mut().declare_variable(String::from(name), function_address);
+            global_object.set_member(String::from(name), function_address);
+        }
+
+        #[cfg(test)] {
+            let tester_address = self.add_builtin_object("tester", vec![("export", JsBuiltinFunction::TesterExport)]);
+            global_object.set_member(String::from("tester"), tester_address);
+        }
+
+        let read_only_globals = [("undefined", JsValue::Undefined), ("NaN", JsValue::Number(f64::NAN)), ("Infinity", JsValue::Number(f64::INFINITY))];
+        for (name, value) in read_only_globals {
+            let value_address = self.add_new_value(value);
+            self.global_environment.borrow_mut().declare_read_only_variable(String::from(name), value_address);
+            global_object.set_member(String::from(name), value_address);
+        }
+
+        self.global_object_address = self.add_new_value(JsValue::Object(global_object));
+        for name in ["window", "globalThis", "self"] {
+            self.global_environment.borrow_mut().declare_variable(String::from(name), self.global_object_address);
+        }
     }
 
-    pub fn run_scripts_in_document(&mut self, document: &RefCell<Document>) {
+    fn add_builtin_object(&mut self, name: &str, functions: Vec<(&str, JsBuiltinFunction)>) -> JsAddress {
+        let builtin_object = self.make_builtin_object(functions);
+        let object_address = self.add_new_value(JsValue::Object(builtin_object));
+        self.global_environment.borrow_mut().declare_variable(String::from(name), object_address);
+        return object_address;
+    }
+
+    fn add_builtin_constructor(&mut self, name: &str, constructor: JsBuiltinFunction, methods: Vec<(&str, JsBuiltinFunction)>,
+                               static_functions: Vec<(&str, JsBuiltinFunction)>) -> (JsAddress, JsAddress) {
+        //the methods are on the prototype (where the objects made by the constructor find them), the static functions on the constructor itself
+        let prototype = self.make_builtin_object(methods);
+        let prototype_address = self.add_new_value(JsValue::Object(prototype));
+
+        let mut constructor_function = JsFunction::new_builtin(constructor);
+        constructor_function.prototype = Some(prototype_address);
+        constructor_function.properties = self.make_builtin_object(static_functions);
+        let constructor_address = self.add_new_value(JsValue::Function(constructor_function));
+
+        if let Some(JsValue::Object(prototype)) = self.get_value_mut(prototype_address) {
+            prototype.set_member(String::from("constructor"), constructor_address);
+        }
+        self.global_environment.borrow_mut().declare_variable(String::from(name), constructor_address);
+        return (constructor_address, prototype_address);
+    }
+
+    fn add_error_constructor(&mut self, error: JsError, parent_prototype: Option<JsAddress>) -> (JsAddress, JsAddress) {
+        //the name is on the prototype, and defaults to an empty message there, the errors themselves only have their message
+        let (constructor_address, prototype_address) = self.add_builtin_constructor(error.name(), JsBuiltinFunction::ErrorConstructor(error), Vec::new(), Vec::new());
+        let name_address = self.add_new_value(JsValue::String(String::from(error.name())));
+        let message_address = self.add_new_value(JsValue::String(String::new()));
+
+        if let Some(JsValue::Object(prototype)) = self.get_value_mut(prototype_address) {
+            prototype.set_prototype(parent_prototype);
+            prototype.set_member(String::from("name"), name_address);
+            prototype.set_member(String::from("message"), message_address);
+        }
+        self.error_prototype_addresses.insert(error, prototype_address);
+        return (constructor_address, prototype_address);
+    }
+
+    fn make_builtin_object(&mut self, functions: Vec<(&str, JsBuiltinFunction)>) -> JsObject {
+        let mut builtin_object = JsObject::new();
+
+        for (function_name, builtin) in functions {
+            let function_address = self.add_new_value(JsValue::Function(JsFunction::new_builtin(builtin)));
+            builtin_object.set_member(String::from(function_name), function_address);
+        }
+        return builtin_object;
+    }
+
+    pub fn add_new_value(&mut self, value: JsValue) -> JsAddress {
+        let new_address = get_next_js_value_address();
+        self.values.insert(new_address, value);
+        return new_address;
+    }
+
+    pub fn store_value(&mut self, value: JsValue) -> JsAddress {
+        //a value that is already stored somewhere is not copied, so variables and members can refer to the same object
+        if let JsValue::Address(address) = value {
+            return address;
+        }
+        return self.add_new_value(value);
+    }
+
+    pub fn get_value(&self, address: JsAddress) -> Option<&JsValue> {
+        return self.values.get(&address);
+    }
+
+    pub fn get_value_mut(&mut self, address: JsAddress) -> Option<&mut JsValue> {
+        return self.values.get_mut(&address);
+    }
+
+    pub fn current_environment(&self) -> Rc<RefCell<JsEnvironment>> {
+        return self.context_stack.iter().last().unwrap().environment.clone();
+    }
+
+    pub fn current_var_environment(&self) -> Rc<RefCell<JsEnvironment>> {
+        return self.context_stack.iter().last().unwrap().var_environment.clone();
+    }
+
+    pub fn declare_variable(&mut self, name: String, value: JsValue) {
+        //declares the variable in the current environment, which is the block that is running, or the function when not in a block
+        let address = self.store_value(value);
+        self.current_environment().borrow_mut().declare_variable(name, address);
+    }
+
+    pub fn enter_block_environment(&mut self) -> Rc<RefCell<JsEnvironment>> {
+        //returns the environment we were in, which should be restored with leave_block_environment()
+        let outer_environment = self.current_environment();
+        self.context_stack.last_mut().unwrap().environment = JsEnvironment::new(Some(outer_environment.clone()));
+        return outer_environment;
+    }
+
+    pub fn leave_block_environment(&mut self, outer_environment: Rc<RefCell<JsEnvironment>>) {
+        self.context_stack.last_mut().unwrap().environment = outer_environment;
+    }
+
+    pub fn set_current_environment(&mut self, environment: Rc<RefCell<JsEnvironment>>) {
+        self.context_stack.last_mut().unwrap().environment = environment;
+    }
+
+    pub fn run_block(&mut self, script: &Script) -> JsCompletion {
+        //a block has its own environment, for the let and const variables and the functions declared in it
+        let outer_environment = self.enter_block_environment();
+        hoist_lexical_declarations(self, script);
+        let completion = self.run_script_with_context_stack(script);
+        self.leave_block_environment(outer_environment);
+        return completion;
+    }
+
+    #[cfg(test)] pub fn run_scripts_in_document(&mut self, document: &Rc<RefCell<Document>>) {
+        self.queue_scripts_in_document(document);
+        self.run_queued_scripts(Duration::MAX);
+    }
+
+    pub fn queue_scripts_in_document(&mut self, document: &Rc<RefCell<Document>>) {
+        self.document = Some(document.clone());
+        if !SETTINGS.lock().unwrap().javascript_enabled {
+            return; //without scripts, the document still gets its events, there is just nothing listening to them
+        }
+
         let mut all_scripts = Vec::new();
-        self.collect_all_scripts_for_node(&document.borrow().document_node.borrow(), &mut all_scripts);
+        let document_node = document.borrow().document_node;
+        self.collect_all_scripts_for_node(&document.borrow(), document_node, &mut all_scripts);
+
+        //TODO: we have collected the id of the node the script is on as well, check if we need that (for scripts that modify the dom)
+        self.queued_scripts.extend(all_scripts.into_iter().map(|(_, script)| script));
+    }
 
-        for script in all_scripts {
-            //TODO: we have collected the internal id of the node the script is on as well, check if we need that (for scripts that modify the dom)
+    pub fn run_queued_scripts(&mut self, budget: Duration) -> bool {
+        //runs queued scripts until the budget is used up, returns true when all scripts have run
+        //TODO: we can't pause a script halfway, so a single slow script still goes over the budget (until it hits the time limit)
+        let start_instant = Instant::now();
+        while !self.queued_scripts.is_empty() {
+            let script = self.queued_scripts.pop_front().unwrap();
+            self.run_script(&script);
 
-            self.run_script(&script.1)
+            if start_instant.elapsed() >= budget {
+                break;
+            }
+        }
+        return self.queued_scripts.is_empty();
+    }
+
+    pub fn make_array(&mut self, values: Vec<JsValue>) -> JsValue {
+        //TODO: arrays should get Array.prototype (with push, map etc.)
+        let mut array = JsObject::new();
+        array.set_internal_slot(JsInternalSlot::Array(values.len()));
+
+        for (idx, value) in values.into_iter().enumerate() {
+            let address = self.store_value(value);
+            array.set_member(idx.to_string(), address);
         }
+        return JsValue::Address(self.add_new_value(JsValue::Object(array)));
+    }
 
+    pub fn make_error(&mut self, error: JsError, message: &str) -> JsValue {
+        //these are the same objects that the constructors make, so scripts can check them with instanceof
+        let mut error_object = match self.error_prototype_addresses.get(&error) {
+            Some(prototype_address) => JsObject::new_with_prototype(Some(*prototype_address)),
+            None => {
+                //the DOM exceptions (like InvalidStateError) don't have a constructor (yet), they are errors with a name of their own
+                let mut error_object = JsObject::new_with_prototype(self.error_prototype_addresses.get(&JsError::Error).copied());
+                let name_address = self.add_new_value(JsValue::String(String::from(error.name())));
+                error_object.set_member(String::from("name"), name_address);
+                error_object
+            }
+        };
+        let message_address = self.add_new_value(JsValue::String(String::from(message)));
+        error_object.set_member(String::from("message"), message_address);
+
+        return JsValue::Object(error_object);
     }
 
-    pub fn register_return_value(&mut self, return_value: JsValue) {
-        self.return_value = Some(return_value);
+    pub fn throw_error(&mut self, error: JsError, message: &str) {
+        let error_value = self.make_error(error, message);
+        self.throw_value(error_value);
     }
 
-    pub fn set_error(&mut self, error: JsError) {
-        self.current_error = Some(error);
+    pub fn throw_value(&mut self, value: JsValue) {
+        //when an exception is already pending, that one was thrown first, and is the one that should be propagated
+        if self.pending_exception.is_none() {
+            self.pending_exception = Some(value);
+        }
+    }
+
+    pub fn has_pending_exception(&self) -> bool {
+        return self.pending_exception.is_some();
+    }
+
+    pub fn take_pending_exception(&mut self) -> Option<JsValue> {
+        return self.pending_exception.take();
     }
 
     pub fn run_script(&mut self, script: &Script) {
         debug_assert!(self.context_stack.len() == 0);
 
-        let global_context = JsExecutionContext::new();
-        self.context_stack.push(global_context);
+        let started_time_limit = self.start_time_limit();
+        self.context_stack.push(self.make_global_context());
+        hoist_var_declarations(self, script);
+        hoist_lexical_declarations(self, script);
 
-        self.run_script_with_context_stack(script);
+        let completion = self.run_script_with_context_stack(script);
+        if let JsCompletion::Throw(exception) = completion {
+            self.report_uncaught_exception(exception);
+        }
+        self.run_microtasks();
 
         self.context_stack.clear();
+        self.end_time_limit(started_time_limit);
+    }
+
+    fn start_time_limit(&mut self) -> bool {
+        //returns whether the time limit was started here, callbacks that run while a script is running (like for an event the script fired)
+        //count towards the limit of that script
+        if self.time_limit_end.is_some() {
+            return false;
+        }
+        self.time_limit_end = Some(Instant::now() + self.script_time_limit);
+        return true;
+    }
+
+    fn end_time_limit(&mut self, started_time_limit: bool) {
+        if !started_time_limit {
+            return;
+        }
+        if self.interrupted && !self.navigation_interrupted {
+            self.log_error(format!("a script was stopped because it ran longer than {} seconds", self.script_time_limit.as_secs_f32()).as_str());
+        }
+        self.time_limit_end = None;
+        self.interrupted = false;
+    }
+
+    pub fn pause_time_limit(&mut self, paused_duration: Duration) {
+        //time the script spends waiting (like for the user to close a dialog) does not count towards its time limit
+        if self.time_limit_end.is_some() {
+            self.time_limit_end = Some(self.time_limit_end.unwrap() + paused_duration);
+        }
+    }
+
+    pub fn time_limit_exceeded(&mut self) -> bool {
+        if !self.interrupted && self.time_limit_end.is_some() && Instant::now() > self.time_limit_end.unwrap() {
+            self.interrupted = true;
+        }
+        return self.interrupted;
+    }
+
+    fn interrupted_by_navigation(&mut self) -> bool {
+        //this stops the script the same way as going over the time limit does, but without reporting it as an error
+        if self.navigation_interrupted {
+            self.interrupted = true;
+        }
+        return self.navigation_interrupted;
+    }
+
+    fn make_global_context(&self) -> JsExecutionContext {
+        return JsExecutionContext { environment: self.global_environment.clone(), var_environment: self.global_environment.clone(),
+                                    this_value: JsValue::Address(self.global_object_address) };
+    }
+
+    fn report_uncaught_exception(&mut self, exception: JsValue) {
+        if self.interrupted {
+            return; //the script did not throw itself, and we report that it was stopped when it is done
+        }
+        //TODO: eventually we want to show the stack etc. here as well
+        let description = self.describe_exception(exception);
+        self.log_error(format!("Uncaught {}", description).as_str());
+    }
+
+    pub fn log_error(&mut self, message: &str) {
+        //TODO: syntax errors are found while parsing, when there is no interpreter yet, so they are not counted
+        self.nr_of_errors += 1;
+        js_console::log_js_error(message);
+    }
+
+    pub fn add_timer(&mut self, callback: JsFunction, arguments: Vec<JsValue>, delay: Duration, repeats: bool) -> u32 {
+        let id = self.next_timer_id;
+        self.next_timer_id += 1;
+
+        let repeat_interval = if repeats { Some(delay) } else { None };
+        self.timers.push(JsTimer { id, due: Instant::now() + delay, callback, arguments, repeat_interval });
+        return id;
+    }
+
+    pub fn remove_timer(&mut self, id: u32) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    pub fn run_expired_timers(&mut self, now: Instant) {
+        //this is called from the main loop every frame, and runs the callbacks of the timers that are due, in the order they are due
+        let mut expired_timers: Vec<(Instant, u32)> = self.timers.iter().filter(|timer| timer.due <= now).map(|timer| (timer.due, timer.id)).collect();
+        expired_timers.sort();
+
+        //timers added by the callbacks only run on a later call (even with a delay of 0), so an interval can't keep us here forever
+        for (_, timer_id) in expired_timers {
+            //a callback that ran before this one could have cleared this timer
+            let timer_idx = self.timers.iter().position(|timer| timer.id == timer_id);
+            if timer_idx.is_none() {
+                continue;
+            }
+            let timer_idx = timer_idx.unwrap();
+
+            let (callback, arguments) = (self.timers[timer_idx].callback.clone(), self.timers[timer_idx].arguments.clone());
+            match self.timers[timer_idx].repeat_interval {
+                Some(interval) => { self.timers[timer_idx].due = now + interval; },
+                None => { self.timers.remove(timer_idx); },
+            }
+
+            self.run_callback(callback, JsValue::Undefined, arguments);
+        }
+    }
+
+    pub fn wait_for_next_timer(&mut self) -> bool {
+        //sleeps until the first timer is due and runs it, returns false when there are no timers to wait for
+        //this is only for running pages without a window (like the web platform tests), never while a script runs, the browser itself runs
+        //the timers from its main loop
+        debug_assert!(self.time_limit_end.is_none());
+        let next_due = self.timers.iter().map(|timer| timer.due).min();
+        if next_due.is_none() {
+            return false;
+        }
+        let next_due = next_due.unwrap();
+
+        let now = Instant::now();
+        if next_due > now {
+            std::thread::sleep(next_due - now);
+        }
+        self.run_expired_timers(Instant::now().max(next_due));
+        return true;
     }
 
-    pub fn run_script_with_context_stack(&mut self, script: &Script) {
+    pub fn run_callback(&mut self, callback: JsFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+        //runs a function from outside of a script (like from a timer), at the top level, like a script itself
+        //(this can happen while a script is still running, like for an event the script fired, so we keep what is on the stack)
+        let started_time_limit = self.start_time_limit();
+        self.context_stack.push(self.make_global_context());
+
+        let mut return_value = call_function(self, callback, this_value, arguments);
+        if let Some(exception) = self.take_pending_exception() {
+            self.report_uncaught_exception(exception);
+            return_value = JsValue::Undefined;
+        }
+        self.run_microtasks();
+
+        self.context_stack.pop();
+        self.end_time_limit(started_time_limit);
+        return return_value;
+    }
+
+    pub fn run_websocket_events(&mut self) {
+        //this is called from the main loop every frame, like the timers, and runs the handlers for what happened on the websockets
+        js_websocket::deliver_websocket_events(self);
+    }
+
+    pub fn dispatch_event(&mut self, target: DomNodeId, event: DomEvent) -> bool {
+        //returns false when a listener cancelled the event, so the default action (like following a link) should not happen
+        return js_events::dispatch_event(self, target, event);
+    }
+
+    pub fn dispatch_window_event(&mut self, event: DomEvent) -> bool {
+        return js_events::dispatch_window_event(self, event);
+    }
+
+    pub fn flush_layout(&self) {
+        //scripts that measure elements need to see the changes they made to the DOM before, so we lay out the page again when it changed
+        if self.layout_context.is_none() || self.document.is_none() {
+            return;
+        }
+        let layout_context = self.layout_context.as_ref().unwrap();
+        self.document.as_ref().unwrap().borrow_mut().post_construct_new_nodes(&layout_context.font_context);
+        let mut document = self.document.as_ref().unwrap().borrow_mut();
+
+        //TODO: this does not load new images, so until the main loop has loaded them, they are laid out without their image
+        let has_dirty_nodes = document.nodes.iter().any(|(node_id, node)| node.dirty && document.is_in_tree(node_id));
+        if has_dirty_nodes {
+            update_dirty_layout(&layout_context.full_layout, &mut document, &layout_context.font_context, layout_context.current_scroll_y);
+        }
+    }
+
+    pub fn enqueue_microtask(&mut self, microtask: JsMicrotask) {
+        self.microtasks.push_back(microtask);
+    }
+
+    pub fn run_next_microtask(&mut self) -> bool {
+        let microtask = self.microtasks.pop_front();
+        if microtask.is_none() {
+            return false;
+        }
+        js_promise::run_microtask(self, microtask.unwrap());
+        return true;
+    }
+
+    fn run_microtasks(&mut self) {
+        //microtasks can add new microtasks, those also run before we return
+        while !self.time_limit_exceeded() && self.run_next_microtask() {}
+
+        if self.interrupted {
+            //what the stopped script left to do is dropped, including reporting its rejected promises
+            self.microtasks.clear();
+            self.possibly_unhandled_rejections.clear();
+            return;
+        }
+
+        for promise in std::mem::take(&mut self.possibly_unhandled_rejections) {
+            let reason = js_promise::unhandled_rejection_reason(self, promise);
+            if reason.is_some() {
+                let description = self.describe_exception(reason.unwrap());
+                self.log_error(format!("Uncaught (in promise) {}", description).as_str());
+            }
+        }
+    }
+
+    pub fn add_possibly_unhandled_rejection(&mut self, promise: JsAddress) {
+        self.possibly_unhandled_rejections.push(promise);
+    }
+
+    pub fn run_script_with_context_stack(&mut self, script: &Script) -> JsCompletion {
+        //this runs for every block, function call and loop iteration, so it is where we stop scripts that take too long, or whose page we leave
+        //(the stop is thrown like an exception, that all code the script still tries to run throws again, so it can't be caught)
+        if self.time_limit_exceeded() || self.interrupted_by_navigation() {
+            return JsCompletion::Throw(JsValue::String(String::from(INTERRUPTED_SCRIPT_MESSAGE)));
+        }
+
         for statement in script {
-            let run_next_statement = statement.execute(self);
-
-            if !run_next_statement {
-                if self.context_stack.len() == 0 {
-                    todo!() //TODO: report some error, there is nothing to return to...
-                } else {
-                    return;
-                }
+            let completion = statement.execute(self);
+
+            if !matches!(completion, JsCompletion::Normal) {
+                return completion;
+            }
+        }
+        return JsCompletion::Normal;
+    }
+
+    fn find_member<'a>(&'a self, object: &'a JsObject, name: &String) -> Option<&'a JsAddress> {
+        //looks in the prototype chain as well, errors for example have their name on their prototype
+        let mut current_object = object;
+        loop {
+            let member = current_object.get_member(name);
+            if member.is_some() {
+                return member;
             }
+            match current_object.prototype().map(|prototype| self.get_value(prototype)) {
+                Some(Some(JsValue::Object(prototype))) => { current_object = prototype; },
+                _ => { return None; },
+            }
+        }
+    }
 
+    fn describe_exception(&self, exception: JsValue) -> String {
+        let exception = exception.deref(self);
+        if let JsValue::Object(ref object) = exception {
+            let name = self.find_member(object, &String::from("name"));
+            let message = self.find_member(object, &String::from("message"));
+            if name.is_some() && message.is_some() {
+                let name = JsValue::Address(*name.unwrap()).deref(self).to_js_string();
+                let message = JsValue::Address(*message.unwrap()).deref(self).to_js_string();
+                return format!("{}: {}", name, message);
+            }
         }
+        return exception.to_js_string();
     }
 
-    fn collect_all_scripts_for_node(&mut self, dom_node: &ElementDomNode, all_scripts: &mut Vec<(usize, Rc<Script>)>) {
+    fn collect_all_scripts_for_node(&mut self, document: &Document, dom_node_id: DomNodeId, all_scripts: &mut Vec<(DomNodeId, Rc<Script>)>) {
+        let dom_node = &document.nodes[dom_node_id];
 
         if dom_node.scripts.is_some() {
             for script in dom_node.scripts.as_ref().unwrap() {
-                all_scripts.push( (dom_node.internal_id, script.clone()) )
+                all_scripts.push( (dom_node_id, script.clone()) )
             }
         }
 
         if dom_node.children.is_some() {
-            for child in dom_node.children.as_ref().unwrap() {
-                self.collect_all_scripts_for_node(&child.borrow(), all_scripts);
+            for child_id in dom_node.children.as_ref().unwrap() {
+                self.collect_all_scripts_for_node(document, *child_id, all_scripts);
             }
         }
 
     }
 
-    pub fn get_var_address(&self, name: &String) -> Option<&JsAddress> {
-        //TODO: for now we check just the last stack frame, but we need to walk them up until we find it...
+    pub fn lookup_variable(&self, name: &String) -> JsVariableLookup {
+        //we look in the environment of the running function first, and then in the environments it was defined in
+        return self.current_environment().borrow().lookup_variable(name);
+    }
+
+    pub fn add_wpt_reporter(&mut self) {
+        //the harness we run the web platform tests with reports the results of its tests with this function
+        let function_address = self.add_new_value(JsValue::Function(JsFunction::new_builtin(JsBuiltinFunction::WptReport)));
+        self.global_environment.borrow_mut().declare_variable(String::from("__wpt_report"), function_address);
+    }
 
-        let current_context = self.context_stack.iter().last().unwrap();
-        return current_context.get_var_address(name);
+    pub fn record_wpt_result(&mut self, id: usize, name: String, status: &str, message: String) {
+        while self.wpt_results.len() <= id {
+            self.wpt_results.push(SubtestResult { name: String::new(), status: SubtestStatus::Running, message: String::new() });
+        }
+        self.wpt_results[id] = SubtestResult { name, status: SubtestStatus::from_report(status), message };
     }
 
     #[cfg(test)] pub fn export_test_data(&mut self, data: JsValue) {
@@ -110,3 +883,12 @@ impl JsInterpreter {
         return &JsValue::Undefined;
     }
 }
+
+
+fn add_builtin_accessors(object: &mut JsObject, accessors: Vec<(&str, JsBuiltinFunction, Option<JsBuiltinFunction>)>) {
+    //properties without a setter are read-only, assigning to them does nothing
+    for (name, getter, setter) in accessors {
+        let accessor = JsAccessor { getter: Some(JsFunction::new_builtin(getter)), setter: setter.map(JsFunction::new_builtin) };
+        object.set_accessor(String::from(name), accessor);
+    }
+}
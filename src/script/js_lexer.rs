@@ -38,6 +38,8 @@ pub enum JsToken {
     CloseBracket,
     Plus,
     Minus,
+    PlusPlus,
+    MinusMinus,
     Star,
     ForwardSlash,
     Comma,
@@ -46,6 +48,17 @@ pub enum JsToken {
     And,
     Pipe,
     ExclamationMark,
+    DoubleEquals,
+    TripleEquals,
+    NotEquals,
+    NotDoubleEquals,
+    BiggerOrEqual,
+    SmallerOrEqual,
+    DoubleAnd,
+    DoublePipe,
+    DoubleQuestionMark,
+    QuestionMarkDot,
+    DotDotDot,
     Colon,
     QuestionMark,
     BitWiseOr,
@@ -57,14 +70,70 @@ pub enum JsToken {
 
     //all keywords:
     KeyWordVar,
+    KeyWordLet,
+    KeyWordConst,
     KeyWordFunction,
     KeyWordReturn,
-
-    //not an actual token of the language, but used as a way to block out:
-    None,
+    KeyWordIf,
+    KeyWordElse,
+    KeyWordFor,
+    KeyWordIn,
+    KeyWordWhile,
+    KeyWordDo,
+    KeyWordBreak,
+    KeyWordContinue,
+    KeyWordThrow,
+    KeyWordTry,
+    KeyWordCatch,
+    KeyWordFinally,
+    KeyWordThis,
+    KeyWordNew,
+    KeyWordInstanceof,
+    KeyWordTypeof,
+    KeyWordDelete,
+    KeyWordVoid,
+    KeyWordTrue,
+    KeyWordFalse,
+    KeyWordNull,
+}
+impl JsToken {
+    pub fn keyword_text(&self) -> Option<&'static str> {
+        return KEYWORDS.iter().find(|(_, keyword)| keyword == self).map(|(text, _)| *text);
+    }
 }
 
 
+//Note that "of" and "async" are not keywords, they only have a meaning in some places, and can be used as a normal identifier
+const KEYWORDS: [(&str, JsToken); 26] = [
+    ("var", JsToken::KeyWordVar),
+    ("let", JsToken::KeyWordLet),
+    ("const", JsToken::KeyWordConst),
+    ("function", JsToken::KeyWordFunction),
+    ("return", JsToken::KeyWordReturn),
+    ("if", JsToken::KeyWordIf),
+    ("else", JsToken::KeyWordElse),
+    ("for", JsToken::KeyWordFor),
+    ("in", JsToken::KeyWordIn),
+    ("while", JsToken::KeyWordWhile),
+    ("do", JsToken::KeyWordDo),
+    ("break", JsToken::KeyWordBreak),
+    ("continue", JsToken::KeyWordContinue),
+    ("throw", JsToken::KeyWordThrow),
+    ("try", JsToken::KeyWordTry),
+    ("catch", JsToken::KeyWordCatch),
+    ("finally", JsToken::KeyWordFinally),
+    ("this", JsToken::KeyWordThis),
+    ("new", JsToken::KeyWordNew),
+    ("instanceof", JsToken::KeyWordInstanceof),
+    ("typeof", JsToken::KeyWordTypeof),
+    ("delete", JsToken::KeyWordDelete),
+    ("void", JsToken::KeyWordVoid),
+    ("true", JsToken::KeyWordTrue),
+    ("false", JsToken::KeyWordFalse),
+    ("null", JsToken::KeyWordNull),
+];
+
+
 pub struct JsSourceIterator<'document> {
     //This is a trackingIterator wrapper that treats commented code as whitespace
     //    we do this so we can implement the comment logic in one place, without
@@ -162,8 +231,19 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
         if js_iterator.has_next() && js_iterator.peek().unwrap().is_numeric() {
             let mut number_text = String::new();
 
-            while js_iterator.has_next() && js_iterator.peek().unwrap().is_numeric() {
-                number_text.push(js_iterator.next());
+            //we read everything that can be part of a number here (including hex digits, a fraction and an exponent), and leave
+            //  checking whether it is a valid number to the parser
+            while js_iterator.has_next() {
+                let next_char = js_iterator.peek().unwrap();
+                let is_hex = number_text.starts_with("0x") || number_text.starts_with("0X");
+                let is_exponent_sign = (next_char == '+' || next_char == '-') && !is_hex && (number_text.ends_with('e') || number_text.ends_with('E'));
+                let is_fraction_dot = next_char == '.' && !is_hex && !number_text.contains('.') && !number_text.contains(['e', 'E']);
+
+                if next_char.is_alphanumeric() || next_char == '_' || is_exponent_sign || is_fraction_dot {
+                    number_text.push(js_iterator.next());
+                } else {
+                    break;
+                }
             }
 
             //TODO: using "make" below is not correct, because it will give the end position of the literal, instead of the start
@@ -204,7 +284,7 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
             //  https://stackoverflow.com/questions/5519596/when-parsing-javascript-what-determines-the-meaning-of-a-slash
 
             //TODO: put this in a better place where we don't need to instatiate it so often
-            const TOKENS_PROBABLY_PRECEDING_REGEX_LITERAL: [JsToken; 14] = [
+            const TOKENS_PROBABLY_PRECEDING_REGEX_LITERAL: [JsToken; 28] = [
                 JsToken::OpenParenthesis,
                 JsToken::Dot,
                 JsToken::OpenBracket,
@@ -219,7 +299,20 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
                 JsToken::Pipe,
                 JsToken::ExclamationMark,
                 JsToken::BitWiseOr,
-                //TODO: when we properly parse multi char operator tokens (like "&&" and "=="), we need to add them to this list
+                JsToken::DoubleEquals,
+                JsToken::TripleEquals,
+                JsToken::NotEquals,
+                JsToken::NotDoubleEquals,
+                JsToken::BiggerOrEqual,
+                JsToken::SmallerOrEqual,
+                JsToken::DoubleAnd,
+                JsToken::DoublePipe,
+                JsToken::QuestionMark,
+                JsToken::DoubleQuestionMark,
+                JsToken::Comma,
+                JsToken::Colon,
+                JsToken::OpenBrace,
+                JsToken::KeyWordReturn,
             ];
 
             let mut last_token = None;
@@ -237,14 +330,9 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
                 buffer.push(js_iterator.next());  // read the opening slash
 
                 let mut prev_was_escape_char = false;
+                let mut in_character_class = false; //a slash inside a character class (as in /[/]/) does not end the regex
                 'literal_regex_parse: while js_iterator.has_next() {
-                    if js_iterator.peek() == Some('\\') {
-                        prev_was_escape_char = true;
-                        js_iterator.next();
-                        continue;
-                    }
-
-                    if !prev_was_escape_char && js_iterator.peek() == Some('/') {
+                    if !prev_was_escape_char && !in_character_class && js_iterator.peek() == Some('/') {
                         buffer.push(js_iterator.next());  // read the closing slash
 
                         //TODO: put this in a better place where we don't need to instatiate it so often
@@ -259,9 +347,15 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
                             }
                         }
                     } else {
-                        buffer.push(js_iterator.next());
+                        let next_char = js_iterator.next();
+                        if !prev_was_escape_char && next_char == '[' {
+                            in_character_class = true;
+                        } else if !prev_was_escape_char && next_char == ']' {
+                            in_character_class = false;
+                        }
+                        prev_was_escape_char = !prev_was_escape_char && next_char == '\\';
+                        buffer.push(next_char);
                     }
-                    prev_was_escape_char = false;
                 }
 
                 //TODO: using "make" below is not correct, because it will give the end position of the literal, instead of the start
@@ -280,16 +374,13 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
                 identifier.push(js_iterator.next());
             }
 
+            let token = match KEYWORDS.iter().find(|(text, _)| *text == identifier) {
+                Some((_, keyword)) => keyword.clone(),
+                None => JsToken::Identifier(identifier),
+            };
+
             //TODO: using "make" below is not correct, because it will give the end position of the literal, instead of the start
-            if identifier == "var" {
-                tokens.push(JsTokenWithLocation::make(&js_iterator, JsToken::KeyWordVar));
-            } else if identifier == "function" {
-                tokens.push(JsTokenWithLocation::make(&js_iterator, JsToken::KeyWordFunction));
-            } else if identifier == "return" {
-                tokens.push(JsTokenWithLocation::make(&js_iterator, JsToken::KeyWordReturn));
-            } else {
-                tokens.push(JsTokenWithLocation::make(&js_iterator, JsToken::Identifier(identifier)));
-            }
+            tokens.push(JsTokenWithLocation::make(&js_iterator, token));
         }
         else {
             //from here we parse single chars as tokens, so any more complex tokens should have been handled before this point
@@ -305,20 +396,48 @@ pub fn lex_js(document: &str, starting_line: u32, starting_char_idx: u32) -> Vec
                         '{' => { JsToken::OpenBrace }
                         '}' => { JsToken::CloseBrace }
                         ',' => { JsToken::Comma }
-                        '.' => { JsToken::Dot }
+                        '.' => {
+                            //two dots can't be valid anyway, so we don't check for the third one
+                            if eat_char(&mut js_iterator, '.') && eat_char(&mut js_iterator, '.') { JsToken::DotDotDot } else { JsToken::Dot }
+                        }
                         ':' => { JsToken::Colon }
                         ';' => { JsToken::Semicolon }
-                        '>' => { JsToken::Bigger }
-                        '<' => { JsToken::Smaller }
-                        '!' => { JsToken::ExclamationMark }
-                        '?' => { JsToken::QuestionMark }
-                        '|' => { JsToken::Pipe }
-                        '&' => { JsToken::And }
+                        '>' => { if eat_char(&mut js_iterator, '=') { JsToken::BiggerOrEqual } else { JsToken::Bigger } }
+                        '<' => { if eat_char(&mut js_iterator, '=') { JsToken::SmallerOrEqual } else { JsToken::Smaller } }
+                        '!' => {
+                            if !eat_char(&mut js_iterator, '=') {
+                                JsToken::ExclamationMark
+                            } else if eat_char(&mut js_iterator, '=') {
+                                JsToken::NotDoubleEquals
+                            } else {
+                                JsToken::NotEquals
+                            }
+                        }
+                        '?' => {
+                            //TODO: "a?.5:b" is a conditional with a number, not optional chaining, but we don't lex numbers starting with a dot yet
+                            if eat_char(&mut js_iterator, '?') {
+                                JsToken::DoubleQuestionMark
+                            } else if eat_char(&mut js_iterator, '.') {
+                                JsToken::QuestionMarkDot
+                            } else {
+                                JsToken::QuestionMark
+                            }
+                        }
+                        '|' => { if eat_char(&mut js_iterator, '|') { JsToken::DoublePipe } else { JsToken::Pipe } }
+                        '&' => { if eat_char(&mut js_iterator, '&') { JsToken::DoubleAnd } else { JsToken::And } }
                         '^' => { JsToken::BitWiseOr }
                         '#' => { JsToken::Hash }
-                        '=' => { JsToken::Equals }
-                        '+' => { JsToken::Plus }
-                        '-' => { JsToken::Minus }
+                        '=' => {
+                            if !eat_char(&mut js_iterator, '=') {
+                                JsToken::Equals
+                            } else if eat_char(&mut js_iterator, '=') {
+                                JsToken::TripleEquals
+                            } else {
+                                JsToken::DoubleEquals
+                            }
+                        }
+                        '+' => { if eat_char(&mut js_iterator, '+') { JsToken::PlusPlus } else { JsToken::Plus } }
+                        '-' => { if eat_char(&mut js_iterator, '-') { JsToken::MinusMinus } else { JsToken::Minus } }
                         '*' => { JsToken::Star }
 
                         '\n' => { JsToken::Newline }
@@ -354,6 +473,16 @@ fn eat_whitespace(iterator: &mut JsSourceIterator) {
 }
 
 
+fn eat_char(iterator: &mut JsSourceIterator, char_to_eat: char) -> bool {
+    //consumes the next char if it is the given char, which we use to lex operators consisting of multiple chars
+    if iterator.peek() == Some(char_to_eat) {
+        iterator.next();
+        return true;
+    }
+    return false;
+}
+
+
 fn is_valid_identifier_char(c: char) -> bool {
     return c.is_alphanumeric() || c == '_' || c == '$';
 }
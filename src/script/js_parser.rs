@@ -3,786 +3,1315 @@ use std::rc::Rc;
 use super::js_ast::*;
 use super::js_console;
 use super::js_lexer::{JsToken, JsTokenWithLocation};
+use super::js_regex::JsRegex;
 
 
-#[derive(Debug)]
-struct JsParserSliceIterator {
+struct JsParser<'a> {
+    tokens: &'a Vec<JsTokenWithLocation>,
     next_idx: usize,
-    end_idx: usize,  //end is inclusive
+    loop_depth: usize, //break and continue are only valid inside a loop, within the same function
+    in_async_function: bool, //await is only an operator inside async functions, elsewhere it is a normal identifier
 }
-impl JsParserSliceIterator {
-    fn has_next(&self) -> bool {
-        return self.next_idx <= self.end_idx;
-    }
-    fn move_after_next_non_whitespace(&mut self, tokens: &Vec<JsTokenWithLocation>) -> bool {
-        let mut temp_next = self.next_idx;
-
-        loop {
-            if temp_next > self.end_idx { return false; }
-
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                _ => {
-                    if temp_next == self.end_idx {
-                        self.next_idx = self.end_idx;
-                    } else {
-                        self.next_idx = temp_next + 1; //we move one after the non-whitespace char (if we can)
-                    }
-                    return true;
-                }
+impl<'a> JsParser<'a> {
+    fn next_non_whitespace_idx(&self) -> Option<usize> {
+        let mut idx = self.next_idx;
+        while idx < self.tokens.len() {
+            match self.tokens[idx].token {
+                JsToken::Whitespace | JsToken::Newline => { idx += 1; },
+                _ => { return Some(idx); }
             }
-            temp_next += 1;
         }
+        return None;
     }
-    fn has_next_non_whitespace(&self, tokens: &Vec<JsTokenWithLocation>) -> bool {
-        let mut temp_next = self.next_idx;
-
-        loop {
-            if temp_next > self.end_idx { return false; }
-
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                _ => { return true; }
+    fn peek(&self) -> Option<&JsToken> {
+        return self.next_non_whitespace_idx().map(|idx| &self.tokens[idx].token);
+    }
+    fn peek_second(&self) -> Option<&JsToken> {
+        //the token after the one peek() returns
+        let first_idx = self.next_non_whitespace_idx();
+        if first_idx.is_none() {
+            return None;
+        }
+        let mut idx = first_idx.unwrap() + 1;
+        while idx < self.tokens.len() {
+            match self.tokens[idx].token {
+                JsToken::Whitespace | JsToken::Newline => { idx += 1; },
+                _ => { return Some(&self.tokens[idx].token); }
             }
-            temp_next += 1;
         }
+        return None;
     }
-    fn next_non_whitespace_token_is(&mut self, tokens: &Vec<JsTokenWithLocation>, token: JsToken) -> bool {
-        let mut temp_next = self.next_idx;
-
-        loop {
-            if temp_next > self.end_idx { return false; }
-
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                matching_token @ _ => {
-                    return *matching_token == token;
-                }
-            }
-            temp_next += 1;
+    fn next_is(&self, token: JsToken) -> bool {
+        return self.peek() == Some(&token);
+    }
+    fn next(&mut self) -> Option<JsToken> {
+        let idx = self.next_non_whitespace_idx();
+        if idx.is_none() {
+            self.next_idx = self.tokens.len();
+            return None;
         }
+        self.next_idx = idx.unwrap() + 1;
+        return Some(self.tokens[idx.unwrap()].token.clone());
     }
-    fn read_only_identifier(&mut self, tokens: &Vec<JsTokenWithLocation>) -> Option<String> {
-        //check if there is only an identifier left, and if so, return it, and consume the iterator
-
-        let mut temp_next = self.next_idx;
-        let mut name_to_return = None;
-        loop {
-            if temp_next > self.end_idx {
-                if name_to_return.is_some() {
-                    self.next_idx = self.end_idx;
-                }
-                return name_to_return;
+    fn newline_before_next(&self) -> bool {
+        let mut idx = self.next_idx;
+        while idx < self.tokens.len() {
+            match self.tokens[idx].token {
+                JsToken::Newline => { return true; },
+                JsToken::Whitespace => { idx += 1; },
+                _ => { return false; }
             }
-
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                JsToken::Identifier(name) => {
-                    if name_to_return.is_some() {
-                        return None;  //we saw more than 1 identifier
-                    }
-                    name_to_return = Some(name.clone());
-                }
-                _ => { return None }
-            }
-            temp_next += 1;
         }
+        return false;
     }
-    fn read_only_literal_regex(&mut self, tokens: &Vec<JsTokenWithLocation>) -> Option<String> {
-        //check if there is only a literal regex left, and if so, return it, and consume the iterator
-
-        let mut temp_next = self.next_idx;
-        let mut content_to_return = None;
-        loop {
-            if temp_next > self.end_idx {
-                if content_to_return.is_some() {
-                    self.next_idx = self.end_idx;
-                }
-                return content_to_return;
-            }
-
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                JsToken::RegexLiteral(content) => {
-                    if content_to_return.is_some() {
-                        return None;  //we saw more than 1 regex
-                    }
-                    content_to_return = Some(content.clone());
-                }
-                _ => { return None }
+    fn next_is_async_function(&self) -> bool {
+        //async is not a keyword, it only makes a function async when "function" follows it on the same line
+        let async_idx = self.next_non_whitespace_idx();
+        if async_idx.is_none() || !matches!(&self.tokens[async_idx.unwrap()].token, JsToken::Identifier(name) if name == "async") {
+            return false;
+        }
+        let mut idx = async_idx.unwrap() + 1;
+        while idx < self.tokens.len() {
+            match self.tokens[idx].token {
+                JsToken::Whitespace => { idx += 1; },
+                JsToken::KeyWordFunction => { return true; },
+                _ => { return false; }
             }
-            temp_next += 1;
         }
+        return false;
+    }
+    fn expect(&mut self, token: JsToken) -> bool {
+        if self.next_is(token) {
+            self.next();
+            return true;
+        }
+        self.log_error("unexpected token");
+        return false;
+    }
+    fn expect_identifier(&mut self) -> Option<String> {
+        if let Some(JsToken::Identifier(name)) = self.peek() {
+            let name = name.clone();
+            self.next();
+            return Some(name);
+        }
+        self.log_error("expected an identifier");
+        return None;
     }
-    fn read_only_literal_number(&mut self, tokens: &Vec<JsTokenWithLocation>) -> Option<String> {
-        //check if there is only an number left, and if so, return it, and consume the iterator
+    fn consume_end_of_statement(&mut self) -> bool {
+        //statements end with a semicolon, but it can be left out before a newline, a closing brace, or the end of the script
+        if self.next_is(JsToken::Semicolon) {
+            self.next();
+            return true;
+        }
+        if self.peek().is_none() || self.next_is(JsToken::CloseBrace) || self.newline_before_next() {
+            return true;
+        }
+        self.log_error("expected the end of the statement");
+        return false;
+    }
+    fn log_error(&self, message: &str) {
+        let idx = self.next_non_whitespace_idx().unwrap_or(self.tokens.len() - 1);
+        let line = self.tokens[idx].line;
+        let char = self.tokens[idx].character;
+        js_console::log_js_error(format!("syntax error: {message} at {line}::{char}").as_str());
+    }
+}
 
-        //TODO: parse fractionals here well, by consuming the dot, and return one string containing the full fractional number
 
-        let mut temp_next = self.next_idx;
-        let mut number_to_return = None;
-        loop {
-            if temp_next > self.end_idx {
-                if number_to_return.is_some() {
-                    self.next_idx = self.end_idx;
-                }
-                return number_to_return;
-            }
+pub fn parse_js(tokens: &Vec<JsTokenWithLocation>) -> Script {
+    if tokens.len() == 0 {
+        return Vec::new();
+    }
 
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                JsToken::Number(number) => {
-                    if number_to_return.is_some() {
-                        return None; // we saw more than 1 number
-                    }
-                    number_to_return = Some(number.clone());
+    let mut parser = JsParser { tokens, next_idx: 0, loop_depth: 0, in_async_function: false };
+    let mut statements = Vec::new();
 
-                }
-                _ => { return None }
-            }
-            temp_next += 1;
+    while parser.peek().is_some() {
+        if parser.next_is(JsToken::Semicolon) {
+            parser.next();
+            continue;
         }
+
+        let statement = parse_statement(&mut parser);
+        if statement.is_none() {
+            //like in other browsers, a script with a syntax error is not run at all
+            return Vec::new();
+        }
+        statements.push(statement.unwrap());
     }
-    fn read_only_literal_string(&mut self, tokens: &Vec<JsTokenWithLocation>) -> Option<String> {
-        //check if there is only an literal string left, and if so, return it, and consume the iterator
 
-        let mut temp_next = self.next_idx;
-        let mut string_to_return = None;
-        loop {
-            if temp_next > self.end_idx {
-                if string_to_return.is_some() {
-                    self.next_idx = self.end_idx;
-                }
-                return string_to_return;
-            }
+    return statements;
+}
 
-            match &tokens[temp_next].token {
-                JsToken::Whitespace | JsToken::Newline => { },
-                JsToken::LiteralString(number) => {
-                    if string_to_return.is_some() {
-                        return None; // we saw more than 1 literal string
-                    }
-                    string_to_return = Some(number.clone());
-                }
-                _ => { return None }
-            }
-            temp_next += 1;
-        }
 
+fn parse_statement(parser: &mut JsParser) -> Option<JsAstStatement> {
+    let next_token = parser.peek().cloned();
+    if next_token.is_none() {
+        parser.log_error("unexpected end of script");
+        return None;
     }
-    fn is_only_object_literal(&mut self, masked_tokens: &Vec<JsToken>) -> bool {
-        let mut temp_next = self.next_idx;
-        let mut in_object = false;
-        let mut seen_end_of_object = false;
 
-        loop {
-            if temp_next > self.end_idx {
-                if seen_end_of_object {
-                    return true;
-                }
-                return false;
+    match next_token.unwrap() {
+        JsToken::Semicolon => {
+            parser.next();
+            return Some(JsAstStatement::Block(Vec::new())); //the empty statement
+        },
+        JsToken::OpenBrace => {
+            let block = parse_block(parser);
+            if block.is_none() {
+                return None;
+            }
+            return Some(JsAstStatement::Block(block.unwrap()));
+        },
+        JsToken::KeyWordVar | JsToken::KeyWordLet | JsToken::KeyWordConst => {
+            let declaration = parse_declaration(parser);
+            if declaration.is_none() || !parser.consume_end_of_statement() {
+                return None;
+            }
+            return Some(JsAstStatement::Declaration(declaration.unwrap()));
+        },
+        JsToken::KeyWordFunction => {
+            let function_declaration = parse_function_declaration(parser, false);
+            if function_declaration.is_none() {
+                return None;
+            }
+            return Some(JsAstStatement::FunctionDeclaration(function_declaration.unwrap()));
+        },
+        JsToken::Identifier(_) if parser.next_is_async_function() => {
+            parser.next(); //consume "async"
+            let function_declaration = parse_function_declaration(parser, true);
+            if function_declaration.is_none() {
+                return None;
+            }
+            return Some(JsAstStatement::FunctionDeclaration(function_declaration.unwrap()));
+        },
+        JsToken::KeyWordReturn => {
+            parser.next();
+
+            //a newline directly after return ends the statement, so the expression on the next line is not returned
+            if parser.next_is(JsToken::Semicolon) || parser.next_is(JsToken::CloseBrace) || parser.peek().is_none() || parser.newline_before_next() {
+                parser.consume_end_of_statement();
+                return Some(JsAstStatement::Return(None));
             }
 
-            match &masked_tokens[temp_next] {
-                JsToken::Whitespace | JsToken::Newline => { },
-                JsToken::OpenBrace => {
-                    in_object = true;
-                }
-                JsToken::CloseBrace => {
-                    in_object = false;
-                    seen_end_of_object = true;
-                }
-                _ => {
-                    if !in_object || seen_end_of_object {
-                        return false;
-                    }
-                }
+            let expression = parse_expression(parser, 0);
+            if expression.is_none() || !parser.consume_end_of_statement() {
+                return None;
             }
-            temp_next += 1;
-        }
+            return Some(JsAstStatement::Return(Some(expression.unwrap())));
+        },
+        JsToken::KeyWordIf => { return parse_if(parser); },
+        JsToken::KeyWordWhile => { return parse_while(parser); },
+        JsToken::KeyWordDo => { return parse_do_while(parser); },
+        JsToken::KeyWordFor => { return parse_for(parser); },
+        JsToken::KeyWordTry => { return parse_try(parser); },
+        JsToken::KeyWordThrow => {
+            parser.next();
+
+            //unlike with return, a newline after throw is not allowed
+            if parser.newline_before_next() {
+                parser.log_error("no newline is allowed after throw");
+                return None;
+            }
+
+            let expression = parse_expression(parser, 0);
+            if expression.is_none() || !parser.consume_end_of_statement() {
+                return None;
+            }
+            return Some(JsAstStatement::Throw(expression.unwrap()));
+        },
+        JsToken::KeyWordBreak | JsToken::KeyWordContinue => {
+            let is_break = parser.next() == Some(JsToken::KeyWordBreak);
+            if parser.loop_depth == 0 {
+                parser.log_error("break and continue can only be used inside a loop");
+                return None;
+            }
+            //TODO: we don't support labels yet
+            if !parser.consume_end_of_statement() {
+                return None;
+            }
+            return Some(if is_break { JsAstStatement::Break } else { JsAstStatement::Continue });
+        },
+        _ => {
+            let statement = parse_expression_or_assignment(parser);
+            if statement.is_none() || !parser.consume_end_of_statement() {
+                return None;
+            }
+            return statement;
+        },
     }
-    fn is_only_function_call(&self, masked_tokens: &Vec<JsToken>) -> bool {
-        let mut temp_next = self.next_idx;
+}
 
-        let mut in_function_expression = true;
-        let mut in_arguments = false;
-        let mut seen_close_parentesis = false;
 
-        loop {
-            if temp_next > self.end_idx { return seen_close_parentesis; }
+fn parse_block(parser: &mut JsParser) -> Option<Script> {
+    if !parser.expect(JsToken::OpenBrace) {
+        return None;
+    }
 
-            if in_function_expression {
-                match &masked_tokens[temp_next] {
-                    JsToken::OpenParenthesis => {
-                        in_arguments = true;
-                        in_function_expression = false;
-                    },
-                    _ => { },
-                }
-            } else if in_arguments {
-                match &masked_tokens[temp_next] {
-                    JsToken::CloseParenthesis => {
-                        seen_close_parentesis = true;
-                        in_arguments = false;
-                    },
-                    _ => { },
-                }
-            } else if seen_close_parentesis {
-                return false;
-            }
+    let mut statements = Vec::new();
+    loop {
+        if parser.peek().is_none() {
+            parser.log_error("expected a closing brace");
+            return None;
+        }
+        if parser.next_is(JsToken::CloseBrace) {
+            parser.next();
+            return Some(statements);
+        }
+        if parser.next_is(JsToken::Semicolon) {
+            parser.next();
+            continue;
+        }
 
-            temp_next += 1;
+        let statement = parse_statement(parser);
+        if statement.is_none() {
+            return None;
         }
+        statements.push(statement.unwrap());
     }
-    fn split_and_advance_until_next_token(&mut self, tokens: &Vec<JsToken>, token_to_find: JsToken) -> Option<JsParserSliceIterator> {
-        let mut size = 1;
-        loop {
-            let potential_end_idx = self.next_idx + (size - 1);
-            if potential_end_idx > self.end_idx { return None; }
+}
 
-            let ending_token = &tokens[potential_end_idx];
-            if *ending_token == token_to_find {
-                let new_start_idx = self.next_idx;
-                self.next_idx += size;
 
-                return Some(JsParserSliceIterator {
-                    end_idx: potential_end_idx - 1, //we remove the token_to_find
-                    next_idx: new_start_idx,
-                });
-            }
+fn parse_loop_body(parser: &mut JsParser) -> Option<Script> {
+    parser.loop_depth += 1;
+    let body = parse_statement(parser);
+    parser.loop_depth -= 1;
 
-            size += 1;
-        }
+    if body.is_none() {
+        return None;
     }
-    fn check_for_and_split_on(&mut self, tokens: &Vec<JsTokenWithLocation>, token: JsToken) -> Option<(JsParserSliceIterator, JsParserSliceIterator)> {
-        // split this iterator in 2 new ones, starting from the current position of this iterator
+    return Some(vec![body.unwrap()]);
+}
 
-        let mut split_idx = self.next_idx;
-        loop {
-            if split_idx > self.end_idx { return None; }
 
-            if tokens[split_idx].token == token {
-                return self.split_at(split_idx);
-            }
-
-            split_idx += 1;
-        }
+fn parse_condition_in_parentheses(parser: &mut JsParser) -> Option<JsAstExpression> {
+    if !parser.expect(JsToken::OpenParenthesis) {
+        return None;
     }
-    fn build_iterator_between_tokens(&self, token_types: &Vec<JsToken>, open_token: JsToken, close_token: JsToken) -> Option<JsParserSliceIterator> {
-        let mut temp_idx = self.next_idx;
-        let mut first_idx = 0;
-        let mut first_seen = false;
+    let condition = parse_expression(parser, 0);
+    if condition.is_none() || !parser.expect(JsToken::CloseParenthesis) {
+        return None;
+    }
+    return condition;
+}
 
-        loop {
-            if temp_idx > self.end_idx { return None; }
 
-            if token_types[temp_idx] == open_token {
-                first_idx = temp_idx + 1;
-                first_seen = true;
-            }
-            if token_types[temp_idx] == close_token {
-                if !first_seen { return None }
-                return Some(JsParserSliceIterator { next_idx: first_idx, end_idx: temp_idx - 1} );
-            }
+fn parse_if(parser: &mut JsParser) -> Option<JsAstStatement> {
+    parser.next(); //consume the "if" keyword
 
-            temp_idx += 1;
-        }
-    }
-    fn find_last_token_idx(&self, tokens: &Vec<JsToken>, token_to_find: JsToken) -> Option<usize> {
-        for idx in (self.next_idx..(self.end_idx+1)).rev() {
-            if tokens[idx] == token_to_find {
-                return Some(idx);
-            }
-        }
+    let condition = parse_condition_in_parentheses(parser);
+    if condition.is_none() {
         return None;
     }
-    fn split_at(&mut self, split_idx: usize) -> Option<(JsParserSliceIterator, JsParserSliceIterator)> {
-        //make 2 iterators from this iterator, starting from the current position of this iterator
 
-        if split_idx > self.end_idx || split_idx < self.next_idx { return None; }
+    let then_statement = parse_statement(parser);
+    if then_statement.is_none() {
+        return None;
+    }
 
-        return Some((
-            JsParserSliceIterator { end_idx: split_idx - 1, next_idx: self.next_idx },
-            JsParserSliceIterator { end_idx: self.end_idx,  next_idx: split_idx + 1 }
-        ));
+    let mut else_script = None;
+    if parser.next_is(JsToken::KeyWordElse) {
+        parser.next();
+        let else_statement = parse_statement(parser);
+        if else_statement.is_none() {
+            return None;
+        }
+        else_script = Some(vec![else_statement.unwrap()]);
     }
+
+    return Some(JsAstStatement::If(JsAstIf { condition: condition.unwrap(), then_script: vec![then_statement.unwrap()], else_script }));
 }
 
 
-pub fn parse_js(tokens: &Vec<JsTokenWithLocation>) -> Script {
-    //TODO: we need to do semicolon insertion (see rules on https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Lexical_grammar#automatic_semicolon_insertion)
+fn parse_while(parser: &mut JsParser) -> Option<JsAstStatement> {
+    parser.next(); //consume the "while" keyword
 
-    if tokens.len() == 0 {
-        return Vec::new();
+    let condition = parse_condition_in_parentheses(parser);
+    if condition.is_none() {
+        return None;
+    }
+    let body = parse_loop_body(parser);
+    if body.is_none() {
+        return None;
     }
 
-    let mut token_iterator = JsParserSliceIterator {
-        end_idx: tokens.len() - 1,
-        next_idx: 0,
-    };
+    return Some(JsAstStatement::While(JsAstWhile { condition: condition.unwrap(), body: body.unwrap(), check_condition_first: true }));
+}
 
-    let token_types = tokens.iter().map(|token| token.token.clone()).collect::<Vec<_>>();
-    let masked_token_types = mask_token_types(&mut token_iterator, &token_types);
 
+fn parse_do_while(parser: &mut JsParser) -> Option<JsAstStatement> {
+    parser.next(); //consume the "do" keyword
 
-    let mut statements = Vec::new();
+    let body = parse_loop_body(parser);
+    if body.is_none() || !parser.expect(JsToken::KeyWordWhile) {
+        return None;
+    }
+    let condition = parse_condition_in_parentheses(parser);
+    if condition.is_none() {
+        return None;
+    }
 
-    while token_iterator.has_next() {
-        //TODO: if the last statement doesn't end with a semicolon we ignore it, we should fix that via semicolon insertion (also insert one at the end)
-        let statement_iterator = token_iterator.split_and_advance_until_next_token(&masked_token_types, JsToken::Semicolon);
-        if statement_iterator.is_some() {
-            if statement_iterator.as_ref().unwrap().has_next_non_whitespace(&tokens) {
-                let stat = parse_statement(&mut statement_iterator.unwrap(), tokens);
-                if stat.is_some() {
-                    statements.push(stat.unwrap());
-                }
-            }
-        } else {
-            break;
-        }
+    //the semicolon after a do-while loop can always be left out
+    if parser.next_is(JsToken::Semicolon) {
+        parser.next();
     }
 
-    return statements;
+    return Some(JsAstStatement::While(JsAstWhile { condition: condition.unwrap(), body: body.unwrap(), check_condition_first: false }));
 }
 
 
-fn parse_function_call(function_iterator: &mut JsParserSliceIterator, tokens: &Vec<JsTokenWithLocation>,
-                       masked_token_types: &Vec<JsToken>) -> Option<JsAstFunctionCall> {
-    let token_types = tokens.iter().map(|token| token.token.clone()).collect::<Vec<_>>();
-
-    let function_expression_iterator = function_iterator.split_and_advance_until_next_token(&masked_token_types, JsToken::OpenParenthesis);
-    let function_expression = parse_expression(&mut function_expression_iterator.unwrap(), tokens);
-    if function_expression.is_none() {
+fn parse_for(parser: &mut JsParser) -> Option<JsAstStatement> {
+    parser.next(); //consume the "for" keyword
+    if !parser.expect(JsToken::OpenParenthesis) {
         return None;
     }
 
-    let mut arguments = Vec::new();
+    //we first check for the "for (var x in ...)" and "for (x of ...)" forms
+    let decl_type = declaration_type(parser.peek());
+    let declares_variable = decl_type.is_some();
+    let start_of_head_idx = parser.next_idx;
+    if declares_variable {
+        parser.next();
+    }
+    if let Some(JsToken::Identifier(variable_name)) = parser.peek().cloned() {
+        let iteration_kind = match parser.peek_second() {
+            Some(JsToken::KeyWordIn) => Some(JsIterationKind::In),
+            Some(JsToken::Identifier(name)) if name == "of" => Some(JsIterationKind::Of),
+            _ => None,
+        };
 
-    //The below basically just removes the close CloseParenthesis
-    let function_iterator = function_iterator.check_for_and_split_on(tokens, JsToken::CloseParenthesis);
+        if iteration_kind.is_some() {
+            parser.next(); //the variable
+            parser.next(); //the in or of
 
-    if function_iterator.is_some() {
-        let (mut function_iterator, _) = function_iterator.unwrap();
+            let iterated_expression = parse_expression(parser, 0);
+            if iterated_expression.is_none() || !parser.expect(JsToken::CloseParenthesis) {
+                return None;
+            }
+            let body = parse_loop_body(parser);
+            if body.is_none() {
+                return None;
+            }
 
-        let masked_token_types_for_args = mask_token_types(&mut function_iterator, &token_types);
+            return Some(JsAstStatement::ForIn(JsAstForIn { decl_type, variable: JsAstIdentifier { name: variable_name }, kind: iteration_kind.unwrap(),
+                                                           iterated_expression: iterated_expression.unwrap(), body: body.unwrap() }));
+        }
+    }
+    parser.next_idx = start_of_head_idx;
 
-        loop {
-            let argument_iterator = function_iterator.split_and_advance_until_next_token(&masked_token_types_for_args, JsToken::Comma);
-            if argument_iterator.is_some() {
-                let expression = parse_expression(&mut argument_iterator.unwrap(), tokens);
-                if expression.is_none() {
-                    return None;
-                }
-                arguments.push(expression.unwrap());
+    //this is the "for (init; condition; update)" form, where all 3 parts are optional
+    let mut init = None;
+    if !parser.next_is(JsToken::Semicolon) {
+        init = if declares_variable {
+            parse_declaration(parser).map(|declaration| JsAstStatement::Declaration(declaration))
+        } else {
+            parse_expression_or_assignment(parser)
+        };
+        if init.is_none() {
+            return None;
+        }
+    }
+    if !parser.expect(JsToken::Semicolon) {
+        return None;
+    }
 
-            } else {
-                if !function_iterator.has_next_non_whitespace(&tokens) {
-                    //This function does not have arguments
-                    //TODO: this is only a valid case in the first iteration of the loop, fix that (do this check somewhere else maybe?)
-                    break;
-                }
+    let mut condition = None;
+    if !parser.next_is(JsToken::Semicolon) {
+        condition = parse_expression(parser, 0);
+        if condition.is_none() {
+            return None;
+        }
+    }
+    if !parser.expect(JsToken::Semicolon) {
+        return None;
+    }
 
-                let expression = parse_expression(&mut function_iterator, tokens);
-                if expression.is_none() {
-                    return None;
-                }
-                arguments.push(expression.unwrap());
-                break;
-            }
+    let mut update = None;
+    if !parser.next_is(JsToken::CloseParenthesis) {
+        update = parse_expression_or_assignment(parser);
+        if update.is_none() {
+            return None;
         }
     }
+    if !parser.expect(JsToken::CloseParenthesis) {
+        return None;
+    }
 
-    return Some(JsAstFunctionCall { function_expression: Rc::from(function_expression.unwrap()), arguments });
-}
+    let body = parse_loop_body(parser);
+    if body.is_none() {
+        return None;
+    }
 
+    return Some(JsAstStatement::For(JsAstFor { init: init.map(|statement| Box::new(statement)), condition,
+                                               update: update.map(|statement| Box::new(statement)), body: body.unwrap() }));
+}
 
-fn parse_function_declaration(iterator: &mut JsParserSliceIterator, tokens: &Vec<JsTokenWithLocation>) -> Option<JsAstFunctionDeclaration> {
-    let token_types = tokens.iter().map(|token| token.token.clone()).collect::<Vec<_>>();
 
-    iterator.move_after_next_non_whitespace(tokens); //consume the "function" keyword
+fn parse_try(parser: &mut JsParser) -> Option<JsAstStatement> {
+    parser.next(); //consume the "try" keyword
 
-    let function_name_split = iterator.check_for_and_split_on(tokens, JsToken::OpenParenthesis);
+    let try_script = parse_block(parser);
+    if try_script.is_none() {
+        return None;
+    }
 
-    if function_name_split.is_some() {
-        let (mut function_name_iterator, mut other_iterator) = function_name_split.unwrap();
+    let mut catch_variable = None;
+    let mut catch_script = None;
+    if parser.next_is(JsToken::KeyWordCatch) {
+        parser.next();
 
-        let function_name = function_name_iterator.read_only_identifier(tokens).unwrap();
+        if parser.next_is(JsToken::OpenParenthesis) {
+            parser.next();
+            let variable_name = parser.expect_identifier();
+            if variable_name.is_none() || !parser.expect(JsToken::CloseParenthesis) {
+                return None;
+            }
+            catch_variable = Some(JsAstIdentifier { name: variable_name.unwrap() });
+        }
 
-        let function_body_split = other_iterator.check_for_and_split_on(tokens, JsToken::CloseParenthesis);
-        if function_body_split.is_some() {
-            let (mut argument_iterator, mut function_body_iterator) = function_body_split.unwrap();
+        catch_script = parse_block(parser);
+        if catch_script.is_none() {
+            return None;
+        }
+    }
 
-            let masked_token_types_for_args = mask_token_types(&mut argument_iterator, &token_types);
+    let mut finally_script = None;
+    if parser.next_is(JsToken::KeyWordFinally) {
+        parser.next();
+        finally_script = parse_block(parser);
+        if finally_script.is_none() {
+            return None;
+        }
+    }
 
-            let mut arguments = Vec::new();
+    if catch_script.is_none() && finally_script.is_none() {
+        parser.log_error("try needs a catch or a finally block");
+        return None;
+    }
 
-            while argument_iterator.has_next() {
-                let possible_argument_iterator = argument_iterator.split_and_advance_until_next_token(&masked_token_types_for_args, JsToken::Comma);
+    return Some(JsAstStatement::Try(JsAstTry { try_script: try_script.unwrap(), catch_variable, catch_script, finally_script }));
+}
 
-                if possible_argument_iterator.is_none() {
-                    let arg_name = argument_iterator.read_only_identifier(tokens).unwrap();
-                    arguments.push(JsAstIdentifier { name: arg_name });
-                    break;
-                } else {
-                    let arg_name = possible_argument_iterator.unwrap().read_only_identifier(tokens).unwrap();
-                    arguments.push(JsAstIdentifier { name: arg_name });
-                }
-            }
 
-            function_body_iterator.move_after_next_non_whitespace(tokens); //consume the opening brace
+fn parse_function_declaration(parser: &mut JsParser, is_async: bool) -> Option<JsAstFunctionDeclaration> {
+    parser.next(); //consume the "function" keyword
 
-            let mut statements = Vec::new();
+    let function_name = parser.expect_identifier();
+    if function_name.is_none() {
+        return None;
+    }
+    let arguments_and_body = parse_function_arguments_and_body(parser, is_async);
+    if arguments_and_body.is_none() {
+        return None;
+    }
 
-            while function_body_iterator.has_next() {
+    let (arguments, rest_argument, body) = arguments_and_body.unwrap();
+    return Some(JsAstFunctionDeclaration { name: function_name.unwrap(), arguments: arguments, rest_argument, script: Rc::from(body), is_async });
+}
 
-                //TODO: if the last statement doesn't end with a semicolon we ignore it, we should fix that via semicolon insertion (also insert one at the end)
-                let statement_iterator = function_body_iterator.split_and_advance_until_next_token(&token_types, JsToken::Semicolon);
-                if statement_iterator.is_some() {
-                    if statement_iterator.as_ref().unwrap().has_next_non_whitespace(&tokens) {
-                        let stat = parse_statement(&mut statement_iterator.unwrap(), tokens);
-                        if stat.is_some() {
-                            statements.push(stat.unwrap());
-                        }
-                    }
-                } else {
-                    break;
-                }
-            }
 
-            return Some(JsAstFunctionDeclaration { name: function_name, arguments: arguments, script: Rc::from(statements) });
-        }
+fn parse_function_expression(parser: &mut JsParser, is_async: bool) -> Option<JsAstFunctionExpression> {
+    parser.next(); //consume the "function" keyword
 
+    //the name is optional for function expressions
+    let mut function_name = None;
+    if let Some(JsToken::Identifier(name)) = parser.peek() {
+        function_name = Some(name.clone());
+        parser.next();
+    }
+    let arguments_and_body = parse_function_arguments_and_body(parser, is_async);
+    if arguments_and_body.is_none() {
+        return None;
     }
 
-    return None;
+    let (arguments, rest_argument, body) = arguments_and_body.unwrap();
+    return Some(JsAstFunctionExpression { name: function_name, arguments: arguments, rest_argument, script: Rc::from(body), is_async });
 }
 
 
-fn parse_declaration(statement_iterator: &mut JsParserSliceIterator, tokens: &Vec<JsTokenWithLocation>) -> Option<JsAstDeclaration> {
-    statement_iterator.move_after_next_non_whitespace(tokens); //consume the "var" keyword
-
-    let optional_equals_split = statement_iterator.check_for_and_split_on(tokens, JsToken::Equals);
-
-    if optional_equals_split.is_some() {
-        let (mut left, mut right) = optional_equals_split.unwrap();
+fn parse_function_arguments_and_body(parser: &mut JsParser, is_async: bool) -> Option<(Vec<JsAstIdentifier>, Option<JsAstIdentifier>, Script)> {
+    if !parser.expect(JsToken::OpenParenthesis) {
+        return None;
+    }
 
-        let possible_ident = left.read_only_identifier(tokens);
-        let variable = if possible_ident.is_some() {
-            JsAstIdentifier { name: possible_ident.unwrap() }
-        } else {
-            panic!("Expected only an identifier after var decl");
-        };
+    let mut arguments = Vec::new();
+    let mut rest_argument = None;
+    while !parser.next_is(JsToken::CloseParenthesis) {
+        if parser.next_is(JsToken::DotDotDot) {
+            //the rest argument, as in "function f(a, ...rest)", has to be the last one
+            parser.next();
+            let argument_name = parser.expect_identifier();
+            if argument_name.is_none() || !parser.expect(JsToken::CloseParenthesis) {
+                return None;
+            }
+            rest_argument = Some(JsAstIdentifier { name: argument_name.unwrap() });
+            parser.next_idx -= 1; //the close parenthesis is consumed below
+            break;
+        }
 
-        let expression = parse_expression(&mut right, tokens);
-        if expression.is_none() {
+        let argument_name = parser.expect_identifier();
+        if argument_name.is_none() {
             return None;
         }
+        arguments.push(JsAstIdentifier { name: argument_name.unwrap() });
 
-        return Some(JsAstDeclaration {
-            variable,
-            initial_value: expression,
-        });
+        if !parser.next_is(JsToken::CloseParenthesis) && !parser.expect(JsToken::Comma) {
+            return None;
+        }
+    }
+    parser.next(); //consume the close parenthesis
+
+    //a loop outside of the function does not allow break or continue inside of it, and await depends on the function it is directly in
+    let outer_loop_depth = parser.loop_depth;
+    let outer_in_async_function = parser.in_async_function;
+    parser.loop_depth = 0;
+    parser.in_async_function = is_async;
+    let body = parse_block(parser);
+    parser.loop_depth = outer_loop_depth;
+    parser.in_async_function = outer_in_async_function;
+
+    if body.is_none() {
+        return None;
     }
+    return Some((arguments, rest_argument, body.unwrap()));
+}
 
-    let possible_ident = statement_iterator.read_only_identifier(tokens);
-    let variable = if possible_ident.is_some() {
-        JsAstIdentifier { name: possible_ident.unwrap() }
-    } else {
-        panic!("Expected only an identifier after var decl");
-    };
 
-    return Some(JsAstDeclaration {
-        variable,
-        initial_value: None,
-    });
+fn declaration_type(token: Option<&JsToken>) -> Option<JsDeclType> {
+    return match token {
+        Some(JsToken::KeyWordVar) => Some(JsDeclType::Var),
+        Some(JsToken::KeyWordLet) => Some(JsDeclType::Let),
+        Some(JsToken::KeyWordConst) => Some(JsDeclType::Const),
+        _ => None,
+    };
 }
 
 
-fn parse_statement(statement_iterator: &mut JsParserSliceIterator, tokens: &Vec<JsTokenWithLocation>) -> Option<JsAstStatement> {
+fn parse_declaration(parser: &mut JsParser) -> Option<JsAstDeclaration> {
+    let decl_type = declaration_type(parser.next().as_ref()).unwrap(); //consume the var, let or const keyword
 
-    if statement_iterator.next_non_whitespace_token_is(&tokens, JsToken::KeyWordVar) {
-        let decl = parse_declaration(statement_iterator, tokens);
-        if decl.is_none() {
-            return None;
-        }
-        return Some(JsAstStatement::Declaration(decl.unwrap()));
+    //TODO: we don't support declaring multiple variables in one statement yet
+    let target = parse_binding_pattern(parser);
+    if target.is_none() {
+        return None;
     }
+    let target = target.unwrap();
 
-    if statement_iterator.next_non_whitespace_token_is(&tokens, JsToken::KeyWordFunction) {
-        let function_declaration = parse_function_declaration(statement_iterator, tokens);
-        if function_declaration.is_none() {
+    if parser.next_is(JsToken::Equals) {
+        parser.next();
+        let expression = parse_expression(parser, 0);
+        if expression.is_none() {
             return None;
         }
-        return Some(JsAstStatement::FunctionDeclaration(function_declaration.unwrap()));
+        return Some(JsAstDeclaration { decl_type, target, initial_value: expression });
     }
 
-    if statement_iterator.next_non_whitespace_token_is(&tokens, JsToken::KeyWordReturn) {
-        statement_iterator.move_after_next_non_whitespace(tokens); //consume the "return" keyword
+    if decl_type == JsDeclType::Const {
+        parser.log_error("a const declaration needs an initial value");
+        return None;
+    }
+    if !matches!(target, JsAstPattern::Identifier(_)) {
+        parser.log_error("a destructuring declaration needs an initial value");
+        return None;
+    }
+    return Some(JsAstDeclaration { decl_type, target, initial_value: None });
+}
 
-        let expression = parse_expression(statement_iterator, tokens);
-        if expression.is_none() {
-            return None;
-        }
-        return Some(JsAstStatement::Return(expression.unwrap()));
 
-    }
+fn parse_binding_pattern(parser: &mut JsParser) -> Option<JsAstPattern> {
+    //parses the name of a variable, or an object or array pattern with more patterns inside, as in "{a, b: [c, d]}"
+    match parser.peek() {
+        Some(JsToken::OpenBrace) => {
+            parser.next();
+            let mut properties = Vec::new();
+            while !parser.next_is(JsToken::CloseBrace) {
+                let shorthand_name = match parser.peek() {
+                    Some(JsToken::Identifier(name)) => Some(name.clone()),
+                    _ => None,
+                };
+                let key = parse_property_name(parser);
+                if key.is_none() {
+                    return None;
+                }
 
-    let optional_equals_split = statement_iterator.check_for_and_split_on(tokens, JsToken::Equals);
+                //the shorthand "{a}" means "{a: a}"
+                let target = if parser.next_is(JsToken::Colon) {
+                    parser.next();
+                    parse_binding_pattern(parser)
+                } else if shorthand_name.is_some() {
+                    Some(JsAstPattern::Identifier(JsAstIdentifier { name: shorthand_name.unwrap() }))
+                } else {
+                    parser.log_error("expected a colon");
+                    None
+                };
+                let element = parse_pattern_element_default(parser, target);
+                if element.is_none() {
+                    return None;
+                }
+                properties.push((key.unwrap(), element.unwrap()));
 
-    if optional_equals_split.is_some() {
-        let (mut left, mut right) = optional_equals_split.unwrap();
-        let parsed_left = parse_expression(&mut left, tokens);
-        let parsed_right = parse_expression(&mut right, tokens);
-        if parsed_left.is_none() || parsed_right.is_none() {
-            return None;
-        }
-        return Some(JsAstStatement::Assign(JsAstAssign { left: parsed_left.unwrap(), right: parsed_right.unwrap() }));
+                if !parser.next_is(JsToken::CloseBrace) && !parser.expect(JsToken::Comma) {
+                    return None;
+                }
+            }
+            parser.next(); //consume the close brace
+            return Some(JsAstPattern::Object(properties));
+        },
+        Some(JsToken::OpenBracket) => {
+            parser.next();
+            let mut elements = Vec::new();
+            let mut rest = None;
+            while !parser.next_is(JsToken::CloseBracket) {
+                if parser.next_is(JsToken::Comma) {
+                    parser.next();
+                    elements.push(None); //a skipped element, as in "[a, , b]"
+                    continue;
+                }
+                if parser.next_is(JsToken::DotDotDot) {
+                    //the rest element has to be the last one
+                    parser.next();
+                    let rest_pattern = parse_binding_pattern(parser);
+                    if rest_pattern.is_none() || !parser.expect(JsToken::CloseBracket) {
+                        return None;
+                    }
+                    rest = Some(Box::new(rest_pattern.unwrap()));
+                    parser.next_idx -= 1; //the close bracket is consumed below
+                    break;
+                }
+
+                let target = parse_binding_pattern(parser);
+                let element = parse_pattern_element_default(parser, target);
+                if element.is_none() {
+                    return None;
+                }
+                elements.push(element);
+
+                if !parser.next_is(JsToken::CloseBracket) && !parser.expect(JsToken::Comma) {
+                    return None;
+                }
+            }
+            parser.next(); //consume the close bracket
+            return Some(JsAstPattern::Array(elements, rest));
+        },
+        _ => {
+            let name = parser.expect_identifier();
+            if name.is_none() {
+                return None;
+            }
+            return Some(JsAstPattern::Identifier(JsAstIdentifier { name: name.unwrap() }));
+        },
     }
+}
 
-    let expression = parse_expression(statement_iterator, tokens);
-    if expression.is_none() {
+
+fn parse_pattern_element_default(parser: &mut JsParser, target: Option<JsAstPattern>) -> Option<JsAstPatternElement> {
+    //an element of a pattern can have a default value for when the value is undefined, as in "{a = 1}"
+    if target.is_none() {
         return None;
     }
 
-    return Some(JsAstStatement::Expression(expression.unwrap()));
+    let mut default_value = None;
+    if parser.next_is(JsToken::Equals) {
+        parser.next();
+        default_value = parse_expression(parser, 0);
+        if default_value.is_none() {
+            return None;
+        }
+    }
+    return Some(JsAstPatternElement { target: target.unwrap(), default_value });
 }
 
 
-fn mask_token_types(iterator: &mut JsParserSliceIterator, token_types: &Vec<JsToken>) -> Vec<JsToken> {
-    //mask token types (set those in braces/brackets/parenthesis to None), but only when in scope of the iterator
+fn pattern_from_expression(expression: JsAstExpression) -> Option<JsAstPattern> {
+    //an array literal in front of an assignment is a pattern, as in "[a, b] = [b, a]"
+    //TODO: object patterns in assignments need parentheses around the whole statement ("({a} = x)"), which we don't support yet
+    match expression {
+        JsAstExpression::Identifier(identifier) => { return Some(JsAstPattern::Identifier(identifier)); },
+        JsAstExpression::ArrayLiteral(elements) => {
+            let mut pattern_elements = Vec::new();
+            let mut rest = None;
+            let element_count = elements.len();
+            for (idx, element) in elements.into_iter().enumerate() {
+                match element {
+                    None => { pattern_elements.push(None); },
+                    Some(JsAstExpression::Spread(spread)) if idx == element_count - 1 => {
+                        let spread = Rc::try_unwrap(spread).ok();
+                        if spread.is_none() {
+                            return None;
+                        }
+                        let rest_pattern = pattern_from_expression(spread.unwrap());
+                        if rest_pattern.is_none() {
+                            return None;
+                        }
+                        rest = Some(Box::new(rest_pattern.unwrap()));
+                    },
+                    Some(element) => {
+                        let target = pattern_from_expression(element);
+                        if target.is_none() {
+                            return None;
+                        }
+                        pattern_elements.push(Some(JsAstPatternElement { target: target.unwrap(), default_value: None }));
+                    },
+                }
+            }
+            return Some(JsAstPattern::Array(pattern_elements, rest));
+        },
+        _ => { return None; },
+    }
+}
 
-    let mut masked = Vec::new();
 
-    let mut open_brace = 0;
-    let mut open_brack = 0;
-    let mut open_paren = 0;
+fn parse_expression_or_assignment(parser: &mut JsParser) -> Option<JsAstStatement> {
+    let expression = parse_expression(parser, 0);
+    if expression.is_none() {
+        return None;
+    }
 
-    for (idx, token) in token_types.iter().enumerate() {
-        if idx < iterator.next_idx || idx > iterator.end_idx {
-            masked.push(token.clone());
-            continue;
+    if parser.next_is(JsToken::Equals) && matches!(expression.as_ref().unwrap(), JsAstExpression::ArrayLiteral(_)) {
+        let pattern = pattern_from_expression(expression.unwrap());
+        if pattern.is_none() {
+            parser.log_error("invalid assignment target");
+            return None;
         }
 
-        match token {
-            JsToken::CloseBrace => { open_brace -= 1 },
-            JsToken::CloseBracket => { open_brack -= 1 },
-            JsToken::CloseParenthesis => { open_paren -= 1 },
-            _ => {},
+        parser.next();
+        let right = parse_expression(parser, 0);
+        if right.is_none() {
+            return None;
         }
+        return Some(JsAstStatement::DestructuringAssign(JsAstDestructuringAssign { pattern: pattern.unwrap(), right: right.unwrap() }));
+    }
 
-        if open_brace == 0 && open_brack == 0 && open_paren == 0 {
-            masked.push(token.clone());
-        } else {
-            masked.push(JsToken::None);
+    if parser.next_is(JsToken::Equals) {
+        if !is_simple_assignment_target(expression.as_ref().unwrap()) {
+            parser.log_error("invalid assignment target");
+            return None;
         }
 
-        match token {
-            JsToken::OpenBrace => { open_brace += 1 },
-            JsToken::OpenBracket => { open_brack += 1 },
-            JsToken::OpenParenthesis => { open_paren += 1 },
-            _ => {},
+        parser.next();
+        let right = parse_expression(parser, 0);
+        if right.is_none() {
+            return None;
         }
-
+        return Some(JsAstStatement::Assign(JsAstAssign { left: expression.unwrap(), right: right.unwrap() }));
     }
 
-    return masked;
+    return Some(JsAstStatement::Expression(expression.unwrap()));
 }
 
 
-fn parse_expression(iterator: &mut JsParserSliceIterator, tokens: &Vec<JsTokenWithLocation>) -> Option<JsAstExpression> {
-    let token_types = tokens.iter().map(|token| token.token.clone()).collect::<Vec<_>>();
-    let masked_token_types = mask_token_types(iterator, &token_types);
+fn is_simple_assignment_target(expression: &JsAstExpression) -> bool {
+    //what can be assigned to (or incremented), without destructuring
+    return match expression {
+        JsAstExpression::Identifier(_) => true,
+        JsAstExpression::BinOp(binop) => matches!(binop.op, JsBinOp::PropertyAccess),
+        _ => false,
+    };
+}
 
 
-    /*  (precendece group 11)   + and -    */
-    {
-        let optional_plus_idx = iterator.find_last_token_idx(&masked_token_types, JsToken::Plus);
-        let optional_minus_idx = iterator.find_last_token_idx(&masked_token_types, JsToken::Minus);
+fn infix_binding_power(token: &JsToken) -> Option<(JsBinOp, u8, u8)> {
+    //the binding powers follow the precedence groups of the operators, left associative operators bind a bit stronger to the right
+    return match token {
+        JsToken::DoubleQuestionMark => Some((JsBinOp::NullishCoalescing, 5, 6)),
+        JsToken::DoublePipe => Some((JsBinOp::LogicalOr, 5, 6)),
+        JsToken::DoubleAnd => Some((JsBinOp::LogicalAnd, 7, 8)),
+        JsToken::DoubleEquals => Some((JsBinOp::Equal, 15, 16)),
+        JsToken::NotEquals => Some((JsBinOp::NotEqual, 15, 16)),
+        JsToken::TripleEquals => Some((JsBinOp::StrictEqual, 15, 16)),
+        JsToken::NotDoubleEquals => Some((JsBinOp::StrictNotEqual, 15, 16)),
+        JsToken::Smaller => Some((JsBinOp::Smaller, 17, 18)),
+        JsToken::Bigger => Some((JsBinOp::Bigger, 17, 18)),
+        JsToken::SmallerOrEqual => Some((JsBinOp::SmallerOrEqual, 17, 18)),
+        JsToken::BiggerOrEqual => Some((JsBinOp::BiggerOrEqual, 17, 18)),
+        JsToken::KeyWordInstanceof => Some((JsBinOp::InstanceOf, 17, 18)),
+        JsToken::KeyWordIn => Some((JsBinOp::In, 17, 18)),
+        JsToken::Plus => Some((JsBinOp::Plus, 21, 22)),
+        JsToken::Minus => Some((JsBinOp::Minus, 21, 22)),
+        JsToken::Star => Some((JsBinOp::Times, 23, 24)),
+        JsToken::ForwardSlash => Some((JsBinOp::Divide, 23, 24)),
+        _ => None,
+    };
+}
 
-        let (operator, split_idx) = if optional_plus_idx.is_some() && optional_minus_idx.is_some() {
-            if optional_plus_idx.unwrap() > optional_minus_idx.unwrap() {
-                (Some(JsBinOp::Plus), Some(optional_plus_idx.unwrap()))
-            } else {
-                (Some(JsBinOp::Minus), Some(optional_minus_idx.unwrap()))
-            }
-        } else if optional_plus_idx.is_some() {
-            (Some(JsBinOp::Plus), Some(optional_plus_idx.unwrap()))
-        } else if optional_minus_idx.is_some() {
-            (Some(JsBinOp::Minus), Some(optional_minus_idx.unwrap()))
-        } else {
-            (None, None)
-        };
 
-        if operator.is_some() {
-            let (mut left_iter, mut right_iter) = iterator.split_at(split_idx.unwrap()).unwrap();
+const CONDITIONAL_BINDING_POWER: u8 = 3;
+const PREFIX_OPERATOR_BINDING_POWER: u8 = 30;
+const POSTFIX_OPERATOR_BINDING_POWER: u8 = 32;
+const MEMBER_ACCESS_AND_CALL_BINDING_POWER: u8 = 34;
 
-            let left_ast = parse_expression(&mut left_iter, &tokens);
-            let right_ast = parse_expression(&mut right_iter, &tokens);
-            if left_ast.is_none() || right_ast.is_none() {
-                return None;
-            }
 
-            return Some(JsAstExpression::BinOp(JsAstBinOp {
-                op: operator.unwrap(),
-                left: Rc::from(left_ast.unwrap()),
-                right: Rc::from(right_ast.unwrap()),
-            }));
-        }
+fn parse_expression(parser: &mut JsParser, min_binding_power: u8) -> Option<JsAstExpression> {
+    //this is a pratt parser, see https://matklad.github.io/2020/04/13/simple-but-powerful-pratt-parsing.html
+
+    let left = parse_primary_expression(parser);
+    if left.is_none() {
+        return None;
     }
+    let mut left = left.unwrap();
 
+    loop {
+        let next_token = parser.peek().cloned();
+        if next_token.is_none() {
+            break;
+        }
+        let next_token = next_token.unwrap();
 
-    /*  (precendece group 12)    * and /    */
-    {
-        let optional_times_idx = iterator.find_last_token_idx(&masked_token_types, JsToken::Star);
-        let optional_divide_idx = iterator.find_last_token_idx(&masked_token_types, JsToken::ForwardSlash);
+        if next_token == JsToken::QuestionMark {
+            if CONDITIONAL_BINDING_POWER < min_binding_power {
+                break;
+            }
+            parser.next();
 
-        let (operator, split_idx) = if optional_times_idx.is_some() && optional_divide_idx.is_some() {
-            if optional_times_idx.unwrap() > optional_divide_idx.unwrap() {
-                (Some(JsBinOp::Times), Some(optional_times_idx.unwrap()))
-            } else {
-                (Some(JsBinOp::Divide), Some(optional_divide_idx.unwrap()))
+            //the conditional operator is right associative, so "a ? b : c ? d : e" means "a ? b : (c ? d : e)"
+            let then_expression = parse_expression(parser, 0);
+            if then_expression.is_none() || !parser.expect(JsToken::Colon) {
+                return None;
+            }
+            let else_expression = parse_expression(parser, CONDITIONAL_BINDING_POWER);
+            if else_expression.is_none() {
+                return None;
             }
-        } else if optional_times_idx.is_some() {
-            (Some(JsBinOp::Times), Some(optional_times_idx.unwrap()))
-        } else if optional_divide_idx.is_some() {
-            (Some(JsBinOp::Divide), Some(optional_divide_idx.unwrap()))
-        } else {
-            (None, None)
-        };
 
-        if operator.is_some() {
-            let (mut left_iter, mut right_iter) = iterator.split_at(split_idx.unwrap()).unwrap();
+            left = JsAstExpression::Conditional(JsAstConditional { condition: Rc::from(left), then_expression: Rc::from(then_expression.unwrap()),
+                                                                   else_expression: Rc::from(else_expression.unwrap()) });
+            continue;
+        }
 
-            let left_ast = parse_expression(&mut left_iter, &tokens);
-            let right_ast = parse_expression(&mut right_iter, &tokens);
-            if left_ast.is_none() || right_ast.is_none() {
+        if next_token == JsToken::QuestionMarkDot {
+            if MEMBER_ACCESS_AND_CALL_BINDING_POWER < min_binding_power {
+                break;
+            }
+            parser.next();
+
+            let access = match parser.peek() {
+                Some(JsToken::OpenBracket) => {
+                    parser.next();
+                    parse_property_access(parser, left, true, JsBinOp::OptionalPropertyAccess)
+                },
+                Some(JsToken::OpenParenthesis) => {
+                    parser.next();
+                    let arguments = parse_call_arguments(parser);
+                    if arguments.is_none() {
+                        return None;
+                    }
+                    Some(JsAstExpression::FunctionCall(JsAstFunctionCall { function_expression: Rc::from(left), arguments: arguments.unwrap(), optional: true }))
+                },
+                _ => parse_property_access(parser, left, false, JsBinOp::OptionalPropertyAccess),
+            };
+            if access.is_none() {
                 return None;
             }
-
-            return Some(JsAstExpression::BinOp(JsAstBinOp {
-                op: operator.unwrap(),
-                left: Rc::from(left_ast.unwrap()),
-                right: Rc::from(right_ast.unwrap()),
-            }));
+            left = access.unwrap();
+            continue;
         }
-    }
 
+        //a newline before ++ or -- ends the statement, so "a\n++b" increments b
+        if (next_token == JsToken::PlusPlus || next_token == JsToken::MinusMinus) && !parser.newline_before_next() {
+            if POSTFIX_OPERATOR_BINDING_POWER < min_binding_power {
+                break;
+            }
+            parser.next();
 
-    /* (precendece group 17): function call and PropertyAccess (dot operator and [])  */
-    {
-        if iterator.is_only_function_call(&masked_token_types) {
-            let call = parse_function_call(iterator, tokens, &masked_token_types);
-            if call.is_none() {
+            if !is_simple_assignment_target(&left) {
+                parser.log_error("invalid update target");
                 return None;
             }
-            return Some(JsAstExpression::FunctionCall(call.unwrap()));
+            let op = if next_token == JsToken::PlusPlus { JsUpdateOp::Increment } else { JsUpdateOp::Decrement };
+            left = JsAstExpression::Update(JsAstUpdate { op, is_prefix: false, target: Rc::from(left) });
+            continue;
         }
 
-        //TODO: implement the [] case
-
-        let optional_dot_idx = iterator.find_last_token_idx(&masked_token_types, JsToken::Dot);
-        if optional_dot_idx.is_some() {
-            let (mut left_iter, mut right_iter) = iterator.split_at(optional_dot_idx.unwrap()).unwrap();
+        if next_token == JsToken::Dot || next_token == JsToken::OpenBracket || next_token == JsToken::OpenParenthesis {
+            if MEMBER_ACCESS_AND_CALL_BINDING_POWER < min_binding_power {
+                break;
+            }
+            parser.next();
 
-            let left_ast = parse_expression(&mut left_iter, &tokens);
-            let right_ast = parse_expression(&mut right_iter, &tokens);
-            if left_ast.is_none() || right_ast.is_none() {
+            let access = match next_token {
+                JsToken::Dot | JsToken::OpenBracket => {
+                    parse_property_access(parser, left, next_token == JsToken::OpenBracket, JsBinOp::PropertyAccess)
+                },
+                _ => {
+                    let arguments = parse_call_arguments(parser);
+                    if arguments.is_none() {
+                        return None;
+                    }
+                    Some(JsAstExpression::FunctionCall(JsAstFunctionCall { function_expression: Rc::from(left), arguments: arguments.unwrap(), optional: false }))
+                },
+            };
+            if access.is_none() {
                 return None;
             }
+            left = access.unwrap();
+            continue;
+        }
+
+        let binding_power = infix_binding_power(&next_token);
+        if binding_power.is_none() {
+            break;
+        }
+        let (operator, left_binding_power, right_binding_power) = binding_power.unwrap();
+        if left_binding_power < min_binding_power {
+            break;
+        }
+        parser.next();
+
+        let right = parse_expression(parser, right_binding_power);
+        if right.is_none() {
+            return None;
+        }
+        left = JsAstExpression::BinOp(JsAstBinOp { op: operator, left: Rc::from(left), right: Rc::from(right.unwrap()) });
+    }
 
-            return Some(JsAstExpression::BinOp(JsAstBinOp{
-                op: JsBinOp::PropertyAccess,
-                left: Rc::from(left_ast.unwrap()),
-                right: Rc::from(right_ast.unwrap()),
-            }));
+    return Some(left);
+}
+
+
+fn parse_property_access(parser: &mut JsParser, object: JsAstExpression, is_computed: bool, op: JsBinOp) -> Option<JsAstExpression> {
+    //the dot or open bracket is already consumed, for computed access (as in "a[b]") we parse the expression up to the close bracket
+    if is_computed {
+        let property = parse_expression(parser, 0);
+        if property.is_none() || !parser.expect(JsToken::CloseBracket) {
+            return None;
         }
+        return Some(JsAstExpression::BinOp(JsAstBinOp { op, left: Rc::from(object), right: Rc::from(property.unwrap()) }));
     }
 
-    let possible_literal_number = iterator.read_only_literal_number(tokens);
-    if possible_literal_number.is_some() {
-        return Some(JsAstExpression::NumericLiteral(possible_literal_number.unwrap()));
+    //keywords are allowed as property names here, as in "promise.catch()"
+    let keyword_name = parser.peek().and_then(|token| token.keyword_text());
+    let property_name = match keyword_name {
+        Some(keyword_name) => {
+            parser.next();
+            Some(String::from(keyword_name))
+        },
+        None => parser.expect_identifier(),
+    };
+    if property_name.is_none() {
+        return None;
     }
+    return Some(JsAstExpression::BinOp(JsAstBinOp { op, left: Rc::from(object), right: Rc::from(JsAstExpression::StringLiteral(property_name.unwrap())) }));
+}
 
-    let possible_literal_string = iterator.read_only_literal_string(tokens);
-    if possible_literal_string.is_some() {
-        return Some(JsAstExpression::StringLiteral(possible_literal_string.unwrap()));
+
+fn parse_object_creation(parser: &mut JsParser) -> Option<JsAstExpression> {
+    parser.next(); //the "new" keyword
+
+    //the constructor can be a property access, but not a call, because the first arguments belong to new (so "new a.B()" is "new (a.B)()")
+    let constructor = if parser.next_is(JsToken::KeyWordNew) { parse_object_creation(parser) } else { parse_primary_expression(parser) };
+    if constructor.is_none() {
+        return None;
     }
+    let mut constructor = constructor.unwrap();
 
-    if iterator.is_only_object_literal(&masked_token_types) {
-        let parsed_object = parse_object_literal(iterator, tokens, &masked_token_types);
-        if parsed_object.is_none() {
+    loop {
+        let is_computed = match parser.peek() {
+            Some(JsToken::Dot) => false,
+            Some(JsToken::OpenBracket) => true,
+            _ => { break; }
+        };
+        parser.next();
+
+        let access = parse_property_access(parser, constructor, is_computed, JsBinOp::PropertyAccess);
+        if access.is_none() {
             return None;
         }
-        return Some(JsAstExpression::ObjectLiteral(parsed_object.unwrap()));
+        constructor = access.unwrap();
     }
 
-    let possible_ident = iterator.read_only_identifier(tokens);
-    if possible_ident.is_some() {
-        return Some(JsAstExpression::Identifier(JsAstIdentifier{ name: possible_ident.unwrap() }));
+    //the arguments can be left out completely, as in "new Thing"
+    let mut arguments = Vec::new();
+    if parser.next_is(JsToken::OpenParenthesis) {
+        parser.next();
+        let parsed_arguments = parse_call_arguments(parser);
+        if parsed_arguments.is_none() {
+            return None;
+        }
+        arguments = parsed_arguments.unwrap();
     }
 
-    let possible_literal_regex = iterator.read_only_literal_regex(tokens);
-    if possible_literal_regex.is_some() {
-        //TODO: regexes are not implemented yet, so for now we just return the regex itself as an empty string
-        return Some(JsAstExpression::StringLiteral(String::new()));
+    return Some(JsAstExpression::ObjectCreation(JsAstObjectCreation { constructor: Rc::from(constructor), arguments }));
+}
+
+
+fn parse_primary_expression(parser: &mut JsParser) -> Option<JsAstExpression> {
+    let next_token = parser.peek().cloned();
+    if next_token.is_none() {
+        parser.log_error("unexpected end of script");
+        return None;
     }
 
-    let line = tokens[iterator.next_idx].line;
-    let char = tokens[iterator.next_idx].character;
-    js_console::log_js_error(format!("unparsable token stream found starting at {line}::{char}").as_str());
+    match next_token.unwrap() {
+        JsToken::Number(number) => {
+            if parse_numeric_literal(&number).is_none() {
+                parser.log_error("invalid number");
+                return None;
+            }
+            parser.next();
+            return Some(JsAstExpression::NumericLiteral(number));
+        },
+        JsToken::LiteralString(string) => {
+            parser.next();
+            return Some(JsAstExpression::StringLiteral(string));
+        },
+        JsToken::Identifier(name) if name == "await" && parser.in_async_function => {
+            parser.next();
+            let expression = parse_expression(parser, PREFIX_OPERATOR_BINDING_POWER);
+            if expression.is_none() {
+                return None;
+            }
+            return Some(JsAstExpression::Await(Rc::from(expression.unwrap())));
+        },
+        JsToken::Identifier(_) if parser.next_is_async_function() => {
+            parser.next(); //consume "async"
+            let function_expression = parse_function_expression(parser, true);
+            if function_expression.is_none() {
+                return None;
+            }
+            return Some(JsAstExpression::Function(function_expression.unwrap()));
+        },
+        JsToken::Identifier(name) => {
+            parser.next();
+            return Some(JsAstExpression::Identifier(JsAstIdentifier { name }));
+        },
+        JsToken::RegexLiteral(literal) => {
+            parser.next();
+
+            //the literal looks like /pattern/flags, and the pattern can't contain an unescaped slash outside of a character class
+            let closing_slash_idx = literal.rfind('/').unwrap();
+            let regex = JsRegex::new(&literal[1..closing_slash_idx], &literal[closing_slash_idx + 1..]);
+            if regex.is_err() {
+                parser.log_error(format!("invalid regular expression: {}", regex.unwrap_err()).as_str());
+                return None;
+            }
+            return Some(JsAstExpression::RegexLiteral(Rc::from(regex.unwrap())));
+        },
+        JsToken::ExclamationMark | JsToken::KeyWordTypeof | JsToken::KeyWordDelete | JsToken::KeyWordVoid => {
+            let op = match parser.next().unwrap() {
+                JsToken::ExclamationMark => JsUnaryOp::Not,
+                JsToken::KeyWordTypeof => JsUnaryOp::TypeOf,
+                JsToken::KeyWordDelete => JsUnaryOp::Delete,
+                _ => JsUnaryOp::Void,
+            };
+            let expression = parse_expression(parser, PREFIX_OPERATOR_BINDING_POWER);
+            if expression.is_none() {
+                return None;
+            }
+            return Some(JsAstExpression::UnaryOp(JsAstUnaryOp { op, expression: Rc::from(expression.unwrap()) }));
+        },
+        JsToken::PlusPlus | JsToken::MinusMinus => {
+            let op = if parser.next() == Some(JsToken::PlusPlus) { JsUpdateOp::Increment } else { JsUpdateOp::Decrement };
+            let target = parse_expression(parser, PREFIX_OPERATOR_BINDING_POWER);
+            if target.is_none() {
+                return None;
+            }
+            if !is_simple_assignment_target(target.as_ref().unwrap()) {
+                parser.log_error("invalid update target");
+                return None;
+            }
+            return Some(JsAstExpression::Update(JsAstUpdate { op, is_prefix: true, target: Rc::from(target.unwrap()) }));
+        },
+        JsToken::KeyWordTrue | JsToken::KeyWordFalse => {
+            let value = parser.next() == Some(JsToken::KeyWordTrue);
+            return Some(JsAstExpression::BooleanLiteral(value));
+        },
+        JsToken::KeyWordNull => {
+            parser.next();
+            return Some(JsAstExpression::NullLiteral);
+        },
+        JsToken::OpenParenthesis => {
+            parser.next();
+            let expression = parse_expression(parser, 0);
+            if expression.is_none() || !parser.expect(JsToken::CloseParenthesis) {
+                return None;
+            }
+            return expression;
+        },
+        JsToken::KeyWordThis => {
+            parser.next();
+            return Some(JsAstExpression::This);
+        },
+        JsToken::KeyWordNew => {
+            return parse_object_creation(parser);
+        },
+        JsToken::KeyWordFunction => {
+            let function_expression = parse_function_expression(parser, false);
+            if function_expression.is_none() {
+                return None;
+            }
+            return Some(JsAstExpression::Function(function_expression.unwrap()));
+        },
+        JsToken::OpenBracket => {
+            parser.next();
+            let mut elements = Vec::new();
+            while !parser.next_is(JsToken::CloseBracket) {
+                if parser.next_is(JsToken::Comma) {
+                    parser.next();
+                    elements.push(None); //a hole, as in "[1, , 3]"
+                    continue;
+                }
+
+                let element = parse_list_element(parser);
+                if element.is_none() {
+                    return None;
+                }
+                elements.push(element);
 
-    return None;
+                //a trailing comma after the last element is allowed, and does not add a hole
+                if !parser.next_is(JsToken::CloseBracket) && !parser.expect(JsToken::Comma) {
+                    return None;
+                }
+            }
+            parser.next(); //consume the close bracket
+            return Some(JsAstExpression::ArrayLiteral(elements));
+        },
+        JsToken::OpenBrace => {
+            let object_literal = parse_object_literal(parser);
+            if object_literal.is_none() {
+                return None;
+            }
+            return Some(JsAstExpression::ObjectLiteral(object_literal.unwrap()));
+        },
+        _ => {
+            parser.log_error("unexpected token");
+            return None;
+        },
+    }
 }
 
 
-fn parse_object_literal(iterator: &mut JsParserSliceIterator, tokens: &Vec<JsTokenWithLocation>,
-    masked_token_types: &Vec<JsToken>) -> Option<JsAstObjectLiteral> {
-    let mut object_properties = Vec::new();
+fn parse_call_arguments(parser: &mut JsParser) -> Option<Vec<JsAstExpression>> {
+    //the open parenthesis is already consumed
+    let mut arguments = Vec::new();
+
+    while !parser.next_is(JsToken::CloseParenthesis) {
+        let argument = parse_list_element(parser);
+        if argument.is_none() {
+            return None;
+        }
+        arguments.push(argument.unwrap());
+
+        if !parser.next_is(JsToken::CloseParenthesis) && !parser.expect(JsToken::Comma) {
+            return None;
+        }
+    }
+    parser.next(); //consume the close parenthesis
+
+    return Some(arguments);
+}
 
-    let mut iterator = iterator.build_iterator_between_tokens(masked_token_types, JsToken::OpenBrace, JsToken::CloseBrace).unwrap();
-    let token_types = tokens.iter().map(|token| token.token.clone()).collect::<Vec<_>>();
-    let masked_token_types = mask_token_types(&mut iterator, &token_types);
 
-    let mut last_element_seen = false;
-    while !last_element_seen {
+fn parse_list_element(parser: &mut JsParser) -> Option<JsAstExpression> {
+    //an element of an array literal or an argument of a call, which can be spread, as in "f(...xs)"
+    if parser.next_is(JsToken::DotDotDot) {
+        parser.next();
+        let expression = parse_expression(parser, 0);
+        if expression.is_none() {
+            return None;
+        }
+        return Some(JsAstExpression::Spread(Rc::from(expression.unwrap())));
+    }
+    return parse_expression(parser, 0);
+}
 
-        let possible_property_iterator = iterator.split_and_advance_until_next_token(&masked_token_types, JsToken::Comma);
 
-        let mut property_iterator = if possible_property_iterator.is_some() {
-            possible_property_iterator.unwrap()
-        } else {
-            last_element_seen = true;
-            JsParserSliceIterator { next_idx: iterator.next_idx, end_idx: iterator.end_idx }
-        };
+fn parse_object_literal(parser: &mut JsParser) -> Option<JsAstObjectLiteral> {
+    parser.next(); //consume the open brace
+    let mut object_properties = Vec::new();
 
-        let mut property_key_iterator = property_iterator.split_and_advance_until_next_token(&masked_token_types, JsToken::Colon).unwrap();
-
-        let key_expression = {
-            let possible_literal_key = property_key_iterator.read_only_literal_string(tokens);
-            if possible_literal_key.is_some() {
-                JsAstExpression::StringLiteral(possible_literal_key.unwrap())
-            } else {
-                // An identifier seen in an object literal is not an identifier, but a literal string without quotes
-                let possible_ident = property_key_iterator.read_only_identifier(tokens);
-                if possible_ident.is_some() {
-                    JsAstExpression::StringLiteral(possible_ident.unwrap())
-                } else {
-                    todo!();  //TODO: give an error
-                }
+    while !parser.next_is(JsToken::CloseBrace) {
+        //get, set and async are only modifiers when another property name follows, otherwise they are the property name themselves
+        let mut kind = JsPropertyKind::Value;
+        let mut is_async = false;
+        if let Some(JsToken::Identifier(name)) = parser.peek() {
+            let modifies_next_name = !matches!(parser.peek_second(), Some(JsToken::Colon) | Some(JsToken::OpenParenthesis) |
+                                                                     Some(JsToken::Comma) | Some(JsToken::CloseBrace) | None);
+            if modifies_next_name && (name == "get" || name == "set" || name == "async") {
+                kind = match name.as_str() {
+                    "get" => JsPropertyKind::Getter,
+                    "set" => JsPropertyKind::Setter,
+                    _ => JsPropertyKind::Value,
+                };
+                is_async = name == "async";
+                parser.next();
             }
-        };
+        }
 
-        let value_expression = parse_expression(&mut property_iterator, tokens);
+        let shorthand_name = match parser.peek() {
+            Some(JsToken::Identifier(name)) => Some(name.clone()),
+            _ => None,
+        };
+        let key_expression = parse_property_name(parser);
+        if key_expression.is_none() {
+            return None;
+        }
 
-        let value_expression = match value_expression {
-            Some(ast) => {
-                ast
-            },
-            _ => { return None },
+        let value_expression = if parser.next_is(JsToken::OpenParenthesis) {
+            //a method, as in "{f() {}}", or a getter or setter
+            let arguments_and_body = parse_function_arguments_and_body(parser, is_async);
+            if arguments_and_body.is_none() {
+                return None;
+            }
+            let (arguments, rest_argument, body) = arguments_and_body.unwrap();
+            JsAstExpression::Function(JsAstFunctionExpression { name: None, arguments, rest_argument, script: Rc::from(body), is_async })
+        } else if !matches!(kind, JsPropertyKind::Value) || is_async {
+            parser.log_error("expected a function");
+            return None;
+        } else if shorthand_name.is_some() && (parser.next_is(JsToken::Comma) || parser.next_is(JsToken::CloseBrace)) {
+            //the shorthand "{a}" means "{a: a}"
+            JsAstExpression::Identifier(JsAstIdentifier { name: shorthand_name.unwrap() })
+        } else {
+            if !parser.expect(JsToken::Colon) {
+                return None;
+            }
+            let value_expression = parse_expression(parser, 0);
+            if value_expression.is_none() {
+                return None;
+            }
+            value_expression.unwrap()
         };
 
-        object_properties.push( (key_expression, value_expression) );
+        object_properties.push(JsAstObjectLiteralMember { key: key_expression.unwrap(), value: value_expression, kind });
+
+        //a trailing comma after the last property is allowed
+        if !parser.next_is(JsToken::CloseBrace) && !parser.expect(JsToken::Comma) {
+            return None;
+        }
     }
+    parser.next(); //consume the close brace
 
     return Some(JsAstObjectLiteral { members: object_properties });
 }
+
+
+fn parse_property_name(parser: &mut JsParser) -> Option<JsAstExpression> {
+    //an identifier (or keyword) seen as key in an object literal is not an identifier, but a literal string without quotes
+    let keyword_name = parser.peek().and_then(|token| token.keyword_text());
+    if keyword_name.is_some() {
+        parser.next();
+        return Some(JsAstExpression::StringLiteral(String::from(keyword_name.unwrap())));
+    }
+
+    match parser.next() {
+        Some(JsToken::LiteralString(key)) | Some(JsToken::Identifier(key)) => { return Some(JsAstExpression::StringLiteral(key)); },
+        Some(JsToken::Number(key)) => {
+            //number keys are used in their normal form, so "{1.0: x}" has the key "1"
+            if parse_numeric_literal(&key).is_none() {
+                parser.log_error("invalid number");
+                return None;
+            }
+            return Some(JsAstExpression::NumericLiteral(key));
+        },
+        Some(JsToken::OpenBracket) => {
+            let key_expression = parse_expression(parser, 0);
+            if key_expression.is_none() || !parser.expect(JsToken::CloseBracket) {
+                return None;
+            }
+            return key_expression;
+        },
+        _ => {
+            parser.log_error("invalid property name in object literal");
+            return None;
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use super::js_ast::{call_function, get_property, JsCompletion};
+use super::js_execution_context::{
+    JsAddress,
+    JsBuiltinFunction,
+    JsError,
+    JsFunction,
+    JsInternalSlot,
+    JsObject,
+    JsValue,
+};
+use super::js_interpreter::JsInterpreter;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub enum JsPromiseStatus {
+    Pending,
+    Fulfilled(JsValue),
+    Rejected(JsValue),
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct JsPromiseReaction {
+    handler: Option<JsFunction>,
+    derived_promise: JsAddress, //the promise returned by then(), which settles with the result of the handler
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct JsPromiseState {
+    status: JsPromiseStatus,
+    fulfill_reactions: Vec<JsPromiseReaction>,
+    reject_reactions: Vec<JsPromiseReaction>,
+    is_handled: bool, //whether anything listens for a rejection, when not, we report the rejection as uncaught
+}
+impl JsPromiseState {
+    fn new() -> JsPromiseState {
+        return JsPromiseState { status: JsPromiseStatus::Pending, fulfill_reactions: Vec::new(), reject_reactions: Vec::new(), is_handled: false };
+    }
+}
+
+
+pub enum JsMicrotask {
+    PromiseReaction(JsPromiseReaction, JsValue, bool), //the bool is true when the promise was rejected
+    ResolveThenable(JsAddress, JsValue, JsFunction), //the promise follows the thenable, by calling its then function
+}
+
+
+fn get_promise_state(js_interpreter: &JsInterpreter, promise: JsAddress) -> Option<&JsPromiseState> {
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value(promise) {
+        if let Some(JsInternalSlot::Promise(state)) = object.internal_slot() {
+            return Some(state);
+        }
+    }
+    return None;
+}
+
+
+fn get_promise_state_mut(js_interpreter: &mut JsInterpreter, promise: JsAddress) -> Option<&mut JsPromiseState> {
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value_mut(promise) {
+        if let Some(JsInternalSlot::Promise(state)) = object.internal_slot_mut() {
+            return Some(state);
+        }
+    }
+    return None;
+}
+
+
+fn promise_address(js_interpreter: &JsInterpreter, value: &JsValue) -> Option<JsAddress> {
+    //returns the address when the value is a promise (the value should not be dereferenced, we need the address)
+    if let JsValue::Address(address) = value {
+        if get_promise_state(js_interpreter, *address).is_some() {
+            return Some(*address);
+        }
+    }
+    return None;
+}
+
+
+pub fn new_promise(js_interpreter: &mut JsInterpreter) -> JsAddress {
+    let mut object = JsObject::new_with_prototype(Some(js_interpreter.promise_prototype_address));
+    object.set_internal_slot(JsInternalSlot::Promise(Box::new(JsPromiseState::new())));
+    return js_interpreter.add_new_value(JsValue::Object(object));
+}
+
+
+pub fn init_promise_object(js_interpreter: &mut JsInterpreter, object_address: JsAddress, executor: JsValue) -> JsValue {
+    //the executor runs right away, with the functions to resolve and reject the new promise
+    let executor = match executor.deref(js_interpreter) {
+        JsValue::Function(executor) => executor,
+        _ => {
+            js_interpreter.throw_error(JsError::TypeError, "Promise resolver is not a function");
+            return JsValue::Undefined;
+        }
+    };
+
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value_mut(object_address) {
+        object.set_internal_slot(JsInternalSlot::Promise(Box::new(JsPromiseState::new())));
+    }
+
+    let (resolve_function, reject_function) = make_resolving_functions(object_address);
+    call_function(js_interpreter, executor, JsValue::Undefined, vec![JsValue::Function(resolve_function), JsValue::Function(reject_function.clone())]);
+
+    //an exception in the executor rejects the promise (unless it was already resolved)
+    if let Some(exception) = js_interpreter.take_pending_exception() {
+        call_function(js_interpreter, reject_function, JsValue::Undefined, vec![exception]);
+    }
+    return JsValue::Address(object_address);
+}
+
+
+fn make_resolving_functions(promise: JsAddress) -> (JsFunction, JsFunction) {
+    //only the first call to either of these does anything, so they share the flag for that
+    let already_resolved = Rc::new(Cell::new(false));
+    let resolve_function = JsFunction::new_builtin(JsBuiltinFunction::PromiseResolver(promise, false, already_resolved.clone()));
+    let reject_function = JsFunction::new_builtin(JsBuiltinFunction::PromiseResolver(promise, true, already_resolved));
+    return (resolve_function, reject_function);
+}
+
+
+pub fn resolve_promise(js_interpreter: &mut JsInterpreter, promise: JsAddress, resolution: JsValue) {
+    if matches!(resolution, JsValue::Address(address) if address == promise) {
+        let error = js_interpreter.make_error(JsError::TypeError, "a promise can't be resolved with itself");
+        reject_promise(js_interpreter, promise, error);
+        return;
+    }
+
+    //when resolved with something that has a then method (like another promise), the promise follows that, which we start in a microtask
+    let resolution_value = resolution.clone().deref(js_interpreter);
+    if matches!(resolution_value, JsValue::Object(_) | JsValue::Function(_)) {
+        let then = get_property(js_interpreter, resolution_value, &String::from("then")).deref(js_interpreter);
+        if let Some(exception) = js_interpreter.take_pending_exception() {
+            reject_promise(js_interpreter, promise, exception);
+            return;
+        }
+        if let JsValue::Function(then_function) = then {
+            js_interpreter.enqueue_microtask(JsMicrotask::ResolveThenable(promise, resolution, then_function));
+            return;
+        }
+    }
+
+    settle_promise(js_interpreter, promise, resolution, false);
+}
+
+
+pub fn reject_promise(js_interpreter: &mut JsInterpreter, promise: JsAddress, reason: JsValue) {
+    settle_promise(js_interpreter, promise, reason, true);
+}
+
+
+fn settle_promise(js_interpreter: &mut JsInterpreter, promise: JsAddress, value: JsValue, is_rejection: bool) {
+    let state = get_promise_state_mut(js_interpreter, promise);
+    if state.is_none() {
+        return;
+    }
+    let state = state.unwrap();
+    if !matches!(state.status, JsPromiseStatus::Pending) {
+        return;
+    }
+
+    let fulfill_reactions = std::mem::take(&mut state.fulfill_reactions);
+    let reject_reactions = std::mem::take(&mut state.reject_reactions);
+    let reactions = if is_rejection { reject_reactions } else { fulfill_reactions };
+    state.status = if is_rejection { JsPromiseStatus::Rejected(value.clone()) } else { JsPromiseStatus::Fulfilled(value.clone()) };
+    let is_handled = state.is_handled;
+
+    if is_rejection && !is_handled {
+        js_interpreter.add_possibly_unhandled_rejection(promise);
+    }
+    for reaction in reactions {
+        js_interpreter.enqueue_microtask(JsMicrotask::PromiseReaction(reaction, value.clone(), is_rejection));
+    }
+}
+
+
+fn perform_then(js_interpreter: &mut JsInterpreter, promise: JsAddress, on_fulfilled: Option<JsFunction>, on_rejected: Option<JsFunction>,
+                derived_promise: JsAddress) {
+    let fulfill_reaction = JsPromiseReaction { handler: on_fulfilled, derived_promise };
+    let reject_reaction = JsPromiseReaction { handler: on_rejected, derived_promise };
+
+    let state = get_promise_state_mut(js_interpreter, promise).unwrap();
+    state.is_handled = true;
+
+    //when the promise is already settled, the handler still runs later, never right away
+    match state.status.clone() {
+        JsPromiseStatus::Pending => {
+            state.fulfill_reactions.push(fulfill_reaction);
+            state.reject_reactions.push(reject_reaction);
+        },
+        JsPromiseStatus::Fulfilled(value) => {
+            js_interpreter.enqueue_microtask(JsMicrotask::PromiseReaction(fulfill_reaction, value, false));
+        },
+        JsPromiseStatus::Rejected(reason) => {
+            js_interpreter.enqueue_microtask(JsMicrotask::PromiseReaction(reject_reaction, reason, true));
+        },
+    }
+}
+
+
+pub fn unhandled_rejection_reason(js_interpreter: &JsInterpreter, promise: JsAddress) -> Option<JsValue> {
+    //returns the reason when the promise is rejected and nothing handled that
+    let state = get_promise_state(js_interpreter, promise);
+    if state.is_none() || state.unwrap().is_handled {
+        return None;
+    }
+    if let JsPromiseStatus::Rejected(reason) = &state.unwrap().status {
+        return Some(reason.clone());
+    }
+    return None;
+}
+
+
+pub fn run_microtask(js_interpreter: &mut JsInterpreter, microtask: JsMicrotask) {
+    match microtask {
+        JsMicrotask::PromiseReaction(reaction, argument, is_rejection) => {
+            if reaction.handler.is_none() {
+                //without a handler, the value or reason passes on to the next promise in the chain
+                if is_rejection {
+                    reject_promise(js_interpreter, reaction.derived_promise, argument);
+                } else {
+                    resolve_promise(js_interpreter, reaction.derived_promise, argument);
+                }
+                return;
+            }
+
+            let result = call_function(js_interpreter, reaction.handler.unwrap(), JsValue::Undefined, vec![argument]);
+            match js_interpreter.take_pending_exception() {
+                Some(exception) => { reject_promise(js_interpreter, reaction.derived_promise, exception); },
+                None => { resolve_promise(js_interpreter, reaction.derived_promise, result); },
+            }
+        },
+        JsMicrotask::ResolveThenable(promise, thenable, then_function) => {
+            let (resolve_function, reject_function) = make_resolving_functions(promise);
+            call_function(js_interpreter, then_function, thenable, vec![JsValue::Function(resolve_function), JsValue::Function(reject_function.clone())]);
+
+            if let Some(exception) = js_interpreter.take_pending_exception() {
+                call_function(js_interpreter, reject_function, JsValue::Undefined, vec![exception]);
+            }
+        },
+    }
+}
+
+
+pub fn promise_resolve(js_interpreter: &mut JsInterpreter, value: JsValue) -> JsAddress {
+    //promises are returned as they are, other values are wrapped in a new promise
+    let existing_promise = promise_address(js_interpreter, &value);
+    if existing_promise.is_some() {
+        return existing_promise.unwrap();
+    }
+    let promise = new_promise(js_interpreter);
+    resolve_promise(js_interpreter, promise, value);
+    return promise;
+}
+
+
+pub fn settle_with_completion(js_interpreter: &mut JsInterpreter, promise: JsAddress, completion: JsCompletion) {
+    //async functions always return a promise, that settles with what the function body returned or threw
+    match completion {
+        JsCompletion::Return(value) => { resolve_promise(js_interpreter, promise, value); },
+        JsCompletion::Throw(exception) => { reject_promise(js_interpreter, promise, exception); },
+        _ => { resolve_promise(js_interpreter, promise, JsValue::Undefined); },
+    }
+}
+
+
+pub fn add_reactions(js_interpreter: &mut JsInterpreter, promise: JsAddress, on_fulfilled: JsFunction, on_rejected: JsFunction) {
+    //like then(), for our own handlers, which don't need the promise then() returns
+    let derived_promise = new_promise(js_interpreter);
+    perform_then(js_interpreter, promise, Some(on_fulfilled), Some(on_rejected), derived_promise);
+}
+
+
+pub fn call_promise_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    let mut arguments = arguments.into_iter();
+    let first_argument = arguments.next().unwrap_or(JsValue::Undefined);
+
+    match builtin {
+        JsBuiltinFunction::PromiseConstructor => {
+            js_interpreter.throw_error(JsError::TypeError, "Promise constructor cannot be invoked without 'new'");
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::PromiseResolver(promise, is_reject, already_resolved) => {
+            if already_resolved.get() {
+                return JsValue::Undefined;
+            }
+            already_resolved.set(true);
+
+            if is_reject {
+                reject_promise(js_interpreter, promise, first_argument);
+            } else {
+                resolve_promise(js_interpreter, promise, first_argument);
+            }
+            return JsValue::Undefined;
+        },
+        JsBuiltinFunction::PromiseThen | JsBuiltinFunction::PromiseCatch => {
+            let promise = promise_address(js_interpreter, &this_value);
+            if promise.is_none() {
+                js_interpreter.throw_error(JsError::TypeError, "then and catch can only be called on a promise");
+                return JsValue::Undefined;
+            }
+
+            //handlers that are not functions are ignored
+            let as_handler = |js_interpreter: &JsInterpreter, value: JsValue| {
+                match value.deref(js_interpreter) {
+                    JsValue::Function(function) => Some(function),
+                    _ => None,
+                }
+            };
+            let (on_fulfilled, on_rejected) = match builtin {
+                JsBuiltinFunction::PromiseThen => {
+                    let on_rejected = arguments.next().unwrap_or(JsValue::Undefined);
+                    (as_handler(js_interpreter, first_argument), as_handler(js_interpreter, on_rejected))
+                },
+                _ => (None, as_handler(js_interpreter, first_argument)),
+            };
+
+            let derived_promise = new_promise(js_interpreter);
+            perform_then(js_interpreter, promise.unwrap(), on_fulfilled, on_rejected, derived_promise);
+            return JsValue::Address(derived_promise);
+        },
+        JsBuiltinFunction::PromiseStaticResolve => {
+            return JsValue::Address(promise_resolve(js_interpreter, first_argument));
+        },
+        JsBuiltinFunction::PromiseStaticReject => {
+            let promise = new_promise(js_interpreter);
+            reject_promise(js_interpreter, promise, first_argument);
+            return JsValue::Address(promise);
+        },
+        _ => { panic!("not a promise function"); },
+    }
+}
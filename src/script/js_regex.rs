@@ -0,0 +1,667 @@
+//this is a backtracking regex engine for the javascript regex syntax (we can't use the regex crate, since it does not support
+//  things like backreferences and lookahead, which scripts use)
+
+//TODO: indices are in chars, but javascript uses utf-16 code units
+
+
+pub type JsRegexCaptures = Vec<Option<(usize, usize)>>; //the start and end of the whole match, and then of each group (if it matched)
+
+
+#[derive(Debug)]
+enum RegexClassItem {
+    Char(char),
+    Range(char, char),
+    Digit,
+    NotDigit,
+    Word,
+    NotWord,
+    Space,
+    NotSpace,
+}
+
+
+#[derive(Debug)]
+enum RegexNode {
+    Empty,
+    Char(char),
+    AnyChar,
+    Class(Vec<RegexClassItem>, bool), //the bool is true for negated classes, as in [^a-z]
+    LineStart,
+    LineEnd,
+    WordBoundary(bool), //the bool is false for \B, which matches where there is no word boundary
+    Group(Box<RegexNode>, Option<usize>), //the index of the capture, or None for groups that don't capture, as in (?:a)
+    LookAhead(Box<RegexNode>, bool), //the bool is true for negative lookahead, as in (?!a)
+    BackReference(usize),
+    Sequence(Vec<RegexNode>),
+    Alternation(Vec<RegexNode>),
+    Repeat(Box<RegexNode>, usize, Option<usize>, bool), //the minimum and maximum count, and whether it is greedy
+}
+
+
+#[derive(Debug)]
+enum RegexInstruction {
+    //the regex is compiled into these, which we run with an explicit stack for backtracking, so long inputs can't overflow the rust stack
+    Char(char),
+    AnyChar,
+    Class(Vec<RegexClassItem>, bool),
+    LineStart,
+    LineEnd,
+    WordBoundary(bool),
+    BackReference(usize),
+    Split(usize, usize), //continues at the first instruction, and when that fails, at the second one
+    Jump(usize),
+    SetRegister(usize), //stores the position in the register
+    SetCapture(usize, usize), //sets the capture to the text from the position in the register to the current one
+    RepeatStart(usize), //sets the counter in the register to 0
+    RepeatLoop { counter: usize, min: usize, max: Option<usize>, greedy: bool, exit: usize }, //the repeated part follows this instruction
+    RepeatEnd { counter: usize, mark: usize, min: usize, loop_start: usize }, //the mark register has the position the repetition started at
+    LookAhead(bool, usize), //the lookahead follows this instruction, ending with Match, after that we continue at the second value
+    Match,
+}
+
+
+enum RegexBacktrack {
+    Resume(usize, usize), //the instruction and the position to try next
+    RestoreRegister(usize, usize),
+    RestoreCapture(usize, Option<(usize, usize)>),
+    RestoreCaptures(JsRegexCaptures),
+}
+
+
+const MAX_MATCH_STEPS: usize = 10_000_000; //a regex that needs more steps than this (like when it backtracks exponentially) does not match
+
+
+#[derive(Debug)]
+pub struct JsRegex {
+    pub source: String,
+    pub flags: String,
+    pub global: bool,
+    pub ignore_case: bool,
+    pub multiline: bool,
+    pub dot_all: bool,
+    pub sticky: bool,
+    pub group_names: Vec<(String, usize)>, //the names of the named groups, with the index of their capture
+    program: Vec<RegexInstruction>,
+    register_count: usize,
+    group_count: usize,
+}
+impl JsRegex {
+    pub fn new(source: &str, flags: &str) -> Result<JsRegex, String> {
+        for (idx, flag) in flags.char_indices() {
+            if !"dgimsuvy".contains(flag) || flags[idx + flag.len_utf8()..].contains(flag) {
+                return Err(format!("invalid flags '{}'", flags));
+            }
+        }
+
+        let mut parser = RegexParser { chars: source.chars().collect(), next_idx: 0, group_count: 0, group_names: Vec::new() };
+        let root = parser.parse_alternation()?;
+        if parser.next_idx < parser.chars.len() {
+            return Err(String::from("unmatched ')'"));
+        }
+
+        let mut compiler = RegexCompiler { program: Vec::new(), register_count: 0 };
+        compiler.compile(root);
+        compiler.program.push(RegexInstruction::Match);
+
+        return Ok(JsRegex {
+            source: String::from(source),
+            flags: String::from(flags),
+            global: flags.contains('g'),
+            ignore_case: flags.contains('i'),
+            multiline: flags.contains('m'),
+            dot_all: flags.contains('s'),
+            sticky: flags.contains('y'),
+            group_names: parser.group_names,
+            program: compiler.program,
+            register_count: compiler.register_count,
+            group_count: parser.group_count,
+        });
+    }
+
+    pub fn find(&self, input: &Vec<char>, start: usize) -> Option<JsRegexCaptures> {
+        //finds the first match that starts at or after start (or only at start for sticky regexes)
+        let mut steps = 0;
+        let mut match_start = start;
+        while match_start <= input.len() {
+            let mut captures = vec![None; self.group_count + 1];
+            let mut registers = vec![0; self.register_count];
+            let match_end = self.run(0, match_start, input, &mut captures, &mut registers, &mut steps);
+
+            if match_end.is_some() {
+                captures[0] = Some((match_start, match_end.unwrap()));
+                return Some(captures);
+            }
+            if self.sticky || steps > MAX_MATCH_STEPS {
+                return None;
+            }
+            match_start += 1;
+        }
+        return None;
+    }
+
+    fn run(&self, start_instruction: usize, start_pos: usize, input: &Vec<char>, captures: &mut JsRegexCaptures, registers: &mut Vec<usize>,
+           steps: &mut usize) -> Option<usize> {
+        //runs the program from the instruction until it reaches Match, and returns the position there, or None when all ways to match failed
+        let mut backtrack_stack = Vec::new();
+        let mut instruction_idx = start_instruction;
+        let mut pos = start_pos;
+
+        loop {
+            *steps += 1;
+            if *steps > MAX_MATCH_STEPS {
+                return None;
+            }
+
+            let can_continue = match &self.program[instruction_idx] {
+                RegexInstruction::Char(_) | RegexInstruction::AnyChar | RegexInstruction::Class(_, _) => {
+                    let matches = pos < input.len() && self.char_matches(&self.program[instruction_idx], input[pos]);
+                    if matches {
+                        pos += 1;
+                    }
+                    matches
+                },
+                RegexInstruction::LineStart => { pos == 0 || (self.multiline && is_line_terminator(input[pos - 1])) },
+                RegexInstruction::LineEnd => { pos == input.len() || (self.multiline && is_line_terminator(input[pos])) },
+                RegexInstruction::WordBoundary(expected) => {
+                    let word_before = pos > 0 && is_word_char(input[pos - 1]);
+                    let word_after = pos < input.len() && is_word_char(input[pos]);
+                    (word_before != word_after) == *expected
+                },
+                RegexInstruction::BackReference(capture_idx) => {
+                    //a reference to a group that did not match (or does not exist), matches the empty string
+                    let capture = captures.get(*capture_idx).copied().flatten();
+                    let (capture_start, capture_end) = capture.unwrap_or((0, 0));
+                    let length = capture_end - capture_start;
+                    let matches = pos + length <= input.len() && (0..length).all(|idx| self.chars_are_equal(input[pos + idx], input[capture_start + idx]));
+                    if matches {
+                        pos += length;
+                    }
+                    matches
+                },
+                RegexInstruction::Split(first, second) => {
+                    backtrack_stack.push(RegexBacktrack::Resume(*second, pos));
+                    instruction_idx = *first;
+                    continue;
+                },
+                RegexInstruction::Jump(target) => {
+                    instruction_idx = *target;
+                    continue;
+                },
+                RegexInstruction::SetRegister(register) => {
+                    backtrack_stack.push(RegexBacktrack::RestoreRegister(*register, registers[*register]));
+                    registers[*register] = pos;
+                    true
+                },
+                RegexInstruction::SetCapture(capture_idx, register) => {
+                    backtrack_stack.push(RegexBacktrack::RestoreCapture(*capture_idx, captures[*capture_idx]));
+                    captures[*capture_idx] = Some((registers[*register], pos));
+                    true
+                },
+                RegexInstruction::RepeatStart(counter) => {
+                    backtrack_stack.push(RegexBacktrack::RestoreRegister(*counter, registers[*counter]));
+                    registers[*counter] = 0;
+                    true
+                },
+                RegexInstruction::RepeatLoop { counter, min, max, greedy, exit } => {
+                    let count = registers[*counter];
+                    let can_repeat = max.is_none() || count < max.unwrap();
+                    if count < *min {
+                        instruction_idx += 1;
+                    } else if !can_repeat {
+                        instruction_idx = *exit;
+                    } else if *greedy {
+                        backtrack_stack.push(RegexBacktrack::Resume(*exit, pos));
+                        instruction_idx += 1;
+                    } else {
+                        backtrack_stack.push(RegexBacktrack::Resume(instruction_idx + 1, pos));
+                        instruction_idx = *exit;
+                    }
+                    continue;
+                },
+                RegexInstruction::RepeatEnd { counter, mark, min, loop_start } => {
+                    //once we have the minimum, repeating an empty match can't lead anywhere new (and would loop forever)
+                    let count = registers[*counter];
+                    if count >= *min && pos == registers[*mark] {
+                        false
+                    } else {
+                        backtrack_stack.push(RegexBacktrack::RestoreRegister(*counter, count));
+                        registers[*counter] = count + 1;
+                        instruction_idx = *loop_start;
+                        continue;
+                    }
+                },
+                RegexInstruction::LookAhead(negated, continue_at) => {
+                    let mut lookahead_captures = captures.clone();
+                    let mut lookahead_registers = registers.clone();
+                    let matched = self.run(instruction_idx + 1, pos, input, &mut lookahead_captures, &mut lookahead_registers, steps).is_some();
+                    if matched == *negated {
+                        false
+                    } else {
+                        if !negated {
+                            backtrack_stack.push(RegexBacktrack::RestoreCaptures(std::mem::replace(captures, lookahead_captures)));
+                        }
+                        instruction_idx = *continue_at;
+                        continue;
+                    }
+                },
+                RegexInstruction::Match => { return Some(pos); },
+            };
+
+            if can_continue {
+                instruction_idx += 1;
+                continue;
+            }
+
+            //this way of matching failed, so we undo what it did, up to the last point where we can try another way
+            loop {
+                match backtrack_stack.pop() {
+                    None => { return None; },
+                    Some(RegexBacktrack::Resume(next_instruction_idx, next_pos)) => {
+                        instruction_idx = next_instruction_idx;
+                        pos = next_pos;
+                        break;
+                    },
+                    Some(RegexBacktrack::RestoreRegister(register, value)) => { registers[register] = value; },
+                    Some(RegexBacktrack::RestoreCapture(capture_idx, capture)) => { captures[capture_idx] = capture; },
+                    Some(RegexBacktrack::RestoreCaptures(previous_captures)) => { *captures = previous_captures; },
+                }
+            }
+        }
+    }
+
+    fn char_matches(&self, instruction: &RegexInstruction, char: char) -> bool {
+        match instruction {
+            RegexInstruction::Char(expected) => { return self.chars_are_equal(char, *expected); },
+            RegexInstruction::AnyChar => { return self.dot_all || !is_line_terminator(char); },
+            RegexInstruction::Class(items, negated) => { return self.class_matches(items, char) != *negated; },
+            _ => { panic!("not an instruction that matches a single char"); },
+        }
+    }
+
+    fn chars_are_equal(&self, one: char, two: char) -> bool {
+        if one == two {
+            return true;
+        }
+        return self.ignore_case && fold_case(one) == fold_case(two);
+    }
+
+    fn class_matches(&self, items: &Vec<RegexClassItem>, char: char) -> bool {
+        let chars_to_check = if self.ignore_case { vec![char, fold_case(char), char.to_uppercase().next().unwrap_or(char)] } else { vec![char] };
+
+        for item in items {
+            let item_matches = match item {
+                RegexClassItem::Char(item_char) => chars_to_check.iter().any(|char| self.chars_are_equal(*char, *item_char)),
+                RegexClassItem::Range(from, to) => chars_to_check.iter().any(|char| char >= from && char <= to),
+                RegexClassItem::Digit => char.is_ascii_digit(),
+                RegexClassItem::NotDigit => !char.is_ascii_digit(),
+                RegexClassItem::Word => is_word_char(char),
+                RegexClassItem::NotWord => !is_word_char(char),
+                RegexClassItem::Space => char.is_whitespace() || char == '\u{feff}',
+                RegexClassItem::NotSpace => !(char.is_whitespace() || char == '\u{feff}'),
+            };
+            if item_matches {
+                return true;
+            }
+        }
+        return false;
+    }
+}
+
+
+fn fold_case(char: char) -> char {
+    return char.to_lowercase().next().unwrap_or(char);
+}
+
+
+fn is_line_terminator(char: char) -> bool {
+    return char == '\n' || char == '\r' || char == '\u{2028}' || char == '\u{2029}';
+}
+
+
+fn is_word_char(char: char) -> bool {
+    return char.is_ascii_alphanumeric() || char == '_';
+}
+
+
+struct RegexCompiler {
+    program: Vec<RegexInstruction>,
+    register_count: usize,
+}
+impl RegexCompiler {
+    fn new_register(&mut self) -> usize {
+        self.register_count += 1;
+        return self.register_count - 1;
+    }
+
+    fn compile(&mut self, node: RegexNode) {
+        match node {
+            RegexNode::Empty => {},
+            RegexNode::Char(char) => { self.program.push(RegexInstruction::Char(char)); },
+            RegexNode::AnyChar => { self.program.push(RegexInstruction::AnyChar); },
+            RegexNode::Class(items, negated) => { self.program.push(RegexInstruction::Class(items, negated)); },
+            RegexNode::LineStart => { self.program.push(RegexInstruction::LineStart); },
+            RegexNode::LineEnd => { self.program.push(RegexInstruction::LineEnd); },
+            RegexNode::WordBoundary(expected) => { self.program.push(RegexInstruction::WordBoundary(expected)); },
+            RegexNode::BackReference(capture_idx) => { self.program.push(RegexInstruction::BackReference(capture_idx)); },
+            RegexNode::Group(inner, capture_idx) => {
+                if capture_idx.is_none() {
+                    self.compile(*inner);
+                    return;
+                }
+                let start_register = self.new_register();
+                self.program.push(RegexInstruction::SetRegister(start_register));
+                self.compile(*inner);
+                self.program.push(RegexInstruction::SetCapture(capture_idx.unwrap(), start_register));
+            },
+            RegexNode::LookAhead(inner, negated) => {
+                let lookahead_idx = self.program.len();
+                self.program.push(RegexInstruction::LookAhead(negated, 0));
+                self.compile(*inner);
+                self.program.push(RegexInstruction::Match);
+                self.program[lookahead_idx] = RegexInstruction::LookAhead(negated, self.program.len());
+            },
+            RegexNode::Sequence(nodes) => {
+                for node in nodes {
+                    self.compile(node);
+                }
+            },
+            RegexNode::Alternation(alternatives) => {
+                //every alternative but the last one splits off to the next one, and jumps to the end when it matched
+                let nr_of_alternatives = alternatives.len();
+                let mut jump_idxs = Vec::new();
+                for (alternative_idx, alternative) in alternatives.into_iter().enumerate() {
+                    if alternative_idx == nr_of_alternatives - 1 {
+                        self.compile(alternative);
+                        break;
+                    }
+                    let split_idx = self.program.len();
+                    self.program.push(RegexInstruction::Split(split_idx + 1, 0));
+                    self.compile(alternative);
+                    jump_idxs.push(self.program.len());
+                    self.program.push(RegexInstruction::Jump(0));
+                    self.program[split_idx] = RegexInstruction::Split(split_idx + 1, self.program.len());
+                }
+                for jump_idx in jump_idxs {
+                    self.program[jump_idx] = RegexInstruction::Jump(self.program.len());
+                }
+            },
+            RegexNode::Repeat(inner, min, max, greedy) => {
+                let counter = self.new_register();
+                let mark = self.new_register();
+                self.program.push(RegexInstruction::RepeatStart(counter));
+                let loop_start = self.program.len();
+                self.program.push(RegexInstruction::RepeatLoop { counter, min, max, greedy, exit: 0 });
+                self.program.push(RegexInstruction::SetRegister(mark));
+                self.compile(*inner);
+                self.program.push(RegexInstruction::RepeatEnd { counter, mark, min, loop_start });
+                self.program[loop_start] = RegexInstruction::RepeatLoop { counter, min, max, greedy, exit: self.program.len() };
+            },
+        }
+    }
+}
+
+
+struct RegexParser {
+    chars: Vec<char>,
+    next_idx: usize,
+    group_count: usize,
+    group_names: Vec<(String, usize)>,
+}
+impl RegexParser {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.next_idx).copied();
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek();
+        self.next_idx += 1;
+        return char;
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.next_idx += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn parse_alternation(&mut self) -> Result<RegexNode, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.parse_sequence()?);
+        }
+
+        if alternatives.len() == 1 {
+            return Ok(alternatives.pop().unwrap());
+        }
+        return Ok(RegexNode::Alternation(alternatives));
+    }
+
+    fn parse_sequence(&mut self) -> Result<RegexNode, String> {
+        let mut nodes = Vec::new();
+
+        while self.peek().is_some() && self.peek() != Some('|') && self.peek() != Some(')') {
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+
+        if nodes.is_empty() {
+            return Ok(RegexNode::Empty);
+        }
+        if nodes.len() == 1 {
+            return Ok(nodes.pop().unwrap());
+        }
+        return Ok(RegexNode::Sequence(nodes));
+    }
+
+    fn parse_quantifier(&mut self, atom: RegexNode) -> Result<RegexNode, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => { self.next(); (0, None) },
+            Some('+') => { self.next(); (1, None) },
+            Some('?') => { self.next(); (0, Some(1)) },
+            Some('{') => {
+                //a brace that does not form a valid quantifier, is just a normal character
+                let quantifier = self.parse_braced_quantifier();
+                if quantifier.is_none() {
+                    return Ok(atom);
+                }
+                quantifier.unwrap()
+            },
+            _ => { return Ok(atom); },
+        };
+        if max.is_some() && max.unwrap() < min {
+            return Err(String::from("numbers out of order in {} quantifier"));
+        }
+        if matches!(atom, RegexNode::LineStart | RegexNode::LineEnd | RegexNode::WordBoundary(_) | RegexNode::LookAhead(_, _)) {
+            return Err(String::from("nothing to repeat"));
+        }
+
+        let greedy = !self.eat('?');
+        return Ok(RegexNode::Repeat(Box::from(atom), min, max, greedy));
+    }
+
+    fn parse_braced_quantifier(&mut self) -> Option<(usize, Option<usize>)> {
+        let start_idx = self.next_idx;
+        self.next(); //the open brace
+
+        let min = self.parse_decimal();
+        if min.is_none() {
+            self.next_idx = start_idx;
+            return None;
+        }
+
+        //in the {n,} form there is no maximum
+        let max = if self.eat(',') { self.parse_decimal() } else { min };
+        if !self.eat('}') {
+            self.next_idx = start_idx;
+            return None;
+        }
+        return Some((min.unwrap(), max));
+    }
+
+    fn parse_decimal(&mut self) -> Option<usize> {
+        let mut number = String::new();
+        while self.peek().is_some() && self.peek().unwrap().is_ascii_digit() {
+            number.push(self.next().unwrap());
+        }
+        return number.parse::<usize>().ok();
+    }
+
+    fn parse_atom(&mut self) -> Result<RegexNode, String> {
+        match self.next().unwrap() {
+            '.' => { return Ok(RegexNode::AnyChar); },
+            '^' => { return Ok(RegexNode::LineStart); },
+            '$' => { return Ok(RegexNode::LineEnd); },
+            '[' => { return self.parse_class(); },
+            '\\' => { return self.parse_escape(); },
+            '(' => {
+                let mut capture_idx = None;
+                let mut lookahead = None;
+
+                if self.eat('?') {
+                    match self.next() {
+                        Some(':') => {},
+                        Some('=') => { lookahead = Some(false); },
+                        Some('!') => { lookahead = Some(true); },
+                        Some('<') => {
+                            let mut name = String::new();
+                            while self.peek().is_some() && (self.peek().unwrap().is_alphanumeric() || self.peek() == Some('_') || self.peek() == Some('$')) {
+                                name.push(self.next().unwrap());
+                            }
+                            if !self.eat('>') || name.is_empty() || name.starts_with(|first_char: char| first_char.is_ascii_digit()) {
+                                return Err(String::from("invalid capture group name"));
+                            }
+                            if self.group_names.iter().any(|(existing_name, _)| *existing_name == name) {
+                                return Err(String::from("duplicate capture group name"));
+                            }
+                            self.group_count += 1;
+                            capture_idx = Some(self.group_count);
+                            self.group_names.push((name, self.group_count));
+                        },
+                        _ => { return Err(String::from("invalid group")); },
+                    }
+                } else {
+                    self.group_count += 1;
+                    capture_idx = Some(self.group_count);
+                }
+
+                let inner = self.parse_alternation()?;
+                if !self.eat(')') {
+                    return Err(String::from("unterminated group"));
+                }
+
+                if lookahead.is_some() {
+                    return Ok(RegexNode::LookAhead(Box::from(inner), lookahead.unwrap()));
+                }
+                return Ok(RegexNode::Group(Box::from(inner), capture_idx));
+            },
+            '*' | '+' | '?' => { return Err(String::from("nothing to repeat")); },
+            char => { return Ok(RegexNode::Char(char)); },
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<RegexNode, String> {
+        //the backslash is already consumed
+        match self.peek() {
+            None => { return Err(String::from("\\ at end of pattern")); },
+            Some('b') => { self.next(); return Ok(RegexNode::WordBoundary(true)); },
+            Some('B') => { self.next(); return Ok(RegexNode::WordBoundary(false)); },
+            Some(char) if char.is_ascii_digit() && char != '0' => {
+                //the number does not fit when it is very long, which no pattern can have that many groups for
+                let group_idx = self.parse_decimal();
+                if group_idx.is_none() {
+                    return Err(String::from("invalid escape"));
+                }
+                return Ok(RegexNode::BackReference(group_idx.unwrap()));
+            },
+            _ => {},
+        }
+
+        let class_item = self.parse_class_escape()?;
+        return match class_item {
+            RegexClassItem::Char(char) => Ok(RegexNode::Char(char)),
+            _ => Ok(RegexNode::Class(vec![class_item], false)),
+        };
+    }
+
+    fn parse_class_escape(&mut self) -> Result<RegexClassItem, String> {
+        //these are the escapes that are valid both inside and outside a class, the backslash is already consumed
+        let char = self.next().unwrap();
+
+        let escaped_char = match char {
+            'd' => { return Ok(RegexClassItem::Digit); },
+            'D' => { return Ok(RegexClassItem::NotDigit); },
+            'w' => { return Ok(RegexClassItem::Word); },
+            'W' => { return Ok(RegexClassItem::NotWord); },
+            's' => { return Ok(RegexClassItem::Space); },
+            'S' => { return Ok(RegexClassItem::NotSpace); },
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'f' => '\u{000c}',
+            'v' => '\u{000b}',
+            '0' => '\0',
+            'x' => { self.parse_hex_escape(2)? },
+            'u' => { self.parse_hex_escape(4)? },
+            _ => char, //escaped special characters (like \. or \/) are just the character itself
+        };
+        return Ok(RegexClassItem::Char(escaped_char));
+    }
+
+    fn parse_hex_escape(&mut self, digit_count: usize) -> Result<char, String> {
+        let mut hex = String::new();
+        for _ in 0..digit_count {
+            match self.next() {
+                Some(char) if char.is_ascii_hexdigit() => { hex.push(char); },
+                _ => { return Err(String::from("invalid escape")); },
+            }
+        }
+        return char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).ok_or(String::from("invalid escape"));
+    }
+
+    fn parse_class(&mut self) -> Result<RegexNode, String> {
+        //the open bracket is already consumed
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+
+        loop {
+            let item = match self.next() {
+                None => { return Err(String::from("unterminated character class")); },
+                Some(']') => { break; },
+                Some('\\') if self.peek() == Some('b') => { self.next(); RegexClassItem::Char('\u{0008}') }, //inside a class, \b is a backspace
+                Some('\\') => {
+                    if self.peek().is_none() {
+                        return Err(String::from("\\ at end of pattern"));
+                    }
+                    self.parse_class_escape()?
+                },
+                Some(char) => RegexClassItem::Char(char),
+            };
+
+            //a dash between two characters makes a range, otherwise (like at the start or end) it is just a dash
+            if self.peek() == Some('-') && self.chars.get(self.next_idx + 1).is_some() && self.chars[self.next_idx + 1] != ']' {
+                if let RegexClassItem::Char(from) = item {
+                    self.next();
+                    let to = match self.next().unwrap() {
+                        '\\' => self.parse_class_escape()?,
+                        char => RegexClassItem::Char(char),
+                    };
+                    match to {
+                        RegexClassItem::Char(to) if to >= from => { items.push(RegexClassItem::Range(from, to)); },
+                        RegexClassItem::Char(_) => { return Err(String::from("range out of order in character class")); },
+                        _ => {
+                            items.push(RegexClassItem::Char(from));
+                            items.push(RegexClassItem::Char('-'));
+                            items.push(to);
+                        },
+                    }
+                    continue;
+                }
+            }
+            items.push(item);
+        }
+
+        return Ok(RegexNode::Class(items, negated));
+    }
+}
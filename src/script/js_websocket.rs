@@ -0,0 +1,189 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::network::is_mixed_content;
+use crate::network::url::Url;
+use crate::network::websocket::{WebSocketConnection, WebSocketEvent};
+
+use super::js_events::{self, DomEvent, DomEventDetails};
+use super::js_execution_context::{JsAddress, JsBuiltinFunction, JsError, JsInternalSlot, JsValue};
+use super::js_interpreter::JsInterpreter;
+
+
+//these are the values of readyState (and the constants with the same names on WebSocket)
+pub const READY_STATE_CONNECTING: u8 = 0;
+pub const READY_STATE_OPEN: u8 = 1;
+pub const READY_STATE_CLOSING: u8 = 2;
+pub const READY_STATE_CLOSED: u8 = 3;
+
+const MAX_CLOSE_REASON_BYTES: usize = 123; //the reason has to fit in a control frame, together with the code
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct JsWebSocketState {
+    url: Url,
+    ready_state: Cell<u8>,
+    connection: WebSocketConnection,
+}
+
+
+pub fn init_websocket_object(js_interpreter: &mut JsInterpreter, object_address: JsAddress, arguments: Vec<JsValue>) -> JsValue {
+    //the arguments should already be dereferenced
+    //TODO: we don't support the protocols argument yet
+    let url_string = arguments.first().cloned().unwrap_or(JsValue::Undefined).to_primitive(js_interpreter).to_js_string();
+
+    let (base_url, page_url) = match js_interpreter.document.as_ref() {
+        Some(document) => (Some(document.borrow().base_url.clone()), Some(document.borrow().page_url.clone())),
+        None => (None, None),
+    };
+    let mut url = Url::from_base_url(&url_string, base_url.as_ref());
+
+    //http urls are allowed as well, since they are what relative urls resolve to, they connect to the same server
+    match url.scheme.as_str() {
+        "http" => { url.scheme = String::from("ws"); },
+        "https" => { url.scheme = String::from("wss"); },
+        "ws" | "wss" => {},
+        _ => {
+            js_interpreter.throw_error(JsError::SyntaxError, format!("the url '{}' is not a websocket url", url_string).as_str());
+            return JsValue::Undefined;
+        },
+    }
+    if !url.fragment.is_empty() {
+        js_interpreter.throw_error(JsError::SyntaxError, "websocket urls can't have a fragment");
+        return JsValue::Undefined;
+    }
+    if page_url.is_some() && is_mixed_content(page_url.as_ref().unwrap(), &url) {
+        js_interpreter.throw_error(JsError::SecurityError, "an insecure websocket can't be opened from a page loaded over https");
+        return JsValue::Undefined;
+    }
+
+    let state = Rc::new(JsWebSocketState { connection: WebSocketConnection::connect(&url), url, ready_state: Cell::new(READY_STATE_CONNECTING) });
+    if let Some(JsValue::Object(object)) = js_interpreter.get_value_mut(object_address) {
+        object.set_internal_slot(JsInternalSlot::WebSocket(state.clone()));
+    }
+    js_interpreter.websockets.push((object_address, state));
+    return JsValue::Address(object_address);
+}
+
+
+fn get_websocket_state(js_interpreter: &JsInterpreter, value: &JsValue) -> Option<Rc<JsWebSocketState>> {
+    if let JsValue::Address(address) = value {
+        if let Some(JsValue::Object(object)) = js_interpreter.get_value(*address) {
+            if let Some(JsInternalSlot::WebSocket(state)) = object.internal_slot() {
+                return Some(state.clone());
+            }
+        }
+    }
+    return None;
+}
+
+
+pub fn call_websocket_function(js_interpreter: &mut JsInterpreter, builtin: JsBuiltinFunction, this_value: JsValue, arguments: Vec<JsValue>) -> JsValue {
+    if matches!(builtin, JsBuiltinFunction::WebSocketConstructor) {
+        js_interpreter.throw_error(JsError::TypeError, "WebSocket constructor cannot be invoked without 'new'");
+        return JsValue::Undefined;
+    }
+
+    let state = get_websocket_state(js_interpreter, &this_value);
+    if state.is_none() {
+        js_interpreter.throw_error(JsError::TypeError, "this is not a WebSocket object");
+        return JsValue::Undefined;
+    }
+    let state = state.unwrap();
+    let arguments: Vec<JsValue> = arguments.into_iter().map(|argument| argument.deref(js_interpreter)).collect();
+
+    match builtin {
+        JsBuiltinFunction::WebSocketSend => {
+            if state.ready_state.get() == READY_STATE_CONNECTING {
+                js_interpreter.throw_error(JsError::InvalidStateError, "the websocket is still connecting");
+                return JsValue::Undefined;
+            }
+
+            //data sent after closing is dropped without an error
+            //TODO: Blobs and ArrayBuffers should be sent as binary messages, when we have those
+            if state.ready_state.get() == READY_STATE_OPEN {
+                let data = arguments.first().cloned().unwrap_or(JsValue::Undefined).to_primitive(js_interpreter).to_js_string();
+                state.connection.send(data);
+            }
+        },
+        JsBuiltinFunction::WebSocketClose => {
+            let code = match arguments.first() {
+                Some(JsValue::Undefined) | None => None,
+                Some(code) => {
+                    //scripts can only use 1000 (a normal close), or the codes reserved for applications
+                    let code = code.to_number();
+                    if code != 1000.0 && !(3000.0..=4999.0).contains(&code) {
+                        js_interpreter.throw_error(JsError::InvalidAccessError, format!("{} is not a valid close code", code).as_str());
+                        return JsValue::Undefined;
+                    }
+                    Some(code as u16)
+                },
+            };
+            let reason = match arguments.get(1) {
+                Some(JsValue::Undefined) | None => String::new(),
+                Some(reason) => reason.to_js_string(),
+            };
+            if reason.len() > MAX_CLOSE_REASON_BYTES {
+                js_interpreter.throw_error(JsError::SyntaxError, "the close reason is too long");
+                return JsValue::Undefined;
+            }
+
+            if state.ready_state.get() == READY_STATE_CLOSING || state.ready_state.get() == READY_STATE_CLOSED {
+                return JsValue::Undefined;
+            }
+            state.ready_state.set(READY_STATE_CLOSING);
+
+            //a reason can only be sent together with a code, so a reason without a code is sent with a normal close
+            let code_and_reason = match code {
+                Some(code) => Some((code, reason)),
+                None if !reason.is_empty() => Some((1000, reason)),
+                None => None,
+            };
+            state.connection.close(code_and_reason);
+        },
+        JsBuiltinFunction::WebSocketGetReadyState => { return JsValue::Number(state.ready_state.get() as f64); },
+        JsBuiltinFunction::WebSocketGetUrl => { return JsValue::String(state.url.to_string()); },
+        _ => {
+            panic!("not a websocket function");
+        },
+    }
+    return JsValue::Undefined;
+}
+
+
+pub fn deliver_websocket_events(js_interpreter: &mut JsInterpreter) {
+    //this runs from the main loop, and fires the events for what happened on the connections since the last time
+    for (address, state) in js_interpreter.websockets.clone() {
+        loop {
+            let event = state.connection.try_receive_event();
+            if event.is_none() {
+                break;
+            }
+
+            let dom_event = match event.unwrap() {
+                WebSocketEvent::Open => {
+                    if state.ready_state.get() == READY_STATE_CONNECTING {
+                        state.ready_state.set(READY_STATE_OPEN);
+                    }
+                    DomEvent::new("open", false, false)
+                },
+                WebSocketEvent::Message(data) => {
+                    //messages that arrive after the script called close() are not delivered anymore
+                    if state.ready_state.get() != READY_STATE_OPEN {
+                        continue;
+                    }
+                    DomEvent { event_type: String::from("message"), bubbles: false, cancelable: false, details: DomEventDetails::Message(data) }
+                },
+                WebSocketEvent::Error => DomEvent::new("error", false, false),
+                WebSocketEvent::Close(code, reason, was_clean) => {
+                    state.ready_state.set(READY_STATE_CLOSED);
+                    DomEvent { event_type: String::from("close"), bubbles: false, cancelable: false, details: DomEventDetails::Close(code, reason, was_clean) }
+                },
+            };
+            js_events::dispatch_object_event(js_interpreter, address, dom_event);
+        }
+    }
+
+    //closed connections won't get any events anymore
+    js_interpreter.websockets.retain(|(_, state)| state.ready_state.get() != READY_STATE_CLOSED);
+}
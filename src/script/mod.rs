@@ -1,8 +1,15 @@
 pub mod js_ast;
+pub mod js_async;
+pub mod js_builtins;
 pub mod js_console;
+pub mod js_dom;
+pub mod js_events;
 pub mod js_execution_context;
 pub mod js_interpreter;
 pub mod js_lexer;
 pub mod js_parser;
+pub mod js_promise;
+pub mod js_regex;
+pub mod js_websocket;
 
 #[cfg(test)] mod tests;
@@ -1,8 +1,22 @@
-use crate::script::js_interpreter::JsInterpreter;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::dom::DocumentReadyState;
+use crate::html_lexer;
+use crate::html_parser;
+use crate::layout::{build_full_layout, compute_layout};
+use crate::network::{url::Url, UA_FIREFOX_WINDOWS};
+use crate::platform::fonts::FontContext;
+use crate::script::js_events::DomEvent;
+use crate::script::js_interpreter::{JsDialog, JsInterpreter, JsLayoutContext};
+use crate::style::media_query::MediaEnvironment;
+use crate::ui::{CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y, WindowDimensions};
 
 use super::js_execution_context::JsValue;
 use super::js_lexer;
 use super::js_parser;
+use super::js_regex::JsRegex;
 
 
 fn js_values_are_equal(one: &JsValue, two: &JsValue) -> bool {
@@ -22,7 +36,12 @@ fn js_values_are_equal(one: &JsValue, two: &JsValue) -> bool {
                 _ => { return false; }
             }
         },
-        JsValue::Boolean(_) => todo!(),
+        JsValue::Boolean(bool_one) => {
+            match two {
+                JsValue::Boolean(bool_two) => { return bool_one == bool_two },
+                _ => { return false; }
+            }
+        },
         JsValue::Object(_) => todo!(),
         JsValue::Function(_) => todo!(),
         JsValue::Undefined => {
@@ -31,6 +50,12 @@ fn js_values_are_equal(one: &JsValue, two: &JsValue) -> bool {
                 _ => { return false; }
             }
         },
+        JsValue::Null => {
+            match two {
+                JsValue::Null => { return true },
+                _ => { return false; }
+            }
+        },
         JsValue::Address(_) => todo!(),
     }
 }
@@ -47,7 +72,7 @@ fn test_basic_assignment_and_export() {
     let mut interpreter = JsInterpreter::new();
     interpreter.run_script(&script);
 
-    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(7)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(7.0)));
 }
 
 
@@ -60,7 +85,7 @@ fn test_binop_associativity() {
     let mut interpreter = JsInterpreter::new();
     interpreter.run_script(&script);
 
-    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(8)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(8.0)));
 }
 
 
@@ -76,7 +101,7 @@ fn test_literal_object_notation() {
     let mut interpreter = JsInterpreter::new();
     interpreter.run_script(&script);
 
-    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(12)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(12.0)));
 }
 
 
@@ -94,7 +119,7 @@ fn test_basic_function_call() {
     let mut interpreter = JsInterpreter::new();
     interpreter.run_script(&script);
 
-    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(6)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(6.0)));
 }
 
 
@@ -111,7 +136,7 @@ fn test_basic_function_call_no_args() {
     let mut interpreter = JsInterpreter::new();
     interpreter.run_script(&script);
 
-    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(150)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(150.0)));
 }
 
 
@@ -144,7 +169,7 @@ fn test_not_parsing_comments() {
     let mut interpreter = JsInterpreter::new();
     interpreter.run_script(&script);
 
-    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(1.0)));
 }
 
 
@@ -159,3 +184,1458 @@ fn test_double_slash_in_string_is_not_a_comment() {
 
     assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String("https://www.reddit.com".to_owned())));
 }
+
+
+#[test]
+fn test_while_and_do_while_loops() {
+    let code = r#"
+        var countdown = 5;
+        var steps = 0;
+        while (countdown) {
+            countdown = countdown - 1;
+            steps = steps + 1;
+        }
+
+        do {
+            steps = steps + 10;
+        } while (0)
+
+        var n = 0;
+        while (true) {
+            n++
+            if (n < 4) { continue } else { break }
+            steps = 1000;
+        }
+        tester.export(steps + n);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(19.0)));
+}
+
+
+#[test]
+fn test_for_loops() {
+    let code = r#"
+        var total = 0;
+        for (var i = 5; i > 0; i--) {
+            if (i != 2) total = total + i;
+            else continue;
+        }
+
+        var squares = [];
+        for (var i = 0; i < 4; i++) squares[i] = i * i;
+
+        var indexes = "";
+        var values = 0;
+        for (var index in [5, 6, 7]) {
+            indexes = indexes + index;
+            values = values + [5, 6, 7][index];
+        }
+
+        function find_second_key(object) {
+            var count = 0;
+            for (var key in object) {
+                count = count + 1;
+                if (count - 2) {} else { return key; }
+            }
+        }
+
+        var last_char = "";
+        for (var character of "abc") last_char = character;
+
+        tester.export(total);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(13.0)));
+
+    let code = format!("{code} tester.export(find_second_key({{ z: 1, a: 2, m: 3 }}));");
+    let tokens = js_lexer::lex_js(&code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("a"))));
+
+    let code = format!("{code} tester.export(last_char);");
+    let tokens = js_lexer::lex_js(&code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("c"))));
+
+    let code = format!("{code} tester.export(squares.length + \" \" + squares[3] + \" \" + indexes + \" \" + values);");
+    let tokens = js_lexer::lex_js(&code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("4 9 012 18"))));
+}
+
+
+#[test]
+fn test_update_expressions_and_boolean_literals() {
+    let cases = [
+        ("x++", JsValue::Number(5.0)),
+        ("++x", JsValue::Number(6.0)),
+        ("x--", JsValue::Number(5.0)),
+        ("--x", JsValue::Number(4.0)),
+        ("x++ + x", JsValue::Number(11.0)),
+        ("-- x * 2", JsValue::Number(8.0)),
+        ("object.count++ + object.count", JsValue::Number(3.0)),
+        ("++list[1] + list[1]", JsValue::Number(6.0)),
+        ("text++", JsValue::Number(7.0)),
+        ("true", JsValue::Boolean(true)),
+        ("!false", JsValue::Boolean(true)),
+        ("true == 1 && false == 0", JsValue::Boolean(true)),
+        ("typeof false", JsValue::String(String::from("boolean"))),
+        ("object.true", JsValue::String(String::from("keyword key"))),
+    ];
+
+    for (expression, expected) in cases {
+        let code = format!(r#"
+            var x = 5;
+            var text = "7";
+            var object = {{ count: 1, true: "keyword key" }};
+            var list = [1, 2];
+            tester.export({expression});"#);
+
+        let tokens = js_lexer::lex_js(&code, 1, 1);
+        let script = js_parser::parse_js(&tokens);
+        let mut interpreter = JsInterpreter::new();
+        interpreter.run_script(&script);
+
+        assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &expected), "{expression}");
+    }
+
+    //a newline before ++ ends the statement, so the ++ belongs to the next line
+    let code = "var a = 1; var b = 1; a\n++b\ntester.export(a * 10 + b);";
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(12.0)));
+
+    //only variables and properties can be incremented
+    let tokens = js_lexer::lex_js("tester.export(1); 1++;", 1, 1);
+    assert_eq!(js_parser::parse_js(&tokens).len(), 0);
+}
+
+
+#[test]
+fn test_break_outside_of_loop_is_a_syntax_error() {
+    let code = "tester.export(1); function f() { break; }";
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    assert_eq!(script.len(), 0);
+}
+
+
+#[test]
+fn test_comparison_and_logical_operators() {
+    let cases = [
+        ("1 + 2 < 4", JsValue::Boolean(true)),
+        ("3 >= 3 && 2 > 3", JsValue::Boolean(false)),
+        ("\"3\" == 3", JsValue::Boolean(true)),
+        ("\"3\" === 3", JsValue::Boolean(false)),
+        ("\"3\" !== 3", JsValue::Boolean(true)),
+        ("1 != 1", JsValue::Boolean(false)),
+        ("\"b\" <= \"a\"", JsValue::Boolean(false)),
+        ("!0 == true", JsValue::Boolean(true)),
+        ("false || 1 > 2", JsValue::Boolean(false)),
+        ("!!\"\"", JsValue::Boolean(false)),
+        ("0 || \"fallback\"", JsValue::String(String::from("fallback"))),
+        ("1 || 2 && 0", JsValue::Number(1.0)),
+        ("1 < 2 == 2 < 3", JsValue::Boolean(true)),
+        ("undefined == null && undefined !== null", JsValue::Boolean(true)),
+        ("NaN == NaN || NaN === NaN", JsValue::Boolean(false)),
+        ("Infinity > 1e308 && 0 - Infinity < 0 - 1e308 && 1 / 0 === Infinity", JsValue::Boolean(true)),
+        ("typeof undefined == \"undefined\"", JsValue::Boolean(true)),
+    ];
+
+    for (expression, expected) in cases {
+        let code = format!("tester.export({expression});");
+
+        let tokens = js_lexer::lex_js(&code, 1, 1);
+        let script = js_parser::parse_js(&tokens);
+        let mut interpreter = JsInterpreter::new();
+        interpreter.run_script(&script);
+
+        assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &expected), "{expression}");
+    }
+
+    //the globals undefined, NaN and Infinity can't be changed, but trying to is no error either
+    let code = r#"
+        undefined = 1;
+        NaN = 2;
+        Infinity = 3;
+        var undefined = 4;
+        tester.export(typeof undefined + " " + NaN + " " + Infinity);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("undefined NaN Infinity"))));
+    assert_eq!(interpreter.nr_of_errors, 0);
+}
+
+
+#[test]
+fn test_logical_operators_short_circuit() {
+    //accessing a property on undefined throws, so the export would not run if the right sides were executed
+    let code = r#"
+        var x = 0 && missing.property;
+        var y = "set" || missing.property;
+        tester.export(y);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("set"))));
+}
+
+
+#[test]
+fn test_string_concatenation_and_number_coercion() {
+    let cases = [
+        ("\"a\" + 1 + 2", JsValue::String(String::from("a12"))),
+        ("1 + 2 + \"a\"", JsValue::String(String::from("3a"))),
+        ("\"count: \" + (1 < 2)", JsValue::String(String::from("count: true"))),
+        ("\"x\" + undefined_value", JsValue::String(String::from("xundefined"))),
+        ("\"\" + {}", JsValue::String(String::from("[object Object]"))),
+        ("\"\" + [1, \"a\", [2, 3]]", JsValue::String(String::from("1,a,2,3"))),
+        ("\"\" + [null, undefined_value, 1] + []", JsValue::String(String::from(",,1"))),
+        ("[5] * 2", JsValue::Number(10.0)),
+        ("\"\" + 0.1 * 3", JsValue::String(String::from("0.30000000000000004"))),
+        ("\"\" + 1e21 + \" \" + 1 / 0", JsValue::String(String::from("1e+21 Infinity"))),
+        ("\"6\" * \"7\"", JsValue::Number(42.0)),
+        ("\" 10 \" - 0x2", JsValue::Number(8.0)),
+        ("(1 < 2) + 1", JsValue::Number(2.0)),
+        ("\"\" + (\"abc\" - 1)", JsValue::String(String::from("NaN"))),
+        ("7 / 2", JsValue::Number(3.5)),
+    ];
+
+    for (expression, expected) in cases {
+        let code = format!("var undefined_value; tester.export({expression});");
+
+        let tokens = js_lexer::lex_js(&code, 1, 1);
+        let script = js_parser::parse_js(&tokens);
+        let mut interpreter = JsInterpreter::new();
+        interpreter.run_script(&script);
+
+        assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &expected), "{expression}");
+    }
+}
+
+
+#[test]
+fn test_conditional_nullish_and_optional_chaining() {
+    let cases = [
+        ("1 < 2 ? \"yes\" : \"no\"", JsValue::String(String::from("yes"))),
+        ("0 ? 1 : 0 ? 2 : 3", JsValue::Number(3.0)),
+        ("nothing ?? \"default\"", JsValue::String(String::from("default"))),
+        ("0 ?? \"default\"", JsValue::Number(0.0)),
+        ("null ?? \"default\"", JsValue::String(String::from("default"))),
+        ("nothing?.member.deeper", JsValue::Undefined),
+        ("null?.member", JsValue::Undefined),
+        ("object.empty?.value", JsValue::Undefined),
+        ("object?.inner.value", JsValue::Number(5.0)),
+        ("object.missing?.value ?? \"no value\"", JsValue::String(String::from("no value"))),
+        ("object?.[\"inner\"]?.value", JsValue::Number(5.0)),
+        ("nothing?.()", JsValue::Undefined),
+        ("object.empty?.()", JsValue::Undefined),
+        ("object.inner.get?.()", JsValue::Number(1.0)),
+    ];
+
+    for (expression, expected) in cases {
+        let code = format!(r#"
+            var nothing;
+            function one() {{ return 1; }}
+            var object = {{ inner: {{ value: 5, get: one }}, empty: null }};
+            tester.export({expression});"#);
+
+        let tokens = js_lexer::lex_js(&code, 1, 1);
+        let script = js_parser::parse_js(&tokens);
+        let mut interpreter = JsInterpreter::new();
+        interpreter.run_script(&script);
+
+        assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &expected), "{expression}");
+    }
+}
+
+
+#[test]
+fn test_null_value() {
+    let cases = [
+        ("typeof null", JsValue::String(String::from("object"))),
+        ("null", JsValue::Null),
+        ("null == undefined", JsValue::Boolean(true)),
+        ("null === undefined", JsValue::Boolean(false)),
+        ("null === null", JsValue::Boolean(true)),
+        ("null == 0", JsValue::Boolean(false)),
+        ("null + 1", JsValue::Number(1.0)),
+        ("\"value: \" + null", JsValue::String(String::from("value: null"))),
+        ("null ? \"yes\" : \"no\"", JsValue::String(String::from("no"))),
+    ];
+
+    for (expression, expected) in cases {
+        let code = format!("var undefined; tester.export({expression});");
+
+        let tokens = js_lexer::lex_js(&code, 1, 1);
+        let script = js_parser::parse_js(&tokens);
+        let mut interpreter = JsInterpreter::new();
+        interpreter.run_script(&script);
+
+        assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &expected), "{expression}");
+    }
+}
+
+
+#[test]
+fn test_try_catch_finally_and_throw() {
+    let code = r#"
+        var log = "";
+
+        try {
+            log = log + "a";
+            missing_function();
+            log = log + "not reached";
+        } catch (e) {
+            log = log + e.name;
+        } finally {
+            log = log + "|finally";
+        }
+
+        function fails() {
+            throw "custom";
+        }
+        try {
+            fails();
+        } catch (e) {
+            log = log + "|" + e;
+        }
+
+        function finally_overrides_return() {
+            try {
+                return 1;
+            } finally {
+                return 2;
+            }
+        }
+        log = log + "|" + finally_overrides_return();
+
+        for (var i = 0; i < 3; i = i + 1) {
+            try {
+                if (i == 1) throw "stop";
+            } catch {
+                break;
+            }
+        }
+        log = log + "|" + i;
+
+        tester.export(log);
+        throw "uncaught";
+        tester.export("not reached");"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("aReferenceError|finally|custom|2|1"))));
+    assert_eq!(interpreter.nr_of_errors, 1); //only the uncaught exception is an error for the console
+}
+
+
+#[test]
+fn test_error_constructors() {
+    let code = r#"
+        var log = "";
+
+        var error = new Error("plain");
+        log = log + error.name + ": " + error.message + " " + (error instanceof Error);
+
+        var type_error = TypeError("without new");
+        log = log + "|" + type_error.name + ": " + type_error.message + " " + (type_error instanceof TypeError) + " " + (type_error instanceof Error);
+        log = log + "|" + (new RangeError() instanceof TypeError) + " [" + new RangeError().message + "]";
+
+        try {
+            missing_function();
+        } catch (e) {
+            log = log + "|" + (e instanceof ReferenceError) + " " + (e instanceof Error);
+        }
+        try {
+            null.property;
+        } catch (e) {
+            log = log + "|" + (e instanceof TypeError);
+        }
+
+        tester.export(log);
+        throw new SyntaxError("custom");"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("Error: plain true|TypeError: without new true true|false []|true true|true"))));
+    assert_eq!(interpreter.nr_of_errors, 1);
+}
+
+
+#[test]
+fn test_too_deep_recursion_throws_a_range_error() {
+    let code = r#"
+        var log = "";
+
+        function forever() { return forever(); }
+        try {
+            forever();
+        } catch (e) {
+            log = log + e.name + ": " + e.message;
+        }
+
+        var depth = 0;
+        function count_down(n) { depth = depth + 1; return n == 0 ? 0 : count_down(n - 1); }
+        count_down(50);
+        log = log + "|" + depth;
+
+        async function forever_async() { return forever(); }
+        forever_async().catch(function(e) { tester.export(log + "|" + e.name); });"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("RangeError: Maximum call stack size exceeded|51|RangeError"))));
+    assert_eq!(interpreter.call_depth, 0);
+}
+
+
+#[test]
+fn test_closures_and_scope_chain() {
+    let code = r#"
+        var counter = (function() {
+            var count = 0;
+            return {
+                increment: function() { count = count + 1; return count; },
+            };
+        })();
+        counter.increment();
+        counter.increment();
+
+        function make_adder(amount) {
+            return function(value) { return value + amount; };
+        }
+        var add_ten = make_adder(10);
+        var add_one = make_adder(1);
+
+        function apply_twice(callback, value) {
+            return callback(callback(value));
+        }
+
+        var factorial = function fact(n) { return n <= 1 ? 1 : n * fact(n - 1); };
+
+        var global_value = "global";
+        function read_global() { return global_value; }
+        function write_global() { global_value = "changed"; created_global = "created"; }
+        write_global();
+
+        function throws_object() { throw { message: "from function" }; }
+        var caught = "";
+        try { throws_object(); } catch (e) { caught = e.message; }
+
+        tester.export(counter.increment() + "|" + apply_twice(add_ten, add_one(0)) + "|" + factorial(5) + "|" +
+                      read_global() + "|" + created_global + "|" + caught);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("3|21|120|changed|created|from function"))));
+}
+
+
+#[test]
+fn test_this_binding_and_method_calls() {
+    let code = r#"
+        var counter = {
+            count: 0,
+            increment: function(amount) { this.count = this.count + amount; return this; },
+        };
+        counter.increment(2).increment(3);
+        var alias = counter;
+        alias.increment(1);
+
+        function describe(prefix, suffix) { return prefix + this.name + suffix; }
+        var named = { name: "thing" };
+        var called = describe.call(named, "<", ">");
+        var applied = describe.apply(named, { length: 2, 0: "[", 1: "]" });
+        var bound = describe.bind(named, "(");
+        named.name = "changed";
+
+        function get_this() { return this; }
+
+        tester.export(counter.count + "|" + called + "|" + applied + "|" + bound(")") + "|" + (get_this() === window) + "|" +
+                      (this === window) + "|" + (alias === counter) + "|" + ({} === {}));"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("6|<thing>|[thing]|(changed)|true|true|true|false"))));
+}
+
+
+#[test]
+fn test_prototypes_and_new() {
+    let code = r#"
+        function Animal(name) { this.name = name; }
+        Animal.prototype.describe = function() { return this.name + " says " + this.sound; };
+        Animal.prototype.sound = "nothing";
+
+        var dog = new Animal("dog");
+        dog.sound = "woof";
+        var fish = new Animal("fish");
+
+        function ReturnsObject() { this.ignored = "ignored"; return { replaced: "yes" }; }
+        var replaced = new ReturnsObject();
+
+        var shapes = { Square: function(size) { this.size = size; } };
+        var square = new shapes.Square(3);
+
+        function NoArguments() { this.made = "made"; }
+
+        var caught = "";
+        try { new dog.name(); } catch (e) { caught = e.name; }
+
+        tester.export(dog.describe() + "|" + fish.describe() + "|" + (dog instanceof Animal) + "|" + (square instanceof Animal) + "|" +
+                      (square instanceof shapes.Square) + "|" + square.size + "|" + replaced.replaced + "|" + (replaced instanceof ReturnsObject) + "|" +
+                      (fish.constructor === Animal) + "|" + new NoArguments().made + "|" + caught);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("dog says woof|fish says nothing|true|false|true|3|yes|false|true|made|TypeError"))));
+}
+
+
+#[test]
+fn test_math_and_number_builtins() {
+    let code = r#"
+        var rounded = Math.floor(2.7) + "," + Math.ceil(2.1) + "," + Math.round(2.5) + "," + Math.round(0 - 2.5) + "," + Math.abs(0 - 3) + "," + Math.pow(2, 10);
+        var extremes = Math.min(4, 2, 8) + "," + Math.max(4, 2, 8) + "," + Math.min() + "," + Math.max(1, "x");
+        var random = Math.random();
+        var random_in_range = random >= 0 && random < 1;
+
+        var parsed_ints = parseInt("42px") + "," + parseInt("  -0x1F") + "," + parseInt("101", 2) + "," + parseInt("z", 36) + "," + parseInt("px");
+        var parsed_floats = parseFloat("3.25em") + "," + Number.parseFloat(".5") + "," + parseFloat("-1.5e3x") + "," + parseFloat("1e") + "," + parseFloat("e5");
+        var nans = isNaN("abc") + "," + isNaN("12") + "," + Number.isNaN("abc") + "," + Number.isNaN(parseInt("abc"));
+
+        tester.export(rounded + "|" + extremes + "|" + random_in_range + "|" + parsed_ints + "|" + parsed_floats + "|" + nans);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("2,3,3,-2,3,1024|2,8,Infinity,NaN|true|42,-31,5,35,NaN|3.25,0.5,-1500,1,NaN|true,false,false,true"))));
+}
+
+
+#[test]
+fn test_date() {
+    let code = r#"
+        var date = new Date(2024, 1, 29, 13, 45, 10, 123);
+        var parts = date.getFullYear() + "," + date.getMonth() + "," + date.getDate() + "," + date.getHours();
+
+        var from_string = new Date("2024-02-29T13:45:10.123Z");
+        var from_offset = new Date("2024-03-01T00:15:10.123+10:30");
+        var same_time = (from_string.getTime() === date.getTime()) + "," + (new Date(from_string).getTime() === date.getTime()) + "," +
+                        (from_offset.getTime() === date.getTime());
+
+        var overflowing = new Date(2023, 12, 32).toISOString();
+        var epoch = new Date(0).toISOString();
+        var before_epoch = new Date(0 - 86400001).toISOString();
+
+        var now = Date.now();
+        var now_is_recent = now > 1700000000000 && new Date().getTime() >= now;
+
+        var caught = "";
+        try { new Date("not a date").toISOString(); } catch (e) { caught = e.name; }
+
+        tester.export(parts + "|" + date.toISOString() + "|" + same_time + "|" + overflowing + "|" + epoch + "|" + before_epoch + "|" +
+                      now_is_recent + "|" + isNaN(new Date("2023-02-29").getTime()) + "|" + caught + "|" + (date instanceof Date));"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("2024,1,29,13|2024-02-29T13:45:10.123Z|true,true,true|2024-02-01T00:00:00.000Z|\
+                                                               1970-01-01T00:00:00.000Z|1969-12-30T23:59:59.999Z|true|true|RangeError|true"))));
+}
+
+
+#[test]
+fn test_regular_expressions() {
+    let code = r#"
+        var date_pattern = /(\d{4})-(\d\d)-(\d\d)/;
+        var found = date_pattern.exec("due on 2024-02-29, or later");
+        var exec_result = found[0] + "," + found[1] + "," + found[3] + "," + found.index + "," + found.length;
+
+        var tests = /^[a-z_]\w*$/i.test("Some_Name1") + "," + /^[a-z_]\w*$/i.test("1name") + "," + /[/]x/.test("a/x") + "," +
+                    /(a|b)\1/.test("abba") + "," + /foo(?=bar)/.test("foobaz") + "," + /foo(?!bar)/.test("foobaz") + "," + /a.c/.test("ac");
+
+        var global = /o/g;
+        var positions = "";
+        while (global.exec("foo boo")) { positions = positions + global.lastIndex; }
+
+        var lazy = "<b>bold</b>".match(/<.+?>/)[0];
+        var all_numbers = "1 plus 22 is 23".match(/\d+/g);
+        var matched = all_numbers.length + ":" + all_numbers[0] + "," + all_numbers[2] + "|" + !"abc".match(/x/);
+
+        var replaced = "a-b-c".replace("-", "+") + "," + "a-b-c".replace(/-/g, "") + "," + "John Smith".replace(/(\w+) (\w+)/, "$2, $1 ($&)") + "," +
+                       "3 apples".replace(/\d+/, function(number) { return number * 2; }) + "," + "cost: $5".replace(/\$(\d)/, "$$$10");
+
+        var caught = "";
+        try { new RegExp("a(b"); } catch (e) { caught = e.name; }
+        var constructed = new RegExp("B+", "gi");
+
+        tester.export(exec_result + "|" + tests + "|" + positions + "|" + lazy + "|" + matched + "|" + replaced + "|" + caught + "|" +
+                      constructed.test("aBbb") + "," + constructed.lastIndex + "," + constructed.source + "," + (constructed instanceof RegExp));"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("2024-02-29,2024,29,7,4|true,false,true,true,false,true,false|2367|<b>|3:1,23|true|\
+                                                               a+b-c,abc,Smith, John (John Smith),6 apples,cost: $50|SyntaxError|true,4,B+,true"))));
+}
+
+
+#[test]
+fn test_named_capture_groups() {
+    let code = r#"
+        var found = /(?<year>\d{4})-(?<month>\d\d)(-(?<day>\d\d))?/.exec("since 2024-02");
+        var named = found.groups.year + "," + found.groups.month + "," + typeof found.groups.day + "," + found[2];
+        var matched = "v1.25".match(/(?<major>\d+)\.(?<minor>\d+)/).groups.minor;
+        var without_names = /(\d+)/.exec("12");
+
+        var caught = "";
+        try { new RegExp("(?<a>x)(?<a>y)"); } catch (e) { caught = e.name; }
+
+        tester.export(named + "|" + matched + "|" + typeof without_names.groups + "|" + caught);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("2024,02,undefined,02|25|undefined|SyntaxError"))));
+}
+
+
+#[test]
+fn test_regex_limits() {
+    //long repetitions don't need a rust stack frame per repetition
+    let long_input: Vec<char> = "a".repeat(1_000_000).chars().collect();
+    assert_eq!(JsRegex::new("^(?:a|b)*$", "").unwrap().find(&long_input, 0).unwrap()[0], Some((0, 1_000_000)));
+
+    //a regex that backtracks exponentially gives up instead of hanging
+    let backtracking_input: Vec<char> = format!("{}c", "a".repeat(40)).chars().collect();
+    assert!(JsRegex::new("^(a|a)*b", "").unwrap().find(&backtracking_input, 0).is_none());
+
+    //a backreference number too long to fit is a syntax error
+    assert_eq!(JsRegex::new("(a)\\99999999999999999999999", "").unwrap_err(), "invalid escape");
+}
+
+
+#[test]
+fn test_timers() {
+    let code = r#"
+        var log = "";
+        function add_to_log(text) { log = log + text; }
+
+        setTimeout(add_to_log, 2000, "late,");
+        setTimeout(add_to_log, 1000, "early,");
+        var cancelled = setTimeout(add_to_log, 500, "cancelled,");
+        clearTimeout(cancelled);
+
+        var ticks = 0;
+        var interval = setInterval(function() {
+            ticks = ticks + 1;
+            if (ticks === 3) { clearInterval(interval); }
+        }, 100);
+
+        setTimeout(function() {
+            add_to_log("outer,");
+            setTimeout(function() { add_to_log("nested,"); }, 0);
+        });
+        setTimeout(function() { missing_function(); });
+
+        add_to_log("sync,");"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    //timers that are added while running timers only run on the next call, and an error in one callback does not stop the others
+    let start = Instant::now();
+    interpreter.run_expired_timers(start + Duration::from_millis(1500));
+    let export_code = r#"tester.export(log + ticks);"#;
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(export_code, 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("sync,outer,early,1"))));
+
+    for idx in 0..5 {
+        interpreter.run_expired_timers(start + Duration::from_millis(5000 + idx * 1000));
+    }
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(export_code, 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("sync,outer,early,nested,late,3"))));
+}
+
+
+#[test]
+fn test_promises_and_async_functions() {
+    let code = r#"
+        var log = "";
+        function add_to_log(text) { log = log + text + ","; }
+
+        var resolved = new Promise(function(resolve, reject) { resolve(1); reject(2); });
+        resolved.then(function(value) { add_to_log("then " + value); return value + 1; })
+                .then(function(value) { add_to_log("chained " + value); throw "oops"; })
+                .then(function(value) { add_to_log("skipped"); })
+                .catch(function(reason) { add_to_log("caught " + reason); });
+
+        Promise.reject("no").then("not a function", function(reason) { add_to_log("rejected " + reason); });
+        new Promise(function() { throw "executor"; }).catch(function(reason) { add_to_log("from " + reason); });
+        Promise.resolve(Promise.resolve("inner")).then(add_to_log);
+
+        async function double(value) {
+            var doubled = await Promise.resolve(value * 2);
+            return doubled;
+        }
+        async function fails() { throw "async error"; }
+        var async_expression = async function() {
+            try {
+                await fails();
+            } catch (reason) {
+                add_to_log("awaited " + reason);
+            }
+            return await double(21);
+        };
+        async_expression().then(function(value) { add_to_log("result " + value); });
+
+        async function wait_for_timer() {
+            var value = await new Promise(function(resolve) { setTimeout(resolve, 10, "timer"); });
+            add_to_log(value);
+        }
+        wait_for_timer();
+
+        async function never_resumed() {
+            await new Promise(function() {});
+            add_to_log("never");
+        }
+        never_resumed();
+
+        var is_promise = double(1) instanceof Promise;
+        add_to_log("sync");"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    //the async functions are suspended at their awaits, so the code after the calls runs first, and the timer only resumes its function later
+    let export_code = r#"tester.export(log + is_promise);"#;
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(export_code, 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("sync,then 1,rejected no,from executor,inner,awaited async error,chained 2,caught oops,result 42,true"))));
+
+    interpreter.wait_for_next_timer();
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(export_code, 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("sync,then 1,rejected no,from executor,inner,awaited async error,chained 2,caught oops,result 42,timer,true"))));
+}
+
+
+#[test]
+fn test_var_hoisting_and_block_scoping() {
+    let code = r#"
+        var log = "";
+        function add_to_log(text) { log = log + text + ","; }
+
+        add_to_log(hoisted_function());
+        function hoisted_function() { return "hoisted " + hoisted_var; }
+        var hoisted_var = "var";
+
+        function var_scope() {
+            if (1) { var inner = "function scoped"; }
+            return inner;
+        }
+        add_to_log(var_scope());
+
+        let outer = "outer";
+        {
+            let outer = "inner";
+            const constant = "block";
+            add_to_log(outer + " " + constant);
+        }
+        add_to_log(outer);
+
+        try { add_to_log(too_early); let too_early = 1; } catch (error) { add_to_log(error.name); }
+        const fixed = 1;
+        try { fixed = 2; } catch (error) { add_to_log(error.name + " " + fixed); }
+        try { undeclared_catch_variable; } catch (catch_variable) {}
+        try { catch_variable; } catch (error) { add_to_log("catch variable " + error.name); }
+
+        var first_number;
+        var second_number;
+        for (let idx = 0; idx < 2; idx = idx + 1) {
+            if (idx === 0) { first_number = function() { return idx; }; } else { second_number = function() { return idx; }; }
+        }
+        add_to_log("" + first_number() + second_number());
+
+        var first_letter;
+        var second_letter;
+        for (const letter of "ab") {
+            if (letter === "a") { first_letter = function() { return letter; }; } else { second_letter = function() { return letter; }; }
+        }
+        add_to_log(first_letter() + second_letter());"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    let export_code = r#"tester.export(log);"#;
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(export_code, 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("hoisted undefined,function scoped,inner block,outer,ReferenceError,TypeError 1,catch variable ReferenceError,01,ab,"))));
+}
+
+
+#[test]
+fn test_typeof_delete_void_and_in() {
+    let code = r#"
+        var types = typeof window + " " + typeof 1 + " " + typeof "a" + " " + typeof (1 < 2) + " " + typeof function() {} + " " +
+                    typeof not_declared_anywhere + " " + typeof void 0;
+
+        var detected = "";
+        if (typeof window !== "undefined") { detected = detected + "window"; }
+        if (typeof require === "undefined") { detected = detected + " no require"; }
+
+        function Base() {}
+        Base.prototype.inherited = 1;
+        var object = new Base();
+        object.own = 2;
+        object.empty = void 0;
+
+        var membership = ("own" in object) + " " + ("inherited" in object) + " " + ("empty" in object) + " " + ("missing" in object);
+        var deleted = delete object.own;
+        var deleted_inherited = delete object.inherited;
+        membership = membership + " " + deleted + " " + ("own" in object) + " " + ("inherited" in object) + " " + deleted_inherited;
+
+        var keys = "";
+        object.again = 3;
+        delete object["empty"];
+        for (var key in object) { keys = keys + key; }
+
+        var error_name = "";
+        try { "length" in "text"; } catch (error) { error_name = error.name; }
+
+        tester.export(types + "|" + detected + "|" + membership + "|" + keys + "|" + error_name + "|" + delete types);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    let expected = "object number string boolean function undefined undefined|window no require|true true true false true false true true|again|TypeError|false";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+}
+
+
+#[test]
+fn test_object_literal_accessors_methods_and_computed_keys() {
+    let code = r#"
+        var log = "";
+        var name = "shorthand";
+        var prefix = "computed";
+
+        var object = {
+            stored: 1,
+            get doubled() { return this.stored * 2; },
+            set doubled(value) { this.stored = value / 2; },
+            get read_only() { return "read only"; },
+            name,
+            [prefix + "_key"]: "computed value",
+            1.0: "number key",
+            default: "keyword key",
+            get: "not a getter",
+            method(extra) { return this.stored + extra; },
+        };
+
+        log = log + object.doubled + ",";
+        object.doubled = 10;
+        log = log + object.stored + "," + object.doubled + ",";
+        object.read_only = "changed";
+        log = log + object.read_only + "," + object.name + "," + object.computed_key + "," + object["1"] + "," + object.default + ",";
+        log = log + object.get + "," + object.method(1) + "," + ("doubled" in object) + ",";
+
+        function Counter() { this.count = 0; }
+        Counter.prototype = { get next() { this.count = this.count + 1; return this.count; } };
+        var counter = new Counter();
+        log = log + counter.next + counter.next + ",";
+
+        var keys = "";
+        for (var key in object) { keys = keys + key + " "; }
+        tester.export(log + keys);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    let expected = "2,5,10,read only,shorthand,computed value,number key,keyword key,not a getter,6,true,12,\
+                    stored doubled read_only name computed_key 1 default get method ";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+}
+
+
+#[test]
+fn test_destructuring_spread_and_rest() {
+    let code = r#"
+        var log = "";
+
+        const {a, b: renamed, c = "default", nested: {inner}} = {a: 1, b: 2, nested: {inner: 3}};
+        log = log + a + renamed + c + inner + ",";
+
+        let [x, , y, ...others] = [4, 5, 6, 7, 8];
+        log = log + x + y + others.length + others[0] + others[1] + ",";
+
+        var [first, second] = "hi";
+        log = log + first + second + ",";
+
+        [x, y] = [y, x];
+        log = log + x + y + ",";
+
+        function count(prefix, ...rest) { return prefix + rest.length; }
+        log = log + count("n") + count("n", 1, 2) + ",";
+
+        function add(p, q, r) { return p + q + r; }
+        var numbers = [1, 2];
+        log = log + add(...numbers, 3) + add(..."abc") + ",";
+
+        var combined = [0, ...numbers, , 9];
+        combined[6] = 10;
+        log = log + combined.length + combined[3] + ",";
+
+        var not_set;
+        try {
+            const {missing} = not_set;
+        } catch (e) {
+            log = log + e.name + ",";
+        }
+        try {
+            var [nothing] = 5;
+        } catch (e) {
+            log = log + e.name;
+        }
+        tester.export(log);"#;
+
+    let tokens = js_lexer::lex_js(code, 1, 1);
+    let script = js_parser::parse_js(&tokens);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&script);
+
+    let expected = "12default3,46278,hi,64,n0n2,6abc,7undefined,TypeError,TypeError";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+}
+
+
+#[test]
+fn test_document_element_lookup() {
+    let html = r##"<html><body>
+        <div id="main" class="box">
+            <p class="note first">one</p>
+            <p>two</p>
+            <span class="note">three</span>
+        </div>
+        <p id="main">duplicate id</p>
+        <script>
+            var log = "";
+            var main = document.getElementById("main");
+            log = log + main.tagName + "," + main.id + "," + (main === document.getElementById("main")) + ",";
+            log = log + (document.getElementById("missing") === null) + ",";
+
+            log = log + document.querySelectorAll("p").length + "," + document.querySelectorAll(".note").length + ",";
+            log = log + document.querySelector(".note").tagName + "," + document.querySelector("span.note").tagName + ",";
+            log = log + document.querySelector("p.note.first").tagName + "," + document.querySelectorAll("p, span").length + ",";
+            log = log + main.querySelectorAll("p").length + "," + (document.querySelector("#main") === main) + ",";
+            log = log + document.querySelectorAll("div p").length;
+            tester.export(log);
+        </script>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_scripts_in_document(&document);
+
+    let expected = "DIV,main,true,true,3,2,P,SPAN,P,4,2,true,0";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+}
+
+
+#[test]
+fn test_dom_manipulation() {
+    let html = r##"<html><body>
+        <div id="list"><p id="first">one</p></div>
+        <div id="target">old <b>content</b></div>
+        <script>
+            var log = "";
+            var list = document.getElementById("list");
+            var first = document.getElementById("first");
+
+            var second = document.createElement("P");
+            second.appendChild(document.createTextNode("two"));
+            list.appendChild(second);
+            var zero = document.createElement("p");
+            zero.textContent = "zero";
+            list.insertBefore(zero, first);
+            log = log + list.innerHTML + "|" + list.textContent + "|";
+
+            log = log + (list.removeChild(first) === first) + "," + list.querySelectorAll("p").length + ",";
+            log = log + (document.getElementById("first") === null) + ",";
+            try {
+                list.removeChild(first);
+            } catch (e) {
+                log = log + e.name + ",";
+            }
+            try {
+                zero.appendChild(list);
+            } catch (e) {
+                log = log + e.name + "|";
+            }
+
+            var target = document.getElementById("target");
+            log = log + target.innerHTML + "|";
+            target.innerHTML = "<span class='new'>a &amp; b</span><br>text";
+            log = log + target.innerHTML + "|" + target.querySelector(".new").textContent + "|" + document.querySelectorAll("span.new").length;
+            tester.export(log);
+        </script>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_scripts_in_document(&document);
+
+    let expected = "<p>zero</p><p id=\"first\">one</p><p>two</p>|zeroonetwo|true,2,true,TypeError,TypeError|\
+                    old <b>content</b>|<span class=\"new\">a &amp; b</span><br>text|a & b|1";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+
+    //the elements that changed are dirty, so their layout is rebuilt
+    let document = document.borrow();
+    assert!(document.nodes[document.find_node_with_id("list").unwrap()].dirty);
+    assert!(document.nodes[document.find_node_with_id("target").unwrap()].dirty);
+    assert!(!document.nodes_to_post_construct.is_empty());
+}
+
+
+#[test]
+fn test_attributes_class_list_and_style() {
+    let html = r##"<html><body>
+        <div id="box" class="a  b" Title="hello" style="color: red; font-size: 12px">box</div>
+        <script>
+            var log = "";
+            var box = document.getElementById("box");
+            log = log + box.getAttribute("title") + "," + box.hasAttribute("TITLE") + "," + (box.getAttribute("missing") === null) + ",";
+            box.setAttribute("data-x", 5);
+            box.removeAttribute("title");
+            log = log + box.getAttribute("data-x") + "," + box.hasAttribute("title") + "|";
+
+            var classes = box.classList;
+            classes.add("c", "a");
+            classes.remove("b");
+            log = log + box.className + "," + classes.contains("a") + "," + classes.toggle("a") + "," + classes.toggle("d") + ",";
+            log = log + classes.toggle("d", 1) + "," + box.className + "," + (box.classList === classes) + "|";
+
+            var style = box.style;
+            log = log + style.color + "," + style.fontSize + "," + style.getPropertyValue("font-size") + "," + style.marginTop + "|";
+            style.backgroundColor = "blue";
+            style.color = "";
+            style.setProperty("margin-top", "4px");
+            log = log + style.removeProperty("font-size") + "," + style.cssText + "|" + box.getAttribute("style");
+            tester.export(log);
+        </script>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_scripts_in_document(&document);
+
+    let expected = "hello,true,true,5,false|a c,true,false,true,true,c d,true|red,12px,12px,|\
+                    12px,background-color: blue; margin-top: 4px;|background-color: blue; margin-top: 4px;";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+
+    //changing attributes marks the element dirty, so its styles are resolved again when its layout is rebuilt
+    let document = document.borrow();
+    assert!(document.nodes[document.find_node_with_id("box").unwrap()].dirty);
+}
+
+
+#[test]
+fn test_event_dispatch() {
+    let html = r##"<html><body>
+        <div id="outer"><a id="link" href="/next" onclick="log = log + 'attr:' + event.type + ',';"><span id="inner">link</span></a></div>
+        <form id="form"><input id="field"></form>
+        <script>
+            var log = "";
+            var outer = document.getElementById("outer");
+            var link = document.getElementById("link");
+            var inner = document.getElementById("inner");
+
+            function phase(name) {
+                return function (e) { log = log + name + e.eventPhase + ","; };
+            }
+            window.addEventListener("click", phase("window-capture"), 1);
+            document.addEventListener("click", phase("document"));
+            outer.addEventListener("click", phase("outer-capture"), { capture: 1 });
+            outer.addEventListener("click", phase("outer"));
+            var inner_listener = function (e) {
+                log = log + "inner" + e.eventPhase + (e.target === inner) + (e.currentTarget === this) + (this === inner) + ",";
+            };
+            inner.addEventListener("click", inner_listener);
+            inner.addEventListener("click", inner_listener);
+
+            document.getElementById("form").addEventListener("submit", function (e) {
+                e.preventDefault();
+                log = log + "submit" + e.defaultPrevented + e.cancelable + "|";
+            });
+            document.getElementById("field").onkeydown = function (e) {
+                log = log + "key:" + e.key + "|";
+                e.stopPropagation();
+            };
+            document.addEventListener("keydown", phase("document-key"));
+        </script>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_scripts_in_document(&document);
+
+    let find_node = |id: &str| document.borrow().find_node_with_id(id).unwrap();
+    let run_and_export_log = |interpreter: &mut JsInterpreter| {
+        let script = js_parser::parse_js(&js_lexer::lex_js("tester.export(log); log = '';", 1, 1));
+        interpreter.run_script(&script);
+        return interpreter.get_last_exported_test_data().to_js_string();
+    };
+
+    assert!(interpreter.dispatch_event(find_node("inner"), DomEvent::mouse("click", 10.0, 20.0)));
+    assert_eq!(run_and_export_log(&mut interpreter), "window-capture1,outer-capture1,inner2truetruetrue,attr:click,outer3,document3,");
+
+    //removed listeners don't run anymore, and stopping the propagation keeps the event from going up
+    let script = js_parser::parse_js(&js_lexer::lex_js("inner.removeEventListener('click', inner_listener);
+                                                        outer.onclick = function (e) { e.stopPropagation(); log = log + 'stopped,'; };", 1, 1));
+    interpreter.run_script(&script);
+    assert!(interpreter.dispatch_event(find_node("link"), DomEvent::mouse("click", 10.0, 20.0)));
+    assert_eq!(run_and_export_log(&mut interpreter), "window-capture1,outer-capture1,attr:click,outer3,stopped,");
+
+    assert!(!interpreter.dispatch_event(find_node("form"), DomEvent::new("submit", true, true)));
+    assert!(interpreter.dispatch_event(find_node("field"), DomEvent::key("keydown", String::from("Enter"))));
+    assert_eq!(run_and_export_log(&mut interpreter), "submittruetrue|key:Enter|");
+}
+
+
+#[test]
+fn test_document_load_events() {
+    let html = r##"<html><body onload="log = log + 'body-onload:' + document.readyState + ',';">
+        <img src="image.png">
+        <script>
+            var log = "parsing:" + document.readyState + ",";
+            document.addEventListener("DOMContentLoaded", function (e) {
+                log = log + "ready:" + document.readyState + (e.target === document) + ",";
+            });
+            window.addEventListener("DOMContentLoaded", function (e) { log = log + "window-ready,"; });
+            window.addEventListener("load", function (e) { log = log + "load:" + e.bubbles + ","; });
+        </script>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_scripts_in_document(&document);
+
+    document.borrow_mut().ready_state = DocumentReadyState::Interactive;
+    let document_node = document.borrow().document_node;
+    interpreter.dispatch_event(document_node, DomEvent::new("DOMContentLoaded", true, false));
+
+    //the image is not loaded yet, so the document is not complete
+    assert!(document.borrow().has_pending_resources());
+
+    document.borrow_mut().ready_state = DocumentReadyState::Complete;
+    interpreter.dispatch_window_event(DomEvent::new("load", false, false));
+
+    let script = js_parser::parse_js(&js_lexer::lex_js("tester.export(log);", 1, 1));
+    interpreter.run_script(&script);
+    let expected = "parsing:loading,ready:interactivetrue,window-ready,load:false,body-onload:complete,";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+}
+
+
+#[test]
+fn test_computed_style_and_bounding_client_rect() {
+    let html = r##"<html><head><style>p { color: red; }</style></head><body>
+        <div id="first" style="background-color: blue;">some text</div>
+        <p id="second">more text</p>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+    let font_context = Rc::new(FontContext::new());
+    let full_layout = Rc::new(RefCell::from(build_full_layout(&mut document.borrow_mut(), &font_context)));
+    compute_layout(&mut full_layout.borrow_mut(), &mut document.borrow_mut(), CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y, &font_context, 0.0, false, true);
+
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_scripts_in_document(&document);
+    interpreter.layout_context = Some(JsLayoutContext { full_layout, font_context, current_scroll_y: 0.0 });
+
+    let code = r#"
+        var first = document.getElementById("first");
+        var second = document.getElementById("second");
+        var style = getComputedStyle(second);
+        var log = style.color + "," + style.getPropertyValue("color") + "," + getComputedStyle(first).backgroundColor + ",";
+
+        var firstRect = first.getBoundingClientRect();
+        var secondRect = second.getBoundingClientRect();
+        log = log + (firstRect.top === 0) + (firstRect.width > 0) + (secondRect.top > firstRect.bottom - 1) + ",";
+
+        //the layout is updated for the new element before measuring
+        var inserted = document.createElement("div");
+        inserted.textContent = "inserted";
+        document.querySelector("body").insertBefore(inserted, second);
+        log = log + (second.getBoundingClientRect().top > secondRect.top) + (inserted.getBoundingClientRect().height > 0);
+        tester.export(log);
+    "#;
+    let script = js_parser::parse_js(&js_lexer::lex_js(code, 1, 1));
+    interpreter.run_script(&script);
+
+    let expected = "red,red,blue,truetruetrue,truetrue";
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(expected))));
+}
+
+
+#[test]
+fn test_window_size_properties() {
+    let url = Url::from(&String::from("http://www.example.com"));
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html("<html><body></body></html>"), &url)));
+    document.borrow_mut().style_context.set_media_environment(MediaEnvironment { width: 1024.0, height: 768.0, ..MediaEnvironment::new_default() });
+
+    let mut interpreter = JsInterpreter::new();
+    interpreter.screen_dimensions = WindowDimensions { width: 1920.0, height: 1080.0 };
+    interpreter.run_scripts_in_document(&document);
+
+    let code = r#"
+        var sizes = window.innerWidth + "x" + window.innerHeight + "," + screen.width + "x" + screen.height + "," + window.devicePixelRatio;
+        tester.export(sizes);
+    "#;
+    let script = js_parser::parse_js(&js_lexer::lex_js(code, 1, 1));
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("1024x768,1920x1080,1"))));
+
+    //the size follows the window when it is resized
+    document.borrow_mut().style_context.set_media_environment(MediaEnvironment { width: 800.0, height: 600.0, ..MediaEnvironment::new_default() });
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("800x600,1920x1080,1"))));
+
+    let script = js_parser::parse_js(&js_lexer::lex_js("tester.export(navigator.userAgent);", 1, 1));
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from(UA_FIREFOX_WINDOWS))));
+}
+
+
+#[test]
+fn test_alert_confirm_and_prompt() {
+    let mut interpreter = JsInterpreter::new();
+
+    let code = r#"
+        var answers = confirm("sure?") + "," + confirm("really sure?") + "," + prompt("name?", "default name") + "," + alert("hi");
+        tester.export(answers);
+    "#;
+    let script = js_parser::parse_js(&js_lexer::lex_js(code, 1, 1));
+
+    //without a dialog handler, all dialogs are treated as cancelled
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("false,false,null,undefined"))));
+
+    let confirm_count = Rc::new(RefCell::new(0));
+    let confirm_count_in_handler = Rc::clone(&confirm_count);
+    interpreter.dialog_handler = Some(Box::new(move |dialog| {
+        match dialog {
+            JsDialog::Alert(_) => { return None; },
+            JsDialog::Confirm(_) => {
+                *confirm_count_in_handler.borrow_mut() += 1;
+                if *confirm_count_in_handler.borrow() == 1 {
+                    return Some(String::new());
+                }
+                return None;
+            },
+            JsDialog::Prompt(message, default_text) => { return Some(format!("{} {}", message, default_text)); },
+        }
+    }));
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("true,false,name? default name,undefined"))));
+    assert!(*confirm_count.borrow() == 2);
+}
+
+
+#[test]
+fn test_queued_scripts_run_within_budget() {
+    let url = Url::from(&String::from("http://www.example.com"));
+    let html = "<html><body><script>var ran = 1;</script><script>ran = ran + 1;</script><script>ran = ran + 1;</script></body></html>";
+    let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(html), &url)));
+
+    let mut interpreter = JsInterpreter::new();
+    interpreter.queue_scripts_in_document(&document);
+
+    //at least one script runs every time, even when there is no time left in the budget
+    let script = js_parser::parse_js(&js_lexer::lex_js("tester.export(ran);", 1, 1));
+    assert!(!interpreter.run_queued_scripts(Duration::ZERO));
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(1.0)));
+
+    assert!(interpreter.run_queued_scripts(Duration::MAX));
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::Number(3.0)));
+}
+
+
+#[test]
+fn test_scripts_are_stopped_after_time_limit() {
+    let mut interpreter = JsInterpreter::new();
+    interpreter.script_time_limit = Duration::from_millis(50);
+
+    //the stop can't be caught, and does not run the rest of the script
+    let code = r#"
+        tester.export("started");
+        try {
+            while (1) {}
+        } catch (e) {
+            tester.export("caught");
+        }
+        tester.export("finished");
+    "#;
+    let script = js_parser::parse_js(&js_lexer::lex_js(code, 1, 1));
+    let start_instant = Instant::now();
+    interpreter.run_script(&script);
+    assert!(start_instant.elapsed() < Duration::from_secs(5));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("started"))));
+
+    //the next script gets its own time limit
+    let script = js_parser::parse_js(&js_lexer::lex_js("tester.export(\"next script\");", 1, 1));
+    interpreter.run_script(&script);
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("next script"))));
+}
+
+
+#[test]
+fn test_scripts_are_stopped_when_navigating_away() {
+    let mut interpreter = JsInterpreter::new();
+    let code = r#"
+        tester.export("started");
+        setTimeout(function() { tester.export("timer"); }, 100);
+    "#;
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(code, 1, 1)));
+
+    //what the page still tries to run after we started navigating is stopped, without that being an error of the page
+    interpreter.navigation_interrupted = true;
+    interpreter.run_expired_timers(Instant::now() + Duration::from_millis(1000));
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js("tester.export(\"after navigating\");", 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("started"))));
+    assert_eq!(interpreter.nr_of_errors, 0);
+
+    //when the navigation is stopped the page stays, and its scripts run again
+    interpreter.navigation_interrupted = false;
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js("tester.export(\"stopped navigating\");", 1, 1)));
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(), &JsValue::String(String::from("stopped navigating"))));
+}
+
+
+#[test]
+fn test_websocket_echo() {
+    //a server that sends every message back, and then closes the connection when the client does
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        loop {
+            match socket.read() {
+                Ok(tungstenite::Message::Text(text)) => { socket.send(tungstenite::Message::text(format!("echo {}", text))).unwrap(); },
+                Ok(_) => {},
+                Err(_) => { break; },
+            }
+        }
+    });
+
+    let code = format!(r#"
+        var log = "";
+        var socket = new WebSocket("ws://127.0.0.1:{}/echo");
+        log = log + "state " + socket.readyState + ",";
+        socket.onopen = function() {{ log = log + "open,"; socket.send("hello"); }};
+        socket.addEventListener("message", function(event) {{ log = log + event.data + ","; socket.close(1000, "done"); }});
+        socket.onclose = function(event) {{
+            log = log + "close " + event.code + " " + event.reason + " " + event.wasClean + " state " + socket.readyState;
+            tester.export(log);
+        }};
+
+        try {{
+            socket.send("too early");
+        }} catch (error) {{
+            log = log + error.name + ",";
+        }}
+        try {{
+            new WebSocket("ftp://127.0.0.1/");
+        }} catch (error) {{
+            log = log + error.name + ",";
+        }}"#, port);
+    let mut interpreter = JsInterpreter::new();
+    interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(&code, 1, 1)));
+
+    let start_instant = Instant::now();
+    while !interpreter.websockets.is_empty() && start_instant.elapsed() < Duration::from_secs(10) {
+        interpreter.run_websocket_events();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(js_values_are_equal(&interpreter.get_last_exported_test_data(),
+                                &JsValue::String(String::from("state 0,InvalidStateError,SyntaxError,open,echo hello,close 1000 done true state 3"))));
+}
+
+
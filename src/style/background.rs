@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use crate::debug::debug_log_warn;
+use crate::style::get_property_from_computed_styles;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackgroundRepeat {
+    Repeat,
+    RepeatX,
+    RepeatY,
+    NoRepeat,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackgroundLength {
+    Auto,
+    Pixels(f32),
+    Percentage(f32), //stored as a fraction, so 50% is 0.5
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackgroundSize {
+    Cover,
+    Contain,
+    Explicit(BackgroundLength, BackgroundLength),
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct BackgroundStyle {
+    pub image_url: String,
+    pub repeat: BackgroundRepeat,
+    pub position_x: BackgroundLength,
+    pub position_y: BackgroundLength,
+    pub size: BackgroundSize,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(PartialEq)]
+pub struct BackgroundTile {
+    //the position of one copy of the background image on the page, before clipping it to the box of the element
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+
+pub fn parse_css_url(value: &str) -> Option<String> {
+    let value = value.trim();
+    if !value.starts_with("url(") || !value.ends_with(')') {
+        return None;
+    }
+
+    let inner = value["url(".len()..value.len() - 1].trim();
+    if inner.len() >= 2 && ((inner.starts_with('"') && inner.ends_with('"')) || (inner.starts_with('\'') && inner.ends_with('\''))) {
+        return Some(inner[1..inner.len() - 1].to_owned());
+    }
+    return Some(inner.to_owned());
+}
+
+
+pub fn resolve_background_style(styles: &HashMap<String, String>) -> Option<BackgroundStyle> {
+    //TODO: we don't support the "background" shorthand property yet, and also not multiple (comma seperated) background layers
+
+    let image_value = get_property_from_computed_styles(styles, "background-image");
+    if image_value.is_none() {
+        return None;
+    }
+    let image_url = parse_css_url(&image_value.unwrap());
+    if image_url.is_none() {
+        //this is not an error, it happens for "none" for example, and for gradients, which we don't support yet
+        return None;
+    }
+
+    let repeat_value = get_property_from_computed_styles(styles, "background-repeat").unwrap();
+    let repeat = match repeat_value.trim() {
+        "repeat" | "repeat repeat" => BackgroundRepeat::Repeat,
+        "repeat-x" | "repeat no-repeat" => BackgroundRepeat::RepeatX,
+        "repeat-y" | "no-repeat repeat" => BackgroundRepeat::RepeatY,
+        "no-repeat" | "no-repeat no-repeat" => BackgroundRepeat::NoRepeat,
+        _ => {
+            debug_log_warn(format!("unsupported background-repeat value: {}", repeat_value));
+            BackgroundRepeat::Repeat
+        }
+    };
+
+    let position_value = get_property_from_computed_styles(styles, "background-position").unwrap();
+    let (position_x, position_y) = parse_background_position(&position_value);
+
+    let size_value = get_property_from_computed_styles(styles, "background-size").unwrap();
+    let size = parse_background_size(&size_value);
+
+    return Some(BackgroundStyle { image_url: image_url.unwrap(), repeat, position_x, position_y, size });
+}
+
+
+fn parse_background_position(value: &str) -> (BackgroundLength, BackgroundLength) {
+    let mut x = None;
+    let mut y = None;
+
+    for part in value.split_whitespace() {
+        match part {
+            "left" => { x = Some(BackgroundLength::Percentage(0.0)); },
+            "right" => { x = Some(BackgroundLength::Percentage(1.0)); },
+            "top" => { y = Some(BackgroundLength::Percentage(0.0)); },
+            "bottom" => { y = Some(BackgroundLength::Percentage(1.0)); },
+            "center" => {
+                if x.is_none() {
+                    x = Some(BackgroundLength::Percentage(0.5));
+                } else {
+                    y = Some(BackgroundLength::Percentage(0.5));
+                }
+            },
+            _ => {
+                let length = parse_background_length(part);
+                if x.is_none() {
+                    x = Some(length);
+                } else {
+                    y = Some(length);
+                }
+            }
+        }
+    }
+
+    //a single value means the other one is centered
+    let center = BackgroundLength::Percentage(0.5);
+    return (x.unwrap_or(center), y.unwrap_or(center));
+}
+
+
+fn parse_background_size(value: &str) -> BackgroundSize {
+    match value.trim() {
+        "cover" => { return BackgroundSize::Cover; },
+        "contain" => { return BackgroundSize::Contain; },
+        _ => {}
+    }
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let width = if parts.len() > 0 { parse_background_length(parts[0]) } else { BackgroundLength::Auto };
+    let height = if parts.len() > 1 { parse_background_length(parts[1]) } else { BackgroundLength::Auto };
+    return BackgroundSize::Explicit(width, height);
+}
+
+
+fn parse_background_length(value: &str) -> BackgroundLength {
+    if value == "auto" {
+        return BackgroundLength::Auto;
+    }
+
+    let (number_part, is_percentage) = if value.ends_with('%') {
+        (&value[..value.len() - 1], true)
+    } else if value.ends_with("px") {
+        (&value[..value.len() - 2], false)
+    } else {
+        (value, false)
+    };
+
+    let parsed = number_part.parse::<f32>();
+    if parsed.is_err() {
+        debug_log_warn(format!("could not parse background length: {}", value));
+        return BackgroundLength::Auto;
+    }
+
+    if is_percentage {
+        return BackgroundLength::Percentage(parsed.unwrap() / 100.0);
+    }
+    return BackgroundLength::Pixels(parsed.unwrap());
+}
+
+
+pub fn compute_background_tiles(box_x: f32, box_y: f32, box_width: f32, box_height: f32, image_width: f32, image_height: f32,
+                                background_style: &BackgroundStyle) -> Vec<BackgroundTile> {
+    let mut tiles = Vec::new();
+    if image_width <= 0.0 || image_height <= 0.0 || box_width <= 0.0 || box_height <= 0.0 {
+        return tiles;
+    }
+
+    let (tile_width, tile_height) = match background_style.size {
+        BackgroundSize::Cover => {
+            let scale = (box_width / image_width).max(box_height / image_height);
+            (image_width * scale, image_height * scale)
+        },
+        BackgroundSize::Contain => {
+            let scale = (box_width / image_width).min(box_height / image_height);
+            (image_width * scale, image_height * scale)
+        },
+        BackgroundSize::Explicit(width, height) => {
+            let explicit_width = resolve_background_length(width, box_width);
+            let explicit_height = resolve_background_length(height, box_height);
+
+            //when only one of the sizes is given, we keep the aspect ratio of the image
+            match (explicit_width, explicit_height) {
+                (Some(width), Some(height)) => (width, height),
+                (Some(width), None) => (width, image_height * (width / image_width)),
+                (None, Some(height)) => (image_width * (height / image_height), height),
+                (None, None) => (image_width, image_height),
+            }
+        },
+    };
+
+    if tile_width <= 0.0 || tile_height <= 0.0 {
+        return tiles;
+    }
+
+    //percentages in background-position align that point of the image with the same point of the box
+    let offset_x = match background_style.position_x {
+        BackgroundLength::Percentage(fraction) => (box_width - tile_width) * fraction,
+        BackgroundLength::Pixels(pixels) => pixels,
+        BackgroundLength::Auto => 0.0,
+    };
+    let offset_y = match background_style.position_y {
+        BackgroundLength::Percentage(fraction) => (box_height - tile_height) * fraction,
+        BackgroundLength::Pixels(pixels) => pixels,
+        BackgroundLength::Auto => 0.0,
+    };
+
+    let repeat_x = background_style.repeat == BackgroundRepeat::Repeat || background_style.repeat == BackgroundRepeat::RepeatX;
+    let repeat_y = background_style.repeat == BackgroundRepeat::Repeat || background_style.repeat == BackgroundRepeat::RepeatY;
+
+    //when repeating, we start at the first tile left (or above) the box that is still (partially) visible
+    let start_x = if repeat_x { offset_x - (offset_x / tile_width).ceil() * tile_width } else { offset_x };
+    let start_y = if repeat_y { offset_y - (offset_y / tile_height).ceil() * tile_height } else { offset_y };
+    let end_x = if repeat_x { box_width } else { start_x + 1.0 };
+    let end_y = if repeat_y { box_height } else { start_y + 1.0 };
+
+    let mut tile_y = start_y;
+    while tile_y < end_y {
+        let mut tile_x = start_x;
+        while tile_x < end_x {
+            tiles.push(BackgroundTile { x: box_x + tile_x, y: box_y + tile_y, width: tile_width, height: tile_height });
+            tile_x += tile_width;
+        }
+        tile_y += tile_height;
+    }
+
+    return tiles;
+}
+
+
+fn resolve_background_length(length: BackgroundLength, reference_size: f32) -> Option<f32> {
+    return match length {
+        BackgroundLength::Auto => None,
+        BackgroundLength::Pixels(pixels) => Some(pixels),
+        BackgroundLength::Percentage(fraction) => Some(reference_size * fraction),
+    };
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use crate::color::Color;
+use crate::style::{get_color_style_value, get_property_from_computed_styles, shadow::split_outside_parentheses};
+
+
+const BORDER_SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+const MEDIUM_BORDER_WIDTH: f32 = 3.0;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum BorderStyle {
+    None,
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct BorderSide {
+    pub width: f32,
+    pub style: BorderStyle,
+    pub color: Color,
+}
+impl BorderSide {
+    pub fn used_width(&self) -> f32 {
+        //a border without a style is not drawn, and does not take up any space
+        if self.style == BorderStyle::None {
+            return 0.0;
+        }
+        return self.width;
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Borders {
+    pub top: BorderSide,
+    pub right: BorderSide,
+    pub bottom: BorderSide,
+    pub left: BorderSide,
+}
+impl Borders {
+    pub fn none() -> Borders {
+        let side = BorderSide { width: MEDIUM_BORDER_WIDTH, style: BorderStyle::None, color: Color::BLACK };
+        return Borders { top: side, right: side, bottom: side, left: side };
+    }
+
+    pub fn has_any(&self) -> bool {
+        return self.sides().iter().any(|side| side.used_width() > 0.0);
+    }
+
+    fn sides(&self) -> [&BorderSide; 4] {
+        return [&self.top, &self.right, &self.bottom, &self.left];
+    }
+
+    fn side_mut(&mut self, side_idx: usize) -> &mut BorderSide {
+        return match side_idx {
+            0 => &mut self.top,
+            1 => &mut self.right,
+            2 => &mut self.bottom,
+            _ => &mut self.left,
+        };
+    }
+}
+
+
+pub fn is_border_property(property: &str) -> bool {
+    //border-collapse and border-spacing are about tables, and are inherited, unlike the properties of the border itself
+    return property == "border" || (property.starts_with("border-") && property != "border-collapse" && property != "border-spacing");
+}
+
+
+pub fn resolve_borders(styles: &HashMap<String, String>) -> Borders {
+    //borders without a color use the text color (currentColor)
+    let current_color = get_color_style_value(styles, "color").unwrap_or(Color::BLACK);
+
+    let mut borders = Borders::none();
+    for side_idx in 0..4 {
+        borders.side_mut(side_idx).color = current_color;
+    }
+
+    //We can't see in which order the properties were declared, so we let the more specific ones win, like in most style sheets
+    let border = get_property_from_computed_styles(styles, "border");
+    if border.is_some() {
+        for side_idx in 0..4 {
+            apply_border_shorthand(borders.side_mut(side_idx), &border.as_ref().unwrap(), current_color);
+        }
+    }
+    for side_idx in 0..4 {
+        let side_border = get_property_from_computed_styles(styles, &format!("border-{}", BORDER_SIDES[side_idx]));
+        if side_border.is_some() {
+            apply_border_shorthand(borders.side_mut(side_idx), &side_border.unwrap(), current_color);
+        }
+    }
+
+    for part in ["width", "style", "color"] {
+        //these have a value for each side, in the same way as margins and paddings (top, right, bottom, left)
+        let all_sides = get_property_from_computed_styles(styles, &format!("border-{}", part));
+        if all_sides.is_some() {
+            let values = split_outside_parentheses(&all_sides.unwrap(), ' ');
+            let values_per_side = match values.len() {
+                1 => Some([&values[0], &values[0], &values[0], &values[0]]),
+                2 => Some([&values[0], &values[1], &values[0], &values[1]]),
+                3 => Some([&values[0], &values[1], &values[2], &values[1]]),
+                4 => Some([&values[0], &values[1], &values[2], &values[3]]),
+                _ => None,
+            };
+            if values_per_side.is_some() {
+                for (side_idx, value) in values_per_side.unwrap().iter().enumerate() {
+                    apply_border_part(borders.side_mut(side_idx), part, value, current_color);
+                }
+            }
+        }
+
+        for side_idx in 0..4 {
+            let value = get_property_from_computed_styles(styles, &format!("border-{}-{}", BORDER_SIDES[side_idx], part));
+            if value.is_some() {
+                apply_border_part(borders.side_mut(side_idx), part, &value.unwrap(), current_color);
+            }
+        }
+    }
+
+    return borders;
+}
+
+
+fn apply_border_shorthand(side: &mut BorderSide, value: &str, current_color: Color) {
+    //the shorthand resets what it does not mention to the initial value
+    let mut new_side = BorderSide { width: MEDIUM_BORDER_WIDTH, style: BorderStyle::None, color: current_color };
+
+    for part in split_outside_parentheses(value, ' ') {
+        let width = parse_border_width(&part);
+        if width.is_some() {
+            new_side.width = width.unwrap();
+            continue;
+        }
+        let style = parse_border_style(&part);
+        if style.is_some() {
+            new_side.style = style.unwrap();
+            continue;
+        }
+        let color = parse_border_color(&part, current_color);
+        if color.is_some() {
+            new_side.color = color.unwrap();
+            continue;
+        }
+        //an invalid part makes the whole declaration invalid
+        return;
+    }
+
+    *side = new_side;
+}
+
+
+fn apply_border_part(side: &mut BorderSide, part: &str, value: &str, current_color: Color) {
+    match part {
+        "width" => { side.width = parse_border_width(value).unwrap_or(side.width); },
+        "style" => { side.style = parse_border_style(value).unwrap_or(side.style); },
+        "color" => { side.color = parse_border_color(value, current_color).unwrap_or(side.color); },
+        _ => { panic!("unknown border part: {}", part); }
+    }
+}
+
+
+fn parse_border_width(value: &str) -> Option<f32> {
+    match value {
+        "thin" => { return Some(1.0); },
+        "medium" => { return Some(MEDIUM_BORDER_WIDTH); },
+        "thick" => { return Some(5.0); },
+        _ => {},
+    }
+
+    //TODO: we only support px (and unitless zero) for now, like elsewhere in our css handling
+    let number_part = if value.ends_with("px") { &value[..value.len() - 2] } else { value };
+    let parsed = number_part.parse::<f32>();
+    if parsed.is_err() || parsed.as_ref().unwrap() < &0.0 {
+        return None;
+    }
+    return Some(parsed.unwrap());
+}
+
+
+fn parse_border_style(value: &str) -> Option<BorderStyle> {
+    //TODO: double, groove, ridge, inset and outset are drawn as solid for now
+    return match value {
+        "none" | "hidden" => Some(BorderStyle::None),
+        "solid" | "double" | "groove" | "ridge" | "inset" | "outset" => Some(BorderStyle::Solid),
+        "dashed" => Some(BorderStyle::Dashed),
+        "dotted" => Some(BorderStyle::Dotted),
+        _ => None,
+    };
+}
+
+
+fn parse_border_color(value: &str, current_color: Color) -> Option<Color> {
+    if value == "currentcolor" || value == "currentColor" {
+        return Some(current_color);
+    }
+    return Color::from_string(&value.to_owned());
+}
+
@@ -13,6 +13,7 @@ pub struct CssTokenWithLocation {
 #[cfg_attr(debug_assertions, derive(Debug))]
 #[derive(PartialEq)]
 pub enum CssToken {
+    AtRule(String),
     Selector(String),
     Property(String),
     Value(String),
@@ -52,6 +53,35 @@ fn lex_css_block(css_iterator: &mut TrackingIterator, tokens: &mut Vec<CssTokenW
             break 'main_loop;
         }
 
+        if css_iterator.peek() == Some(&'@') {
+            //at-rules (like @media) can contain a : in their prelude, so we need to read them before we look for properties
+
+            css_iterator.next(); //eat the @
+
+            let mut at_rule_data = String::new();
+            while css_iterator.has_next() && css_iterator.peek() != Some(&'{') && css_iterator.peek() != Some(&';') {
+                at_rule_data.push(css_iterator.next());
+            }
+
+            tokens.push(CssTokenWithLocation { css_token: CssToken::AtRule(at_rule_data.trim().to_owned()),
+                                               line: css_iterator.current_line,
+                                               character: css_iterator.current_char });
+
+            if css_iterator.peek() == Some(&'{') {
+                css_iterator.next(); //eat the {
+                tokens.push(CssTokenWithLocation { css_token: CssToken::BlockStart, line: css_iterator.current_line, character: css_iterator.current_char });
+                lex_css_block(css_iterator, tokens);
+            } else if css_iterator.peek() == Some(&';') {
+                css_iterator.next(); //eat the ;
+            }
+
+            eat_whitespace(css_iterator);
+            if !css_iterator.has_next() {
+                break 'main_loop;
+            }
+            continue;
+        }
+
         let mut selector_or_property_data = String::new();
         while css_iterator.has_next() && css_iterator.peek() != Some(&'{') && css_iterator.peek() != Some(&':') {
             selector_or_property_data.push(css_iterator.next());
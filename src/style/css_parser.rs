@@ -1,30 +1,94 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::debug::debug_log_warn;
 use crate::style::{
+    CascadeLayer,
     Selector,
+    StyleImport,
     StyleRule,
-    css_lexer::{CssToken, CssTokenWithLocation}
+    StyleSheet,
+    css_lexer::{CssToken, CssTokenWithLocation},
+    media_query::{MediaQueryList, parse_media_query_list},
 };
 
 
-pub fn parse_css(css_tokens: &Vec<CssTokenWithLocation>) -> Vec<StyleRule> {
-    let mut style_rules = Vec::new();
+static NEXT_ANONYMOUS_LAYER_ID: AtomicUsize = AtomicUsize::new(1);
+fn get_next_anonymous_layer_id() -> usize { NEXT_ANONYMOUS_LAYER_ID.fetch_add(1, Ordering::Relaxed) }
+
+
+enum ParseContext<'a> {
+    Selector(&'a String),
+    Media(Arc<MediaQueryList>),
+    Layer(String),
+    UnsupportedAtRule,
+}
+
+
+pub fn parse_css(css_tokens: &Vec<CssTokenWithLocation>) -> StyleSheet {
+    let mut style_sheet = StyleSheet { rules: Vec::new(), layers: Vec::new(), imports: Vec::new(), imported_sheets: Vec::new() };
     let mut current_context = Vec::new();
     let mut last_property = "";
+    let mut last_at_rule: Option<&String> = None;
 
     for token in css_tokens {
 
+        if last_at_rule.is_some() && token.css_token != CssToken::BlockStart {
+            //the previous at-rule did not have a block (like @import or @charset), so it was a statement
+            parse_statement_at_rule(last_at_rule.unwrap(), &current_context, &mut style_sheet);
+            last_at_rule = None;
+        }
+
         match &token.css_token {
+            CssToken::AtRule(at_rule) => {
+                //we only know if this at-rule has a block when we see the next token, so we store it until then
+                last_at_rule = Some(at_rule);
+            }
             CssToken::Selector(element) => {
-                current_context.push(element);
+                current_context.push(ParseContext::Selector(element));
             }
             CssToken::Property(property) => {
                 last_property = property;
             }
             CssToken::Value(value) => {
-                style_rules.push( StyleRule { selector: build_selector_from_context(&current_context),
-                                              property: last_property.to_string(), value: value.to_string() } );
+                if is_inside_unsupported_at_rule(&current_context) {
+                    continue;
+                }
+                let style_rule = StyleRule { selector: build_selector_from_context(&current_context),
+                                             property: last_property.to_string(), value: value.to_string(),
+                                             media: build_media_from_context(&current_context) };
+
+                let layer_name = build_layer_name_from_context(&current_context);
+                if layer_name.is_some() {
+                    get_or_create_layer(&mut style_sheet, layer_name.unwrap()).rules.push(style_rule);
+                } else {
+                    style_sheet.rules.push(style_rule);
+                }
             },
             CssToken::BlockStart => {
-                // currently we have no logic for a block start, since we push the context for each selector, assuming we start a block after...
+                // for selectors we push the context when we see them, assuming we start a block after, at-rules are pushed here
+
+                if last_at_rule.is_some() {
+                    let at_rule = last_at_rule.unwrap();
+                    if at_rule.starts_with("media") {
+                        let media_query_list = parse_media_query_list(&at_rule["media".len()..]);
+                        current_context.push(ParseContext::Media(Arc::new(media_query_list)));
+                    } else if at_rule.starts_with("layer") {
+                        let mut layer_name = at_rule["layer".len()..].trim().to_owned();
+                        if layer_name.is_empty() {
+                            layer_name = format!("<anonymous-{}>", get_next_anonymous_layer_id());
+                        }
+                        current_context.push(ParseContext::Layer(layer_name));
+
+                        //a layer block also declares the layer, even if there are no rules in it
+                        let full_layer_name = build_layer_name_from_context(&current_context).unwrap();
+                        get_or_create_layer(&mut style_sheet, full_layer_name);
+                    } else {
+                        debug_log_warn(format!("unsupported css at-rule: @{}", at_rule));
+                        current_context.push(ParseContext::UnsupportedAtRule);
+                    }
+                    last_at_rule = None;
+                }
             },
             CssToken::BlockEnd => {
                 current_context.pop();
@@ -32,17 +96,174 @@ pub fn parse_css(css_tokens: &Vec<CssTokenWithLocation>) -> Vec<StyleRule> {
         }
     }
 
-    return style_rules;
+    if last_at_rule.is_some() {
+        parse_statement_at_rule(last_at_rule.unwrap(), &current_context, &mut style_sheet);
+    }
+
+    return style_sheet;
+}
+
+
+fn parse_statement_at_rule(at_rule: &String, context: &Vec<ParseContext>, style_sheet: &mut StyleSheet) {
+    if at_rule.starts_with("import") {
+        if !context.is_empty() || !style_sheet.rules.is_empty() {
+            //@import is only valid before all other rules (except @charset and @layer statements)
+            debug_log_warn(format!("ignoring @import that is not at the start of the stylesheet: @{}", at_rule));
+            return;
+        }
+
+        let style_import = parse_import(&at_rule["import".len()..]);
+        if style_import.is_some() {
+            let mut style_import = style_import.unwrap();
+            style_import.layers_declared_before = style_sheet.layers.len();
+            style_sheet.imports.push(style_import);
+        }
+
+    } else if at_rule.starts_with("layer") {
+        //this is the statement form (@layer a, b;), which only declares the order of the layers
+        for layer_name in at_rule["layer".len()..].split(',') {
+            let layer_name = layer_name.trim();
+            if layer_name.is_empty() {
+                continue;
+            }
+
+            let mut full_layer_name = build_layer_name_from_context(context).unwrap_or(String::new());
+            if !full_layer_name.is_empty() {
+                full_layer_name.push('.');
+            }
+            full_layer_name.push_str(layer_name);
+            get_or_create_layer(style_sheet, full_layer_name);
+        }
+
+    } else if at_rule.starts_with("charset") {
+        //we always decode as utf-8 for now, so there is nothing to do here
+
+    } else {
+        debug_log_warn(format!("unsupported css at-rule: @{}", at_rule));
+    }
+}
+
+
+fn parse_import(import_data: &str) -> Option<StyleImport> {
+    let mut remaining = import_data.trim();
+
+    let url;
+    if remaining.starts_with("url(") {
+        let closing_idx = remaining.find(')');
+        if closing_idx.is_none() {
+            debug_log_warn(format!("could not parse @import url: {}", import_data));
+            return None;
+        }
+        url = strip_quotes(&remaining["url(".len()..closing_idx.unwrap()]);
+        remaining = &remaining[closing_idx.unwrap() + 1..];
+
+    } else if remaining.starts_with('"') || remaining.starts_with('\'') {
+        let quote = remaining.chars().next().unwrap();
+        let closing_idx = remaining[1..].find(quote);
+        if closing_idx.is_none() {
+            debug_log_warn(format!("could not parse @import url: {}", import_data));
+            return None;
+        }
+        url = remaining[1..closing_idx.unwrap() + 1].to_owned();
+        remaining = &remaining[closing_idx.unwrap() + 2..];
+
+    } else {
+        debug_log_warn(format!("could not parse @import url: {}", import_data));
+        return None;
+    }
+
+    remaining = remaining.trim_start();
+    let mut layer = None;
+    if remaining.starts_with("layer(") {
+        let closing_idx = remaining.find(')');
+        if closing_idx.is_some() {
+            layer = Some(remaining["layer(".len()..closing_idx.unwrap()].trim().to_owned());
+            remaining = &remaining[closing_idx.unwrap() + 1..];
+        }
+    } else if remaining == "layer" || remaining.starts_with("layer ") {
+        layer = Some(format!("<anonymous-{}>", get_next_anonymous_layer_id()));
+        remaining = &remaining["layer".len()..];
+    }
+
+    let mut media = Vec::new();
+    if !remaining.trim().is_empty() {
+        media.push(Arc::new(parse_media_query_list(remaining)));
+    }
+
+    return Some(StyleImport { url, layer, media, layers_declared_before: 0 });
+}
+
+
+fn strip_quotes(text: &str) -> String {
+    let text = text.trim();
+    if text.len() >= 2 && ((text.starts_with('"') && text.ends_with('"')) || (text.starts_with('\'') && text.ends_with('\''))) {
+        return text[1..text.len() - 1].to_owned();
+    }
+    return text.to_owned();
+}
+
+
+fn get_or_create_layer(style_sheet: &mut StyleSheet, layer_name: String) -> &mut CascadeLayer {
+    let existing_idx = style_sheet.layers.iter().position(|layer| layer.name == layer_name);
+    if existing_idx.is_some() {
+        return &mut style_sheet.layers[existing_idx.unwrap()];
+    }
+
+    style_sheet.layers.push(CascadeLayer { name: layer_name, rules: Vec::new() });
+    return style_sheet.layers.last_mut().unwrap();
 }
 
 
-fn build_selector_from_context(context: &Vec<&String>) -> Selector {
+fn build_selector_from_context(context: &Vec<ParseContext>) -> Selector {
     //TODO: eventually we need to parse other things than just nodes here...
 
     let mut all_selectors = Vec::new();
-    for selector in context {
-        all_selectors.push((*selector).clone());
+    for context_item in context {
+        match context_item {
+            ParseContext::Selector(selector) => { all_selectors.push((*selector).clone()); },
+            ParseContext::Media(_) | ParseContext::Layer(_) | ParseContext::UnsupportedAtRule => {},
+        }
     }
 
     return Selector { nodes: Some(all_selectors) }
 }
+
+
+fn build_media_from_context(context: &Vec<ParseContext>) -> Vec<Arc<MediaQueryList>> {
+    let mut media = Vec::new();
+    for context_item in context {
+        match context_item {
+            ParseContext::Media(media_query_list) => { media.push(Arc::clone(media_query_list)); },
+            ParseContext::Selector(_) | ParseContext::Layer(_) | ParseContext::UnsupportedAtRule => {},
+        }
+    }
+    return media;
+}
+
+
+fn build_layer_name_from_context(context: &Vec<ParseContext>) -> Option<String> {
+    //nested layers get a name that is joined with dots, so "@layer a { @layer b {} }" is the same as "@layer a.b {}"
+    let mut layer_names = Vec::new();
+    for context_item in context {
+        match context_item {
+            ParseContext::Layer(layer_name) => { layer_names.push(layer_name.as_str()); },
+            ParseContext::Selector(_) | ParseContext::Media(_) | ParseContext::UnsupportedAtRule => {},
+        }
+    }
+
+    if layer_names.is_empty() {
+        return None;
+    }
+    return Some(layer_names.join("."));
+}
+
+
+fn is_inside_unsupported_at_rule(context: &Vec<ParseContext>) -> bool {
+    for context_item in context {
+        match context_item {
+            ParseContext::UnsupportedAtRule => { return true; },
+            ParseContext::Selector(_) | ParseContext::Media(_) | ParseContext::Layer(_) => {},
+        }
+    }
+    return false;
+}
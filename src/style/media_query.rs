@@ -0,0 +1,251 @@
+use crate::debug::debug_log_warn;
+use crate::ui::WindowDimensions;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct MediaEnvironment {
+    pub width: f32,
+    pub height: f32,
+    pub color_scheme: ColorScheme,
+}
+impl MediaEnvironment {
+    pub fn new_default() -> MediaEnvironment {
+        let window_dimensions = WindowDimensions::new_default();
+        return MediaEnvironment { width: window_dimensions.content_width(), height: window_dimensions.content_height(), color_scheme: ColorScheme::Light };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+impl ColorScheme {
+    pub fn from_str(value: &str) -> Option<ColorScheme> {
+        return match value.trim().to_lowercase().as_str() {
+            "light" => Some(ColorScheme::Light),
+            "dark" => Some(ColorScheme::Dark),
+            _ => None,
+        };
+    }
+
+    pub fn toggled(&self) -> ColorScheme {
+        return match self {
+            ColorScheme::Light => ColorScheme::Dark,
+            ColorScheme::Dark => ColorScheme::Light,
+        };
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct MediaQueryList {
+    //this list matches if any of the queries in it match (they are comma separated in css)
+    pub queries: Vec<MediaQuery>,
+}
+impl MediaQueryList {
+    pub fn matches(&self, environment: &MediaEnvironment) -> bool {
+        for query in &self.queries {
+            if query.matches(environment) {
+                return true;
+            }
+        }
+        return false;
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct MediaQuery {
+    pub negated: bool,
+    pub media_type: MediaType,
+    pub features: Vec<MediaFeature>,
+}
+impl MediaQuery {
+    fn matches(&self, environment: &MediaEnvironment) -> bool {
+        let type_matches = match self.media_type {
+            MediaType::All | MediaType::Screen => true,
+            MediaType::Print | MediaType::Unknown => false,
+        };
+
+        let mut all_features_match = true;
+        for feature in &self.features {
+            if !feature.matches(environment) {
+                all_features_match = false;
+                break;
+            }
+        }
+
+        let result = type_matches && all_features_match;
+        if self.negated {
+            return !result;
+        }
+        return result;
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(PartialEq)]
+pub enum MediaType {
+    All,
+    Screen,
+    Print,
+    Unknown,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(PartialEq)]
+pub enum MediaFeature {
+    MinWidth(f32),
+    MaxWidth(f32),
+    MinHeight(f32),
+    MaxHeight(f32),
+    PrefersColorScheme(ColorScheme),
+    Unsupported,
+}
+impl MediaFeature {
+    fn matches(&self, environment: &MediaEnvironment) -> bool {
+        return match self {
+            MediaFeature::MinWidth(value) => environment.width >= *value,
+            MediaFeature::MaxWidth(value) => environment.width <= *value,
+            MediaFeature::MinHeight(value) => environment.height >= *value,
+            MediaFeature::MaxHeight(value) => environment.height <= *value,
+            MediaFeature::PrefersColorScheme(color_scheme) => environment.color_scheme == *color_scheme,
+            MediaFeature::Unsupported => false,
+        };
+    }
+}
+
+
+pub fn parse_media_query_list(text: &str) -> MediaQueryList {
+    //TODO: this only supports the most common forms, like "screen and (max-width: 600px)", not the newer range syntax (width <= 600px)
+
+    let mut queries = Vec::new();
+    for query_text in text.split(',') {
+        let query_text = query_text.trim();
+        if query_text.is_empty() {
+            continue;
+        }
+        queries.push(parse_media_query(query_text));
+    }
+
+    if queries.is_empty() {
+        //an empty media query list (for example "@media {") matches everything
+        queries.push(MediaQuery { negated: false, media_type: MediaType::All, features: Vec::new() });
+    }
+
+    return MediaQueryList { queries };
+}
+
+
+fn parse_media_query(text: &str) -> MediaQuery {
+    let mut negated = false;
+    let mut media_type = MediaType::All;
+    let mut features = Vec::new();
+
+    let lowercase_text = text.to_lowercase();
+    let mut remaining = lowercase_text.as_str();
+
+    loop {
+        remaining = remaining.trim_start();
+        if remaining.is_empty() {
+            break;
+        }
+
+        if remaining.starts_with('(') {
+            let closing_idx = remaining.find(')');
+            if closing_idx.is_none() {
+                debug_log_warn(format!("unclosed media feature in media query: {}", text));
+                features.push(MediaFeature::Unsupported);
+                break;
+            }
+            let closing_idx = closing_idx.unwrap();
+            features.push(parse_media_feature(&remaining[1..closing_idx]));
+            remaining = &remaining[closing_idx + 1..];
+            continue;
+        }
+
+        let word_end = remaining.find(|c: char| c.is_whitespace() || c == '(').unwrap_or(remaining.len());
+        let word = &remaining[..word_end];
+        remaining = &remaining[word_end..];
+
+        match word {
+            "not" => { negated = true; }
+            "only" | "and" => {}
+            "all" => { media_type = MediaType::All; }
+            "screen" => { media_type = MediaType::Screen; }
+            "print" => { media_type = MediaType::Print; }
+            _ => {
+                debug_log_warn(format!("unknown media type in media query: {}", word));
+                media_type = MediaType::Unknown;
+            }
+        }
+    }
+
+    return MediaQuery { negated, media_type, features };
+}
+
+
+fn parse_media_feature(text: &str) -> MediaFeature {
+    let colon_idx = text.find(':');
+    if colon_idx.is_none() {
+        debug_log_warn(format!("unsupported media feature: {}", text));
+        return MediaFeature::Unsupported;
+    }
+    let colon_idx = colon_idx.unwrap();
+
+    let name = text[..colon_idx].trim();
+    if name == "prefers-color-scheme" {
+        let color_scheme = ColorScheme::from_str(&text[colon_idx + 1..]);
+        if color_scheme.is_none() {
+            debug_log_warn(format!("could not parse media feature value: {}", text));
+            return MediaFeature::Unsupported;
+        }
+        return MediaFeature::PrefersColorScheme(color_scheme.unwrap());
+    }
+
+    let value = parse_media_length(text[colon_idx + 1..].trim());
+    if value.is_none() {
+        debug_log_warn(format!("could not parse media feature value: {}", text));
+        return MediaFeature::Unsupported;
+    }
+    let value = value.unwrap();
+
+    return match name {
+        "min-width" => MediaFeature::MinWidth(value),
+        "max-width" => MediaFeature::MaxWidth(value),
+        "min-height" => MediaFeature::MinHeight(value),
+        "max-height" => MediaFeature::MaxHeight(value),
+        _ => {
+            debug_log_warn(format!("unsupported media feature: {}", name));
+            MediaFeature::Unsupported
+        }
+    };
+}
+
+
+fn parse_media_length(value: &str) -> Option<f32> {
+    //TODO: em and rem in media queries are relative to the initial font size, we assume 16px for now
+    const INITIAL_FONT_SIZE: f32 = 16.0;
+
+    let (number_part, multiplier) = if value.ends_with("px") {
+        (&value[..value.len() - 2], 1.0)
+    } else if value.ends_with("rem") {
+        (&value[..value.len() - 3], INITIAL_FONT_SIZE)
+    } else if value.ends_with("em") {
+        (&value[..value.len() - 2], INITIAL_FONT_SIZE)
+    } else {
+        (value, 1.0)
+    };
+
+    let parsed = number_part.trim().parse::<f32>();
+    if parsed.is_err() {
+        return None;
+    }
+    return Some(parsed.unwrap() * multiplier);
+}
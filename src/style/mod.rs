@@ -1,15 +1,25 @@
+pub mod background;
+pub mod border;
 pub mod css_lexer;
 pub mod css_parser;
+pub mod media_query;
+pub mod shadow;
+pub mod transition;
 
 
-use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+#[cfg(feature="timings")] use std::time::Instant;
 
+use rayon::prelude::*;
+
+use crate::arena::Arena;
 use crate::color::Color;
+use crate::config::SETTINGS;
 use crate::debug::debug_log_warn;
-use crate::dom::ElementDomNode;
+use crate::dom::{AttributeDomNode, DomNodeId, ElementDomNode};
+use crate::style::media_query::{ColorScheme, MediaEnvironment, MediaQueryList, parse_media_query_list};
 
 
 #[cfg(test)] mod tests;
@@ -17,18 +27,236 @@ use crate::dom::ElementDomNode;
 #[cfg(test)] mod test_parser;
 
 
+//TODO: there are many more properties that are not inherited: https://developer.mozilla.org/en-US/docs/Web/CSS/Inheritance
+const NON_INHERITED_PROPERTIES: [&str; 12] = ["background-color", "background-image", "background-position", "background-repeat", "background-size",
+                                              "box-shadow", "opacity", "transition", "transition-delay", "transition-duration",
+                                              "transition-property", "transition-timing-function"];
+
+//in a dark color scheme, pages that don't set their own colors are shown as light text on a dark background
+const DARK_SCHEME_TEXT_COLOR: &str = "#e8eaed";
+const DARK_SCHEME_LINK_COLOR: &str = "#8ab4f8";
+const DARK_SCHEME_VISITED_LINK_COLOR: &str = "#c58af9";
+const VISITED_LINK_COLOR: &str = "#551a8b";
+const DARK_SCHEME_CANVAS_COLOR: Color = Color::new(32, 33, 36);
+
+const USER_AGENT_LAYER_NAME: &str = "<user-agent>";
+const UNLAYERED_AUTHOR_LAYER_NAME: &str = "<unlayered>";
+
+
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct StyleContext {
-    pub user_agent_sheet: Vec<StyleRule>,
-    pub author_sheet: Vec<StyleRule>,
+    //The layers are ordered from lowest to highest priority. The user agent styles are always the first layer, and the author styles that are
+    //not in an explicit @layer are always the last one. We don't implement the USER origin.
+    pub cascade_layers: Vec<CascadeLayer>,
+    author_style_sheets: Vec<StyleSheet>, //the sheets of the document in order, the layers (except the user agent one) are built from these
+    media_environment: MediaEnvironment,
+    visited_links: HashSet<String>, //the href attributes (as written) of the links on the page that go to a page in the browsing history
+    generation: u32, //this goes up when anything changes that can change the styles of any node, so the styles cached on the nodes are outdated
+}
+impl StyleContext {
+    pub fn new(user_agent_sheet: Vec<StyleRule>, author_sheet: Vec<StyleRule>, media_environment: MediaEnvironment) -> StyleContext {
+        let mut style_context = StyleContext {
+            cascade_layers: vec![CascadeLayer { name: USER_AGENT_LAYER_NAME.to_owned(), rules: user_agent_sheet }],
+            author_style_sheets: vec![StyleSheet { rules: author_sheet, layers: Vec::new(), imports: Vec::new(), imported_sheets: Vec::new() }],
+            media_environment,
+            visited_links: HashSet::new(),
+            generation: 0,
+        };
+        style_context.rebuild_author_layers();
+        return style_context;
+    }
+    pub fn media_environment(&self) -> MediaEnvironment {
+        return self.media_environment;
+    }
+    pub fn set_media_environment(&mut self, media_environment: MediaEnvironment) {
+        if media_environment != self.media_environment {
+            self.media_environment = media_environment;
+            self.generation += 1;
+        }
+    }
+    pub fn visited_links(&self) -> &HashSet<String> {
+        return &self.visited_links;
+    }
+    pub fn set_visited_links(&mut self, visited_links: HashSet<String>) {
+        if visited_links != self.visited_links {
+            self.visited_links = visited_links;
+            self.generation += 1;
+        }
+    }
+    pub fn collect_background_image_urls(&self) -> Vec<String> {
+        //we use this to start loading background images early, without knowing yet if the rules will actually match an element
+        let mut urls = Vec::new();
+        for cascade_layer in &self.cascade_layers {
+            for rule in &cascade_layer.rules {
+                if rule.property == "background-image" {
+                    let url = background::parse_css_url(&rule.value);
+                    if url.is_some() && !urls.contains(url.as_ref().unwrap()) {
+                        urls.push(url.unwrap());
+                    }
+                }
+            }
+        }
+        return urls;
+    }
+    pub fn add_author_style_sheet(&mut self, mut style_sheet: StyleSheet) -> StyleSheetSlot {
+        //returns the slot of the sheet, the sheets it imports go in the slots below that, when they are loaded
+        style_sheet.imported_sheets = style_sheet.imports.iter().map(|_| None).collect();
+        self.author_style_sheets.push(style_sheet);
+        self.rebuild_author_layers();
+        return vec![self.author_style_sheets.len() - 1];
+    }
+    pub fn fill_import_slot(&mut self, slot: &StyleSheetSlot, mut style_sheet: StyleSheet) {
+        //the slot was reserved when the importing sheet was added, so the imported rules come before the rules of that sheet, like the spec says
+        let mut imports_on_path = Vec::new();
+        let mut importing_sheet = self.author_style_sheets.get(slot[0]);
+        for import_idx in &slot[1..] {
+            if importing_sheet.is_none() {
+                return;
+            }
+            imports_on_path.push(importing_sheet.unwrap().imports[*import_idx].clone());
+            importing_sheet = importing_sheet.unwrap().imported_sheets[*import_idx].as_ref();
+        }
+
+        //the conditions of all the @imports that lead to the sheet apply to it, starting with its own @import
+        for style_import in imports_on_path.iter().rev() {
+            style_sheet.apply_import_conditions(style_import);
+        }
+        style_sheet.imported_sheets = style_sheet.imports.iter().map(|_| None).collect();
+
+        let mut parent_sheet = &mut self.author_style_sheets[slot[0]];
+        for import_idx in &slot[1..slot.len() - 1] {
+            parent_sheet = parent_sheet.imported_sheets[*import_idx].as_mut().unwrap();
+        }
+        parent_sheet.imported_sheets[*slot.last().unwrap()] = Some(style_sheet);
+        self.rebuild_author_layers();
+    }
+    fn rebuild_author_layers(&mut self) {
+        let mut unlayered = CascadeLayerTree { name: UNLAYERED_AUTHOR_LAYER_NAME.to_owned(), rules: Vec::new(), sublayers: Vec::new() };
+        for style_sheet in &self.author_style_sheets {
+            unlayered.add_style_sheet(style_sheet);
+        }
+
+        self.cascade_layers.truncate(1);
+        unlayered.append_in_cascade_order(&mut self.cascade_layers);
+        self.generation += 1;
+    }
+}
+
+
+pub type StyleSheetSlot = Vec<usize>; //the index of a sheet of the document, followed by the index of the @import in it, for each level of imports
+
+
+struct CascadeLayerTree {
+    name: String,
+    rules: Vec<StyleRule>,
+    sublayers: Vec<CascadeLayerTree>, //in the order they were first declared
+}
+impl CascadeLayerTree {
+    fn add_style_sheet(&mut self, style_sheet: &StyleSheet) {
+        //the imported sheets come first, and layers declared in an @layer statement before an @import are declared before its layers
+        let mut next_layer_idx = 0;
+        for (style_import, imported_sheet) in style_sheet.imports.iter().zip(style_sheet.imported_sheets.iter()) {
+            while next_layer_idx < style_import.layers_declared_before && next_layer_idx < style_sheet.layers.len() {
+                self.find_or_declare_layer(&style_sheet.layers[next_layer_idx].name);
+                next_layer_idx += 1;
+            }
+            if imported_sheet.is_some() {
+                self.add_style_sheet(imported_sheet.as_ref().unwrap());
+            }
+        }
+
+        for layer in &style_sheet.layers {
+            self.find_or_declare_layer(&layer.name).rules.extend(layer.rules.iter().cloned());
+        }
+        self.rules.extend(style_sheet.rules.iter().cloned());
+    }
+
+    fn find_or_declare_layer(&mut self, full_name: &str) -> &mut CascadeLayerTree {
+        //nested layers have their names joined with dots, so "a.b" is layer b in layer a (which is declared as well when it is not yet)
+        let mut layer = self;
+        let mut name_so_far = String::new();
+        for name_part in full_name.split('.') {
+            if !name_so_far.is_empty() {
+                name_so_far.push('.');
+            }
+            name_so_far.push_str(name_part);
+
+            let sublayer_idx = layer.sublayers.iter().position(|sublayer| sublayer.name == name_so_far);
+            if sublayer_idx.is_none() {
+                layer.sublayers.push(CascadeLayerTree { name: name_so_far.clone(), rules: Vec::new(), sublayers: Vec::new() });
+            }
+            let sublayer_idx = sublayer_idx.unwrap_or(layer.sublayers.len() - 1);
+            layer = &mut layer.sublayers[sublayer_idx];
+        }
+        return layer;
+    }
+
+    fn append_in_cascade_order(self, cascade_layers: &mut Vec<CascadeLayer>) {
+        //the rules directly in a layer win over the rules in its sublayers, so the sublayers go first
+        for sublayer in self.sublayers {
+            sublayer.append_in_cascade_order(cascade_layers);
+        }
+        cascade_layers.push(CascadeLayer { name: self.name, rules: self.rules });
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct CascadeLayer {
+    pub name: String,
+    pub rules: Vec<StyleRule>,
 }
 
 
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct StyleSheet {
+    pub rules: Vec<StyleRule>, //these are the rules not in an explicit @layer
+    pub layers: Vec<CascadeLayer>,
+    pub imports: Vec<StyleImport>,
+    pub imported_sheets: Vec<Option<StyleSheet>>, //the sheets for the imports, once they are loaded
+}
+impl StyleSheet {
+    pub fn apply_import_conditions(&mut self, style_import: &StyleImport) {
+        //the layer and media conditions on an @import apply to all rules in the imported sheet
+
+        let all_rules = self.rules.iter_mut().chain(self.layers.iter_mut().flat_map(|layer| layer.rules.iter_mut()));
+        for rule in all_rules {
+            let mut media = style_import.media.clone();
+            media.append(&mut rule.media);
+            rule.media = media;
+        }
+
+        if style_import.layer.is_some() {
+            let import_layer_name = style_import.layer.as_ref().unwrap();
+
+            for layer in self.layers.iter_mut() {
+                layer.name = format!("{}.{}", import_layer_name, layer.name);
+            }
+
+            let unlayered_rules = std::mem::take(&mut self.rules);
+            self.layers.push(CascadeLayer { name: import_layer_name.clone(), rules: unlayered_rules });
+        }
+    }
+}
+
+
+#[derive(Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct StyleImport {
+    pub url: String,
+    pub layer: Option<String>,
+    pub media: Vec<Arc<MediaQueryList>>,
+    pub layers_declared_before: usize, //the number of layers of the sheet that were declared (with an @layer statement) before this @import
+}
+
+
+#[derive(Clone)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct StyleRule {
     pub selector: Selector,
     pub property: String,
     pub value: String,
+    pub media: Vec<Arc<MediaQueryList>>, //all of these need to match for the rule to apply (they can be nested)
 }
 
 
@@ -40,18 +268,10 @@ pub struct Selector {
 }
 
 
-#[derive(PartialEq)]
-enum Origin {
-    Author,
-    UserAgent,
-    //we don't implement the USER origin
-}
-
-
 struct ActiveStyleRule<'a> {
     property: &'a String,
     property_value: &'a String,
-    origin: Origin,
+    cascade_layer_idx: usize,
     specificity_attribute: u8,
     specificity_id: u8,
     specificity_class: u8,
@@ -60,52 +280,166 @@ struct ActiveStyleRule<'a> {
 }
 
 
-//TODO: we are now doing this when rendering. It might make more sense to do this earlier, cache the result on the node, and recompute only when needed
-pub fn resolve_full_styles_for_layout_node<'a>(dom_node: &'a Rc<RefCell<ElementDomNode>>, all_dom_nodes: &'a HashMap<usize, Rc<RefCell<ElementDomNode>>>,
-                                               style_context: &StyleContext) -> HashMap<String, String> {
+//the parts of a dom node that matching style rules looks at. Dom nodes can't be shared between threads (they have scripts and page components),
+//so when we resolve styles in parallel the threads get these instead
+#[derive(Clone, Copy)]
+struct StyledElement<'a> {
+    name: Option<&'a String>,
+    attributes: Option<&'a Vec<AttributeDomNode>>,
+}
+impl<'a> StyledElement<'a> {
+    fn of(dom_node: &'a ElementDomNode) -> StyledElement<'a> {
+        return StyledElement { name: dom_node.name.as_ref(), attributes: dom_node.attributes.as_ref() };
+    }
+    fn get_attribute_value(&self, attribute_name: &str) -> Option<String> {
+        if self.attributes.is_some() {
+            for att in self.attributes.unwrap() {
+                if att.name == attribute_name {
+                    return Some(att.value.clone());
+                }
+            }
+        }
+        return None;
+    }
+}
+
+
+//These are the styles of a dom node, as computed by compute_styles. They stay valid until the attributes of the node change, the style context
+//changes, or the styles of the parent they were inherited from change (which we know because its generation is different).
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct CachedStyles {
+    pub styles: HashMap<String, String>, //these include the inherited styles
+    matched_styles: HashMap<String, String>, //the styles from the rules that match the node, and from its style attribute
+    matched_styles_outdated: bool,
+    style_context_generation: u32,
+    inherited_from: Option<(DomNodeId, u32)>, //the parent, and the generation of its styles, when we inherited from it
+    generation: u32, //this goes up when the styles change, so the children know they need to inherit again
+}
+impl CachedStyles {
+    pub fn mark_outdated(&mut self) {
+        self.matched_styles_outdated = true;
+    }
+}
+
+
+pub fn resolve_full_styles_for_layout_node(dom_node_id: DomNodeId, dom_nodes: &Arena<ElementDomNode>, style_context: &StyleContext) -> HashMap<String, String> {
+    //this resolves the styles without looking at (or updating) the styles cached on the nodes, compute_styles does use those
+    let dom_node = &dom_nodes[dom_node_id];
+
+    let mut resolved_styles = resolve_own_styles(&StyledElement::of(dom_node), style_context);
+
+    let parent_styles = dom_node.parent.map(|parent_id| resolve_full_styles_for_layout_node(parent_id, dom_nodes, style_context));
+    inherit_styles(&mut resolved_styles, parent_styles.as_ref(), dom_node.text.is_some(), style_context);
+
+    return resolved_styles;
+}
+
+
+pub fn compute_styles(dom_node_ids: &HashSet<DomNodeId>, dom_nodes: &mut Arena<ElementDomNode>,
+                      style_context: &StyleContext) -> HashMap<DomNodeId, HashMap<String, String>> {
+    //This updates the styles cached on the nodes, and returns them. Matching the style rules is the expensive part, and does not depend on other
+    //nodes, so we do that in parallel for the nodes that need it. Inheriting needs the styles of the ancestors (also the ones not in dom_node_ids),
+    //so that is done afterwards, from the top down.
+    #[cfg(feature="timings")] let start_instant = Instant::now();
+
+    let mut styled_elements = Vec::new();
+    let mut seen_node_ids = HashSet::new();
+    for dom_node_id in dom_node_ids {
+        let mut current_id = Some(*dom_node_id);
+        while current_id.is_some() && seen_node_ids.insert(current_id.unwrap()) {
+            let dom_node = &dom_nodes[current_id.unwrap()];
+            let cached_styles = dom_node.cached_styles.as_ref();
+            if cached_styles.is_none() || cached_styles.unwrap().matched_styles_outdated ||
+               cached_styles.unwrap().style_context_generation != style_context.generation {
+                styled_elements.push((current_id.unwrap(), StyledElement::of(dom_node)));
+            }
+            current_id = dom_node.parent;
+        }
+    }
+
+    let mut rematched_styles: HashMap<DomNodeId, HashMap<String, String>> = styled_elements.par_iter().map(|(dom_node_id, styled_element)| {
+        return (*dom_node_id, resolve_own_styles(styled_element, style_context));
+    }).collect();
+
+    #[cfg(feature="timings")] let nr_of_matched_nodes = rematched_styles.len();
+
+    let mut updated_node_ids = HashSet::new();
+    for dom_node_id in dom_node_ids {
+        update_cached_styles(*dom_node_id, dom_nodes, &mut rematched_styles, &mut updated_node_ids, style_context);
+    }
+
+    #[cfg(feature="timings")] println!("computing styles for {} nodes ({} matched again) elapsed millis: {}",
+                                       dom_node_ids.len(), nr_of_matched_nodes, start_instant.elapsed().as_millis());
+
+    return dom_node_ids.iter().map(|dom_node_id| (*dom_node_id, dom_nodes[*dom_node_id].cached_styles.as_ref().unwrap().styles.clone())).collect();
+}
+
+
+fn update_cached_styles(dom_node_id: DomNodeId, dom_nodes: &mut Arena<ElementDomNode>, rematched_styles: &mut HashMap<DomNodeId, HashMap<String, String>>,
+                        updated_node_ids: &mut HashSet<DomNodeId>, style_context: &StyleContext) {
+    if !updated_node_ids.insert(dom_node_id) {
+        return;
+    }
+
+    let parent_id = dom_nodes[dom_node_id].parent;
+    if parent_id.is_some() {
+        update_cached_styles(parent_id.unwrap(), dom_nodes, rematched_styles, updated_node_ids, style_context);
+    }
+    let inherited_from = parent_id.map(|parent_id| (parent_id, dom_nodes[parent_id].cached_styles.as_ref().unwrap().generation));
+
+    let new_matched_styles = rematched_styles.remove(&dom_node_id);
+    let old_cached_styles = dom_nodes[dom_node_id].cached_styles.take();
+    if new_matched_styles.is_none() && old_cached_styles.as_ref().unwrap().inherited_from == inherited_from {
+        //nothing changed that could change the styles of this node
+        dom_nodes[dom_node_id].cached_styles = old_cached_styles;
+        return;
+    }
 
-    //TODO: we are doing the cascade here by first doing the ua sheet, and then the author sheet. We need to make this more general in cascades
-    //      because we need to support @layer, which adds an arbitrary amount of cascades
+    let matched_styles = if new_matched_styles.is_some() { new_matched_styles.unwrap() } else { old_cached_styles.as_ref().unwrap().matched_styles.clone() };
+    let mut styles = matched_styles.clone();
+    let parent_styles = parent_id.map(|parent_id| &dom_nodes[parent_id].cached_styles.as_ref().unwrap().styles);
+    inherit_styles(&mut styles, parent_styles, dom_nodes[dom_node_id].text.is_some(), style_context);
 
-    let dom_node = dom_node.borrow();
+    //when the styles turn out the same, the children don't need to inherit again
+    let generation = match old_cached_styles {
+        Some(old_cached_styles) if old_cached_styles.styles == styles => old_cached_styles.generation,
+        Some(old_cached_styles) => old_cached_styles.generation + 1,
+        None => 0,
+    };
 
+    dom_nodes[dom_node_id].cached_styles = Some(CachedStyles {
+        styles,
+        matched_styles,
+        matched_styles_outdated: false,
+        style_context_generation: style_context.generation,
+        inherited_from,
+        generation,
+    });
+}
+
+
+fn resolve_own_styles(styled_element: &StyledElement, style_context: &StyleContext) -> HashMap<String, String> {
     let mut rule_idx = 1;
 
     let mut active_style_rules = Vec::new();
-    for style_rule in &style_context.user_agent_sheet {
-        if style_rule_does_apply(&style_rule, &dom_node) {
-            active_style_rules.push(
-                ActiveStyleRule {
-                    property: &style_rule.property,
-                    property_value: &style_rule.value,
-                    origin: Origin::UserAgent,
-                    specificity_attribute: 0,  //TODO: implement
-                    specificity_id: 0,  //TODO: implement
-                    specificity_class: 0,  //TODO: implement
-                    specificity_type: 0,  //TODO: implement
-                    definition_order: rule_idx,
-                }
-            );
-        }
-        rule_idx += 1;
-    }
-
-    for style_rule in &style_context.author_sheet {
-        if style_rule_does_apply(&style_rule, &dom_node) {
-            active_style_rules.push(
-                ActiveStyleRule {
-                    property: &style_rule.property,
-                    property_value: &style_rule.value,
-                    origin: Origin::Author,
-                    specificity_attribute: 0,  //TODO: implement
-                    specificity_id: 0,  //TODO: implement
-                    specificity_class: 0,  //TODO: implement
-                    specificity_type: 0,  //TODO: implement
-                    definition_order: rule_idx,
-                }
-            );
+    for (cascade_layer_idx, cascade_layer) in style_context.cascade_layers.iter().enumerate() {
+        for style_rule in &cascade_layer.rules {
+            if style_rule_does_apply(&style_rule, styled_element, style_context) {
+                active_style_rules.push(
+                    ActiveStyleRule {
+                        property: &style_rule.property,
+                        property_value: &style_rule.value,
+                        cascade_layer_idx,
+                        specificity_attribute: 0,  //TODO: implement
+                        specificity_id: 0,  //TODO: implement
+                        specificity_class: 0,  //TODO: implement
+                        specificity_type: 0,  //TODO: implement
+                        definition_order: rule_idx,
+                    }
+                );
+            }
+            rule_idx += 1;
         }
-        rule_idx += 1;
     }
 
     active_style_rules.sort_by(|rule_a, rule_b| compare_style_rules(rule_a, rule_b));
@@ -115,46 +449,123 @@ pub fn resolve_full_styles_for_layout_node<'a>(dom_node: &'a Rc<RefCell<ElementD
         resolved_styles.insert((*active_style_rule.property).clone(), (*active_style_rule.property_value).clone());
     }
 
-    if dom_node.parent_id != 0 {
-        let parent_node = all_dom_nodes.get(&dom_node.parent_id).expect(format!("id {} not present in all nodes", dom_node.parent_id).as_str());
+    //the inline style (in the style attribute) wins over all style rules
+    //TODO: !important declarations in style sheets should win over the inline style, but we don't support !important yet
+    let inline_style = styled_element.get_attribute_value("style");
+    if inline_style.is_some() {
+        for (property, value) in parse_inline_style(&inline_style.unwrap()) {
+            resolved_styles.insert(property, value);
+        }
+    }
+
+    return resolved_styles;
+}
 
-        //TODO: not all properties should be inherited: https://developer.mozilla.org/en-US/docs/Web/CSS/Inheritance
 
-        let parent_styles = resolve_full_styles_for_layout_node(parent_node, all_dom_nodes, style_context);
+fn inherit_styles(resolved_styles: &mut HashMap<String, String>, parent_styles: Option<&HashMap<String, String>>, is_text_node: bool,
+                  style_context: &StyleContext) {
+    if parent_styles.is_some() {
+        for (parent_style_property, parent_style_value) in parent_styles.unwrap() {
+            //text nodes don't have styles themselves, they do need the non-inherited ones to render their part of the parent (like the background)
+            let is_inherited = is_text_node || (!NON_INHERITED_PROPERTIES.contains(&parent_style_property.as_str()) &&
+                                                !border::is_border_property(parent_style_property));
 
-        for (parent_style_property, parent_style_value) in parent_styles {
-            if !resolved_styles.contains_key(&parent_style_property) {
+            if is_inherited && !resolved_styles.contains_key(parent_style_property) {
                 resolved_styles.insert(parent_style_property.clone(), parent_style_value.clone());
             }
         }
+    } else if style_context.media_environment.color_scheme == ColorScheme::Dark && !resolved_styles.contains_key("color") {
+        //the nodes at the top have nothing to inherit from, and the default (black) text would not be readable on the dark background
+        resolved_styles.insert(String::from("color"), DARK_SCHEME_TEXT_COLOR.to_owned());
     }
+}
 
-    return resolved_styles;
+
+pub fn parse_inline_style(style_text: &str) -> Vec<(String, String)> {
+    //this parses the declarations of a style attribute, as in style="color: red; background-image: url('a;b.png')"
+    let mut declarations = Vec::new();
+    let mut current_declaration = String::new();
+    let mut open_quote = None;
+    let mut parenthesis_depth = 0;
+
+    //a semicolon only ends a declaration when it is not in quotes or parentheses, and the last declaration does not need one
+    for c in style_text.chars().chain([';']) {
+        if open_quote.is_some() {
+            if Some(c) == open_quote {
+                open_quote = None;
+            }
+            current_declaration.push(c);
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => { open_quote = Some(c); },
+            '(' => { parenthesis_depth += 1; },
+            ')' if parenthesis_depth > 0 => { parenthesis_depth -= 1; },
+            ';' if parenthesis_depth == 0 => {
+                let declaration = current_declaration.split_once(':');
+                if declaration.is_some() {
+                    let (property, value) = declaration.unwrap();
+                    if !property.trim().is_empty() && !value.trim().is_empty() {
+                        declarations.push((property.trim().to_ascii_lowercase(), value.trim().to_owned()));
+                    }
+                }
+                current_declaration.clear();
+                continue;
+            },
+            _ => {},
+        }
+        current_declaration.push(c);
+    }
+
+    return declarations;
+}
+
+
+pub fn serialize_inline_style(declarations: &Vec<(String, String)>) -> String {
+    return declarations.iter().map(|(property, value)| format!("{}: {};", property, value)).collect::<Vec<String>>().join(" ");
+}
+
+
+pub fn default_canvas_color(color_scheme: ColorScheme) -> Color {
+    //this is the background behind the whole page, where the page does not paint anything itself
+    return match color_scheme {
+        ColorScheme::Light => Color::WHITE,
+        ColorScheme::Dark => DARK_SCHEME_CANVAS_COLOR,
+    };
 }
 
 
 pub fn get_user_agent_style_sheet() -> Vec<StyleRule> {
     //These are the styles that are applied to the outer most node, and are used when no styling is specified.
+    let dark_color_scheme = Arc::new(parse_media_query_list("(prefers-color-scheme: dark)"));
+
     return vec![
         //TODO: convert to an actual stylesheet (CSS string) we load in (or maybe not, but a better other format?)
 
         StyleRule { selector: Selector { nodes: Some(vec!["h1".to_owned()]) },
-                    property: "font-size".to_owned(), value: "32".to_owned() },
+                    property: "font-size".to_owned(), value: "32".to_owned(), media: Vec::new() },
         StyleRule { selector: Selector { nodes: Some(vec!["h2".to_owned()]) },
-                    property: "font-size".to_owned(), value: "30".to_owned() },
+                    property: "font-size".to_owned(), value: "30".to_owned(), media: Vec::new() },
         StyleRule { selector: Selector { nodes: Some(vec!["h3".to_owned()]) },
-                    property: "font-size".to_owned(), value: "28".to_owned() },
+                    property: "font-size".to_owned(), value: "28".to_owned(), media: Vec::new() },
         StyleRule { selector: Selector { nodes: Some(vec!["h4".to_owned()]) },
-                    property: "font-size".to_owned(), value: "26".to_owned() },
+                    property: "font-size".to_owned(), value: "26".to_owned(), media: Vec::new() },
         StyleRule { selector: Selector { nodes: Some(vec!["h5".to_owned()]) },
-                    property: "font-size".to_owned(), value: "24".to_owned() },
+                    property: "font-size".to_owned(), value: "24".to_owned(), media: Vec::new() },
         StyleRule { selector: Selector { nodes: Some(vec!["h6".to_owned()]) },
-                    property: "font-size".to_owned(), value: "22".to_owned() },
+                    property: "font-size".to_owned(), value: "22".to_owned(), media: Vec::new() },
 
         StyleRule { selector: Selector { nodes: Some(vec!["a".to_owned()]) },
-                    property: "color".to_owned(), value: "blue".to_owned() },
+                    property: "color".to_owned(), value: "blue".to_owned(), media: Vec::new() },
         StyleRule { selector: Selector { nodes: Some(vec!["a".to_owned()]) },
-                    property: "text-decoration".to_owned(), value: "underline".to_owned() },
+                    property: "text-decoration".to_owned(), value: "underline".to_owned(), media: Vec::new() },
+        StyleRule { selector: Selector { nodes: Some(vec!["a:visited".to_owned()]) },
+                    property: "color".to_owned(), value: VISITED_LINK_COLOR.to_owned(), media: Vec::new() },
+        StyleRule { selector: Selector { nodes: Some(vec!["a".to_owned()]) },
+                    property: "color".to_owned(), value: DARK_SCHEME_LINK_COLOR.to_owned(), media: vec![Arc::clone(&dark_color_scheme)] },
+        StyleRule { selector: Selector { nodes: Some(vec!["a:visited".to_owned()]) },
+                    property: "color".to_owned(), value: DARK_SCHEME_VISITED_LINK_COLOR.to_owned(), media: vec![dark_color_scheme] },
 
     ];
 }
@@ -170,8 +581,16 @@ pub fn get_property_from_computed_styles(styles: &HashMap<String, String>, prope
 
     //Defaults per css property:
     match property {
+        "background-color" => return Some(String::from("transparent")),
+        "background-image" => return Some(String::from("none")),
+        "background-position" => return Some(String::from("0% 0%")),
+        "background-repeat" => return Some(String::from("repeat")),
+        "background-size" => return Some(String::from("auto")),
+        "box-shadow" => return Some(String::from("none")),
+        "opacity" => return Some(String::from("1")),
+        "text-shadow" => return Some(String::from("none")),
         "color" => return Some(String::from("black")),
-        "font-size" => return Some(String::from("18")),
+        "font-size" => return Some(SETTINGS.lock().unwrap().default_font_size.to_string()),
         "font-weight" => return Some(String::from("normal")),
         _ => { return None }
     };
@@ -208,6 +627,44 @@ pub fn has_style_value(styles: &HashMap<String, String>, style_name: &str, style
 }
 
 
+pub fn parse_font_family_list(value: &str) -> Vec<String> {
+    //font family names are case insensitive, so we lowercase them here (the generic families like "serif" are lowercase already)
+    let mut font_families = Vec::new();
+    for font_family in value.split(',') {
+        let mut font_family = font_family.trim();
+        if font_family.len() >= 2 && ((font_family.starts_with('"') && font_family.ends_with('"')) ||
+                                      (font_family.starts_with('\'') && font_family.ends_with('\''))) {
+            font_family = &font_family[1..font_family.len() - 1];
+        }
+
+        //unquoted names can consist of multiple words, which are joined by a single space
+        let font_family = font_family.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase();
+        if !font_family.is_empty() {
+            font_families.push(font_family);
+        }
+    }
+    return font_families;
+}
+
+
+pub fn get_opacity_style_value(styles: &HashMap<String, String>) -> f32 {
+    let opacity_value = get_property_from_computed_styles(styles, "opacity").unwrap();
+    let opacity_value = opacity_value.trim();
+
+    let parsed_opacity = if opacity_value.ends_with('%') {
+        opacity_value[..opacity_value.len() - 1].parse::<f32>().map(|percentage| percentage / 100.0)
+    } else {
+        opacity_value.parse::<f32>()
+    };
+
+    if parsed_opacity.is_err() {
+        debug_log_warn(format!("could not parse opacity value: {}", opacity_value));
+        return 1.0;
+    }
+    return parsed_opacity.unwrap().clamp(0.0, 1.0);
+}
+
+
 pub fn get_color_style_value(styles: &HashMap<String, String>, property: &str) -> Option<Color> {
     let item = get_property_from_computed_styles(styles, property);
     if item.is_none() {
@@ -228,39 +685,129 @@ pub fn get_color_style_value(styles: &HashMap<String, String>, property: &str) -
 // This function returns what rule_a is compare to rule_b (less, equal or greater), greater meaning having higher priority
 fn compare_style_rules(rule_a: &ActiveStyleRule, rule_b: &ActiveStyleRule) -> Ordering {
 
-    //TODO: this check needs to be different in the future, because we need to support an arbitrary set of cascades
-    if rule_a.origin != rule_b.origin {
-        if rule_a.origin == Origin::UserAgent {
-            return Ordering::Less;
+    //TODO: !important declarations should reverse the order of the layers
+    if rule_a.cascade_layer_idx > rule_b.cascade_layer_idx { return Ordering::Greater; }
+    if rule_a.cascade_layer_idx < rule_b.cascade_layer_idx { return Ordering::Less; }
+
+    if rule_a.specificity_attribute > rule_b.specificity_attribute { return Ordering::Greater; }
+    if rule_a.specificity_attribute < rule_b.specificity_attribute { return Ordering::Less; }
+
+    if rule_a.specificity_id > rule_b.specificity_id { return Ordering::Greater; }
+    if rule_a.specificity_id < rule_b.specificity_id { return Ordering::Less; }
+
+    if rule_a.specificity_class > rule_b.specificity_class { return Ordering::Greater; }
+    if rule_a.specificity_class < rule_b.specificity_class { return Ordering::Less; }
+
+    if rule_a.specificity_type > rule_b.specificity_type { return Ordering::Greater; }
+    if rule_a.specificity_type < rule_b.specificity_type { return Ordering::Less; }
+
+    if rule_a.definition_order > rule_b.definition_order { return Ordering::Greater; }
+    if rule_a.definition_order < rule_b.definition_order { return Ordering::Less; }
+
+    return Ordering::Equal;
+}
+
+
+fn style_rule_does_apply(style_rule: &StyleRule, styled_element: &StyledElement, style_context: &StyleContext) -> bool {
+    if styled_element.name.is_none() {
+        return false;
+    }
+
+    for media_query_list in &style_rule.media {
+        if !media_query_list.matches(&style_context.media_environment) {
+            return false;
         }
-        return Ordering::Greater;
     }
 
-    if rule_a.specificity_attribute > rule_a.specificity_attribute { return Ordering::Greater; }
-    if rule_a.specificity_attribute < rule_a.specificity_attribute { return Ordering::Less; }
+    //TODO: other browsers only let :visited change colors, so pages can't find out which links were visited by measuring them
+    return selector_matches(&style_rule.selector, styled_element, &style_context.visited_links);
+}
+
 
-    if rule_a.specificity_id > rule_a.specificity_id { return Ordering::Greater; }
-    if rule_a.specificity_id < rule_a.specificity_id { return Ordering::Less; }
+pub fn is_link(element_dom_node: &ElementDomNode) -> bool {
+    return styled_element_is_link(&StyledElement::of(element_dom_node));
+}
 
-    if rule_a.specificity_class > rule_a.specificity_class { return Ordering::Greater; }
-    if rule_a.specificity_class < rule_a.specificity_class { return Ordering::Less; }
 
-    if rule_a.specificity_type > rule_a.specificity_type { return Ordering::Greater; }
-    if rule_a.specificity_type < rule_a.specificity_type { return Ordering::Less; }
+fn styled_element_is_link(styled_element: &StyledElement) -> bool {
+    if styled_element.name.is_none() {
+        return false;
+    }
+    let name = styled_element.name.unwrap();
+    return (name == "a" || name == "area") && styled_element.get_attribute_value("href").is_some();
+}
 
-    if rule_a.definition_order > rule_a.definition_order { return Ordering::Greater; }
-    if rule_a.definition_order < rule_a.definition_order { return Ordering::Less; }
 
-    return Ordering::Equal;
+pub fn check_selector_for_match(selector: &Selector, element_dom_node: &ElementDomNode, visited_links: &HashSet<String>) -> bool {
+    return selector_matches(selector, &StyledElement::of(element_dom_node), visited_links);
 }
 
 
-fn style_rule_does_apply(style_rule: &StyleRule, element_dom_node: &ElementDomNode) -> bool {
-    if element_dom_node.name.is_none() {
+fn selector_matches(selector: &Selector, styled_element: &StyledElement, visited_links: &HashSet<String>) -> bool {
+    if styled_element.name.is_none() || selector.nodes.is_none() {
         return false;
     }
 
     //TODO: currently this matches if any of the nodes matches, I'm not sure if this is correct, do they all need to match?
-    return style_rule.selector.nodes.is_some() &&
-           style_rule.selector.nodes.as_ref().unwrap().contains(&element_dom_node.name.as_ref().unwrap());
+    for selector_text in selector.nodes.as_ref().unwrap() {
+        //a selector list, as in "h1, h2", matches when any of the selectors in it matches
+        if selector_text.split(',').any(|compound_selector| compound_selector_matches(compound_selector.trim(), styled_element, visited_links)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+
+fn compound_selector_matches(selector_text: &str, styled_element: &StyledElement, visited_links: &HashSet<String>) -> bool {
+    //a compound selector is a tag name (or *), followed by any number of #id and .class parts, and then pseudo classes, as in "a.note#main:visited"
+    //TODO: combinators (like "div p"), attribute selectors and most pseudo classes are not supported yet, so selectors with them never match
+    if selector_text.is_empty() || selector_text.contains(|c: char| c.is_whitespace() || ">+~[".contains(c)) {
+        return false;
+    }
+
+    let pseudo_classes_start = selector_text.find(':').unwrap_or(selector_text.len());
+    for pseudo_class in selector_text[pseudo_classes_start..].split(':').skip(1) {
+        let is_link = styled_element_is_link(styled_element);
+        let is_visited = is_link && visited_links.contains(&styled_element.get_attribute_value("href").unwrap());
+        let pseudo_class_matches = match pseudo_class.to_ascii_lowercase().as_str() {
+            "link" => is_link && !is_visited,
+            "visited" => is_visited,
+            "any-link" => is_link,
+            _ => false, //this includes pseudo elements (like ::before), which have an empty part here
+        };
+        if !pseudo_class_matches {
+            return false;
+        }
+    }
+    let selector_text = &selector_text[..pseudo_classes_start];
+
+    let tag_name_end = selector_text.find(|c: char| c == '#' || c == '.').unwrap_or(selector_text.len());
+    let tag_name = &selector_text[..tag_name_end];
+    if !tag_name.is_empty() && tag_name != "*" && !tag_name.eq_ignore_ascii_case(styled_element.name.unwrap()) {
+        return false;
+    }
+
+    let mut remaining = &selector_text[tag_name_end..];
+    while !remaining.is_empty() {
+        let part_end = remaining[1..].find(|c: char| c == '#' || c == '.').map(|idx| idx + 1).unwrap_or(remaining.len());
+        let part_value = &remaining[1..part_end];
+        if part_value.is_empty() {
+            return false;
+        }
+
+        let is_id = remaining.starts_with('#');
+        let attribute_value = styled_element.get_attribute_value(if is_id { "id" } else { "class" });
+        if attribute_value.is_none() {
+            return false;
+        }
+        let attribute_value = attribute_value.unwrap();
+        let part_matches = if is_id { attribute_value == part_value } else { attribute_value.split_whitespace().any(|class| class == part_value) };
+        if !part_matches {
+            return false;
+        }
+        remaining = &remaining[part_end..];
+    }
+
+    return true;
 }
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use crate::color::Color;
+use crate::debug::debug_log_warn;
+use crate::style::{get_color_style_value, get_property_from_computed_styles};
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub blur_radius: f32,
+    pub spread_radius: f32, //always 0 for text-shadow
+    pub color: Color,
+    pub inset: bool, //always false for text-shadow
+}
+
+
+pub fn resolve_box_shadows(styles: &HashMap<String, String>) -> Vec<Shadow> {
+    return resolve_shadows(styles, "box-shadow", true);
+}
+
+
+pub fn resolve_text_shadows(styles: &HashMap<String, String>) -> Vec<Shadow> {
+    return resolve_shadows(styles, "text-shadow", false);
+}
+
+
+fn resolve_shadows(styles: &HashMap<String, String>, property: &str, is_box_shadow: bool) -> Vec<Shadow> {
+    let value = get_property_from_computed_styles(styles, property);
+    if value.is_none() {
+        return Vec::new();
+    }
+
+    //shadows without a color use the text color (currentColor)
+    let current_color = get_color_style_value(styles, "color").unwrap_or(Color::BLACK);
+    return parse_shadow_list(&value.unwrap(), current_color, is_box_shadow);
+}
+
+
+pub fn parse_shadow_list(value: &str, current_color: Color, is_box_shadow: bool) -> Vec<Shadow> {
+    let mut shadows = Vec::new();
+    if value.trim() == "none" {
+        return shadows;
+    }
+
+    for shadow_text in split_outside_parentheses(value, ',') {
+        let shadow = parse_shadow(&shadow_text, current_color, is_box_shadow);
+        if shadow.is_none() {
+            //an invalid shadow makes the whole declaration invalid
+            debug_log_warn(format!("could not parse shadow value: {}", value));
+            return Vec::new();
+        }
+        shadows.push(shadow.unwrap());
+    }
+
+    return shadows;
+}
+
+
+fn parse_shadow(shadow_text: &str, current_color: Color, is_box_shadow: bool) -> Option<Shadow> {
+    let mut lengths = Vec::new();
+    let mut color = None;
+    let mut inset = false;
+
+    for part in split_outside_parentheses(shadow_text, ' ') {
+        if part == "inset" && is_box_shadow {
+            inset = true;
+            continue;
+        }
+
+        let length = parse_shadow_length(&part);
+        if length.is_some() {
+            lengths.push(length.unwrap());
+            continue;
+        }
+
+        if part == "currentcolor" || part == "currentColor" {
+            color = Some(current_color);
+            continue;
+        }
+
+        let parsed_color = Color::from_string(&part);
+        if parsed_color.is_none() || color.is_some() {
+            return None;
+        }
+        color = parsed_color;
+    }
+
+    let max_lengths = if is_box_shadow { 4 } else { 3 };
+    if lengths.len() < 2 || lengths.len() > max_lengths {
+        return None;
+    }
+
+    let blur_radius = if lengths.len() > 2 { lengths[2] } else { 0.0 };
+    if blur_radius < 0.0 {
+        return None;
+    }
+
+    return Some(Shadow {
+        offset_x: lengths[0],
+        offset_y: lengths[1],
+        blur_radius,
+        spread_radius: if lengths.len() > 3 { lengths[3] } else { 0.0 },
+        color: color.unwrap_or(current_color),
+        inset,
+    });
+}
+
+
+fn parse_shadow_length(value: &str) -> Option<f32> {
+    //TODO: we only support px (and unitless zero) for now, like elsewhere in our css handling
+    let number_part = if value.ends_with("px") { &value[..value.len() - 2] } else { value };
+    let parsed = number_part.parse::<f32>();
+    if parsed.is_err() {
+        return None;
+    }
+    return Some(parsed.unwrap());
+}
+
+
+pub fn split_outside_parentheses(value: &str, separator: char) -> Vec<String> {
+    //colors like rgba(0, 0, 0, 0.5) contain the separators we split on, so we should not split inside parentheses
+    let mut parts = Vec::new();
+    let mut current_part = String::new();
+    let mut depth = 0;
+
+    for c in value.chars() {
+        if c == '(' {
+            depth += 1;
+        } else if c == ')' && depth > 0 {
+            depth -= 1;
+        }
+
+        let is_separator = if separator == ' ' { c.is_whitespace() } else { c == separator };
+        if is_separator && depth == 0 {
+            if !current_part.trim().is_empty() {
+                parts.push(current_part.trim().to_owned());
+            }
+            current_part = String::new();
+        } else {
+            current_part.push(c);
+        }
+    }
+    if !current_part.trim().is_empty() {
+        parts.push(current_part.trim().to_owned());
+    }
+
+    return parts;
+}
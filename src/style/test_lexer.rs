@@ -66,3 +66,26 @@ fn test_parsing_nested_style() {
         assert_eq!(&token.css_token, expected_token);
     }
 }
+
+
+#[test]
+fn test_lexing_media_query() {
+    let css_text = "@media screen and (max-width: 600px) { h3 { color: red; } }";
+    let tokens = css_lexer::lex_css(&css_text, 1, 1);
+
+    let expected_tokens = vec![
+        CssToken::AtRule("media screen and (max-width: 600px)".to_owned()),
+        CssToken::BlockStart,
+        CssToken::Selector("h3".to_owned()),
+        CssToken::BlockStart,
+        CssToken::Property("color".to_owned()),
+        CssToken::Value("red".to_owned()),
+        CssToken::BlockEnd,
+        CssToken::BlockEnd,
+    ];
+    assert_eq!(tokens.len(), expected_tokens.len());
+
+    for (token, expected_token) in tokens.iter().zip(expected_tokens.iter()) {
+        assert_eq!(&token.css_token, expected_token);
+    }
+}
@@ -1,5 +1,6 @@
-use super::css_lexer::{CssToken, CssTokenWithLocation};
+use super::css_lexer::{self, CssToken, CssTokenWithLocation};
 use super::css_parser;
+use super::media_query::MediaEnvironment;
 
 
 
@@ -13,9 +14,57 @@ fn test_parse_basic_style() {
         CssTokenWithLocation { css_token: CssToken::BlockEnd, line: 1, character: 13 },
     ];
 
-    let result = css_parser::parse_css(&tokens);
+    let result = css_parser::parse_css(&tokens).rules;
     assert_eq!(result.len(), 1);
     assert_eq!(result[0].property, "color");
     assert_eq!(result[0].value, "red");
     assert_eq!(result[0].selector.nodes.as_ref().unwrap()[0], "h3");
 }
+
+
+#[test]
+fn test_parse_media_query() {
+    let tokens = vec![
+        CssTokenWithLocation { css_token: CssToken::AtRule("media screen and (max-width: 600px)".to_owned()), line: 1, character: 1 },
+        CssTokenWithLocation { css_token: CssToken::BlockStart, line: 1, character: 38 },
+        CssTokenWithLocation { css_token: CssToken::Selector("h3".to_owned()), line: 1, character: 40 },
+        CssTokenWithLocation { css_token: CssToken::BlockStart, line: 1, character: 43 },
+        CssTokenWithLocation { css_token: CssToken::Property("color".to_owned()), line: 1, character: 45 },
+        CssTokenWithLocation { css_token: CssToken::Value("red".to_owned()), line: 1, character: 52 },
+        CssTokenWithLocation { css_token: CssToken::BlockEnd, line: 1, character: 57 },
+        CssTokenWithLocation { css_token: CssToken::BlockEnd, line: 1, character: 59 },
+    ];
+
+    let result = css_parser::parse_css(&tokens).rules;
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].property, "color");
+    assert_eq!(result[0].selector.nodes.as_ref().unwrap().len(), 1);
+    assert_eq!(result[0].selector.nodes.as_ref().unwrap()[0], "h3");
+    assert_eq!(result[0].media.len(), 1);
+
+    assert!(result[0].media[0].matches(&MediaEnvironment { width: 500.0, height: 800.0, ..MediaEnvironment::new_default() }));
+    assert!(!result[0].media[0].matches(&MediaEnvironment { width: 700.0, height: 800.0, ..MediaEnvironment::new_default() }));
+}
+
+
+#[test]
+fn test_parse_layers_and_imports() {
+    let css_text = "@import url(\"base.css\") layer(base); @layer reset, base; @layer reset { h3 { color: red; } } p { color: blue; }";
+    let tokens = css_lexer::lex_css(&css_text, 1, 1);
+
+    let result = css_parser::parse_css(&tokens);
+
+    assert_eq!(result.imports.len(), 1);
+    assert_eq!(result.imports[0].url, "base.css");
+    assert_eq!(result.imports[0].layer, Some("base".to_owned()));
+
+    assert_eq!(result.layers.len(), 2);
+    assert_eq!(result.layers[0].name, "reset");
+    assert_eq!(result.layers[0].rules.len(), 1);
+    assert_eq!(result.layers[0].rules[0].value, "red");
+    assert_eq!(result.layers[1].name, "base");
+    assert_eq!(result.layers[1].rules.len(), 0);
+
+    assert_eq!(result.rules.len(), 1);
+    assert_eq!(result.rules[0].value, "blue");
+}
@@ -1,16 +1,31 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
 
 
 use crate::style::{
+    background::{BackgroundTile, compute_background_tiles, resolve_background_style},
+    border::{BorderSide, BorderStyle, resolve_borders},
+    css_lexer,
+    css_parser,
+    media_query::{ColorScheme, MediaEnvironment, parse_media_query_list},
+    shadow::{Shadow, parse_shadow_list},
+    transition::{TimingFunction, resolve_transitions},
     Selector,
     StyleContext,
     StyleRule,
+    check_selector_for_match,
+    compute_styles,
+    get_color_style_value,
+    get_opacity_style_value,
+    parse_font_family_list,
+    parse_inline_style,
     resolve_full_styles_for_layout_node,
 };
+use crate::arena::Arena;
+use crate::color::Color;
 use crate::dom::{ElementDomNode, TagName};
-use crate::test_util::get_next_test_id;
+use crate::ui::WindowDimensions;
 
 
 fn check_style(resolved_styles: &HashMap<String, String>, property: &str, value: &str) {
@@ -21,20 +36,17 @@ fn check_style(resolved_styles: &HashMap<String, String>, property: &str, value:
 
 #[test]
 fn test_basic_style_resolving() {
-    let document_node_id = 0;
-    let dom_node_id = get_next_test_id();
-    let dom_node = Rc::new(RefCell::from(ElementDomNode { internal_id: dom_node_id, parent_id: document_node_id, text: None, is_document_node: false, dirty: false,
-                                                          name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
-                                                          attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None }));
-
-    let mut all_dom_nodes = HashMap::new();
-    all_dom_nodes.insert(dom_node_id, Rc::clone(&dom_node));
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
 
     let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
-                                        property: "prop".to_owned(), value: "some value".to_owned() } ];
+                                        property: "prop".to_owned(), value: "some value".to_owned(), media: Vec::new() } ];
 
-    let style_context = StyleContext { user_agent_sheet: Vec::new(), author_sheet: style_rules };
-    let resolved_styles = resolve_full_styles_for_layout_node(&dom_node, &all_dom_nodes, &style_context);
+    let style_context = StyleContext::new(Vec::new(), style_rules, MediaEnvironment::new_default());
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
 
     check_style(&resolved_styles, "prop", "some value");
 }
@@ -42,54 +54,498 @@ fn test_basic_style_resolving() {
 
 #[test]
 fn test_inherit_style_from_parent() {
-    let document_node_id = 0;
-    let main_node_id = get_next_test_id();
-    let parent_node_id = get_next_test_id();
-    let main_node = Rc::new(RefCell::from(ElementDomNode { internal_id: main_node_id, parent_id: parent_node_id, text: None, is_document_node: false, dirty: false,
-                                                           name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
-                                                           attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None }));
-    let parent_node = Rc::new(RefCell::from(ElementDomNode { internal_id: parent_node_id, parent_id: document_node_id, text: None, dirty: false,
-                                                             is_document_node: false, name: Some("h3".to_owned()), name_for_layout: TagName::Other,
-                                                             children: Some(vec![Rc::clone(&main_node)]), attributes: None, image: None, img_job_tracker: None,
-                                                             scripts: None, page_component: None }));
-
-    let mut all_dom_nodes = HashMap::new();
-    all_dom_nodes.insert(main_node_id, Rc::clone(&main_node));
-    all_dom_nodes.insert(parent_node_id, Rc::clone(&parent_node));
+    let mut dom_nodes = Arena::new();
+    let main_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                                name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                                attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                                cached_styles: None });
+    let parent_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, dirty: false,
+                                                                  is_document_node: false, name: Some("h3".to_owned()), name_for_layout: TagName::Other,
+                                                                  children: Some(vec![main_node]), attributes: None, image: None, img_job_tracker: None,
+                                                                  scripts: None, page_component: None, default_value: None, cached_styles: None });
+    dom_nodes[main_node].parent = Some(parent_node);
 
     let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["h3".to_owned()]) },
-                                        property: "font-size".to_owned(), value: "50".to_owned() } ];
+                                        property: "font-size".to_owned(), value: "50".to_owned(), media: Vec::new() } ];
 
-    let style_context = StyleContext { user_agent_sheet: Vec::new(), author_sheet: style_rules };
+    let style_context = StyleContext::new(Vec::new(), style_rules, MediaEnvironment::new_default());
 
-    let resolved_styles = resolve_full_styles_for_layout_node(&main_node, &all_dom_nodes, &style_context);
+    let resolved_styles = resolve_full_styles_for_layout_node(main_node, &dom_nodes, &style_context);
 
     check_style(&resolved_styles, "font-size", "50");
 }
 
 
 #[test]
-fn test_cascade() {
-    let document_node_id = 0;
-    let dom_node_id = get_next_test_id();
-    let dom_node = Rc::new(RefCell::from(ElementDomNode { internal_id: dom_node_id, parent_id: document_node_id, text: None, is_document_node: false, dirty: false,
-                                                          name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
-                                                          attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None }));
+fn test_compute_styles() {
+    let mut dom_nodes = Arena::new();
+    let main_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                                name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                                attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                                cached_styles: None });
+    let parent_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, dirty: false,
+                                                                  is_document_node: false, name: Some("h3".to_owned()), name_for_layout: TagName::Other,
+                                                                  children: Some(vec![main_node]), attributes: None, image: None, img_job_tracker: None,
+                                                                  scripts: None, page_component: None, default_value: None, cached_styles: None });
+    dom_nodes[main_node].parent = Some(parent_node);
+
+    let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["h3".to_owned()]) },
+                                        property: "font-size".to_owned(), value: "50".to_owned(), media: Vec::new() },
+                            StyleRule { selector: Selector { nodes: Some(vec!["h3".to_owned()]) },
+                                        property: "background-color".to_owned(), value: "red".to_owned(), media: Vec::new() },
+                            StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
+                                        property: "color".to_owned(), value: "green".to_owned(), media: Vec::new() } ];
+    let style_context = StyleContext::new(Vec::new(), style_rules, MediaEnvironment::new_default());
+
+    //the parent is not asked for, but its styles are still used to inherit from
+    let computed_styles = compute_styles(&HashSet::from([main_node]), &mut dom_nodes, &style_context);
+
+    assert_eq!(computed_styles.len(), 1);
+    let resolved_styles = &computed_styles[&main_node];
+    check_style(resolved_styles, "font-size", "50");
+    check_style(resolved_styles, "color", "green");
+    assert!(!resolved_styles.contains_key("background-color"));
+    assert_eq!(*resolved_styles, resolve_full_styles_for_layout_node(main_node, &dom_nodes, &style_context));
+
+    //when nothing changed, the cached styles stay the same
+    compute_styles(&HashSet::from([main_node]), &mut dom_nodes, &style_context);
+    assert_eq!(dom_nodes[parent_node].cached_styles.as_ref().unwrap().generation, 0);
+    assert_eq!(dom_nodes[main_node].cached_styles.as_ref().unwrap().generation, 0);
+
+    //a changed attribute of the parent also changes the styles the child inherits
+    dom_nodes[parent_node].set_attribute("style", "font-size: 60");
+    let computed_styles = compute_styles(&HashSet::from([main_node]), &mut dom_nodes, &style_context);
+    check_style(&computed_styles[&main_node], "font-size", "60");
+    assert_eq!(dom_nodes[parent_node].cached_styles.as_ref().unwrap().generation, 1);
+    assert_eq!(dom_nodes[main_node].cached_styles.as_ref().unwrap().generation, 1);
+}
+
+
+#[test]
+fn test_compute_styles_after_style_context_change() {
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+
+    let mut style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
+    let computed_styles = compute_styles(&HashSet::from([dom_node]), &mut dom_nodes, &style_context);
+    assert!(computed_styles[&dom_node].get("font-size").is_none());
+
+    //the styles cached on the node are outdated when a style sheet is added, or the media environment changes
+    style_context.add_author_style_sheet(css_parser::parse_css(&css_lexer::lex_css("@media (max-width: 600px) { b { font-size: 30; } }", 1, 1)));
+    let computed_styles = compute_styles(&HashSet::from([dom_node]), &mut dom_nodes, &style_context);
+    assert!(computed_styles[&dom_node].get("font-size").is_none());
+
+    style_context.set_media_environment(MediaEnvironment { width: 500.0, height: 800.0, ..MediaEnvironment::new_default() });
+    let computed_styles = compute_styles(&HashSet::from([dom_node]), &mut dom_nodes, &style_context);
+    check_style(&computed_styles[&dom_node], "font-size", "30");
+}
 
-    let mut all_dom_nodes = HashMap::new();
-    all_dom_nodes.insert(dom_node_id, Rc::clone(&dom_node));
 
+#[test]
+fn test_inline_style() {
+    let declarations = parse_inline_style("Color: red;; background-image: url('a;b.png') ; font-family: \"x;y\", serif; broken; margin:");
+    assert_eq!(declarations, vec![
+        ("color".to_owned(), "red".to_owned()),
+        ("background-image".to_owned(), "url('a;b.png')".to_owned()),
+        ("font-family".to_owned(), "\"x;y\", serif".to_owned()),
+    ]);
+
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+    dom_nodes[dom_node].set_attribute("style", "color: blue");
+
+    //the inline style wins over the style rules
     let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
-                                        property: "color".to_owned(), value: "red".to_owned() },
+                                        property: "color".to_owned(), value: "green".to_owned(), media: Vec::new() },
                             StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
-                                        property: "font-size".to_owned(), value: "25".to_owned() } ];
+                                        property: "font-size".to_owned(), value: "20".to_owned(), media: Vec::new() } ];
+    let style_context = StyleContext::new(Vec::new(), style_rules, MediaEnvironment::new_default());
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+
+    check_style(&resolved_styles, "color", "blue");
+    check_style(&resolved_styles, "font-size", "20");
+}
+
+
+#[test]
+fn test_cascade() {
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+
+    let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
+                                        property: "color".to_owned(), value: "red".to_owned(), media: Vec::new() },
+                            StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
+                                        property: "font-size".to_owned(), value: "25".to_owned(), media: Vec::new() } ];
     let ua_styles = vec![ StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
-                                      property: "color".to_owned(), value: "red".to_owned() } ];
+                                      property: "color".to_owned(), value: "red".to_owned(), media: Vec::new() } ];
 
-    let style_context = StyleContext { user_agent_sheet: ua_styles, author_sheet: style_rules };
+    let style_context = StyleContext::new(ua_styles, style_rules, MediaEnvironment::new_default());
 
-    let resolved_styles = resolve_full_styles_for_layout_node(&dom_node, &all_dom_nodes, &style_context);
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
 
     check_style(&resolved_styles, "color", "red");
     check_style(&resolved_styles, "font-size", "25");
 }
+
+
+#[test]
+fn test_media_query_rules_only_apply_when_matching() {
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+
+    let narrow_media = Arc::new(parse_media_query_list("(max-width: 600px)"));
+    let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
+                                        property: "color".to_owned(), value: "red".to_owned(), media: Vec::new() },
+                            StyleRule { selector: Selector { nodes: Some(vec!["b".to_owned()]) },
+                                        property: "color".to_owned(), value: "green".to_owned(), media: vec![narrow_media] } ];
+
+    let mut style_context = StyleContext::new(Vec::new(), style_rules, MediaEnvironment { width: 1000.0, height: 800.0, ..MediaEnvironment::new_default() });
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    check_style(&resolved_styles, "color", "red");
+
+    style_context.set_media_environment(MediaEnvironment { width: 500.0, height: 800.0, ..MediaEnvironment::new_default() });
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    check_style(&resolved_styles, "color", "green");
+}
+
+
+#[test]
+fn test_media_queries_are_about_the_content_area() {
+    //the browser ui and the scrollbar are not part of the viewport, so the page is smaller than the window
+    let environment = MediaEnvironment::new_default();
+    let window_dimensions = WindowDimensions::new_default();
+    assert!(parse_media_query_list(&format!("(max-width: {}px) and (max-height: {}px)", window_dimensions.content_width(), window_dimensions.content_height()))
+                .matches(&environment));
+    assert!(!parse_media_query_list(&format!("(min-width: {}px)", window_dimensions.width)).matches(&environment));
+    assert!(!parse_media_query_list(&format!("(min-height: {}px)", window_dimensions.height)).matches(&environment));
+}
+
+
+#[test]
+fn test_prefers_color_scheme() {
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+
+    let css_text = "@media (prefers-color-scheme: dark) { b { font-size: 30; } }";
+    let style_sheet = css_parser::parse_css(&css_lexer::lex_css(&css_text, 1, 1));
+
+    let mut style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
+    style_context.add_author_style_sheet(style_sheet);
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    assert!(resolved_styles.get("font-size").is_none());
+    assert!(resolved_styles.get("color").is_none());
+
+    //in the dark scheme, the node at the top also gets a light default text color
+    style_context.set_media_environment(MediaEnvironment { color_scheme: ColorScheme::Dark, ..MediaEnvironment::new_default() });
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    check_style(&resolved_styles, "font-size", "30");
+    check_style(&resolved_styles, "color", "#e8eaed");
+}
+
+
+#[test]
+fn test_cascade_layers() {
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+
+    //the unlayered rule should win over the layered one, and the later declared layer should win over the earlier one
+    let css_text = "@layer first, second; b { color: red; } @layer second { b { color: green; font-size: 30; } } @layer first { b { font-size: 20; } }";
+    let style_sheet = css_parser::parse_css(&css_lexer::lex_css(&css_text, 1, 1));
+
+    let mut style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
+    style_context.add_author_style_sheet(style_sheet);
+
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+
+    check_style(&resolved_styles, "color", "red");
+    check_style(&resolved_styles, "font-size", "30");
+}
+
+
+#[test]
+fn test_cascade_order_of_imports_and_nested_layers() {
+    let mut dom_nodes = Arena::new();
+    let dom_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                               name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                               attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                               cached_styles: None });
+    let parse = |css_text: &str| css_parser::parse_css(&css_lexer::lex_css(&css_text.to_owned(), 1, 1));
+
+    //the imported sheet is loaded after the sheet importing it, but its rules still come before the rules of that sheet
+    let mut style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
+    let sheet_slot = style_context.add_author_style_sheet(parse("@import 'base.css'; b { color: red; }"));
+    style_context.fill_import_slot(&vec![sheet_slot[0], 0], parse("b { color: green; font-size: 20; }"));
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    check_style(&resolved_styles, "color", "red");
+    check_style(&resolved_styles, "font-size", "20");
+
+    //layers declared before an @import come before the layers in the imported sheet
+    let mut style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
+    let sheet_slot = style_context.add_author_style_sheet(parse("@layer base, theme; @import 'theme.css' layer(theme); @layer base { b { font-size: 10; } }"));
+    style_context.fill_import_slot(&vec![sheet_slot[0], 0], parse("b { font-size: 20; }"));
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    check_style(&resolved_styles, "font-size", "20");
+
+    //the rules directly in a layer win over the rules in its sublayers, even when the sublayer is declared later
+    let mut style_context = StyleContext::new(Vec::new(), Vec::new(), MediaEnvironment::new_default());
+    style_context.add_author_style_sheet(parse("@layer outer { b { color: blue; } @layer inner { b { color: green; font-size: 30; } } } \
+                                                @layer outer.inner { b { font-size: 40; } }"));
+    let resolved_styles = resolve_full_styles_for_layout_node(dom_node, &dom_nodes, &style_context);
+    check_style(&resolved_styles, "color", "blue");
+    check_style(&resolved_styles, "font-size", "40");
+}
+
+
+#[test]
+fn test_color_values() {
+    let mut styles = HashMap::new();
+    styles.insert("color".to_owned(), "rgb(255, 0, 0)".to_owned());
+    styles.insert("background-color".to_owned(), "rgba(0 128 255 / 50%)".to_owned());
+    styles.insert("border-color".to_owned(), "hsl(120, 100%, 50%)".to_owned());
+    styles.insert("outline-color".to_owned(), "#00ff0080".to_owned());
+    styles.insert("text-decoration-color".to_owned(), "#f008".to_owned());
+    styles.insert("caret-color".to_owned(), "transparent".to_owned());
+
+    assert!(get_color_style_value(&styles, "color") == Some(Color::new(255, 0, 0)));
+    assert!(get_color_style_value(&styles, "background-color") == Some(Color::new_with_alpha(0, 128, 255, 128)));
+    assert!(get_color_style_value(&styles, "border-color") == Some(Color::new(0, 255, 0)));
+    assert!(get_color_style_value(&styles, "outline-color") == Some(Color::new_with_alpha(0, 255, 0, 128)));
+    assert!(get_color_style_value(&styles, "text-decoration-color") == Some(Color::new_with_alpha(255, 0, 0, 136)));
+    assert!(get_color_style_value(&styles, "caret-color") == Some(Color::TRANSPARENT));
+}
+
+
+#[test]
+fn test_background_color_is_not_inherited() {
+    let mut dom_nodes = Arena::new();
+    let main_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                                name: Some("b".to_owned()), name_for_layout: TagName::B, children: Some(Vec::new()),
+                                                                attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None, default_value: None,
+                                                                cached_styles: None });
+    let parent_node = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, dirty: false,
+                                                                  is_document_node: false, name: Some("div".to_owned()), name_for_layout: TagName::Other,
+                                                                  children: Some(vec![main_node]), attributes: None, image: None, img_job_tracker: None,
+                                                                  scripts: None, page_component: None, default_value: None, cached_styles: None });
+    dom_nodes[main_node].parent = Some(parent_node);
+
+    let style_rules = vec![ StyleRule { selector: Selector { nodes: Some(vec!["div".to_owned()]) },
+                                        property: "background-color".to_owned(), value: "red".to_owned(), media: Vec::new() },
+                            StyleRule { selector: Selector { nodes: Some(vec!["div".to_owned()]) },
+                                        property: "color".to_owned(), value: "blue".to_owned(), media: Vec::new() } ];
+
+    let style_context = StyleContext::new(Vec::new(), style_rules, MediaEnvironment::new_default());
+    let resolved_styles = resolve_full_styles_for_layout_node(main_node, &dom_nodes, &style_context);
+
+    check_style(&resolved_styles, "color", "blue");
+    assert!(!resolved_styles.contains_key("background-color"));
+    assert!(get_color_style_value(&resolved_styles, "background-color") == Some(Color::TRANSPARENT));
+}
+
+
+#[test]
+fn test_background_image_tiles() {
+    let mut styles = HashMap::new();
+    styles.insert("background-image".to_owned(), "url('images/tile.png')".to_owned());
+    styles.insert("background-repeat".to_owned(), "no-repeat".to_owned());
+    styles.insert("background-position".to_owned(), "center".to_owned());
+
+    let background_style = resolve_background_style(&styles).unwrap();
+    assert_eq!(background_style.image_url, "images/tile.png");
+
+    let tiles = compute_background_tiles(10.0, 20.0, 100.0, 50.0, 20.0, 10.0, &background_style);
+    assert!(tiles == vec![BackgroundTile { x: 50.0, y: 40.0, width: 20.0, height: 10.0 }]);
+
+    //repeating should cover the whole box, and a size with one value keeps the aspect ratio of the image
+    styles.insert("background-repeat".to_owned(), "repeat".to_owned());
+    styles.insert("background-position".to_owned(), "0 0".to_owned());
+    styles.insert("background-size".to_owned(), "50px".to_owned());
+
+    let background_style = resolve_background_style(&styles).unwrap();
+    let tiles = compute_background_tiles(0.0, 0.0, 100.0, 50.0, 20.0, 10.0, &background_style);
+    assert_eq!(tiles.len(), 4);
+    assert!(tiles[3] == BackgroundTile { x: 50.0, y: 25.0, width: 50.0, height: 25.0 });
+
+    styles.insert("background-image".to_owned(), "none".to_owned());
+    assert!(resolve_background_style(&styles).is_none());
+}
+
+
+#[test]
+fn test_shadow_values() {
+    let current_color = Color::new(0, 0, 255);
+
+    let shadows = parse_shadow_list("2px 3px 4px rgba(0, 0, 0, 0.5), inset 0 0 1px 2px red", current_color, true);
+    assert_eq!(shadows.len(), 2);
+    assert!(shadows[0] == Shadow { offset_x: 2.0, offset_y: 3.0, blur_radius: 4.0, spread_radius: 0.0,
+                                   color: Color::new_with_alpha(0, 0, 0, 128), inset: false });
+    assert!(shadows[1] == Shadow { offset_x: 0.0, offset_y: 0.0, blur_radius: 1.0, spread_radius: 2.0, color: Color::new(255, 0, 0), inset: true });
+
+    //without a color, the current text color is used
+    let shadows = parse_shadow_list("1px 1px", current_color, false);
+    assert!(shadows[0].color == current_color);
+
+    //text-shadow does not allow a spread radius or inset
+    assert!(parse_shadow_list("1px 1px 1px 1px", current_color, false).is_empty());
+    assert!(parse_shadow_list("inset 1px 1px", current_color, false).is_empty());
+    assert!(parse_shadow_list("none", current_color, true).is_empty());
+}
+
+
+#[test]
+fn test_border_values() {
+    let mut styles = HashMap::new();
+    styles.insert("color".to_owned(), "blue".to_owned());
+    styles.insert("border".to_owned(), "2px solid rgb(255, 0, 0)".to_owned());
+    styles.insert("border-left".to_owned(), "dotted".to_owned());
+    styles.insert("border-width".to_owned(), "1px 4px".to_owned());
+    styles.insert("border-bottom-style".to_owned(), "dashed".to_owned());
+
+    let borders = resolve_borders(&styles);
+    assert!(borders.top == BorderSide { width: 1.0, style: BorderStyle::Solid, color: Color::new(255, 0, 0) });
+    assert!(borders.right == BorderSide { width: 4.0, style: BorderStyle::Solid, color: Color::new(255, 0, 0) });
+    assert!(borders.bottom == BorderSide { width: 1.0, style: BorderStyle::Dashed, color: Color::new(255, 0, 0) });
+    //the side shorthand resets the color to the text color, and the width to medium (which border-width then overrides)
+    assert!(borders.left == BorderSide { width: 4.0, style: BorderStyle::Dotted, color: Color::new(0, 0, 255) });
+
+    //without a style, there is no border
+    let mut styles = HashMap::new();
+    styles.insert("border-width".to_owned(), "2px".to_owned());
+    let borders = resolve_borders(&styles);
+    assert!(!borders.has_any());
+    assert_eq!(borders.top.used_width(), 0.0);
+}
+
+
+#[test]
+fn test_opacity_values() {
+    let mut styles = HashMap::new();
+    assert_eq!(get_opacity_style_value(&styles), 1.0);
+
+    styles.insert("opacity".to_owned(), "0.25".to_owned());
+    assert_eq!(get_opacity_style_value(&styles), 0.25);
+
+    styles.insert("opacity".to_owned(), "50%".to_owned());
+    assert_eq!(get_opacity_style_value(&styles), 0.5);
+
+    styles.insert("opacity".to_owned(), "3".to_owned());
+    assert_eq!(get_opacity_style_value(&styles), 1.0);
+}
+
+
+#[test]
+fn test_font_family_list() {
+    let font_families = parse_font_family_list("\"Helvetica Neue\", Arial,  Liberation   Sans, 'Noto Sans', sans-serif");
+    assert_eq!(font_families, vec!["helvetica neue", "arial", "liberation sans", "noto sans", "sans-serif"]);
+}
+
+
+#[test]
+fn test_transition_values() {
+    let mut styles = HashMap::new();
+    styles.insert("transition".to_owned(), "opacity 0.5s ease-in, color 200ms cubic-bezier(0.1, 0.2, 0.3, 0.4) 1s".to_owned());
+
+    let transitions = resolve_transitions(&styles);
+    assert_eq!(transitions.len(), 2);
+    assert_eq!(transitions[0].property, "opacity");
+    assert_eq!(transitions[0].duration, Duration::from_millis(500));
+    assert!(transitions[0].timing_function == TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0));
+    assert_eq!(transitions[1].property, "color");
+    assert_eq!(transitions[1].duration, Duration::from_millis(200));
+    assert_eq!(transitions[1].delay, Duration::from_secs(1));
+
+    //the longhand properties override the shorthand, and are repeated when the list is shorter
+    styles.insert("transition-duration".to_owned(), "2s".to_owned());
+    let transitions = resolve_transitions(&styles);
+    assert_eq!(transitions[0].duration, Duration::from_secs(2));
+    assert_eq!(transitions[1].duration, Duration::from_secs(2));
+
+    assert_eq!(TimingFunction::Linear.apply(0.25), 0.25);
+    assert!((TimingFunction::ease().apply(0.5) - 0.8024).abs() < 0.001);
+    assert_eq!(TimingFunction::Steps(4, false).apply(0.3), 0.25);
+
+    //without a duration there is nothing to animate
+    let mut styles = HashMap::new();
+    styles.insert("transition-property".to_owned(), "opacity".to_owned());
+    assert!(resolve_transitions(&styles).is_empty());
+}
+
+
+#[test]
+fn test_selector_matching() {
+    let mut dom_nodes = Arena::new();
+    let dom_node_id = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                                  name: Some("div".to_owned()), name_for_layout: TagName::Other, children: Some(Vec::new()),
+                                                                  attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None,
+                                                                  default_value: None, cached_styles: None });
+    let dom_node = &mut dom_nodes[dom_node_id];
+    dom_node.set_attribute("id", "main");
+    dom_node.set_attribute("class", "box wide");
+
+    let matches = |selector_text: &str| check_selector_for_match(&Selector { nodes: Some(vec![selector_text.to_owned()]) }, &dom_node, &HashSet::new());
+
+    assert!(matches("div"));
+    assert!(matches("DIV"));
+    assert!(matches("*"));
+    assert!(matches("#main"));
+    assert!(matches(".wide"));
+    assert!(matches("div.box.wide#main"));
+    assert!(matches("p, .box"));
+    assert!(!matches("p"));
+    assert!(!matches(".narrow"));
+    assert!(!matches("div#other"));
+    assert!(!matches("body div"));
+    assert!(!matches("div:hover"));
+}
+
+
+#[test]
+fn test_link_pseudo_classes() {
+    let new_link = |href: &str| {
+        //the nodes are taken out of the arena again, we only need it to give them an id
+        let mut dom_nodes = Arena::new();
+        let dom_node_id = dom_nodes.insert_with(|id| ElementDomNode { id, parent: None, text: None, is_document_node: false, dirty: false,
+                                                                      name: Some("a".to_owned()), name_for_layout: TagName::Other, children: Some(Vec::new()),
+                                                                      attributes: None, image: None, img_job_tracker: None, scripts: None, page_component: None,
+                                                                      default_value: None, cached_styles: None });
+        let mut dom_node = dom_nodes.remove(dom_node_id).unwrap();
+        dom_node.set_attribute("href", href);
+        return dom_node;
+    };
+    let visited_link = new_link("/visited");
+    let unvisited_link = new_link("/new");
+    let visited_links = HashSet::from([String::from("/visited")]);
+
+    let matches = |selector_text: &str, dom_node: &ElementDomNode| {
+        return check_selector_for_match(&Selector { nodes: Some(vec![selector_text.to_owned()]) }, dom_node, &visited_links);
+    };
+
+    assert!(matches("a:visited", &visited_link));
+    assert!(matches(":any-link", &visited_link));
+    assert!(!matches("a:link", &visited_link));
+    assert!(matches("a:link", &unvisited_link));
+    assert!(!matches(":visited", &unvisited_link));
+    assert!(!matches("a::before", &unvisited_link));
+
+    let mut anchor_without_href = new_link("/visited");
+    anchor_without_href.attributes = None;
+    assert!(!matches("a:any-link", &anchor_without_href));
+    assert!(matches("a", &anchor_without_href));
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::debug::debug_log_warn;
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimingFunction {
+    Linear,
+    CubicBezier(f32, f32, f32, f32),
+    Steps(u32, bool), //the bool indicates if the jump happens at the start of each step (step-start), otherwise at the end
+}
+impl TimingFunction {
+    pub fn ease() -> TimingFunction {
+        return TimingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0);
+    }
+
+    pub fn ease_out() -> TimingFunction {
+        return TimingFunction::CubicBezier(0.0, 0.0, 0.58, 1.0);
+    }
+
+    pub fn apply(&self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+
+        match self {
+            TimingFunction::Linear => {
+                return progress;
+            },
+            TimingFunction::Steps(number_of_steps, jump_at_start) => {
+                let number_of_steps = (*number_of_steps).max(1) as f32;
+                let step = if *jump_at_start { (progress * number_of_steps).ceil() } else { (progress * number_of_steps).floor() };
+                return (step / number_of_steps).min(1.0);
+            },
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => {
+                //The curve is defined by x(t) and y(t), so we first need to find the t for which x(t) is our progress, which we do by bisection
+                let bezier = |t: f32, p1: f32, p2: f32| {
+                    let inverse_t = 1.0 - t;
+                    return (3.0 * inverse_t * inverse_t * t * p1) + (3.0 * inverse_t * t * t * p2) + (t * t * t);
+                };
+
+                let mut lower_t = 0.0;
+                let mut upper_t = 1.0;
+                for _ in 0..30 {
+                    let middle_t = (lower_t + upper_t) / 2.0;
+                    if bezier(middle_t, *x1, *x2) < progress {
+                        lower_t = middle_t;
+                    } else {
+                        upper_t = middle_t;
+                    }
+                }
+
+                return bezier((lower_t + upper_t) / 2.0, *y1, *y2);
+            },
+        }
+    }
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq)]
+pub struct TransitionStyle {
+    pub property: String, //this can also be "all"
+    pub duration: Duration,
+    pub delay: Duration,
+    pub timing_function: TimingFunction,
+}
+impl TransitionStyle {
+    pub fn applies_to(&self, property: &str) -> bool {
+        return self.property == "all" || self.property == property;
+    }
+}
+
+
+pub fn resolve_transitions(styles: &HashMap<String, String>) -> Vec<TransitionStyle> {
+    let mut transitions = Vec::new();
+
+    //the longhand properties override the values from the shorthand, so we first parse the shorthand
+    let shorthand = styles.get("transition");
+    if shorthand.is_some() {
+        for transition_text in split_transition_list(shorthand.unwrap()) {
+            transitions.push(parse_transition_shorthand(&transition_text));
+        }
+    }
+
+    let properties = styles.get("transition-property");
+    if properties.is_some() {
+        let properties = split_transition_list(properties.unwrap());
+        if transitions.is_empty() {
+            for property in &properties {
+                transitions.push(TransitionStyle { property: property.clone(), duration: Duration::ZERO, delay: Duration::ZERO,
+                                                   timing_function: TimingFunction::ease() });
+            }
+        } else {
+            //the lists are repeated when they are shorter than the list of properties
+            for (idx, transition) in transitions.iter_mut().enumerate() {
+                transition.property = properties[idx % properties.len()].clone();
+            }
+        }
+    }
+
+    if transitions.is_empty() {
+        //without transition-property the default is "all", but there is only something to animate when there is a duration
+        if styles.get("transition-duration").is_none() {
+            return transitions;
+        }
+        transitions.push(TransitionStyle { property: "all".to_owned(), duration: Duration::ZERO, delay: Duration::ZERO,
+                                           timing_function: TimingFunction::ease() });
+    }
+
+    apply_longhand_list(styles, "transition-duration", &mut transitions, |transition, value| {
+        transition.duration = parse_time(value).unwrap_or(Duration::ZERO);
+    });
+    apply_longhand_list(styles, "transition-delay", &mut transitions, |transition, value| {
+        transition.delay = parse_time(value).unwrap_or(Duration::ZERO);
+    });
+    apply_longhand_list(styles, "transition-timing-function", &mut transitions, |transition, value| {
+        transition.timing_function = parse_timing_function(value).unwrap_or(TimingFunction::ease());
+    });
+
+    transitions.retain(|transition| !transition.duration.is_zero() && transition.property != "none");
+    return transitions;
+}
+
+
+fn apply_longhand_list<F: Fn(&mut TransitionStyle, &str)>(styles: &HashMap<String, String>, property: &str,
+                                                          transitions: &mut Vec<TransitionStyle>, apply_function: F) {
+    let value = styles.get(property);
+    if value.is_none() {
+        return;
+    }
+
+    let values = split_transition_list(value.unwrap());
+    if values.is_empty() {
+        return;
+    }
+    for (idx, transition) in transitions.iter_mut().enumerate() {
+        apply_function(transition, &values[idx % values.len()]);
+    }
+}
+
+
+fn parse_transition_shorthand(transition_text: &str) -> TransitionStyle {
+    //the first time in the shorthand is the duration, the second one the delay
+    let mut transition = TransitionStyle { property: "all".to_owned(), duration: Duration::ZERO, delay: Duration::ZERO,
+                                           timing_function: TimingFunction::ease() };
+    let mut seen_duration = false;
+
+    for part in split_outside_parentheses(transition_text) {
+        let time = parse_time(&part);
+        if time.is_some() {
+            if seen_duration {
+                transition.delay = time.unwrap();
+            } else {
+                transition.duration = time.unwrap();
+                seen_duration = true;
+            }
+            continue;
+        }
+
+        let timing_function = parse_timing_function(&part);
+        if timing_function.is_some() {
+            transition.timing_function = timing_function.unwrap();
+            continue;
+        }
+
+        transition.property = part;
+    }
+
+    return transition;
+}
+
+
+fn parse_time(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    let seconds = if value.ends_with("ms") {
+        value[..value.len() - 2].parse::<f64>().ok().map(|milliseconds| milliseconds / 1000.0)
+    } else if value.ends_with('s') {
+        value[..value.len() - 1].parse::<f64>().ok()
+    } else {
+        None
+    };
+
+    if seconds.is_none() || seconds.unwrap() < 0.0 {
+        return None;
+    }
+    return Some(Duration::from_secs_f64(seconds.unwrap()));
+}
+
+
+fn parse_timing_function(value: &str) -> Option<TimingFunction> {
+    let value = value.trim();
+    match value {
+        "linear" => { return Some(TimingFunction::Linear); },
+        "ease" => { return Some(TimingFunction::ease()); },
+        "ease-in" => { return Some(TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0)); },
+        "ease-out" => { return Some(TimingFunction::ease_out()); },
+        "ease-in-out" => { return Some(TimingFunction::CubicBezier(0.42, 0.0, 0.58, 1.0)); },
+        "step-start" => { return Some(TimingFunction::Steps(1, true)); },
+        "step-end" => { return Some(TimingFunction::Steps(1, false)); },
+        _ => {},
+    }
+
+    if !value.ends_with(')') {
+        return None;
+    }
+
+    let open_paren_idx = value.find('(');
+    if open_paren_idx.is_none() {
+        return None;
+    }
+    let function_name = &value[..open_paren_idx.unwrap()];
+    let arguments: Vec<&str> = value[open_paren_idx.unwrap() + 1..value.len() - 1].split(',').map(|argument| argument.trim()).collect();
+
+    if function_name == "cubic-bezier" && arguments.len() == 4 {
+        let numbers: Vec<f32> = arguments.iter().filter_map(|argument| argument.parse::<f32>().ok()).collect();
+        if numbers.len() == 4 {
+            return Some(TimingFunction::CubicBezier(numbers[0].clamp(0.0, 1.0), numbers[1], numbers[2].clamp(0.0, 1.0), numbers[3]));
+        }
+    } else if function_name == "steps" && !arguments.is_empty() {
+        let number_of_steps = arguments[0].parse::<u32>();
+        if number_of_steps.is_ok() {
+            let jump_at_start = arguments.len() > 1 && (arguments[1] == "start" || arguments[1] == "jump-start");
+            return Some(TimingFunction::Steps(number_of_steps.unwrap(), jump_at_start));
+        }
+    }
+
+    debug_log_warn(format!("unsupported transition timing function: {}", value));
+    return None;
+}
+
+
+fn split_transition_list(value: &str) -> Vec<String> {
+    //we can't just split on commas, since they are also used inside cubic-bezier() and steps()
+    let mut items = Vec::new();
+    let mut current_item = String::new();
+    let mut depth = 0;
+
+    for c in value.chars() {
+        if c == '(' {
+            depth += 1;
+        } else if c == ')' && depth > 0 {
+            depth -= 1;
+        }
+
+        if c == ',' && depth == 0 {
+            items.push(current_item.trim().to_owned());
+            current_item = String::new();
+        } else {
+            current_item.push(c);
+        }
+    }
+    if !current_item.trim().is_empty() {
+        items.push(current_item.trim().to_owned());
+    }
+
+    return items;
+}
+
+
+fn split_outside_parentheses(value: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current_part = String::new();
+    let mut depth = 0;
+
+    for c in value.chars() {
+        if c == '(' {
+            depth += 1;
+        } else if c == ')' && depth > 0 {
+            depth -= 1;
+        }
+
+        if c.is_whitespace() && depth == 0 {
+            if !current_part.is_empty() {
+                parts.push(current_part);
+            }
+            current_part = String::new();
+        } else {
+            current_part.push(c);
+        }
+    }
+    if !current_part.is_empty() {
+        parts.push(current_part);
+    }
+
+    return parts;
+}
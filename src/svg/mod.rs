@@ -0,0 +1,450 @@
+mod parser;
+mod path;
+mod rasterizer;
+#[cfg(test)] mod tests;
+
+use std::collections::HashMap;
+
+use image::DynamicImage;
+
+use crate::color::Color;
+use crate::debug::debug_log_warn;
+use crate::dom::{Document, DomNodeId};
+use crate::svg::path::{
+    parse_number_list,
+    parse_path_data,
+    parse_transform,
+    Point,
+    Transform,
+};
+use crate::svg::rasterizer::{
+    stroke_to_polygons,
+    Canvas,
+    FillRule,
+};
+
+
+const DEFAULT_SVG_WIDTH: f32 = 300.0;
+const DEFAULT_SVG_HEIGHT: f32 = 150.0;
+const MAX_SVG_SIZE: f32 = 4096.0; //so a huge width or height does not make us allocate gigabytes of pixels
+
+//these are the elements that can contain other elements. Inline svg goes through the html parser, which does not know that svg elements
+//like <rect /> can be self-closing, so it nests everything after them inside of them. For the other elements we undo that nesting.
+const CONTAINER_ELEMENTS: [&str; 10] = ["a", "defs", "g", "lineargradient", "mask", "pattern", "radialgradient", "svg", "switch", "symbol"];
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SvgElement {
+    pub name: String,
+    pub attributes: HashMap<String, String>,
+    pub children: Vec<SvgElement>,
+}
+impl SvgElement {
+    fn get_property(&self, property_name: &str) -> Option<String> {
+        //properties can be set both as an attribute and in the style attribute, where the style attribute wins
+        let style = self.attributes.get("style");
+        if style.is_some() {
+            for declaration in style.unwrap().split(';') {
+                let colon_idx = declaration.find(':');
+                if colon_idx.is_some() && declaration[..colon_idx.unwrap()].trim().to_ascii_lowercase() == property_name {
+                    return Some(declaration[colon_idx.unwrap() + 1..].trim().to_owned());
+                }
+            }
+        }
+
+        return self.attributes.get(property_name).map(|value| value.trim().to_owned());
+    }
+}
+
+
+#[derive(Clone)]
+struct PaintStyle {
+    fill: Option<Color>,
+    stroke: Option<Color>,
+    stroke_width: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    opacity: f32,
+    fill_rule: FillRule,
+    current_color: Color,
+}
+
+
+struct RenderContext<'a> {
+    canvas: Canvas,
+    elements_by_id: HashMap<String, &'a SvgElement>,
+    viewport_width: f32,
+    viewport_height: f32,
+}
+
+
+pub fn render_svg(svg_text: &str) -> Option<DynamicImage> {
+    let root = parser::parse_svg(svg_text);
+    if root.is_none() || root.as_ref().unwrap().name != "svg" {
+        return None;
+    }
+    return Some(render_svg_element(&root.unwrap()));
+}
+
+
+pub fn svg_element_from_dom_node(document: &Document, dom_node_id: DomNodeId) -> SvgElement {
+    let dom_node = &document.nodes[dom_node_id];
+    let mut attributes = HashMap::new();
+    if dom_node.attributes.is_some() {
+        for attribute in dom_node.attributes.as_ref().unwrap() {
+            attributes.insert(attribute.name.clone(), attribute.value.clone());
+        }
+    }
+
+    let name = dom_node.name.clone().unwrap_or(String::new());
+    let mut children = Vec::new();
+    if dom_node.children.is_some() {
+        for child_id in dom_node.children.as_ref().unwrap() {
+            if document.nodes[*child_id].name.is_none() {
+                continue; //we don't render text in svg yet
+            }
+
+            let mut svg_child = svg_element_from_dom_node(document, *child_id);
+            let is_container = CONTAINER_ELEMENTS.contains(&svg_child.name.as_str());
+            let nested_elements = if is_container { Vec::new() } else { std::mem::take(&mut svg_child.children) };
+            children.push(svg_child);
+            children.extend(nested_elements);
+        }
+    }
+
+    return SvgElement { name, attributes, children };
+}
+
+
+pub fn render_svg_element(root: &SvgElement) -> DynamicImage {
+    let view_box = root.attributes.get("viewbox").map(|view_box| parse_number_list(view_box))
+                                                  .filter(|numbers| numbers.len() == 4 && numbers[2] > 0.0 && numbers[3] > 0.0);
+
+    let mut width = root.attributes.get("width").and_then(|width| parse_length(width, None));
+    let mut height = root.attributes.get("height").and_then(|height| parse_length(height, None));
+    if view_box.is_some() {
+        //when only one of the sizes is given, we keep the aspect ratio of the viewbox
+        let view_box = view_box.as_ref().unwrap();
+        let aspect_ratio = view_box[2] / view_box[3];
+        if width.is_none() && height.is_none() {
+            width = Some(view_box[2]);
+            height = Some(view_box[3]);
+        } else if width.is_none() {
+            width = Some(height.unwrap() * aspect_ratio);
+        } else if height.is_none() {
+            height = Some(width.unwrap() / aspect_ratio);
+        }
+    }
+    let width = width.unwrap_or(DEFAULT_SVG_WIDTH).clamp(1.0, MAX_SVG_SIZE);
+    let height = height.unwrap_or(DEFAULT_SVG_HEIGHT).clamp(1.0, MAX_SVG_SIZE);
+
+    let mut transform = Transform::identity();
+    let mut viewport_width = width;
+    let mut viewport_height = height;
+    if view_box.is_some() {
+        let view_box = view_box.unwrap();
+        let mut scale_x = width / view_box[2];
+        let mut scale_y = height / view_box[3];
+
+        //TODO: we only support "none" and the default (xMidYMid meet) for preserveAspectRatio
+        let preserve_aspect_ratio = root.attributes.get("preserveaspectratio");
+        if preserve_aspect_ratio.is_none() || preserve_aspect_ratio.unwrap().trim() != "none" {
+            scale_x = scale_x.min(scale_y);
+            scale_y = scale_x;
+        }
+        let offset_x = (width - view_box[2] * scale_x) / 2.0;
+        let offset_y = (height - view_box[3] * scale_y) / 2.0;
+
+        transform = Transform::translate(offset_x, offset_y).then(&Transform::scale(scale_x, scale_y))
+                                                            .then(&Transform::translate(-view_box[0], -view_box[1]));
+        viewport_width = view_box[2];
+        viewport_height = view_box[3];
+    }
+
+    let mut elements_by_id = HashMap::new();
+    collect_elements_by_id(root, &mut elements_by_id);
+
+    let mut context = RenderContext { canvas: Canvas::new(width as usize, height as usize), elements_by_id, viewport_width, viewport_height };
+    let initial_style = PaintStyle { fill: Some(Color::BLACK), stroke: None, stroke_width: 1.0, fill_opacity: 1.0, stroke_opacity: 1.0, opacity: 1.0,
+                                     fill_rule: FillRule::NonZero, current_color: Color::BLACK };
+
+    //the root element is rendered as a group, its own viewport is already handled above
+    let root_style = compute_style(root, &initial_style, &context);
+    for child in root.children.iter() {
+        render_element(child, &mut context, &transform, &root_style);
+    }
+
+    return context.canvas.into_image();
+}
+
+
+fn collect_elements_by_id<'a>(element: &'a SvgElement, elements_by_id: &mut HashMap<String, &'a SvgElement>) {
+    let id = element.attributes.get("id");
+    if id.is_some() {
+        elements_by_id.insert(id.unwrap().clone(), element);
+    }
+    for child in element.children.iter() {
+        collect_elements_by_id(child, elements_by_id);
+    }
+}
+
+
+fn render_element(element: &SvgElement, context: &mut RenderContext, parent_transform: &Transform, parent_style: &PaintStyle) {
+    if element.get_property("display") == Some(String::from("none")) {
+        return;
+    }
+
+    let style = compute_style(element, parent_style, context);
+    let mut transform = *parent_transform;
+    let element_transform = element.attributes.get("transform");
+    if element_transform.is_some() {
+        transform = transform.then(&parse_transform(element_transform.unwrap()));
+    }
+
+    let path_data = match element.name.as_str() {
+        "g" | "a" | "switch" => {
+            for child in element.children.iter() {
+                render_element(child, context, &transform, &style);
+            }
+            return;
+        },
+        "svg" => {
+            //TODO: nested svg elements should get their own viewport (and viewbox), we now only move them to their position
+            let x = length_attribute(element, "x", context.viewport_width);
+            let y = length_attribute(element, "y", context.viewport_height);
+            let nested_transform = transform.then(&Transform::translate(x, y));
+            for child in element.children.iter() {
+                render_element(child, context, &nested_transform, &style);
+            }
+            return;
+        },
+        "rect" => rect_path_data(element, context),
+        "circle" => {
+            let diagonal = (context.viewport_width.powi(2) + context.viewport_height.powi(2)).sqrt() / 2.0_f32.sqrt();
+            let radius = length_attribute(element, "r", diagonal);
+            ellipse_path_data(length_attribute(element, "cx", context.viewport_width), length_attribute(element, "cy", context.viewport_height),
+                              radius, radius)
+        },
+        "ellipse" => {
+            ellipse_path_data(length_attribute(element, "cx", context.viewport_width), length_attribute(element, "cy", context.viewport_height),
+                              length_attribute(element, "rx", context.viewport_width), length_attribute(element, "ry", context.viewport_height))
+        },
+        "line" => {
+            format!("M {} {} L {} {}", length_attribute(element, "x1", context.viewport_width), length_attribute(element, "y1", context.viewport_height),
+                                       length_attribute(element, "x2", context.viewport_width), length_attribute(element, "y2", context.viewport_height))
+        },
+        "polyline" | "polygon" => {
+            let numbers = parse_number_list(element.attributes.get("points").map(|points| points.as_str()).unwrap_or(""));
+            let mut path_data = String::new();
+            for (idx, point) in numbers.chunks_exact(2).enumerate() {
+                path_data.push_str(&format!("{} {} {} ", if idx == 0 { "M" } else { "L" }, point[0], point[1]));
+            }
+            if element.name == "polygon" && !path_data.is_empty() {
+                path_data.push('Z');
+            }
+            path_data
+        },
+        "path" => element.attributes.get("d").cloned().unwrap_or(String::new()),
+
+        "defs" | "lineargradient" | "radialgradient" | "stop" | "title" | "desc" | "metadata" | "style" | "symbol" | "mask" | "clippath" | "pattern" => {
+            //these are not rendered themselves, or we only use them through other elements
+            return;
+        },
+        _ => {
+            //TODO: we don't support text and use elements yet, and neither images or filters inside svg
+            debug_log_warn(format!("unsupported svg element: {}", element.name));
+            return;
+        },
+    };
+
+    if element.get_property("visibility") == Some(String::from("hidden")) {
+        return;
+    }
+
+    //the path is flattened in user space, and then transformed to pixels, so the stroke is also done in pixels
+    let scale_factor = transform.scale_factor();
+    let mut subpaths = parse_path_data(&path_data, scale_factor);
+    for subpath in subpaths.iter_mut() {
+        for point in subpath.points.iter_mut() {
+            *point = transform.apply(*point);
+        }
+    }
+
+    if style.fill.is_some() {
+        let polygons = subpaths.iter().map(|subpath| subpath.points.clone()).collect();
+        context.canvas.fill_polygons(&polygons, style.fill.unwrap(), style.fill_opacity * style.opacity, style.fill_rule);
+    }
+    if style.stroke.is_some() && style.stroke_width > 0.0 {
+        let polygons = stroke_to_polygons(&subpaths, style.stroke_width * scale_factor);
+        context.canvas.fill_polygons(&polygons, style.stroke.unwrap(), style.stroke_opacity * style.opacity, FillRule::NonZero);
+    }
+}
+
+
+fn compute_style(element: &SvgElement, parent_style: &PaintStyle, context: &RenderContext) -> PaintStyle {
+    let mut style = parent_style.clone();
+
+    let color = element.get_property("color");
+    if color.is_some() {
+        style.current_color = Color::from_string(&color.unwrap()).unwrap_or(style.current_color);
+    }
+
+    let fill = element.get_property("fill");
+    if fill.is_some() {
+        style.fill = parse_paint(&fill.unwrap(), parent_style.fill, &style, context);
+    }
+    let stroke = element.get_property("stroke");
+    if stroke.is_some() {
+        style.stroke = parse_paint(&stroke.unwrap(), parent_style.stroke, &style, context);
+    }
+
+    let stroke_width = element.get_property("stroke-width").and_then(|stroke_width| parse_length(&stroke_width, Some(context.viewport_width)));
+    if stroke_width.is_some() {
+        style.stroke_width = stroke_width.unwrap();
+    }
+
+    let parse_opacity = |property_name: &str| -> Option<f32> {
+        return element.get_property(property_name).and_then(|opacity| parse_length(&opacity, Some(1.0))).map(|opacity| opacity.clamp(0.0, 1.0));
+    };
+    style.fill_opacity = parse_opacity("fill-opacity").unwrap_or(style.fill_opacity);
+    style.stroke_opacity = parse_opacity("stroke-opacity").unwrap_or(style.stroke_opacity);
+
+    //TODO: opacity should be applied to the group as a whole, we now apply it to every shape in it, so overlapping shapes blend with each other
+    style.opacity *= parse_opacity("opacity").unwrap_or(1.0);
+
+    let fill_rule = element.get_property("fill-rule");
+    if fill_rule.is_some() {
+        style.fill_rule = if fill_rule.unwrap() == "evenodd" { FillRule::EvenOdd } else { FillRule::NonZero };
+    }
+
+    return style;
+}
+
+
+fn parse_paint(paint: &String, inherited_paint: Option<Color>, style: &PaintStyle, context: &RenderContext) -> Option<Color> {
+    let paint = paint.trim();
+    if paint == "none" {
+        return None;
+    }
+    if paint == "inherit" {
+        return inherited_paint;
+    }
+    if paint.eq_ignore_ascii_case("currentcolor") {
+        return Some(style.current_color);
+    }
+
+    if paint.starts_with("url(") {
+        //TODO: we don't render gradients yet, so we use the color of their first stop instead
+        //like in css, a url that is not closed runs until the end
+        let close_paren_idx = paint.find(')').unwrap_or(paint.len());
+        let referenced_id = paint[4..close_paren_idx].trim().trim_matches(|c| c == '"' || c == '\'').trim_start_matches('#');
+        let referenced_element = context.elements_by_id.get(referenced_id);
+
+        if referenced_element.is_some() {
+            let first_stop = referenced_element.unwrap().children.iter().find(|child| child.name == "stop");
+            if first_stop.is_some() {
+                let first_stop = first_stop.unwrap();
+                let mut stop_color = first_stop.get_property("stop-color").and_then(|stop_color| Color::from_string(&stop_color))
+                                                                           .unwrap_or(Color::BLACK);
+                let stop_opacity = first_stop.get_property("stop-opacity").and_then(|opacity| parse_length(&opacity, Some(1.0)));
+                if stop_opacity.is_some() {
+                    stop_color.a = (stop_color.a as f32 * stop_opacity.unwrap().clamp(0.0, 1.0)) as u8;
+                }
+                return Some(stop_color);
+            }
+        }
+
+        //the url can be followed by a fallback color, for when the referenced element can't be used
+        let fallback = paint.get(close_paren_idx + 1..).unwrap_or("").trim();
+        if !fallback.is_empty() {
+            return parse_paint(&fallback.to_owned(), inherited_paint, style, context);
+        }
+        return None;
+    }
+
+    let color = Color::from_string(&paint.to_owned());
+    if color.is_none() {
+        debug_log_warn(format!("unsupported svg paint: {}", paint));
+        return inherited_paint;
+    }
+    return color;
+}
+
+
+fn parse_length(length: &String, percentage_reference: Option<f32>) -> Option<f32> {
+    //lengths in svg are in user units (which are pixels), or they have a css unit
+    let length = length.trim().to_ascii_lowercase();
+
+    let units = [("px", 1.0), ("pt", 4.0 / 3.0), ("pc", 16.0), ("mm", 96.0 / 25.4), ("cm", 96.0 / 2.54), ("in", 96.0), ("em", 16.0), ("ex", 8.0)];
+    for (unit, factor) in units.iter() {
+        if length.ends_with(unit) {
+            return length[..length.len() - unit.len()].trim().parse::<f32>().ok().filter(|number| number.is_finite()).map(|number| number * factor);
+        }
+    }
+
+    if length.ends_with('%') {
+        if percentage_reference.is_none() {
+            return None;
+        }
+        return length[..length.len() - 1].trim().parse::<f32>().ok().filter(|number| number.is_finite())
+                                                                     .map(|number| number / 100.0 * percentage_reference.unwrap());
+    }
+
+    return length.parse::<f32>().ok().filter(|number| number.is_finite());
+}
+
+
+fn length_attribute(element: &SvgElement, attribute_name: &str, percentage_reference: f32) -> f32 {
+    let value = element.attributes.get(attribute_name);
+    if value.is_none() {
+        return 0.0;
+    }
+    return parse_length(value.unwrap(), Some(percentage_reference)).unwrap_or(0.0);
+}
+
+
+fn rect_path_data(element: &SvgElement, context: &RenderContext) -> String {
+    let x = length_attribute(element, "x", context.viewport_width);
+    let y = length_attribute(element, "y", context.viewport_height);
+    let width = length_attribute(element, "width", context.viewport_width);
+    let height = length_attribute(element, "height", context.viewport_height);
+    if width <= 0.0 || height <= 0.0 {
+        return String::new();
+    }
+
+    //when only one of the corner radii is given, it is used for both
+    let mut radius_x = length_attribute(element, "rx", context.viewport_width);
+    let mut radius_y = length_attribute(element, "ry", context.viewport_height);
+    if !element.attributes.contains_key("rx") {
+        radius_x = radius_y;
+    }
+    if !element.attributes.contains_key("ry") {
+        radius_y = radius_x;
+    }
+    let radius_x = radius_x.clamp(0.0, width / 2.0);
+    let radius_y = radius_y.clamp(0.0, height / 2.0);
+
+    if radius_x == 0.0 || radius_y == 0.0 {
+        return format!("M {} {} H {} V {} H {} Z", x, y, x + width, y + height, x);
+    }
+
+    let corner = |end: Point| -> String { return format!("A {} {} 0 0 1 {} {}", radius_x, radius_y, end.x, end.y); };
+    return format!("M {} {} H {} {} V {} {} H {} {} V {} {} Z",
+                   x + radius_x, y,
+                   x + width - radius_x, corner(Point { x: x + width, y: y + radius_y }),
+                   y + height - radius_y, corner(Point { x: x + width - radius_x, y: y + height }),
+                   x + radius_x, corner(Point { x, y: y + height - radius_y }),
+                   y + radius_y, corner(Point { x: x + radius_x, y }));
+}
+
+
+fn ellipse_path_data(center_x: f32, center_y: f32, radius_x: f32, radius_y: f32) -> String {
+    if radius_x <= 0.0 || radius_y <= 0.0 {
+        return String::new();
+    }
+
+    //we draw the ellipse as two half arcs, since a single arc can't end where it started
+    return format!("M {} {} A {} {} 0 1 0 {} {} A {} {} 0 1 0 {} {} Z", center_x - radius_x, center_y,
+                   radius_x, radius_y, center_x + radius_x, center_y,
+                   radius_x, radius_y, center_x - radius_x, center_y);
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use crate::svg::SvgElement;
+
+
+//This is a small xml parser, that only does what we need for svg images. We ignore text content, since we don't render svg text yet.
+pub fn parse_svg(svg_text: &str) -> Option<SvgElement> {
+    let chars: Vec<char> = svg_text.chars().collect();
+    let mut position = 0;
+    let mut open_elements: Vec<SvgElement> = Vec::new();
+    let mut root = None;
+
+    while position < chars.len() {
+        if chars[position] != '<' {
+            position += 1;
+            continue;
+        }
+
+        if starts_with_at(&chars, position, "<!--") {
+            position = find_end_of(&chars, position, "-->");
+            continue;
+        }
+        if starts_with_at(&chars, position, "<![CDATA[") {
+            position = find_end_of(&chars, position, "]]>");
+            continue;
+        }
+        if starts_with_at(&chars, position, "<?") || starts_with_at(&chars, position, "<!") {
+            //the xml declaration, processing instructions and the doctype
+            position = find_end_of(&chars, position, ">");
+            continue;
+        }
+
+        if starts_with_at(&chars, position, "</") {
+            position += 2;
+            let name = element_name_without_prefix(&read_name(&chars, &mut position));
+            position = find_end_of(&chars, position, ">");
+
+            //we close the last element with this name, and implicitly everything that was opened after it
+            let possible_idx = open_elements.iter().rposition(|element| element.name == name);
+            if possible_idx.is_some() {
+                while open_elements.len() > possible_idx.unwrap() {
+                    let element = open_elements.pop().unwrap();
+                    add_to_parent(&mut open_elements, &mut root, element);
+                }
+            }
+            continue;
+        }
+
+        position += 1;
+        let name = element_name_without_prefix(&read_name(&chars, &mut position));
+        let mut attributes = HashMap::new();
+        let mut self_closing = false;
+
+        while position < chars.len() {
+            skip_whitespace(&chars, &mut position);
+            if position >= chars.len() {
+                break;
+            }
+
+            if chars[position] == '>' {
+                position += 1;
+                break;
+            }
+            if chars[position] == '/' {
+                self_closing = true;
+                position += 1;
+                continue;
+            }
+
+            let attribute_name = read_name(&chars, &mut position);
+            if attribute_name.is_empty() {
+                //this is not valid xml, we skip the character so we don't loop forever
+                position += 1;
+                continue;
+            }
+
+            skip_whitespace(&chars, &mut position);
+            let mut attribute_value = String::new();
+            if position < chars.len() && chars[position] == '=' {
+                position += 1;
+                skip_whitespace(&chars, &mut position);
+                attribute_value = read_attribute_value(&chars, &mut position);
+            }
+
+            //we lowercase the names, since inline svg comes through the html lexer, which does the same
+            attributes.insert(attribute_name.to_ascii_lowercase(), decode_entities(&attribute_value));
+        }
+
+        let element = SvgElement { name, attributes, children: Vec::new() };
+        if self_closing {
+            add_to_parent(&mut open_elements, &mut root, element);
+        } else {
+            open_elements.push(element);
+        }
+    }
+
+    //elements that are not closed at the end of the document are closed implicitly
+    while !open_elements.is_empty() {
+        let element = open_elements.pop().unwrap();
+        add_to_parent(&mut open_elements, &mut root, element);
+    }
+
+    return root;
+}
+
+
+fn add_to_parent(open_elements: &mut Vec<SvgElement>, root: &mut Option<SvgElement>, element: SvgElement) {
+    if !open_elements.is_empty() {
+        open_elements.last_mut().unwrap().children.push(element);
+    } else if root.is_none() {
+        *root = Some(element);
+    }
+}
+
+
+fn element_name_without_prefix(name: &String) -> String {
+    //svg elements are sometimes written with a namespace prefix, like <svg:rect>
+    return name.rsplit(':').next().unwrap().to_ascii_lowercase();
+}
+
+
+fn starts_with_at(chars: &Vec<char>, position: usize, text: &str) -> bool {
+    let mut idx = position;
+    for c in text.chars() {
+        if idx >= chars.len() || chars[idx] != c {
+            return false;
+        }
+        idx += 1;
+    }
+    return true;
+}
+
+
+fn find_end_of(chars: &Vec<char>, position: usize, end_text: &str) -> usize {
+    //returns the position right after end_text, or the end of the document if it is not there
+    let mut idx = position;
+    while idx < chars.len() {
+        if starts_with_at(chars, idx, end_text) {
+            return idx + end_text.chars().count();
+        }
+        idx += 1;
+    }
+    return chars.len();
+}
+
+
+fn skip_whitespace(chars: &Vec<char>, position: &mut usize) {
+    while *position < chars.len() && chars[*position].is_whitespace() {
+        *position += 1;
+    }
+}
+
+
+fn read_name(chars: &Vec<char>, position: &mut usize) -> String {
+    let mut name = String::new();
+    while *position < chars.len() {
+        let c = chars[*position];
+        if c.is_whitespace() || c == '/' || c == '>' || c == '=' {
+            break;
+        }
+        name.push(c);
+        *position += 1;
+    }
+    return name;
+}
+
+
+fn read_attribute_value(chars: &Vec<char>, position: &mut usize) -> String {
+    let mut value = String::new();
+    if *position >= chars.len() {
+        return value;
+    }
+
+    let quote = chars[*position];
+    if quote == '"' || quote == '\'' {
+        *position += 1;
+        while *position < chars.len() && chars[*position] != quote {
+            value.push(chars[*position]);
+            *position += 1;
+        }
+        *position += 1; //the closing quote
+        return value;
+    }
+
+    //unquoted values are not valid xml, but we read them like html does
+    while *position < chars.len() && !chars[*position].is_whitespace() && chars[*position] != '>' {
+        value.push(chars[*position]);
+        *position += 1;
+    }
+    return value;
+}
+
+
+fn decode_entities(text: &String) -> String {
+    if !text.contains('&') {
+        return text.clone();
+    }
+
+    //TODO: we only support the predefined xml entities, and not numeric character references
+    return text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&");
+}
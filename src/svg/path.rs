@@ -0,0 +1,486 @@
+use std::f32::consts::PI;
+
+use crate::debug::debug_log_warn;
+
+
+const MIN_CURVE_SEGMENTS: usize = 2;
+const MAX_CURVE_SEGMENTS: usize = 64;
+const CURVE_SEGMENT_LENGTH: f32 = 3.0; //in pixels, after the transform
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Subpath {
+    pub points: Vec<Point>,
+    pub closed: bool,
+}
+
+
+//This is the affine transform (a c e / b d f / 0 0 1), so a point is transformed to (a*x + c*y + e, b*x + d*y + f)
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+impl Transform {
+    pub fn identity() -> Transform {
+        return Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+    }
+
+    pub fn translate(x: f32, y: f32) -> Transform {
+        return Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: x, f: y };
+    }
+
+    pub fn scale(x: f32, y: f32) -> Transform {
+        return Transform { a: x, b: 0.0, c: 0.0, d: y, e: 0.0, f: 0.0 };
+    }
+
+    pub fn rotate(degrees: f32) -> Transform {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        return Transform { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 };
+    }
+
+    pub fn then(&self, other: &Transform) -> Transform {
+        //the result first applies other, and then self, which is the order in which nested transforms are applied to a shape
+        return Transform {
+            a: self.a * other.a + self.c * other.b,
+            b: self.b * other.a + self.d * other.b,
+            c: self.a * other.c + self.c * other.d,
+            d: self.b * other.c + self.d * other.d,
+            e: self.a * other.e + self.c * other.f + self.e,
+            f: self.b * other.e + self.d * other.f + self.f,
+        };
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        return Point { x: self.a * point.x + self.c * point.y + self.e, y: self.b * point.x + self.d * point.y + self.f };
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        //for non-uniform scaling this is an average, which we use for things like the stroke width
+        return (self.a * self.d - self.b * self.c).abs().sqrt();
+    }
+}
+
+
+pub fn parse_transform(transform_text: &str) -> Transform {
+    let mut transform = Transform::identity();
+
+    //the transform attribute is a list of functions, like "translate(10, 20) rotate(45)", which are applied from right to left
+    for function_text in transform_text.split(')') {
+        let open_paren_idx = function_text.find('(');
+        if open_paren_idx.is_none() {
+            continue;
+        }
+        let function_name = function_text[..open_paren_idx.unwrap()].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let arguments = parse_number_list(&function_text[open_paren_idx.unwrap() + 1..]);
+        let argument = |idx: usize, default: f32| -> f32 { return *arguments.get(idx).unwrap_or(&default); };
+
+        let function_transform = match function_name {
+            "matrix" if arguments.len() == 6 => {
+                Transform { a: arguments[0], b: arguments[1], c: arguments[2], d: arguments[3], e: arguments[4], f: arguments[5] }
+            },
+            "translate" if !arguments.is_empty() => Transform::translate(argument(0, 0.0), argument(1, 0.0)),
+            "scale" if !arguments.is_empty() => Transform::scale(argument(0, 1.0), argument(1, argument(0, 1.0))),
+            "rotate" if !arguments.is_empty() => {
+                //rotating around a given point is the same as moving that point to the origin, rotating, and moving it back
+                let (center_x, center_y) = (argument(1, 0.0), argument(2, 0.0));
+                Transform::translate(center_x, center_y).then(&Transform::rotate(argument(0, 0.0))).then(&Transform::translate(-center_x, -center_y))
+            },
+            "skewX" | "skewx" if arguments.len() == 1 => Transform { c: arguments[0].to_radians().tan(), ..Transform::identity() },
+            "skewY" | "skewy" if arguments.len() == 1 => Transform { b: arguments[0].to_radians().tan(), ..Transform::identity() },
+            _ => {
+                debug_log_warn(format!("unsupported svg transform: {}", function_text));
+                continue;
+            },
+        };
+
+        transform = transform.then(&function_transform);
+    }
+
+    return transform;
+}
+
+
+pub fn parse_number_list(text: &str) -> Vec<f32> {
+    let mut scanner = PathDataScanner::new(text);
+    let mut numbers = Vec::new();
+    while scanner.has_number_next() {
+        let number = scanner.read_number();
+        if number.is_none() {
+            break;
+        }
+        numbers.push(number.unwrap());
+    }
+    return numbers;
+}
+
+
+struct PathDataScanner {
+    chars: Vec<char>,
+    position: usize,
+}
+impl PathDataScanner {
+    fn new(text: &str) -> PathDataScanner {
+        return PathDataScanner { chars: text.chars().collect(), position: 0 };
+    }
+
+    fn skip_separators(&mut self) {
+        while self.position < self.chars.len() && (self.chars[self.position].is_whitespace() || self.chars[self.position] == ',') {
+            self.position += 1;
+        }
+    }
+
+    fn next_command(&mut self) -> Option<char> {
+        self.skip_separators();
+        if self.position < self.chars.len() && self.chars[self.position].is_ascii_alphabetic() {
+            self.position += 1;
+            return Some(self.chars[self.position - 1]);
+        }
+        return None;
+    }
+
+    fn has_number_next(&mut self) -> bool {
+        self.skip_separators();
+        return self.position < self.chars.len() && (self.chars[self.position].is_ascii_digit() || "+-.".contains(self.chars[self.position]));
+    }
+
+    fn read_number(&mut self) -> Option<f32> {
+        //numbers in path data don't need separators, "10-5" are two numbers, and so is "1.5.5"
+        self.skip_separators();
+        let start = self.position;
+
+        if self.position < self.chars.len() && (self.chars[self.position] == '+' || self.chars[self.position] == '-') {
+            self.position += 1;
+        }
+        self.skip_digits();
+        if self.position < self.chars.len() && self.chars[self.position] == '.' {
+            self.position += 1;
+            self.skip_digits();
+        }
+        if self.position < self.chars.len() && (self.chars[self.position] == 'e' || self.chars[self.position] == 'E') {
+            let exponent_start = self.position;
+            self.position += 1;
+            if self.position < self.chars.len() && (self.chars[self.position] == '+' || self.chars[self.position] == '-') {
+                self.position += 1;
+            }
+            if self.position < self.chars.len() && self.chars[self.position].is_ascii_digit() {
+                self.skip_digits();
+            } else {
+                self.position = exponent_start;
+            }
+        }
+
+        let number = self.chars[start..self.position].iter().collect::<String>().parse::<f32>();
+        if number.is_err() {
+            self.position = start;
+            return None;
+        }
+        return Some(number.unwrap());
+    }
+
+    fn read_flag(&mut self) -> Option<bool> {
+        //the flags of an arc are a single character, and are often written without separators, like "a10 10 0 015 5"
+        self.skip_separators();
+        if self.position < self.chars.len() && (self.chars[self.position] == '0' || self.chars[self.position] == '1') {
+            self.position += 1;
+            return Some(self.chars[self.position - 1] == '1');
+        }
+        return None;
+    }
+
+    fn read_point(&mut self) -> Option<Point> {
+        let x = self.read_number()?;
+        let y = self.read_number()?;
+        return Some(Point { x, y });
+    }
+
+    fn skip_digits(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_ascii_digit() {
+            self.position += 1;
+        }
+    }
+}
+
+
+//This parses path data (the d attribute of <path>) into lines, flattening the curves. The scale is how much the path is scaled when it is
+//drawn, so we can decide how many segments we need for curves.
+pub fn parse_path_data(path_data: &str, scale: f32) -> Vec<Subpath> {
+    let mut scanner = PathDataScanner::new(path_data);
+    let mut subpaths = Vec::new();
+    let mut current_subpath = Subpath { points: Vec::new(), closed: false };
+
+    let mut current_point = Point { x: 0.0, y: 0.0 };
+    let mut subpath_start = current_point;
+    let mut last_control_point: Option<Point> = None; //for the smooth curve commands, it is only set after a curve of the same kind
+    let mut last_command = ' ';
+
+    loop {
+        let possible_command = scanner.next_command();
+        let command = if possible_command.is_some() {
+            possible_command.unwrap()
+        } else if scanner.has_number_next() && last_command != ' ' && last_command.to_ascii_lowercase() != 'z' {
+            //a command can be repeated by just giving more parameters, and a moveto that is repeated becomes a lineto
+            match last_command {
+                'M' => 'L',
+                'm' => 'l',
+                _ => last_command,
+            }
+        } else {
+            break;
+        };
+
+        let is_relative = command.is_ascii_lowercase();
+        let offset = move |point: Point| -> Point {
+            if is_relative { Point { x: point.x + current_point.x, y: point.y + current_point.y } } else { point }
+        };
+
+        let mut new_control_point = None;
+        let mut parameters_valid = true;
+
+        match command.to_ascii_uppercase() {
+            'M' => {
+                let point = scanner.read_point();
+                if point.is_some() {
+                    finish_subpath(&mut subpaths, &mut current_subpath);
+                    current_point = offset(point.unwrap());
+                    subpath_start = current_point;
+                    current_subpath.points.push(current_point);
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'L' => {
+                let point = scanner.read_point();
+                if point.is_some() {
+                    current_point = offset(point.unwrap());
+                    current_subpath.points.push(current_point);
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'H' => {
+                let x = scanner.read_number();
+                if x.is_some() {
+                    current_point = Point { x: if is_relative { current_point.x + x.unwrap() } else { x.unwrap() }, y: current_point.y };
+                    current_subpath.points.push(current_point);
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'V' => {
+                let y = scanner.read_number();
+                if y.is_some() {
+                    current_point = Point { x: current_point.x, y: if is_relative { current_point.y + y.unwrap() } else { y.unwrap() } };
+                    current_subpath.points.push(current_point);
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'C' | 'S' => {
+                let first_control = if command.to_ascii_uppercase() == 'C' {
+                    scanner.read_point().map(|point| offset(point))
+                } else {
+                    //the first control point is the reflection of the last one of the previous curve, or the current point if there is none
+                    let is_after_cubic = "CcSs".contains(last_command);
+                    Some(reflect_control_point(if is_after_cubic { last_control_point } else { None }, current_point))
+                };
+                let second_control = scanner.read_point().map(|point| offset(point));
+                let end = scanner.read_point().map(|point| offset(point));
+
+                if first_control.is_some() && second_control.is_some() && end.is_some() {
+                    flatten_cubic_curve(current_point, first_control.unwrap(), second_control.unwrap(), end.unwrap(), scale, &mut current_subpath.points);
+                    current_point = end.unwrap();
+                    new_control_point = second_control;
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'Q' | 'T' => {
+                let control = if command.to_ascii_uppercase() == 'Q' {
+                    scanner.read_point().map(|point| offset(point))
+                } else {
+                    let is_after_quadratic = "QqTt".contains(last_command);
+                    Some(reflect_control_point(if is_after_quadratic { last_control_point } else { None }, current_point))
+                };
+                let end = scanner.read_point().map(|point| offset(point));
+
+                if control.is_some() && end.is_some() {
+                    //a quadratic curve is a cubic one with both control points two thirds of the way to the quadratic control point
+                    let control = control.unwrap();
+                    let end = end.unwrap();
+                    let first_control = Point { x: current_point.x + (2.0 / 3.0) * (control.x - current_point.x),
+                                                y: current_point.y + (2.0 / 3.0) * (control.y - current_point.y) };
+                    let second_control = Point { x: end.x + (2.0 / 3.0) * (control.x - end.x), y: end.y + (2.0 / 3.0) * (control.y - end.y) };
+                    flatten_cubic_curve(current_point, first_control, second_control, end, scale, &mut current_subpath.points);
+                    current_point = end;
+                    new_control_point = Some(control);
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'A' => {
+                let radius_x = scanner.read_number();
+                let radius_y = scanner.read_number();
+                let x_axis_rotation = scanner.read_number();
+                let large_arc = scanner.read_flag();
+                let sweep = scanner.read_flag();
+                let end = scanner.read_point().map(|point| offset(point));
+
+                if radius_x.is_some() && radius_y.is_some() && x_axis_rotation.is_some() && large_arc.is_some() && sweep.is_some() && end.is_some() {
+                    flatten_arc(current_point, radius_x.unwrap(), radius_y.unwrap(), x_axis_rotation.unwrap(), large_arc.unwrap(), sweep.unwrap(),
+                                end.unwrap(), scale, &mut current_subpath.points);
+                    current_point = end.unwrap();
+                } else {
+                    parameters_valid = false;
+                }
+            },
+            'Z' => {
+                current_subpath.closed = true;
+                finish_subpath(&mut subpaths, &mut current_subpath);
+
+                //drawing after a close without a moveto starts a new subpath at the start of the closed one
+                current_point = subpath_start;
+                current_subpath.points.push(current_point);
+            },
+            _ => {
+                debug_log_warn(format!("unknown command in svg path data: {}", command));
+                parameters_valid = false;
+            }
+        }
+
+        if !parameters_valid {
+            //like in the spec, we render the path up to the first error
+            break;
+        }
+
+        last_command = command;
+        last_control_point = new_control_point;
+    }
+
+    finish_subpath(&mut subpaths, &mut current_subpath);
+    return subpaths;
+}
+
+
+fn finish_subpath(subpaths: &mut Vec<Subpath>, current_subpath: &mut Subpath) {
+    let subpath = std::mem::replace(current_subpath, Subpath { points: Vec::new(), closed: false });
+    if subpath.points.len() > 1 {
+        subpaths.push(subpath);
+    }
+}
+
+
+fn reflect_control_point(control_point: Option<Point>, current_point: Point) -> Point {
+    if control_point.is_none() {
+        return current_point;
+    }
+    let control_point = control_point.unwrap();
+    return Point { x: 2.0 * current_point.x - control_point.x, y: 2.0 * current_point.y - control_point.y };
+}
+
+
+fn number_of_curve_segments(approximate_length: f32, scale: f32) -> usize {
+    let segments = (approximate_length * scale / CURVE_SEGMENT_LENGTH).ceil();
+    if !segments.is_finite() {
+        return MIN_CURVE_SEGMENTS;
+    }
+    return (segments as usize).clamp(MIN_CURVE_SEGMENTS, MAX_CURVE_SEGMENTS);
+}
+
+
+fn distance(from: Point, to: Point) -> f32 {
+    return ((to.x - from.x).powi(2) + (to.y - from.y).powi(2)).sqrt();
+}
+
+
+fn flatten_cubic_curve(start: Point, first_control: Point, second_control: Point, end: Point, scale: f32, points: &mut Vec<Point>) {
+    //the length of the control polygon is always at least the length of the curve
+    let control_polygon_length = distance(start, first_control) + distance(first_control, second_control) + distance(second_control, end);
+    let number_of_segments = number_of_curve_segments(control_polygon_length, scale);
+
+    for segment_idx in 1..=number_of_segments {
+        let t = segment_idx as f32 / number_of_segments as f32;
+        let inverse_t = 1.0 - t;
+        let weights = [inverse_t * inverse_t * inverse_t, 3.0 * inverse_t * inverse_t * t, 3.0 * inverse_t * t * t, t * t * t];
+        points.push(Point {
+            x: weights[0] * start.x + weights[1] * first_control.x + weights[2] * second_control.x + weights[3] * end.x,
+            y: weights[0] * start.y + weights[1] * first_control.y + weights[2] * second_control.y + weights[3] * end.y,
+        });
+    }
+}
+
+
+pub fn flatten_arc(start: Point, radius_x: f32, radius_y: f32, x_axis_rotation: f32, large_arc: bool, sweep: bool, end: Point, scale: f32,
+                   points: &mut Vec<Point>) {
+    //This converts the arc from the endpoint parameterization to the center parameterization, as described in the svg spec (appendix B.2.4)
+    if start == end {
+        return;
+    }
+    let mut radius_x = radius_x.abs();
+    let mut radius_y = radius_y.abs();
+    if radius_x == 0.0 || radius_y == 0.0 {
+        points.push(end);
+        return;
+    }
+
+    let (sin_phi, cos_phi) = x_axis_rotation.to_radians().sin_cos();
+    let half_dx = (start.x - end.x) / 2.0;
+    let half_dy = (start.y - end.y) / 2.0;
+    let x1_prime = cos_phi * half_dx + sin_phi * half_dy;
+    let y1_prime = -sin_phi * half_dx + cos_phi * half_dy;
+
+    //when the radii are too small to reach the end point, they are scaled up
+    let lambda = (x1_prime * x1_prime) / (radius_x * radius_x) + (y1_prime * y1_prime) / (radius_y * radius_y);
+    if lambda > 1.0 {
+        radius_x *= lambda.sqrt();
+        radius_y *= lambda.sqrt();
+    }
+
+    let numerator = radius_x * radius_x * radius_y * radius_y - radius_x * radius_x * y1_prime * y1_prime - radius_y * radius_y * x1_prime * x1_prime;
+    let denominator = radius_x * radius_x * y1_prime * y1_prime + radius_y * radius_y * x1_prime * x1_prime;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient;
+    }
+    let center_x_prime = coefficient * radius_x * y1_prime / radius_y;
+    let center_y_prime = -coefficient * radius_y * x1_prime / radius_x;
+    let center_x = cos_phi * center_x_prime - sin_phi * center_y_prime + (start.x + end.x) / 2.0;
+    let center_y = sin_phi * center_x_prime + cos_phi * center_y_prime + (start.y + end.y) / 2.0;
+
+    let angle_between = |ux: f32, uy: f32, vx: f32, vy: f32| -> f32 { return (ux * vy - uy * vx).atan2(ux * vx + uy * vy); };
+    let start_vector = ((x1_prime - center_x_prime) / radius_x, (y1_prime - center_y_prime) / radius_y);
+    let end_vector = ((-x1_prime - center_x_prime) / radius_x, (-y1_prime - center_y_prime) / radius_y);
+    let start_angle = angle_between(1.0, 0.0, start_vector.0, start_vector.1);
+    let mut delta_angle = angle_between(start_vector.0, start_vector.1, end_vector.0, end_vector.1);
+    if !sweep && delta_angle > 0.0 {
+        delta_angle -= 2.0 * PI;
+    } else if sweep && delta_angle < 0.0 {
+        delta_angle += 2.0 * PI;
+    }
+
+    let number_of_segments = number_of_curve_segments(delta_angle.abs() * radius_x.max(radius_y), scale);
+    for segment_idx in 1..=number_of_segments {
+        let angle = start_angle + delta_angle * (segment_idx as f32 / number_of_segments as f32);
+        let (sin_angle, cos_angle) = angle.sin_cos();
+        points.push(Point {
+            x: center_x + radius_x * cos_phi * cos_angle - radius_y * sin_phi * sin_angle,
+            y: center_y + radius_x * sin_phi * cos_angle + radius_y * cos_phi * sin_angle,
+        });
+    }
+
+    //we make sure we end exactly on the end point, so there are no small gaps due to rounding
+    *points.last_mut().unwrap() = end;
+}
@@ -0,0 +1,236 @@
+use std::f32::consts::PI;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::color::Color;
+use crate::svg::path::{Point, Subpath};
+
+
+const SUBSAMPLES_PER_PIXEL_ROW: usize = 4;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+
+struct Edge {
+    from: Point,
+    to: Point,
+    direction: i32,
+}
+
+
+//The canvas keeps premultiplied colors, with components between 0 and 1, so we can blend without losing precision
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Canvas {
+        return Canvas { width, height, pixels: vec![[0.0; 4]; width * height] };
+    }
+
+    pub fn fill_polygons(&mut self, polygons: &Vec<Vec<Point>>, color: Color, opacity: f32, fill_rule: FillRule) {
+        let coverage = self.compute_coverage(polygons, fill_rule);
+
+        let alpha_factor = opacity.clamp(0.0, 1.0) * (color.a as f32 / 255.0);
+        let color_components = [color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0];
+
+        for (pixel, pixel_coverage) in self.pixels.iter_mut().zip(coverage.iter()) {
+            if *pixel_coverage <= 0.0 {
+                continue;
+            }
+            let alpha = pixel_coverage.min(1.0) * alpha_factor;
+            for component_idx in 0..3 {
+                pixel[component_idx] = color_components[component_idx] * alpha + pixel[component_idx] * (1.0 - alpha);
+            }
+            pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+        }
+    }
+
+    pub fn into_image(self) -> DynamicImage {
+        let mut image = RgbaImage::new(self.width as u32, self.height as u32);
+
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            let alpha = pixel[3];
+            let unpremultiply = |component: f32| -> u8 {
+                if alpha <= 0.0 {
+                    return 0;
+                }
+                return ((component / alpha).clamp(0.0, 1.0) * 255.0).round() as u8;
+            };
+
+            let rgba = Rgba([unpremultiply(pixel[0]), unpremultiply(pixel[1]), unpremultiply(pixel[2]), (alpha.clamp(0.0, 1.0) * 255.0).round() as u8]);
+            image.put_pixel((idx % self.width) as u32, (idx / self.width) as u32, rgba);
+        }
+
+        return DynamicImage::ImageRgba8(image);
+    }
+
+    fn compute_coverage(&self, polygons: &Vec<Vec<Point>>, fill_rule: FillRule) -> Vec<f32> {
+        //We scan every pixel row on several sub rows, and for each of them we compute the spans that are inside the polygons.
+        //Horizontally we compute the exact part of the pixel that is covered, which together gives us anti-aliased edges.
+        let mut coverage = vec![0.0; self.width * self.height];
+
+        let mut edges = Vec::new();
+        let mut min_y = f32::MAX;
+        let mut max_y = f32::MIN;
+        for polygon in polygons {
+            for point_idx in 0..polygon.len() {
+                let from = polygon[point_idx];
+                let to = polygon[(point_idx + 1) % polygon.len()];
+                if from.y == to.y || !from.y.is_finite() || !to.y.is_finite() || !from.x.is_finite() || !to.x.is_finite() {
+                    continue;
+                }
+                min_y = min_y.min(from.y.min(to.y));
+                max_y = max_y.max(from.y.max(to.y));
+                edges.push(Edge { from, to, direction: if to.y > from.y { 1 } else { -1 } });
+            }
+        }
+        if edges.is_empty() {
+            return coverage;
+        }
+
+        let first_row = min_y.floor().max(0.0) as usize;
+        let last_row = (max_y.ceil().max(0.0) as usize).min(self.height);
+        let sub_row_weight = 1.0 / SUBSAMPLES_PER_PIXEL_ROW as f32;
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+
+        for row in first_row..last_row {
+            let row_coverage = &mut coverage[row * self.width..(row + 1) * self.width];
+
+            for sub_row in 0..SUBSAMPLES_PER_PIXEL_ROW {
+                let scan_y = row as f32 + (sub_row as f32 + 0.5) * sub_row_weight;
+
+                crossings.clear();
+                for edge in edges.iter() {
+                    let (top, bottom) = if edge.from.y < edge.to.y { (edge.from.y, edge.to.y) } else { (edge.to.y, edge.from.y) };
+                    if scan_y < top || scan_y >= bottom {
+                        continue;
+                    }
+                    let x = edge.from.x + (scan_y - edge.from.y) * (edge.to.x - edge.from.x) / (edge.to.y - edge.from.y);
+                    crossings.push((x, edge.direction));
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let is_inside = |winding: i32| -> bool {
+                    return match fill_rule {
+                        FillRule::NonZero => winding != 0,
+                        FillRule::EvenOdd => winding % 2 != 0,
+                    };
+                };
+
+                let mut winding = 0;
+                let mut span_start = 0.0;
+                for (x, direction) in crossings.iter() {
+                    let was_inside = is_inside(winding);
+                    winding += direction;
+                    let now_inside = is_inside(winding);
+
+                    if !was_inside && now_inside {
+                        span_start = *x;
+                    } else if was_inside && !now_inside {
+                        add_span_coverage(row_coverage, span_start, *x, sub_row_weight);
+                    }
+                }
+            }
+        }
+
+        return coverage;
+    }
+}
+
+
+fn add_span_coverage(row_coverage: &mut [f32], span_start: f32, span_end: f32, weight: f32) {
+    let row_width = row_coverage.len() as f32;
+    let span_start = span_start.clamp(0.0, row_width);
+    let span_end = span_end.clamp(0.0, row_width);
+    if span_end <= span_start {
+        return;
+    }
+
+    let first_pixel = span_start.floor() as usize;
+    let last_pixel = span_end.floor() as usize;
+    if first_pixel == last_pixel {
+        row_coverage[first_pixel] += (span_end - span_start) * weight;
+        return;
+    }
+
+    row_coverage[first_pixel] += (first_pixel as f32 + 1.0 - span_start) * weight;
+    for pixel in first_pixel + 1..last_pixel {
+        row_coverage[pixel] += weight;
+    }
+    if last_pixel < row_coverage.len() {
+        row_coverage[last_pixel] += (span_end - last_pixel as f32) * weight;
+    }
+}
+
+
+//This turns the outline of the subpaths into polygons that can be filled with the non-zero rule. Every line segment becomes a rectangle,
+//and we put a circle on every corner, so the corners are round.
+//TODO: we always draw round line joins and butt line caps, we don't support the stroke-linejoin and stroke-linecap properties yet
+pub fn stroke_to_polygons(subpaths: &Vec<Subpath>, stroke_width: f32) -> Vec<Vec<Point>> {
+    let mut polygons = Vec::new();
+    let half_width = stroke_width / 2.0;
+    if half_width <= 0.0 {
+        return polygons;
+    }
+
+    for subpath in subpaths {
+        let mut points = subpath.points.clone();
+        if subpath.closed && points.first() != points.last() {
+            points.push(points[0]);
+        }
+
+        for segment in points.windows(2) {
+            let (from, to) = (segment[0], segment[1]);
+            let length = ((to.x - from.x).powi(2) + (to.y - from.y).powi(2)).sqrt();
+            if length == 0.0 {
+                continue;
+            }
+            let normal = Point { x: -(to.y - from.y) / length * half_width, y: (to.x - from.x) / length * half_width };
+
+            polygons.push(with_positive_orientation(vec![
+                Point { x: from.x + normal.x, y: from.y + normal.y },
+                Point { x: to.x + normal.x, y: to.y + normal.y },
+                Point { x: to.x - normal.x, y: to.y - normal.y },
+                Point { x: from.x - normal.x, y: from.y - normal.y },
+            ]));
+        }
+
+        let corner_points = if subpath.closed { &points[..] } else if points.len() > 2 { &points[1..points.len() - 1] } else { &points[0..0] };
+        for corner in corner_points {
+            polygons.push(with_positive_orientation(circle_polygon(*corner, half_width)));
+        }
+    }
+
+    return polygons;
+}
+
+
+fn circle_polygon(center: Point, radius: f32) -> Vec<Point> {
+    let number_of_points = ((radius * 4.0).ceil() as usize).clamp(8, 32);
+    return (0..number_of_points).map(|idx| {
+        let angle = 2.0 * PI * (idx as f32 / number_of_points as f32);
+        return Point { x: center.x + radius * angle.cos(), y: center.y + radius * angle.sin() };
+    }).collect();
+}
+
+
+fn with_positive_orientation(mut polygon: Vec<Point>) -> Vec<Point> {
+    //with the non-zero fill rule, overlapping polygons only add up when they all go around in the same direction
+    let mut double_area = 0.0;
+    for point_idx in 0..polygon.len() {
+        let from = polygon[point_idx];
+        let to = polygon[(point_idx + 1) % polygon.len()];
+        double_area += from.x * to.y - to.x * from.y;
+    }
+    if double_area < 0.0 {
+        polygon.reverse();
+    }
+    return polygon;
+}
@@ -0,0 +1,98 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::svg::{
+    parser::parse_svg,
+    path::{parse_path_data, Point},
+    render_svg,
+};
+
+
+fn pixel_at(image: &DynamicImage, x: u32, y: u32) -> [u8; 4] {
+    return image.get_pixel(x, y).0;
+}
+
+
+#[test]
+fn test_parse_svg_document() {
+    let svg_text = "<?xml version=\"1.0\"?>\n<!-- a comment -->\n<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 10 10\">\
+                    <g fill=\"red\"><svg:rect x=\"1\" y=\"2\" width=\"3\" height=\"4\"/><circle r='5'></circle></g><title>a &amp; b</title></svg>";
+
+    let root = parse_svg(svg_text).unwrap();
+    assert_eq!(root.name, "svg");
+    assert_eq!(root.attributes.get("viewbox").unwrap(), "0 0 10 10");
+    assert_eq!(root.children.len(), 2);
+
+    let group = &root.children[0];
+    assert_eq!(group.name, "g");
+    assert_eq!(group.children.len(), 2);
+    assert_eq!(group.children[0].name, "rect");
+    assert_eq!(group.children[0].attributes.get("height").unwrap(), "4");
+    assert_eq!(group.children[1].name, "circle");
+    assert_eq!(group.children[1].attributes.get("r").unwrap(), "5");
+}
+
+
+#[test]
+fn test_parse_path_data() {
+    let subpaths = parse_path_data("M10 10 h 5 v5 l-5-5z m 1,1 2 0 0 2", 1.0);
+
+    assert_eq!(subpaths.len(), 2);
+    assert!(subpaths[0].closed);
+    assert_eq!(subpaths[0].points[0..4], [Point { x: 10.0, y: 10.0 }, Point { x: 15.0, y: 10.0 }, Point { x: 15.0, y: 15.0 }, Point { x: 10.0, y: 10.0 }]);
+
+    //the moveto after a close starts from the start of the closed subpath, and the repeated parameters become linetos
+    assert!(!subpaths[1].closed);
+    assert_eq!(subpaths[1].points, vec![Point { x: 11.0, y: 11.0 }, Point { x: 13.0, y: 11.0 }, Point { x: 13.0, y: 13.0 }]);
+}
+
+
+#[test]
+fn test_render_rect() {
+    let image = render_svg("<svg width=\"20\" height=\"10\"><rect x=\"5\" y=\"2\" width=\"10\" height=\"4\" fill=\"#ff0000\" /></svg>").unwrap();
+
+    assert_eq!(image.width(), 20);
+    assert_eq!(image.height(), 10);
+    assert_eq!(pixel_at(&image, 10, 4), [255, 0, 0, 255]);
+    assert_eq!(pixel_at(&image, 2, 4), [0, 0, 0, 0]);
+    assert_eq!(pixel_at(&image, 10, 8), [0, 0, 0, 0]);
+}
+
+
+#[test]
+fn test_render_unclosed_paint_url() {
+    //a url that is not closed refers to an element that does not exist, so without a fallback, nothing is painted
+    for fill in ["url(", "url(#missing"] {
+        let image = render_svg(&format!("<svg width=\"20\" height=\"10\"><rect width=\"20\" height=\"10\" fill=\"{}\" /></svg>", fill)).unwrap();
+        assert_eq!(pixel_at(&image, 10, 5), [0, 0, 0, 0]);
+    }
+
+    let image = render_svg("<svg width=\"20\" height=\"10\"><rect width=\"20\" height=\"10\" fill=\"url(#missing) #00ff00\" /></svg>").unwrap();
+    assert_eq!(pixel_at(&image, 10, 5), [0, 255, 0, 255]);
+}
+
+
+#[test]
+fn test_render_circle_with_viewbox() {
+    //the viewbox is scaled up to the size of the image, and the default fill is black
+    let image = render_svg("<svg width=\"100\" height=\"100\" viewBox=\"0 0 10 10\"><circle cx=\"5\" cy=\"5\" r=\"4\" /></svg>").unwrap();
+
+    assert_eq!(pixel_at(&image, 50, 50), [0, 0, 0, 255]);
+    assert_eq!(pixel_at(&image, 50, 15), [0, 0, 0, 255]);
+    assert_eq!(pixel_at(&image, 50, 5)[3], 0);
+    assert_eq!(pixel_at(&image, 15, 15)[3], 0); //the corner of the bounding box is outside the circle
+}
+
+
+#[test]
+fn test_render_fill_rule_and_stroke() {
+    let square_with_hole = "M 0 0 H 30 V 30 H 0 Z M 10 10 H 20 V 20 H 10 Z";
+    let svg_text = format!("<svg width=\"60\" height=\"30\"><path d=\"{}\" style=\"fill: blue; fill-rule: evenodd\" />\
+                            <line x1=\"40\" y1=\"0\" x2=\"40\" y2=\"30\" stroke=\"lime\" stroke-width=\"4\" /></svg>", square_with_hole);
+    let image = render_svg(&svg_text).unwrap();
+
+    assert_eq!(pixel_at(&image, 5, 5), [0, 0, 255, 255]);
+    assert_eq!(pixel_at(&image, 15, 15)[3], 0);
+
+    assert_eq!(pixel_at(&image, 39, 15), [0, 255, 0, 255]);
+    assert_eq!(pixel_at(&image, 44, 15)[3], 0);
+}
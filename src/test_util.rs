@@ -1,10 +1,49 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::env;
+use std::fs;
+use std::path::Path;
 
 use crate::html_lexer::{AttributeContent, HtmlToken, HtmlTokenWithLocation};
 
 
-static NEXT_TEST_ID: AtomicUsize = AtomicUsize::new(1);
-pub fn get_next_test_id() -> usize { NEXT_TEST_ID.fetch_add(1, Ordering::Relaxed) }
+const SNAPSHOT_CONTEXT_LINES: usize = 5;
+
+
+pub fn compare_with_snapshot(snapshot_path: &Path, actual: &String) -> Option<String> {
+    //this returns what is different from the checked in snapshot, running the tests with UPDATE_SNAPSHOTS=1 makes what we get now the new snapshot
+    if env::var("UPDATE_SNAPSHOTS").is_ok() {
+        fs::write(snapshot_path, actual).unwrap();
+        return None;
+    }
+
+    let expected = fs::read_to_string(snapshot_path);
+    if expected.is_err() {
+        return Some(format!("{:?} does not exist yet, run the tests with UPDATE_SNAPSHOTS=1 to create it", snapshot_path));
+    }
+    let expected = expected.unwrap().replace("\r\n", "\n"); //git might have checked out the snapshot with windows line endings
+    if expected == *actual {
+        return None;
+    }
+
+    //we show the first lines that are different, with a few lines before them, which is usually enough to see which node it is
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let mut first_difference = 0;
+    while first_difference < expected_lines.len() && first_difference < actual_lines.len() && expected_lines[first_difference] == actual_lines[first_difference] {
+        first_difference += 1;
+    }
+
+    let mut difference = format!("the output is different from {:?}, starting at line {}:\n", snapshot_path, first_difference + 1);
+    for line in &expected_lines[first_difference.saturating_sub(SNAPSHOT_CONTEXT_LINES)..first_difference] {
+        difference += format!("  {}\n", line).as_str();
+    }
+    for line in expected_lines.iter().skip(first_difference).take(SNAPSHOT_CONTEXT_LINES) {
+        difference += format!("- {}\n", line).as_str();
+    }
+    for line in actual_lines.iter().skip(first_difference).take(SNAPSHOT_CONTEXT_LINES) {
+        difference += format!("+ {}\n", line).as_str();
+    }
+    return Some(difference);
+}
 
 
 pub fn tokens_equal_ignoring_location(actual_tokens: Vec<HtmlTokenWithLocation>, expected_tokens: Vec<HtmlTokenWithLocation>) -> bool {
@@ -62,6 +101,12 @@ pub fn html_comment_loc(text: &str, line_nr: u32, character_nr: u32) -> HtmlToke
 pub fn html_comment(text: &str) -> HtmlTokenWithLocation { return html_comment_loc(text, 0, 0); }
 
 
+pub fn html_entity_loc(text: &str, line_nr: u32, character_nr: u32) -> HtmlTokenWithLocation {
+    return HtmlTokenWithLocation { html_token: HtmlToken::Entity(text.to_owned()), line: line_nr, character: character_nr };
+}
+pub fn html_entity(text: &str) -> HtmlTokenWithLocation { return html_entity_loc(text, 0, 0); }
+
+
 pub fn html_attribute_loc(name: &str, value: &str, line_nr: u32, character_nr: u32) -> HtmlTokenWithLocation {
     return HtmlTokenWithLocation { html_token: HtmlToken::Attribute(AttributeContent { name: name.to_owned(), value: value.to_owned() }),
                                    line: line_nr, character: character_nr };
@@ -73,3 +118,9 @@ pub fn html_script_loc(value: &str, line_nr: u32, character_nr: u32) -> HtmlToke
     return HtmlTokenWithLocation { html_token: HtmlToken::Script(value.to_owned()), line: line_nr, character: character_nr };
 }
 pub fn html_script(value: &str) -> HtmlTokenWithLocation { return html_script_loc(value, 0, 0); }
+
+
+pub fn html_style_loc(value: &str, line_nr: u32, character_nr: u32) -> HtmlTokenWithLocation {
+    return HtmlTokenWithLocation { html_token: HtmlToken::Style(value.to_owned()), line: line_nr, character: character_nr };
+}
+pub fn html_style(value: &str) -> HtmlTokenWithLocation { return html_style_loc(value, 0, 0); }
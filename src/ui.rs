@@ -1,45 +1,156 @@
-use std::cell::RefCell;
-use std::ops::DerefMut;
+use std::cell::{Cell, RefCell};
+use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use sdl2::{
+    event::{Event as SdlEvent, WindowEvent},
+    keyboard::Keycode,
+    mouse::MouseButton,
+    EventPump,
+    Sdl,
+};
 
-use crate::dom::Document;
-use crate::layout::LayoutNode;
-use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::address_bar;
+use crate::browsing_history::BROWSING_HISTORY;
+use crate::dom::{Document, DomNodeId};
+use crate::layout::Rect;
+use crate::{frame_time_check, SCREEN_HEIGHT, SCREEN_WIDTH, TARGET_MS_PER_FRAME};
 use crate::color::Color;
+use crate::debug::debug_log_warn;
+use crate::display_list::DisplayList;
 use crate::network::url::Url;
+use crate::page::Page;
+use crate::profiler::{FramePhase, PROFILER};
 use crate::platform::{
+    self,
+    fonts::{Font, FontContext},
     KeyCode,
     Platform,
     Position
 };
+use crate::resource_loader::ResourceQueueStats;
+use crate::script::js_interpreter::JsDialog;
+use crate::style::media_query::ColorScheme;
 use crate::ui_components::{
+    ContextMenu,
+    ContextMenuAction,
+    ContextMenuItem,
+    CredentialsDialog,
+    Dialog,
+    DialogResult,
+    DIALOG_WIDTH,
+    ModalDialog,
     NavigationButton,
     PageComponent,
+    ReloadButton,
     Scrollbar,
+    SuggestionList,
     TextField
 };
 
 
-pub const CONTENT_HEIGHT: f32 = SCREEN_HEIGHT - HEADER_HEIGHT;
-pub const CONTENT_WIDTH: f32 = SCREEN_WIDTH - MAIN_SCROLLBAR_WIDTH;
 pub const CONTENT_TOP_LEFT_X: f32 = 0.0;
 pub const CONTENT_TOP_LEFT_Y: f32 = HEADER_HEIGHT;
 
 pub const HEADER_HEIGHT: f32 = 50.0;
-
-pub const UI_BASIC_COLOR: Color = Color::new(212, 208, 200);
-pub const UI_BASIC_DARKER_COLOR: Color = Color::new(116, 107, 90);
+const PROGRESS_BAR_HEIGHT: f32 = 3.0;
+
+pub const STATUS_BAR_HEIGHT: f32 = 22.0;
+const STATUS_BAR_TEXT_MARGIN: f32 = 8.0;
+const MINIMUM_LOADING_PROGRESS: f32 = 0.05; //the bar starts with a bit of progress, to show right away that something is happening
+const PROFILER_GRAPH_BAR_WIDTH: f32 = 2.0;
+const PROFILER_GRAPH_HEIGHT: f32 = 100.0; //this is twice the target frame time, so the target is halfway
+const PROFILER_OVERLAY_MARGIN: f32 = 10.0;
+const TOOLTIP_MARGIN: f32 = 4.0;
+const TOOLTIP_MOUSE_OFFSET: f32 = 16.0; //the tooltip is shown below the mouse pointer, so the pointer does not cover it
+
+pub const LIGHT_UI_COLORS: UiColors = UiColors {
+    background: Color::WHITE,
+    foreground: Color::BLACK,
+    disabled_foreground: Color::GRAY,
+    basic: Color::new(212, 208, 200),
+    basic_darker: Color::new(116, 107, 90),
+    selection: Color::DEFAULT_SELECTION_COLOR,
+    focus_ring: Color::new(0, 95, 204),
+    progress_bar: Color::new(26, 115, 232),
+};
+pub const DARK_UI_COLORS: UiColors = UiColors {
+    background: Color::new(32, 33, 36),
+    foreground: Color::new(232, 234, 237),
+    disabled_foreground: Color::GRAY,
+    basic: Color::new(60, 62, 66),
+    basic_darker: Color::new(154, 160, 166),
+    selection: Color::new(38, 79, 120),
+    focus_ring: Color::new(138, 180, 248),
+    progress_bar: Color::new(138, 180, 248),
+};
 
 pub const MAIN_SCROLLBAR_WIDTH: f32 = 20.0;
-pub const MAIN_SCROLLBAR_HEIGHT: f32 = SCREEN_HEIGHT - HEADER_HEIGHT;
-pub const MAIN_SCROLLBAR_X_POS: f32 = SCREEN_WIDTH - MAIN_SCROLLBAR_WIDTH;
+const ADDRESSBAR_RIGHT_MARGIN: f32 = 100.0; //the address bar takes the width of the window, except for this space right of it
+
+
+static NEXT_HISTORY_ENTRY_ID: AtomicUsize = AtomicUsize::new(1);
+pub fn get_next_history_entry_id() -> usize { NEXT_HISTORY_ENTRY_ID.fetch_add(1, Ordering::Relaxed) }
+
+
+pub struct UiColors {
+    pub background: Color, //of the header
+    pub foreground: Color, //for text, lines and the borders of fields and buttons
+    pub disabled_foreground: Color,
+    pub basic: Color, //for larger areas, like dialogs and the scrollbar
+    pub basic_darker: Color,
+    pub selection: Color,
+    pub focus_ring: Color, //around the link or form field that has the keyboard focus
+    pub progress_bar: Color,
+}
+impl UiColors {
+    pub fn for_color_scheme(color_scheme: ColorScheme) -> &'static UiColors {
+        return match color_scheme {
+            ColorScheme::Light => &LIGHT_UI_COLORS,
+            ColorScheme::Dark => &DARK_UI_COLORS,
+        };
+    }
+}
+
+
+pub struct HistoryEntry {
+    pub id: usize, //the url does not identify an entry, since the same url can be in the history more than once
+    pub url: Url,
+}
 
 
 pub struct History {
-    pub list: Vec<Url>,
+    pub list: Vec<HistoryEntry>,
     pub position: usize,
     pub currently_navigating_from_history: bool,
+    pub shown_entry_id: Option<usize>, //the entry of the page on screen, which is not the current entry while the page for that is loading
+}
+impl History {
+    pub fn current_entry_id(&self) -> Option<usize> {
+        return self.list.get(self.position).map(|entry| entry.id);
+    }
+
+    pub fn go_back(&mut self) -> Option<Url> {
+        if self.position == 0 {
+            debug_log_warn("going back should not have been possible");
+            return None;
+        }
+        self.currently_navigating_from_history = true;
+        self.position -= 1;
+        return Some(self.list[self.position].url.clone());
+    }
+
+    pub fn go_forward(&mut self) -> Option<Url> {
+        if self.list.len() <= self.position + 1 {
+            debug_log_warn("going forward should not have been possible");
+            return None;
+        }
+        self.currently_navigating_from_history = true;
+        self.position += 1;
+        return Some(self.list[self.position].url.clone());
+    }
 }
 
 #[cfg_attr(debug_assertions, derive(Debug))]
@@ -49,26 +160,84 @@ pub enum FocusTarget {
     AddressBar,
     ScrollBlock, //TODO: eventually we could have more scrollbars, so maybe make scrollbars page components
     Component(Rc<RefCell<PageComponent>>),
+    Link(DomNodeId), //links can only get focus with the keyboard, clicking them follows them right away
+}
+
+pub struct Tooltip {
+    pub text: String,
+    pub x: f32, //the position of the mouse in the window, the tooltip is shown next to it
+    pub y: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct WindowDimensions {
+    pub width: f32,
+    pub height: f32,
+}
+impl WindowDimensions {
+    pub fn new_default() -> WindowDimensions {
+        //the size the window opens with
+        return WindowDimensions { width: SCREEN_WIDTH, height: SCREEN_HEIGHT };
+    }
+    pub fn content_width(&self) -> f32 {
+        //the page is shown below the header and above the status bar, with the main scrollbar right of it
+        return self.width - MAIN_SCROLLBAR_WIDTH;
+    }
+    pub fn content_height(&self) -> f32 {
+        return self.height - HEADER_HEIGHT - STATUS_BAR_HEIGHT;
+    }
+    pub fn status_bar_y(&self) -> f32 {
+        return self.height - STATUS_BAR_HEIGHT;
+    }
 }
 
 pub struct UIState {
+    pub window_dimensions: WindowDimensions,
     pub addressbar: TextField,
-    pub current_scroll_y: f32,
     pub back_button: NavigationButton,
     pub forward_button: NavigationButton,
+    pub reload_button: ReloadButton,
     pub history: History,
-    pub currently_loading_page: bool,
+    pub currently_loading_page: bool, //this is only the page itself, the loading progress also includes what the page needs
+    pub loading_progress: Option<f32>, //from 0 to 1, and set while the page or anything it needs (like images and styles) is loading
     pub animation_tick: u32,
     pub focus_target: FocusTarget,
+    pub focus_visible: bool, //the focus ring is only shown when the focus was moved with the keyboard, as in other browsers
     pub main_scrollbar: Scrollbar, //TODO: eventually this should become a dynamic page component in the list, because there might be more than 1 scrollbar
+    pub context_menu: Option<ContextMenu>,
+    pub address_bar_suggestions: SuggestionList,
+    pub bookmarks: Vec<Url>,
+    pub hovered_link: Option<Url>, //the link under the mouse, the status bar shows where it goes
+    pub resource_counts: (usize, usize), //the finished and the scheduled loading jobs, since the page started loading
+    pub nr_of_script_errors: usize,
+    pub layout_overlay: bool, //outlines all layout nodes, with a tooltip about the one under the mouse, to find out what goes wrong in layout
+    pub layout_overlay_tooltip: Option<Tooltip>,
+    pub profiler_overlay: bool, //shows a graph of how long the last frames took, and on what
 }
 
 
 pub fn render_ui(platform: &mut Platform, ui_state: &mut UIState) {
     update_animation_state(ui_state);
-    render_header(platform, ui_state);
 
-    ui_state.main_scrollbar.render(platform);
+    //the ui is not scrolled, so its display list is painted as is
+    let ui_colors = UiColors::for_color_scheme(platform.color_scheme.get());
+    let mut display_list = DisplayList::new();
+    display_list.push_clip(Rect { x: 0.0, y: 0.0, width: ui_state.window_dimensions.width, height: HEADER_HEIGHT });
+    paint_header(&mut display_list, ui_state, ui_colors);
+    display_list.pop();
+    paint_status_bar(&mut display_list, ui_state, ui_colors, &platform.font_context);
+    ui_state.address_bar_suggestions.render(&mut display_list, ui_colors);
+    ui_state.main_scrollbar.render(&mut display_list, ui_colors);
+    if ui_state.context_menu.is_some() {
+        ui_state.context_menu.as_ref().unwrap().render(&mut display_list, ui_colors);
+    }
+    if ui_state.profiler_overlay {
+        paint_profiler_overlay(&mut display_list, ui_state.window_dimensions, ui_colors, &platform.font_context);
+    }
+    if ui_state.layout_overlay_tooltip.is_some() {
+        paint_tooltip(&mut display_list, ui_state.layout_overlay_tooltip.as_ref().unwrap(), ui_state.window_dimensions, ui_colors, &platform.font_context);
+    }
+    display_list.replay(platform, 0.0);
 }
 
 
@@ -79,7 +248,7 @@ pub fn register_in_history(ui_state: &mut UIState, url: &Url) {
             ui_state.history.list.remove(idx);
         }
     }
-    ui_state.history.list.push(url.clone());
+    ui_state.history.list.push(HistoryEntry { id: get_next_history_entry_id(), url: url.clone() });
     ui_state.history.position = ui_state.history.list.len() - 1;
     if ui_state.history.position > 0 {
         ui_state.back_button.enabled = true;
@@ -87,6 +256,47 @@ pub fn register_in_history(ui_state: &mut UIState, url: &Url) {
 }
 
 
+pub fn update_loading_progress(ui_state: &mut UIState, queue_stats: &ResourceQueueStats) {
+    //all jobs are cancelled when a page starts loading, so the jobs counted since then are the page itself and what it needs
+    //TODO: we don't know how large resources are before they are loaded, so each of them counts the same
+    ui_state.resource_counts = (queue_stats.finished_jobs, queue_stats.scheduled_jobs);
+    if !ui_state.currently_loading_page && queue_stats.finished_jobs >= queue_stats.scheduled_jobs {
+        ui_state.loading_progress = None;
+        return;
+    }
+
+    let loaded_fraction = if queue_stats.scheduled_jobs == 0 { 0.0 } else { queue_stats.finished_jobs as f32 / queue_stats.scheduled_jobs as f32 };
+    //resources found while loading make the fraction smaller again, but the bar should not go back
+    let previous_progress = ui_state.loading_progress.unwrap_or(0.0);
+    ui_state.loading_progress = Some(loaded_fraction.max(previous_progress).max(MINIMUM_LOADING_PROGRESS));
+}
+
+
+pub fn addressbar_width(window_dimensions: WindowDimensions, addressbar_x: f32) -> f32 {
+    return window_dimensions.width - addressbar_x - ADDRESSBAR_RIGHT_MARGIN;
+}
+
+
+pub fn handle_window_resize(ui_state: &mut UIState, new_width: f32, new_height: f32) {
+    //the ui around the page follows the size of the window (the page itself is laid out again by the caller)
+    ui_state.window_dimensions = WindowDimensions { width: new_width, height: new_height };
+    let window_dimensions = ui_state.window_dimensions;
+
+    let addressbar = &mut ui_state.addressbar;
+    let addressbar_width = addressbar_width(window_dimensions, addressbar.x);
+    addressbar.update_position(addressbar.x, addressbar.y, addressbar_width, addressbar.height);
+    ui_state.address_bar_suggestions.width = addressbar_width;
+
+    //the scroll block is sized again for the new height when the page updates its scroll position
+    ui_state.main_scrollbar.x = window_dimensions.content_width();
+    ui_state.main_scrollbar.height = window_dimensions.content_height();
+    ui_state.main_scrollbar.content_visible_height = window_dimensions.content_height();
+
+    //the context menu was placed for the old size, so it might not fit anymore
+    ui_state.context_menu = None;
+}
+
+
 pub fn update_history_buttons(ui_state: &mut UIState) {
     ui_state.forward_button.enabled = ui_state.history.list.len() > ui_state.history.position + 1;
     ui_state.back_button.enabled = ui_state.history.position > 0;
@@ -98,17 +308,34 @@ pub fn handle_keyboard_input(platform: &mut Platform, input: Option<&String>, ke
     match &ui_state.focus_target {
         FocusTarget::None => {},
         FocusTarget::MainContent => {},
+        FocusTarget::Link(_) => {},
         FocusTarget::AddressBar => {
-            ui_state.addressbar.handle_keyboard_input(platform, input, key_code);
+            match key_code {
+                Some(KeyCode::DOWN) => { ui_state.address_bar_suggestions.select_next(); },
+                Some(KeyCode::UP) => { ui_state.address_bar_suggestions.select_previous(); },
+                _ => {
+                    let previous_text = ui_state.addressbar.text.clone();
+                    ui_state.addressbar.handle_keyboard_input(&platform.font_context, input, key_code);
+                    if ui_state.addressbar.text != previous_text {
+                        update_address_bar_suggestions(ui_state);
+                    }
+                },
+            }
         },
         FocusTarget::ScrollBlock => {},
         FocusTarget::Component(component) => {
             match component.borrow_mut().deref_mut() {
                 PageComponent::Button(_) => {
-                    //TODO: handle enter here
+                    //enter activates the button, which is handled in the main loop, since that can navigate
                 }
                 PageComponent::TextField(text_field) => {
-                    text_field.handle_keyboard_input(platform, input, key_code);
+                    text_field.handle_keyboard_input(&platform.font_context, input, key_code);
+                },
+                PageComponent::TextArea(text_area) => {
+                    text_area.handle_keyboard_input(platform, input, key_code);
+                },
+                PageComponent::FileInput(_) => {
+                    //enter opens the file picker, which is handled in the main loop, as for buttons
                 },
             }
         },
@@ -116,6 +343,12 @@ pub fn handle_keyboard_input(platform: &mut Platform, input: Option<&String>, ke
 }
 
 
+fn update_address_bar_suggestions(ui_state: &mut UIState) {
+    let suggestions = address_bar::find_suggestions(&ui_state.addressbar.text, &BROWSING_HISTORY.lock().unwrap(), &ui_state.bookmarks, SystemTime::now());
+    ui_state.address_bar_suggestions.set_suggestions(suggestions);
+}
+
+
 pub fn handle_possible_ui_click(ui_state: &mut UIState, x: f32, y: f32) -> Option<Url> {
     let possible_url = ui_state.back_button.click(x, y, &mut ui_state.history);
     if possible_url.is_some() {
@@ -129,8 +362,39 @@ pub fn handle_possible_ui_click(ui_state: &mut UIState, x: f32, y: f32) -> Optio
     return None;
 }
 
-pub fn handle_possible_ui_mouse_down(root_layout_node: &Rc<RefCell<LayoutNode>>, document: &RefCell<Document>, platform: &mut Platform, ui_state: &mut UIState, x: f32, y: f32) -> Option<Url> {
+pub fn open_context_menu(page: &Page, ui_state: &mut UIState, x: f32, y: f32) {
+    let target = page.context_menu_target(x, y);
+
+    let mut items = vec![
+        ContextMenuItem { action: ContextMenuAction::Back, enabled: ui_state.back_button.enabled },
+        ContextMenuItem { action: ContextMenuAction::Forward, enabled: ui_state.forward_button.enabled },
+        ContextMenuItem { action: ContextMenuAction::Reload, enabled: true },
+    ];
+
+    //the items about what is under the mouse (or selected) are only shown when there is something like that
+    if !page.selected_text().is_empty() {
+        items.push(ContextMenuItem { action: ContextMenuAction::Copy, enabled: true });
+    }
+    if target.link_url.is_some() {
+        //TODO: enable this when we have tabs
+        items.push(ContextMenuItem { action: ContextMenuAction::OpenLinkInNewTab, enabled: false });
+        items.push(ContextMenuItem { action: ContextMenuAction::CopyLinkAddress, enabled: true });
+    }
+    if target.image.is_some() {
+        items.push(ContextMenuItem { action: ContextMenuAction::SaveImageAs, enabled: true });
+    }
+    items.push(ContextMenuItem { action: ContextMenuAction::ViewPageSource, enabled: true });
+    if target.dom_node.is_some() {
+        items.push(ContextMenuItem { action: ContextMenuAction::InspectElement, enabled: true });
+    }
+
+    ui_state.context_menu = Some(ContextMenu::new(x, y, items, target, ui_state.window_dimensions));
+}
+
+
+pub fn handle_possible_ui_mouse_down(page: &Page, platform: &mut Platform, ui_state: &mut UIState, x: f32, y: f32) -> Option<Url> {
     let mut any_text_field_has_focus = false;
+    ui_state.focus_visible = false;
 
     if ui_state.addressbar.is_inside(x, y) {
         ui_state.focus_target = FocusTarget::AddressBar;
@@ -142,10 +406,21 @@ pub fn handle_possible_ui_mouse_down(root_layout_node: &Rc<RefCell<LayoutNode>>,
 
         let mut component_found = false;
 
-        let possible_dom_node = root_layout_node.borrow().find_dom_node_at_position(x, y + ui_state.current_scroll_y);
+        let document = page.document.borrow();
+        let mut possible_dom_node = page.find_dom_node_at_position(x, y);
+        let (mut x, mut y) = (x, y);
+
+        if possible_dom_node.is_some() && document.nodes[possible_dom_node.unwrap()].page_component.is_none() {
+            //clicking on a label focusses its control, as if we clicked at the end of the control
+            let possible_control = document.find_control_for_label(possible_dom_node.unwrap());
+            if possible_control.is_some() {
+                (x, y) = document.nodes[possible_control.unwrap()].page_component.as_ref().unwrap().borrow().get_end_position();
+                possible_dom_node = possible_control;
+            }
+        }
+
         if possible_dom_node.is_some() {
-            let dom_node = possible_dom_node.unwrap();
-            let borr_dom_node = dom_node.borrow();
+            let borr_dom_node = &document.nodes[possible_dom_node.unwrap()];
             if borr_dom_node.page_component.is_some() {
                 let rc_component_clone = borr_dom_node.page_component.as_ref().unwrap().clone();
 
@@ -156,10 +431,23 @@ pub fn handle_possible_ui_mouse_down(root_layout_node: &Rc<RefCell<LayoutNode>>,
                         component_found = true;
                     },
                     PageComponent::TextField(text_field) => {
+                        if !text_field.disabled {
+                            ui_state.focus_target = FocusTarget::Component(rc_component_clone);
+                            component_found = true;
+                            any_text_field_has_focus = text_field.is_editable();
+                            text_field.mouse_down(x, y);
+                        }
+                    },
+                    PageComponent::TextArea(text_area) => {
                         ui_state.focus_target = FocusTarget::Component(rc_component_clone);
                         component_found = true;
                         any_text_field_has_focus = true;
-                        text_field.mouse_down(x, y);
+                        text_area.mouse_down(x, y);
+                    },
+                    PageComponent::FileInput(file_input) => {
+                        ui_state.focus_target = FocusTarget::Component(rc_component_clone);
+                        file_input.has_focus = true;
+                        component_found = true;
                     },
                 }
             }
@@ -178,12 +466,245 @@ pub fn handle_possible_ui_mouse_down(root_layout_node: &Rc<RefCell<LayoutNode>>,
         platform.disable_text_input();
     }
 
-    clear_other_focus(ui_state, document);
+    clear_other_focus(ui_state, &page.document);
 
     return None;
 }
 
 
+pub fn move_focus(page: &mut Page, platform: &mut Platform, ui_state: &mut UIState, backwards: bool) {
+    //tab goes through the links and form fields in the order they are on the page, and starts at the other end again after the last one
+    let focus_order = page.focus_order();
+    if focus_order.is_empty() {
+        return;
+    }
+
+    let focused_dom_node = focused_dom_node(ui_state, &page.document.borrow());
+    let current_idx = focus_order.iter().position(|dom_node_id| focused_dom_node.is_some() && *dom_node_id == focused_dom_node.unwrap());
+    let next_idx = match current_idx {
+        Some(idx) => if backwards { (idx + focus_order.len() - 1) % focus_order.len() } else { (idx + 1) % focus_order.len() },
+        None => if backwards { focus_order.len() - 1 } else { 0 },
+    };
+    let next_dom_node_id = focus_order[next_idx];
+    let next_page_component = page.document.borrow().nodes[next_dom_node_id].page_component.clone();
+
+    let mut any_text_field_has_focus = false;
+    if next_page_component.is_some() {
+        let component = next_page_component.unwrap();
+        match component.borrow_mut().deref_mut() {
+            PageComponent::Button(button) => { button.has_focus = true; },
+            PageComponent::TextField(text_field) => {
+                text_field.has_focus = true;
+                any_text_field_has_focus = text_field.is_editable();
+            },
+            PageComponent::TextArea(text_area) => {
+                text_area.has_focus = true;
+                any_text_field_has_focus = true;
+            },
+            PageComponent::FileInput(file_input) => { file_input.has_focus = true; },
+        }
+        ui_state.focus_target = FocusTarget::Component(component);
+    } else {
+        ui_state.focus_target = FocusTarget::Link(next_dom_node_id);
+    }
+    ui_state.focus_visible = true;
+
+    if any_text_field_has_focus {
+        platform.enable_text_input();
+    } else {
+        platform.disable_text_input();
+    }
+    clear_other_focus(ui_state, &page.document);
+    page.scroll_into_view(next_dom_node_id, &ui_state.main_scrollbar);
+}
+
+
+pub fn focus_address_bar(page: &Page, platform: &mut Platform, ui_state: &mut UIState) {
+    //as when clicking in it, the whole address is selected, so typing replaces it
+    ui_state.focus_target = FocusTarget::AddressBar;
+    ui_state.focus_visible = false;
+    ui_state.addressbar.has_focus = true;
+    ui_state.addressbar.select_all();
+    platform.enable_text_input();
+    clear_other_focus(ui_state, &page.document);
+}
+
+
+pub fn select_all(page: &Page, ui_state: &mut UIState) {
+    //this selects the text of the focused text field, or the whole page when no text field has focus
+    match &ui_state.focus_target {
+        FocusTarget::AddressBar => {
+            ui_state.addressbar.select_all();
+            return;
+        },
+        FocusTarget::Component(component) => {
+            match component.borrow_mut().deref_mut() {
+                PageComponent::TextField(text_field) => {
+                    text_field.select_all();
+                    return;
+                },
+                PageComponent::TextArea(_) => {
+                    return; //TODO: selecting text inside a text area is not supported yet
+                },
+                PageComponent::Button(_) | PageComponent::FileInput(_) => {},
+            }
+        },
+        FocusTarget::None | FocusTarget::MainContent | FocusTarget::ScrollBlock | FocusTarget::Link(_) => {},
+    }
+
+    let mut full_layout = page.full_layout.borrow_mut();
+    full_layout.reset_selection();
+    let root_node = full_layout.root_node;
+    full_layout.select_all(root_node);
+}
+
+
+pub fn selected_text_in_text_field(ui_state: &UIState) -> String {
+    return match &ui_state.focus_target {
+        FocusTarget::AddressBar if ui_state.addressbar.has_selection_active() => ui_state.addressbar.get_selected_text(),
+        FocusTarget::Component(component) => {
+            match component.borrow().deref() {
+                PageComponent::TextField(text_field) if text_field.has_selection_active() => text_field.get_selected_text(),
+                _ => String::new(),
+            }
+        },
+        _ => String::new(),
+    };
+}
+
+
+pub fn cut_selected_text(platform: &Platform, ui_state: &mut UIState) -> String {
+    //only text in text fields can be cut, this returns the text that was cut, for the clipboard
+    return match &ui_state.focus_target {
+        FocusTarget::AddressBar => {
+            let cut_text = ui_state.addressbar.cut_selected_text(&platform.font_context);
+            update_address_bar_suggestions(ui_state);
+            cut_text
+        },
+        FocusTarget::Component(component) => {
+            match component.borrow_mut().deref_mut() {
+                PageComponent::TextField(text_field) => text_field.cut_selected_text(&platform.font_context),
+                _ => String::new(),
+            }
+        },
+        _ => String::new(),
+    };
+}
+
+
+pub fn paste_text(platform: &Platform, ui_state: &mut UIState, text: &String) {
+    //the pasted text goes to the focused field, on forms the text of the field is what is submitted, so that includes the pasted text
+    match &ui_state.focus_target {
+        FocusTarget::AddressBar => {
+            ui_state.addressbar.paste_text(&platform.font_context, text);
+            update_address_bar_suggestions(ui_state);
+        },
+        FocusTarget::Component(component) => {
+            match component.borrow_mut().deref_mut() {
+                PageComponent::TextField(text_field) => {
+                    text_field.paste_text(&platform.font_context, text);
+                },
+                PageComponent::TextArea(text_area) => {
+                    text_area.insert_text(platform, &text.replace("\r\n", "\n"));
+                },
+                PageComponent::Button(_) | PageComponent::FileInput(_) => {},
+            }
+        },
+        FocusTarget::None | FocusTarget::MainContent | FocusTarget::ScrollBlock | FocusTarget::Link(_) => {},
+    }
+}
+
+
+pub fn drop_text(page: &Page, platform: &mut Platform, ui_state: &mut UIState, x: f32, y: f32, text: &String) {
+    //text dropped on a text field goes in where it is dropped, as if the field was clicked there first, and dropping it anywhere else does nothing
+    let possible_dom_node = page.find_dom_node_at_position(x, y);
+    let document = page.document.borrow();
+    if possible_dom_node.is_none() || document.nodes[possible_dom_node.unwrap()].page_component.is_none() {
+        return;
+    }
+
+    let is_on_text_field = match document.nodes[possible_dom_node.unwrap()].page_component.as_ref().unwrap().borrow().deref() {
+        PageComponent::TextField(_) | PageComponent::TextArea(_) => true,
+        PageComponent::Button(_) | PageComponent::FileInput(_) => false,
+    };
+    drop(document);
+    if is_on_text_field {
+        handle_possible_ui_mouse_down(page, platform, ui_state, x, y);
+        paste_text(platform, ui_state, text);
+    }
+}
+
+
+pub fn move_cursor_by_word(ui_state: &mut UIState, forward: bool) -> bool {
+    //this returns false when no text field has focus, so the key can be used for something else
+    return match &ui_state.focus_target {
+        FocusTarget::AddressBar => {
+            ui_state.addressbar.move_cursor_by_word(forward);
+            true
+        },
+        FocusTarget::Component(component) => {
+            match component.borrow_mut().deref_mut() {
+                PageComponent::TextField(text_field) => {
+                    text_field.move_cursor_by_word(forward);
+                    true
+                },
+                _ => false,
+            }
+        },
+        _ => false,
+    };
+}
+
+
+pub fn extend_selection(page: &Page, ui_state: &mut UIState, key_code: KeyCode) -> bool {
+    //this returns false when there is no selection to extend, so the key can be used for something else (like scrolling the page)
+    return match &ui_state.focus_target {
+        FocusTarget::AddressBar => {
+            ui_state.addressbar.extend_selection(key_code);
+            true
+        },
+        FocusTarget::MainContent => page.full_layout.borrow_mut().extend_selection(key_code),
+        FocusTarget::Component(component) => {
+            match component.borrow_mut().deref_mut() {
+                PageComponent::TextField(text_field) => {
+                    text_field.extend_selection(key_code);
+                    true
+                },
+                _ => false, //TODO: selecting text inside a text area is not supported yet
+            }
+        },
+        FocusTarget::None | FocusTarget::ScrollBlock | FocusTarget::Link(_) => false,
+    };
+}
+
+
+pub fn focused_dom_node(ui_state: &UIState, document: &Document) -> Option<DomNodeId> {
+    //scripts might have removed the focused element from the page, so unlike Document::find_node_for_component, this does not assume we find it
+    return match &ui_state.focus_target {
+        FocusTarget::Link(link) => Some(*link).filter(|link| document.nodes.contains(*link)),
+        FocusTarget::Component(component) => {
+            let component_id = component.borrow().get_id();
+            document.nodes.iter().find(|(_, dom_node)| {
+                return dom_node.page_component.is_some() && dom_node.page_component.as_ref().unwrap().borrow().get_id() == component_id;
+            }).map(|(dom_node_id, _)| dom_node_id)
+        },
+        FocusTarget::None | FocusTarget::MainContent | FocusTarget::AddressBar | FocusTarget::ScrollBlock => None,
+    };
+}
+
+
+pub fn clear_page_focus(ui_state: &mut UIState) {
+    //when another page is shown, what had focus on the previous one can't have it anymore
+    match ui_state.focus_target {
+        FocusTarget::Component(_) | FocusTarget::Link(_) => {
+            ui_state.focus_target = FocusTarget::MainContent;
+            ui_state.focus_visible = false;
+        },
+        FocusTarget::None | FocusTarget::MainContent | FocusTarget::AddressBar | FocusTarget::ScrollBlock => {},
+    }
+}
+
+
 fn clear_other_focus(ui_state: &mut UIState, document: &RefCell<Document>) {
 
     let mut component_id_with_focus = None;
@@ -193,6 +714,7 @@ fn clear_other_focus(ui_state: &mut UIState, document: &RefCell<Document>) {
         FocusTarget::None => {},
         FocusTarget::MainContent => {},
         FocusTarget::ScrollBlock => {},
+        FocusTarget::Link(_) => {},
         FocusTarget::AddressBar => { addressbar_has_focus = true; },
         FocusTarget::Component(component) => {
             component_id_with_focus = Some(component.borrow().get_id())
@@ -202,10 +724,10 @@ fn clear_other_focus(ui_state: &mut UIState, document: &RefCell<Document>) {
     if !addressbar_has_focus {
         ui_state.addressbar.has_focus = false;
         ui_state.addressbar.clear_selection();
+        ui_state.address_bar_suggestions.clear();
     }
 
-    for node in document.borrow().all_nodes.values() {
-        let node_borr = node.borrow();
+    for (_, node_borr) in document.borrow().nodes.iter() {
         if node_borr.page_component.is_some() {
             if component_id_with_focus.is_none() || node_borr.page_component.as_ref().unwrap().borrow().get_id() != component_id_with_focus.unwrap() {
                 match node_borr.page_component.as_ref().unwrap().borrow_mut().deref_mut() {
@@ -216,6 +738,12 @@ fn clear_other_focus(ui_state: &mut UIState, document: &RefCell<Document>) {
                         text_field.has_focus = false;
                         text_field.clear_selection();
                     },
+                    PageComponent::TextArea(text_area) => {
+                        text_area.has_focus = false;
+                    },
+                    PageComponent::FileInput(file_input) => {
+                        file_input.has_focus = false;
+                    },
                 }
             }
         }
@@ -224,24 +752,126 @@ fn clear_other_focus(ui_state: &mut UIState, document: &RefCell<Document>) {
 }
 
 
-fn render_header(platform: &mut Platform, ui_state: &UIState) {
-    platform.fill_rect(0.0, 0.0, SCREEN_WIDTH, HEADER_HEIGHT, Color::WHITE, 255);
+fn paint_header(display_list: &mut DisplayList, ui_state: &UIState, ui_colors: &UiColors) {
+    let window_width = ui_state.window_dimensions.width;
+    display_list.fill_rect(0.0, 0.0, window_width, HEADER_HEIGHT, ui_colors.background);
 
-    platform.draw_line(Position { x: 0.0, y: HEADER_HEIGHT - 1.0 },
-                       Position { x: SCREEN_WIDTH, y: HEADER_HEIGHT - 1.0 },
-                       Color::BLACK);
+    display_list.draw_line(Position { x: 0.0, y: HEADER_HEIGHT - 1.0 },
+                           Position { x: window_width, y: HEADER_HEIGHT - 1.0 },
+                           ui_colors.foreground);
 
-    if ui_state.currently_loading_page {
-        render_spinner(platform, ui_state);
+    if ui_state.loading_progress.is_some() {
+        paint_spinner(display_list, ui_state, ui_colors);
     }
 
-    ui_state.back_button.render(platform);
-    ui_state.forward_button.render(platform);
-    ui_state.addressbar.render(&ui_state, platform, 0.0);
+    ui_state.back_button.render(display_list, ui_colors);
+    ui_state.forward_button.render(display_list, ui_colors);
+    ui_state.reload_button.render(display_list, ui_colors, ui_state.loading_progress.is_some());
+    ui_state.addressbar.render(ui_state.animation_tick, display_list, ui_colors);
+
+    if ui_state.loading_progress.is_some() {
+        let progress_bar_y = ui_state.addressbar.y + ui_state.addressbar.height + 1.0;
+        let progress_bar_width = ui_state.addressbar.width * ui_state.loading_progress.unwrap();
+        display_list.fill_rect(ui_state.addressbar.x, progress_bar_y, progress_bar_width, PROGRESS_BAR_HEIGHT, ui_colors.progress_bar);
+    }
 }
 
 
-fn render_spinner(platform: &mut Platform, ui_state: &UIState) {
+fn paint_status_bar(display_list: &mut DisplayList, ui_state: &UIState, ui_colors: &UiColors, font_context: &FontContext) {
+    let (window_width, status_bar_y) = (ui_state.window_dimensions.width, ui_state.window_dimensions.status_bar_y());
+    display_list.fill_rect(0.0, status_bar_y, window_width, STATUS_BAR_HEIGHT, ui_colors.background);
+    display_list.draw_line(Position { x: 0.0, y: status_bar_y }, Position { x: window_width, y: status_bar_y }, ui_colors.foreground);
+
+    let font = Font::default();
+    let (_, text_height) = font_context.get_text_dimension_str("X", &font);
+    let text_y = status_bar_y + (STATUS_BAR_HEIGHT - text_height) / 2.0;
+
+    //where the link under the mouse goes is the most useful to see, so it is shown instead of the loading status
+    let (finished_jobs, scheduled_jobs) = ui_state.resource_counts;
+    let status_text = if ui_state.hovered_link.is_some() {
+        ui_state.hovered_link.as_ref().unwrap().to_string()
+    } else if ui_state.currently_loading_page {
+        String::from("Waiting for the page...")
+    } else if ui_state.loading_progress.is_some() {
+        format!("Loaded {} of {} resources", finished_jobs, scheduled_jobs)
+    } else {
+        String::from("Done")
+    };
+    display_list.render_text(&status_text, STATUS_BAR_TEXT_MARGIN, text_y, &font, ui_colors.foreground);
+
+    if ui_state.nr_of_script_errors > 0 {
+        let errors_text = if ui_state.nr_of_script_errors == 1 { String::from("1 script error") } else { format!("{} script errors", ui_state.nr_of_script_errors) };
+        let (text_width, _) = font_context.get_text_dimension(&errors_text, &font);
+        display_list.render_text(&errors_text, window_width - text_width - STATUS_BAR_TEXT_MARGIN, text_y, &font, ui_colors.foreground);
+    }
+}
+
+
+fn paint_profiler_overlay(display_list: &mut DisplayList, window_dimensions: WindowDimensions, ui_colors: &UiColors, font_context: &FontContext) {
+    //each frame is a bar, with the time of each phase stacked on top of each other, and the rest of the frame (like waiting for the gpu) in gray
+    let profiler = PROFILER.lock().unwrap();
+    let font = Font::default();
+    let (_, line_height) = font_context.get_text_dimension_str("X", &font);
+    let legend_height = line_height * (FramePhase::ALL.len() + 1) as f32;
+
+    let graph_width = profiler.frames().len().max(1) as f32 * PROFILER_GRAPH_BAR_WIDTH;
+    let width = (graph_width + PROFILER_OVERLAY_MARGIN * 2.0).max(200.0);
+    let height = PROFILER_GRAPH_HEIGHT + legend_height + PROFILER_OVERLAY_MARGIN * 3.0;
+    let x = window_dimensions.content_width() - width - PROFILER_OVERLAY_MARGIN;
+    let y = window_dimensions.status_bar_y() - height - PROFILER_OVERLAY_MARGIN;
+    display_list.fill_rect(x, y, width, height, ui_colors.background);
+    display_list.draw_square(x, y, width, height, ui_colors.basic_darker);
+
+    let graph_x = x + PROFILER_OVERLAY_MARGIN;
+    let graph_bottom = y + PROFILER_OVERLAY_MARGIN + PROFILER_GRAPH_HEIGHT;
+    let pixels_per_millisecond = PROFILER_GRAPH_HEIGHT / (TARGET_MS_PER_FRAME as f32 * 2.0);
+    for (frame_idx, frame) in profiler.frames().iter().enumerate() {
+        let bar_x = graph_x + frame_idx as f32 * PROFILER_GRAPH_BAR_WIDTH;
+        let total_height = (frame.total.as_secs_f32() * 1000.0 * pixels_per_millisecond).min(PROFILER_GRAPH_HEIGHT);
+        display_list.fill_rect(bar_x, graph_bottom - total_height, PROFILER_GRAPH_BAR_WIDTH, total_height, ui_colors.basic);
+
+        let mut bar_top = graph_bottom;
+        for phase in FramePhase::ALL {
+            let phase_height = frame.phase_durations[phase as usize].as_secs_f32() * 1000.0 * pixels_per_millisecond;
+            let phase_height = phase_height.min(bar_top - (graph_bottom - PROFILER_GRAPH_HEIGHT));
+            bar_top -= phase_height;
+            display_list.fill_rect(bar_x, bar_top, PROFILER_GRAPH_BAR_WIDTH, phase_height, phase.color());
+        }
+    }
+    let target_y = graph_bottom - (TARGET_MS_PER_FRAME as f32 * pixels_per_millisecond);
+    display_list.draw_line(Position { x: graph_x, y: target_y }, Position { x: graph_x + graph_width, y: target_y }, ui_colors.foreground);
+
+    let average_frame = profiler.average_frame();
+    let mut text_y = graph_bottom + PROFILER_OVERLAY_MARGIN;
+    for phase in FramePhase::ALL {
+        display_list.fill_rect(graph_x, text_y + line_height / 4.0, line_height / 2.0, line_height / 2.0, phase.color());
+        let text = format!("{}: {:.1} ms", phase.name(), average_frame.phase_durations[phase as usize].as_secs_f32() * 1000.0);
+        display_list.render_text(&text, graph_x + line_height, text_y, &font, ui_colors.foreground);
+        text_y += line_height;
+    }
+    let total_text = format!("frame: {:.1} ms on average (target {} ms)", average_frame.total.as_secs_f32() * 1000.0, TARGET_MS_PER_FRAME);
+    display_list.render_text(&total_text, graph_x, text_y, &font, ui_colors.foreground);
+}
+
+
+fn paint_tooltip(display_list: &mut DisplayList, tooltip: &Tooltip, window_dimensions: WindowDimensions, ui_colors: &UiColors, font_context: &FontContext) {
+    let font = Font::default();
+    let (text_width, text_height) = font_context.get_text_dimension(&tooltip.text, &font);
+    let width = text_width + (TOOLTIP_MARGIN * 2.0);
+    let height = text_height + (TOOLTIP_MARGIN * 2.0);
+
+    //the tooltip stays inside the window, also for the mouse near the right side or the bottom
+    let x = tooltip.x.min(window_dimensions.width - width).max(0.0);
+    let y = if tooltip.y + TOOLTIP_MOUSE_OFFSET + height > window_dimensions.height { tooltip.y - height } else { tooltip.y + TOOLTIP_MOUSE_OFFSET };
+
+    display_list.fill_rect(x, y, width, height, ui_colors.basic);
+    display_list.draw_square(x, y, width, height, ui_colors.basic_darker);
+    display_list.render_text(&tooltip.text, x + TOOLTIP_MARGIN, y + TOOLTIP_MARGIN, &font, ui_colors.foreground);
+}
+
+
+fn paint_spinner(display_list: &mut DisplayList, ui_state: &UIState, ui_colors: &UiColors) {
+    //TODO: once we have tabs, each tab that is loading should get its own spinner
     let block_size = 5.0;
     let block_spacing = 15.0;
     let spinner_x_pos = ui_state.addressbar.x + ui_state.addressbar.width + 15.0;
@@ -250,19 +880,139 @@ fn render_spinner(platform: &mut Platform, ui_state: &UIState) {
     let number_of_blocks = (ui_state.animation_tick % 1000) / 250;
 
     if number_of_blocks > 0 {
-        platform.fill_rect(spinner_x_pos, spinner_y_pos, block_size, block_size, Color::BLACK, 255);
+        display_list.fill_rect(spinner_x_pos, spinner_y_pos, block_size, block_size, ui_colors.foreground);
     }
     if number_of_blocks > 1 {
-        platform.fill_rect(spinner_x_pos + block_spacing, spinner_y_pos, block_size, block_size, Color::BLACK, 255);
+        display_list.fill_rect(spinner_x_pos + block_spacing, spinner_y_pos, block_size, block_size, ui_colors.foreground);
     }
     if number_of_blocks > 2 {
-        platform.fill_rect(spinner_x_pos + (block_spacing * 2.0), spinner_y_pos, block_size, block_size, Color::BLACK, 255);
+        display_list.fill_rect(spinner_x_pos + (block_spacing * 2.0), spinner_y_pos, block_size, block_size, ui_colors.foreground);
     }
 }
 
 
 fn update_animation_state(ui_state: &mut UIState) {
+    ui_state.animation_tick = current_animation_tick();
+}
+
+
+fn current_animation_tick() -> u32 {
     let current_millis = SystemTime::now().duration_since(UNIX_EPOCH)
                             .expect("Time went backwards, please check if you entered a wormhole").as_millis();
-    ui_state.animation_tick = (current_millis % 10_000) as u32;
+    return (current_millis % 10_000) as u32;
+}
+
+
+pub fn make_dialog_handler(platform: &Platform) -> Box<dyn Fn(&JsDialog) -> Option<String>> {
+    //this is how scripts show alert, confirm and prompt dialogs, the main window does not respond while they are open
+    let sdl_context = platform.sdl_context.clone();
+    let font_context = Rc::clone(&platform.font_context);
+    let event_pump = Rc::clone(&platform.event_pump);
+    let color_scheme = Rc::clone(&platform.color_scheme);
+    return Box::new(move |js_dialog| {
+        let (message, has_cancel_button, text) = match js_dialog {
+            JsDialog::Alert(message) => (message, false, None),
+            JsDialog::Confirm(message) => (message, true, None),
+            JsDialog::Prompt(message, default_text) => (message, true, Some(default_text.clone())),
+        };
+        let dialog = ModalDialog::Message(Dialog::new(message, has_cancel_button, text, &font_context));
+
+        return match run_modal_dialog(&sdl_context, &font_context, &event_pump, &color_scheme, dialog) {
+            Some(DialogResult::Text(text)) => Some(text),
+            _ => None,
+        };
+    });
+}
+
+
+pub fn ask_for_credentials(platform: &Platform, url: &String, realm: Option<&String>) -> Option<(String, String)> {
+    //this is for pages that need (basic) authentication, the main window does not respond while the dialog is open
+    let mut message = format!("{} asks you to log in.", url);
+    if realm.is_some() && !realm.unwrap().is_empty() {
+        message += format!("\nThe site says: \"{}\"", realm.unwrap()).as_str();
+    }
+    let dialog = ModalDialog::Credentials(CredentialsDialog::new(&message));
+
+    return match run_modal_dialog(&platform.sdl_context, &platform.font_context, &platform.event_pump, &platform.color_scheme, dialog) {
+        Some(DialogResult::Credentials(username, password)) => Some((username, password)),
+        _ => None,
+    };
+}
+
+
+fn run_modal_dialog(sdl_context: &Sdl, font_context: &Rc<FontContext>, event_pump: &Rc<RefCell<EventPump>>, color_scheme: &Rc<Cell<ColorScheme>>,
+                    dialog: ModalDialog) -> Option<DialogResult> {
+    //script dialogs block the script until they are closed, so we run a loop of our own here, and not return to the main loop
+    //TODO: the dialog is a separate window now, it would be nicer to show it on top of the page in the main window
+    let mut dialog = dialog;
+
+    let dialog_platform = platform::init_dialog_platform(sdl_context, font_context, event_pump, color_scheme, "Webcrustacean", DIALOG_WIDTH, dialog.height());
+    if dialog_platform.is_err() {
+        debug_log_warn(format!("could not open a dialog: {}", dialog_platform.err().unwrap()));
+        return None;
+    }
+    let mut dialog_platform = dialog_platform.unwrap();
+
+    let text_input_was_enabled = dialog_platform.text_input_enabled();
+    if dialog.has_text_input() {
+        dialog_platform.enable_text_input();
+    }
+
+    let result = 'dialog_loop: loop {
+        let start_loop_instant = Instant::now();
+
+        let events: Vec<SdlEvent> = event_pump.borrow_mut().poll_iter().collect();
+        for event in events {
+            //the browser can still be closed while a dialog is open, then we close the dialog, and send the quit on to the main loop
+            let closes_main_window = match &event {
+                SdlEvent::Quit { .. } => true,
+                SdlEvent::Window { win_event: WindowEvent::Close, window_id, .. } => *window_id != dialog_platform.window_id(),
+                _ => false,
+            };
+            if closes_main_window {
+                let push_result = sdl_context.event().and_then(|event_subsystem| event_subsystem.push_event(SdlEvent::Quit { timestamp: 0 }));
+                if push_result.is_err() {
+                    debug_log_warn(format!("could not pass on quitting while a dialog is open: {}", push_result.err().unwrap()));
+                }
+                break 'dialog_loop None;
+            }
+
+            //other events for the main window are dropped, since it is waiting for the dialog
+            if event.get_window_id() != Some(dialog_platform.window_id()) {
+                continue;
+            }
+
+            match event {
+                SdlEvent::Window { win_event: WindowEvent::Close, .. } | SdlEvent::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    break 'dialog_loop None;
+                },
+                SdlEvent::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                    break 'dialog_loop Some(dialog.confirm());
+                },
+                SdlEvent::KeyDown { keycode: Some(keycode), .. } => {
+                    dialog.handle_keyboard_input(&dialog_platform, None, dialog_platform.convert_key_code(&keycode));
+                },
+                SdlEvent::TextInput { text, .. } => {
+                    dialog.handle_keyboard_input(&dialog_platform, Some(&text), None);
+                },
+                SdlEvent::MouseButtonUp { mouse_btn: MouseButton::Left, x: mouse_x, y: mouse_y, .. } => {
+                    let click_result = dialog.click(mouse_x as f32, mouse_y as f32);
+                    if click_result.is_some() {
+                        break 'dialog_loop click_result.unwrap();
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        dialog.render(current_animation_tick(), &mut dialog_platform);
+        frame_time_check(&start_loop_instant);
+    };
+
+    if text_input_was_enabled {
+        dialog_platform.enable_text_input();
+    } else {
+        dialog_platform.disable_text_input();
+    }
+    return result;
 }
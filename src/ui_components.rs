@@ -1,21 +1,22 @@
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::color::Color;
-use crate::debug::debug_log_warn;
+use image::DynamicImage;
+
+use crate::address_bar::Suggestion;
+use crate::display_list::DisplayList;
+use crate::dom::DomNodeId;
 use crate::layout::Rect;
 use crate::network::url::Url;
 use crate::platform::{
-    fonts::Font,
+    self,
+    fonts::{Font, FontContext},
     KeyCode,
     Platform,
     Position
 };
-use crate::ui::{
-    History,
-    UI_BASIC_COLOR,
-    UI_BASIC_DARKER_COLOR,
-    UIState
-};
+use crate::ui::{History, UiColors, WindowDimensions};
 
 
 const TEXT_FIELD_OFFSET_FROM_BORDER: f32 = 5.0;
@@ -29,18 +30,34 @@ pub fn get_next_component_id() -> usize { NEXT_COMPONENT_ID.fetch_add(1, Orderin
 pub enum PageComponent {
     Button(Button),
     TextField(TextField),
+    TextArea(TextArea),
+    FileInput(FileInput),
 }
 impl PageComponent {
     pub fn get_id(&self) -> usize {
         match self {
             PageComponent::Button(button) => button.id,
             PageComponent::TextField(text_field) => text_field.id,
+            PageComponent::TextArea(text_area) => text_area.id,
+            PageComponent::FileInput(file_input) => file_input.id,
         }
     }
+    pub fn get_end_position(&self) -> (f32, f32) {
+        //this is the position of the last point inside the component, which is where the cursor goes when it is focussed without clicking on it
+        let (x, y, width, height) = match self {
+            PageComponent::Button(button) => (button.x, button.y, button.width, button.height),
+            PageComponent::TextField(text_field) => (text_field.x, text_field.y, text_field.width, text_field.height),
+            PageComponent::TextArea(text_area) => (text_area.x, text_area.y, text_area.width, text_area.height),
+            PageComponent::FileInput(file_input) => (file_input.x, file_input.y, file_input.width, file_input.height),
+        };
+        return (x + width - 1.0, y + height - 1.0);
+    }
     pub fn click(&mut self) {
         match self {
             PageComponent::Button(button) => button.click(),
             PageComponent::TextField(_) => {},
+            PageComponent::TextArea(_) => {},
+            PageComponent::FileInput(file_input) => file_input.click(),
         }
     }
 }
@@ -65,6 +82,12 @@ pub struct TextField {
     pub selection_start_idx: usize,
     pub selection_end_idx: usize,
 
+    pub placeholder: String,
+    pub max_length: Option<usize>,
+    pub disabled: bool, //a disabled field can't get focus, and is not submitted with its form
+    pub readonly: bool, //a readonly field can get focus, but its text can't be edited
+    pub masked: bool, //for passwords, we show a dot for each character instead of the text
+
     pub font: Font,
     pub char_position_mapping: Vec<f32>,
 }
@@ -76,10 +99,12 @@ impl TextField {
         //      -> yes, we are going to make a lazy_static PLATFORM variable
         let font = Font::default();
         return TextField { id: get_next_component_id(), x, y, width, height, has_focus: false, cursor_text_position: 0, text: String::new(), select_on_first_click,
-                           selection_start_x: 0.0, selection_end_x: 0.0, selection_start_idx: 0, selection_end_idx: 0, font, char_position_mapping: Vec::new() };
+                           selection_start_x: 0.0, selection_end_x: 0.0, selection_start_idx: 0, selection_end_idx: 0, placeholder: String::new(),
+                           max_length: None, disabled: false, readonly: false, masked: false, font, char_position_mapping: Vec::new() };
     }
-    pub fn render(&self, ui_state: &UIState, platform: &mut Platform, y_offset: f32) {
-        platform.draw_square(self.x, self.y - y_offset, self.width, self.height, Color::BLACK, 255);
+    pub fn render(&self, animation_tick: u32, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        let color = if self.disabled { ui_colors.disabled_foreground } else { ui_colors.foreground };
+        display_list.draw_square(self.x, self.y, self.width, self.height, color);
 
         if self.selection_start_x != self.selection_end_x {
             let start_x = if self.selection_start_x < self.selection_end_x { self.selection_start_x } else { self.selection_end_x };
@@ -87,15 +112,20 @@ impl TextField {
 
             let y_start = self.y + TEXT_FIELD_OFFSET_FROM_BORDER;
             let height = self.height - (TEXT_FIELD_OFFSET_FROM_BORDER * 2.0);
-            platform.fill_rect(start_x, y_start - y_offset, end_x - start_x, height, Color::DEFAULT_SELECTION_COLOR, 255);
+            display_list.fill_rect(start_x, y_start, end_x - start_x, height, ui_colors.selection);
         }
 
-        platform.render_text(&self.text, self.x + TEXT_FIELD_OFFSET_FROM_BORDER, self.y + TEXT_FIELD_OFFSET_FROM_BORDER - y_offset, &self.font, Color::BLACK);
+        if self.text.is_empty() && !self.placeholder.is_empty() {
+            display_list.render_text(&self.placeholder, self.x + TEXT_FIELD_OFFSET_FROM_BORDER, self.y + TEXT_FIELD_OFFSET_FROM_BORDER, &self.font,
+                                     ui_colors.disabled_foreground);
+        } else {
+            display_list.render_text(&self.displayed_text(), self.x + TEXT_FIELD_OFFSET_FROM_BORDER, self.y + TEXT_FIELD_OFFSET_FROM_BORDER, &self.font, color);
+        }
 
         if self.has_focus && !self.has_selection_active() {
 
             //TODO: also we need to make sure we reset the cycle whenever the cursor is moved, so it stays visible while using the arrow keys quickly
-            let cursor_visible = ui_state.animation_tick % (CURSOR_BLINK_SPEED_MILLIS * 2) > CURSOR_BLINK_SPEED_MILLIS;
+            let cursor_visible = animation_tick % (CURSOR_BLINK_SPEED_MILLIS * 2) > CURSOR_BLINK_SPEED_MILLIS;
             if cursor_visible {
                 let relative_cursor_position = if self.cursor_text_position == 0 {
                     0.0
@@ -106,9 +136,9 @@ impl TextField {
                 let cursor_position = relative_cursor_position + self.x + TEXT_FIELD_OFFSET_FROM_BORDER;
                 let cursor_top_bottom_margin = 2.0;
                 let cursor_bottom_pos = (self.y + self.height) - cursor_top_bottom_margin;
-                platform.draw_line(Position { x: cursor_position, y: self.y + cursor_top_bottom_margin - y_offset},
-                                   Position { x: cursor_position, y: cursor_bottom_pos - y_offset },
-                                   Color::BLACK);
+                display_list.draw_line(Position { x: cursor_position, y: self.y + cursor_top_bottom_margin },
+                                       Position { x: cursor_position, y: cursor_bottom_pos },
+                                       ui_colors.foreground);
             }
         }
     }
@@ -120,7 +150,7 @@ impl TextField {
         self.height = height;
     }
 
-    pub fn set_text(&mut self, platform: &Platform, text: String) { //TODO: use this everywhere...
+    pub fn set_text(&mut self, font_context: &FontContext, text: String) { //TODO: use this everywhere...
         self.clear_selection();
         self.text = text;
 
@@ -128,18 +158,30 @@ impl TextField {
             self.cursor_text_position = self.text.len();
         }
 
-        self.char_position_mapping = platform.font_context.compute_char_position_mapping(&self.font, &self.text);
+        self.char_position_mapping = font_context.compute_char_position_mapping(&self.font, &self.displayed_text());
     }
 
-    pub fn insert_text(&mut self, platform: &Platform, text: &String) {
+    pub fn insert_text(&mut self, font_context: &FontContext, text: &String) {
         if self.has_selection_active() {
-            self.remove_selected_text(platform);
+            self.remove_selected_text(font_context);
         }
         for char in text.chars() {
+            if self.max_length.is_some() && self.text.chars().count() >= self.max_length.unwrap() {
+                //like other browsers, we cut off the inserted text when it does not fit, for example when pasting
+                break;
+            }
             self.text.insert(self.cursor_text_position, char);
             self.cursor_text_position += 1;
         }
-        self.char_position_mapping = platform.font_context.compute_char_position_mapping(&self.font, &self.text);
+        self.char_position_mapping = font_context.compute_char_position_mapping(&self.font, &self.displayed_text());
+    }
+
+    pub fn paste_text(&mut self, font_context: &FontContext, text: &String) {
+        //a text field has only one line, so like other browsers we leave out the line breaks of what is pasted
+        if !self.is_editable() {
+            return;
+        }
+        self.insert_text(font_context, &text.replace(['\r', '\n'], ""));
     }
 
     pub fn is_inside(&self, x: f32, y: f32) -> bool {
@@ -149,10 +191,7 @@ impl TextField {
 
     pub fn mouse_down(&mut self, x: f32, _: f32) {
         if self.select_on_first_click && !self.has_focus {
-            self.selection_start_idx = 0;
-            self.selection_end_idx = self.text.len() - 1;
-            self.selection_start_x = self.x + TEXT_FIELD_OFFSET_FROM_BORDER;
-            self.selection_end_x = self.x + TEXT_FIELD_OFFSET_FROM_BORDER + self.char_position_mapping.iter().last().unwrap();
+            self.select_all();
             self.has_focus = true;
             return;
         }
@@ -211,6 +250,8 @@ impl TextField {
                 self.selection_end_idx = self.text.len() - 1;
             }
 
+            //the cursor goes to the end of the selection, so extending it with the keyboard moves that side
+            self.cursor_text_position = self.selection_end_idx + 1;
             self.has_focus = true;
         } else {
             self.clear_selection();
@@ -218,6 +259,18 @@ impl TextField {
         }
     }
 
+    pub fn select_all(&mut self) {
+        if self.text.is_empty() {
+            self.clear_selection();
+            return;
+        }
+        self.selection_start_idx = 0;
+        self.selection_end_idx = self.text.len() - 1;
+        self.selection_start_x = self.x + TEXT_FIELD_OFFSET_FROM_BORDER;
+        self.selection_end_x = self.x + TEXT_FIELD_OFFSET_FROM_BORDER + self.char_position_mapping.last().copied().unwrap_or(0.0);
+        self.cursor_text_position = self.text.len();
+    }
+
     pub fn clear_selection(&mut self) {
         self.selection_start_x = 0.0;
         self.selection_end_x = 0.0;
@@ -225,41 +278,131 @@ impl TextField {
         self.selection_end_idx = 0;
     }
 
+    pub fn displayed_text(&self) -> String {
+        if self.masked {
+            return "\u{2022}".repeat(self.text.chars().count());
+        }
+        return self.text.clone();
+    }
+
     pub fn get_selected_text(&self) -> String {
+        if self.masked {
+            return String::new(); //like other browsers, we don't allow copying passwords
+        }
         let selection_size = self.selection_end_idx - self.selection_start_idx + 1;
         return self.text.chars().skip(self.selection_start_idx).take(selection_size).collect::<String>();
     }
 
-    fn remove_selected_text(&mut self, platform: &Platform) {
+    pub fn cut_selected_text(&mut self, font_context: &FontContext) -> String {
+        //this returns the text for the clipboard, which is empty when nothing could be cut (passwords can't be cut, as they can't be copied)
+        if !self.is_editable() || self.masked || !self.has_selection_active() {
+            return String::new();
+        }
+        let selected_text = self.get_selected_text();
+        self.remove_selected_text(font_context);
+        return selected_text;
+    }
+
+    pub fn move_cursor_by_word(&mut self, forward: bool) {
+        //as in other browsers, this moves to the end of the next word, or to the start of the previous one
+        self.clear_selection();
+        if self.masked {
+            //we don't show where the words in a password are
+            self.cursor_text_position = if forward { self.text.len() } else { 0 };
+            return;
+        }
+
+        let chars: Vec<char> = self.text.chars().collect();
+        let is_word_char = |idx: usize| chars[idx].is_alphanumeric() || chars[idx] == '_';
+        let mut position = self.cursor_text_position;
+        if forward {
+            while position < chars.len() && !is_word_char(position) {
+                position += 1;
+            }
+            while position < chars.len() && is_word_char(position) {
+                position += 1;
+            }
+        } else {
+            while position > 0 && !is_word_char(position - 1) {
+                position -= 1;
+            }
+            while position > 0 && is_word_char(position - 1) {
+                position -= 1;
+            }
+        }
+        self.cursor_text_position = position;
+    }
+
+    pub fn extend_selection(&mut self, key_code: KeyCode) {
+        //the anchor is the side of the selection that stays put, the cursor is on the side that moves
+        let anchor = if !self.has_selection_active() {
+            self.cursor_text_position
+        } else if self.cursor_text_position == self.selection_start_idx {
+            self.selection_end_idx + 1
+        } else {
+            self.selection_start_idx
+        };
+
+        let text_length = self.text.chars().count();
+        self.cursor_text_position = match key_code {
+            KeyCode::LEFT => { self.cursor_text_position.saturating_sub(1) },
+            KeyCode::RIGHT => { usize::min(self.cursor_text_position + 1, text_length) },
+            KeyCode::HOME | KeyCode::UP | KeyCode::PAGEUP => { 0 },
+            KeyCode::END | KeyCode::DOWN | KeyCode::PAGEDOWN => { text_length },
+            KeyCode::BACKSPACE | KeyCode::RETURN | KeyCode::SPACE => { return; },
+        };
+
+        let from_idx = usize::min(anchor, self.cursor_text_position);
+        let to_idx = usize::max(anchor, self.cursor_text_position);
+        if from_idx == to_idx {
+            self.clear_selection();
+            return;
+        }
+
+        let text_start_x = self.x + TEXT_FIELD_OFFSET_FROM_BORDER;
+        self.selection_start_idx = from_idx;
+        self.selection_end_idx = to_idx - 1;
+        self.selection_start_x = text_start_x + if from_idx == 0 { 0.0 } else { self.char_position_mapping[from_idx - 1] };
+        self.selection_end_x = text_start_x + self.char_position_mapping[to_idx - 1];
+    }
+
+    fn remove_selected_text(&mut self, font_context: &FontContext) {
         if self.has_selection_active() {
             for _ in self.selection_start_idx..(self.selection_end_idx+1) {
                 self.text.remove(self.selection_start_idx);
             }
             self.cursor_text_position = self.selection_start_idx;
-            self.char_position_mapping = platform.font_context.compute_char_position_mapping(&self.font, &self.text);
+            self.char_position_mapping = font_context.compute_char_position_mapping(&self.font, &self.displayed_text());
             self.clear_selection();
         }
     }
 
     pub fn has_selection_active(&self) -> bool {
-        return self.selection_start_idx != self.selection_end_idx;
+        //the end index is included in the selection, so we check the positions, since a selection of one char has the same start and end index
+        return self.selection_start_x != self.selection_end_x;
     }
 
-    pub fn handle_keyboard_input(&mut self, platform: &Platform, input: Option<&String>, key_code: Option<KeyCode>) {
+    pub fn is_editable(&self) -> bool {
+        return !self.disabled && !self.readonly;
+    }
+
+    pub fn handle_keyboard_input(&mut self, font_context: &FontContext, input: Option<&String>, key_code: Option<KeyCode>) {
         if input.is_some() {
-            self.insert_text(platform, &input.unwrap());
+            if self.is_editable() {
+                self.insert_text(font_context, &input.unwrap());
+            }
             return;
         }
 
         if key_code.is_some() {
             match key_code.unwrap() {
                 KeyCode::BACKSPACE => {
-                    if self.has_selection_active() {
-                        self.remove_selected_text(platform);
-                    } else if self.cursor_text_position > 0 {
+                    if self.is_editable() && self.has_selection_active() {
+                        self.remove_selected_text(font_context);
+                    } else if self.is_editable() && self.cursor_text_position > 0 {
                         self.text.remove(self.cursor_text_position - 1);  //TODO: this does not work with unicode, but we probably have many more places here that don't
                         self.cursor_text_position -= 1;
-                        self.char_position_mapping = platform.font_context.compute_char_position_mapping(&self.font, &self.text);
+                        self.char_position_mapping = font_context.compute_char_position_mapping(&self.font, &self.displayed_text());
                     }
                 },
                 KeyCode::LEFT => {
@@ -277,13 +420,336 @@ impl TextField {
                         self.cursor_text_position += 1;
                     }
                 },
+                KeyCode::HOME => {
+                    self.clear_selection();
+                    self.cursor_text_position = 0;
+                },
+                KeyCode::END => {
+                    self.clear_selection();
+                    self.cursor_text_position = self.text.len();
+                },
+                KeyCode::UP | KeyCode::DOWN | KeyCode::PAGEUP | KeyCode::PAGEDOWN => {
+                    //a text field only has one line, so there is nowhere to move to
+                },
+                KeyCode::SPACE => {
+                    //the space itself comes in as text input
+                },
+            }
+        }
+    }
+}
+
+
+pub const TEXT_AREA_DEFAULT_COLS: usize = 20;
+pub const TEXT_AREA_DEFAULT_ROWS: usize = 2;
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+struct TextAreaLine {
+    start_idx: usize, //the char index in the text where this line starts
+    end_idx: usize, //the char index after the last char of this line (a newline ending the line is not part of it)
+    char_position_mapping: Vec<f32>,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct TextArea {
+    pub id: usize,
+
+    pub x: f32, //NOTE: x and y are the absolute positions in the window, not content positions in the page.
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+
+    pub cols: usize,
+    pub rows: usize,
+
+    pub has_focus: bool,
+    pub cursor_text_position: usize, //this is a char index, with the same meaning as in TextField
+    pub text: String,
+    pub first_visible_line: usize,
+
+    //TODO: selecting text inside a text area is not supported yet
+
+    pub font: Font,
+    line_height: f32,
+    lines: Vec<TextAreaLine>, //the text wrapped to the width of the text area, recomputed whenever the text or the width changes
+}
+impl TextArea {
+    pub fn new(x: f32, y: f32, width: f32, height: f32, cols: usize, rows: usize) -> TextArea {
+        //as for TextField, we don't set the text here, since wrapping it into lines needs the font context
+        let lines = vec![TextAreaLine { start_idx: 0, end_idx: 0, char_position_mapping: Vec::new() }];
+        return TextArea { id: get_next_component_id(), x, y, width, height, cols, rows, has_focus: false, cursor_text_position: 0, text: String::new(),
+                          first_visible_line: 0, font: Font::default(), line_height: 0.0, lines };
+    }
+
+    pub fn render(&self, animation_tick: u32, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        display_list.draw_square(self.x, self.y, self.width, self.height, ui_colors.foreground);
+
+        let current_line_idx = self.current_line_idx();
+        let visible_lines = self.lines.iter().enumerate().skip(self.first_visible_line).take(self.number_of_visible_lines());
+
+        for (visible_line_idx, (line_idx, line)) in visible_lines.enumerate() {
+            let line_y = self.y + TEXT_FIELD_OFFSET_FROM_BORDER + (visible_line_idx as f32 * self.line_height);
+            let line_text = self.text.chars().skip(line.start_idx).take(line.end_idx - line.start_idx).collect::<String>();
+            display_list.render_text(&line_text, self.x + TEXT_FIELD_OFFSET_FROM_BORDER, line_y, &self.font, ui_colors.foreground);
+
+            if self.has_focus && line_idx == current_line_idx {
+                let cursor_visible = animation_tick % (CURSOR_BLINK_SPEED_MILLIS * 2) > CURSOR_BLINK_SPEED_MILLIS;
+                if cursor_visible {
+                    let cursor_position = self.x + TEXT_FIELD_OFFSET_FROM_BORDER + self.cursor_x_in_line(line_idx);
+                    display_list.draw_line(Position { x: cursor_position, y: line_y },
+                                           Position { x: cursor_position, y: line_y + self.line_height },
+                                           ui_colors.foreground);
+                }
+            }
+        }
+    }
+
+    pub fn compute_size(&self, font_context: &FontContext) -> (f32, f32) {
+        //like other browsers we size the area based on the number of columns and rows, using the width of a digit as the average char width
+        let (char_width, line_height) = font_context.get_text_dimension_str("0", &self.font);
+        return ((char_width * self.cols as f32) + (TEXT_FIELD_OFFSET_FROM_BORDER * 2.0),
+                (line_height * self.rows as f32) + (TEXT_FIELD_OFFSET_FROM_BORDER * 2.0));
+    }
+
+    pub fn update_position(&mut self, font_context: &FontContext, x: f32, y: f32, width: f32, height: f32) {
+        let width_changed = self.width != width;
+
+        self.x = x;
+        self.y = y;
+        self.width = width;
+        self.height = height;
+
+        if width_changed {
+            self.wrap_lines(font_context);
+            self.scroll_to_cursor();
+        }
+    }
+
+    pub fn set_text(&mut self, font_context: &FontContext, text: String) {
+        self.text = text;
+
+        let number_of_chars = self.text.chars().count();
+        if self.cursor_text_position > number_of_chars {
+            self.cursor_text_position = number_of_chars;
+        }
+
+        self.wrap_lines(font_context);
+        self.scroll_to_cursor();
+    }
+
+    pub fn insert_text(&mut self, platform: &Platform, text: &String) {
+        for char in text.chars() {
+            let byte_idx = self.byte_idx(self.cursor_text_position);
+            self.text.insert(byte_idx, char);
+            self.cursor_text_position += 1;
+        }
+        self.wrap_lines(&platform.font_context);
+        self.scroll_to_cursor();
+    }
+
+    pub fn is_inside(&self, x: f32, y: f32) -> bool {
+        return x > self.x && x < (self.x + self.width) &&
+               y > self.y && y < (self.y + self.height);
+    }
+
+    pub fn mouse_down(&mut self, x: f32, y: f32) {
+        self.has_focus = true;
+
+        let mut line_idx = self.first_visible_line;
+        if self.line_height > 0.0 && y > self.y + TEXT_FIELD_OFFSET_FROM_BORDER {
+            line_idx += ((y - self.y - TEXT_FIELD_OFFSET_FROM_BORDER) / self.line_height).floor() as usize;
+        }
+        line_idx = usize::min(line_idx, self.lines.len() - 1);
+
+        self.move_cursor_to_x_in_line(line_idx, x - self.x - TEXT_FIELD_OFFSET_FROM_BORDER);
+    }
+
+    pub fn scroll(&mut self, number_of_lines: i32) {
+        let max_first_visible_line = self.lines.len().saturating_sub(self.number_of_visible_lines());
+        let new_first_visible_line = (self.first_visible_line as i32 + number_of_lines).max(0) as usize;
+        self.first_visible_line = usize::min(new_first_visible_line, max_first_visible_line);
+    }
+
+    pub fn handle_keyboard_input(&mut self, platform: &Platform, input: Option<&String>, key_code: Option<KeyCode>) {
+        if input.is_some() {
+            self.insert_text(platform, &input.unwrap());
+            return;
+        }
+
+        if key_code.is_some() {
+            match key_code.unwrap() {
+                KeyCode::BACKSPACE => {
+                    if self.cursor_text_position > 0 {
+                        let byte_idx = self.byte_idx(self.cursor_text_position - 1);
+                        self.text.remove(byte_idx);
+                        self.cursor_text_position -= 1;
+                        self.wrap_lines(&platform.font_context);
+                    }
+                },
+                KeyCode::LEFT => {
+                    if self.cursor_text_position > 0 {
+                        self.cursor_text_position -= 1;
+                    }
+                },
+                KeyCode::RETURN => {
+                    self.insert_text(platform, &String::from("\n"));
+                },
+                KeyCode::RIGHT => {
+                    if self.cursor_text_position < self.text.chars().count() {
+                        self.cursor_text_position += 1;
+                    }
+                },
+                KeyCode::UP => {
+                    let current_line_idx = self.current_line_idx();
+                    if current_line_idx == 0 {
+                        self.cursor_text_position = 0;
+                    } else {
+                        let cursor_x = self.cursor_x_in_line(current_line_idx);
+                        self.move_cursor_to_x_in_line(current_line_idx - 1, cursor_x);
+                    }
+                },
+                KeyCode::DOWN => {
+                    let current_line_idx = self.current_line_idx();
+                    if current_line_idx == self.lines.len() - 1 {
+                        self.cursor_text_position = self.text.chars().count();
+                    } else {
+                        let cursor_x = self.cursor_x_in_line(current_line_idx);
+                        self.move_cursor_to_x_in_line(current_line_idx + 1, cursor_x);
+                    }
+                },
+                KeyCode::HOME => {
+                    self.cursor_text_position = self.lines[self.current_line_idx()].start_idx;
+                },
+                KeyCode::END => {
+                    self.move_cursor_to_x_in_line(self.current_line_idx(), f32::MAX);
+                },
+                KeyCode::PAGEUP => {
+                    let target_line_idx = self.current_line_idx().saturating_sub(self.number_of_visible_lines());
+                    let cursor_x = self.cursor_x_in_line(self.current_line_idx());
+                    self.move_cursor_to_x_in_line(target_line_idx, cursor_x);
+                },
+                KeyCode::PAGEDOWN => {
+                    let target_line_idx = usize::min(self.current_line_idx() + self.number_of_visible_lines(), self.lines.len() - 1);
+                    let cursor_x = self.cursor_x_in_line(self.current_line_idx());
+                    self.move_cursor_to_x_in_line(target_line_idx, cursor_x);
+                },
+                KeyCode::SPACE => {
+                    //the space itself comes in as text input
+                },
+            }
+            self.scroll_to_cursor();
+        }
+    }
+
+    fn wrap_lines(&mut self, font_context: &FontContext) {
+        self.line_height = font_context.get_text_dimension_str("0", &self.font).1;
+        let available_width = self.width - (TEXT_FIELD_OFFSET_FROM_BORDER * 2.0);
+
+        self.lines = Vec::new();
+        let mut paragraph_start_idx = 0;
+
+        for paragraph in self.text.split('\n') {
+            let paragraph_chars: Vec<char> = paragraph.chars().collect();
+            let char_position_mapping = font_context.compute_char_position_mapping(&self.font, &paragraph.to_owned());
+
+            let mut line_start_idx = 0;
+            loop {
+                let line_end_idx = find_line_wrap_position(&paragraph_chars, &char_position_mapping, line_start_idx, available_width);
+
+                let x_offset = if line_start_idx == 0 { 0.0 } else { char_position_mapping[line_start_idx - 1] };
+                let line_position_mapping = char_position_mapping[line_start_idx..line_end_idx].iter().map(|x_position| x_position - x_offset).collect();
+                self.lines.push(TextAreaLine { start_idx: paragraph_start_idx + line_start_idx, end_idx: paragraph_start_idx + line_end_idx,
+                                               char_position_mapping: line_position_mapping });
+
+                if line_end_idx >= paragraph_chars.len() {
+                    break;
+                }
+                line_start_idx = line_end_idx;
+            }
+
+            paragraph_start_idx += paragraph_chars.len() + 1; //the + 1 is for the newline
+        }
+    }
+
+    fn current_line_idx(&self) -> usize {
+        //when a line is wrapped, its end is the same position as the start of the next line, we show the cursor at the start of the next line then
+        return self.lines.iter().rposition(|line| line.start_idx <= self.cursor_text_position).unwrap_or(0);
+    }
+
+    fn cursor_x_in_line(&self, line_idx: usize) -> f32 {
+        let line = &self.lines[line_idx];
+        if self.cursor_text_position <= line.start_idx {
+            return 0.0;
+        }
+        return line.char_position_mapping[self.cursor_text_position - line.start_idx - 1];
+    }
+
+    fn move_cursor_to_x_in_line(&mut self, line_idx: usize, x: f32) {
+        let line = &self.lines[line_idx];
+
+        //for a wrapped line, the position after the last char is on the next line, so we should stay before the last char
+        let line_is_wrapped = line_idx + 1 < self.lines.len() && self.lines[line_idx + 1].start_idx == line.end_idx;
+        let max_position = if line_is_wrapped { line.end_idx - 1 } else { line.end_idx };
+
+        self.cursor_text_position = max_position;
+        for (idx, x_position) in line.char_position_mapping.iter().enumerate() {
+            let char_start_x = if idx == 0 { 0.0 } else { line.char_position_mapping[idx - 1] };
+            if (char_start_x + x_position) / 2.0 > x {
+                self.cursor_text_position = usize::min(line.start_idx + idx, max_position);
+                break;
+            }
+        }
+    }
+
+    fn number_of_visible_lines(&self) -> usize {
+        if self.line_height <= 0.0 {
+            return self.rows;
+        }
+        let number_of_lines = ((self.height - (TEXT_FIELD_OFFSET_FROM_BORDER * 2.0)) / self.line_height).floor() as usize;
+        return usize::max(number_of_lines, 1);
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let current_line_idx = self.current_line_idx();
+        let number_of_visible_lines = self.number_of_visible_lines();
+
+        if current_line_idx < self.first_visible_line {
+            self.first_visible_line = current_line_idx;
+        } else if current_line_idx >= self.first_visible_line + number_of_visible_lines {
+            self.first_visible_line = current_line_idx + 1 - number_of_visible_lines;
+        }
+    }
+
+    fn byte_idx(&self, char_idx: usize) -> usize {
+        return self.text.char_indices().nth(char_idx).map(|(byte_idx, _)| byte_idx).unwrap_or(self.text.len());
+    }
+}
+
+
+fn find_line_wrap_position(chars: &Vec<char>, char_position_mapping: &Vec<f32>, line_start_idx: usize, available_width: f32) -> usize {
+    //returns the char index (exclusive) where the line starting at line_start_idx ends. We wrap after the last space that still fits,
+    //   and only break inside a word if there is no such space. Every line gets at least one char, otherwise we would never finish.
+    let x_offset = if line_start_idx == 0 { 0.0 } else { char_position_mapping[line_start_idx - 1] };
+    let mut last_space_idx = None;
+
+    for idx in line_start_idx..chars.len() {
+        if char_position_mapping[idx] - x_offset > available_width && idx > line_start_idx && chars[idx] != ' ' {
+            if last_space_idx.is_some() {
+                return last_space_idx.unwrap() + 1;
             }
+            return idx;
+        }
+        if chars[idx] == ' ' {
+            last_space_idx = Some(idx);
         }
     }
+
+    return chars.len();
 }
 
 
-const BUTTON_TEXT_OFFSET_FROM_BORDER: f32 = 5.0;
+pub const BUTTON_TEXT_OFFSET_FROM_BORDER: f32 = 5.0;
 
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub struct Button {
@@ -304,9 +770,9 @@ impl Button {
         return Button { id: get_next_component_id(), x, y, width, height, has_focus: false, text, font: Font::default()};
     }
 
-    pub fn render(&self, platform: &mut Platform, y_offset: f32) {
-        platform.draw_square(self.x, self.y - y_offset, self.width, self.height, Color::BLACK, 255);
-        platform.render_text(&self.text, self.x + BUTTON_TEXT_OFFSET_FROM_BORDER, self.y + BUTTON_TEXT_OFFSET_FROM_BORDER - y_offset, &self.font, Color::BLACK);
+    pub fn render(&self, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        display_list.draw_square(self.x, self.y, self.width, self.height, ui_colors.foreground);
+        display_list.render_text(&self.text, self.x + BUTTON_TEXT_OFFSET_FROM_BORDER, self.y + BUTTON_TEXT_OFFSET_FROM_BORDER, &self.font, ui_colors.foreground);
     }
 
     pub fn click(&mut self) {
@@ -328,6 +794,70 @@ impl Button {
 }
 
 
+const FILE_INPUT_BUTTON_TEXT: &str = "Browse...";
+const FILE_INPUT_NO_FILE_TEXT: &str = "No file selected.";
+const FILE_INPUT_SPACING: f32 = 5.0;
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct FileInput {
+    pub id: usize,
+    pub x: f32, //NOTE: x and y are the absolute positions in the window, not content positions in the page.
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    #[allow(dead_code)] pub has_focus: bool,  //TODO: set in the correct cases, and use (to open the picker on enter)
+    pub selected_file: Option<PathBuf>,
+    pub font: Font,
+}
+impl FileInput {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> FileInput {
+        return FileInput { id: get_next_component_id(), x, y, width, height, has_focus: false, selected_file: None, font: Font::default() };
+    }
+
+    pub fn render(&self, font_context: &FontContext, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        let button_width = font_context.get_text_dimension_str(FILE_INPUT_BUTTON_TEXT, &self.font).0 + (BUTTON_TEXT_OFFSET_FROM_BORDER * 2.0);
+
+        display_list.draw_square(self.x, self.y, button_width, self.height, ui_colors.foreground);
+        display_list.render_text(&FILE_INPUT_BUTTON_TEXT.to_owned(), self.x + BUTTON_TEXT_OFFSET_FROM_BORDER, self.y + BUTTON_TEXT_OFFSET_FROM_BORDER,
+                                 &self.font, ui_colors.foreground);
+
+        let label_x = self.x + button_width + FILE_INPUT_SPACING;
+        display_list.render_text(&self.get_label_text(), label_x, self.y + BUTTON_TEXT_OFFSET_FROM_BORDER, &self.font, ui_colors.foreground);
+    }
+
+    pub fn compute_size(&self, font_context: &FontContext) -> (f32, f32) {
+        let button_dimension = font_context.get_text_dimension_str(FILE_INPUT_BUTTON_TEXT, &self.font);
+        let label_dimension = font_context.get_text_dimension(&self.get_label_text(), &self.font);
+        return (button_dimension.0 + (BUTTON_TEXT_OFFSET_FROM_BORDER * 2.0) + FILE_INPUT_SPACING + label_dimension.0,
+                button_dimension.1 + (BUTTON_TEXT_OFFSET_FROM_BORDER * 2.0));
+    }
+
+    pub fn click(&mut self) {
+        //when the user cancels the picker, we keep the file that was selected before
+        //TODO: the layout is not recomputed after picking a file, so a long filename can overlap the content after the input
+        let picked_file = platform::pick_file();
+        if picked_file.is_some() {
+            self.selected_file = picked_file;
+        }
+    }
+
+    pub fn update_position(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.x = x;
+        self.y = y;
+        self.width = width;
+        self.height = height;
+    }
+
+    fn get_label_text(&self) -> String {
+        if self.selected_file.is_none() {
+            return FILE_INPUT_NO_FILE_TEXT.to_owned();
+        }
+        let file_name = self.selected_file.as_ref().unwrap().file_name();
+        return file_name.map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or(String::new());
+    }
+}
+
+
 pub struct NavigationButton {
     pub x: f32,
     pub y: f32,
@@ -335,22 +865,22 @@ pub struct NavigationButton {
     pub enabled: bool, //TODO: this one is not yet set based on the history, needs to be fixed
 }
 impl NavigationButton {
-    pub fn render(&self, platform: &mut Platform) {
+    pub fn render(&self, display_list: &mut DisplayList, ui_colors: &UiColors) {
 
         if self.forward {
             //The forward button
-            let color = if self.enabled { Color::BLACK } else { Color::GRAY };
+            let color = if self.enabled { ui_colors.foreground } else { ui_colors.disabled_foreground };
             let center_point = Position {x: self.x + 25.0, y: self.y + 10.0 };
-            platform.draw_line(Position {x: self.x + 0.0, y: self.y + 10.0 }, center_point,  color);
-            platform.draw_line(center_point, Position {x: self.x + 15.0, y: self.y + 20.0 }, color);
-            platform.draw_line(center_point, Position {x: self.x + 15.0, y: self.y + 0.0 },  color);
+            display_list.draw_line(Position {x: self.x + 0.0, y: self.y + 10.0 }, center_point,  color);
+            display_list.draw_line(center_point, Position {x: self.x + 15.0, y: self.y + 20.0 }, color);
+            display_list.draw_line(center_point, Position {x: self.x + 15.0, y: self.y + 0.0 },  color);
         } else {
             //The back button
-            let color = if self.enabled { Color::BLACK } else { Color::GRAY };
+            let color = if self.enabled { ui_colors.foreground } else { ui_colors.disabled_foreground };
             let center_point = Position {x: self.x + 0.0, y: self.y + 10.0 };
-            platform.draw_line(center_point, Position {x: self.x + 25.0, y: self.y + 10.0 }, color);
-            platform.draw_line(center_point, Position {x: self.x + 10.0, y: self.y + 20.0 }, color);
-            platform.draw_line(center_point, Position {x: self.x + 10.0, y: self.y + 0.0  }, color);
+            display_list.draw_line(center_point, Position {x: self.x + 25.0, y: self.y + 10.0 }, color);
+            display_list.draw_line(center_point, Position {x: self.x + 10.0, y: self.y + 20.0 }, color);
+            display_list.draw_line(center_point, Position {x: self.x + 10.0, y: self.y + 0.0  }, color);
         }
 
     }
@@ -365,28 +895,57 @@ impl NavigationButton {
                         y > (self.y - 10.0) && y < (self.y + 30.0);
 
         if is_inside && self.enabled {
-            if self.forward {
-                if history.list.len() > (history.position + 1) {
-                    history.currently_navigating_from_history = true;
-                    history.position = history.position + 1;
-                    return Some(history.list.get(history.position).unwrap().clone());
-                } else {
-                    debug_log_warn("history button should have been disabled")
-                }
+            return if self.forward { history.go_forward() } else { history.go_back() };
+        }
 
-            } else {
-                if history.position > 0 {
-                    history.currently_navigating_from_history = true;
-                    history.position = history.position - 1;
-                    return Some(history.list.get(history.position).unwrap().clone());
-                } else {
-                    debug_log_warn("history button should have been disabled")
-                }
+        return None;
+    }
+}
 
-            }
+
+const RELOAD_ICON_ARC_SEGMENTS: usize = 12;
+
+pub struct ReloadButton {
+    //while a page is loading, this is the stop button
+    pub x: f32,
+    pub y: f32,
+}
+impl ReloadButton {
+    pub fn render(&self, display_list: &mut DisplayList, ui_colors: &UiColors, loading: bool) {
+        if loading {
+            //The stop button, a cross
+            display_list.draw_line(Position { x: self.x + 2.0, y: self.y + 2.0 }, Position { x: self.x + 18.0, y: self.y + 18.0 }, ui_colors.foreground);
+            display_list.draw_line(Position { x: self.x + 18.0, y: self.y + 2.0 }, Position { x: self.x + 2.0, y: self.y + 18.0 }, ui_colors.foreground);
+            return;
         }
 
-        return None;
+        //The reload button, a circle with an opening at the top right, and an arrow head at its end
+        let center_x = self.x + 10.0;
+        let center_y = self.y + 10.0;
+        let radius = 9.0;
+        let start_angle = 0.0_f32;
+        let end_angle = 300.0_f32.to_radians(); //angles go clockwise on screen, since y goes down
+        let point_at = |angle: f32| Position { x: center_x + radius * angle.cos(), y: center_y + radius * angle.sin() };
+
+        for segment in 0..RELOAD_ICON_ARC_SEGMENTS {
+            let segment_start = start_angle + (end_angle - start_angle) * (segment as f32 / RELOAD_ICON_ARC_SEGMENTS as f32);
+            let segment_end = start_angle + (end_angle - start_angle) * ((segment + 1) as f32 / RELOAD_ICON_ARC_SEGMENTS as f32);
+            display_list.draw_line(point_at(segment_start), point_at(segment_end), ui_colors.foreground);
+        }
+
+        //the arrow points along the circle, so its wings point back, at 45 degrees to both sides
+        let arrow_point = point_at(end_angle);
+        let wing_length = 6.0;
+        for wing_angle in [end_angle - 45.0_f32.to_radians(), end_angle - 135.0_f32.to_radians()] {
+            let wing_end = Position { x: arrow_point.x + wing_length * wing_angle.cos(), y: arrow_point.y + wing_length * wing_angle.sin() };
+            display_list.draw_line(arrow_point, wing_end, ui_colors.foreground);
+        }
+    }
+
+    pub fn is_inside(&self, x: f32, y: f32) -> bool {
+        //as for the navigation buttons, the click region is a bit larger than the icon
+        return x > (self.x - 10.0) && x < (self.x + 30.0) &&
+               y > (self.y - 10.0) && y < (self.y + 30.0);
     }
 }
 
@@ -411,10 +970,10 @@ pub struct Scrollbar {
     pub enabled: bool,
 }
 impl Scrollbar {
-    pub fn render(&self, platform: &mut Platform) {
-        platform.fill_rect(self.x, self.y, self.width, self.height, UI_BASIC_COLOR, 255);
+    pub fn render(&self, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        display_list.fill_rect(self.x, self.y, self.width, self.height, ui_colors.basic);
         if self.enabled {
-            platform.fill_rect(self.x, self.block_y, self.width, self.block_height, UI_BASIC_DARKER_COLOR, 255);
+            display_list.fill_rect(self.x, self.block_y, self.width, self.block_height, ui_colors.basic_darker);
         }
     }
 
@@ -453,7 +1012,7 @@ impl Scrollbar {
                self.block_y <= y && (self.block_y + self.block_height) >= y;
     }
 
-    fn clamp_scroll_position(&self, content_scroll_y: f32) -> f32 {
+    pub fn clamp_scroll_position(&self, content_scroll_y: f32) -> f32 {
         if content_scroll_y < 0.0 {
             return 0.0;
         }
@@ -467,3 +1026,418 @@ impl Scrollbar {
         return content_scroll_y;
     }
 }
+
+
+const SUGGESTION_HEIGHT: f32 = 25.0;
+const SUGGESTION_TEXT_OFFSET: f32 = 8.0;
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SuggestionList {
+    //the suggestions shown below the address bar while typing in it, it is not shown when there are none
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub suggestions: Vec<Suggestion>,
+    pub selected_idx: Option<usize>,
+    pub font: Font,
+}
+impl SuggestionList {
+    pub fn new(x: f32, y: f32, width: f32) -> SuggestionList {
+        return SuggestionList { x, y, width, suggestions: Vec::new(), selected_idx: None, font: Font::default() };
+    }
+
+    pub fn render(&self, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        let height = self.suggestions.len() as f32 * SUGGESTION_HEIGHT;
+        display_list.fill_rect(self.x, self.y, self.width, height, ui_colors.background);
+
+        for (suggestion_idx, suggestion) in self.suggestions.iter().enumerate() {
+            let suggestion_y = self.y + (suggestion_idx as f32 * SUGGESTION_HEIGHT);
+            if self.selected_idx == Some(suggestion_idx) {
+                display_list.fill_rect(self.x, suggestion_y, self.width, SUGGESTION_HEIGHT, ui_colors.selection);
+            }
+            let text = if suggestion.title.is_empty() { suggestion.url.to_string() } else { format!("{} - {}", suggestion.title, suggestion.url.to_string()) };
+            display_list.render_text(&text, self.x + SUGGESTION_TEXT_OFFSET, suggestion_y + 4.0, &self.font, ui_colors.foreground);
+        }
+
+        display_list.draw_square(self.x, self.y, self.width, height, ui_colors.basic_darker);
+    }
+
+    pub fn set_suggestions(&mut self, suggestions: Vec<Suggestion>) {
+        self.suggestions = suggestions;
+        self.selected_idx = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set_suggestions(Vec::new());
+    }
+
+    pub fn select_next(&mut self) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        self.selected_idx = match self.selected_idx {
+            None => Some(0),
+            Some(idx) => Some((idx + 1).min(self.suggestions.len() - 1)),
+        };
+    }
+
+    pub fn select_previous(&mut self) {
+        //going up from the first suggestion goes back to what was typed
+        self.selected_idx = match self.selected_idx {
+            None | Some(0) => None,
+            Some(idx) => Some(idx - 1),
+        };
+    }
+
+    pub fn selected(&self) -> Option<&Suggestion> {
+        return self.selected_idx.and_then(|idx| self.suggestions.get(idx));
+    }
+
+    pub fn is_inside(&self, x: f32, y: f32) -> bool {
+        return x > self.x && x < (self.x + self.width) &&
+               y > self.y && y < (self.y + (self.suggestions.len() as f32 * SUGGESTION_HEIGHT));
+    }
+
+    pub fn click(&self, x: f32, y: f32) -> Option<&Suggestion> {
+        if !self.is_inside(x, y) {
+            return None;
+        }
+        return self.suggestions.get(((y - self.y) / SUGGESTION_HEIGHT) as usize);
+    }
+}
+
+
+const CONTEXT_MENU_WIDTH: f32 = 200.0;
+const CONTEXT_MENU_ITEM_HEIGHT: f32 = 25.0;
+const CONTEXT_MENU_TEXT_OFFSET: f32 = 8.0;
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum ContextMenuAction {
+    Back,
+    Forward,
+    Reload,
+    Copy,
+    CopyLinkAddress,
+    OpenLinkInNewTab,
+    SaveImageAs,
+    InspectElement,
+    ViewPageSource,
+}
+impl ContextMenuAction {
+    fn label(&self) -> &'static str {
+        return match self {
+            ContextMenuAction::Back => "Back",
+            ContextMenuAction::Forward => "Forward",
+            ContextMenuAction::Reload => "Reload",
+            ContextMenuAction::Copy => "Copy",
+            ContextMenuAction::CopyLinkAddress => "Copy Link Address",
+            ContextMenuAction::OpenLinkInNewTab => "Open Link in New Tab",
+            ContextMenuAction::SaveImageAs => "Save Image As...",
+            ContextMenuAction::InspectElement => "Inspect Element",
+            ContextMenuAction::ViewPageSource => "View Page Source",
+        };
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ContextMenuItem {
+    pub action: ContextMenuAction,
+    pub enabled: bool,
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ContextMenuTarget {
+    //what was under the mouse when the menu was opened, the actions of the menu work on this
+    pub dom_node: Option<DomNodeId>,
+    pub link_url: Option<Url>,
+    pub image: Option<(Arc<DynamicImage>, Option<Url>)>, //the image, and the url it came from (when it was not for example an inline svg)
+}
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct ContextMenu {
+    pub x: f32,
+    pub y: f32,
+    pub items: Vec<ContextMenuItem>,
+    pub hovered_item_idx: Option<usize>,
+    pub target: ContextMenuTarget,
+    pub font: Font,
+}
+impl ContextMenu {
+    pub fn new(x: f32, y: f32, items: Vec<ContextMenuItem>, target: ContextMenuTarget, window_dimensions: WindowDimensions) -> ContextMenu {
+        //the menu opens to the bottom right of the mouse, unless that would put it (partly) outside of the window
+        let height = items.len() as f32 * CONTEXT_MENU_ITEM_HEIGHT;
+        let x = if x + CONTEXT_MENU_WIDTH > window_dimensions.width { x - CONTEXT_MENU_WIDTH } else { x };
+        let y = if y + height > window_dimensions.height { y - height } else { y };
+        return ContextMenu { x: x.max(0.0), y: y.max(0.0), items, hovered_item_idx: None, target, font: Font::default() };
+    }
+
+    pub fn render(&self, display_list: &mut DisplayList, ui_colors: &UiColors) {
+        let height = self.items.len() as f32 * CONTEXT_MENU_ITEM_HEIGHT;
+        display_list.fill_rect(self.x, self.y, CONTEXT_MENU_WIDTH, height, ui_colors.background);
+
+        for (item_idx, item) in self.items.iter().enumerate() {
+            let item_y = self.y + (item_idx as f32 * CONTEXT_MENU_ITEM_HEIGHT);
+            if self.hovered_item_idx == Some(item_idx) && item.enabled {
+                display_list.fill_rect(self.x, item_y, CONTEXT_MENU_WIDTH, CONTEXT_MENU_ITEM_HEIGHT, ui_colors.selection);
+            }
+            let color = if item.enabled { ui_colors.foreground } else { ui_colors.disabled_foreground };
+            display_list.render_text(&item.action.label().to_owned(), self.x + CONTEXT_MENU_TEXT_OFFSET, item_y + 4.0, &self.font, color);
+        }
+
+        display_list.draw_square(self.x, self.y, CONTEXT_MENU_WIDTH, height, ui_colors.basic_darker);
+    }
+
+    pub fn is_inside(&self, x: f32, y: f32) -> bool {
+        return x > self.x && x < (self.x + CONTEXT_MENU_WIDTH) &&
+               y > self.y && y < (self.y + (self.items.len() as f32 * CONTEXT_MENU_ITEM_HEIGHT));
+    }
+
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.hovered_item_idx = if self.is_inside(x, y) { Some(((y - self.y) / CONTEXT_MENU_ITEM_HEIGHT) as usize) } else { None };
+    }
+
+    pub fn click(&self, x: f32, y: f32) -> Option<ContextMenuAction> {
+        if !self.is_inside(x, y) {
+            return None;
+        }
+        let item = self.items.get(((y - self.y) / CONTEXT_MENU_ITEM_HEIGHT) as usize);
+        return item.filter(|item| item.enabled).map(|item| item.action);
+    }
+}
+
+
+const DIALOG_MARGIN: f32 = 15.0;
+const DIALOG_LINE_HEIGHT: f32 = 20.0;
+const DIALOG_BUTTON_WIDTH: f32 = 80.0;
+const DIALOG_BUTTON_HEIGHT: f32 = 30.0;
+const DIALOG_LABEL_WIDTH: f32 = 90.0;
+pub const DIALOG_WIDTH: f32 = 400.0;
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct Dialog {
+    pub message_lines: Vec<String>,
+    pub text_field: Option<TextField>, //only prompt dialogs have a text field
+    pub ok_button: Button,
+    pub cancel_button: Option<Button>, //alert dialogs only have an ok button
+    pub height: f32,
+    pub font: Font,
+}
+impl Dialog {
+    pub fn new(message: &str, has_cancel_button: bool, text: Option<String>, font_context: &FontContext) -> Dialog {
+        let message_lines: Vec<String> = message.lines().map(|line| line.to_owned()).collect();
+        let mut next_y = DIALOG_MARGIN + (message_lines.len().max(1) as f32 * DIALOG_LINE_HEIGHT) + DIALOG_MARGIN;
+
+        let text_field = text.map(|text| {
+            let mut text_field = TextField::new(DIALOG_MARGIN, next_y, DIALOG_WIDTH - (DIALOG_MARGIN * 2.0), DIALOG_BUTTON_HEIGHT, false);
+            text_field.set_text(font_context, text);
+            text_field.cursor_text_position = text_field.text.len();
+            text_field.has_focus = true;
+            return text_field;
+        });
+        if text_field.is_some() {
+            next_y += DIALOG_BUTTON_HEIGHT + DIALOG_MARGIN;
+        }
+
+        //the buttons are in the bottom right corner, with the cancel button being the rightmost one
+        let mut button_x = DIALOG_WIDTH - DIALOG_MARGIN - DIALOG_BUTTON_WIDTH;
+        let cancel_button = if has_cancel_button {
+            let cancel_button = Button::new(button_x, next_y, DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT, String::from("Cancel"));
+            button_x -= DIALOG_BUTTON_WIDTH + DIALOG_MARGIN;
+            Some(cancel_button)
+        } else {
+            None
+        };
+        let ok_button = Button::new(button_x, next_y, DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT, String::from("OK"));
+
+        let height = next_y + DIALOG_BUTTON_HEIGHT + DIALOG_MARGIN;
+        return Dialog { message_lines, text_field, ok_button, cancel_button, height, font: Font::default() };
+    }
+
+    pub fn render(&self, animation_tick: u32, platform: &mut Platform) {
+        let ui_colors = UiColors::for_color_scheme(platform.color_scheme.get());
+        let mut display_list = DisplayList::new();
+        for (line_idx, line) in self.message_lines.iter().enumerate() {
+            display_list.render_text(line, DIALOG_MARGIN, DIALOG_MARGIN + (line_idx as f32 * DIALOG_LINE_HEIGHT), &self.font, ui_colors.foreground);
+        }
+        if self.text_field.is_some() {
+            self.text_field.as_ref().unwrap().render(animation_tick, &mut display_list, ui_colors);
+        }
+        self.ok_button.render(&mut display_list, ui_colors);
+        if self.cancel_button.is_some() {
+            self.cancel_button.as_ref().unwrap().render(&mut display_list, ui_colors);
+        }
+
+        platform.render_clear(ui_colors.basic);
+        display_list.replay(platform, 0.0);
+        platform.present();
+    }
+
+    pub fn click(&mut self, x: f32, y: f32) -> Option<Option<String>> {
+        //this returns the result of the dialog when one of its buttons is clicked, which is the text of the dialog for ok, and None for cancel
+        if self.ok_button.is_inside(x, y) {
+            return Some(Some(self.get_text()));
+        }
+        if self.cancel_button.is_some() && self.cancel_button.as_ref().unwrap().is_inside(x, y) {
+            return Some(None);
+        }
+        if self.text_field.is_some() && self.text_field.as_ref().unwrap().is_inside(x, y) {
+            self.text_field.as_mut().unwrap().mouse_down(x, y);
+        }
+        return None;
+    }
+
+    pub fn handle_keyboard_input(&mut self, platform: &Platform, input: Option<&String>, key_code: Option<KeyCode>) {
+        if self.text_field.is_some() {
+            self.text_field.as_mut().unwrap().handle_keyboard_input(&platform.font_context, input, key_code);
+        }
+    }
+
+    pub fn get_text(&self) -> String {
+        return self.text_field.as_ref().map(|text_field| text_field.text.clone()).unwrap_or_default();
+    }
+}
+
+
+pub struct CredentialsDialog {
+    pub message_lines: Vec<String>,
+    pub username_field: TextField,
+    pub password_field: TextField,
+    pub ok_button: Button,
+    pub cancel_button: Button,
+    pub height: f32,
+    pub font: Font,
+}
+impl CredentialsDialog {
+    pub fn new(message: &str) -> CredentialsDialog {
+        let message_lines: Vec<String> = message.lines().map(|line| line.to_owned()).collect();
+        let mut next_y = DIALOG_MARGIN + (message_lines.len().max(1) as f32 * DIALOG_LINE_HEIGHT) + DIALOG_MARGIN;
+
+        //the fields have their label on the left of them
+        let field_x = DIALOG_MARGIN + DIALOG_LABEL_WIDTH;
+        let field_width = DIALOG_WIDTH - field_x - DIALOG_MARGIN;
+        let mut username_field = TextField::new(field_x, next_y, field_width, DIALOG_BUTTON_HEIGHT, false);
+        username_field.has_focus = true;
+        next_y += DIALOG_BUTTON_HEIGHT + DIALOG_MARGIN;
+
+        let mut password_field = TextField::new(field_x, next_y, field_width, DIALOG_BUTTON_HEIGHT, false);
+        password_field.masked = true;
+        next_y += DIALOG_BUTTON_HEIGHT + DIALOG_MARGIN;
+
+        let cancel_button = Button::new(DIALOG_WIDTH - DIALOG_MARGIN - DIALOG_BUTTON_WIDTH, next_y, DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT, String::from("Cancel"));
+        let ok_button = Button::new(cancel_button.x - DIALOG_MARGIN - DIALOG_BUTTON_WIDTH, next_y, DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT, String::from("OK"));
+
+        let height = next_y + DIALOG_BUTTON_HEIGHT + DIALOG_MARGIN;
+        return CredentialsDialog { message_lines, username_field, password_field, ok_button, cancel_button, height, font: Font::default() };
+    }
+
+    pub fn render(&self, animation_tick: u32, platform: &mut Platform) {
+        let ui_colors = UiColors::for_color_scheme(platform.color_scheme.get());
+        let mut display_list = DisplayList::new();
+        for (line_idx, line) in self.message_lines.iter().enumerate() {
+            display_list.render_text(line, DIALOG_MARGIN, DIALOG_MARGIN + (line_idx as f32 * DIALOG_LINE_HEIGHT), &self.font, ui_colors.foreground);
+        }
+        for (label, field) in [("Username", &self.username_field), ("Password", &self.password_field)] {
+            display_list.render_text(&String::from(label), DIALOG_MARGIN, field.y + TEXT_FIELD_OFFSET_FROM_BORDER, &self.font, ui_colors.foreground);
+            field.render(animation_tick, &mut display_list, ui_colors);
+        }
+        self.ok_button.render(&mut display_list, ui_colors);
+        self.cancel_button.render(&mut display_list, ui_colors);
+
+        platform.render_clear(ui_colors.basic);
+        display_list.replay(platform, 0.0);
+        platform.present();
+    }
+
+    pub fn click(&mut self, x: f32, y: f32) -> Option<Option<(String, String)>> {
+        //this returns the result of the dialog when one of its buttons is clicked, which is the username and password for ok, and None for cancel
+        if self.ok_button.is_inside(x, y) {
+            return Some(Some(self.get_credentials()));
+        }
+        if self.cancel_button.is_inside(x, y) {
+            return Some(None);
+        }
+
+        if self.username_field.is_inside(x, y) {
+            self.password_field.has_focus = false;
+            self.username_field.mouse_down(x, y);
+        } else if self.password_field.is_inside(x, y) {
+            self.username_field.has_focus = false;
+            self.password_field.mouse_down(x, y);
+        }
+        return None;
+    }
+
+    pub fn handle_keyboard_input(&mut self, platform: &Platform, input: Option<&String>, key_code: Option<KeyCode>) {
+        if self.username_field.has_focus {
+            self.username_field.handle_keyboard_input(&platform.font_context, input, key_code);
+        } else if self.password_field.has_focus {
+            self.password_field.handle_keyboard_input(&platform.font_context, input, key_code);
+        }
+    }
+
+    pub fn get_credentials(&self) -> (String, String) {
+        return (self.username_field.text.clone(), self.password_field.text.clone());
+    }
+}
+
+
+//the dialogs that are shown in their own window, while the main window waits for them to be closed
+pub enum ModalDialog {
+    Message(Dialog),
+    Credentials(CredentialsDialog),
+}
+pub enum DialogResult {
+    Text(String),
+    Credentials(String, String),
+}
+impl ModalDialog {
+    pub fn height(&self) -> f32 {
+        match self {
+            ModalDialog::Message(dialog) => dialog.height,
+            ModalDialog::Credentials(dialog) => dialog.height,
+        }
+    }
+
+    pub fn has_text_input(&self) -> bool {
+        match self {
+            ModalDialog::Message(dialog) => dialog.text_field.is_some(),
+            ModalDialog::Credentials(_) => true,
+        }
+    }
+
+    pub fn render(&self, animation_tick: u32, platform: &mut Platform) {
+        match self {
+            ModalDialog::Message(dialog) => dialog.render(animation_tick, platform),
+            ModalDialog::Credentials(dialog) => dialog.render(animation_tick, platform),
+        }
+    }
+
+    pub fn click(&mut self, x: f32, y: f32) -> Option<Option<DialogResult>> {
+        match self {
+            ModalDialog::Message(dialog) => dialog.click(x, y).map(|result| result.map(DialogResult::Text)),
+            ModalDialog::Credentials(dialog) => dialog.click(x, y).map(|result| result.map(|(username, password)| DialogResult::Credentials(username, password))),
+        }
+    }
+
+    pub fn handle_keyboard_input(&mut self, platform: &Platform, input: Option<&String>, key_code: Option<KeyCode>) {
+        match self {
+            ModalDialog::Message(dialog) => dialog.handle_keyboard_input(platform, input, key_code),
+            ModalDialog::Credentials(dialog) => dialog.handle_keyboard_input(platform, input, key_code),
+        }
+    }
+
+    pub fn confirm(&self) -> DialogResult {
+        //this is the result when enter is pressed, which is the same as clicking ok
+        match self {
+            ModalDialog::Message(dialog) => DialogResult::Text(dialog.get_text()),
+            ModalDialog::Credentials(dialog) => {
+                let (username, password) = dialog.get_credentials();
+                return DialogResult::Credentials(username, password);
+            },
+        }
+    }
+}
@@ -0,0 +1,251 @@
+#[cfg(test)] mod tests;
+
+use std::cell::RefCell;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::dom::DocumentReadyState;
+use crate::html_lexer;
+use crate::html_parser;
+use crate::layout::{build_full_layout, compute_layout};
+use crate::network::url::Url;
+use crate::page::catch_crash;
+use crate::platform::fonts::FontContext;
+use crate::script::js_events::DomEvent;
+use crate::script::js_interpreter::{JsInterpreter, JsLayoutContext};
+use crate::script::{js_lexer, js_parser};
+use crate::ui::{CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y};
+
+
+//this runs tests from a local checkout of the web platform tests (https://github.com/web-platform-tests/wpt), to see how much of the specs we support.
+//the tests are run with our own (much smaller) version of testharness.js, since the real one uses more javascript than we support.
+const HARNESS: &str = include_str!("testharness.js");
+const DEFAULT_TEST_DIRECTORIES: [&str; 3] = ["html", "dom", "css"];
+const SKIPPED_DIRECTORIES: [&str; 5] = ["resources", "support", "tools", "reference", "common"]; //these contain helpers for the tests, not tests
+const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub enum SubtestStatus {
+    Pass,
+    Fail,
+    Timeout,
+    Running, //the test did not finish yet, when it is still running at the end it timed out
+}
+impl SubtestStatus {
+    pub fn from_report(status: &str) -> SubtestStatus {
+        return match status {
+            "PASS" => SubtestStatus::Pass,
+            "FAIL" => SubtestStatus::Fail,
+            "TIMEOUT" => SubtestStatus::Timeout,
+            _ => SubtestStatus::Running,
+        };
+    }
+}
+
+
+#[derive(Clone)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct SubtestResult {
+    pub name: String,
+    pub status: SubtestStatus,
+    pub message: String,
+}
+
+
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub struct TestFileResult {
+    pub subtests: Vec<SubtestResult>,
+    pub harness_error: Option<String>, //something went wrong outside of the tests, like an uncaught error, so the results can't be trusted
+}
+impl TestFileResult {
+    pub fn nr_of_passes(&self) -> usize {
+        return self.subtests.iter().filter(|subtest| subtest.status == SubtestStatus::Pass).count();
+    }
+}
+
+
+pub fn run_wpt(wpt_directory: &Path, test_directories: &[String]) -> Result<(), String> {
+    if !wpt_directory.join("resources").join("testharness.js").is_file() {
+        return Err(format!("{:?} does not look like a checkout of the web platform tests (resources/testharness.js is missing)", wpt_directory));
+    }
+
+    let mut test_files = Vec::new();
+    if test_directories.is_empty() {
+        for directory in DEFAULT_TEST_DIRECTORIES {
+            collect_test_files(&wpt_directory.join(directory), &mut test_files);
+        }
+    } else {
+        for directory in test_directories {
+            collect_test_files(&wpt_directory.join(directory), &mut test_files);
+        }
+    }
+    test_files.sort();
+
+    //pages that panic are reported as a harness error, we don't want the panic messages in between the results
+    let default_panic_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let font_context = Rc::new(FontContext::new());
+    let (mut nr_of_files, mut nr_of_skipped_files, mut nr_of_passing_files) = (0, 0, 0);
+    let (mut nr_of_subtests, mut nr_of_passing_subtests) = (0, 0);
+
+    for test_file in test_files {
+        let relative_path = test_file.strip_prefix(wpt_directory).unwrap_or(&test_file).to_string_lossy().replace('\\', "/");
+        let content = match fs::read(&test_file) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            Err(_) => { continue; },
+        };
+        if !is_supported_test(&content) {
+            nr_of_skipped_files += 1;
+            continue;
+        }
+
+        let absolute_path = fs::canonicalize(&test_file).unwrap_or(test_file.clone());
+        let url = Url::from(&format!("file://{}", absolute_path.to_string_lossy().replace('\\', "/")));
+        let result = run_test_document(&content, &url, &font_context, TEST_TIMEOUT);
+
+        nr_of_files += 1;
+        nr_of_subtests += result.subtests.len();
+        nr_of_passing_subtests += result.nr_of_passes();
+        print_test_file_result(&relative_path, &result);
+        if result.harness_error.is_none() && result.nr_of_passes() == result.subtests.len() {
+            nr_of_passing_files += 1;
+        }
+    }
+
+    panic::set_hook(default_panic_hook);
+
+    let percentage = if nr_of_subtests == 0 { 0.0 } else { nr_of_passing_subtests as f32 * 100.0 / nr_of_subtests as f32 };
+    println!();
+    println!("{} of {} files passed completely ({} skipped because they need features the runner does not support)",
+             nr_of_passing_files, nr_of_files, nr_of_skipped_files);
+    println!("{} of {} subtests passed ({:.1}%)", nr_of_passing_subtests, nr_of_subtests, percentage);
+    return Ok(());
+}
+
+
+fn collect_test_files(directory: &PathBuf, test_files: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(directory);
+    if entries.is_err() {
+        return;
+    }
+
+    for entry in entries.unwrap().flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        if path.is_dir() {
+            if !SKIPPED_DIRECTORIES.contains(&file_name.as_str()) {
+                collect_test_files(&path, test_files);
+            }
+            continue;
+        }
+
+        //reftests (and the files they compare with) need screenshots, and manual tests need someone to click around
+        let is_html = file_name.ends_with(".html") || file_name.ends_with(".htm");
+        let is_reftest_file = file_name.contains("-ref.") || file_name.contains("-notref.") || file_name.contains("-manual.");
+        if is_html && !is_reftest_file {
+            test_files.push(path);
+        }
+    }
+}
+
+
+pub fn is_supported_test(content: &String) -> bool {
+    //only testharness.js tests report results we can count. We don't load external scripts yet, so we run our own harness instead of the
+    //linked one, but tests that need other external scripts (like helpers from /common) would fail for the wrong reason.
+    if !content.contains("/resources/testharness.js") {
+        return false;
+    }
+
+    let mut remaining_content = content.as_str();
+    while let Some(script_start) = remaining_content.find("<script") {
+        let tag_end = remaining_content[script_start..].find('>').map(|idx| script_start + idx).unwrap_or(remaining_content.len());
+        let tag = &remaining_content[script_start..tag_end];
+        if tag.contains("src=") && !tag.contains("/resources/testharness.js") && !tag.contains("/resources/testharnessreport.js") {
+            return false;
+        }
+        remaining_content = &remaining_content[tag_end..];
+    }
+    return true;
+}
+
+
+pub fn run_test_document(content: &String, url: &Url, font_context: &Rc<FontContext>, timeout: Duration) -> TestFileResult {
+    let run_result = catch_crash(|| {
+        let document = Rc::from(RefCell::from(html_parser::parse(html_lexer::lex_html(content), url)));
+        let document_node = document.borrow().document_node;
+        document.borrow_mut().post_construct(document_node, font_context);
+
+        let full_layout = Rc::new(RefCell::from(build_full_layout(&mut document.borrow_mut(), font_context)));
+        compute_layout(&mut full_layout.borrow_mut(), &mut document.borrow_mut(), CONTENT_TOP_LEFT_X, CONTENT_TOP_LEFT_Y, font_context, 0.0, false, true);
+
+        let mut interpreter = JsInterpreter::new();
+        interpreter.add_wpt_reporter();
+        interpreter.layout_context = Some(JsLayoutContext { full_layout, font_context: font_context.clone(), current_scroll_y: 0.0 });
+        interpreter.queue_scripts_in_document(&document);
+
+        //the harness runs before the scripts of the page, as if it was loaded by the first script tag
+        interpreter.run_script(&js_parser::parse_js(&js_lexer::lex_js(HARNESS, 1, 1)));
+        interpreter.run_queued_scripts(Duration::MAX);
+
+        document.borrow_mut().ready_state = DocumentReadyState::Interactive;
+        interpreter.dispatch_event(document_node, DomEvent::new("DOMContentLoaded", true, false));
+        document.borrow_mut().post_construct_new_nodes(font_context);
+        document.borrow_mut().ready_state = DocumentReadyState::Complete;
+        interpreter.dispatch_window_event(DomEvent::new("load", false, false));
+
+        //TODO: a timer that is due after the timeout still makes us wait until it is due
+        let timeout_instant = Instant::now() + timeout;
+        while interpreter.wpt_results.iter().any(|subtest| subtest.status == SubtestStatus::Running) && Instant::now() < timeout_instant {
+            if !interpreter.wait_for_next_timer() {
+                break; //without timers, nothing can finish the tests that are still running
+            }
+        }
+
+        return (interpreter.wpt_results.clone(), interpreter.nr_of_errors);
+    });
+
+    if run_result.is_err() {
+        return TestFileResult { subtests: Vec::new(), harness_error: Some(format!("the page crashed: {}", run_result.unwrap_err())) };
+    }
+    let (mut subtests, nr_of_errors) = run_result.unwrap();
+
+    for subtest in subtests.iter_mut() {
+        if subtest.status == SubtestStatus::Running {
+            subtest.status = SubtestStatus::Timeout;
+        }
+    }
+
+    let harness_error = if nr_of_errors > 0 {
+        Some(format!("{} error(s) while running the scripts of the page", nr_of_errors))
+    } else if subtests.is_empty() {
+        Some(String::from("no tests were run"))
+    } else {
+        None
+    };
+    return TestFileResult { subtests, harness_error };
+}
+
+
+fn print_test_file_result(relative_path: &str, result: &TestFileResult) {
+    let passed = result.harness_error.is_none() && result.nr_of_passes() == result.subtests.len();
+    println!("{} {}/{} {}", if passed { "PASS" } else { "FAIL" }, result.nr_of_passes(), result.subtests.len(), relative_path);
+
+    if result.harness_error.is_some() {
+        println!("    ERROR {}", result.harness_error.as_ref().unwrap());
+    }
+    for subtest in result.subtests.iter().filter(|subtest| subtest.status != SubtestStatus::Pass) {
+        let status = if subtest.status == SubtestStatus::Timeout { "TIMEOUT" } else { "FAIL" };
+        if subtest.message.is_empty() {
+            println!("    {} {}", status, subtest.name);
+        } else {
+            println!("    {} {}: {}", status, subtest.name, subtest.message);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//this replaces resources/testharness.js of the web platform tests, which uses more javascript than we support yet
+//it has the same functions for writing tests (the ones most tests use), and reports to the runner with __wpt_report(id, name, status, message)
+//TODO: promise_test runs all promise tests at the same time, testharness.js runs them one after the other
+
+var __wpt_nr_of_tests = 0;
+
+
+function AssertionError(message) {
+    this.name = "AssertionError";
+    this.message = message;
+}
+
+
+function __wpt_format_value(value) {
+    if (typeof value === "string") {
+        return "\"" + value + "\"";
+    }
+    return "" + value;
+}
+
+
+function __wpt_describe_error(error) {
+    if (error !== null && typeof error === "object" && error.message !== undefined) {
+        return (error.name === undefined ? "Error" : error.name) + ": " + error.message;
+    }
+    return "" + error;
+}
+
+
+function __wpt_assert(condition, assert_name, description, message) {
+    if (!condition) {
+        throw new AssertionError(assert_name + ": " + (description === undefined ? "" : description + " ") + message);
+    }
+}
+
+
+function __wpt_same_value(one, two) {
+    if (one !== one && two !== two) {
+        return true; //NaN is the same as itself here
+    }
+    if (one === 0 && two === 0) {
+        return 1 / one === 1 / two; //but 0 and -0 are not
+    }
+    return one === two;
+}
+
+
+function assert_true(actual, description) {
+    __wpt_assert(actual === true, "assert_true", description, "expected true got " + __wpt_format_value(actual));
+}
+
+
+function assert_false(actual, description) {
+    __wpt_assert(actual === false, "assert_false", description, "expected false got " + __wpt_format_value(actual));
+}
+
+
+function assert_equals(actual, expected, description) {
+    __wpt_assert(__wpt_same_value(actual, expected), "assert_equals", description,
+                 "expected " + __wpt_format_value(expected) + " but got " + __wpt_format_value(actual));
+}
+
+
+function assert_not_equals(actual, expected, description) {
+    __wpt_assert(!__wpt_same_value(actual, expected), "assert_not_equals", description, "got disallowed value " + __wpt_format_value(actual));
+}
+
+
+function assert_in_array(actual, expected, description) {
+    var found = false;
+    for (var idx = 0; idx < expected.length; idx++) {
+        if (__wpt_same_value(actual, expected[idx])) {
+            found = true;
+        }
+    }
+    __wpt_assert(found, "assert_in_array", description, "value " + __wpt_format_value(actual) + " not in array");
+}
+
+
+function assert_array_equals(actual, expected, description) {
+    __wpt_assert(actual !== null && typeof actual === "object", "assert_array_equals", description, "value is not an array");
+    __wpt_assert(actual.length === expected.length, "assert_array_equals", description,
+                 "lengths differ, expected " + expected.length + " got " + actual.length);
+    for (var idx = 0; idx < expected.length; idx++) {
+        __wpt_assert(__wpt_same_value(actual[idx], expected[idx]), "assert_array_equals", description,
+                     "expected " + __wpt_format_value(expected[idx]) + " but got " + __wpt_format_value(actual[idx]) + " at index " + idx);
+    }
+}
+
+
+function assert_approx_equals(actual, expected, epsilon, description) {
+    __wpt_assert(typeof actual === "number", "assert_approx_equals", description, "expected a number but got " + __wpt_format_value(actual));
+    __wpt_assert(Math.abs(actual - expected) <= epsilon, "assert_approx_equals", description,
+                 "expected " + expected + " +/- " + epsilon + " but got " + actual);
+}
+
+
+function assert_less_than(actual, expected, description) {
+    __wpt_assert(actual < expected, "assert_less_than", description, "expected a number less than " + expected + " but got " + __wpt_format_value(actual));
+}
+
+
+function assert_greater_than(actual, expected, description) {
+    __wpt_assert(actual > expected, "assert_greater_than", description, "expected a number greater than " + expected + " but got " + __wpt_format_value(actual));
+}
+
+
+function assert_less_than_equal(actual, expected, description) {
+    __wpt_assert(actual <= expected, "assert_less_than_equal", description, "expected a number at most " + expected + " but got " + __wpt_format_value(actual));
+}
+
+
+function assert_greater_than_equal(actual, expected, description) {
+    __wpt_assert(actual >= expected, "assert_greater_than_equal", description, "expected a number at least " + expected + " but got " + __wpt_format_value(actual));
+}
+
+
+function assert_own_property(object, property_name, description) {
+    __wpt_assert(property_name in object, "assert_own_property", description, "expected property " + __wpt_format_value(property_name) + " missing");
+}
+
+
+function assert_unreached(description) {
+    __wpt_assert(false, "assert_unreached", description, "reached unreachable code");
+}
+
+
+function assert_throws_js(constructor, func, description) {
+    try {
+        func();
+    } catch (error) {
+        __wpt_assert(error instanceof constructor || (error !== null && typeof error === "object" && error.name === constructor.name),
+                     "assert_throws_js", description, "threw " + __wpt_describe_error(error) + " of the wrong type");
+        return;
+    }
+    __wpt_assert(false, "assert_throws_js", description, "the function did not throw");
+}
+
+
+function assert_throws_dom(type, func, description) {
+    try {
+        func();
+    } catch (error) {
+        __wpt_assert(error !== null && typeof error === "object" && error.name === type, "assert_throws_dom", description,
+                     "threw " + __wpt_describe_error(error) + " instead of a " + type);
+        return;
+    }
+    __wpt_assert(false, "assert_throws_dom", description, "the function did not throw");
+}
+
+
+function __wpt_make_test(name) {
+    var id = __wpt_nr_of_tests;
+    __wpt_nr_of_tests++;
+    if (name === undefined) {
+        name = "test " + (id + 1);
+    }
+    __wpt_report(id, name, "RUNNING", "");
+
+    var cleanups = [];
+    var test = { name: name, finished: false };
+
+    var finish = function (status, message) {
+        if (test.finished) {
+            return;
+        }
+        test.finished = true;
+        for (var idx = 0; idx < cleanups.length; idx++) {
+            cleanups[idx]();
+        }
+        __wpt_report(id, name, status, message);
+    };
+
+    test.step = function (func, this_object, argument) {
+        if (test.finished) {
+            return undefined;
+        }
+        try {
+            return func.call(this_object === undefined ? test : this_object, argument);
+        } catch (error) {
+            finish("FAIL", __wpt_describe_error(error));
+        }
+        return undefined;
+    };
+    test.step_func = function (func, this_object) {
+        return function (first_argument) {
+            return test.step(func, this_object, first_argument);
+        };
+    };
+    test.step_func_done = function (func, this_object) {
+        return function (first_argument) {
+            if (func !== undefined) {
+                test.step(func, this_object, first_argument);
+            }
+            test.done();
+        };
+    };
+    test.unreached_func = function (description) {
+        return test.step_func(function () {
+            assert_unreached(description);
+        });
+    };
+    test.step_timeout = function (func, timeout) {
+        return setTimeout(test.step_func(func), timeout);
+    };
+    test.add_cleanup = function (func) {
+        cleanups[cleanups.length] = func;
+    };
+    test.done = function () {
+        finish("PASS", "");
+    };
+    test.fail = function (error) {
+        finish("FAIL", __wpt_describe_error(error));
+    };
+    return test;
+}
+
+
+function test(func, name) {
+    var test = __wpt_make_test(name);
+    test.step(func, test, test);
+    test.done();
+}
+
+
+function async_test(func, name) {
+    if (typeof func !== "function") {
+        name = func;
+        func = undefined;
+    }
+    var test = __wpt_make_test(name);
+    if (func !== undefined) {
+        test.step(func, test, test);
+    }
+    return test;
+}
+
+
+function promise_test(func, name) {
+    var test = __wpt_make_test(name);
+    var promise = test.step(func, test, test);
+    if (test.finished) {
+        return;
+    }
+    if (promise === undefined || promise === null || typeof promise.then !== "function") {
+        test.fail("promise_test: the test function did not return a promise");
+        return;
+    }
+    promise.then(function () {
+        test.done();
+    }, function (error) {
+        test.fail(error);
+    });
+}
+
+
+function step_timeout(func, timeout) {
+    return setTimeout(func, timeout);
+}
+
+
+function setup(func_or_properties) {
+    if (typeof func_or_properties === "function") {
+        func_or_properties();
+    }
+}
+
+
+function done() {}
+function add_completion_callback(callback) {}
+function add_result_callback(callback) {}
@@ -0,0 +1,62 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::network::url::Url;
+use crate::platform::fonts::FontContext;
+use crate::wpt::{is_supported_test, run_test_document, SubtestStatus};
+
+
+#[test]
+fn test_run_test_document() {
+    let html = r##"<html><head><script src="/resources/testharness.js"></script></head><body>
+        <div id="target">some text</div>
+        <script>
+            test(function() {
+                assert_equals(document.getElementById("target").textContent, "some text");
+                assert_array_equals([1, 2], [1, 2]);
+                function CustomError() {}
+                assert_throws_js(CustomError, function() { throw new CustomError(); });
+            }, "passing test");
+
+            test(function() {
+                assert_equals(1 + 1, 3, "wrong sum");
+            }, "failing test");
+
+            async_test(function(t) {
+                setTimeout(t.step_func_done(function() {
+                    assert_true(true);
+                }), 10);
+            }, "async test");
+
+            promise_test(function() {
+                return Promise.resolve(1).then(function(value) { assert_equals(value, 1); });
+            }, "promise test");
+
+            async_test("unfinished test");
+        </script>
+    </body></html>"##;
+
+    let url = Url::from(&String::from("file:///wpt/dom/test.html"));
+    let result = run_test_document(&String::from(html), &url, &Rc::new(FontContext::new()), Duration::from_secs(5));
+
+    assert!(result.harness_error.is_none());
+    let statuses: Vec<(&str, SubtestStatus)> = result.subtests.iter().map(|subtest| (subtest.name.as_str(), subtest.status)).collect();
+    assert_eq!(statuses, vec![
+        ("passing test", SubtestStatus::Pass),
+        ("failing test", SubtestStatus::Fail),
+        ("async test", SubtestStatus::Pass),
+        ("promise test", SubtestStatus::Pass),
+        ("unfinished test", SubtestStatus::Timeout),
+    ]);
+    assert_eq!(result.subtests[1].message, "AssertionError: assert_equals: wrong sum expected 3 but got 2");
+    assert_eq!(result.nr_of_passes(), 3);
+}
+
+
+#[test]
+fn test_is_supported_test() {
+    assert!(is_supported_test(&String::from("<script src=\"/resources/testharness.js\"></script>\
+                                             <script src=\"/resources/testharnessreport.js\"></script><script>test(function() {});</script>")));
+    assert!(!is_supported_test(&String::from("<link rel=\"match\" href=\"test-ref.html\"><p>reftest</p>")));
+    assert!(!is_supported_test(&String::from("<script src=\"/resources/testharness.js\"></script><script src=\"/common/utils.js\"></script>")));
+}